@@ -1,33 +1,70 @@
 use crate::{
+  can_see_deleted_persons,
+  can_view_removed,
   check_community_ban,
+  check_community_downvotes_enabled,
   check_downvotes_enabled,
   collect_moderated_communities,
+  get_listing_defaults,
   get_local_user_view_from_jwt,
   get_local_user_view_from_jwt_opt,
+  get_local_user_view_from_jwt_read_only,
   get_post,
+  is_exempt_from_slur_filter,
   is_mod_or_admin,
+  send_vote_debounced,
   Perform,
 };
 use actix_web::web::Data;
-use lemmy_api_structs::{blocking, comment::*, send_local_notifs};
-use lemmy_apub::{generate_apub_endpoint, ApubLikeableType, ApubObjectType, EndpointType};
+use lemmy_api_structs::{
+  blocking,
+  blocking_transaction,
+  check_spam_rules,
+  comment::*,
+  get_content_limits,
+  hide_downvotes_if_disabled,
+  log_spam_removal,
+  send_local_notifs,
+  FederationStatus,
+  VoteTarget,
+};
+use lemmy_apub::{
+  generate_apub_endpoint,
+  send_create_comment_without_mentions,
+  ApubLikeableType,
+  ApubObjectType,
+  EndpointType,
+};
 use lemmy_db_queries::{
-  source::comment::Comment_,
+  source::{
+    comment::Comment_,
+    language::{Language_, UNDETERMINED_LANGUAGE_ID},
+    person_mention::PersonMention_,
+    spam_rule::SpamCheckContent,
+  },
   Crud,
   Likeable,
-  ListingType,
   Reportable,
   Saveable,
-  SortType,
+  SpamAction,
+};
+use lemmy_db_schema::source::{
+  comment::*,
+  comment_report::*,
+  language::Language,
+  moderator::*,
+  person_mention::*,
 };
-use lemmy_db_schema::source::{comment::*, comment_report::*, moderator::*};
 use lemmy_db_views::{
   comment_report_view::{CommentReportQueryBuilder, CommentReportView},
   comment_view::{CommentQueryBuilder, CommentView},
+  local_user_view::LocalUserView,
 };
+use lemmy_db_views_actor::person_mention_view::PersonMentionView;
 use lemmy_utils::{
-  utils::{remove_slurs, scrape_text_for_mentions},
+  utils::{remove_slurs, scrape_text_for_mentions, scrape_text_for_new_mentions},
   ApiError,
+  ApiErrorCode,
   ConnectionId,
   LemmyError,
 };
@@ -36,7 +73,6 @@ use lemmy_websocket::{
   LemmyContext,
   UserOperation,
 };
-use std::str::FromStr;
 
 #[async_trait::async_trait(?Send)]
 impl Perform for CreateComment {
@@ -50,12 +86,17 @@ impl Perform for CreateComment {
     let data: &CreateComment = &self;
     let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
 
-    let content_slurs_removed = remove_slurs(&data.content.to_owned());
-
     // Check for a community ban
     let post_id = data.post_id;
     let post = get_post(post_id, context.pool()).await?;
 
+    let exempt = is_exempt_from_slur_filter(post.community_id, context.pool()).await?;
+    let slur_regex = context.slur_filter().regex_for_community(exempt);
+    let content_slurs_removed = remove_slurs(&data.content.to_owned(), &slur_regex);
+    get_content_limits(context.pool())
+      .await?
+      .check_comment(&data.content)?;
+
     check_community_ban(local_user_view.person.id, post.community_id, context.pool()).await?;
 
     // Check if post is locked, no new comments
@@ -63,6 +104,11 @@ impl Perform for CreateComment {
       return Err(ApiError::err("locked").into());
     }
 
+    // Comments couldn't be federated before the post is approved
+    if post.is_awaiting_approval() {
+      return Err(ApiError::err("post_awaiting_approval").into());
+    }
+
     // If there's a parent_id, check to make sure that comment is in that post
     if let Some(parent_id) = data.parent_id {
       // Make sure the parent comment exists
@@ -74,6 +120,46 @@ impl Perform for CreateComment {
       if parent.post_id != post_id {
         return Err(ApiError::err("couldnt_create_comment").into());
       }
+
+      // No new replies anywhere below a locked comment
+      let in_locked_thread = blocking(context.pool(), move |conn| {
+        Comment::in_locked_thread(conn, parent_id)
+      })
+      .await??;
+      if in_locked_thread {
+        return Err(ApiError::err("comment_locked").into());
+      }
+    }
+
+    // The language has to exist, and be allowed in the community
+    let language_id = data.language_id.unwrap_or(UNDETERMINED_LANGUAGE_ID);
+    let community_id = post.community_id;
+    let language_allowed = blocking(context.pool(), move |conn| -> Result<bool, LemmyError> {
+      Ok(
+        Language::read_from_id(conn, language_id).is_ok()
+          && Language::is_allowed_in_community(conn, community_id, language_id)?,
+      )
+    })
+    .await??;
+    if !language_allowed {
+      return Err(
+        ApiError::new(ApiErrorCode::LanguageNotAllowed)
+          .with_field("language_id")
+          .into(),
+      );
+    }
+
+    // Spam which isn't rejected is created, but removed right away and not federated. The content
+    // is compared as it is stored, so that duplicates are found.
+    let spam_content = SpamCheckContent::Comment {
+      id: None,
+      content: content_slurs_removed.to_owned(),
+    };
+    let spam_rule = check_spam_rules(context.pool(), &local_user_view.person, spam_content).await?;
+    if let Some(rule) = &spam_rule {
+      if SpamAction::from_i16(rule.action) == SpamAction::Reject {
+        return Err(ApiError::err("rejected_as_spam").into());
+      }
     }
 
     let comment_form = CommentForm {
@@ -81,7 +167,7 @@ impl Perform for CreateComment {
       parent_id: data.parent_id.to_owned(),
       post_id: data.post_id,
       creator_id: local_user_view.person.id,
-      removed: None,
+      removed: spam_rule.as_ref().map(|_| true),
       deleted: None,
       read: None,
       published: None,
@@ -90,24 +176,34 @@ impl Perform for CreateComment {
       local: true,
     };
 
-    // Create the comment
+    // Create the comment, spam is logged together with it
     let comment_form2 = comment_form.clone();
-    let inserted_comment = match blocking(context.pool(), move |conn| {
-      Comment::create(&conn, &comment_form2)
+    let spam_rule2 = spam_rule.clone();
+    let inserted_comment = match blocking_transaction(context.pool(), move |conn| {
+      let comment = Comment::create(&conn, &comment_form2)?;
+      if let Some(rule) = &spam_rule2 {
+        log_spam_removal(conn, rule, comment.post_id, Some(comment.id))?;
+      }
+      Ok(comment)
     })
-    .await?
+    .await
     {
       Ok(comment) => comment,
       Err(_e) => return Err(ApiError::err("couldnt_create_comment").into()),
     };
 
-    // Necessary to update the ap_id
+    // Necessary to update the ap_id, the language is set together with it
     let inserted_comment_id = inserted_comment.id;
     let updated_comment: Comment =
       match blocking(context.pool(), move |conn| -> Result<Comment, LemmyError> {
         let apub_id =
           generate_apub_endpoint(EndpointType::Comment, &inserted_comment_id.to_string())?;
-        Ok(Comment::update_ap_id(&conn, inserted_comment_id, apub_id)?)
+        Comment::update_ap_id(&conn, inserted_comment_id, apub_id)?;
+        Ok(Comment::update_language(
+          &conn,
+          inserted_comment_id,
+          language_id,
+        )?)
       })
       .await?
       {
@@ -115,22 +211,41 @@ impl Perform for CreateComment {
         Err(_e) => return Err(ApiError::err("couldnt_create_comment").into()),
       };
 
-    updated_comment
-      .send_create(&local_user_view.person, context)
-      .await?;
+    let removed_as_spam = spam_rule.is_some();
+
+    let disable_mentions = data.disable_mentions.unwrap_or(false);
+    let queued = if removed_as_spam {
+      0
+    } else if disable_mentions {
+      send_create_comment_without_mentions(&updated_comment, &local_user_view.person, context)
+        .await?
+    } else {
+      updated_comment
+        .send_create(&local_user_view.person, context)
+        .await?
+    };
 
     // Scan the comment for user mentions, add those rows
     let post_id = post.id;
-    let mentions = scrape_text_for_mentions(&comment_form.content);
-    let recipient_ids = send_local_notifs(
-      mentions,
-      updated_comment.clone(),
-      local_user_view.person.clone(),
-      post,
-      context.pool(),
-      true,
-    )
-    .await?;
+    let community_id = post.community_id;
+    let mentions = if disable_mentions || removed_as_spam {
+      Vec::new()
+    } else {
+      scrape_text_for_mentions(&comment_form.content)
+    };
+    let recipient_ids = if removed_as_spam {
+      Vec::new()
+    } else {
+      send_local_notifs(
+        mentions,
+        updated_comment.clone(),
+        local_user_view.person.clone(),
+        post,
+        context.pool(),
+        true,
+      )
+      .await?
+    };
 
     // You like your own comment by default
     let like_form = CommentLikeForm {
@@ -145,9 +260,11 @@ impl Perform for CreateComment {
       return Err(ApiError::err("couldnt_like_comment").into());
     }
 
-    updated_comment
-      .send_like(&local_user_view.person, context)
-      .await?;
+    if !removed_as_spam {
+      updated_comment
+        .send_like(&local_user_view.person, context)
+        .await?;
+    }
 
     let person_id = local_user_view.person.id;
     let mut comment_view = blocking(context.pool(), move |conn| {
@@ -169,11 +286,34 @@ impl Perform for CreateComment {
       comment_view.comment.read = true;
     }
 
+    let mentions = blocking(context.pool(), move |conn| {
+      PersonMention::list_for_comment(conn, comment_id)?
+        .iter()
+        .map(|mention| PersonMentionView::read(conn, mention.id, Some(person_id)))
+        .collect::<Result<Vec<PersonMentionView>, _>>()
+    })
+    .await??;
+
+    let federation = FederationStatus::new(queued, comment_view.community.local);
     let mut res = CommentResponse {
       comment_view,
       recipient_ids,
       form_id: data.form_id.to_owned(),
+      mentions,
+      federation: Some(federation),
     };
+    hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
+
+    if removed_as_spam {
+      // Only the mods are told about it, so that they can restore it
+      context.chat_server().do_send(SendModRoomMessage {
+        op: UserOperation::CreateComment,
+        response: res.clone(),
+        community_id,
+        websocket_id,
+      });
+      return Ok(res);
+    }
 
     context.chat_server().do_send(SendComment {
       op: UserOperation::CreateComment,
@@ -218,7 +358,13 @@ impl Perform for EditComment {
     }
 
     // Do the update
-    let content_slurs_removed = remove_slurs(&data.content.to_owned());
+    let slur_regex = context
+      .slur_filter()
+      .regex_for_community(orig_comment.community.exempt_from_slur_filter);
+    let content_slurs_removed = remove_slurs(&data.content.to_owned(), &slur_regex);
+    get_content_limits(context.pool())
+      .await?
+      .check_comment(&data.content)?;
     let comment_id = data.comment_id;
     let updated_comment = match blocking(context.pool(), move |conn| {
       Comment::update_content(conn, comment_id, &content_slurs_removed)
@@ -235,8 +381,9 @@ impl Perform for EditComment {
       .await?;
 
     // Do the mentions / recipients
-    let updated_comment_content = updated_comment.content.to_owned();
-    let mentions = scrape_text_for_mentions(&updated_comment_content);
+    // Only the persons which weren't mentioned before get notified
+    let mentions =
+      scrape_text_for_new_mentions(&orig_comment.comment.content, &updated_comment.content);
     let recipient_ids = send_local_notifs(
       mentions,
       updated_comment,
@@ -254,11 +401,14 @@ impl Perform for EditComment {
     })
     .await??;
 
-    let res = CommentResponse {
+    let mut res = CommentResponse {
       comment_view,
       recipient_ids,
       form_id: data.form_id.to_owned(),
+      mentions: Vec::new(),
+      federation: None,
     };
+    hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
     context.chat_server().do_send(SendComment {
       op: UserOperation::EditComment,
@@ -343,11 +493,14 @@ impl Perform for DeleteComment {
     )
     .await?;
 
-    let res = CommentResponse {
+    let mut res = CommentResponse {
       comment_view,
       recipient_ids,
       form_id: None, // TODO a comment delete might clear forms?
+      mentions: Vec::new(),
+      federation: None,
     };
+    hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
     context.chat_server().do_send(SendComment {
       op: UserOperation::DeleteComment,
@@ -448,11 +601,14 @@ impl Perform for RemoveComment {
     )
     .await?;
 
-    let res = CommentResponse {
+    let mut res = CommentResponse {
       comment_view,
       recipient_ids,
       form_id: None, // TODO maybe this might clear other forms
+      mentions: Vec::new(),
+      federation: None,
     };
+    hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
     context.chat_server().do_send(SendComment {
       op: UserOperation::RemoveComment,
@@ -464,6 +620,178 @@ impl Perform for RemoveComment {
   }
 }
 
+#[async_trait::async_trait(?Send)]
+impl Perform for LockComment {
+  type Response = CommentResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    websocket_id: Option<ConnectionId>,
+  ) -> Result<CommentResponse, LemmyError> {
+    let data: &LockComment = &self;
+    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+
+    let comment_id = data.comment_id;
+    let orig_comment = blocking(context.pool(), move |conn| {
+      CommentView::read(&conn, comment_id, None)
+    })
+    .await??;
+
+    check_community_ban(
+      local_user_view.person.id,
+      orig_comment.community.id,
+      context.pool(),
+    )
+    .await?;
+
+    // Verify that only a mod or admin can lock
+    is_mod_or_admin(
+      context.pool(),
+      local_user_view.person.id,
+      orig_comment.community.id,
+    )
+    .await?;
+
+    // Do the lock
+    let locked = data.locked;
+    if blocking(context.pool(), move |conn| {
+      Comment::update_locked(conn, comment_id, locked)
+    })
+    .await?
+    .is_err()
+    {
+      return Err(ApiError::err("couldnt_update_comment").into());
+    }
+
+    // Mod tables
+    let form = ModLockCommentForm {
+      mod_person_id: local_user_view.person.id,
+      comment_id: data.comment_id,
+      locked: Some(locked),
+      reason: data.reason.to_owned(),
+    };
+    blocking(context.pool(), move |conn| {
+      ModLockComment::create(conn, &form)
+    })
+    .await??;
+
+    // Refetch it
+    let person_id = local_user_view.person.id;
+    let comment_view = blocking(context.pool(), move |conn| {
+      CommentView::read(conn, comment_id, Some(person_id))
+    })
+    .await??;
+
+    let mut res = CommentResponse {
+      comment_view,
+      recipient_ids: Vec::new(),
+      form_id: None,
+      mentions: Vec::new(),
+      federation: None,
+    };
+    hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
+
+    context.chat_server().do_send(SendComment {
+      op: UserOperation::LockComment,
+      comment: res.clone(),
+      websocket_id,
+    });
+
+    Ok(res)
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for StickyComment {
+  type Response = CommentResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    websocket_id: Option<ConnectionId>,
+  ) -> Result<CommentResponse, LemmyError> {
+    let data: &StickyComment = &self;
+    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+
+    let comment_id = data.comment_id;
+    let orig_comment = blocking(context.pool(), move |conn| {
+      CommentView::read(&conn, comment_id, None)
+    })
+    .await??;
+
+    check_community_ban(
+      local_user_view.person.id,
+      orig_comment.community.id,
+      context.pool(),
+    )
+    .await?;
+
+    // Verify that only a mod or admin can sticky
+    is_mod_or_admin(
+      context.pool(),
+      local_user_view.person.id,
+      orig_comment.community.id,
+    )
+    .await?;
+
+    // Only top-level comments are sorted first
+    if orig_comment.comment.parent_id.is_some() {
+      return Err(ApiError::err("comment_not_top_level").into());
+    }
+
+    // Do the sticky, which unstickies the previously stickied comment of the post
+    let stickied = data.stickied;
+    let updated_comment = match blocking(context.pool(), move |conn| {
+      Comment::update_stickied(conn, comment_id, stickied)
+    })
+    .await?
+    {
+      Ok(comment) => comment,
+      Err(_e) => return Err(ApiError::err("couldnt_update_comment").into()),
+    };
+
+    // Mod tables
+    let form = ModStickyCommentForm {
+      mod_person_id: local_user_view.person.id,
+      comment_id: data.comment_id,
+      stickied: Some(stickied),
+    };
+    blocking(context.pool(), move |conn| {
+      ModStickyComment::create(conn, &form)
+    })
+    .await??;
+
+    // Apub updates
+    updated_comment
+      .send_update(&local_user_view.person, context)
+      .await?;
+
+    // Refetch it
+    let person_id = local_user_view.person.id;
+    let comment_view = blocking(context.pool(), move |conn| {
+      CommentView::read(conn, comment_id, Some(person_id))
+    })
+    .await??;
+
+    let mut res = CommentResponse {
+      comment_view,
+      recipient_ids: Vec::new(),
+      form_id: None,
+      mentions: Vec::new(),
+    };
+    hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
+
+    context.chat_server().do_send(SendComment {
+      op: UserOperation::StickyComment,
+      comment: res.clone(),
+      websocket_id,
+    });
+
+    Ok(res)
+  }
+}
+
 #[async_trait::async_trait(?Send)]
 impl Perform for MarkCommentAsRead {
   type Response = CommentResponse;
@@ -513,11 +841,14 @@ impl Perform for MarkCommentAsRead {
     })
     .await??;
 
-    let res = CommentResponse {
+    let mut res = CommentResponse {
       comment_view,
       recipient_ids: Vec::new(),
       form_id: None,
+      mentions: Vec::new(),
+      federation: None,
     };
+    hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
     Ok(res)
   }
@@ -554,15 +885,18 @@ impl Perform for SaveComment {
 
     let comment_id = data.comment_id;
     let person_id = local_user_view.person.id;
-    let comment_view = blocking(context.pool(), move |conn| {
+    let mut comment_view = blocking(context.pool(), move |conn| {
       CommentView::read(conn, comment_id, Some(person_id))
     })
     .await??;
+    hide_downvotes_if_disabled(std::slice::from_mut(&mut comment_view), context.pool()).await?;
 
     Ok(CommentResponse {
       comment_view,
       recipient_ids: Vec::new(),
       form_id: None,
+      mentions: Vec::new(),
+      federation: None,
     })
   }
 }
@@ -596,9 +930,18 @@ impl Perform for CreateCommentLike {
       context.pool(),
     )
     .await?;
+    check_community_downvotes_enabled(data.score, orig_comment.community.id, context.pool())
+      .await?;
 
     // Add parent user to recipients
-    recipient_ids.push(orig_comment.get_recipient_id());
+    let recipient_id = orig_comment.get_recipient_id();
+    if let Ok(local_recipient) = blocking(context.pool(), move |conn| {
+      LocalUserView::read_person(conn, recipient_id)
+    })
+    .await?
+    {
+      recipient_ids.push(local_recipient.local_user.id);
+    }
 
     let like_form = CommentLikeForm {
       comment_id: data.comment_id,
@@ -609,8 +952,10 @@ impl Perform for CreateCommentLike {
 
     // Remove any likes first
     let person_id = local_user_view.person.id;
-    blocking(context.pool(), move |conn| {
-      CommentLike::remove(conn, person_id, comment_id)
+    let previous_score = blocking(context.pool(), move |conn| {
+      let previous_score = CommentLike::read_score(conn, person_id, comment_id)?;
+      CommentLike::remove(conn, person_id, comment_id)?;
+      Ok(previous_score) as Result<_, LemmyError>
     })
     .await??;
 
@@ -623,20 +968,19 @@ impl Perform for CreateCommentLike {
       if blocking(context.pool(), like).await?.is_err() {
         return Err(ApiError::err("couldnt_like_comment").into());
       }
-
-      if like_form.score == 1 {
-        comment.send_like(&local_user_view.person, context).await?;
-      } else if like_form.score == -1 {
-        comment
-          .send_dislike(&local_user_view.person, context)
-          .await?;
-      }
-    } else {
-      comment
-        .send_undo_like(&local_user_view.person, context)
-        .await?;
     }
 
+    let score = if do_add { like_form.score } else { 0 };
+    send_vote_debounced(
+      &comment,
+      VoteTarget::Comment(comment.id),
+      &local_user_view.person,
+      previous_score,
+      score,
+      context,
+    )
+    .await?;
+
     // Have to refetch the comment to get the current state
     let comment_id = data.comment_id;
     let person_id = local_user_view.person.id;
@@ -645,11 +989,14 @@ impl Perform for CreateCommentLike {
     })
     .await??;
 
-    let res = CommentResponse {
+    let mut res = CommentResponse {
       comment_view: liked_comment,
       recipient_ids,
       form_id: None,
+      mentions: Vec::new(),
+      federation: None,
     };
+    hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
     context.chat_server().do_send(SendComment {
       op: UserOperation::CreateCommentLike,
@@ -661,6 +1008,51 @@ impl Perform for CreateCommentLike {
   }
 }
 
+#[async_trait::async_trait(?Send)]
+impl Perform for GetComment {
+  type Response = GetCommentResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<GetCommentResponse, LemmyError> {
+    let data: &GetComment = &self;
+    let local_user_view = get_local_user_view_from_jwt_opt(&data.auth, context.pool()).await?;
+    let person_id = local_user_view.map(|u| u.person.id);
+
+    let id = data.id;
+    let mut comment_view = match blocking(context.pool(), move |conn| {
+      CommentView::read(conn, id, person_id)
+    })
+    .await?
+    {
+      Ok(comment) => comment,
+      Err(_e) => return Err(ApiError::err("couldnt_find_comment").into()),
+    };
+
+    let can_view_removed =
+      can_view_removed(person_id, comment_view.community.id, context.pool()).await?;
+
+    // Removed and deleted comments stay visible to their creator and the mods, eg for links from
+    // the modlog
+    let is_creator = person_id == Some(comment_view.comment.creator_id);
+    if (comment_view.comment.removed || comment_view.comment.deleted)
+      && !is_creator
+      && !can_view_removed
+    {
+      return Err(ApiError::err("couldnt_find_comment").into());
+    }
+
+    hide_downvotes_if_disabled(std::slice::from_mut(&mut comment_view), context.pool()).await?;
+
+    Ok(GetCommentResponse {
+      comment_view,
+      can_view_removed,
+    })
+  }
+}
+
 #[async_trait::async_trait(?Send)]
 impl Perform for GetComments {
   type Response = GetCommentsResponse;
@@ -672,31 +1064,46 @@ impl Perform for GetComments {
   ) -> Result<GetCommentsResponse, LemmyError> {
     let data: &GetComments = &self;
     let local_user_view = get_local_user_view_from_jwt_opt(&data.auth, context.pool()).await?;
-    let person_id = local_user_view.map(|u| u.person.id);
+    let person_id = local_user_view.as_ref().map(|u| u.person.id);
+    let show_nsfw = match &local_user_view {
+      Some(uv) => uv.local_user.show_nsfw,
+      None => false,
+    };
 
-    let type_ = ListingType::from_str(&data.type_)?;
-    let sort = SortType::from_str(&data.sort)?;
+    let for_community = data.community_id.is_some() || data.community_name.is_some();
+    let (type_, sort) = get_listing_defaults(
+      data.type_,
+      data.sort,
+      for_community,
+      &local_user_view,
+      context.pool(),
+    )
+    .await?;
 
     let community_id = data.community_id;
     let community_name = data.community_name.to_owned();
+    let saved_only = data.saved_only.unwrap_or(false);
     let page = data.page;
     let limit = data.limit;
     let comments = blocking(context.pool(), move |conn| {
       CommentQueryBuilder::create(conn)
         .listing_type(type_)
         .sort(&sort)
+        .show_nsfw(show_nsfw)
         .community_id(community_id)
         .community_name(community_name)
         .my_person_id(person_id)
+        .saved_only(saved_only)
         .page(page)
         .limit(limit)
         .list()
     })
     .await?;
-    let comments = match comments {
+    let mut comments = match comments {
       Ok(comments) => comments,
       Err(_) => return Err(ApiError::err("couldnt_get_comments").into()),
     };
+    hide_downvotes_if_disabled(&mut comments, context.pool()).await?;
 
     Ok(GetCommentsResponse { comments })
   }
@@ -754,7 +1161,7 @@ impl Perform for CreateCommentReport {
     context.chat_server().do_send(SendUserRoomMessage {
       op: UserOperation::CreateCommentReport,
       response: res.clone(),
-      local_recipient_id: local_user_view.person.id,
+      local_recipient_id: local_user_view.local_user.id,
       websocket_id,
     });
 
@@ -783,8 +1190,9 @@ impl Perform for ResolveCommentReport {
     let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
 
     let report_id = data.report_id;
+    let show_deleted_persons = can_see_deleted_persons(&local_user_view, context.pool()).await?;
     let report = blocking(context.pool(), move |conn| {
-      CommentReportView::read(&conn, report_id)
+      CommentReportView::read(&conn, report_id, show_deleted_persons)
     })
     .await??;
 
@@ -833,18 +1241,21 @@ impl Perform for ListCommentReports {
     websocket_id: Option<ConnectionId>,
   ) -> Result<ListCommentReportsResponse, LemmyError> {
     let data: &ListCommentReports = &self;
-    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+    let local_user_view =
+      get_local_user_view_from_jwt_read_only(&data.auth, context.pool()).await?;
 
     let person_id = local_user_view.person.id;
     let community_id = data.community;
     let community_ids =
       collect_moderated_communities(person_id, community_id, context.pool()).await?;
+    let show_deleted_persons = can_see_deleted_persons(&local_user_view, context.pool()).await?;
 
     let page = data.page;
     let limit = data.limit;
     let comments = blocking(context.pool(), move |conn| {
       CommentReportQueryBuilder::create(conn)
         .community_ids(community_ids)
+        .show_deleted_persons(show_deleted_persons)
         .page(page)
         .limit(limit)
         .list()
@@ -856,7 +1267,7 @@ impl Perform for ListCommentReports {
     context.chat_server().do_send(SendUserRoomMessage {
       op: UserOperation::ListCommentReports,
       response: res.clone(),
-      local_recipient_id: local_user_view.person.id,
+      local_recipient_id: local_user_view.local_user.id,
       websocket_id,
     });
 
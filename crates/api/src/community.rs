@@ -1,62 +1,116 @@
 use crate::{
   check_community_ban,
+  check_images_are_local,
   get_local_user_view_from_jwt,
   get_local_user_view_from_jwt_opt,
+  get_local_user_view_from_jwt_read_only,
   is_admin,
+  is_exempt_from_slur_filter,
   is_mod_or_admin,
+  site_requires_local_images,
   Perform,
 };
 use actix_web::web::Data;
 use anyhow::Context;
-use lemmy_api_structs::{blocking, community::*};
+use chrono::Duration;
+use lemmy_api_structs::{blocking, blocking_read, blocking_transaction, community::*};
 use lemmy_apub::{
+  answer_follow_request,
+  fetcher::{
+    community::refresh_remote_community,
+    person::resolve_person,
+    search::resolve_community_name,
+  },
   generate_apub_endpoint,
   generate_followers_url,
   generate_inbox_url,
   generate_shared_inbox_url,
+  is_instance_blocked,
   ActorType,
+  ApubObjectType,
   EndpointType,
 };
 use lemmy_db_queries::{
+  diesel_option_overwrite,
   diesel_option_overwrite_to_url,
   source::{
     comment::Comment_,
-    community::{CommunityModerator_, Community_},
+    community::{
+      CommunityFavorite_,
+      CommunityFollower_,
+      CommunityLastViewed_,
+      CommunityModerator_,
+      Community_,
+    },
+    community_federation_log::CommunityFederationLog_,
+    community_tag::CommunityTag_,
+    language::Language_,
     post::Post_,
   },
   ApubObject,
   Bannable,
   Crud,
+  DbPool,
   Followable,
   Joinable,
-  ListingType,
   SortType,
 };
 use lemmy_db_schema::{
   naive_now,
-  source::{comment::Comment, community::*, moderator::*, post::Post, site::*},
+  source::{
+    comment::Comment,
+    community::*,
+    community_federation_log::CommunityFederationLog,
+    community_tag::{CommunityTag, CommunityTagForm},
+    language::Language,
+    moderator::*,
+    person::Person,
+    post::Post,
+    site::*,
+  },
+  CommunityId,
+  PersonId,
+};
+use lemmy_db_views::{
+  comment_report_view::CommentReportView,
+  comment_view::CommentQueryBuilder,
+  local_user_view::LocalUserView,
+  post_report_view::PostReportView,
 };
-use lemmy_db_views::comment_view::CommentQueryBuilder;
 use lemmy_db_views_actor::{
+  community_autocomplete_view::CommunityAutocompleteView,
   community_follower_view::CommunityFollowerView,
   community_moderator_view::CommunityModeratorView,
-  community_view::{CommunityQueryBuilder, CommunityView},
+  community_person_ban_view::CommunityPersonBanView,
+  community_view::{fill_instance_status, CommunityQueryBuilder, CommunityView},
   person_view::PersonViewSafe,
 };
 use lemmy_utils::{
   apub::generate_actor_keypair,
   location_info,
-  utils::{check_slurs, check_slurs_opt, is_valid_community_name, naive_from_unix},
+  utils::{
+    check_slurs,
+    check_slurs_opt,
+    is_valid_community_name,
+    is_valid_post_tag_name,
+    is_valid_theme_color,
+    parse_expires,
+    MAX_SHORT_DESCRIPTION_LENGTH,
+  },
   ApiError,
+  ApiErrorCode,
   ConnectionId,
   LemmyError,
+  LocalUserId,
 };
 use lemmy_websocket::{
-  messages::{GetCommunityUsersOnline, SendCommunityRoomMessage},
+  messages::{GetCommunityUsersOnline, SendCommunityRoomMessage, SendUserRoomMessage},
+  send::send_community_websocket,
   LemmyContext,
   UserOperation,
 };
-use std::str::FromStr;
+use log::error;
+use std::collections::HashMap;
 
 #[async_trait::async_trait(?Send)]
 impl Perform for GetCommunity {
@@ -71,50 +125,51 @@ impl Perform for GetCommunity {
     let local_user_view = get_local_user_view_from_jwt_opt(&data.auth, context.pool()).await?;
     let person_id = local_user_view.map(|u| u.person.id);
 
-    let community_id = match data.id {
-      Some(id) => id,
-      None => {
-        let name = data.name.to_owned().unwrap_or_else(|| "main".to_string());
-        match blocking(context.pool(), move |conn| {
-          Community::read_from_name(conn, &name)
-        })
-        .await?
-        {
-          Ok(community) => community,
-          Err(_e) => return Err(ApiError::err("couldnt_find_community").into()),
-        }
-        .id
-      }
-    };
-
-    let community_view = match blocking(context.pool(), move |conn| {
-      CommunityView::read(conn, community_id, person_id)
-    })
-    .await?
-    {
-      Ok(community) => community,
-      Err(_e) => return Err(ApiError::err("couldnt_find_community").into()),
-    };
-
-    let moderators: Vec<CommunityModeratorView> = match blocking(context.pool(), move |conn| {
-      CommunityModeratorView::for_community(conn, community_id)
-    })
-    .await?
-    {
-      Ok(moderators) => moderators,
-      Err(_e) => return Err(ApiError::err("couldnt_find_community").into()),
-    };
+    let (community_view, moderators) = read_community_and_moderators(
+      context.read_pool(),
+      data.id,
+      data.name.to_owned(),
+      person_id,
+    )
+    .await?;
 
+    let community_id = community_view.community.id;
+    // Remember the visit, so that only newer posts are counted as unread. The community can be
+    // shown anyway if that fails.
+    if let Some(person_id) = person_id {
+      let marked = blocking(context.pool(), move |conn| {
+        CommunityLastViewed::mark_viewed(conn, community_id, person_id)
+      })
+      .await;
+      if let Err(e) = marked.and_then(|r| r.map_err(LemmyError::from)) {
+        error!(
+          "Couldn't mark community {} as viewed: {}",
+          community_id.0, e
+        );
+      }
+    }
     let online = context
       .chat_server()
       .send(GetCommunityUsersOnline { community_id })
       .await
       .unwrap_or(1);
 
+    let tags = blocking_read(context.read_pool(), move |conn| {
+      CommunityTag::list_for_community(conn, community_id)
+    })
+    .await??;
+
+    let discussion_languages = blocking(context.read_pool(), move |conn| {
+      Language::read_community_languages(conn, community_id)
+    })
+    .await??;
+
     let res = GetCommunityResponse {
       community_view,
       moderators,
       online,
+      tags,
+      discussion_languages,
     };
 
     // Return the jwt
@@ -134,12 +189,21 @@ impl Perform for CreateCommunity {
     let data: &CreateCommunity = &self;
     let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
 
-    check_slurs(&data.name)?;
-    check_slurs(&data.title)?;
-    check_slurs_opt(&data.description)?;
+    let slur_regex = context.slur_filter().regex();
+    check_slurs(&data.name, &slur_regex)?;
+    check_slurs(&data.title, &slur_regex)?;
+    check_slurs_opt(&data.description, &slur_regex)?;
+    check_slurs_opt(&data.short_description, &slur_regex)?;
+    let theme_color = diesel_option_overwrite(&data.theme_color);
+    let short_description = diesel_option_overwrite(&data.short_description);
+    check_community_appearance(&theme_color, &short_description)?;
 
     if !is_valid_community_name(&data.name) {
-      return Err(ApiError::err("invalid_community_name").into());
+      return Err(
+        ApiError::new(ApiErrorCode::InvalidCommunityName)
+          .with_field("name")
+          .into(),
+      );
     }
 
     // Double check for duplicate community actor_ids
@@ -149,13 +213,21 @@ impl Perform for CreateCommunity {
       Community::read_from_apub_id(conn, &actor_id_cloned)
     })
     .await?;
-    if community_dupe.is_ok() {
-      return Err(ApiError::err("community_already_exists").into());
+    if let Ok(community_dupe) = community_dupe {
+      return Err(
+        ApiError::new(ApiErrorCode::CommunityAlreadyExists)
+          .with_field("name")
+          .with_detail("community_id", community_dupe.id.0)
+          .into(),
+      );
     }
 
     // Check to make sure the icon and banners are urls
     let icon = diesel_option_overwrite_to_url(&data.icon)?;
     let banner = diesel_option_overwrite_to_url(&data.banner)?;
+    if site_requires_local_images(context.pool()).await? {
+      check_images_are_local(&[("icon", &icon), ("banner", &banner)])?;
+    }
 
     // When you create a community, make sure the user becomes a moderator and a follower
     let keypair = generate_actor_keypair()?;
@@ -180,39 +252,42 @@ impl Perform for CreateCommunity {
       followers_url: Some(generate_followers_url(&community_actor_id)?),
       inbox_url: Some(generate_inbox_url(&community_actor_id)?),
       shared_inbox_url: Some(Some(generate_shared_inbox_url(&community_actor_id)?)),
+      downvotes_disabled: data.downvotes_disabled,
+      requires_post_approval: data.requires_post_approval,
+      theme_color,
+      short_description,
+      default_comment_sort: data.default_comment_sort.map(|s| s as i16),
+      require_post_tag: data.require_post_tag,
+      manual_follow_approval: data.manual_follow_approval,
     };
 
-    let inserted_community = match blocking(context.pool(), move |conn| {
-      Community::create(conn, &community_form)
-    })
-    .await?
-    {
-      Ok(community) => community,
-      Err(_e) => return Err(ApiError::err("community_already_exists").into()),
-    };
-
-    // The community creator becomes a moderator
-    let community_moderator_form = CommunityModeratorForm {
-      community_id: inserted_community.id,
-      person_id: local_user_view.person.id,
-    };
+    // Create the community, and make the creator a moderator and a follower, all in one
+    // transaction so that a failure never leaves behind a community without moderators.
+    let creator_id = local_user_view.person.id;
+    let inserted_community = blocking_transaction(context.pool(), move |conn| {
+      let inserted_community = Community::create(conn, &community_form)
+        .map_err(|_| ApiError::new(ApiErrorCode::CommunityAlreadyExists).with_field("name"))?;
 
-    let join = move |conn: &'_ _| CommunityModerator::join(conn, &community_moderator_form);
-    if blocking(context.pool(), join).await?.is_err() {
-      return Err(ApiError::err("community_moderator_already_exists").into());
-    }
+      // The community creator becomes a moderator
+      let community_moderator_form = CommunityModeratorForm {
+        community_id: inserted_community.id,
+        person_id: creator_id,
+      };
+      CommunityModerator::join(conn, &community_moderator_form)
+        .map_err(|_| ApiError::new(ApiErrorCode::CommunityModeratorAlreadyExists))?;
 
-    // Follow your own community
-    let community_follower_form = CommunityFollowerForm {
-      community_id: inserted_community.id,
-      person_id: local_user_view.person.id,
-      pending: false,
-    };
+      // Follow your own community
+      let community_follower_form = CommunityFollowerForm {
+        community_id: inserted_community.id,
+        person_id: creator_id,
+        pending: false,
+      };
+      CommunityFollower::follow(conn, &community_follower_form)
+        .map_err(|_| ApiError::new(ApiErrorCode::CommunityFollowerAlreadyExists))?;
 
-    let follow = move |conn: &'_ _| CommunityFollower::follow(conn, &community_follower_form);
-    if blocking(context.pool(), follow).await?.is_err() {
-      return Err(ApiError::err("community_follower_already_exists").into());
-    }
+      Ok(inserted_community)
+    })
+    .await?;
 
     let person_id = local_user_view.person.id;
     let community_view = blocking(context.pool(), move |conn| {
@@ -236,18 +311,24 @@ impl Perform for EditCommunity {
     let data: &EditCommunity = &self;
     let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
 
-    check_slurs(&data.title)?;
-    check_slurs_opt(&data.description)?;
+    let slur_regex = context.slur_filter().regex();
+    check_slurs(&data.title, &slur_regex)?;
+    check_slurs_opt(&data.description, &slur_regex)?;
+    check_slurs_opt(&data.short_description, &slur_regex)?;
+    let theme_color = diesel_option_overwrite(&data.theme_color);
+    let short_description = diesel_option_overwrite(&data.short_description);
+    check_community_appearance(&theme_color, &short_description)?;
 
-    // Verify its a mod (only mods can edit it)
+    // Verify its a mod or an admin (only they can edit it)
     let community_id = data.community_id;
-    let mods: Vec<i32> = blocking(context.pool(), move |conn| {
+    let mods: Vec<PersonId> = blocking(context.pool(), move |conn| {
       CommunityModeratorView::for_community(conn, community_id)
         .map(|v| v.into_iter().map(|m| m.moderator.id).collect())
     })
     .await??;
-    if !mods.contains(&local_user_view.person.id) {
-      return Err(ApiError::err("not_a_moderator").into());
+    let is_mod = mods.contains(&local_user_view.person.id);
+    if !is_mod && !local_user_view.local_user.admin {
+      return Err(ApiError::new(ApiErrorCode::NotAModerator).into());
     }
 
     let community_id = data.community_id;
@@ -255,9 +336,13 @@ impl Perform for EditCommunity {
       Community::read(conn, community_id)
     })
     .await??;
+    let old_community = read_community.clone();
 
     let icon = diesel_option_overwrite_to_url(&data.icon)?;
     let banner = diesel_option_overwrite_to_url(&data.banner)?;
+    if site_requires_local_images(context.pool()).await? {
+      check_images_are_local(&[("icon", &icon), ("banner", &banner)])?;
+    }
 
     let community_form = CommunityForm {
       name: read_community.name,
@@ -279,31 +364,46 @@ impl Perform for EditCommunity {
       followers_url: None,
       inbox_url: None,
       shared_inbox_url: None,
+      downvotes_disabled: data.downvotes_disabled,
+      requires_post_approval: data.requires_post_approval,
+      theme_color,
+      short_description,
+      default_comment_sort: data.default_comment_sort.map(|s| s as i16),
+      require_post_tag: data.require_post_tag,
+      manual_follow_approval: data.manual_follow_approval,
     };
 
+    // Update the community and its languages, and read it back on the same connection. Edits by an
+    // admin who isn't a mod of the community go into the modlog, so that its mods can see them.
     let community_id = data.community_id;
-    match blocking(context.pool(), move |conn| {
-      Community::update(conn, community_id, &community_form)
+    let person_id = local_user_view.person.id;
+    let discussion_languages = data.discussion_languages.to_owned();
+    let community_view = blocking_transaction(context.pool(), move |conn| {
+      let updated_community = Community::update(conn, community_id, &community_form)
+        .map_err(|_| ApiError::new(ApiErrorCode::CouldntUpdateCommunity))?;
+      if let Some(discussion_languages) = discussion_languages {
+        Language::update_community_languages(conn, community_id, &discussion_languages).map_err(
+          |_| ApiError::new(ApiErrorCode::LanguageNotAllowed).with_field("discussion_languages"),
+        )?;
+      }
+      if !is_mod {
+        let form = ModEditCommunityForm {
+          mod_person_id: person_id,
+          community_id,
+          changed_fields: changed_community_fields(&old_community, &updated_community),
+        };
+        ModEditCommunity::create(conn, &form)?;
+      }
+      Ok(CommunityView::read(conn, community_id, Some(person_id))?)
     })
-    .await?
-    {
-      Ok(community) => community,
-      Err(_e) => return Err(ApiError::err("couldnt_update_community").into()),
-    };
+    .await?;
 
     // TODO there needs to be some kind of an apub update
     // process for communities and users
 
-    let community_id = data.community_id;
-    let person_id = local_user_view.person.id;
-    let community_view = blocking(context.pool(), move |conn| {
-      CommunityView::read(conn, community_id, Some(person_id))
-    })
-    .await??;
-
     let res = CommunityResponse { community_view };
 
-    send_community_websocket(&res, context, websocket_id, UserOperation::EditCommunity);
+    send_community_websocket(&res, context, websocket_id, UserOperation::EditCommunity).await?;
 
     Ok(res)
   }
@@ -321,27 +421,45 @@ impl Perform for DeleteCommunity {
     let data: &DeleteCommunity = &self;
     let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
 
-    // Verify its the creator (only a creator can delete the community)
+    // Verify its the creator or an admin (only they can delete the community)
     let community_id = data.community_id;
-    let read_community = blocking(context.pool(), move |conn| {
-      Community::read(conn, community_id)
+    let (read_community, mods) = blocking(context.pool(), move |conn| -> Result<_, LemmyError> {
+      let community = Community::read(conn, community_id)?;
+      let mods: Vec<PersonId> = CommunityModeratorView::for_community(conn, community_id)?
+        .into_iter()
+        .map(|m| m.moderator.id)
+        .collect();
+      Ok((community, mods))
     })
     .await??;
-    if read_community.creator_id != local_user_view.person.id {
-      return Err(ApiError::err("no_community_edit_allowed").into());
+    if read_community.creator_id != local_user_view.person.id && !local_user_view.local_user.admin {
+      return Err(ApiError::new(ApiErrorCode::NoCommunityEditAllowed).into());
     }
+    // Creators who aren't mods anymore don't leave an entry, only admins do
+    let is_admin_action =
+      !mods.contains(&local_user_view.person.id) && local_user_view.local_user.admin;
 
-    // Do the delete
+    // Do the delete, and read back the view on the same connection. Like with edits, admins who
+    // aren't mods of the community leave a modlog entry.
     let community_id = data.community_id;
     let deleted = data.deleted;
-    let updated_community = match blocking(context.pool(), move |conn| {
-      Community::update_deleted(conn, community_id, deleted)
-    })
-    .await?
-    {
-      Ok(community) => community,
-      Err(_e) => return Err(ApiError::err("couldnt_update_community").into()),
-    };
+    let person_id = local_user_view.person.id;
+    let (updated_community, community_view) =
+      blocking(context.pool(), move |conn| -> Result<_, LemmyError> {
+        let updated_community = Community::update_deleted(conn, community_id, deleted)
+          .map_err(|_| ApiError::new(ApiErrorCode::CouldntUpdateCommunity))?;
+        if is_admin_action {
+          let form = ModEditCommunityForm {
+            mod_person_id: person_id,
+            community_id,
+            changed_fields: vec!["deleted".to_string()],
+          };
+          ModEditCommunity::create(conn, &form)?;
+        }
+        let community_view = CommunityView::read(conn, community_id, Some(person_id))?;
+        Ok((updated_community, community_view))
+      })
+      .await??;
 
     // Send apub messages
     if deleted {
@@ -350,21 +468,109 @@ impl Perform for DeleteCommunity {
       updated_community.send_undo_delete(context).await?;
     }
 
-    let community_id = data.community_id;
-    let person_id = local_user_view.person.id;
-    let community_view = blocking(context.pool(), move |conn| {
-      CommunityView::read(conn, community_id, Some(person_id))
-    })
-    .await??;
+    // The content can take long to update for large communities, so it happens in the background
+    if !deleted || data.remove_content.unwrap_or(false) {
+      let context = context.clone();
+      actix_rt::spawn(async move {
+        if let Err(e) = update_community_content_deleted(
+          community_id,
+          deleted,
+          local_user_view.person,
+          local_user_view.local_user.id,
+          &context,
+        )
+        .await
+        {
+          error!(
+            "Failed to update content of community {}: {}",
+            community_id, e
+          );
+        }
+      });
+    }
 
     let res = CommunityResponse { community_view };
 
-    send_community_websocket(&res, context, websocket_id, UserOperation::DeleteCommunity);
+    send_community_websocket(&res, context, websocket_id, UserOperation::DeleteCommunity).await?;
 
     Ok(res)
   }
 }
 
+/// How many posts or comments are federated between two progress messages
+const DELETE_COMMUNITY_BATCH_SIZE: usize = 100;
+
+/// Deletes all posts and comments of a community, or restores the ones which were deleted together
+/// with it, and federates the changes of local content. The creator gets progress messages over
+/// websocket. Remote posts and comments are only changed locally, because other instances dont
+/// accept deletes of them from the community creator.
+async fn update_community_content_deleted(
+  community_id: CommunityId,
+  deleted: bool,
+  creator: Person,
+  local_user_id: LocalUserId,
+  context: &LemmyContext,
+) -> Result<(), LemmyError> {
+  let (posts, comments) = blocking_transaction(context.pool(), move |conn| {
+    let posts = Post::update_deleted_for_community_delete(conn, community_id, deleted)?;
+    let comments = Comment::update_deleted_for_community_delete(conn, community_id, deleted)?;
+    Ok((posts, comments))
+  })
+  .await?;
+  if posts.is_empty() && comments.is_empty() {
+    return Ok(());
+  }
+
+  let mut progress = DeleteCommunityProgress {
+    community_id,
+    deleted,
+    total_posts: posts.len(),
+    total_comments: comments.len(),
+    processed_posts: 0,
+    processed_comments: 0,
+    done: false,
+  };
+  for batch in posts.chunks(DELETE_COMMUNITY_BATCH_SIZE) {
+    for post in batch.iter().filter(|p| p.local) {
+      if deleted {
+        post.send_delete(&creator, context).await?;
+      } else {
+        post.send_undo_delete(&creator, context).await?;
+      }
+    }
+    progress.processed_posts += batch.len();
+    send_delete_community_progress(&progress, local_user_id, context);
+  }
+  for batch in comments.chunks(DELETE_COMMUNITY_BATCH_SIZE) {
+    for comment in batch.iter().filter(|c| c.local) {
+      if deleted {
+        comment.send_delete(&creator, context).await?;
+      } else {
+        comment.send_undo_delete(&creator, context).await?;
+      }
+    }
+    progress.processed_comments += batch.len();
+    send_delete_community_progress(&progress, local_user_id, context);
+  }
+
+  progress.done = true;
+  send_delete_community_progress(&progress, local_user_id, context);
+  Ok(())
+}
+
+fn send_delete_community_progress(
+  progress: &DeleteCommunityProgress,
+  local_user_id: LocalUserId,
+  context: &LemmyContext,
+) {
+  context.chat_server().do_send(SendUserRoomMessage {
+    op: UserOperation::DeleteCommunityProgress,
+    response: progress.clone(),
+    local_recipient_id: local_user_id,
+    websocket_id: None,
+  });
+}
+
 #[async_trait::async_trait(?Send)]
 impl Perform for RemoveCommunity {
   type Response = CommunityResponse;
@@ -380,34 +586,33 @@ impl Perform for RemoveCommunity {
     // Verify its an admin (only an admin can remove a community)
     is_admin(&local_user_view)?;
 
-    // Do the remove
-    let community_id = data.community_id;
-    let removed = data.removed;
-    let updated_community = match blocking(context.pool(), move |conn| {
-      Community::update_removed(conn, community_id, removed)
-    })
-    .await?
-    {
-      Ok(community) => community,
-      Err(_e) => return Err(ApiError::err("couldnt_update_community").into()),
-    };
-
     // Mod tables
-    let expires = match data.expires {
-      Some(time) => Some(naive_from_unix(time)),
-      None => None,
-    };
+    let expires = parse_expires(data.expires, data.expires_rfc3339.as_deref())?;
+    let removed = data.removed;
+    let remove_content = removed && data.remove_content.unwrap_or(false);
     let form = ModRemoveCommunityForm {
       mod_person_id: local_user_view.person.id,
       community_id: data.community_id,
       removed: Some(removed),
       reason: data.reason.to_owned(),
       expires,
+      remove_content: Some(remove_content),
     };
-    blocking(context.pool(), move |conn| {
-      ModRemoveCommunity::create(conn, &form)
+
+    // The remove, the removal of the content and the mod log entry are written in one transaction.
+    // Restoring the community also restores the content which was removed together with it.
+    let community_id = data.community_id;
+    let updated_community = blocking_transaction(context.pool(), move |conn| {
+      let updated_community = Community::update_removed(conn, community_id, removed)
+        .map_err(|_| ApiError::new(ApiErrorCode::CouldntUpdateCommunity))?;
+      if !removed || remove_content {
+        Post::update_removed_for_community_remove(conn, community_id, removed)?;
+        Comment::update_removed_for_community_remove(conn, community_id, removed)?;
+      }
+      ModRemoveCommunity::create(conn, &form)?;
+      Ok(updated_community)
     })
-    .await??;
+    .await?;
 
     // Apub messages
     if removed {
@@ -416,7 +621,71 @@ impl Perform for RemoveCommunity {
       updated_community.send_undo_remove(context).await?;
     }
 
+    let person_id = local_user_view.person.id;
+    let creator_id = updated_community.creator_id;
+    let (community_view, creator_local_user) =
+      blocking(context.pool(), move |conn| -> Result<_, LemmyError> {
+        let community_view = CommunityView::read(conn, community_id, Some(person_id))?;
+        let creator_local_user = LocalUserView::read_person(conn, creator_id).ok();
+        Ok((community_view, creator_local_user))
+      })
+      .await??;
+
+    let res = CommunityResponse { community_view };
+
+    send_community_websocket(&res, context, websocket_id, UserOperation::RemoveCommunity).await?;
+
+    // Let the creator know why their community is gone, even if they dont follow it
+    if let Some(creator_local_user) = creator_local_user {
+      context.chat_server().do_send(SendUserRoomMessage {
+        op: UserOperation::RemoveCommunity,
+        response: res.clone(),
+        local_recipient_id: creator_local_user.local_user.id,
+        websocket_id: None,
+      });
+    }
+
+    Ok(res)
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for ExemptCommunityFromSlurFilter {
+  type Response = CommunityResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    websocket_id: Option<ConnectionId>,
+  ) -> Result<CommunityResponse, LemmyError> {
+    let data: &ExemptCommunityFromSlurFilter = &self;
+    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+
+    // Verify its an admin, community mods can't exempt their own community
+    is_admin(&local_user_view)?;
+
     let community_id = data.community_id;
+    let exempt = data.exempt;
+    if blocking(context.pool(), move |conn| {
+      Community::update_exempt_from_slur_filter(conn, community_id, exempt)
+    })
+    .await?
+    .is_err()
+    {
+      return Err(ApiError::new(ApiErrorCode::CouldntUpdateCommunity).into());
+    }
+
+    // Mod tables
+    let form = ModExemptCommunityForm {
+      mod_person_id: local_user_view.person.id,
+      community_id: data.community_id,
+      exempt: Some(exempt),
+    };
+    blocking(context.pool(), move |conn| {
+      ModExemptCommunity::create(conn, &form)
+    })
+    .await??;
+
     let person_id = local_user_view.person.id;
     let community_view = blocking(context.pool(), move |conn| {
       CommunityView::read(conn, community_id, Some(person_id))
@@ -425,12 +694,21 @@ impl Perform for RemoveCommunity {
 
     let res = CommunityResponse { community_view };
 
-    send_community_websocket(&res, context, websocket_id, UserOperation::RemoveCommunity);
+    send_community_websocket(
+      &res,
+      context,
+      websocket_id,
+      UserOperation::ExemptCommunityFromSlurFilter,
+    )
+    .await?;
 
     Ok(res)
   }
 }
 
+/// Communities without posts or comments for longer are hidden by `ListCommunities::hide_inactive`
+const INACTIVE_COMMUNITY_DAYS: i64 = 90;
+
 #[async_trait::async_trait(?Send)]
 impl Perform for ListCommunities {
   type Response = ListCommunitiesResponse;
@@ -454,25 +732,114 @@ impl Perform for ListCommunities {
       None => false,
     };
 
-    let type_ = ListingType::from_str(&data.type_)?;
-    let sort = SortType::from_str(&data.sort)?;
+    // Logged out users don't moderate anything
+    let moderated_only = data.moderated_only.unwrap_or(false);
+    if moderated_only && person_id.is_none() {
+      return Ok(ListCommunitiesResponse {
+        communities: vec![],
+        report_counts: vec![],
+        total_count: data.include_total.filter(|t| *t).map(|_| 0),
+        has_more: false,
+      });
+    }
+
+    let type_ = data.type_.unwrap_or_default();
+    let sort = data.sort.unwrap_or_default();
+    let active_since = if data.hide_inactive.unwrap_or(false) {
+      Some(naive_now() - Duration::days(INACTIVE_COMMUNITY_DAYS))
+    } else {
+      None
+    };
 
     let page = data.page;
     let limit = data.limit;
-    let communities = blocking(context.pool(), move |conn| {
-      CommunityQueryBuilder::create(conn)
-        .listing_type(&type_)
-        .sort(&sort)
-        .show_nsfw(show_nsfw)
-        .my_person_id(person_id)
-        .page(page)
-        .limit(limit)
-        .list()
+    let include_total = data.include_total.unwrap_or(false);
+    let (communities, has_more, total_count, comment_reports, post_reports) =
+      blocking_read(context.read_pool(), move |conn| -> Result<_, LemmyError> {
+        let query = CommunityQueryBuilder::create(conn)
+          .listing_type(&type_)
+          .sort(&sort)
+          .show_nsfw(show_nsfw)
+          .moderated_only(moderated_only)
+          .active_since(active_since)
+          .my_person_id(person_id)
+          .page(page)
+          .limit(limit);
+        let total_count = if include_total {
+          Some(query.count()?)
+        } else {
+          None
+        };
+        let (communities, has_more) = query.list_with_has_more()?;
+
+        // The report counts for a mod dashboard, so that it needs only one request
+        if !moderated_only {
+          return Ok((communities, has_more, total_count, vec![], vec![]));
+        }
+        let community_ids: Vec<CommunityId> = communities.iter().map(|c| c.community.id).collect();
+        let comment_reports =
+          CommentReportView::get_report_counts_by_community(conn, &community_ids)?;
+        let post_reports = PostReportView::get_report_counts_by_community(conn, &community_ids)?;
+        Ok((
+          communities,
+          has_more,
+          total_count,
+          comment_reports,
+          post_reports,
+        ))
+      })
+      .await??;
+
+    let comment_reports: HashMap<CommunityId, i64> = comment_reports.into_iter().collect();
+    let post_reports: HashMap<CommunityId, i64> = post_reports.into_iter().collect();
+    let report_counts = communities
+      .iter()
+      .map(|c| c.community.id)
+      .filter(|id| comment_reports.contains_key(id) || post_reports.contains_key(id))
+      .map(|community_id| CommunityReportCount {
+        community_id,
+        comment_reports: comment_reports.get(&community_id).copied().unwrap_or(0),
+        post_reports: post_reports.get(&community_id).copied().unwrap_or(0),
+      })
+      .collect();
+
+    Ok(ListCommunitiesResponse {
+      communities,
+      report_counts,
+      total_count,
+      has_more,
+    })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for CommunityAutocomplete {
+  type Response = CommunityAutocompleteResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<CommunityAutocompleteResponse, LemmyError> {
+    let data: &CommunityAutocomplete = &self;
+    let local_user_view = get_local_user_view_from_jwt_opt(&data.auth, context.pool()).await?;
+    let person_id = local_user_view.map(|uv| uv.person.id);
+
+    // An empty prefix would match every community
+    let q = data.q.trim().to_owned();
+    if q.is_empty() {
+      return Ok(CommunityAutocompleteResponse {
+        communities: vec![],
+      });
+    }
+
+    let limit = data.limit;
+    let communities = blocking_read(context.read_pool(), move |conn| {
+      CommunityAutocompleteView::list(conn, &q, person_id, limit)
     })
     .await??;
 
-    // Return the jwt
-    Ok(ListCommunitiesResponse { communities })
+    Ok(CommunityAutocompleteResponse { communities })
   }
 }
 
@@ -488,35 +855,61 @@ impl Perform for FollowCommunity {
     let data: &FollowCommunity = &self;
     let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
 
-    let community_id = data.community_id;
-    let community = blocking(context.pool(), move |conn| {
-      Community::read(conn, community_id)
-    })
-    .await??;
+    let community = match (data.community_id, &data.community_name) {
+      (Some(community_id), _) => {
+        blocking(context.pool(), move |conn| {
+          Community::read(conn, community_id)
+        })
+        .await??
+      }
+      (None, Some(community_name)) => resolve_community_name(community_name, context)
+        .await
+        .map_err(|_| {
+          ApiError::new(ApiErrorCode::CouldntFindCommunity).with_field("community_name")
+        })?,
+      (None, None) => return Err(ApiError::new(ApiErrorCode::CouldntFindCommunity).into()),
+    };
+    let community_id = community.id;
     let community_follower_form = CommunityFollowerForm {
-      community_id: data.community_id,
+      community_id,
       person_id: local_user_view.person.id,
       pending: false,
     };
 
+    let mut pending = false;
     if community.local {
       if data.follow {
         check_community_ban(local_user_view.person.id, community_id, context.pool()).await?;
 
-        let follow = move |conn: &'_ _| CommunityFollower::follow(conn, &community_follower_form);
-        if blocking(context.pool(), follow).await?.is_err() {
-          return Err(ApiError::err("community_follower_already_exists").into());
+        // Mods and admins don't need to wait for an approval
+        let person_id = local_user_view.person.id;
+        let needs_approval = community.manual_follow_approval
+          && !blocking(context.pool(), move |conn| {
+            CommunityView::is_mod_or_admin(conn, person_id, community_id)
+          })
+          .await?;
+        if needs_approval {
+          let follower = blocking(context.pool(), move |conn| {
+            CommunityFollower::follow_requested(conn, community_id, person_id, None)
+          })
+          .await??;
+          pending = follower.pending == Some(true);
+        } else {
+          let follow = move |conn: &'_ _| CommunityFollower::follow(conn, &community_follower_form);
+          if blocking(context.pool(), follow).await?.is_err() {
+            return Err(ApiError::new(ApiErrorCode::CommunityFollowerAlreadyExists).into());
+          }
         }
       } else {
         let unfollow =
           move |conn: &'_ _| CommunityFollower::unfollow(conn, &community_follower_form);
         if blocking(context.pool(), unfollow).await?.is_err() {
-          return Err(ApiError::err("community_follower_already_exists").into());
+          return Err(ApiError::new(ApiErrorCode::CommunityFollowerAlreadyExists).into());
         }
       }
     } else if data.follow {
-      // Dont actually add to the community followers here, because you need
-      // to wait for the accept
+      // The follow stays pending until the accept is received. Following again reuses a pending
+      // or failed follow, and sends the Follow again.
       local_user_view
         .person
         .send_follow(&community.actor_id(), context)
@@ -528,11 +921,22 @@ impl Perform for FollowCommunity {
         .await?;
       let unfollow = move |conn: &'_ _| CommunityFollower::unfollow(conn, &community_follower_form);
       if blocking(context.pool(), unfollow).await?.is_err() {
-        return Err(ApiError::err("community_follower_already_exists").into());
+        return Err(ApiError::new(ApiErrorCode::CommunityFollowerAlreadyExists).into());
       }
     }
 
-    let community_id = data.community_id;
+    // Favorites are a subset of the followed communities
+    if !data.follow {
+      let favorite_form = CommunityFavoriteForm {
+        community_id,
+        person_id: local_user_view.person.id,
+      };
+      blocking(context.pool(), move |conn| {
+        CommunityFavorite::unfavorite(conn, &favorite_form)
+      })
+      .await??;
+    }
+
     let person_id = local_user_view.person.id;
     let mut community_view = blocking(context.pool(), move |conn| {
       CommunityView::read(conn, community_id, Some(person_id))
@@ -545,120 +949,280 @@ impl Perform for FollowCommunity {
     if !community.local {
       community_view.subscribed = data.follow;
     }
+    // A follow request only counts once a mod approved it
+    if pending {
+      community_view.subscribed = false;
+    }
 
     Ok(CommunityResponse { community_view })
   }
 }
 
 #[async_trait::async_trait(?Send)]
-impl Perform for GetFollowedCommunities {
-  type Response = GetFollowedCommunitiesResponse;
+impl Perform for FavoriteCommunity {
+  type Response = CommunityResponse;
 
   async fn perform(
     &self,
     context: &Data<LemmyContext>,
-    _websocket_id: Option<ConnectionId>,
-  ) -> Result<GetFollowedCommunitiesResponse, LemmyError> {
-    let data: &GetFollowedCommunities = &self;
+    websocket_id: Option<ConnectionId>,
+  ) -> Result<CommunityResponse, LemmyError> {
+    let data: &FavoriteCommunity = &self;
     let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
 
+    let community_id = data.community_id;
     let person_id = local_user_view.person.id;
-    let communities = match blocking(context.pool(), move |conn| {
-      CommunityFollowerView::for_person(conn, person_id)
+    let community_view = blocking(context.pool(), move |conn| {
+      CommunityView::read(conn, community_id, Some(person_id))
     })
     .await?
-    {
-      Ok(communities) => communities,
-      _ => return Err(ApiError::err("system_err_login").into()),
+    .map_err(|_| ApiError::new(ApiErrorCode::CouldntFindCommunity).with_field("community_id"))?;
+
+    // Favorites are a subset of the followed communities, so follow it first
+    if data.favorite && !community_view.subscribed {
+      let follow = FollowCommunity {
+        community_id: Some(community_id),
+        community_name: None,
+        follow: true,
+        auth: data.auth.to_owned(),
+      };
+      follow.perform(context, websocket_id).await?;
+    }
+
+    let favorite_form = CommunityFavoriteForm {
+      community_id,
+      person_id,
+    };
+    let favorite = data.favorite;
+    let update_favorite = move |conn: &'_ _| {
+      if favorite {
+        CommunityFavorite::favorite(conn, &favorite_form)
+      } else {
+        CommunityFavorite::unfavorite(conn, &favorite_form)
+      }
     };
+    if blocking(context.pool(), update_favorite).await?.is_err() {
+      return Err(ApiError::err("couldnt_favorite_community").into());
+    }
 
-    // Return the jwt
-    Ok(GetFollowedCommunitiesResponse { communities })
+    let mut community_view = blocking(context.pool(), move |conn| {
+      CommunityView::read(conn, community_id, Some(person_id))
+    })
+    .await??;
+
+    // Like in FollowCommunity, remote follows are assumed to be accepted
+    if data.favorite {
+      community_view.subscribed = true;
+    }
+
+    Ok(CommunityResponse { community_view })
   }
 }
 
 #[async_trait::async_trait(?Send)]
-impl Perform for BanFromCommunity {
-  type Response = BanFromCommunityResponse;
+impl Perform for MuteCommunity {
+  type Response = MuteCommunityResponse;
 
   async fn perform(
     &self,
     context: &Data<LemmyContext>,
-    websocket_id: Option<ConnectionId>,
-  ) -> Result<BanFromCommunityResponse, LemmyError> {
-    let data: &BanFromCommunity = &self;
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<MuteCommunityResponse, LemmyError> {
+    let data: &MuteCommunity = &self;
     let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
 
+    let mute_until =
+      parse_expires(data.mute_until, None).map_err(|e| e.with_field("mute_until"))?;
+
+    // Only followed communities can be muted
     let community_id = data.community_id;
-    let banned_person_id = data.person_id;
+    let person_id = local_user_view.person.id;
+    let follower = blocking(context.pool(), move |conn| {
+      CommunityFollower::mute(conn, community_id, person_id, mute_until)
+    })
+    .await?
+    .map_err(|_| ApiError::err("not_following_community").with_field("community_id"))?;
 
-    // Verify that only mods or admins can ban
-    is_mod_or_admin(context.pool(), local_user_view.person.id, community_id).await?;
+    Ok(MuteCommunityResponse {
+      community_id,
+      mute_until: follower.mute_until,
+    })
+  }
+}
 
-    let community_user_ban_form = CommunityPersonBanForm {
-      community_id: data.community_id,
-      person_id: data.person_id,
-    };
+#[async_trait::async_trait(?Send)]
+impl Perform for ListFollowRequests {
+  type Response = ListFollowRequestsResponse;
 
-    if data.ban {
-      let ban = move |conn: &'_ _| CommunityPersonBan::ban(conn, &community_user_ban_form);
-      if blocking(context.pool(), ban).await?.is_err() {
-        return Err(ApiError::err("community_user_already_banned").into());
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<ListFollowRequestsResponse, LemmyError> {
+    let data: &ListFollowRequests = &self;
+    let local_user_view =
+      get_local_user_view_from_jwt_read_only(&data.auth, context.pool()).await?;
+
+    let community_id = data.community_id;
+    is_mod_or_admin(context.pool(), local_user_view.person.id, community_id).await?;
+
+    let follow_requests = blocking(context.pool(), move |conn| {
+      CommunityFollowerView::list_pending(conn, community_id)
+    })
+    .await??;
+
+    Ok(ListFollowRequestsResponse { follow_requests })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for ApproveFollowRequest {
+  type Response = ApproveFollowRequestResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<ApproveFollowRequestResponse, LemmyError> {
+    let data: &ApproveFollowRequest = &self;
+    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+
+    let community_id = data.community_id;
+    is_mod_or_admin(context.pool(), local_user_view.person.id, community_id).await?;
+
+    let person_id = data.person_id;
+    let follower = blocking(context.pool(), move |conn| {
+      CommunityFollower::read_follow(conn, community_id, person_id)
+    })
+    .await?
+    .ok()
+    .filter(|f| f.pending == Some(true))
+    .ok_or_else(|| ApiError::err("follow_request_not_found").with_field("person_id"))?;
+
+    let approve = data.approve;
+    let (community, person) = blocking(context.pool(), move |conn| {
+      if approve {
+        CommunityFollower::follow_accepted(conn, community_id, person_id)?;
+      } else {
+        let form = CommunityFollowerForm {
+          community_id,
+          person_id,
+          pending: false,
+        };
+        CommunityFollower::unfollow(conn, &form)?;
       }
+      Ok((
+        Community::read(conn, community_id)?,
+        Person::read(conn, person_id)?,
+      )) as Result<_, LemmyError>
+    })
+    .await??;
 
-      // Also unsubscribe them from the community, if they are subscribed
-      let community_follower_form = CommunityFollowerForm {
-        community_id: data.community_id,
-        person_id: banned_person_id,
-        pending: false,
-      };
-      blocking(context.pool(), move |conn: &'_ _| {
-        CommunityFollower::unfollow(conn, &community_follower_form)
-      })
-      .await?
-      .ok();
-    } else {
-      let unban = move |conn: &'_ _| CommunityPersonBan::unban(conn, &community_user_ban_form);
-      if blocking(context.pool(), unban).await?.is_err() {
-        return Err(ApiError::err("community_user_already_banned").into());
+    // Local persons see the result when they load the community
+    if let Some(follow_activity_id) = follower.follow_activity_id {
+      if !person.local {
+        answer_follow_request(
+          &community,
+          &person,
+          follow_activity_id.into_inner(),
+          approve,
+          context,
+        )
+        .await?;
       }
     }
 
-    // Remove/Restore their data if that's desired
-    if data.remove_data {
-      // Posts
-      blocking(context.pool(), move |conn: &'_ _| {
-        Post::update_removed_for_creator(conn, banned_person_id, Some(community_id), true)
-      })
-      .await??;
+    Ok(ApproveFollowRequestResponse {
+      community_id,
+      person_id,
+      approved: approve,
+    })
+  }
+}
 
-      // Comments
-      // TODO Diesel doesn't allow updates with joins, so this has to be a loop
-      let comments = blocking(context.pool(), move |conn| {
-        CommentQueryBuilder::create(conn)
-          .creator_id(banned_person_id)
-          .community_id(community_id)
-          .limit(std::i64::MAX)
-          .list()
+#[async_trait::async_trait(?Send)]
+impl Perform for GetFollowedCommunities {
+  type Response = GetFollowedCommunitiesResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<GetFollowedCommunitiesResponse, LemmyError> {
+    let data: &GetFollowedCommunities = &self;
+    let local_user_view =
+      get_local_user_view_from_jwt_read_only(&data.auth, context.pool()).await?;
+
+    let person_id = local_user_view.person.id;
+    let page = data.page;
+    let limit = data.limit;
+    let unread_counts = data.unread_counts.unwrap_or(false);
+    let include_removed = data.include_removed.unwrap_or(false);
+    let include_total = data.include_total.unwrap_or(false);
+    let (communities, has_more, total_count) =
+      match blocking_read(context.read_pool(), move |conn| -> Result<_, LemmyError> {
+        let (mut communities, has_more) = CommunityFollowerView::for_person_with_has_more(
+          conn,
+          person_id,
+          include_removed,
+          page,
+          limit,
+        )?;
+        if unread_counts {
+          CommunityFollowerView::fill_unread_posts(conn, person_id, &mut communities)?;
+        }
+        fill_instance_status(conn, &mut communities, is_instance_blocked)?;
+        let total_count = if include_total {
+          Some(CommunityFollowerView::count_for_person(
+            conn,
+            person_id,
+            include_removed,
+          )?)
+        } else {
+          None
+        };
+        Ok((communities, has_more, total_count))
       })
-      .await??;
+      .await?
+      {
+        Ok(res) => res,
+        _ => return Err(ApiError::new(ApiErrorCode::SystemErrLogin).into()),
+      };
 
-      for comment_view in &comments {
-        let comment_id = comment_view.comment.id;
-        blocking(context.pool(), move |conn: &'_ _| {
-          Comment::update_removed(conn, comment_id, true)
-        })
-        .await??;
-      }
-    }
+    // Return the jwt
+    Ok(GetFollowedCommunitiesResponse {
+      communities,
+      total_count,
+      has_more,
+    })
+  }
+}
 
-    // Mod tables
-    // TODO eventually do correct expires
-    let expires = match data.expires {
-      Some(time) => Some(naive_from_unix(time)),
-      None => None,
+#[async_trait::async_trait(?Send)]
+impl Perform for BanFromCommunity {
+  type Response = BanFromCommunityResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    websocket_id: Option<ConnectionId>,
+  ) -> Result<BanFromCommunityResponse, LemmyError> {
+    let data: &BanFromCommunity = &self;
+    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+
+    let community_id = data.community_id;
+    let banned_person_id = data.person_id;
+
+    // Verify that only mods or admins can ban
+    is_mod_or_admin(context.pool(), local_user_view.person.id, community_id).await?;
+
+    let community_user_ban_form = CommunityPersonBanForm {
+      community_id: data.community_id,
+      person_id: data.person_id,
     };
 
+    let expires = parse_expires(data.expires, data.expires_rfc3339.as_deref())?;
+
     let form = ModBanFromCommunityForm {
       mod_person_id: local_user_view.person.id,
       other_person_id: data.person_id,
@@ -667,10 +1231,76 @@ impl Perform for BanFromCommunity {
       banned: Some(data.ban),
       expires,
     };
-    blocking(context.pool(), move |conn| {
-      ModBanFromCommunity::create(conn, &form)
+
+    // The ban, the removal of their data and the mod log entry are written in one transaction, so
+    // that a failure halfway through doesn't leave their content partially removed.
+    let ban = data.ban;
+    let remove_data = data.remove_data;
+    blocking_transaction(context.pool(), move |conn| {
+      let was_banned = CommunityPersonBanView::get(conn, banned_person_id, community_id).is_ok();
+      if ban {
+        CommunityPersonBan::ban(conn, &community_user_ban_form)
+          .map_err(|_| ApiError::new(ApiErrorCode::CommunityUserAlreadyBanned))?;
+
+        // Also unsubscribe them from the community, if they are subscribed
+        let community_follower_form = CommunityFollowerForm {
+          community_id,
+          person_id: banned_person_id,
+          pending: false,
+        };
+        CommunityFollower::unfollow(conn, &community_follower_form).ok();
+      } else {
+        CommunityPersonBan::unban(conn, &community_user_ban_form)
+          .map_err(|_| ApiError::new(ApiErrorCode::CommunityUserAlreadyBanned))?;
+      }
+
+      // Remove/Restore their data if that's desired
+      if remove_data {
+        // Posts
+        Post::update_removed_for_creator(conn, banned_person_id, Some(community_id), true)?;
+
+        // Comments
+        // TODO Diesel doesn't allow updates with joins, so this has to be a loop
+        let comments = CommentQueryBuilder::create(conn)
+          .creator_id(banned_person_id)
+          .community_id(community_id)
+          .limit(std::i64::MAX)
+          .list()?;
+
+        for comment_view in &comments {
+          Comment::update_removed(conn, comment_view.comment.id, true)?;
+        }
+      }
+
+      // Mod tables, unless the person already was in the requested state
+      if was_banned != ban {
+        ModBanFromCommunity::create(conn, &form)?;
+      }
+
+      Ok(())
+    })
+    .await?;
+
+    // Let the instance of the banned person, and all other followers know about the ban
+    let community = blocking(context.pool(), move |conn: &'_ _| {
+      Community::read(conn, community_id)
     })
     .await??;
+    if community.local {
+      let banned_person = blocking(context.pool(), move |conn: &'_ _| {
+        Person::read(conn, banned_person_id)
+      })
+      .await??;
+      if data.ban {
+        community
+          .send_block_user(&banned_person, data.remove_data, context)
+          .await?;
+      } else {
+        community
+          .send_undo_block_user(&banned_person, context)
+          .await?;
+      }
+    }
 
     let person_id = data.person_id;
     let person_view = blocking(context.pool(), move |conn| {
@@ -686,6 +1316,7 @@ impl Perform for BanFromCommunity {
     context.chat_server().do_send(SendCommunityRoomMessage {
       op: UserOperation::BanFromCommunity,
       response: res.clone(),
+      person_responses: HashMap::new(),
       community_id,
       websocket_id,
     });
@@ -716,29 +1347,38 @@ impl Perform for AddModToCommunity {
     // Verify that only mods or admins can add mod
     is_mod_or_admin(context.pool(), local_user_view.person.id, community_id).await?;
 
+    let person_id = data.person_id;
+    let was_mod = blocking(context.pool(), move |conn| {
+      CommunityModerator::get_person_moderated_communities(conn, person_id)
+    })
+    .await??
+    .contains(&community_id);
+
     if data.added {
       let join = move |conn: &'_ _| CommunityModerator::join(conn, &community_moderator_form);
       if blocking(context.pool(), join).await?.is_err() {
-        return Err(ApiError::err("community_moderator_already_exists").into());
+        return Err(ApiError::new(ApiErrorCode::CommunityModeratorAlreadyExists).into());
       }
     } else {
       let leave = move |conn: &'_ _| CommunityModerator::leave(conn, &community_moderator_form);
       if blocking(context.pool(), leave).await?.is_err() {
-        return Err(ApiError::err("community_moderator_already_exists").into());
+        return Err(ApiError::new(ApiErrorCode::CommunityModeratorAlreadyExists).into());
       }
     }
 
-    // Mod tables
-    let form = ModAddCommunityForm {
-      mod_person_id: local_user_view.person.id,
-      other_person_id: data.person_id,
-      community_id: data.community_id,
-      removed: Some(!data.added),
-    };
-    blocking(context.pool(), move |conn| {
-      ModAddCommunity::create(conn, &form)
-    })
-    .await??;
+    // Mod tables, unless the person already was in the requested state
+    if was_mod != data.added {
+      let form = ModAddCommunityForm {
+        mod_person_id: local_user_view.person.id,
+        other_person_id: data.person_id,
+        community_id: data.community_id,
+        removed: Some(!data.added),
+      };
+      blocking(context.pool(), move |conn| {
+        ModAddCommunity::create(conn, &form)
+      })
+      .await??;
+    }
 
     let community_id = data.community_id;
     let moderators = blocking(context.pool(), move |conn| {
@@ -751,6 +1391,7 @@ impl Perform for AddModToCommunity {
     context.chat_server().do_send(SendCommunityRoomMessage {
       op: UserOperation::AddModToCommunity,
       response: res.clone(),
+      person_responses: HashMap::new(),
       community_id,
       websocket_id,
     });
@@ -799,104 +1440,990 @@ impl Perform for TransferCommunity {
         .map(|a| a.person.id)
         .any(|x| x == local_user_view.person.id)
     {
-      return Err(ApiError::err("not_an_admin").into());
+      return Err(ApiError::new(ApiErrorCode::NotAnAdmin).into());
     }
 
+    // Update the creator and make them the head moderator. This runs in one transaction so a
+    // failure leaves the community as it was.
     let community_id = data.community_id;
     let new_creator = data.person_id;
-    let update = move |conn: &'_ _| Community::update_creator(conn, community_id, new_creator);
-    if blocking(context.pool(), update).await?.is_err() {
-      return Err(ApiError::err("couldnt_update_community").into());
-    };
+    let mod_person_id = local_user_view.person.id;
+    blocking_transaction(context.pool(), move |conn| {
+      Community::update_creator(conn, community_id, new_creator)
+        .map_err(|_| ApiError::new(ApiErrorCode::CouldntUpdateCommunity))?;
+
+      CommunityModerator::make_head_moderator(conn, community_id, new_creator).map_err(|_| {
+        ApiError::new(ApiErrorCode::NotAModerator).with_detail("person_id", new_creator.0)
+      })?;
+
+      // Mod tables
+      let form = ModAddCommunityForm {
+        mod_person_id,
+        other_person_id: new_creator,
+        community_id,
+        removed: Some(false),
+      };
+      ModAddCommunity::create(conn, &form)?;
 
-    // You also have to re-do the community_moderator table, reordering it.
-    let community_id = data.community_id;
-    let mut community_mods = blocking(context.pool(), move |conn| {
-      CommunityModeratorView::for_community(conn, community_id)
+      Ok(())
+    })
+    .await?;
+
+    let person_id = local_user_view.person.id;
+    let (community_view, moderators) =
+      read_community_and_moderators(context.pool(), Some(community_id), None, Some(person_id))
+        .await?;
+    let tags = blocking(context.pool(), move |conn| {
+      CommunityTag::list_for_community(conn, community_id)
     })
     .await??;
-    let creator_index = community_mods
-      .iter()
-      .position(|r| r.moderator.id == data.person_id)
-      .context(location_info!())?;
-    let creator_person = community_mods.remove(creator_index);
-    community_mods.insert(0, creator_person);
 
-    let community_id = data.community_id;
-    blocking(context.pool(), move |conn| {
-      CommunityModerator::delete_for_community(conn, community_id)
+    let discussion_languages = blocking(context.pool(), move |conn| {
+      Language::read_community_languages(conn, community_id)
     })
     .await??;
 
-    // TODO: this should probably be a bulk operation
-    for cmod in &community_mods {
-      let community_moderator_form = CommunityModeratorForm {
-        community_id: cmod.community.id,
-        person_id: cmod.moderator.id,
-      };
+    // Return the jwt
+    Ok(GetCommunityResponse {
+      community_view,
+      moderators,
+      online: 0,
+      tags,
+      discussion_languages,
+    })
+  }
+}
 
-      let join = move |conn: &'_ _| CommunityModerator::join(conn, &community_moderator_form);
-      if blocking(context.pool(), join).await?.is_err() {
-        return Err(ApiError::err("community_moderator_already_exists").into());
+#[async_trait::async_trait(?Send)]
+impl Perform for CreateCommunityTag {
+  type Response = CommunityTagResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<CommunityTagResponse, LemmyError> {
+    let data: &CreateCommunityTag = &self;
+    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+    is_mod_or_admin(context.pool(), local_user_view.person.id, data.community_id).await?;
+
+    if !is_valid_post_tag_name(&data.name) {
+      return Err(
+        ApiError::new(ApiErrorCode::InvalidPostTagName)
+          .with_field("name")
+          .into(),
+      );
+    }
+    let exempt = is_exempt_from_slur_filter(data.community_id, context.pool()).await?;
+    check_slurs(
+      &data.name,
+      &context.slur_filter().regex_for_community(exempt),
+    )?;
+
+    let color = data.color.to_owned().filter(|c| !c.is_empty());
+    if let Some(color) = &color {
+      if !is_valid_theme_color(color) {
+        return Err(
+          ApiError::new(ApiErrorCode::InvalidThemeColor)
+            .with_field("color")
+            .into(),
+        );
       }
     }
 
-    // Mod tables
-    let form = ModAddCommunityForm {
-      mod_person_id: local_user_view.person.id,
-      other_person_id: data.person_id,
+    let form = CommunityTagForm {
       community_id: data.community_id,
-      removed: Some(false),
+      name: data.name.to_owned(),
+      color,
     };
+    let tag = blocking(context.pool(), move |conn| {
+      CommunityTag::create(conn, &form)
+    })
+    .await?
+    .map_err(|_| ApiError::new(ApiErrorCode::PostTagAlreadyExists).with_field("name"))?;
+
+    Ok(CommunityTagResponse { tag })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for DeleteCommunityTag {
+  type Response = CommunityTagResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<CommunityTagResponse, LemmyError> {
+    let data: &DeleteCommunityTag = &self;
+    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+
+    let tag_id = data.tag_id;
+    let tag = blocking(context.pool(), move |conn| CommunityTag::read(conn, tag_id))
+      .await?
+      .map_err(|_| ApiError::new(ApiErrorCode::InvalidPostTag).with_detail("tag_id", tag_id))?;
+    is_mod_or_admin(context.pool(), local_user_view.person.id, tag.community_id).await?;
+
+    // Also removes the tag from all posts
     blocking(context.pool(), move |conn| {
-      ModAddCommunity::create(conn, &form)
+      CommunityTag::delete(conn, tag_id)
+    })
+    .await??;
+
+    Ok(CommunityTagResponse { tag })
+  }
+}
+
+/// How many inbound and outbound entries GetCommunityFederationStatus returns
+const FEDERATION_STATUS_LIMIT: i64 = 50;
+
+#[async_trait::async_trait(?Send)]
+impl Perform for GetCommunityFederationStatus {
+  type Response = GetCommunityFederationStatusResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<GetCommunityFederationStatusResponse, LemmyError> {
+    let data: &GetCommunityFederationStatus = &self;
+    let local_user_view =
+      get_local_user_view_from_jwt_read_only(&data.auth, context.pool()).await?;
+    is_admin(&local_user_view)?;
+
+    let community_id = data.community_id;
+    let inbound = blocking_read(context.read_pool(), move |conn| {
+      CommunityFederationLog::list_for_community(conn, community_id, true, FEDERATION_STATUS_LIMIT)
+    })
+    .await??;
+    let outbound = blocking_read(context.read_pool(), move |conn| {
+      CommunityFederationLog::list_for_community(conn, community_id, false, FEDERATION_STATUS_LIMIT)
     })
     .await??;
 
+    Ok(GetCommunityFederationStatusResponse { inbound, outbound })
+  }
+}
+
+/// How long after a refresh a remote community can be refreshed again
+const COMMUNITY_REFRESH_INTERVAL_MINUTES: i64 = 10;
+
+#[async_trait::async_trait(?Send)]
+impl Perform for RefreshRemoteCommunity {
+  type Response = RefreshRemoteCommunityResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<RefreshRemoteCommunityResponse, LemmyError> {
+    let data: &RefreshRemoteCommunity = &self;
+    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+
     let community_id = data.community_id;
-    let person_id = local_user_view.person.id;
-    let community_view = match blocking(context.pool(), move |conn| {
-      CommunityView::read(conn, community_id, Some(person_id))
+    let old_community = blocking(context.pool(), move |conn| {
+      Community::read(conn, community_id)
     })
     .await?
-    {
-      Ok(community) => community,
-      Err(_e) => return Err(ApiError::err("couldnt_find_community").into()),
-    };
+    .map_err(|_| ApiError::new(ApiErrorCode::CouldntFindCommunity).with_field("community_id"))?;
+    if old_community.local {
+      return Err(ApiError::err("community_is_local").into());
+    }
+    is_mod_or_admin(context.pool(), local_user_view.person.id, community_id).await?;
+
+    // The regular refetch sets last_refreshed_at as well, so manual refreshes are limited by their
+    // own timestamp
+    let refreshed_before = naive_now() - Duration::minutes(COMMUNITY_REFRESH_INTERVAL_MINUTES);
+    let marked = blocking(context.pool(), move |conn| {
+      Community::mark_manually_refreshed(conn, community_id, refreshed_before)
+    })
+    .await??;
+    if !marked {
+      return Err(ApiError::err("community_refreshed_recently").into());
+    }
+
+    let refresh = refresh_remote_community(&old_community, context).await?;
+    let changed_fields = changed_community_fields(&old_community, &refresh.community);
+
+    let person_id = local_user_view.person.id;
+    let (community_view, moderators) = blocking(context.pool(), move |conn| {
+      let community_view = CommunityView::read(conn, community_id, Some(person_id))?;
+      let moderators = CommunityModeratorView::for_community(conn, community_id)?;
+      Ok((community_view, moderators)) as Result<_, LemmyError>
+    })
+    .await??;
+
+    Ok(RefreshRemoteCommunityResponse {
+      community_view,
+      moderators,
+      changed_fields,
+      added_moderators: refresh.added_moderators,
+      removed_moderators: refresh.removed_moderators,
+    })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for ExportCommunity {
+  type Response = ExportCommunityResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<ExportCommunityResponse, LemmyError> {
+    let data: &ExportCommunity = &self;
+    let local_user_view =
+      get_local_user_view_from_jwt_read_only(&data.auth, context.pool()).await?;
+    is_mod_or_admin(context.pool(), local_user_view.person.id, data.community_id).await?;
 
     let community_id = data.community_id;
-    let moderators = match blocking(context.pool(), move |conn| {
-      CommunityModeratorView::for_community(conn, community_id)
+    let (community, tags, moderators) = blocking(context.pool(), move |conn| {
+      let community = Community::read(conn, community_id)?;
+      let tags = CommunityTag::list_for_community(conn, community_id)?;
+      let moderators = CommunityModeratorView::for_community(conn, community_id)?;
+      Ok((community, tags, moderators)) as Result<_, LemmyError>
     })
-    .await?
-    {
-      Ok(moderators) => moderators,
-      Err(_e) => return Err(ApiError::err("couldnt_find_community").into()),
+    .await??;
+
+    let export = CommunityExport {
+      name: community.name,
+      title: community.title,
+      description: community.description,
+      short_description: community.short_description,
+      icon: community.icon.map(|i| i.into()),
+      banner: community.banner.map(|b| b.into()),
+      theme_color: community.theme_color,
+      nsfw: community.nsfw,
+      downvotes_disabled: community.downvotes_disabled,
+      requires_post_approval: community.requires_post_approval,
+      default_comment_sort: SortType::from_i16(community.default_comment_sort),
+      require_post_tag: community.require_post_tag,
+      manual_follow_approval: community.manual_follow_approval,
+      tags: tags
+        .into_iter()
+        .map(|t| CommunityExportTag {
+          name: t.name,
+          color: t.color,
+        })
+        .collect(),
+      moderators: moderators
+        .into_iter()
+        .map(|m| m.moderator.actor_id.into())
+        .collect(),
     };
+    let export_json = serde_json::to_string(&export)?;
 
-    // Return the jwt
-    Ok(GetCommunityResponse {
+    Ok(ExportCommunityResponse { export_json })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for ImportCommunity {
+  type Response = ImportCommunityResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<ImportCommunityResponse, LemmyError> {
+    let data: &ImportCommunity = &self;
+    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+    is_admin(&local_user_view)?;
+
+    let export: CommunityExport = serde_json::from_str(&data.export_json)
+      .map_err(|_| ApiError::err("invalid_community_export").with_field("export_json"))?;
+
+    // The export comes from another instance, so it is checked like a new community
+    let slur_regex = context.slur_filter().regex();
+    check_slurs(&export.name, &slur_regex)?;
+    check_slurs(&export.title, &slur_regex)?;
+    check_slurs_opt(&export.description, &slur_regex)?;
+    check_slurs_opt(&export.short_description, &slur_regex)?;
+    let theme_color = Some(export.theme_color.to_owned());
+    let short_description = Some(export.short_description.to_owned());
+    check_community_appearance(&theme_color, &short_description)?;
+    for tag in &export.tags {
+      if !is_valid_post_tag_name(&tag.name) {
+        return Err(
+          ApiError::new(ApiErrorCode::InvalidPostTagName)
+            .with_field("tags")
+            .into(),
+        );
+      }
+      check_slurs(&tag.name, &slur_regex)?;
+      if let Some(color) = &tag.color {
+        if !is_valid_theme_color(color) {
+          return Err(
+            ApiError::new(ApiErrorCode::InvalidThemeColor)
+              .with_field("tags")
+              .into(),
+          );
+        }
+      }
+    }
+
+    if !is_valid_community_name(&export.name) {
+      return Err(
+        ApiError::new(ApiErrorCode::InvalidCommunityName)
+          .with_field("name")
+          .into(),
+      );
+    }
+
+    let community_actor_id = generate_apub_endpoint(EndpointType::Community, &export.name)?;
+    let actor_id_cloned = community_actor_id.to_owned();
+    let community_dupe = blocking(context.pool(), move |conn| {
+      Community::read_from_apub_id(conn, &actor_id_cloned)
+    })
+    .await?;
+    if let Ok(community_dupe) = community_dupe {
+      return Err(
+        ApiError::new(ApiErrorCode::CommunityAlreadyExists)
+          .with_field("name")
+          .with_detail("community_id", community_dupe.id.0)
+          .into(),
+      );
+    }
+
+    // Images of the old instance would be rejected, they have to be uploaded again
+    let (icon, banner) = if site_requires_local_images(context.pool()).await? {
+      (None, None)
+    } else {
+      (
+        export.icon.to_owned().map(|i| i.into()),
+        export.banner.to_owned().map(|b| b.into()),
+      )
+    };
+
+    // Moderators which can't be fetched are left out, and the admin takes over if none is left
+    let mut moderator_ids: Vec<PersonId> = Vec::new();
+    for actor_id in &export.moderators {
+      match resolve_person(actor_id, context).await {
+        Ok(person) if !moderator_ids.contains(&person.id) => moderator_ids.push(person.id),
+        Ok(_) => {}
+        Err(e) => error!(
+          "Couldn't fetch moderator {} of imported community: {}",
+          actor_id, e
+        ),
+      }
+    }
+    if moderator_ids.is_empty() {
+      moderator_ids.push(local_user_view.person.id);
+    }
+
+    let keypair = generate_actor_keypair()?;
+    let community_form = CommunityForm {
+      name: export.name.to_owned(),
+      title: export.title.to_owned(),
+      description: export.description.to_owned(),
+      icon: Some(icon),
+      banner: Some(banner),
+      creator_id: local_user_view.person.id,
+      removed: None,
+      deleted: None,
+      nsfw: export.nsfw,
+      updated: None,
+      actor_id: Some(community_actor_id.to_owned()),
+      local: true,
+      private_key: Some(keypair.private_key),
+      public_key: Some(keypair.public_key),
+      last_refreshed_at: None,
+      published: None,
+      followers_url: Some(generate_followers_url(&community_actor_id)?),
+      inbox_url: Some(generate_inbox_url(&community_actor_id)?),
+      shared_inbox_url: Some(Some(generate_shared_inbox_url(&community_actor_id)?)),
+      downvotes_disabled: Some(export.downvotes_disabled),
+      requires_post_approval: Some(export.requires_post_approval),
+      theme_color,
+      short_description,
+      default_comment_sort: Some(export.default_comment_sort as i16),
+      require_post_tag: Some(export.require_post_tag),
+      manual_follow_approval: Some(export.manual_follow_approval),
+    };
+
+    let tags = export.tags;
+    let inserted_community = blocking_transaction(context.pool(), move |conn| {
+      let inserted_community = Community::create(conn, &community_form)
+        .map_err(|_| ApiError::new(ApiErrorCode::CommunityAlreadyExists).with_field("name"))?;
+
+      // Joined in the order of the export, which keeps the moderator ranks
+      for person_id in moderator_ids {
+        let community_moderator_form = CommunityModeratorForm {
+          community_id: inserted_community.id,
+          person_id,
+        };
+        CommunityModerator::join(conn, &community_moderator_form)
+          .map_err(|_| ApiError::new(ApiErrorCode::CommunityModeratorAlreadyExists))?;
+      }
+
+      for tag in tags {
+        let tag_form = CommunityTagForm {
+          community_id: inserted_community.id,
+          name: tag.name,
+          color: tag.color,
+        };
+        CommunityTag::create(conn, &tag_form)
+          .map_err(|_| ApiError::new(ApiErrorCode::PostTagAlreadyExists).with_field("tags"))?;
+      }
+
+      Ok(inserted_community)
+    })
+    .await?;
+
+    let community_id = inserted_community.id;
+    let person_id = local_user_view.person.id;
+    let (community_view, moderators, tags) = blocking(context.pool(), move |conn| {
+      let community_view = CommunityView::read(conn, community_id, Some(person_id))?;
+      let moderators = CommunityModeratorView::for_community(conn, community_id)?;
+      let tags = CommunityTag::list_for_community(conn, community_id)?;
+      Ok((community_view, moderators, tags)) as Result<_, LemmyError>
+    })
+    .await??;
+
+    Ok(ImportCommunityResponse {
       community_view,
       moderators,
-      online: 0,
+      tags,
     })
   }
 }
 
-fn send_community_websocket(
-  res: &CommunityResponse,
-  context: &Data<LemmyContext>,
-  websocket_id: Option<ConnectionId>,
-  op: UserOperation,
-) {
-  // Strip out the person id and subscribed when sending to others
-  let mut res_sent = res.clone();
-  res_sent.community_view.subscribed = false;
-
-  context.chat_server().do_send(SendCommunityRoomMessage {
-    op,
-    response: res_sent,
-    community_id: res.community_view.community.id,
-    websocket_id,
-  });
+/// Names of the fields which differ between the two versions of a community
+fn changed_community_fields(old: &Community, new: &Community) -> Vec<String> {
+  let fields = [
+    ("name", old.name != new.name),
+    ("title", old.title != new.title),
+    ("description", old.description != new.description),
+    ("icon", old.icon != new.icon),
+    ("banner", old.banner != new.banner),
+    ("nsfw", old.nsfw != new.nsfw),
+    (
+      "downvotes_disabled",
+      old.downvotes_disabled != new.downvotes_disabled,
+    ),
+    ("theme_color", old.theme_color != new.theme_color),
+    (
+      "short_description",
+      old.short_description != new.short_description,
+    ),
+    (
+      "requires_post_approval",
+      old.requires_post_approval != new.requires_post_approval,
+    ),
+    (
+      "default_comment_sort",
+      old.default_comment_sort != new.default_comment_sort,
+    ),
+    (
+      "require_post_tag",
+      old.require_post_tag != new.require_post_tag,
+    ),
+    (
+      "manual_follow_approval",
+      old.manual_follow_approval != new.manual_follow_approval,
+    ),
+    ("public_key", old.public_key != new.public_key),
+    ("inbox_url", old.inbox_url != new.inbox_url),
+    (
+      "shared_inbox_url",
+      old.shared_inbox_url != new.shared_inbox_url,
+    ),
+  ];
+  fields
+    .iter()
+    .filter(|(_, changed)| *changed)
+    .map(|(name, _)| name.to_string())
+    .collect()
+}
+
+/// Checks the theme color and the short description of a created or edited community, if they are
+/// set
+fn check_community_appearance(
+  theme_color: &Option<Option<String>>,
+  short_description: &Option<Option<String>>,
+) -> Result<(), LemmyError> {
+  if let Some(Some(theme_color)) = theme_color {
+    if !is_valid_theme_color(theme_color) {
+      return Err(
+        ApiError::new(ApiErrorCode::InvalidThemeColor)
+          .with_field("theme_color")
+          .into(),
+      );
+    }
+  }
+  if let Some(Some(short_description)) = short_description {
+    if short_description.chars().count() > MAX_SHORT_DESCRIPTION_LENGTH {
+      return Err(
+        ApiError::new(ApiErrorCode::ShortDescriptionTooLong)
+          .with_field("short_description")
+          .into(),
+      );
+    }
+  }
+  Ok(())
+}
+
+/// Reads a community together with its moderators. If no id is given, the community is first
+/// looked up by name. The view and the moderators are read on a single pooled connection.
+async fn read_community_and_moderators(
+  pool: &DbPool,
+  community_id: Option<CommunityId>,
+  name: Option<String>,
+  person_id: Option<PersonId>,
+) -> Result<(CommunityView, Vec<CommunityModeratorView>), LemmyError> {
+  let community_id = match community_id {
+    Some(id) => id,
+    None => {
+      let name = name.unwrap_or_else(|| "main".to_string());
+      match blocking(pool, move |conn| Community::read_from_name(conn, &name)).await? {
+        Ok(community) => community.id,
+        Err(_e) => return Err(ApiError::new(ApiErrorCode::CouldntFindCommunity).into()),
+      }
+    }
+  };
+
+  blocking(pool, move |conn| -> Result<_, LemmyError> {
+    let mut community_view = CommunityView::read(conn, community_id, person_id).map_err(|_| {
+      ApiError::new(ApiErrorCode::CouldntFindCommunity).with_detail("community_id", community_id.0)
+    })?;
+    fill_instance_status(
+      conn,
+      std::slice::from_mut(&mut community_view),
+      is_instance_blocked,
+    )?;
+    let moderators = CommunityModeratorView::for_community(conn, community_id)
+      .map_err(|_| ApiError::new(ApiErrorCode::CouldntFindCommunity))?;
+    Ok((community_view, moderators))
+  })
+  .await?
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    community::read_community_and_moderators,
+    test::{create_test_community, create_test_user, error_code, test_context},
+    Perform,
+  };
+  use diesel::{
+    r2d2::{event::CheckoutEvent, ConnectionManager, HandleEvent, Pool},
+    ExpressionMethods,
+    PgConnection,
+    QueryDsl,
+    RunQueryDsl,
+  };
+  use lemmy_api_structs::community::{
+    ApproveFollowRequest,
+    CommunityExport,
+    ExportCommunity,
+    FollowCommunity,
+    GetFollowedCommunities,
+    ImportCommunity,
+    ListFollowRequests,
+    MuteCommunity,
+  };
+  use lemmy_db_queries::{
+    establish_unpooled_connection,
+    get_database_url_from_env,
+    source::community::CommunityFollower_,
+    Crud,
+    Followable,
+    Joinable,
+  };
+  use lemmy_db_schema::{
+    naive_now,
+    schema::community,
+    source::{
+      community::*,
+      community_tag::{CommunityTag, CommunityTagForm},
+      person::*,
+    },
+    CommunityId,
+    PersonId,
+  };
+  use lemmy_utils::ApiErrorCode;
+  use serial_test::serial;
+  use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+  };
+
+  #[derive(Debug)]
+  struct CheckoutCounter(Arc<AtomicUsize>);
+
+  impl HandleEvent for CheckoutCounter {
+    fn handle_checkout(&self, _event: CheckoutEvent) {
+      self.0.fetch_add(1, Ordering::SeqCst);
+    }
+  }
+
+  #[test]
+  #[serial]
+  fn test_get_community_pool_checkouts() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "checkout_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "checkout_community".into(),
+      creator_id: inserted_person.id,
+      title: "nada".to_owned(),
+      description: None,
+      nsfw: false,
+      removed: None,
+      deleted: None,
+      updated: None,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let community_moderator_form = CommunityModeratorForm {
+      community_id: inserted_community.id,
+      person_id: inserted_person.id,
+    };
+    CommunityModerator::join(&conn, &community_moderator_form).unwrap();
+
+    let checkouts = Arc::new(AtomicUsize::new(0));
+    let manager = ConnectionManager::<PgConnection>::new(get_database_url_from_env().unwrap());
+    let pool = Pool::builder()
+      .max_size(1)
+      .event_handler(Box::new(CheckoutCounter(checkouts.clone())))
+      .build(manager)
+      .unwrap();
+
+    let mut system = actix_rt::System::new("test_get_community_pool_checkouts");
+
+    // By name: one checkout for the id lookup, one for the view and moderators
+    checkouts.store(0, Ordering::SeqCst);
+    let (by_name, moderators) = system
+      .block_on(read_community_and_moderators(
+        &pool,
+        None,
+        Some("checkout_community".into()),
+        None,
+      ))
+      .unwrap();
+    let by_name_checkouts = checkouts.load(Ordering::SeqCst);
+
+    // By id: only the combined read
+    checkouts.store(0, Ordering::SeqCst);
+    let (by_id, _) = system
+      .block_on(read_community_and_moderators(
+        &pool,
+        Some(inserted_community.id),
+        None,
+        None,
+      ))
+      .unwrap();
+    let by_id_checkouts = checkouts.load(Ordering::SeqCst);
+
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(2, by_name_checkouts);
+    assert_eq!(1, by_id_checkouts);
+    assert_eq!(inserted_community.id, by_name.community.id);
+    assert_eq!(inserted_community.id, by_id.community.id);
+    assert_eq!(1, moderators.len());
+    assert_eq!(inserted_person.id, moderators[0].moderator.id);
+  }
+
+  #[actix_rt::test]
+  #[serial]
+  async fn test_export_and_import_community() {
+    let context = test_context();
+    let conn = context.pool().get().unwrap();
+    let (_, admin, jwt) = create_test_user(&conn, "export_admin", true);
+    let community = create_test_community(&conn, "export_community", &admin);
+    let community_form = CommunityForm {
+      name: community.name.to_owned(),
+      title: "Exported".into(),
+      description: Some("the sidebar".into()),
+      creator_id: admin.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: true,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: Some(true),
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: Some(true),
+      manual_follow_approval: None,
+    };
+    let community = Community::update(&conn, community.id, &community_form).unwrap();
+    CommunityModerator::join(
+      &conn,
+      &CommunityModeratorForm {
+        community_id: community.id,
+        person_id: admin.id,
+      },
+    )
+    .unwrap();
+    CommunityTag::create(
+      &conn,
+      &CommunityTagForm {
+        community_id: community.id,
+        name: "news".into(),
+        color: None,
+      },
+    )
+    .unwrap();
+
+    let exported = ExportCommunity {
+      community_id: community.id,
+      auth: jwt.to_owned(),
+    }
+    .perform(&context, None)
+    .await
+    .unwrap();
+    // Imported under another name, as the original community is still there
+    let mut export: CommunityExport = serde_json::from_str(&exported.export_json).unwrap();
+    export.name = "import_community".into();
+    let import = ImportCommunity {
+      export_json: serde_json::to_string(&export).unwrap(),
+      auth: jwt.to_owned(),
+    };
+    let imported = import.perform(&context, None).await.unwrap();
+    let imported_again = import.perform(&context, None).await;
+
+    let imported_id = imported.community_view.community.id;
+    Community::delete(&conn, imported_id).unwrap();
+    Community::delete(&conn, community.id).unwrap();
+    Person::delete(&conn, admin.id).unwrap();
+
+    let imported_community = imported.community_view.community;
+    assert_eq!("import_community", imported_community.name);
+    assert_eq!(community.title, imported_community.title);
+    assert_eq!(community.description, imported_community.description);
+    assert!(imported_community.local);
+    assert!(imported_community.nsfw);
+    assert!(imported_community.downvotes_disabled);
+    assert!(imported_community.require_post_tag);
+    assert_eq!(1, imported.moderators.len());
+    assert_eq!(admin.id, imported.moderators[0].moderator.id);
+    assert_eq!(
+      vec!["news".to_string()],
+      imported
+        .tags
+        .iter()
+        .map(|t| t.name.to_owned())
+        .collect::<Vec<_>>()
+    );
+    assert_eq!(
+      Some(ApiErrorCode::CommunityAlreadyExists),
+      error_code(imported_again)
+    );
+  }
+
+  #[actix_rt::test]
+  #[serial]
+  async fn test_mute_community() {
+    let context = test_context();
+    let conn = context.pool().get().unwrap();
+    let (_, person, jwt) = create_test_user(&conn, "mute_person", false);
+    let followed = create_test_community(&conn, "mute_followed", &person);
+    let not_followed = create_test_community(&conn, "mute_not_followed", &person);
+    CommunityFollower::follow(
+      &conn,
+      &CommunityFollowerForm {
+        community_id: followed.id,
+        person_id: person.id,
+        pending: false,
+      },
+    )
+    .unwrap();
+
+    let mute = |community_id: CommunityId, mute_until: Option<i64>| MuteCommunity {
+      community_id,
+      mute_until,
+      auth: jwt.to_owned(),
+    };
+    let in_a_week = naive_now().timestamp() + 7 * 24 * 60 * 60;
+    let muted = mute(followed.id, Some(in_a_week))
+      .perform(&context, None)
+      .await
+      .unwrap();
+    let followed_communities = GetFollowedCommunities {
+      page: None,
+      limit: None,
+      unread_counts: None,
+      include_removed: None,
+      include_total: None,
+      auth: jwt.to_owned(),
+    }
+    .perform(&context, None)
+    .await
+    .unwrap();
+    let in_the_past = mute(followed.id, Some(naive_now().timestamp() - 60))
+      .perform(&context, None)
+      .await;
+    // Out of the range of timestamps, this must not panic
+    let out_of_range = mute(followed.id, Some(i64::MAX))
+      .perform(&context, None)
+      .await;
+    let not_following = mute(not_followed.id, Some(in_a_week))
+      .perform(&context, None)
+      .await;
+    let unmuted = mute(followed.id, None)
+      .perform(&context, None)
+      .await
+      .unwrap();
+
+    Community::delete(&conn, followed.id).unwrap();
+    Community::delete(&conn, not_followed.id).unwrap();
+    Person::delete(&conn, person.id).unwrap();
+
+    assert_eq!(Some(in_a_week), muted.mute_until.map(|m| m.timestamp()));
+    assert_eq!(1, followed_communities.communities.len());
+    assert_eq!(
+      muted.mute_until,
+      followed_communities.communities[0].mute_until
+    );
+    assert_eq!(
+      Some(ApiErrorCode::InvalidExpiryInPast),
+      error_code(in_the_past)
+    );
+    assert_eq!(Some(ApiErrorCode::InvalidExpiry), error_code(out_of_range));
+    assert_eq!(
+      Some(ApiErrorCode::Other("not_following_community".into())),
+      error_code(not_following)
+    );
+    assert_eq!(None, unmuted.mute_until);
+  }
+
+  #[actix_rt::test]
+  #[serial]
+  async fn test_approve_follow_request() {
+    let context = test_context();
+    let conn = context.pool().get().unwrap();
+    let (_, moderator, mod_jwt) = create_test_user(&conn, "follow_request_mod", false);
+    let (_, approved_person, approved_jwt) =
+      create_test_user(&conn, "follow_request_approved", false);
+    let (_, rejected_person, rejected_jwt) =
+      create_test_user(&conn, "follow_request_rejected", false);
+    let inserted_community = create_test_community(&conn, "follow_requests", &moderator);
+    let community_id = inserted_community.id;
+    diesel::update(community::table.find(community_id))
+      .set(community::manual_follow_approval.eq(true))
+      .execute(&conn)
+      .unwrap();
+    CommunityModerator::join(
+      &conn,
+      &CommunityModeratorForm {
+        community_id,
+        person_id: moderator.id,
+      },
+    )
+    .unwrap();
+
+    let follow = |auth: &str| FollowCommunity {
+      community_id: Some(community_id),
+      community_name: None,
+      follow: true,
+      auth: auth.to_owned(),
+    };
+    let followed = follow(&approved_jwt).perform(&context, None).await.unwrap();
+    follow(&rejected_jwt).perform(&context, None).await.unwrap();
+    let requests = ListFollowRequests {
+      community_id,
+      auth: mod_jwt.to_owned(),
+    }
+    .perform(&context, None)
+    .await
+    .unwrap();
+
+    let approve = |person_id: PersonId, approve: bool, auth: &str| ApproveFollowRequest {
+      community_id,
+      person_id,
+      approve,
+      auth: auth.to_owned(),
+    };
+    let not_a_mod = approve(approved_person.id, true, &approved_jwt)
+      .perform(&context, None)
+      .await;
+    let approved = approve(approved_person.id, true, &mod_jwt)
+      .perform(&context, None)
+      .await
+      .unwrap();
+    let rejected = approve(rejected_person.id, false, &mod_jwt)
+      .perform(&context, None)
+      .await
+      .unwrap();
+    // The request was already answered
+    let approved_again = approve(approved_person.id, true, &mod_jwt)
+      .perform(&context, None)
+      .await;
+    let approved_follow =
+      CommunityFollower::read_follow(&conn, community_id, approved_person.id).unwrap();
+    let rejected_follow = CommunityFollower::read_follow(&conn, community_id, rejected_person.id);
+
+    Community::delete(&conn, community_id).unwrap();
+    Person::delete(&conn, moderator.id).unwrap();
+    Person::delete(&conn, approved_person.id).unwrap();
+    Person::delete(&conn, rejected_person.id).unwrap();
+
+    assert!(!followed.community_view.subscribed);
+    assert_eq!(2, requests.follow_requests.len());
+    assert_eq!(
+      Some(ApiErrorCode::Other("not_a_mod_or_admin".into())),
+      error_code(not_a_mod)
+    );
+    assert!(approved.approved);
+    assert!(!rejected.approved);
+    assert_eq!(
+      Some(ApiErrorCode::Other("follow_request_not_found".into())),
+      error_code(approved_again)
+    );
+    assert_eq!(Some(false), approved_follow.pending);
+    assert!(matches!(rejected_follow, Err(diesel::NotFound)));
+  }
 }
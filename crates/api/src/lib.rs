@@ -1,25 +1,45 @@
 use actix_web::{web, web::Data};
+use diesel::PgConnection;
 use lemmy_api_structs::{
   blocking,
   comment::*,
   community::*,
+  decode_jwt,
   person::*,
   post::*,
   site::*,
   websocket::*,
+  VoteTarget,
 };
+use lemmy_apub::ApubLikeableType;
 use lemmy_db_queries::{
   source::{
     community::{CommunityModerator_, Community_},
+    instance::Instance_,
+    person::{is_last_activity_outdated, Person_},
     site::Site_,
   },
+  ApiTokenScope,
   Crud,
   DbPool,
+  FederateVotesAs,
+  ListingType,
+  SortType,
 };
-use lemmy_db_schema::source::{
-  community::{Community, CommunityModerator},
-  post::Post,
-  site::Site,
+use lemmy_db_schema::{
+  source::{
+    comment::Comment,
+    community::{Community, CommunityModerator},
+    instance::Instance,
+    login_session::{LoginSession, LoginSessionForm},
+    person::Person,
+    post::Post,
+    site::Site,
+  },
+  CommunityId,
+  DbUrl,
+  PersonId,
+  PostId,
 };
 use lemmy_db_views::local_user_view::{LocalUserSettingsView, LocalUserView};
 use lemmy_db_views_actor::{
@@ -28,13 +48,19 @@ use lemmy_db_views_actor::{
 };
 use lemmy_utils::{
   claims::Claims,
+  debounce::{vote_activities, DebouncedVote, PendingVote, VoteActivity},
+  federation_mode::FederationMode,
   settings::structs::Settings,
+  utils::{check_image_is_local, MAX_PASSWORD_BYTES, MAX_PASSWORD_LENGTH, MIN_PASSWORD_LENGTH},
   ApiError,
+  ApiErrorCode,
   ConnectionId,
   LemmyError,
 };
 use lemmy_websocket::{serialize_websocket_message, LemmyContext, UserOperation};
+use log::error;
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
 use std::process::Command;
 use url::Url;
 
@@ -44,6 +70,8 @@ pub mod local_user;
 pub mod post;
 pub mod routes;
 pub mod site;
+#[cfg(test)]
+mod test;
 pub mod websocket;
 
 #[async_trait::async_trait(?Send)]
@@ -59,8 +87,8 @@ pub trait Perform {
 
 pub(crate) async fn is_mod_or_admin(
   pool: &DbPool,
-  person_id: i32,
-  community_id: i32,
+  person_id: PersonId,
+  community_id: CommunityId,
 ) -> Result<(), LemmyError> {
   let is_mod_or_admin = blocking(pool, move |conn| {
     CommunityView::is_mod_or_admin(conn, person_id, community_id)
@@ -72,6 +100,23 @@ pub(crate) async fn is_mod_or_admin(
   Ok(())
 }
 
+/// Whether the person can see removed and deleted content in the community, ie is a mod or admin
+pub(crate) async fn can_view_removed(
+  person_id: Option<PersonId>,
+  community_id: CommunityId,
+  pool: &DbPool,
+) -> Result<bool, LemmyError> {
+  match person_id {
+    Some(person_id) => Ok(
+      blocking(pool, move |conn| {
+        CommunityView::is_mod_or_admin(conn, person_id, community_id)
+      })
+      .await?,
+    ),
+    None => Ok(false),
+  }
+}
+
 // TODO this probably isn't necessary anymore
 // pub async fn is_admin(pool: &DbPool, person_id: i32) -> Result<(), LemmyError> {
 //   let user = blocking(pool, move |conn| LocalUser::read(conn, person_id)).await??;
@@ -88,37 +133,122 @@ pub fn is_admin(local_user_view: &LocalUserView) -> Result<(), LemmyError> {
   Ok(())
 }
 
-pub(crate) async fn get_post(post_id: i32, pool: &DbPool) -> Result<Post, LemmyError> {
+pub(crate) async fn get_post(post_id: PostId, pool: &DbPool) -> Result<Post, LemmyError> {
   match blocking(pool, move |conn| Post::read(conn, post_id)).await? {
     Ok(post) => Ok(post),
     Err(_e) => Err(ApiError::err("couldnt_find_post").into()),
   }
 }
 
-pub(crate) async fn get_local_user_view_from_jwt(
+/// Checks if an admin exempted the community from the slur filter
+pub(crate) async fn is_exempt_from_slur_filter(
+  community_id: CommunityId,
+  pool: &DbPool,
+) -> Result<bool, LemmyError> {
+  let community = blocking(pool, move |conn| Community::read(conn, community_id)).await??;
+  Ok(community.exempt_from_slur_filter)
+}
+
+/// The local user of a login or API token, and the scopes of the token if it is one
+pub(crate) async fn get_local_user_view_and_scopes_from_jwt(
   jwt: &str,
   pool: &DbPool,
-) -> Result<LocalUserView, LemmyError> {
-  let claims = match Claims::decode(&jwt) {
-    Ok(claims) => claims.claims,
-    Err(_e) => return Err(ApiError::err("not_logged_in").into()),
-  };
-  let local_user_id = claims.id;
+) -> Result<(LocalUserView, Option<Vec<ApiTokenScope>>), LemmyError> {
+  let (local_user_id, scopes) = decode_jwt(jwt, pool).await?;
   let local_user_view =
     blocking(pool, move |conn| LocalUserView::read(conn, local_user_id)).await??;
   // Check for a site ban
   if local_user_view.person.banned {
     return Err(ApiError::err("site_ban").into());
   }
+  if is_last_activity_outdated(local_user_view.person.last_activity_at) {
+    // Not worth failing the request for
+    let person_id = local_user_view.person.id;
+    match blocking(pool, move |conn| Person::mark_active(conn, person_id)).await {
+      Ok(Ok(_)) => {}
+      Ok(Err(e)) => error!("Failed to mark person {} as active: {}", person_id, e),
+      Err(e) => error!("Failed to mark person {} as active: {}", person_id, e),
+    }
+  }
+  Ok((local_user_view, scopes))
+}
+
+/// The local user of a login or API token. API tokens need the `Write` scope, endpoints which
+/// don't change anything use `get_local_user_view_from_jwt_read_only` instead.
+pub(crate) async fn get_local_user_view_from_jwt(
+  jwt: &str,
+  pool: &DbPool,
+) -> Result<LocalUserView, LemmyError> {
+  get_local_user_view_from_jwt_with_scope(jwt, ApiTokenScope::Write, pool).await
+}
+
+/// Like `get_local_user_view_from_jwt`, for endpoints which only read. API tokens need the `Read`
+/// or the `Write` scope.
+pub(crate) async fn get_local_user_view_from_jwt_read_only(
+  jwt: &str,
+  pool: &DbPool,
+) -> Result<LocalUserView, LemmyError> {
+  get_local_user_view_from_jwt_with_scope(jwt, ApiTokenScope::Read, pool).await
+}
+
+/// The local user of a login or of an API token with the given scope
+async fn get_local_user_view_from_jwt_with_scope(
+  jwt: &str,
+  scope: ApiTokenScope,
+  pool: &DbPool,
+) -> Result<LocalUserView, LemmyError> {
+  let (local_user_view, scopes) = get_local_user_view_and_scopes_from_jwt(jwt, pool).await?;
+  let allowed = match scopes {
+    None => true,
+    // Whoever may write, may also read
+    Some(scopes) => scopes.contains(&scope) || scopes.contains(&ApiTokenScope::Write),
+  };
+  if !allowed {
+    return Err(ApiError::new(ApiErrorCode::InsufficientScope).into());
+  }
+  Ok(local_user_view)
+}
+
+/// Like `get_local_user_view_from_jwt`, but API tokens are rejected. For account actions like
+/// managing the API tokens themselves.
+pub(crate) async fn get_local_user_view_from_login_jwt(
+  jwt: &str,
+  pool: &DbPool,
+) -> Result<LocalUserView, LemmyError> {
+  let (local_user_view, scopes) = get_local_user_view_and_scopes_from_jwt(jwt, pool).await?;
+  if scopes.is_some() {
+    return Err(ApiError::new(ApiErrorCode::InsufficientScope).into());
+  }
   Ok(local_user_view)
 }
 
+/// Starts a new login session and returns the jwt for it
+pub(crate) async fn create_login_session(
+  local_user_id: i32,
+  client: &LoginClient,
+  pool: &DbPool,
+) -> Result<String, LemmyError> {
+  let form = LoginSessionForm {
+    local_user_id,
+    user_agent: client.user_agent.to_owned(),
+    ip: client.ip.to_owned(),
+  };
+  let session = blocking(pool, move |conn| LoginSession::create(conn, &form)).await??;
+  Ok(Claims::jwt(
+    local_user_id,
+    session.id,
+    Settings::get().hostname(),
+  )?)
+}
+
 pub(crate) async fn get_local_user_view_from_jwt_opt(
   jwt: &Option<String>,
   pool: &DbPool,
 ) -> Result<Option<LocalUserView>, LemmyError> {
   match jwt {
-    Some(jwt) => Ok(Some(get_local_user_view_from_jwt(jwt, pool).await?)),
+    Some(jwt) => Ok(Some(
+      get_local_user_view_from_jwt_read_only(jwt, pool).await?,
+    )),
     None => Ok(None),
   }
 }
@@ -127,11 +257,7 @@ pub(crate) async fn get_local_user_settings_view_from_jwt(
   jwt: &str,
   pool: &DbPool,
 ) -> Result<LocalUserSettingsView, LemmyError> {
-  let claims = match Claims::decode(&jwt) {
-    Ok(claims) => claims.claims,
-    Err(_e) => return Err(ApiError::err("not_logged_in").into()),
-  };
-  let local_user_id = claims.id;
+  let (local_user_id, _scopes) = decode_jwt(jwt, pool).await?;
   let local_user_view = blocking(pool, move |conn| {
     LocalUserSettingsView::read(conn, local_user_id)
   })
@@ -156,8 +282,8 @@ pub(crate) async fn get_local_user_settings_view_from_jwt_opt(
 }
 
 pub(crate) async fn check_community_ban(
-  person_id: i32,
-  community_id: i32,
+  person_id: PersonId,
+  community_id: CommunityId,
   pool: &DbPool,
 ) -> Result<(), LemmyError> {
   let is_banned =
@@ -169,6 +295,105 @@ pub(crate) async fn check_community_ban(
   }
 }
 
+/// Federates a vote which replaced `previous_score`. A previous vote in the other direction is
+/// undone first, otherwise remote instances would count both votes. If the site federates votes
+/// as an instance aggregate, the new summed score of the local votes is sent instead, without the
+/// voter.
+pub(crate) async fn send_vote<T: ApubLikeableType>(
+  object: &T,
+  person: &Person,
+  previous_score: Option<i16>,
+  score: i16,
+  context: &LemmyContext,
+) -> Result<(), LemmyError> {
+  let context_data = web::Data::new(context.clone());
+  let federate_votes_as = context
+    .site_cache()
+    .get_or_refresh(|| site::read_site(&context_data, None))
+    .await?
+    .site_view
+    .map(|site_view| FederateVotesAs::from_i16(site_view.site.federate_votes_as))
+    // Not set up yet
+    .unwrap_or_default();
+  if federate_votes_as == FederateVotesAs::InstanceAggregate {
+    return object.send_vote_aggregate(context).await;
+  }
+
+  for activity in vote_activities(previous_score, score) {
+    match activity {
+      VoteActivity::Like => object.send_like(person, context).await?,
+      VoteActivity::Dislike => object.send_dislike(person, context).await?,
+      VoteActivity::UndoLike => object.send_undo_like(person, context).await?,
+      VoteActivity::UndoDislike => object.send_undo_dislike(person, context).await?,
+    }
+  }
+  Ok(())
+}
+
+/// Like `send_vote()`, but holds the vote back for a few seconds so that quick changes of the same
+/// vote are federated as a single activity, or not at all if they cancel out. The vote has to be
+/// saved locally already.
+pub(crate) async fn send_vote_debounced<T: ApubLikeableType>(
+  object: &T,
+  target: VoteTarget,
+  person: &Person,
+  previous_score: Option<i16>,
+  score: i16,
+  context: &LemmyContext,
+) -> Result<(), LemmyError> {
+  let key = (person.id, target);
+  let debouncer = context.vote_debouncer();
+  match debouncer.record(key, previous_score.unwrap_or(0), score) {
+    DebouncedVote::SendNow => send_vote(object, person, previous_score, score, context).await,
+    DebouncedVote::Merged => Ok(()),
+    DebouncedVote::Scheduled => {
+      let delay = debouncer.delay();
+      let context = context.clone();
+      actix_rt::spawn(async move {
+        actix_rt::time::delay_for(delay).await;
+        if let Some(vote) = context.vote_debouncer().take(&key) {
+          if let Err(e) = send_pending_vote(key, vote, &context).await {
+            error!("Failed to send held back vote: {}", e);
+          }
+        }
+      });
+      Ok(())
+    }
+  }
+}
+
+async fn send_pending_vote(
+  key: (PersonId, VoteTarget),
+  vote: PendingVote,
+  context: &LemmyContext,
+) -> Result<(), LemmyError> {
+  if vote.activities().is_empty() {
+    return Ok(());
+  }
+  let (person_id, target) = key;
+  let person = blocking(context.pool(), move |conn| Person::read(conn, person_id)).await??;
+  let previous_score = Some(vote.previous_score);
+  match target {
+    VoteTarget::Post(post_id) => {
+      let post = blocking(context.pool(), move |conn| Post::read(conn, post_id)).await??;
+      send_vote(&post, &person, previous_score, vote.score, context).await
+    }
+    VoteTarget::Comment(comment_id) => {
+      let comment = blocking(context.pool(), move |conn| Comment::read(conn, comment_id)).await??;
+      send_vote(&comment, &person, previous_score, vote.score, context).await
+    }
+  }
+}
+
+/// Sends all votes which are still held back, so that they aren't lost on shutdown
+pub async fn flush_pending_votes(context: &LemmyContext) {
+  for (key, vote) in context.vote_debouncer().take_all() {
+    if let Err(e) = send_pending_vote(key, vote, context).await {
+      error!("Failed to send held back vote: {}", e);
+    }
+  }
+}
+
 pub(crate) async fn check_downvotes_enabled(score: i16, pool: &DbPool) -> Result<(), LemmyError> {
   if score == -1 {
     let site = blocking(pool, move |conn| Site::read_simple(conn)).await??;
@@ -179,6 +404,115 @@ pub(crate) async fn check_downvotes_enabled(score: i16, pool: &DbPool) -> Result
   Ok(())
 }
 
+/// Whether the site only allows avatars, banners and icons which are hosted by this instance
+pub(crate) async fn site_requires_local_images(pool: &DbPool) -> Result<bool, LemmyError> {
+  let require_local_images = blocking(pool, move |conn| Site::read_simple(conn))
+    .await?
+    .map(|site| site.require_local_images)
+    // Not set up yet
+    .unwrap_or(false);
+  Ok(require_local_images)
+}
+
+/// Whether the report listings show the profiles of deleted accounts to the user, which only
+/// admins can see if the site allows it
+pub(crate) async fn can_see_deleted_persons(
+  local_user_view: &LocalUserView,
+  pool: &DbPool,
+) -> Result<bool, LemmyError> {
+  if !local_user_view.local_user.admin {
+    return Ok(false);
+  }
+  let site = blocking(pool, move |conn| Site::read_simple(conn)).await??;
+  Ok(site.show_deleted_usernames_to_admins)
+}
+
+/// The listing type and sort of a post or comment listing, for the ones which the request leaves
+/// out. Logged in users get the defaults from their settings, and anonymous users the listing type
+/// of the site. A listing of a single community shows all of its content by default.
+pub(crate) async fn get_listing_defaults(
+  type_: Option<ListingType>,
+  sort: Option<SortType>,
+  for_community: bool,
+  local_user_view: &Option<LocalUserView>,
+  pool: &DbPool,
+) -> Result<(ListingType, SortType), LemmyError> {
+  let type_ = match (type_, local_user_view) {
+    (Some(type_), _) => type_,
+    (None, _) if for_community => ListingType::Community,
+    (None, Some(uv)) => ListingType::from_i16(uv.local_user.default_listing_type),
+    (None, None) => blocking(pool, move |conn| Site::read_simple(conn))
+      .await?
+      .map(|site| ListingType::from_i16(site.default_post_listing_type))
+      // Not set up yet
+      .unwrap_or_default(),
+  };
+  let sort = match (sort, local_user_view) {
+    (Some(sort), _) => sort,
+    // Settings from before `MostViews` was rejected as a default
+    (None, Some(uv)) => match SortType::from_i16(uv.local_user.default_sort_type) {
+      SortType::MostViews => SortType::default(),
+      sort => sort,
+    },
+    (None, None) => SortType::default(),
+  };
+  Ok((type_, sort))
+}
+
+/// View counts are only compared by the mods of a community and the admins, so `MostViews` is
+/// rejected unless the listing is limited to a community which the person moderates.
+pub(crate) async fn check_sort_allowed(
+  sort: SortType,
+  community_id: Option<CommunityId>,
+  community_name: Option<String>,
+  person_id: Option<PersonId>,
+  pool: &DbPool,
+) -> Result<(), LemmyError> {
+  if sort != SortType::MostViews {
+    return Ok(());
+  }
+  let community_id = match (community_id, community_name) {
+    (Some(community_id), _) => community_id,
+    (None, Some(name)) => {
+      blocking(pool, move |conn| Community::read_from_name(conn, &name))
+        .await?
+        .map_err(|_| ApiError::err("couldnt_find_community"))?
+        .id
+    }
+    (None, None) => return Err(ApiError::err("not_a_mod_or_admin").into()),
+  };
+  let person_id = person_id.ok_or_else(|| ApiError::err("not_a_mod_or_admin"))?;
+  is_mod_or_admin(pool, person_id, community_id).await
+}
+
+/// Rejects the images which arent hosted by this instance, each given with the name of its api
+/// field. Images which are erased or left unchanged are skipped.
+pub(crate) fn check_images_are_local(
+  images: &[(&str, &Option<Option<DbUrl>>)],
+) -> Result<(), ApiError> {
+  for (field, image) in images {
+    if let Some(Some(image)) = image {
+      check_image_is_local(&Url::from(image.to_owned()), field)?;
+    }
+  }
+  Ok(())
+}
+
+/// Communities can disable downvotes on their own. Existing downvotes are still counted.
+pub(crate) async fn check_community_downvotes_enabled(
+  score: i16,
+  community_id: CommunityId,
+  pool: &DbPool,
+) -> Result<(), LemmyError> {
+  if score == -1 {
+    let community = blocking(pool, move |conn| Community::read(conn, community_id)).await??;
+    if community.downvotes_disabled {
+      return Err(ApiError::err("downvotes_disabled").into());
+    }
+  }
+  Ok(())
+}
+
 /// Returns a list of communities that the user moderates
 /// or if a community_id is supplied validates the user is a moderator
 /// of that community and returns the community id in a vec
@@ -187,10 +521,10 @@ pub(crate) async fn check_downvotes_enabled(score: i16, pool: &DbPool) -> Result
 /// * `community_id` - optional community id to check for moderator privileges
 /// * `pool` - the diesel db pool
 pub(crate) async fn collect_moderated_communities(
-  person_id: i32,
-  community_id: Option<i32>,
+  person_id: PersonId,
+  community_id: Option<CommunityId>,
   pool: &DbPool,
-) -> Result<Vec<i32>, LemmyError> {
+) -> Result<Vec<CommunityId>, LemmyError> {
   if let Some(community_id) = community_id {
     // if the user provides a community_id, just check for mod/admin privileges
     is_mod_or_admin(pool, person_id, community_id).await?;
@@ -204,42 +538,80 @@ pub(crate) async fn collect_moderated_communities(
   }
 }
 
-pub(crate) async fn build_federated_instances(
-  pool: &DbPool,
-) -> Result<Option<FederatedInstances>, LemmyError> {
-  if Settings::get().federation().enabled {
-    let distinct_communities = blocking(pool, move |conn| {
-      Community::distinct_federated_communities(conn)
-    })
-    .await??;
+/// Domains of the remote instances which we federate with, without the blocked ones
+pub fn linked_instances(conn: &PgConnection) -> Result<Vec<String>, LemmyError> {
+  let distinct_communities = Community::distinct_federated_communities(conn)?;
+
+  let mut linked = distinct_communities
+    .iter()
+    .map(|actor_id| Ok(Url::parse(actor_id)?.host_str().unwrap_or("").to_string()))
+    .collect::<Result<Vec<String>, LemmyError>>()?;
 
-    let allowed = Settings::get().get_allowed_instances();
-    let blocked = Settings::get().get_blocked_instances();
+  if let Some(allowed) = Settings::get().get_allowed_instances() {
+    linked.extend(allowed);
+  }
 
-    let mut linked = distinct_communities
-      .iter()
-      .map(|actor_id| Ok(Url::parse(actor_id)?.host_str().unwrap_or("").to_string()))
-      .collect::<Result<Vec<String>, LemmyError>>()?;
+  if let Some(blocked) = Settings::get().get_blocked_instances() {
+    linked.retain(|a| !blocked.contains(a) && !a.eq(&Settings::get().hostname()));
+  }
 
-    if let Some(allowed) = allowed.as_ref() {
-      linked.extend_from_slice(allowed);
+  match FederationMode::effective() {
+    FederationMode::Open => {}
+    FederationMode::Allowlist => {
+      let allowed = Settings::get().get_allowed_instances().unwrap_or_default();
+      linked.retain(|a| allowed.contains(a));
     }
+    FederationMode::Disabled => linked.clear(),
+  }
 
-    if let Some(blocked) = blocked.as_ref() {
-      linked.retain(|a| !blocked.contains(a) && !a.eq(&Settings::get().hostname()));
-    }
+  // Sort and remove dupes
+  linked.sort_unstable();
+  linked.dedup();
 
-    // Sort and remove dupes
-    linked.sort_unstable();
-    linked.dedup();
+  Ok(linked)
+}
 
-    Ok(Some(FederatedInstances {
-      linked,
-      allowed,
-      blocked,
-    }))
-  } else {
-    Ok(None)
+pub(crate) async fn build_federated_instances(
+  pool: &DbPool,
+) -> Result<Option<FederatedInstances>, LemmyError> {
+  if !Settings::get().federation().enabled {
+    return Ok(None);
+  }
+
+  let mode = FederationMode::effective();
+  let linked = blocking(pool, move |conn| linked_instances(conn)).await??;
+  let (allowed, blocked) = match mode {
+    FederationMode::Open => (
+      Settings::get().get_allowed_instances(),
+      Settings::get().get_blocked_instances(),
+    ),
+    FederationMode::Allowlist => (
+      Some(Settings::get().get_allowed_instances().unwrap_or_default()),
+      None,
+    ),
+    FederationMode::Disabled => (None, None),
+  };
+
+  Ok(Some(FederatedInstances {
+    mode,
+    linked,
+    allowed,
+    blocked,
+    details: None,
+  }))
+}
+
+/// Adds the software of each instance to the federated instances, this is only shown to admins.
+pub(crate) async fn add_federated_instance_details(
+  federated_instances: Option<FederatedInstances>,
+  pool: &DbPool,
+) -> Result<Option<FederatedInstances>, LemmyError> {
+  match federated_instances {
+    Some(federated_instances) => {
+      let known = blocking(pool, move |conn| Instance::list(conn)).await??;
+      Ok(Some(federated_instances.with_details(known)))
+    }
+    None => Ok(None),
   }
 }
 
@@ -253,19 +625,37 @@ pub async fn match_websocket_operation(
     // User ops
     UserOperation::Login => do_websocket_operation::<Login>(context, id, op, data).await,
     UserOperation::Register => do_websocket_operation::<Register>(context, id, op, data).await,
+    UserOperation::CheckUsernameAvailable => {
+      do_websocket_operation::<CheckUsernameAvailable>(context, id, op, data).await
+    }
     UserOperation::GetCaptcha => do_websocket_operation::<GetCaptcha>(context, id, op, data).await,
+    UserOperation::GetRegistrationChallenge => {
+      do_websocket_operation::<GetRegistrationChallenge>(context, id, op, data).await
+    }
     UserOperation::GetPersonDetails => {
       do_websocket_operation::<GetPersonDetails>(context, id, op, data).await
     }
+    UserOperation::PinPersonPost => {
+      do_websocket_operation::<PinPersonPost>(context, id, op, data).await
+    }
+    UserOperation::SetPersonNote => {
+      do_websocket_operation::<SetPersonNote>(context, id, op, data).await
+    }
     UserOperation::GetReplies => do_websocket_operation::<GetReplies>(context, id, op, data).await,
     UserOperation::AddAdmin => do_websocket_operation::<AddAdmin>(context, id, op, data).await,
     UserOperation::BanPerson => do_websocket_operation::<BanPerson>(context, id, op, data).await,
+    UserOperation::MergePersons => {
+      do_websocket_operation::<MergePersons>(context, id, op, data).await
+    }
     UserOperation::GetPersonMentions => {
       do_websocket_operation::<GetPersonMentions>(context, id, op, data).await
     }
     UserOperation::MarkPersonMentionAsRead => {
       do_websocket_operation::<MarkPersonMentionAsRead>(context, id, op, data).await
     }
+    UserOperation::MarkPersonPostMentionAsRead => {
+      do_websocket_operation::<MarkPersonPostMentionAsRead>(context, id, op, data).await
+    }
     UserOperation::MarkAllAsRead => {
       do_websocket_operation::<MarkAllAsRead>(context, id, op, data).await
     }
@@ -278,8 +668,25 @@ pub async fn match_websocket_operation(
     UserOperation::PasswordChange => {
       do_websocket_operation::<PasswordChange>(context, id, op, data).await
     }
+    UserOperation::CreateApiToken => {
+      do_websocket_operation::<CreateApiToken>(context, id, op, data).await
+    }
+    UserOperation::ListApiTokens => {
+      do_websocket_operation::<ListApiTokens>(context, id, op, data).await
+    }
+    UserOperation::RevokeApiToken => {
+      do_websocket_operation::<RevokeApiToken>(context, id, op, data).await
+    }
+    UserOperation::ListSessions => {
+      do_websocket_operation::<ListSessions>(context, id, op, data).await
+    }
+    UserOperation::RevokeSession => {
+      do_websocket_operation::<RevokeSession>(context, id, op, data).await
+    }
     UserOperation::UserJoin => do_websocket_operation::<UserJoin>(context, id, op, data).await,
     UserOperation::PostJoin => do_websocket_operation::<PostJoin>(context, id, op, data).await,
+    // Only sent by the server
+    UserOperation::PostUsersOnline => Err(ApiError::err("invalid_operation").into()),
     UserOperation::CommunityJoin => {
       do_websocket_operation::<CommunityJoin>(context, id, op, data).await
     }
@@ -319,6 +726,24 @@ pub async fn match_websocket_operation(
     UserOperation::SaveSiteConfig => {
       do_websocket_operation::<SaveSiteConfig>(context, id, op, data).await
     }
+    UserOperation::GetSpamRules => {
+      do_websocket_operation::<GetSpamRules>(context, id, op, data).await
+    }
+    UserOperation::EditSpamRules => {
+      do_websocket_operation::<EditSpamRules>(context, id, op, data).await
+    }
+    UserOperation::GetAdminStats => {
+      do_websocket_operation::<GetAdminStats>(context, id, op, data).await
+    }
+    UserOperation::RecalculateAggregates => {
+      do_websocket_operation::<RecalculateAggregates>(context, id, op, data).await
+    }
+    UserOperation::ListScheduledTasks => {
+      do_websocket_operation::<ListScheduledTasks>(context, id, op, data).await
+    }
+    UserOperation::RunScheduledTask => {
+      do_websocket_operation::<RunScheduledTask>(context, id, op, data).await
+    }
     UserOperation::Search => do_websocket_operation::<Search>(context, id, op, data).await,
     UserOperation::TransferCommunity => {
       do_websocket_operation::<TransferCommunity>(context, id, op, data).await
@@ -334,6 +759,9 @@ pub async fn match_websocket_operation(
     UserOperation::ListCommunities => {
       do_websocket_operation::<ListCommunities>(context, id, op, data).await
     }
+    UserOperation::CommunityAutocomplete => {
+      do_websocket_operation::<CommunityAutocomplete>(context, id, op, data).await
+    }
     UserOperation::CreateCommunity => {
       do_websocket_operation::<CreateCommunity>(context, id, op, data).await
     }
@@ -346,9 +774,44 @@ pub async fn match_websocket_operation(
     UserOperation::RemoveCommunity => {
       do_websocket_operation::<RemoveCommunity>(context, id, op, data).await
     }
+    UserOperation::ExemptCommunityFromSlurFilter => {
+      do_websocket_operation::<ExemptCommunityFromSlurFilter>(context, id, op, data).await
+    }
+    UserOperation::CreateCommunityTag => {
+      do_websocket_operation::<CreateCommunityTag>(context, id, op, data).await
+    }
+    UserOperation::DeleteCommunityTag => {
+      do_websocket_operation::<DeleteCommunityTag>(context, id, op, data).await
+    }
+    UserOperation::GetCommunityFederationStatus => {
+      do_websocket_operation::<GetCommunityFederationStatus>(context, id, op, data).await
+    }
+    UserOperation::RefreshRemoteCommunity => {
+      do_websocket_operation::<RefreshRemoteCommunity>(context, id, op, data).await
+    }
+    UserOperation::ExportCommunity => {
+      do_websocket_operation::<ExportCommunity>(context, id, op, data).await
+    }
+    UserOperation::ImportCommunity => {
+      do_websocket_operation::<ImportCommunity>(context, id, op, data).await
+    }
+    // Only sent by the server
+    UserOperation::DeleteCommunityProgress => Err(ApiError::err("invalid_operation").into()),
     UserOperation::FollowCommunity => {
       do_websocket_operation::<FollowCommunity>(context, id, op, data).await
     }
+    UserOperation::FavoriteCommunity => {
+      do_websocket_operation::<FavoriteCommunity>(context, id, op, data).await
+    }
+    UserOperation::MuteCommunity => {
+      do_websocket_operation::<MuteCommunity>(context, id, op, data).await
+    }
+    UserOperation::ListFollowRequests => {
+      do_websocket_operation::<ListFollowRequests>(context, id, op, data).await
+    }
+    UserOperation::ApproveFollowRequest => {
+      do_websocket_operation::<ApproveFollowRequest>(context, id, op, data).await
+    }
     UserOperation::GetFollowedCommunities => {
       do_websocket_operation::<GetFollowedCommunities>(context, id, op, data).await
     }
@@ -361,13 +824,31 @@ pub async fn match_websocket_operation(
 
     // Post ops
     UserOperation::CreatePost => do_websocket_operation::<CreatePost>(context, id, op, data).await,
+    UserOperation::CreateMultiPost => {
+      do_websocket_operation::<CreateMultiPost>(context, id, op, data).await
+    }
     UserOperation::GetPost => do_websocket_operation::<GetPost>(context, id, op, data).await,
+    UserOperation::MarkPostViewed => {
+      do_websocket_operation::<MarkPostViewed>(context, id, op, data).await
+    }
     UserOperation::GetPosts => do_websocket_operation::<GetPosts>(context, id, op, data).await,
+    UserOperation::GetRandomPost => {
+      do_websocket_operation::<GetRandomPost>(context, id, op, data).await
+    }
     UserOperation::EditPost => do_websocket_operation::<EditPost>(context, id, op, data).await,
     UserOperation::DeletePost => do_websocket_operation::<DeletePost>(context, id, op, data).await,
     UserOperation::RemovePost => do_websocket_operation::<RemovePost>(context, id, op, data).await,
+    UserOperation::ListPendingPosts => {
+      do_websocket_operation::<ListPendingPosts>(context, id, op, data).await
+    }
+    UserOperation::ApprovePost => {
+      do_websocket_operation::<ApprovePost>(context, id, op, data).await
+    }
     UserOperation::LockPost => do_websocket_operation::<LockPost>(context, id, op, data).await,
     UserOperation::StickyPost => do_websocket_operation::<StickyPost>(context, id, op, data).await,
+    UserOperation::RefetchPostMetadata => {
+      do_websocket_operation::<RefetchPostMetadata>(context, id, op, data).await
+    }
     UserOperation::CreatePostLike => {
       do_websocket_operation::<CreatePostLike>(context, id, op, data).await
     }
@@ -395,12 +876,19 @@ pub async fn match_websocket_operation(
     UserOperation::RemoveComment => {
       do_websocket_operation::<RemoveComment>(context, id, op, data).await
     }
+    UserOperation::LockComment => {
+      do_websocket_operation::<LockComment>(context, id, op, data).await
+    }
+    UserOperation::StickyComment => {
+      do_websocket_operation::<StickyComment>(context, id, op, data).await
+    }
     UserOperation::MarkCommentAsRead => {
       do_websocket_operation::<MarkCommentAsRead>(context, id, op, data).await
     }
     UserOperation::SaveComment => {
       do_websocket_operation::<SaveComment>(context, id, op, data).await
     }
+    UserOperation::GetComment => do_websocket_operation::<GetComment>(context, id, op, data).await,
     UserOperation::GetComments => {
       do_websocket_operation::<GetComments>(context, id, op, data).await
     }
@@ -483,10 +971,34 @@ pub(crate) fn espeak_wav_base64(text: &str) -> Result<String, LemmyError> {
   Ok(base64)
 }
 
+/// Checks that the SHA-256 hash of the challenge followed by the nonce starts with at least
+/// `difficulty` zero bits
+pub(crate) fn check_proof_of_work(challenge: &str, nonce: &str, difficulty: i32) -> bool {
+  let hash = Sha256::digest(format!("{}{}", challenge, nonce).as_bytes());
+  let mut zero_bits = 0;
+  for byte in hash.iter() {
+    zero_bits += byte.leading_zeros() as i32;
+    if *byte != 0 {
+      break;
+    }
+  }
+  zero_bits >= difficulty
+}
+
 /// Checks the password length
-pub(crate) fn password_length_check(pass: &str) -> Result<(), LemmyError> {
-  if pass.len() > 60 {
-    Err(ApiError::err("invalid_password").into())
+/// Used for all new passwords, ie when registering, changing and resetting them. Passwords with
+/// many multibyte characters are also limited by their length in bytes, so that bcrypt doesn't cut
+/// them off.
+pub(crate) fn password_length_check(pass: &str) -> Result<(), ApiError> {
+  let length = pass.chars().count();
+  if length < MIN_PASSWORD_LENGTH {
+    Err(
+      ApiError::new(ApiErrorCode::PasswordTooShort).with_detail("min_length", MIN_PASSWORD_LENGTH),
+    )
+  } else if length > MAX_PASSWORD_LENGTH {
+    Err(ApiError::new(ApiErrorCode::InvalidPassword).with_detail("max_length", MAX_PASSWORD_LENGTH))
+  } else if pass.len() > MAX_PASSWORD_BYTES {
+    Err(ApiError::new(ApiErrorCode::InvalidPassword).with_detail("max_bytes", MAX_PASSWORD_BYTES))
   } else {
     Ok(())
   }
@@ -494,10 +1006,33 @@ pub(crate) fn password_length_check(pass: &str) -> Result<(), LemmyError> {
 
 #[cfg(test)]
 mod tests {
-  use crate::captcha_espeak_wav_base64;
+  use crate::{captcha_espeak_wav_base64, check_proof_of_work, password_length_check};
 
   #[test]
   fn test_espeak() {
     assert!(captcha_espeak_wav_base64("WxRt2l").is_ok())
   }
+
+  #[test]
+  fn test_proof_of_work() {
+    let challenge = "d3b07384d113edec49eaa6238ad5ff00";
+    let nonce = (0..)
+      .map(|n: u32| n.to_string())
+      .find(|nonce| check_proof_of_work(challenge, nonce, 8))
+      .unwrap();
+    assert!(check_proof_of_work(challenge, &nonce, 1));
+    assert!(!check_proof_of_work(challenge, &nonce, 257));
+    assert!(!check_proof_of_work("another challenge", "", 256));
+  }
+
+  #[test]
+  fn test_password_length_check() {
+    assert!(password_length_check("123456789").is_err());
+    assert!(password_length_check("1234567890").is_ok());
+    assert!(password_length_check(&"a".repeat(60)).is_ok());
+    assert!(password_length_check(&"a".repeat(61)).is_err());
+    // 36 characters, but 72 bytes
+    assert!(password_length_check(&"ä".repeat(36)).is_ok());
+    assert!(password_length_check(&"ä".repeat(37)).is_err());
+  }
 }
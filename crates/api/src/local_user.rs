@@ -1,10 +1,18 @@
 use crate::{
   captcha_espeak_wav_base64,
+  check_images_are_local,
+  check_proof_of_work,
+  check_sort_allowed,
   collect_moderated_communities,
+  create_login_session,
   get_local_user_view_from_jwt,
   get_local_user_view_from_jwt_opt,
+  get_local_user_view_from_jwt_read_only,
+  get_local_user_view_from_login_jwt,
+  get_post,
   is_admin,
   password_length_check,
+  site_requires_local_images,
   Perform,
 };
 use actix_web::web::Data;
@@ -12,47 +20,73 @@ use anyhow::Context;
 use bcrypt::verify;
 use captcha::{gen, Difficulty};
 use chrono::Duration;
-use lemmy_api_structs::{blocking, person::*, send_email_to_user};
+use lemmy_api_structs::{
+  blocking,
+  blocking_read,
+  blocking_transaction,
+  hide_downvotes_if_disabled,
+  person::*,
+  post::PostResponse,
+  send_email_to_user,
+  sends_immediate_emails,
+};
 use lemmy_apub::{
   generate_apub_endpoint,
   generate_followers_url,
   generate_inbox_url,
   generate_shared_inbox_url,
+  ActorType,
   ApubObjectType,
   EndpointType,
 };
 use lemmy_db_queries::{
   diesel_option_overwrite,
   diesel_option_overwrite_to_url,
+  is_email_regex,
   source::{
+    api_token::ApiToken_,
     comment::Comment_,
     community::Community_,
+    language::Language_,
     local_user::LocalUser_,
+    login_session::LoginSession_,
     password_reset_request::PasswordResetRequest_,
     person::Person_,
     person_mention::PersonMention_,
-    post::Post_,
+    person_note::{PersonNote_, MAX_PERSON_NOTE_LENGTH},
+    person_post_mention::PersonPostMention_,
+    post::{PersonPinnedPost_, Post_},
     private_message::PrivateMessage_,
+    registration_question::RegistrationQuestion_,
     site::Site_,
   },
   Crud,
+  EmailDigestFrequency,
   Followable,
   Joinable,
   ListingType,
+  MentionType,
+  RegistrationChallenge,
   SortType,
 };
 use lemmy_db_schema::{
   naive_now,
   source::{
+    api_token::{ApiToken, ApiTokenForm},
     comment::Comment,
     community::*,
+    language::Language,
     local_user::{LocalUser, LocalUserForm},
+    login_session::LoginSession,
     moderator::*,
     password_reset_request::*,
     person::*,
     person_mention::*,
-    post::Post,
+    person_note::{PersonNote, PersonNoteForm},
+    person_post_mention::PersonPostMention,
+    post::{PersonPinnedPost, PersonPinnedPostForm, Post},
     private_message::*,
+    registration_question::RegistrationQuestion,
     site::*,
   },
 };
@@ -61,13 +95,14 @@ use lemmy_db_views::{
   comment_view::CommentQueryBuilder,
   local_user_view::LocalUserView,
   post_report_view::PostReportView,
-  post_view::PostQueryBuilder,
+  post_view::{PostQueryBuilder, PostView},
   private_message_view::{PrivateMessageQueryBuilder, PrivateMessageView},
 };
 use lemmy_db_views_actor::{
   community_follower_view::CommunityFollowerView,
   community_moderator_view::CommunityModeratorView,
   person_mention_view::{PersonMentionQueryBuilder, PersonMentionView},
+  person_post_mention_view::{PersonPostMentionQueryBuilder, PersonPostMentionView},
   person_view::PersonViewSafe,
 };
 use lemmy_utils::{
@@ -77,23 +112,27 @@ use lemmy_utils::{
   location_info,
   settings::structs::Settings,
   utils::{
+    check_max_length,
     check_slurs,
     generate_random_string,
     is_valid_preferred_username,
+    is_valid_theme_color,
     is_valid_username,
-    naive_from_unix,
+    parse_expires,
     remove_slurs,
   },
   ApiError,
+  ApiErrorCode,
   ConnectionId,
   LemmyError,
 };
 use lemmy_websocket::{
-  messages::{CaptchaItem, CheckCaptcha, SendAllMessage, SendUserRoomMessage},
+  messages::{CaptchaItem, CheckCaptcha, SendAllMessage, SendUserRoomMessage, TakeCaptcha},
   LemmyContext,
   UserOperation,
 };
-use std::str::FromStr;
+use log::error;
+use rand::seq::SliceRandom;
 
 #[async_trait::async_trait(?Send)]
 impl Perform for Login {
@@ -129,11 +168,168 @@ impl Perform for Login {
 
     // Return the jwt
     Ok(LoginResponse {
-      jwt: Claims::jwt(local_user_view.local_user.id, Settings::get().hostname())?,
+      jwt: create_login_session(local_user_view.local_user.id, &data.client, context.pool())
+        .await?,
     })
   }
 }
 
+/// Validates a username for a new account. Returns all problems with it, so that they can be shown
+/// together with the other registration errors.
+async fn check_username(
+  username: &str,
+  context: &LemmyContext,
+) -> Result<Vec<ApiError>, LemmyError> {
+  let mut errors = Vec::new();
+  if !is_valid_username(username) {
+    errors.push(ApiError::new(ApiErrorCode::InvalidUsername).with_field("username"));
+  }
+  if let Err(e) = check_slurs(username, &context.slur_filter().regex()) {
+    errors.push(e.with_field("username"));
+  }
+  if errors.is_empty() {
+    // Persons and communities share the namespace for webfinger, so their names can't collide
+    let name = username.to_owned();
+    let taken = blocking(context.pool(), move |conn| -> Result<bool, LemmyError> {
+      Ok(
+        Person::local_name_exists(conn, &name)?
+          || Community::read_from_name(conn, &name.to_lowercase()).is_ok(),
+      )
+    })
+    .await??;
+    if taken {
+      errors.push(ApiError::new(ApiErrorCode::UserAlreadyExists).with_field("username"));
+    }
+  }
+  Ok(errors)
+}
+
+/// Checks the answer to the registration challenge of the site. Each challenge can only be
+/// answered once.
+async fn check_registration_challenge(
+  site: Option<&Site>,
+  data: &Register,
+  context: &LemmyContext,
+) -> Result<bool, LemmyError> {
+  let uuid = data.captcha_uuid.to_owned().unwrap_or_default();
+  let answer = data.captcha_answer.to_owned().unwrap_or_default();
+  let challenge = site
+    .map(|site| RegistrationChallenge::from_i16(site.registration_challenge))
+    .unwrap_or_default();
+
+  let solved = match challenge {
+    RegistrationChallenge::Captcha => {
+      !Settings::get().captcha().enabled
+        || context
+          .chat_server()
+          .send(CheckCaptcha { uuid, answer })
+          .await?
+    }
+    RegistrationChallenge::ProofOfWork => {
+      let difficulty = site
+        .map(|site| site.proof_of_work_difficulty)
+        .unwrap_or_default();
+      match context.chat_server().send(TakeCaptcha { uuid }).await? {
+        Some(challenge) => check_proof_of_work(&challenge, &answer, difficulty),
+        None => false,
+      }
+    }
+    RegistrationChallenge::Question => {
+      let question_id = context
+        .chat_server()
+        .send(TakeCaptcha { uuid })
+        .await?
+        .and_then(|id| id.parse::<i32>().ok());
+      match question_id {
+        Some(question_id) => {
+          let question = blocking(context.pool(), move |conn| {
+            RegistrationQuestion::read(conn, question_id)
+          })
+          .await?;
+          let answer = answer.trim().to_lowercase();
+          question
+            .map(|q| q.answers.iter().any(|a| a.trim().to_lowercase() == answer))
+            .unwrap_or(false)
+        }
+        None => false,
+      }
+    }
+  };
+  Ok(solved)
+}
+
+/// Generates a captcha image, and stores its answer so that `Register` can check it
+fn generate_captcha(context: &LemmyContext) -> CaptchaResponse {
+  let captcha = match Settings::get().captcha().difficulty.as_str() {
+    "easy" => gen(Difficulty::Easy),
+    "medium" => gen(Difficulty::Medium),
+    "hard" => gen(Difficulty::Hard),
+    _ => gen(Difficulty::Medium),
+  };
+
+  let answer = captcha.chars_as_string();
+
+  let png_byte_array = captcha.as_png().expect("failed to generate captcha");
+
+  let png = base64::encode(png_byte_array);
+
+  let wav = captcha_espeak_wav_base64(&answer).ok();
+
+  let uuid = store_challenge_answer(context, answer);
+
+  CaptchaResponse { png, uuid, wav }
+}
+
+/// Stores the answer of a registration challenge on the queue, and returns its uuid
+fn store_challenge_answer(context: &LemmyContext, answer: String) -> String {
+  let uuid = uuid::Uuid::new_v4().to_string();
+
+  let captcha_item = CaptchaItem {
+    answer,
+    uuid: uuid.to_owned(),
+    expires: naive_now() + Duration::minutes(10), // expires in 10 minutes
+  };
+  context.chat_server().do_send(captcha_item);
+
+  uuid
+}
+
+async fn read_registration_challenge(
+  context: &LemmyContext,
+) -> Result<RegistrationChallenge, LemmyError> {
+  let site = blocking(context.pool(), move |conn| Site::read_simple(conn)).await?;
+  Ok(
+    site
+      .map(|site| RegistrationChallenge::from_i16(site.registration_challenge))
+      .unwrap_or_default(),
+  )
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for CheckUsernameAvailable {
+  type Response = CheckUsernameAvailableResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<CheckUsernameAvailableResponse, LemmyError> {
+    let data: &CheckUsernameAvailable = &self;
+
+    let mut errors = check_username(&data.username, context).await?;
+    // A taken name is the expected answer, everything else means the name can't be used at all
+    let available = !errors
+      .iter()
+      .any(|e| e.code == ApiErrorCode::UserAlreadyExists);
+    errors.retain(|e| e.code != ApiErrorCode::UserAlreadyExists);
+    if let Some(e) = ApiError::combine(errors) {
+      return Err(e.into());
+    }
+
+    Ok(CheckUsernameAvailableResponse { available })
+  }
+}
+
 #[async_trait::async_trait(?Send)]
 impl Perform for Register {
   type Response = LoginResponse;
@@ -146,17 +342,38 @@ impl Perform for Register {
     let data: &Register = &self;
 
     // Make sure site has open registration
-    if let Ok(site) = blocking(context.pool(), move |conn| Site::read_simple(conn)).await? {
+    let site = blocking(context.pool(), move |conn| Site::read_simple(conn))
+      .await?
+      .ok();
+    if let Some(site) = &site {
       if !site.open_registration {
         return Err(ApiError::err("registration_closed").into());
       }
     }
 
-    password_length_check(&data.password)?;
-
-    // Make sure passwords match
+    // Collect all validation errors, so that they can be shown at once
+    let mut errors = check_username(&data.username, context).await?;
+    if let Err(e) = password_length_check(&data.password) {
+      errors.push(e.with_field("password"));
+    }
     if data.password != data.password_verify {
-      return Err(ApiError::err("passwords_dont_match").into());
+      errors.push(ApiError::new(ApiErrorCode::PasswordsDontMatch).with_field("password_verify"));
+    }
+    let email = data.email.to_owned().filter(|e| !e.is_empty());
+    if let Some(email) = email.to_owned() {
+      if !is_email_regex(&email) {
+        errors.push(ApiError::new(ApiErrorCode::InvalidEmail).with_field("email"));
+      } else if blocking(context.pool(), move |conn| {
+        LocalUserView::find_by_email(conn, &email)
+      })
+      .await?
+      .is_ok()
+      {
+        errors.push(ApiError::new(ApiErrorCode::EmailAlreadyExists).with_field("email"));
+      }
+    }
+    if let Some(e) = ApiError::combine(errors) {
+      return Err(e.into());
     }
 
     // Check if there are admins. False if admins exist
@@ -166,31 +383,11 @@ impl Perform for Register {
     .await??;
 
     // If its not the admin, check the captcha
-    if !no_admins && Settings::get().captcha().enabled {
-      let check = context
-        .chat_server()
-        .send(CheckCaptcha {
-          uuid: data
-            .captcha_uuid
-            .to_owned()
-            .unwrap_or_else(|| "".to_string()),
-          answer: data
-            .captcha_answer
-            .to_owned()
-            .unwrap_or_else(|| "".to_string()),
-        })
-        .await?;
-      if !check {
-        return Err(ApiError::err("captcha_incorrect").into());
-      }
+    if !no_admins && !check_registration_challenge(site.as_ref(), data, context).await? {
+      return Err(ApiError::err("captcha_incorrect").into());
     }
 
-    check_slurs(&data.username)?;
-
     let actor_keypair = generate_actor_keypair()?;
-    if !is_valid_username(&data.username) {
-      return Err(ApiError::err("invalid_username").into());
-    }
     let actor_id = generate_apub_endpoint(EndpointType::Person, &data.username)?;
 
     // We have to create both a person, and local_user
@@ -223,14 +420,18 @@ impl Perform for Register {
     {
       Ok(u) => u,
       Err(_) => {
-        return Err(ApiError::err("user_already_exists").into());
+        return Err(
+          ApiError::new(ApiErrorCode::UserAlreadyExists)
+            .with_field("username")
+            .into(),
+        );
       }
     };
 
     // Create the local user
     let local_user_form = LocalUserForm {
       person_id: inserted_person.id,
-      email: Some(data.email.to_owned()),
+      email: Some(email),
       matrix_user_id: None,
       password_encrypted: data.password.to_owned(),
       admin: Some(no_admins),
@@ -241,6 +442,9 @@ impl Perform for Register {
       lang: Some("browser".into()),
       show_avatars: Some(true),
       send_notifications_to_email: Some(false),
+      email_digest_frequency: Some(EmailDigestFrequency::Off as i16),
+      hide_last_active: Some(false),
+      hide_profile_from_federation: Some(false),
     };
 
     let inserted_local_user = match blocking(context.pool(), move |conn| {
@@ -250,12 +454,12 @@ impl Perform for Register {
     {
       Ok(lu) => lu,
       Err(e) => {
-        let err_type = if e.to_string()
+        let err = if e.to_string()
           == "duplicate key value violates unique constraint \"local_user_email_key\""
         {
-          "email_already_exists"
+          ApiError::new(ApiErrorCode::EmailAlreadyExists).with_field("email")
         } else {
-          "user_already_exists"
+          ApiError::new(ApiErrorCode::UserAlreadyExists).with_field("username")
         };
 
         // If the local user creation errored, then delete that person
@@ -264,7 +468,7 @@ impl Perform for Register {
         })
         .await??;
 
-        return Err(ApiError::err(err_type).into());
+        return Err(err.into());
       }
     };
 
@@ -297,6 +501,13 @@ impl Perform for Register {
             followers_url: Some(generate_followers_url(&actor_id)?),
             inbox_url: Some(generate_inbox_url(&actor_id)?),
             shared_inbox_url: Some(Some(generate_shared_inbox_url(&actor_id)?)),
+            downvotes_disabled: None,
+            requires_post_approval: None,
+            theme_color: None,
+            short_description: None,
+            default_comment_sort: None,
+            require_post_tag: None,
+            manual_follow_approval: None,
           };
           blocking(context.pool(), move |conn| {
             Community::create(conn, &community_form)
@@ -332,7 +543,7 @@ impl Perform for Register {
 
     // Return the jwt
     Ok(LoginResponse {
-      jwt: Claims::jwt(inserted_local_user.id, Settings::get().hostname())?,
+      jwt: create_login_session(inserted_local_user.id, &data.client, context.pool()).await?,
     })
   }
 }
@@ -346,41 +557,75 @@ impl Perform for GetCaptcha {
     context: &Data<LemmyContext>,
     _websocket_id: Option<ConnectionId>,
   ) -> Result<Self::Response, LemmyError> {
-    let captcha_settings = Settings::get().captcha();
-
-    if !captcha_settings.enabled {
+    // Sites with other registration challenges need GetRegistrationChallenge
+    if !Settings::get().captcha().enabled
+      || read_registration_challenge(context).await? != RegistrationChallenge::Captcha
+    {
       return Ok(GetCaptchaResponse { ok: None });
     }
 
-    let captcha = match captcha_settings.difficulty.as_str() {
-      "easy" => gen(Difficulty::Easy),
-      "medium" => gen(Difficulty::Medium),
-      "hard" => gen(Difficulty::Hard),
-      _ => gen(Difficulty::Medium),
-    };
-
-    let answer = captcha.chars_as_string();
-
-    let png_byte_array = captcha.as_png().expect("failed to generate captcha");
-
-    let png = base64::encode(png_byte_array);
-
-    let uuid = uuid::Uuid::new_v4().to_string();
+    Ok(GetCaptchaResponse {
+      ok: Some(generate_captcha(context)),
+    })
+  }
+}
 
-    let wav = captcha_espeak_wav_base64(&answer).ok();
+#[async_trait::async_trait(?Send)]
+impl Perform for GetRegistrationChallenge {
+  type Response = GetRegistrationChallengeResponse;
 
-    let captcha_item = CaptchaItem {
-      answer,
-      uuid: uuid.to_owned(),
-      expires: naive_now() + Duration::minutes(10), // expires in 10 minutes
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<Self::Response, LemmyError> {
+    let site = blocking(context.pool(), move |conn| Site::read_simple(conn))
+      .await?
+      .ok();
+    let type_ = site
+      .as_ref()
+      .map(|site| RegistrationChallenge::from_i16(site.registration_challenge))
+      .unwrap_or_default();
+    let mut res = RegistrationChallengeResponse {
+      type_,
+      uuid: String::new(),
+      captcha: None,
+      proof_of_work: None,
+      question: None,
     };
 
-    // Stores the captcha item on the queue
-    context.chat_server().do_send(captcha_item);
+    match type_ {
+      RegistrationChallenge::Captcha => {
+        if !Settings::get().captcha().enabled {
+          return Ok(GetRegistrationChallengeResponse { ok: None });
+        }
+        let captcha = generate_captcha(context);
+        res.uuid = captcha.uuid.to_owned();
+        res.captcha = Some(captcha);
+      }
+      RegistrationChallenge::ProofOfWork => {
+        let challenge = generate_random_string();
+        res.uuid = store_challenge_answer(context, challenge.to_owned());
+        res.proof_of_work = Some(ProofOfWorkChallenge {
+          challenge,
+          difficulty: site
+            .map(|site| site.proof_of_work_difficulty)
+            .unwrap_or_default(),
+        });
+      }
+      RegistrationChallenge::Question => {
+        let questions =
+          blocking(context.pool(), move |conn| RegistrationQuestion::list(conn)).await??;
+        let question = match questions.choose(&mut rand::thread_rng()) {
+          Some(question) => question,
+          None => return Ok(GetRegistrationChallengeResponse { ok: None }),
+        };
+        res.uuid = store_challenge_answer(context, question.id.to_string());
+        res.question = Some(question.question.to_owned());
+      }
+    }
 
-    Ok(GetCaptchaResponse {
-      ok: Some(CaptchaResponse { png, uuid, wav }),
-    })
+    Ok(GetRegistrationChallengeResponse { ok: Some(res) })
   }
 }
 
@@ -394,10 +639,13 @@ impl Perform for SaveUserSettings {
     _websocket_id: Option<ConnectionId>,
   ) -> Result<LoginResponse, LemmyError> {
     let data: &SaveUserSettings = &self;
-    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+    let local_user_view = get_local_user_view_from_login_jwt(&data.auth, context.pool()).await?;
 
     let avatar = diesel_option_overwrite_to_url(&data.avatar)?;
     let banner = diesel_option_overwrite_to_url(&data.banner)?;
+    if site_requires_local_images(context.pool()).await? {
+      check_images_are_local(&[("avatar", &avatar), ("banner", &banner)])?;
+    }
     let email = diesel_option_overwrite(&data.email);
     let bio = diesel_option_overwrite(&data.bio);
     let preferred_username = diesel_option_overwrite(&data.preferred_username);
@@ -415,37 +663,40 @@ impl Perform for SaveUserSettings {
       }
     }
 
+    check_old_password(
+      &local_user_view.local_user.email,
+      &local_user_view.local_user.password_encrypted,
+      &email,
+      &data.new_password,
+      &data.old_password,
+    )?;
+    let old_email = local_user_view.local_user.email.to_owned();
+    let email_changed = email_changes(&old_email, &email);
+
     let local_user_id = local_user_view.local_user.id;
     let person_id = local_user_view.person.id;
+    let person_name = local_user_view.person.name.to_owned();
     let password_encrypted = match &data.new_password {
       Some(new_password) => {
         match &data.new_password_verify {
           Some(new_password_verify) => {
-            password_length_check(&new_password)?;
+            password_length_check(&new_password).map_err(|e| e.with_field("new_password"))?;
 
             // Make sure passwords match
             if new_password != new_password_verify {
               return Err(ApiError::err("passwords_dont_match").into());
             }
 
-            // Check the old password
-            match &data.old_password {
-              Some(old_password) => {
-                let valid: bool =
-                  verify(old_password, &local_user_view.local_user.password_encrypted)
-                    .unwrap_or(false);
-                if !valid {
-                  return Err(ApiError::err("password_incorrect").into());
-                }
-                let new_password = new_password.to_owned();
-                let user = blocking(context.pool(), move |conn| {
-                  LocalUser::update_password(conn, local_user_id, &new_password)
-                })
-                .await??;
-                user.password_encrypted
-              }
-              None => return Err(ApiError::err("password_incorrect").into()),
-            }
+            // Log out everywhere else
+            let new_password = new_password.to_owned();
+            let session_id = Claims::decode(&data.auth)?.claims.session_id;
+            let user = blocking(context.pool(), move |conn| -> Result<_, LemmyError> {
+              let user = LocalUser::update_password(conn, local_user_id, &new_password)?;
+              LoginSession::revoke_all(conn, local_user_id, session_id)?;
+              Ok(user)
+            })
+            .await??;
+            user.password_encrypted
           }
           None => return Err(ApiError::err("passwords_dont_match").into()),
         }
@@ -455,6 +706,32 @@ impl Perform for SaveUserSettings {
 
     let default_listing_type = data.default_listing_type;
     let default_sort_type = data.default_sort_type;
+    // It is only allowed for the listings of single communities
+    if default_sort_type == Some(SortType::MostViews as i16) {
+      return Err(
+        ApiError::err("invalid_default_sort_type")
+          .with_field("default_sort_type")
+          .into(),
+      );
+    }
+
+    let current_frequency =
+      EmailDigestFrequency::from_i16(local_user_view.local_user.email_digest_frequency);
+    let email_digest_frequency = match (
+      data.email_digest_frequency,
+      data.send_notifications_to_email,
+    ) {
+      (Some(frequency), _) => frequency,
+      (None, Some(false)) => EmailDigestFrequency::Off,
+      (None, Some(true)) if current_frequency == EmailDigestFrequency::Off => {
+        EmailDigestFrequency::Immediate
+      }
+      (None, _) => current_frequency,
+    };
+    let frequency_changed = email_digest_frequency != current_frequency;
+
+    let profile_changed =
+      avatar.is_some() || banner.is_some() || preferred_username.is_some() || bio.is_some();
 
     let person_form = PersonForm {
       name: local_user_view.person.name,
@@ -479,7 +756,7 @@ impl Perform for SaveUserSettings {
       Person::update(conn, person_id, &person_form)
     })
     .await?;
-    let _updated_person: Person = match person_res {
+    let updated_person: Person = match person_res {
       Ok(p) => p,
       Err(_) => {
         return Err(ApiError::err("user_already_exists").into());
@@ -498,35 +775,109 @@ impl Perform for SaveUserSettings {
       default_listing_type,
       lang: data.lang.to_owned(),
       show_avatars: data.show_avatars,
-      send_notifications_to_email: data.send_notifications_to_email,
+      send_notifications_to_email: Some(email_digest_frequency != EmailDigestFrequency::Off),
+      email_digest_frequency: Some(email_digest_frequency as i16),
+      hide_last_active: data.hide_last_active,
+      hide_profile_from_federation: data.hide_profile_from_federation,
     };
 
     let local_user_res = blocking(context.pool(), move |conn| {
       LocalUser::update(conn, local_user_id, &local_user_form)
     })
     .await?;
-    let updated_local_user = match local_user_res {
-      Ok(u) => u,
-      Err(e) => {
-        let err_type = if e.to_string()
-          == "duplicate key value violates unique constraint \"local_user_email_key\""
-        {
-          "email_already_exists"
-        } else {
-          "user_already_exists"
-        };
+    if let Err(e) = local_user_res {
+      let err_type = if e.to_string()
+        == "duplicate key value violates unique constraint \"local_user_email_key\""
+      {
+        "email_already_exists"
+      } else {
+        "user_already_exists"
+      };
 
-        return Err(ApiError::err(err_type).into());
+      return Err(ApiError::err(err_type).into());
+    }
+
+    // Let remote instances know about the new profile
+    if profile_changed {
+      updated_person.send_update(context).await?;
+    }
+
+    // Let the previous address know, in case someone else took over the account
+    if email_changed {
+      if let Some(old_email) = old_email {
+        send_email_changed_notification(&old_email, &person_name);
       }
-    };
+    }
 
-    // Return the jwt
+    // Don't put notifications from before the change into the first digest
+    if frequency_changed {
+      blocking(context.pool(), move |conn| {
+        LocalUser::update_last_email_digest(conn, local_user_id, naive_now())
+      })
+      .await??;
+    }
+
+    if let Some(discussion_languages) = data.discussion_languages.to_owned() {
+      blocking(context.pool(), move |conn| {
+        Language::update_local_user_languages(conn, local_user_id, &discussion_languages)
+      })
+      .await?
+      .map_err(|_| {
+        ApiError::new(ApiErrorCode::LanguageNotAllowed).with_field("discussion_languages")
+      })?;
+    }
+
+    // The session stays the same, so the jwt is still valid
     Ok(LoginResponse {
-      jwt: Claims::jwt(updated_local_user.id, Settings::get().hostname())?,
+      jwt: data.auth.to_owned(),
     })
   }
 }
 
+/// Whether `email` from the settings form differs from the current address
+fn email_changes(current_email: &Option<String>, email: &Option<Option<String>>) -> bool {
+  match email {
+    Some(email) => email != current_email,
+    None => false,
+  }
+}
+
+/// Changing the email or the password requires the old password, so that a hijacked session
+/// can't take over the account. All other settings can be changed without it.
+fn check_old_password(
+  current_email: &Option<String>,
+  password_encrypted: &str,
+  email: &Option<Option<String>>,
+  new_password: &Option<String>,
+  old_password: &Option<String>,
+) -> Result<(), LemmyError> {
+  if !email_changes(current_email, email) && new_password.is_none() {
+    return Ok(());
+  }
+  let valid = match old_password {
+    Some(old_password) => verify(old_password, password_encrypted).unwrap_or(false),
+    None => false,
+  };
+  if valid {
+    Ok(())
+  } else {
+    Err(ApiError::err("password_incorrect").into())
+  }
+}
+
+fn send_email_changed_notification(old_email: &str, person_name: &str) {
+  // TODO no i18n support here.
+  let hostname = Settings::get().hostname();
+  let subject = &format!("Your email was changed - {}", hostname);
+  let html = &format!(
+    "<h1>Your email was changed</h1><br><div>The email address of your account {} on {} was changed. If this wasn't you, please contact the admins of {}.</div>",
+    person_name, hostname, hostname
+  );
+  if let Err(e) = send_email(subject, old_email, person_name, html) {
+    error!("{}", e);
+  }
+}
+
 #[async_trait::async_trait(?Send)]
 impl Perform for GetPersonDetails {
   type Response = GetPersonDetailsResponse;
@@ -544,41 +895,34 @@ impl Perform for GetPersonDetails {
       None => false,
     };
 
-    let sort = SortType::from_str(&data.sort)?;
+    let sort = data.sort.unwrap_or_default();
+    let person_id = local_user_view.map(|uv| uv.person.id);
+    check_sort_allowed(sort, data.community_id, None, person_id, context.pool()).await?;
 
     let username = data
       .username
       .to_owned()
       .unwrap_or_else(|| "admin".to_string());
-    let person_details_id = match data.person_id {
-      Some(id) => id,
-      None => {
-        let person = blocking(context.pool(), move |conn| {
-          Person::find_by_name(conn, &username)
-        })
-        .await?;
-        match person {
-          Ok(p) => p.id,
-          Err(_e) => return Err(ApiError::err("couldnt_find_that_username_or_email").into()),
-        }
-      }
-    };
-
-    let person_id = local_user_view.map(|uv| uv.person.id);
-
-    // You don't need to return settings for the user, since this comes back with GetSite
-    // `my_user`
-    let person_view = blocking(context.pool(), move |conn| {
-      PersonViewSafe::read(conn, person_details_id)
-    })
-    .await??;
-
+    let person_details_id = data.person_id;
     let page = data.page;
     let limit = data.limit;
     let saved_only = data.saved_only;
     let community_id = data.community_id;
 
-    let (posts, comments) = blocking(context.pool(), move |conn| {
+    // Everything is read on a single pooled connection
+    let mut res = blocking_read(context.read_pool(), move |conn| -> Result<_, LemmyError> {
+      let person_details_id = match person_details_id {
+        Some(id) => id,
+        None => match Person::find_by_name(conn, &username) {
+          Ok(p) => p.id,
+          Err(_e) => return Err(ApiError::err("couldnt_find_that_username_or_email").into()),
+        },
+      };
+
+      // You don't need to return settings for the user, since this comes back with GetSite
+      // `my_user`
+      let person_view = PersonViewSafe::read(conn, person_details_id)?;
+
       let mut posts_query = PostQueryBuilder::create(conn)
         .sort(&sort)
         .show_nsfw(show_nsfw)
@@ -604,32 +948,174 @@ impl Perform for GetPersonDetails {
 
       let posts = posts_query.list()?;
       let comments = comments_query.list()?;
+      let pinned_posts = PostView::list_pinned_for_person(conn, person_details_id, person_id)?;
 
-      Ok((posts, comments)) as Result<_, LemmyError>
+      // The totals are only meaningful for the person's own content. They are counted with the
+      // same filters as the listings above, over all pages.
+      let (total_posts, total_comments) = if saved_only {
+        (None, None)
+      } else {
+        let total_posts = PostQueryBuilder::create(conn)
+          .show_nsfw(show_nsfw)
+          .community_id(community_id)
+          .creator_id(person_details_id)
+          .my_person_id(person_id)
+          .count()?;
+        let total_comments = CommentQueryBuilder::create(conn)
+          .creator_id(person_details_id)
+          .my_person_id(person_id)
+          .count()?;
+        (Some(total_posts), Some(total_comments))
+      };
+
+      let follows = if person_id == Some(person_details_id) {
+        CommunityFollowerView::for_person(conn, person_details_id, false, None, None)?
+      } else {
+        vec![]
+      };
+      let moderates = CommunityModeratorView::for_person(conn, person_details_id)?;
+      let note = match person_id {
+        Some(person_id) => PersonNote::read(conn, person_id, person_details_id).ok(),
+        None => None,
+      };
+
+      Ok(GetPersonDetailsResponse {
+        person_view,
+        note,
+        pinned_posts,
+        follows,
+        moderates,
+        comments,
+        posts,
+        total_posts,
+        total_comments,
+      })
     })
     .await??;
 
-    let mut follows = vec![];
-    if let Some(pid) = person_id {
-      if pid == person_details_id {
-        follows = blocking(context.pool(), move |conn| {
-          CommunityFollowerView::for_person(conn, person_details_id)
-        })
-        .await??;
-      }
+    hide_downvotes_if_disabled(&mut res.pinned_posts, context.pool()).await?;
+    hide_downvotes_if_disabled(&mut res.posts, context.pool()).await?;
+    hide_downvotes_if_disabled(&mut res.comments, context.pool()).await?;
+    Ok(res)
+  }
+}
+
+/// Pins one of the person's own posts to their profile. The pinned posts are federated as the
+/// `featured` collection of the person, so remote instances get a profile update.
+#[async_trait::async_trait(?Send)]
+impl Perform for PinPersonPost {
+  type Response = PostResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<PostResponse, LemmyError> {
+    let data: &PinPersonPost = &self;
+    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+    let person_id = local_user_view.person.id;
+
+    let post = get_post(data.post_id, context.pool()).await?;
+    if post.creator_id != person_id {
+      return Err(ApiError::err("no_post_edit_allowed").into());
+    }
+
+    let pinned_post_form = PersonPinnedPostForm {
+      person_id,
+      post_id: post.id,
     };
-    let moderates = blocking(context.pool(), move |conn| {
-      CommunityModeratorView::for_person(conn, person_details_id)
+    if data.pinned {
+      if post.deleted || post.removed {
+        return Err(ApiError::err("couldnt_find_post").into());
+      }
+      let pin = move |conn: &'_ _| PersonPinnedPost::pin(conn, &pinned_post_form);
+      match blocking(context.pool(), pin).await? {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(ApiError::err("too_many_pinned_posts").into()),
+        Err(_) => return Err(ApiError::err("couldnt_pin_post").into()),
+      }
+    } else {
+      let unpin = move |conn: &'_ _| PersonPinnedPost::unpin(conn, &pinned_post_form);
+      if blocking(context.pool(), unpin).await?.is_err() {
+        return Err(ApiError::err("couldnt_pin_post").into());
+      }
+    }
+
+    local_user_view.person.send_update(context).await?;
+
+    let post_id = post.id;
+    let mut post_view = blocking(context.pool(), move |conn| {
+      PostView::read(conn, post_id, Some(person_id))
     })
     .await??;
+    hide_downvotes_if_disabled(std::slice::from_mut(&mut post_view), context.pool()).await?;
 
-    // Return the jwt
-    Ok(GetPersonDetailsResponse {
-      person_view,
-      follows,
-      moderates,
-      comments,
-      posts,
+    Ok(PostResponse {
+      post_view,
+      federation: None,
+    })
+  }
+}
+
+/// Notes are private, so they are neither federated nor checked for slurs
+#[async_trait::async_trait(?Send)]
+impl Perform for SetPersonNote {
+  type Response = SetPersonNoteResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<SetPersonNoteResponse, LemmyError> {
+    let data: &SetPersonNote = &self;
+    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+    let creator_person_id = local_user_view.person.id;
+    let target_person_id = data.person_id;
+
+    let note_text = data.note.trim().to_owned();
+    if note_text.is_empty() {
+      blocking(context.pool(), move |conn| {
+        PersonNote::remove(conn, creator_person_id, target_person_id)
+      })
+      .await??;
+      return Ok(SetPersonNoteResponse {
+        person_id: target_person_id,
+        note: None,
+      });
+    }
+
+    check_max_length(
+      &note_text,
+      MAX_PERSON_NOTE_LENGTH,
+      ApiErrorCode::PersonNoteTooLong,
+    )
+    .map_err(|e| e.with_field("note"))?;
+    let color = data.color.to_owned().filter(|c| !c.is_empty());
+    if let Some(color) = &color {
+      if !is_valid_theme_color(color) {
+        return Err(
+          ApiError::new(ApiErrorCode::InvalidThemeColor)
+            .with_field("color")
+            .into(),
+        );
+      }
+    }
+
+    let form = PersonNoteForm {
+      creator_person_id,
+      target_person_id,
+      note: note_text,
+      color,
+      updated: Some(naive_now()),
+    };
+    // Fails if the person doesn't exist
+    let note = blocking(context.pool(), move |conn| PersonNote::set(conn, &form))
+      .await?
+      .map_err(|_| ApiError::err("couldnt_set_person_note").with_field("person_id"))?;
+
+    Ok(SetPersonNoteResponse {
+      person_id: target_person_id,
+      note: Some(note),
     })
   }
 }
@@ -670,6 +1156,7 @@ impl Perform for AddAdmin {
     };
 
     blocking(context.pool(), move |conn| ModAdd::create(conn, &form)).await??;
+    context.site_cache().invalidate().await;
 
     let site_creator_id = blocking(context.pool(), move |conn| {
       Site::read(conn, 1).map(|s| s.creator_id)
@@ -711,6 +1198,8 @@ impl Perform for BanPerson {
     // Make sure user is an admin
     is_admin(&local_user_view)?;
 
+    let expires = parse_expires(data.expires, data.expires_rfc3339.as_deref())?;
+
     let ban = data.ban;
     let banned_person_id = data.person_id;
     let ban_person = move |conn: &'_ _| Person::ban_person(conn, banned_person_id, ban);
@@ -740,11 +1229,6 @@ impl Perform for BanPerson {
     }
 
     // Mod tables
-    let expires = match data.expires {
-      Some(time) => Some(naive_from_unix(time)),
-      None => None,
-    };
-
     let form = ModBanForm {
       mod_person_id: local_user_view.person.id,
       other_person_id: data.person_id,
@@ -754,6 +1238,7 @@ impl Perform for BanPerson {
     };
 
     blocking(context.pool(), move |conn| ModBan::create(conn, &form)).await??;
+    context.site_cache().invalidate().await;
 
     let person_id = data.person_id;
     let person_view = blocking(context.pool(), move |conn| {
@@ -776,6 +1261,50 @@ impl Perform for BanPerson {
   }
 }
 
+#[async_trait::async_trait(?Send)]
+impl Perform for MergePersons {
+  type Response = MergePersonsResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<MergePersonsResponse, LemmyError> {
+    let data: &MergePersons = &self;
+    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+
+    // Make sure user is an admin
+    is_admin(&local_user_view)?;
+
+    let keep_person_id = data.keep_person_id;
+    let merge_person_id = data.merge_person_id;
+    if keep_person_id == merge_person_id {
+      return Err(ApiError::err("cant_merge_person_with_itself").into());
+    }
+
+    blocking_transaction(context.pool(), move |conn| {
+      // A local person has an account attached, which would be deleted with it. This also refuses
+      // to merge two local accounts.
+      let merge_person = Person::read(conn, merge_person_id)?;
+      if merge_person.local {
+        return Err(ApiError::err("cant_merge_local_person").into());
+      }
+      Person::merge(conn, keep_person_id, merge_person_id)?;
+      Ok(())
+    })
+    .await?;
+    // The merged person might have been the site creator
+    context.site_cache().invalidate().await;
+
+    let person_view = blocking(context.pool(), move |conn| {
+      PersonViewSafe::read(conn, keep_person_id)
+    })
+    .await??;
+
+    Ok(MergePersonsResponse { person_view })
+  }
+}
+
 #[async_trait::async_trait(?Send)]
 impl Perform for GetReplies {
   type Response = GetRepliesResponse;
@@ -786,19 +1315,22 @@ impl Perform for GetReplies {
     _websocket_id: Option<ConnectionId>,
   ) -> Result<GetRepliesResponse, LemmyError> {
     let data: &GetReplies = &self;
-    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+    let local_user_view =
+      get_local_user_view_from_jwt_read_only(&data.auth, context.pool()).await?;
 
-    let sort = SortType::from_str(&data.sort)?;
+    let sort = data.sort.unwrap_or_default();
 
     let page = data.page;
     let limit = data.limit;
     let unread_only = data.unread_only;
+    let community_id = data.community_id;
     let person_id = local_user_view.person.id;
     let replies = blocking(context.pool(), move |conn| {
       CommentQueryBuilder::create(conn)
         .sort(&sort)
         .unread_only(unread_only)
         .recipient_id(person_id)
+        .community_id(community_id)
         .my_person_id(person_id)
         .page(page)
         .limit(limit)
@@ -820,27 +1352,57 @@ impl Perform for GetPersonMentions {
     _websocket_id: Option<ConnectionId>,
   ) -> Result<GetPersonMentionsResponse, LemmyError> {
     let data: &GetPersonMentions = &self;
-    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+    let local_user_view =
+      get_local_user_view_from_jwt_read_only(&data.auth, context.pool()).await?;
 
-    let sort = SortType::from_str(&data.sort)?;
+    let sort = data.sort.unwrap_or_default();
 
     let page = data.page;
     let limit = data.limit;
     let unread_only = data.unread_only;
+    let community_id = data.community_id;
     let person_id = local_user_view.person.id;
-    let mentions = blocking(context.pool(), move |conn| {
-      PersonMentionQueryBuilder::create(conn)
-        .recipient_id(person_id)
-        .my_person_id(person_id)
-        .sort(&sort)
-        .unread_only(unread_only)
-        .page(page)
-        .limit(limit)
-        .list()
-    })
-    .await??;
+    let type_ = data.type_.unwrap_or_default();
 
-    Ok(GetPersonMentionsResponse { mentions })
+    let mentions = if type_ == MentionType::Posts {
+      vec![]
+    } else {
+      blocking(context.pool(), move |conn| {
+        PersonMentionQueryBuilder::create(conn)
+          .recipient_id(person_id)
+          .my_person_id(person_id)
+          .community_id(community_id)
+          .sort(&sort)
+          .unread_only(unread_only)
+          .page(page)
+          .limit(limit)
+          .list()
+      })
+      .await??
+    };
+
+    let post_mentions = if type_ == MentionType::Comments {
+      vec![]
+    } else {
+      blocking(context.pool(), move |conn| {
+        PersonPostMentionQueryBuilder::create(conn)
+          .recipient_id(person_id)
+          .my_person_id(person_id)
+          .community_id(community_id)
+          .sort(&sort)
+          .unread_only(unread_only)
+          .page(page)
+          .limit(limit)
+          .list()
+      })
+      .await??
+    };
+
+    Ok(GetPersonMentionsResponse {
+      type_,
+      mentions,
+      post_mentions,
+    })
   }
 }
 
@@ -887,6 +1449,47 @@ impl Perform for MarkPersonMentionAsRead {
   }
 }
 
+#[async_trait::async_trait(?Send)]
+impl Perform for MarkPersonPostMentionAsRead {
+  type Response = PersonPostMentionResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<PersonPostMentionResponse, LemmyError> {
+    let data: &MarkPersonPostMentionAsRead = &self;
+    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+
+    let person_post_mention_id = data.person_post_mention_id;
+    let read_mention = blocking(context.pool(), move |conn| {
+      PersonPostMention::read(conn, person_post_mention_id)
+    })
+    .await??;
+
+    if local_user_view.person.id != read_mention.recipient_id {
+      return Err(ApiError::err("couldnt_update_post").into());
+    }
+
+    let read = data.read;
+    let update_mention =
+      move |conn: &'_ _| PersonPostMention::update_read(conn, person_post_mention_id, read);
+    if blocking(context.pool(), update_mention).await?.is_err() {
+      return Err(ApiError::err("couldnt_update_post").into());
+    };
+
+    let person_id = local_user_view.person.id;
+    let person_post_mention_view = blocking(context.pool(), move |conn| {
+      PersonPostMentionView::read(conn, person_post_mention_id, Some(person_id))
+    })
+    .await??;
+
+    Ok(PersonPostMentionResponse {
+      person_post_mention_view,
+    })
+  }
+}
+
 #[async_trait::async_trait(?Send)]
 impl Perform for MarkAllAsRead {
   type Response = GetRepliesResponse;
@@ -932,6 +1535,16 @@ impl Perform for MarkAllAsRead {
       return Err(ApiError::err("couldnt_update_comment").into());
     }
 
+    // Mark all mentions in posts as read
+    let update_post_mentions =
+      move |conn: &'_ _| PersonPostMention::mark_all_as_read(conn, person_id);
+    if blocking(context.pool(), update_post_mentions)
+      .await?
+      .is_err()
+    {
+      return Err(ApiError::err("couldnt_update_post").into());
+    }
+
     // Mark all private_messages as read
     let update_pm = move |conn: &'_ _| PrivateMessage::mark_all_as_read(conn, person_id);
     if blocking(context.pool(), update_pm).await?.is_err() {
@@ -952,7 +1565,7 @@ impl Perform for DeleteAccount {
     _websocket_id: Option<ConnectionId>,
   ) -> Result<LoginResponse, LemmyError> {
     let data: &DeleteAccount = &self;
-    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+    let local_user_view = get_local_user_view_from_login_jwt(&data.auth, context.pool()).await?;
 
     // Verify the password
     let valid: bool = verify(
@@ -977,8 +1590,11 @@ impl Perform for DeleteAccount {
       return Err(ApiError::err("couldnt_update_post").into());
     }
 
-    blocking(context.pool(), move |conn| {
-      Person::delete_account(conn, person_id)
+    let local_user_id = local_user_view.local_user.id;
+    blocking(context.pool(), move |conn| -> Result<_, LemmyError> {
+      Person::delete_account(conn, person_id)?;
+      LoginSession::revoke_all(conn, local_user_id, None)?;
+      Ok(())
     })
     .await??;
 
@@ -1054,31 +1670,182 @@ impl Perform for PasswordChange {
     })
     .await??;
 
-    password_length_check(&data.password)?;
+    password_length_check(&data.password).map_err(|e| e.with_field("password"))?;
 
     // Make sure passwords match
     if data.password != data.password_verify {
       return Err(ApiError::err("passwords_dont_match").into());
     }
 
-    // Update the user with the new password
+    // Update the user with the new password, and log them out everywhere
     let password = data.password.clone();
-    let updated_local_user = match blocking(context.pool(), move |conn| {
-      LocalUser::update_password(conn, local_user_id, &password)
-    })
+    let updated_local_user = match blocking(
+      context.pool(),
+      move |conn| -> Result<_, diesel::result::Error> {
+        let updated_local_user = LocalUser::update_password(conn, local_user_id, &password)?;
+        LoginSession::revoke_all(conn, local_user_id, None)?;
+        Ok(updated_local_user)
+      },
+    )
     .await?
     {
       Ok(u) => u,
       Err(_e) => return Err(ApiError::err("couldnt_update_user").into()),
     };
 
-    // Return the jwt
+    // Return the jwt of a new session
     Ok(LoginResponse {
-      jwt: Claims::jwt(updated_local_user.id, Settings::get().hostname())?,
+      jwt: create_login_session(updated_local_user.id, &data.client, context.pool()).await?,
+    })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for CreateApiToken {
+  type Response = CreateApiTokenResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<CreateApiTokenResponse, LemmyError> {
+    let data: &CreateApiToken = &self;
+    // API tokens can't create other tokens, otherwise a read only token could give itself write
+    // access
+    let local_user_view = get_local_user_view_from_login_jwt(&data.auth, context.pool()).await?;
+
+    if data.name.trim().is_empty() {
+      return Err(ApiError::err("invalid_api_token_name").into());
+    }
+    if data.scopes.is_empty() {
+      return Err(ApiError::err("invalid_scope").into());
+    }
+    let mut scopes: Vec<String> = data.scopes.iter().map(|s| s.to_string()).collect();
+    scopes.sort();
+    scopes.dedup();
+    let expires = parse_expires(data.expires, None)?;
+
+    let form = ApiTokenForm {
+      local_user_id: local_user_view.local_user.id,
+      name: data.name.trim().to_owned(),
+      scopes,
+      expires,
+    };
+    let api_token =
+      match blocking(context.pool(), move |conn| ApiToken::create(conn, &form)).await? {
+        Ok(api_token) => api_token,
+        Err(_e) => return Err(ApiError::err("couldnt_create_api_token").into()),
+      };
+
+    Ok(CreateApiTokenResponse {
+      jwt: Claims::api_token_jwt(
+        api_token.local_user_id,
+        api_token.id,
+        Settings::get().hostname(),
+      )?,
+      api_token,
     })
   }
 }
 
+#[async_trait::async_trait(?Send)]
+impl Perform for ListApiTokens {
+  type Response = ListApiTokensResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<ListApiTokensResponse, LemmyError> {
+    let data: &ListApiTokens = &self;
+    let local_user_view = get_local_user_view_from_login_jwt(&data.auth, context.pool()).await?;
+
+    let local_user_id = local_user_view.local_user.id;
+    let api_tokens = blocking(context.pool(), move |conn| {
+      ApiToken::list_for_local_user(conn, local_user_id)
+    })
+    .await??;
+
+    Ok(ListApiTokensResponse { api_tokens })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for RevokeApiToken {
+  type Response = RevokeApiTokenResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<RevokeApiTokenResponse, LemmyError> {
+    let data: &RevokeApiToken = &self;
+    let local_user_view = get_local_user_view_from_login_jwt(&data.auth, context.pool()).await?;
+
+    // The token is deleted, so that the next request which uses it fails
+    let api_token_id = data.api_token_id;
+    let local_user_id = local_user_view.local_user.id;
+    let revoked = blocking(context.pool(), move |conn| {
+      ApiToken::revoke(conn, api_token_id, local_user_id)
+    })
+    .await??;
+    if revoked == 0 {
+      return Err(ApiError::err("couldnt_find_api_token").into());
+    }
+
+    Ok(RevokeApiTokenResponse {})
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for ListSessions {
+  type Response = ListSessionsResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<ListSessionsResponse, LemmyError> {
+    let data: &ListSessions = &self;
+    let local_user_view = get_local_user_view_from_login_jwt(&data.auth, context.pool()).await?;
+
+    let local_user_id = local_user_view.local_user.id;
+    let sessions = blocking(context.pool(), move |conn| {
+      LoginSession::list_for_local_user(conn, local_user_id)
+    })
+    .await??;
+
+    Ok(ListSessionsResponse { sessions })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for RevokeSession {
+  type Response = RevokeSessionResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<RevokeSessionResponse, LemmyError> {
+    let data: &RevokeSession = &self;
+    let local_user_view = get_local_user_view_from_login_jwt(&data.auth, context.pool()).await?;
+
+    // The session is deleted, so that the next request with its jwt fails
+    let session_id = data.session_id;
+    let local_user_id = local_user_view.local_user.id;
+    let revoked = blocking(context.pool(), move |conn| {
+      LoginSession::revoke(conn, session_id, local_user_id)
+    })
+    .await??;
+    if revoked == 0 {
+      return Err(ApiError::err("couldnt_find_session").into());
+    }
+
+    Ok(RevokeSessionResponse {})
+  }
+}
+
 #[async_trait::async_trait(?Send)]
 impl Perform for CreatePrivateMessage {
   type Response = PrivateMessageResponse;
@@ -1091,7 +1858,8 @@ impl Perform for CreatePrivateMessage {
     let data: &CreatePrivateMessage = &self;
     let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
 
-    let content_slurs_removed = remove_slurs(&data.content.to_owned());
+    let content_slurs_removed =
+      remove_slurs(&data.content.to_owned(), &context.slur_filter().regex());
 
     let private_message_form = PrivateMessageForm {
       content: content_slurs_removed.to_owned(),
@@ -1157,7 +1925,7 @@ impl Perform for CreatePrivateMessage {
     })
     .await?
     {
-      if local_recipient.local_user.send_notifications_to_email {
+      if sends_immediate_emails(&local_recipient) {
         send_email_to_user(
           &local_recipient,
           "Private Message from",
@@ -1202,7 +1970,7 @@ impl Perform for EditPrivateMessage {
     }
 
     // Doing the update
-    let content_slurs_removed = remove_slurs(&data.content);
+    let content_slurs_removed = remove_slurs(&data.content, &context.slur_filter().regex());
     let private_message_id = data.private_message_id;
     let updated_private_message = match blocking(context.pool(), move |conn| {
       PrivateMessage::update_content(conn, private_message_id, &content_slurs_removed)
@@ -1398,7 +2166,8 @@ impl Perform for GetPrivateMessages {
     _websocket_id: Option<ConnectionId>,
   ) -> Result<PrivateMessagesResponse, LemmyError> {
     let data: &GetPrivateMessages = &self;
-    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+    let local_user_view =
+      get_local_user_view_from_jwt_read_only(&data.auth, context.pool()).await?;
     let person_id = local_user_view.person.id;
 
     let page = data.page;
@@ -1429,7 +2198,8 @@ impl Perform for GetReportCount {
     websocket_id: Option<ConnectionId>,
   ) -> Result<GetReportCountResponse, LemmyError> {
     let data: &GetReportCount = &self;
-    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+    let local_user_view =
+      get_local_user_view_from_jwt_read_only(&data.auth, context.pool()).await?;
 
     let person_id = local_user_view.person.id;
     let community_id = data.community;
@@ -1467,10 +2237,125 @@ impl Perform for GetReportCount {
     context.chat_server().do_send(SendUserRoomMessage {
       op: UserOperation::GetReportCount,
       response: res.clone(),
-      local_recipient_id: local_user_view.person.id,
+      local_recipient_id: local_user_view.local_user.id,
       websocket_id,
     });
 
     Ok(res)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    local_user::check_old_password,
+    test::{create_test_user, error_code, test_context},
+    Perform,
+  };
+  use bcrypt::hash;
+  use lemmy_api_structs::person::{CreateApiToken, GetReplies, MarkAllAsRead};
+  use lemmy_db_queries::{ApiTokenScope, Crud};
+  use lemmy_db_schema::{naive_now, source::person::Person};
+  use lemmy_utils::ApiErrorCode;
+  use serial_test::serial;
+
+  fn check(
+    email: Option<Option<&str>>,
+    new_password: Option<&str>,
+    old_password: Option<&str>,
+  ) -> bool {
+    let current_email = Some("current@example.com".to_string());
+    let password_encrypted = hash("old_password", 4).unwrap();
+    check_old_password(
+      &current_email,
+      &password_encrypted,
+      &email.map(|e| e.map(|e| e.to_string())),
+      &new_password.map(|p| p.to_string()),
+      &old_password.map(|p| p.to_string()),
+    )
+    .is_ok()
+  }
+
+  #[test]
+  fn test_other_settings_dont_need_password() {
+    assert!(check(None, None, None));
+    assert!(check(None, None, Some("wrong_password")));
+    // The settings form sends the unchanged email along
+    assert!(check(Some(Some("current@example.com")), None, None));
+  }
+
+  #[test]
+  fn test_email_change_needs_password() {
+    assert!(check(
+      Some(Some("new@example.com")),
+      None,
+      Some("old_password")
+    ));
+    assert!(!check(Some(Some("new@example.com")), None, None));
+    assert!(!check(
+      Some(Some("new@example.com")),
+      None,
+      Some("wrong_password")
+    ));
+    // Removing the email is a change too
+    assert!(check(Some(None), None, Some("old_password")));
+    assert!(!check(Some(None), None, None));
+  }
+
+  #[test]
+  fn test_password_change_needs_password() {
+    assert!(check(None, Some("new_password"), Some("old_password")));
+    assert!(!check(None, Some("new_password"), None));
+    assert!(!check(None, Some("new_password"), Some("wrong_password")));
+  }
+
+  #[test]
+  fn test_email_and_password_change_needs_password() {
+    let new_email = Some(Some("new@example.com"));
+    assert!(check(new_email, Some("new_password"), Some("old_password")));
+    assert!(!check(new_email, Some("new_password"), None));
+    assert!(!check(
+      new_email,
+      Some("new_password"),
+      Some("wrong_password")
+    ));
+  }
+
+  #[actix_rt::test]
+  #[serial]
+  async fn test_read_only_api_token() {
+    let context = test_context();
+    let conn = context.pool().get().unwrap();
+    let (_, person, jwt) = create_test_user(&conn, "api_token_person", false);
+
+    let create_token = |expires: Option<i64>| CreateApiToken {
+      name: "bot".into(),
+      scopes: vec![ApiTokenScope::Read],
+      expires,
+      auth: jwt.to_owned(),
+    };
+    let expired = create_token(Some(naive_now().timestamp() - 60))
+      .perform(&context, None)
+      .await;
+    let token = create_token(None).perform(&context, None).await.unwrap();
+    let read = GetReplies {
+      sort: None,
+      page: None,
+      limit: None,
+      unread_only: false,
+      community_id: None,
+      auth: token.jwt.to_owned(),
+    }
+    .perform(&context, None)
+    .await;
+    let write = MarkAllAsRead { auth: token.jwt }
+      .perform(&context, None)
+      .await;
+
+    Person::delete(&conn, person.id).unwrap();
+
+    assert_eq!(Some(ApiErrorCode::InvalidExpiryInPast), error_code(expired));
+    assert!(read.is_ok());
+    assert_eq!(Some(ApiErrorCode::InsufficientScope), error_code(write));
+  }
+}
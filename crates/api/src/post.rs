@@ -1,54 +1,108 @@
 use crate::{
+  can_see_deleted_persons,
+  can_view_removed,
   check_community_ban,
+  check_community_downvotes_enabled,
   check_downvotes_enabled,
+  check_sort_allowed,
   collect_moderated_communities,
+  get_listing_defaults,
   get_local_user_view_from_jwt,
   get_local_user_view_from_jwt_opt,
+  get_local_user_view_from_jwt_read_only,
+  get_post,
+  is_admin,
+  is_exempt_from_slur_filter,
   is_mod_or_admin,
+  send_vote,
+  send_vote_debounced,
   Perform,
 };
 use actix_web::web::Data;
-use lemmy_api_structs::{blocking, post::*};
+use lemmy_api_structs::{
+  blocking,
+  blocking_read,
+  blocking_transaction,
+  check_spam_rules,
+  get_content_limits,
+  hide_downvotes_if_disabled,
+  log_spam_removal,
+  post::*,
+  save_post_metadata,
+  send_local_post_notifs,
+  FederationStatus,
+  VoteTarget,
+};
 use lemmy_apub::{generate_apub_endpoint, ApubLikeableType, ApubObjectType, EndpointType};
 use lemmy_db_queries::{
-  source::post::Post_,
+  limit_and_offset,
+  source::{
+    community_tag::{CommunityTag_, MAX_POST_TAGS},
+    post::Post_,
+    spam_rule::SpamCheckContent,
+  },
   Crud,
+  DbPool,
   Likeable,
-  ListingType,
   Reportable,
   Saveable,
   SortType,
+  SpamAction,
 };
 use lemmy_db_schema::{
   naive_now,
   source::{
+    community::Community,
+    community_tag::CommunityTag,
     moderator::*,
+    person::Person,
     post::*,
     post_report::{PostReport, PostReportForm},
   },
+  CommunityId,
+  DbUrl,
+  PersonId,
+  PostId,
 };
 use lemmy_db_views::{
   comment_view::CommentQueryBuilder,
+  local_user_view::LocalUserView,
   post_report_view::{PostReportQueryBuilder, PostReportView},
-  post_view::{PostQueryBuilder, PostView},
+  post_view::{PaginationCursor, PostQueryBuilder, PostView},
 };
 use lemmy_db_views_actor::{
   community_moderator_view::CommunityModeratorView,
   community_view::CommunityView,
 };
 use lemmy_utils::{
-  request::fetch_iframely_and_pictrs_data,
-  utils::{check_slurs, check_slurs_opt, is_valid_post_title},
+  request::fetch_post_metadata,
+  settings::structs::Settings,
+  utils::{
+    check_slurs,
+    check_slurs_opt,
+    is_valid_post_title,
+    scrape_text_for_mentions,
+    scrape_text_for_new_mentions,
+  },
   ApiError,
+  ApiErrorCode,
   ConnectionId,
   LemmyError,
 };
 use lemmy_websocket::{
-  messages::{GetPostUsersOnline, SendModRoomMessage, SendPost, SendUserRoomMessage},
+  messages::{
+    GetPostUsersOnline,
+    RecordPostView,
+    SendModRoomMessage,
+    SendPost,
+    SendUserRoomMessage,
+  },
   LemmyContext,
   UserOperation,
 };
-use std::str::FromStr;
+use log::error;
+use std::collections::{HashMap, HashSet};
+use url::Url;
 
 #[async_trait::async_trait(?Send)]
 impl Perform for CreatePost {
@@ -62,54 +116,101 @@ impl Perform for CreatePost {
     let data: &CreatePost = &self;
     let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
 
-    check_slurs(&data.name)?;
-    check_slurs_opt(&data.body)?;
+    let exempt = is_exempt_from_slur_filter(data.community_id, context.pool()).await?;
+    let slur_regex = context.slur_filter().regex_for_community(exempt);
+    check_slurs(&data.name, &slur_regex)?;
+    check_slurs_opt(&data.body, &slur_regex)?;
 
     if !is_valid_post_title(&data.name) {
       return Err(ApiError::err("invalid_post_title").into());
     }
+    get_content_limits(context.pool())
+      .await?
+      .check_post(&data.body, data.url.as_ref())?;
 
     check_community_ban(local_user_view.person.id, data.community_id, context.pool()).await?;
+    let tag_ids = check_post_tags(
+      data.tags.as_deref().unwrap_or_default(),
+      data.community_id,
+      context.pool(),
+    )
+    .await?;
 
-    // Fetch Iframely and pictrs cached image
-    let data_url = data.url.as_ref();
-    let (iframely_title, iframely_description, iframely_html, pictrs_thumbnail) =
-      fetch_iframely_and_pictrs_data(context.client(), data_url).await;
+    // Posts of non-mods wait for approval if the community requires it
+    let person_id = local_user_view.person.id;
+    let community_id = data.community_id;
+    let awaiting_approval = blocking(context.pool(), move |conn| -> Result<_, LemmyError> {
+      let community = Community::read(conn, community_id)?;
+      Ok(
+        community.requires_post_approval
+          && !CommunityView::is_mod_or_admin(conn, person_id, community_id),
+      )
+    })
+    .await??;
+
+    // Spam which isn't rejected is created, but removed right away. Only its creator and the mods
+    // see it, so it isn't federated either.
+    let spam_content = SpamCheckContent::Post {
+      id: None,
+      name: data.name.trim().to_owned(),
+      url: data.url.as_ref().map(|u| u.to_string()),
+      body: data.body.to_owned(),
+    };
+    let spam_rule = check_spam_rules(context.pool(), &local_user_view.person, spam_content).await?;
+    if let Some(rule) = &spam_rule {
+      if SpamAction::from_i16(rule.action) == SpamAction::Reject {
+        return Err(ApiError::err("rejected_as_spam").into());
+      }
+    }
 
+    // The metadata of the url is added once it was fetched
     let post_form = PostForm {
       name: data.name.trim().to_owned(),
-      url: data_url.map(|u| u.to_owned().into()),
+      url: data.url.to_owned().map(|u| u.into()),
       body: data.body.to_owned(),
       community_id: data.community_id,
       creator_id: local_user_view.person.id,
-      removed: None,
+      removed: spam_rule.as_ref().map(|_| true),
       deleted: None,
       nsfw: data.nsfw,
       locked: None,
       stickied: None,
       updated: None,
-      embed_title: iframely_title,
-      embed_description: iframely_description,
-      embed_html: iframely_html,
-      thumbnail_url: pictrs_thumbnail.map(|u| u.into()),
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      thumbnail_url: None,
       ap_id: None,
       local: true,
       published: None,
     };
 
-    let inserted_post =
-      match blocking(context.pool(), move |conn| Post::create(conn, &post_form)).await? {
-        Ok(post) => post,
-        Err(e) => {
-          let err_type = if e.to_string() == "value too long for type character varying(200)" {
-            "post_title_too_long"
-          } else {
-            "couldnt_create_post"
-          };
+    let spam_rule2 = spam_rule.clone();
+    let inserted_post = match blocking_transaction(context.pool(), move |conn| {
+      let post = Post::create(conn, &post_form)?;
+      CommunityTag::set_for_post(conn, post.id, &tag_ids)?;
+      if let Some(rule) = &spam_rule2 {
+        log_spam_removal(conn, rule, post.id, None)?;
+      }
+      if awaiting_approval {
+        Ok(Post::update_approved(conn, post.id, None)?)
+      } else {
+        Ok(post)
+      }
+    })
+    .await
+    {
+      Ok(post) => post,
+      Err(e) => {
+        let err_type = if e.to_string() == "value too long for type character varying(200)" {
+          "post_title_too_long"
+        } else {
+          "couldnt_create_post"
+        };
 
-          return Err(ApiError::err(err_type).into());
-        }
-      };
+        return Err(ApiError::err(err_type).into());
+      }
+    };
 
     let inserted_post_id = inserted_post.id;
     let updated_post = match blocking(context.pool(), move |conn| -> Result<Post, LemmyError> {
@@ -122,9 +223,28 @@ impl Perform for CreatePost {
       Err(_e) => return Err(ApiError::err("couldnt_create_post").into()),
     };
 
-    updated_post
-      .send_create(&local_user_view.person, context)
-      .await?;
+    let synchronous_metadata = Settings::get().synchronous_post_metadata();
+    let updated_post = match &data.url {
+      Some(url) if synchronous_metadata => {
+        // The post might have been edited already, then its own update is sent
+        fetch_and_save_post_metadata(vec![inserted_post_id], url.to_owned(), context)
+          .await?
+          .pop()
+          .unwrap_or(updated_post)
+      }
+      _ => updated_post,
+    };
+
+    let removed_as_spam = spam_rule.is_some();
+
+    // Isn't federated while it waits for approval
+    let queued = if removed_as_spam {
+      0
+    } else {
+      updated_post
+        .send_create(&local_user_view.person, context)
+        .await?
+    };
 
     // They like their own post by default
     let like_form = PostLikeForm {
@@ -138,9 +258,26 @@ impl Perform for CreatePost {
       return Err(ApiError::err("couldnt_like_post").into());
     }
 
-    updated_post
-      .send_like(&local_user_view.person, context)
-      .await?;
+    if !removed_as_spam {
+      updated_post
+        .send_like(&local_user_view.person, context)
+        .await?;
+    }
+
+    // The mentioned persons are notified once the post is approved
+    let recipient_ids = if awaiting_approval || removed_as_spam {
+      vec![]
+    } else {
+      let mentions = scrape_text_for_mentions(updated_post.body.as_deref().unwrap_or(""));
+      send_local_post_notifs(
+        mentions,
+        updated_post,
+        local_user_view.person.clone(),
+        context.pool(),
+        true,
+      )
+      .await?
+    };
 
     // Refetch the view
     let inserted_post_id = inserted_post.id;
@@ -153,7 +290,29 @@ impl Perform for CreatePost {
       Err(_e) => return Err(ApiError::err("couldnt_find_post").into()),
     };
 
-    let res = PostResponse { post_view };
+    let federation = FederationStatus::new(queued, post_view.community.local);
+    let mut res = PostResponse {
+      post_view,
+      federation: Some(federation),
+    };
+    hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
+
+    if let Some(url) = &data.url {
+      if !synchronous_metadata {
+        spawn_post_metadata_fetch(vec![inserted_post_id], url.to_owned(), context);
+      }
+    }
+
+    if awaiting_approval || removed_as_spam {
+      // Only the mods are told about it, until one of them approves or restores it
+      context.chat_server().do_send(SendModRoomMessage {
+        op: UserOperation::CreatePost,
+        response: res.clone(),
+        community_id: data.community_id,
+        websocket_id,
+      });
+      return Ok(res);
+    }
 
     context.chat_server().do_send(SendPost {
       op: UserOperation::CreatePost,
@@ -161,10 +320,301 @@ impl Perform for CreatePost {
       websocket_id,
     });
 
+    // Notify the mentioned local users
+    for local_recipient_id in recipient_ids {
+      context.chat_server().do_send(SendUserRoomMessage {
+        op: UserOperation::CreatePost,
+        response: res.clone(),
+        local_recipient_id,
+        websocket_id: None,
+      });
+    }
+
     Ok(res)
   }
 }
 
+/// Most communities which a post can be created in at once
+const MAX_MULTI_POST_COMMUNITIES: usize = 10;
+
+#[async_trait::async_trait(?Send)]
+impl Perform for CreateMultiPost {
+  type Response = CreateMultiPostResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    websocket_id: Option<ConnectionId>,
+  ) -> Result<CreateMultiPostResponse, LemmyError> {
+    let data: &CreateMultiPost = &self;
+    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+    let person = local_user_view.person;
+
+    let mut seen = HashSet::new();
+    let community_ids: Vec<CommunityId> = data
+      .community_ids
+      .iter()
+      .copied()
+      .filter(|id| seen.insert(*id))
+      .collect();
+    if community_ids.is_empty() {
+      return Err(
+        ApiError::new(ApiErrorCode::CouldntFindCommunity)
+          .with_field("community_ids")
+          .into(),
+      );
+    }
+    if community_ids.len() > MAX_MULTI_POST_COMMUNITIES {
+      return Err(
+        ApiError::new(ApiErrorCode::TooManyCommunities)
+          .with_field("community_ids")
+          .with_detail("max", MAX_MULTI_POST_COMMUNITIES)
+          .into(),
+      );
+    }
+
+    if !is_valid_post_title(&data.name) {
+      return Err(ApiError::err("invalid_post_title").into());
+    }
+    get_content_limits(context.pool())
+      .await?
+      .check_post(&data.body, data.url.as_ref())?;
+
+    let spam_content = SpamCheckContent::Post {
+      id: None,
+      name: data.name.trim().to_owned(),
+      url: data.url.as_ref().map(|u| u.to_string()),
+      body: data.body.to_owned(),
+    };
+    let spam_rule = check_spam_rules(context.pool(), &person, spam_content).await?;
+    if let Some(rule) = &spam_rule {
+      if SpamAction::from_i16(rule.action) == SpamAction::Reject {
+        return Err(ApiError::err("rejected_as_spam").into());
+      }
+    }
+
+    // A ban in one community doesn't keep the post from the others
+    let mut errors = HashMap::new();
+    let mut forms = vec![];
+    for community_id in &community_ids {
+      match check_multi_post_community(*community_id, data, person.id, context).await {
+        Ok(awaiting_approval) => {
+          let form = PostForm {
+            name: data.name.trim().to_owned(),
+            url: data.url.to_owned().map(|u| u.into()),
+            body: data.body.to_owned(),
+            community_id: *community_id,
+            creator_id: person.id,
+            removed: spam_rule.as_ref().map(|_| true),
+            deleted: None,
+            nsfw: data.nsfw,
+            locked: None,
+            stickied: None,
+            updated: None,
+            embed_title: None,
+            embed_description: None,
+            embed_html: None,
+            thumbnail_url: None,
+            ap_id: None,
+            local: true,
+            published: None,
+          };
+          forms.push((form, awaiting_approval));
+        }
+        Err(e) => {
+          errors.insert(*community_id, e.to_api_error());
+        }
+      }
+    }
+
+    // Only as many posts as the rate limit allows are created, the other communities get an error
+    let allowance = data.rate_limit_allowance.unwrap_or_else(|| forms.len());
+    if forms.len() > allowance {
+      for (form, _) in forms.split_off(allowance) {
+        errors.insert(form.community_id, ApiError::new(ApiErrorCode::RateLimited));
+      }
+    }
+
+    // The posts which passed the checks are created together, and liked by their creator
+    let spam_rule2 = spam_rule.clone();
+    let posts = blocking_transaction(context.pool(), move |conn| {
+      let mut posts = Vec::with_capacity(forms.len());
+      for (form, awaiting_approval) in forms {
+        let post = Post::create(conn, &form)?;
+        if let Some(rule) = &spam_rule2 {
+          log_spam_removal(conn, rule, post.id, None)?;
+        }
+        let apub_id = generate_apub_endpoint(EndpointType::Post, &post.id.to_string())?;
+        let mut post = Post::update_ap_id(conn, post.id, apub_id)?;
+        if awaiting_approval {
+          post = Post::update_approved(conn, post.id, None)?;
+        }
+        let like_form = PostLikeForm {
+          post_id: post.id,
+          person_id: post.creator_id,
+          score: 1,
+        };
+        PostLike::like(conn, &like_form)?;
+        posts.push(post);
+      }
+      Ok(posts)
+    })
+    .await
+    .map_err(|e| {
+      let err_type = if e.to_string() == "value too long for type character varying(200)" {
+        "post_title_too_long"
+      } else {
+        "couldnt_create_post"
+      };
+      ApiError::err(err_type)
+    })?;
+
+    // The url is fetched only once for all posts
+    let post_ids: Vec<PostId> = posts.iter().map(|p| p.id).collect();
+    let synchronous_metadata = Settings::get().synchronous_post_metadata();
+    let posts = match &data.url {
+      Some(url) if synchronous_metadata && !posts.is_empty() => {
+        match fetch_and_save_post_metadata(post_ids.to_owned(), url.to_owned(), context).await {
+          Ok(posts) => posts,
+          Err(e) => {
+            error!("Failed to save post metadata: {}", e);
+            posts
+          }
+        }
+      }
+      _ => posts,
+    };
+
+    // The posts exist now, so a failure only goes into the result of its community
+    let mut post_views = HashMap::new();
+    let mut mentions_sent = false;
+    for post in posts {
+      let community_id = post.community_id;
+      // The mentioned persons are only notified about one of the posts
+      let notify_mentions = !mentions_sent && spam_rule.is_none() && !post.is_awaiting_approval();
+      mentions_sent |= notify_mentions;
+      match announce_multi_post(post, &person, notify_mentions, websocket_id, context).await {
+        Ok(post_view) => {
+          post_views.insert(community_id, post_view);
+        }
+        Err(e) => {
+          errors.insert(community_id, e.to_api_error());
+        }
+      }
+    }
+
+    if let Some(url) = &data.url {
+      if !synchronous_metadata && !post_ids.is_empty() {
+        spawn_post_metadata_fetch(post_ids, url.to_owned(), context);
+      }
+    }
+
+    let results = community_ids
+      .into_iter()
+      .map(|community_id| MultiPostResult {
+        community_id,
+        post_view: post_views.remove(&community_id),
+        error: errors.remove(&community_id),
+      })
+      .collect();
+    Ok(CreateMultiPostResponse { results })
+  }
+}
+
+/// Federates one of the posts created by `CreateMultiPost` and sends it to the websocket clients.
+/// Posts which were removed as spam or wait for approval are only sent to the mods.
+async fn announce_multi_post(
+  post: Post,
+  person: &Person,
+  notify_mentions: bool,
+  websocket_id: Option<ConnectionId>,
+  context: &LemmyContext,
+) -> Result<PostView, LemmyError> {
+  let post_id = post.id;
+  let community_id = post.community_id;
+  // Isn't federated while it waits for approval
+  let held_back = post.removed || post.is_awaiting_approval();
+  if !held_back {
+    post.send_create(person, context).await?;
+    post.send_like(person, context).await?;
+  }
+
+  let recipient_ids = if notify_mentions {
+    let mentions = scrape_text_for_mentions(post.body.as_deref().unwrap_or(""));
+    send_local_post_notifs(mentions, post, person.clone(), context.pool(), true).await?
+  } else {
+    vec![]
+  };
+
+  let person_id = person.id;
+  let post_view = blocking(context.pool(), move |conn| {
+    PostView::read(conn, post_id, Some(person_id))
+  })
+  .await??;
+  let mut res = PostResponse {
+    post_view,
+    federation: None,
+  };
+  hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
+
+  if held_back {
+    context.chat_server().do_send(SendModRoomMessage {
+      op: UserOperation::CreatePost,
+      response: res.clone(),
+      community_id,
+      websocket_id,
+    });
+  } else {
+    context.chat_server().do_send(SendPost {
+      op: UserOperation::CreatePost,
+      post: res.clone(),
+      websocket_id,
+    });
+    for local_recipient_id in recipient_ids {
+      context.chat_server().do_send(SendUserRoomMessage {
+        op: UserOperation::CreatePost,
+        response: res.clone(),
+        local_recipient_id,
+        websocket_id: None,
+      });
+    }
+  }
+  Ok(res.post_view)
+}
+
+/// Checks if the person can post in the community, and returns whether the post has to wait for
+/// approval. Posts in several communities at once can't have tags, because the tags belong to a
+/// single community.
+async fn check_multi_post_community(
+  community_id: CommunityId,
+  data: &CreateMultiPost,
+  person_id: PersonId,
+  context: &LemmyContext,
+) -> Result<bool, LemmyError> {
+  let (community, is_mod) = blocking(context.pool(), move |conn| -> Result<_, LemmyError> {
+    let community = Community::read(conn, community_id)
+      .map_err(|_| ApiError::new(ApiErrorCode::CouldntFindCommunity))?;
+    let is_mod = CommunityView::is_mod_or_admin(conn, person_id, community_id);
+    Ok((community, is_mod))
+  })
+  .await??;
+  if community.removed || community.deleted {
+    return Err(ApiError::new(ApiErrorCode::CouldntFindCommunity).into());
+  }
+  if community.require_post_tag {
+    return Err(ApiError::new(ApiErrorCode::PostTagRequired).into());
+  }
+
+  let exempt = is_exempt_from_slur_filter(community_id, context.pool()).await?;
+  let slur_regex = context.slur_filter().regex_for_community(exempt);
+  check_slurs(&data.name, &slur_regex)?;
+  check_slurs_opt(&data.body, &slur_regex)?;
+
+  check_community_ban(person_id, community_id, context.pool()).await?;
+
+  Ok(community.requires_post_approval && !is_mod)
+}
+
 #[async_trait::async_trait(?Send)]
 impl Perform for GetPost {
   type Response = GetPostResponse;
@@ -172,14 +622,18 @@ impl Perform for GetPost {
   async fn perform(
     &self,
     context: &Data<LemmyContext>,
-    _websocket_id: Option<ConnectionId>,
+    websocket_id: Option<ConnectionId>,
   ) -> Result<GetPostResponse, LemmyError> {
     let data: &GetPost = &self;
     let local_user_view = get_local_user_view_from_jwt_opt(&data.auth, context.pool()).await?;
-    let person_id = local_user_view.map(|u| u.person.id);
+    let person_id = local_user_view.as_ref().map(|u| u.person.id);
+    let show_nsfw = local_user_view
+      .as_ref()
+      .map(|u| u.local_user.show_nsfw)
+      .unwrap_or(false);
 
     let id = data.id;
-    let post_view = match blocking(context.pool(), move |conn| {
+    let mut post_view = match blocking(context.pool(), move |conn| {
       PostView::read(conn, id, person_id)
     })
     .await?
@@ -188,15 +642,44 @@ impl Perform for GetPost {
       Err(_e) => return Err(ApiError::err("couldnt_find_post").into()),
     };
 
+    let can_view_removed =
+      can_view_removed(person_id, post_view.post.community_id, context.pool()).await?;
+
+    let is_creator = person_id == Some(post_view.post.creator_id);
+
+    // Posts which wait for approval are only visible to their creator and the mods
+    if post_view.post.is_awaiting_approval() && !is_creator && !can_view_removed {
+      return Err(ApiError::err("couldnt_find_post").into());
+    }
+
+    // Removed and deleted posts stay visible to their creator and the mods, eg for links from the
+    // modlog
+    if (post_view.post.removed || post_view.post.deleted) && !is_creator && !can_view_removed {
+      return Err(ApiError::err("couldnt_find_post").into());
+    }
+
+    context.chat_server().do_send(RecordPostView {
+      post_id: data.id,
+      person_id,
+      ip: data.viewer_ip.to_owned(),
+      websocket_id,
+    });
+
     let id = data.id;
-    let comments = blocking(context.pool(), move |conn| {
+    let sort = data
+      .sort
+      .unwrap_or_else(|| SortType::from_i16(post_view.community.default_comment_sort));
+    let mut comments = blocking(context.pool(), move |conn| {
       CommentQueryBuilder::create(conn)
         .my_person_id(person_id)
         .post_id(id)
+        .sort(&sort)
         .limit(9999)
         .list()
     })
     .await??;
+    hide_downvotes_if_disabled(std::slice::from_mut(&mut post_view), context.pool()).await?;
+    hide_downvotes_if_disabled(&mut comments, context.pool()).await?;
 
     let community_id = post_view.community.id;
     let moderators = blocking(context.pool(), move |conn| {
@@ -220,6 +703,19 @@ impl Perform for GetPost {
       .await
       .unwrap_or(1);
 
+    let id = data.id;
+    let tags = blocking(context.pool(), move |conn| {
+      CommunityTag::list_for_post(conn, id)
+    })
+    .await??;
+
+    let post = post_view.post.to_owned();
+    let mut cross_posts = blocking(context.pool(), move |conn| {
+      PostView::list_cross_posts(conn, &post, person_id, show_nsfw)
+    })
+    .await??;
+    hide_downvotes_if_disabled(&mut cross_posts, context.pool()).await?;
+
     // Return the jwt
     Ok(GetPostResponse {
       post_view,
@@ -227,6 +723,9 @@ impl Perform for GetPost {
       comments,
       moderators,
       online,
+      can_view_removed,
+      tags,
+      cross_posts,
     })
   }
 }
@@ -253,22 +752,51 @@ impl Perform for GetPosts {
       None => false,
     };
 
-    let type_ = ListingType::from_str(&data.type_)?;
-    let sort = SortType::from_str(&data.sort)?;
+    let for_community = data.community_id.is_some() || data.community_name.is_some();
+    let (type_, sort) = get_listing_defaults(
+      data.type_,
+      data.sort,
+      for_community,
+      &local_user_view,
+      context.pool(),
+    )
+    .await?;
+
+    check_sort_allowed(
+      sort,
+      data.community_id,
+      data.community_name.to_owned(),
+      person_id,
+      context.pool(),
+    )
+    .await?;
+
+    let page_after = match &data.page_cursor {
+      Some(cursor) => Some(
+        PaginationCursor::decode(cursor)
+          .ok_or_else(|| ApiError::err("invalid_page_cursor").with_field("page_cursor"))?,
+      ),
+      None => None,
+    };
 
     let page = data.page;
     let limit = data.limit;
     let community_id = data.community_id;
     let community_name = data.community_name.to_owned();
-    let posts = match blocking(context.pool(), move |conn| {
+    let tag_id = data.tag_id;
+    let favorites_only = data.favorites_only.unwrap_or(false);
+    let mut posts = match blocking(context.pool(), move |conn| {
       PostQueryBuilder::create(conn)
         .listing_type(&type_)
         .sort(&sort)
         .show_nsfw(show_nsfw)
         .community_id(community_id)
         .community_name(community_name)
+        .tag_id(tag_id)
+        .favorites_only(favorites_only)
         .my_person_id(person_id)
         .page(page)
+        .page_after(page_after)
         .limit(limit)
         .list()
     })
@@ -277,8 +805,99 @@ impl Perform for GetPosts {
       Ok(posts) => posts,
       Err(_e) => return Err(ApiError::err("couldnt_get_posts").into()),
     };
+    hide_downvotes_if_disabled(&mut posts, context.pool()).await?;
+
+    // Only hand out a cursor if there might be more posts
+    let (limit, _) = limit_and_offset(None, limit);
+    let next_page = match posts.last() {
+      Some(last) if posts.len() as i64 >= limit => {
+        Some(PaginationCursor::after_post(last).encode())
+      }
+      _ => None,
+    };
+
+    Ok(GetPostsResponse { posts, next_page })
+  }
+}
+
+/// Posts which can be marked as viewed with one request
+const MAX_VIEWED_POSTS: usize = 50;
+
+#[async_trait::async_trait(?Send)]
+impl Perform for GetRandomPost {
+  type Response = GetRandomPostResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<GetRandomPostResponse, LemmyError> {
+    let data: &GetRandomPost = &self;
+    let local_user_view = get_local_user_view_from_jwt_opt(&data.auth, context.pool()).await?;
+    let person_id = local_user_view.as_ref().map(|uv| uv.person.id);
+    // Don't show NSFW by default
+    let show_nsfw = local_user_view
+      .as_ref()
+      .map(|uv| uv.local_user.show_nsfw)
+      .unwrap_or(false);
+
+    let community_id = data.community_id;
+    let community = blocking(context.pool(), move |conn| {
+      Community::read(conn, community_id)
+    })
+    .await?
+    .map_err(|_| ApiError::err("couldnt_find_community"))?;
+    if community.removed || community.deleted {
+      return Err(ApiError::err("couldnt_find_community").into());
+    }
+    if community.nsfw && !show_nsfw {
+      return Ok(GetRandomPostResponse { post_view: None });
+    }
+
+    let mut post_view = blocking_read(context.read_pool(), move |conn| {
+      PostView::read_random_in_community(conn, community_id, person_id, show_nsfw)
+    })
+    .await??;
+    if let Some(post_view) = post_view.as_mut() {
+      hide_downvotes_if_disabled(std::slice::from_mut(post_view), context.pool()).await?;
+    }
+
+    Ok(GetRandomPostResponse { post_view })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for MarkPostViewed {
+  type Response = MarkPostViewedResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    websocket_id: Option<ConnectionId>,
+  ) -> Result<MarkPostViewedResponse, LemmyError> {
+    let data: &MarkPostViewed = &self;
+    let local_user_view = get_local_user_view_from_jwt_opt(&data.auth, context.pool()).await?;
+    let person_id = local_user_view.map(|u| u.person.id);
+
+    if data.post_ids.len() > MAX_VIEWED_POSTS {
+      return Err(
+        ApiError::err("too_many_posts")
+          .with_field("post_ids")
+          .with_detail("max", MAX_VIEWED_POSTS)
+          .into(),
+      );
+    }
+
+    for post_id in &data.post_ids {
+      context.chat_server().do_send(RecordPostView {
+        post_id: *post_id,
+        person_id,
+        ip: data.viewer_ip.to_owned(),
+        websocket_id,
+      });
+    }
 
-    Ok(GetPostsResponse { posts })
+    Ok(MarkPostViewedResponse { success: true })
   }
 }
 
@@ -302,6 +921,7 @@ impl Perform for CreatePostLike {
     let post = blocking(context.pool(), move |conn| Post::read(conn, post_id)).await??;
 
     check_community_ban(local_user_view.person.id, post.community_id, context.pool()).await?;
+    check_community_downvotes_enabled(data.score, post.community_id, context.pool()).await?;
 
     let like_form = PostLikeForm {
       post_id: data.post_id,
@@ -311,8 +931,10 @@ impl Perform for CreatePostLike {
 
     // Remove any likes first
     let person_id = local_user_view.person.id;
-    blocking(context.pool(), move |conn| {
-      PostLike::remove(conn, person_id, post_id)
+    let previous_score = blocking(context.pool(), move |conn| {
+      let previous_score = PostLike::read_score(conn, person_id, post_id)?;
+      PostLike::remove(conn, person_id, post_id)?;
+      Ok(previous_score) as Result<_, LemmyError>
     })
     .await??;
 
@@ -324,18 +946,19 @@ impl Perform for CreatePostLike {
       if blocking(context.pool(), like).await?.is_err() {
         return Err(ApiError::err("couldnt_like_post").into());
       }
-
-      if like_form.score == 1 {
-        post.send_like(&local_user_view.person, context).await?;
-      } else if like_form.score == -1 {
-        post.send_dislike(&local_user_view.person, context).await?;
-      }
-    } else {
-      post
-        .send_undo_like(&local_user_view.person, context)
-        .await?;
     }
 
+    let score = if do_add { like_form.score } else { 0 };
+    send_vote_debounced(
+      &post,
+      VoteTarget::Post(post.id),
+      &local_user_view.person,
+      previous_score,
+      score,
+      context,
+    )
+    .await?;
+
     let post_id = data.post_id;
     let person_id = local_user_view.person.id;
     let post_view = match blocking(context.pool(), move |conn| {
@@ -347,7 +970,11 @@ impl Perform for CreatePostLike {
       Err(_e) => return Err(ApiError::err("couldnt_find_post").into()),
     };
 
-    let res = PostResponse { post_view };
+    let mut res = PostResponse {
+      post_view,
+      federation: None,
+    };
+    hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
     context.chat_server().do_send(SendPost {
       op: UserOperation::CreatePostLike,
@@ -371,15 +998,20 @@ impl Perform for EditPost {
     let data: &EditPost = &self;
     let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
 
-    check_slurs(&data.name)?;
-    check_slurs_opt(&data.body)?;
+    let post_id = data.post_id;
+    let orig_post = blocking(context.pool(), move |conn| Post::read(conn, post_id)).await??;
+
+    let exempt = is_exempt_from_slur_filter(orig_post.community_id, context.pool()).await?;
+    let slur_regex = context.slur_filter().regex_for_community(exempt);
+    check_slurs(&data.name, &slur_regex)?;
+    check_slurs_opt(&data.body, &slur_regex)?;
 
     if !is_valid_post_title(&data.name) {
       return Err(ApiError::err("invalid_post_title").into());
     }
-
-    let post_id = data.post_id;
-    let orig_post = blocking(context.pool(), move |conn| Post::read(conn, post_id)).await??;
+    get_content_limits(context.pool())
+      .await?
+      .check_post(&data.body, data.url.as_ref())?;
 
     check_community_ban(
       local_user_view.person.id,
@@ -393,14 +1025,33 @@ impl Perform for EditPost {
       return Err(ApiError::err("no_post_edit_allowed").into());
     }
 
-    // Fetch Iframely and Pictrs cached image
-    let data_url = data.url.as_ref();
-    let (iframely_title, iframely_description, iframely_html, pictrs_thumbnail) =
-      fetch_iframely_and_pictrs_data(context.client(), data_url).await;
+    let tag_ids = match &data.tags {
+      Some(tags) => Some(check_post_tags(tags, orig_post.community_id, context.pool()).await?),
+      None => None,
+    };
+
+    // Only the persons which weren't mentioned before get notified
+    let mentions = scrape_text_for_new_mentions(
+      orig_post.body.as_deref().unwrap_or(""),
+      data.body.as_deref().unwrap_or(""),
+    );
+
+    // The metadata of the url is only fetched again if the url changed
+    let url_changed = data.url.to_owned().map(DbUrl::from) != orig_post.url;
+    let (embed_title, embed_description, embed_html, thumbnail_url) = if url_changed {
+      (None, None, None, None)
+    } else {
+      (
+        orig_post.embed_title,
+        orig_post.embed_description,
+        orig_post.embed_html,
+        orig_post.thumbnail_url,
+      )
+    };
 
     let post_form = PostForm {
       name: data.name.trim().to_owned(),
-      url: data_url.map(|u| u.to_owned().into()),
+      url: data.url.to_owned().map(|u| u.into()),
       body: data.body.to_owned(),
       nsfw: data.nsfw,
       creator_id: orig_post.creator_id.to_owned(),
@@ -410,20 +1061,24 @@ impl Perform for EditPost {
       locked: Some(orig_post.locked),
       stickied: Some(orig_post.stickied),
       updated: Some(naive_now()),
-      embed_title: iframely_title,
-      embed_description: iframely_description,
-      embed_html: iframely_html,
-      thumbnail_url: pictrs_thumbnail.map(|u| u.into()),
+      embed_title,
+      embed_description,
+      embed_html,
+      thumbnail_url,
       ap_id: Some(orig_post.ap_id),
       local: orig_post.local,
       published: None,
     };
 
     let post_id = data.post_id;
-    let res = blocking(context.pool(), move |conn| {
-      Post::update(conn, post_id, &post_form)
+    let res = blocking_transaction(context.pool(), move |conn| {
+      let post = Post::update(conn, post_id, &post_form)?;
+      if let Some(tag_ids) = tag_ids {
+        CommunityTag::set_for_post(conn, post.id, &tag_ids)?;
+      }
+      Ok(post)
     })
-    .await?;
+    .await;
     let updated_post: Post = match res {
       Ok(post) => post,
       Err(e) => {
@@ -437,18 +1092,49 @@ impl Perform for EditPost {
       }
     };
 
+    let synchronous_metadata = Settings::get().synchronous_post_metadata();
+    let updated_post = match &data.url {
+      Some(url) if url_changed && synchronous_metadata => {
+        // The post might have been edited again, then its own update is sent
+        fetch_and_save_post_metadata(vec![post_id], url.to_owned(), context)
+          .await?
+          .pop()
+          .unwrap_or(updated_post)
+      }
+      _ => updated_post,
+    };
+
     // Send apub update
     updated_post
       .send_update(&local_user_view.person, context)
       .await?;
 
+    let recipient_ids = send_local_post_notifs(
+      mentions,
+      updated_post,
+      local_user_view.person.clone(),
+      context.pool(),
+      false,
+    )
+    .await?;
+
     let post_id = data.post_id;
     let post_view = blocking(context.pool(), move |conn| {
       PostView::read(conn, post_id, Some(local_user_view.person.id))
     })
     .await??;
 
-    let res = PostResponse { post_view };
+    let mut res = PostResponse {
+      post_view,
+      federation: None,
+    };
+    hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
+
+    if let Some(url) = &data.url {
+      if url_changed && !synchronous_metadata {
+        spawn_post_metadata_fetch(vec![post_id], url.to_owned(), context);
+      }
+    }
 
     context.chat_server().do_send(SendPost {
       op: UserOperation::EditPost,
@@ -456,6 +1142,16 @@ impl Perform for EditPost {
       websocket_id,
     });
 
+    // Notify the mentioned local users
+    for local_recipient_id in recipient_ids {
+      context.chat_server().do_send(SendUserRoomMessage {
+        op: UserOperation::EditPost,
+        response: res.clone(),
+        local_recipient_id,
+        websocket_id: None,
+      });
+    }
+
     Ok(res)
   }
 }
@@ -513,7 +1209,11 @@ impl Perform for DeletePost {
     })
     .await??;
 
-    let res = PostResponse { post_view };
+    let mut res = PostResponse {
+      post_view,
+      federation: None,
+    };
+    hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
     context.chat_server().do_send(SendPost {
       op: UserOperation::DeletePost,
@@ -594,7 +1294,11 @@ impl Perform for RemovePost {
     })
     .await??;
 
-    let res = PostResponse { post_view };
+    let mut res = PostResponse {
+      post_view,
+      federation: None,
+    };
+    hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
     context.chat_server().do_send(SendPost {
       op: UserOperation::RemovePost,
@@ -606,6 +1310,178 @@ impl Perform for RemovePost {
   }
 }
 
+#[async_trait::async_trait(?Send)]
+impl Perform for ListPendingPosts {
+  type Response = ListPendingPostsResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<ListPendingPostsResponse, LemmyError> {
+    let data: &ListPendingPosts = &self;
+    let local_user_view =
+      get_local_user_view_from_jwt_read_only(&data.auth, context.pool()).await?;
+
+    is_mod_or_admin(context.pool(), local_user_view.person.id, data.community_id).await?;
+
+    let community_id = data.community_id;
+    let person_id = local_user_view.person.id;
+    let page = data.page;
+    let limit = data.limit;
+    let posts = blocking(context.pool(), move |conn| {
+      PostQueryBuilder::create(conn)
+        .sort(&SortType::New)
+        .community_id(community_id)
+        .my_person_id(person_id)
+        .pending_only(true)
+        .page(page)
+        .limit(limit)
+        .list()
+    })
+    .await??;
+
+    Ok(ListPendingPostsResponse { posts })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for ApprovePost {
+  type Response = PostResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    websocket_id: Option<ConnectionId>,
+  ) -> Result<PostResponse, LemmyError> {
+    let data: &ApprovePost = &self;
+    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+
+    let orig_post = get_post(data.post_id, context.pool()).await?;
+
+    is_mod_or_admin(
+      context.pool(),
+      local_user_view.person.id,
+      orig_post.community_id,
+    )
+    .await?;
+
+    if orig_post.approved.is_some() {
+      return Err(ApiError::err("post_not_pending").into());
+    }
+
+    // A rejected post is removed, with the reason in the modlog
+    let post_id = data.post_id;
+    let approve = data.approve;
+    let form = ModRemovePostForm {
+      mod_person_id: local_user_view.person.id,
+      post_id,
+      removed: Some(true),
+      reason: data.reason.to_owned(),
+    };
+    let updated_post = blocking_transaction(context.pool(), move |conn| {
+      if approve {
+        return Ok(Post::update_approved(conn, post_id, Some(true))?);
+      }
+      Post::update_approved(conn, post_id, Some(false))?;
+      let post = Post::update_removed(conn, post_id, true)?;
+      ModRemovePost::create(conn, &form)?;
+      Ok(post)
+    })
+    .await?;
+
+    let creator_id = updated_post.creator_id;
+    let creator = blocking(context.pool(), move |conn| {
+      LocalUserView::read_person(conn, creator_id).ok()
+    })
+    .await?;
+
+    // Local posts are only federated once they are approved, while federated posts were already
+    // announced by the community, so they have to be removed again.
+    let mut recipient_ids = vec![];
+    if approve && updated_post.local {
+      if let Some(creator) = &creator {
+        updated_post.send_create(&creator.person, context).await?;
+        let person_id = creator.person.id;
+        let score = blocking(context.pool(), move |conn| {
+          PostLike::read_score(conn, person_id, post_id)
+        })
+        .await??;
+        send_vote(
+          &updated_post,
+          &creator.person,
+          None,
+          score.unwrap_or(0),
+          context,
+        )
+        .await?;
+
+        let mentions = scrape_text_for_mentions(updated_post.body.as_deref().unwrap_or(""));
+        recipient_ids = send_local_post_notifs(
+          mentions,
+          updated_post.to_owned(),
+          creator.person.to_owned(),
+          context.pool(),
+          true,
+        )
+        .await?;
+      }
+    } else if !approve && !updated_post.local {
+      updated_post
+        .send_remove(&local_user_view.person, context)
+        .await?;
+    }
+
+    // Refetch the post
+    let person_id = local_user_view.person.id;
+    let post_view = blocking(context.pool(), move |conn| {
+      PostView::read(conn, post_id, Some(person_id))
+    })
+    .await??;
+
+    let mut res = PostResponse {
+      post_view,
+      federation: None,
+    };
+    hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
+
+    context.chat_server().do_send(SendModRoomMessage {
+      op: UserOperation::ApprovePost,
+      response: res.clone(),
+      community_id: orig_post.community_id,
+      websocket_id,
+    });
+
+    if let Some(creator) = creator {
+      context.chat_server().do_send(SendUserRoomMessage {
+        op: UserOperation::ApprovePost,
+        response: res.clone(),
+        local_recipient_id: creator.local_user.id,
+        websocket_id: None,
+      });
+    }
+
+    // Everyone else sees an approved post for the first time
+    if approve && orig_post.local {
+      context.chat_server().do_send(SendPost {
+        op: UserOperation::CreatePost,
+        post: res.clone(),
+        websocket_id: None,
+      });
+      for local_recipient_id in recipient_ids {
+        context.chat_server().do_send(SendUserRoomMessage {
+          op: UserOperation::CreatePost,
+          response: res.clone(),
+          local_recipient_id,
+          websocket_id: None,
+        });
+      }
+    }
+
+    Ok(res)
+  }
+}
+
 #[async_trait::async_trait(?Send)]
 impl Perform for LockPost {
   type Response = PostResponse;
@@ -664,7 +1540,11 @@ impl Perform for LockPost {
     })
     .await??;
 
-    let res = PostResponse { post_view };
+    let mut res = PostResponse {
+      post_view,
+      federation: None,
+    };
+    hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
     context.chat_server().do_send(SendPost {
       op: UserOperation::LockPost,
@@ -691,45 +1571,58 @@ impl Perform for StickyPost {
     let post_id = data.post_id;
     let orig_post = blocking(context.pool(), move |conn| Post::read(conn, post_id)).await??;
 
-    check_community_ban(
-      local_user_view.person.id,
-      orig_post.community_id,
-      context.pool(),
-    )
-    .await?;
+    if data.site_wide.unwrap_or(false) {
+      // Only admins can feature posts on the front page
+      is_admin(&local_user_view)?;
 
-    // Verify that only the mods can sticky
-    is_mod_or_admin(
-      context.pool(),
-      local_user_view.person.id,
-      orig_post.community_id,
-    )
-    .await?;
+      update_featured_local(
+        context.pool(),
+        local_user_view.person.id,
+        &orig_post,
+        data.stickied,
+      )
+      .await?;
+    } else {
+      check_community_ban(
+        local_user_view.person.id,
+        orig_post.community_id,
+        context.pool(),
+      )
+      .await?;
 
-    // Update the post
-    let post_id = data.post_id;
-    let stickied = data.stickied;
-    let updated_post = blocking(context.pool(), move |conn| {
-      Post::update_stickied(conn, post_id, stickied)
-    })
-    .await??;
+      // Verify that only the mods can sticky
+      is_mod_or_admin(
+        context.pool(),
+        local_user_view.person.id,
+        orig_post.community_id,
+      )
+      .await?;
 
-    // Mod tables
-    let form = ModStickyPostForm {
-      mod_person_id: local_user_view.person.id,
-      post_id: data.post_id,
-      stickied: Some(stickied),
-    };
-    blocking(context.pool(), move |conn| {
-      ModStickyPost::create(conn, &form)
-    })
-    .await??;
+      // Update the post
+      let post_id = data.post_id;
+      let stickied = data.stickied;
+      let updated_post = blocking(context.pool(), move |conn| {
+        Post::update_stickied(conn, post_id, stickied)
+      })
+      .await??;
+
+      // Mod tables
+      let form = ModStickyPostForm {
+        mod_person_id: local_user_view.person.id,
+        post_id: data.post_id,
+        stickied: Some(stickied),
+      };
+      blocking(context.pool(), move |conn| {
+        ModStickyPost::create(conn, &form)
+      })
+      .await??;
 
-    // Apub updates
-    // TODO stickied should pry work like locked for ease of use
-    updated_post
-      .send_update(&local_user_view.person, context)
-      .await?;
+      // Apub updates
+      // TODO stickied should pry work like locked for ease of use
+      updated_post
+        .send_update(&local_user_view.person, context)
+        .await?;
+    }
 
     // Refetch the post
     let post_id = data.post_id;
@@ -738,7 +1631,11 @@ impl Perform for StickyPost {
     })
     .await??;
 
-    let res = PostResponse { post_view };
+    let mut res = PostResponse {
+      post_view,
+      federation: None,
+    };
+    hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
     context.chat_server().do_send(SendPost {
       op: UserOperation::StickyPost,
@@ -750,6 +1647,230 @@ impl Perform for StickyPost {
   }
 }
 
+/// Checks that the tags belong to the community, and that there is at least one if the community
+/// requires post tags. Returns the tag ids without duplicates.
+async fn check_post_tags(
+  tag_ids: &[i32],
+  community_id: CommunityId,
+  pool: &DbPool,
+) -> Result<Vec<i32>, LemmyError> {
+  let mut tag_ids = tag_ids.to_vec();
+  tag_ids.sort_unstable();
+  tag_ids.dedup();
+  if tag_ids.len() > MAX_POST_TAGS {
+    return Err(
+      ApiError::new(ApiErrorCode::TooManyPostTags)
+        .with_field("tags")
+        .into(),
+    );
+  }
+
+  let (community, community_tags) = blocking(pool, move |conn| -> Result<_, LemmyError> {
+    Ok((
+      Community::read(conn, community_id)?,
+      CommunityTag::list_for_community(conn, community_id)?,
+    ))
+  })
+  .await??;
+  if community.require_post_tag && tag_ids.is_empty() {
+    return Err(
+      ApiError::new(ApiErrorCode::PostTagRequired)
+        .with_field("tags")
+        .into(),
+    );
+  }
+  if let Some(unknown) = tag_ids
+    .iter()
+    .find(|id| !community_tags.iter().any(|t| t.id == **id))
+  {
+    return Err(
+      ApiError::new(ApiErrorCode::InvalidPostTag)
+        .with_field("tags")
+        .with_detail("tag_id", *unknown)
+        .into(),
+    );
+  }
+  Ok(tag_ids)
+}
+
+/// How many posts can be featured on the front page of the instance at the same time
+const MAX_FEATURED_LOCAL_POSTS: i64 = 5;
+
+/// Pins or unpins a post at the top of the Local and All listings of this instance. This is only
+/// stored locally, so other instances keep showing the post in its normal place.
+async fn update_featured_local(
+  pool: &DbPool,
+  mod_person_id: PersonId,
+  post: &Post,
+  featured: bool,
+) -> Result<(), LemmyError> {
+  let post_id = post.id;
+  let check_limit = featured && !post.featured_local;
+  blocking_transaction(pool, move |conn| {
+    // Counted in the same transaction as the update and the modlog entry
+    if check_limit && Post::count_featured_local(conn)? >= MAX_FEATURED_LOCAL_POSTS {
+      return Err(
+        ApiError::new(ApiErrorCode::TooManyFeaturedPosts)
+          .with_detail("limit", MAX_FEATURED_LOCAL_POSTS)
+          .into(),
+      );
+    }
+    Post::update_featured_local(conn, post_id, featured)?;
+
+    let form = ModFeaturePostForm {
+      mod_person_id,
+      post_id,
+      featured: Some(featured),
+    };
+    ModFeaturePost::create(conn, &form)?;
+    Ok(())
+  })
+  .await
+}
+
+/// Fetches the metadata of the url once, and saves it for each of the posts which still link to
+/// it. Posts whose url was edited in the meantime are left out. If the fetch fails, the returned
+/// posts have `metadata_fetch_failed_at` set.
+async fn fetch_and_save_post_metadata(
+  post_ids: Vec<PostId>,
+  url: Url,
+  context: &LemmyContext,
+) -> Result<Vec<Post>, LemmyError> {
+  let fetched = fetch_post_metadata(context.client(), &url).await;
+  let posts = blocking(context.pool(), move |conn| {
+    post_ids
+      .into_iter()
+      .map(|post_id| save_post_metadata(conn, post_id, &url, fetched.clone()))
+      .collect::<Result<Vec<_>, LemmyError>>()
+  })
+  .await??;
+  Ok(posts.into_iter().flatten().collect())
+}
+
+/// Fetches the metadata of new or edited posts in the background, so that a slow site doesn't
+/// hold up the posts, and sends the updated posts to the websocket clients once they are saved
+fn spawn_post_metadata_fetch(post_ids: Vec<PostId>, url: Url, context: &LemmyContext) {
+  let context = context.clone();
+  actix_rt::spawn(async move {
+    let posts = match fetch_and_save_post_metadata(post_ids, url, &context).await {
+      Ok(posts) => posts,
+      Err(e) => {
+        error!("Failed to save post metadata: {}", e);
+        return;
+      }
+    };
+    for post in posts {
+      if post.metadata_fetch_failed_at.is_some() {
+        continue;
+      }
+      let post_id = post.id;
+      let post_view = match blocking(context.pool(), move |conn| {
+        PostView::read(conn, post_id, None)
+      })
+      .await
+      {
+        Ok(Ok(post_view)) => post_view,
+        _ => continue,
+      };
+      let mut res = PostResponse {
+        post_view,
+        federation: None,
+      };
+      hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
+      send_post_metadata_update(&post, UserOperation::EditPost, res, None, &context);
+    }
+  });
+}
+
+/// Posts which wait for approval or were removed as spam are only sent to the mods
+fn send_post_metadata_update(
+  post: &Post,
+  op: UserOperation,
+  res: PostResponse,
+  websocket_id: Option<ConnectionId>,
+  context: &LemmyContext,
+) {
+  if post.is_awaiting_approval() || post.removed {
+    context.chat_server().do_send(SendModRoomMessage {
+      op,
+      response: res,
+      community_id: post.community_id,
+      websocket_id,
+    });
+  } else {
+    context.chat_server().do_send(SendPost {
+      op,
+      post: res,
+      websocket_id,
+    });
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for RefetchPostMetadata {
+  type Response = PostResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    websocket_id: Option<ConnectionId>,
+  ) -> Result<PostResponse, LemmyError> {
+    let data: &RefetchPostMetadata = &self;
+    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+
+    let post_id = data.post_id;
+    let orig_post = blocking(context.pool(), move |conn| Post::read(conn, post_id)).await??;
+
+    // Verify that only the creator and the mods can refetch
+    if !Post::is_post_creator(local_user_view.person.id, orig_post.creator_id) {
+      is_mod_or_admin(
+        context.pool(),
+        local_user_view.person.id,
+        orig_post.community_id,
+      )
+      .await?;
+    }
+
+    let url = match orig_post.url {
+      Some(url) => url.into_inner(),
+      None => return Err(ApiError::err("post_has_no_url").into()),
+    };
+    // If the post was edited in the meantime, the metadata of the new url is fetched with the edit
+    let updated_post = match fetch_and_save_post_metadata(vec![post_id], url, context)
+      .await?
+      .pop()
+    {
+      Some(post) => post,
+      None => blocking(context.pool(), move |conn| Post::read(conn, post_id)).await??,
+    };
+    if updated_post.metadata_fetch_failed_at.is_some() {
+      return Err(ApiError::err("couldnt_fetch_post_metadata").into());
+    }
+
+    // Refetch the post
+    let post_view = blocking(context.pool(), move |conn| {
+      PostView::read(conn, post_id, Some(local_user_view.person.id))
+    })
+    .await??;
+
+    let mut res = PostResponse {
+      post_view,
+      federation: None,
+    };
+    hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
+
+    send_post_metadata_update(
+      &updated_post,
+      UserOperation::RefetchPostMetadata,
+      res.clone(),
+      websocket_id,
+      context,
+    );
+
+    Ok(res)
+  }
+}
+
 #[async_trait::async_trait(?Send)]
 impl Perform for SavePost {
   type Response = PostResponse;
@@ -781,12 +1902,16 @@ impl Perform for SavePost {
 
     let post_id = data.post_id;
     let person_id = local_user_view.person.id;
-    let post_view = blocking(context.pool(), move |conn| {
+    let mut post_view = blocking(context.pool(), move |conn| {
       PostView::read(conn, post_id, Some(person_id))
     })
     .await??;
+    hide_downvotes_if_disabled(std::slice::from_mut(&mut post_view), context.pool()).await?;
 
-    Ok(PostResponse { post_view })
+    Ok(PostResponse {
+      post_view,
+      federation: None,
+    })
   }
 }
 
@@ -873,8 +1998,9 @@ impl Perform for ResolvePostReport {
     let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
 
     let report_id = data.report_id;
+    let show_deleted_persons = can_see_deleted_persons(&local_user_view, context.pool()).await?;
     let report = blocking(context.pool(), move |conn| {
-      PostReportView::read(&conn, report_id)
+      PostReportView::read(&conn, report_id, show_deleted_persons)
     })
     .await??;
 
@@ -922,18 +2048,21 @@ impl Perform for ListPostReports {
     websocket_id: Option<ConnectionId>,
   ) -> Result<ListPostReportsResponse, LemmyError> {
     let data: &ListPostReports = &self;
-    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+    let local_user_view =
+      get_local_user_view_from_jwt_read_only(&data.auth, context.pool()).await?;
 
     let person_id = local_user_view.person.id;
     let community_id = data.community;
     let community_ids =
       collect_moderated_communities(person_id, community_id, context.pool()).await?;
+    let show_deleted_persons = can_see_deleted_persons(&local_user_view, context.pool()).await?;
 
     let page = data.page;
     let limit = data.limit;
     let posts = blocking(context.pool(), move |conn| {
       PostReportQueryBuilder::create(conn)
         .community_ids(community_ids)
+        .show_deleted_persons(show_deleted_persons)
         .page(page)
         .limit(limit)
         .list()
@@ -952,3 +2081,100 @@ impl Perform for ListPostReports {
     Ok(res)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    test::{create_test_community, create_test_user, error_code, test_context},
+    Perform,
+  };
+  use lemmy_api_structs::post::CreateMultiPost;
+  use lemmy_db_queries::{Bannable, Crud};
+  use lemmy_db_schema::{
+    source::{
+      community::{Community, CommunityPersonBan, CommunityPersonBanForm},
+      person::Person,
+    },
+    CommunityId,
+  };
+  use lemmy_utils::ApiErrorCode;
+  use serial_test::serial;
+
+  #[actix_rt::test]
+  #[serial]
+  async fn test_create_multi_post() {
+    let context = test_context();
+    let conn = context.pool().get().unwrap();
+    let (_, person, jwt) = create_test_user(&conn, "multi_post_person", false);
+    let allowed = create_test_community(&conn, "multi_post_allowed", &person);
+    let allowed2 = create_test_community(&conn, "multi_post_allowed2", &person);
+    let banned = create_test_community(&conn, "multi_post_banned", &person);
+    CommunityPersonBan::ban(
+      &conn,
+      &CommunityPersonBanForm {
+        community_id: banned.id,
+        person_id: person.id,
+      },
+    )
+    .unwrap();
+
+    let multi_post = |community_ids: Vec<CommunityId>| CreateMultiPost {
+      community_ids,
+      name: "A multi post".into(),
+      url: None,
+      body: None,
+      nsfw: false,
+      auth: jwt.to_owned(),
+      rate_limit_allowance: None,
+    };
+    // The ban only fails the post in that community, and the duplicate id is dropped
+    let created = multi_post(vec![allowed.id, banned.id, allowed.id])
+      .perform(&context, None)
+      .await
+      .unwrap();
+    let too_many = multi_post((1..=11).map(CommunityId).collect())
+      .perform(&context, None)
+      .await;
+    let none = multi_post(vec![]).perform(&context, None).await;
+    // Only the first post is covered by the rate limit
+    let rate_limited = CreateMultiPost {
+      rate_limit_allowance: Some(1),
+      ..multi_post(vec![allowed2.id, allowed.id])
+    }
+    .perform(&context, None)
+    .await
+    .unwrap();
+
+    Community::delete(&conn, allowed.id).unwrap();
+    Community::delete(&conn, allowed2.id).unwrap();
+    Community::delete(&conn, banned.id).unwrap();
+    Person::delete(&conn, person.id).unwrap();
+
+    assert_eq!(2, created.results.len());
+    assert_eq!(1, created.created_count());
+    let allowed_result = &created.results[0];
+    assert_eq!(allowed.id, allowed_result.community_id);
+    assert!(allowed_result.error.is_none());
+    let post_view = allowed_result.post_view.as_ref().unwrap();
+    assert_eq!(allowed.id, post_view.post.community_id);
+    assert_eq!(Some(1), post_view.my_vote);
+    let banned_result = &created.results[1];
+    assert_eq!(banned.id, banned_result.community_id);
+    assert!(banned_result.post_view.is_none());
+    assert_eq!(
+      Some(ApiErrorCode::Other("community_ban".into())),
+      banned_result.error.as_ref().map(|e| e.code.to_owned())
+    );
+    assert_eq!(Some(ApiErrorCode::TooManyCommunities), error_code(too_many));
+    assert_eq!(Some(ApiErrorCode::CouldntFindCommunity), error_code(none));
+    assert_eq!(1, rate_limited.created_count());
+    assert!(rate_limited.results[0].post_view.is_some());
+    assert_eq!(
+      Some(ApiErrorCode::RateLimited),
+      rate_limited.results[1]
+        .error
+        .as_ref()
+        .map(|e| e.code.to_owned())
+    );
+  }
+}
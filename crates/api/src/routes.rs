@@ -1,10 +1,16 @@
 use crate::Perform;
-use actix_web::{error::ErrorBadRequest, *};
+use actix_web::{http::header, *};
 use lemmy_api_structs::{comment::*, community::*, person::*, post::*, site::*, websocket::*};
-use lemmy_utils::rate_limit::RateLimit;
+use lemmy_utils::{
+  rate_limit::RateLimit,
+  utils::{get_ip, truncate_ip},
+};
 use lemmy_websocket::{routes::chat_route, LemmyContext};
 use serde::Deserialize;
 
+/// Longer user agents are cut off before they are stored in the login session
+const MAX_USER_AGENT_LENGTH: usize = 200;
+
 pub fn config(cfg: &mut web::ServiceConfig, rate_limit: &RateLimit) {
   cfg.service(
     web::scope("/api/v2")
@@ -20,7 +26,28 @@ pub fn config(cfg: &mut web::ServiceConfig, rate_limit: &RateLimit) {
           .route("", web::put().to(route_post::<EditSite>))
           .route("/transfer", web::post().to(route_post::<TransferSite>))
           .route("/config", web::get().to(route_get::<GetSiteConfig>))
-          .route("/config", web::put().to(route_post::<SaveSiteConfig>)),
+          .route("/config", web::put().to(route_post::<SaveSiteConfig>))
+          .route("/admin/stats", web::get().to(route_get::<GetAdminStats>))
+          .route(
+            "/admin/spam_rules",
+            web::get().to(route_get::<GetSpamRules>),
+          )
+          .route(
+            "/admin/spam_rules",
+            web::put().to(route_post::<EditSpamRules>),
+          )
+          .route(
+            "/admin/recalculate_aggregates",
+            web::post().to(route_post::<RecalculateAggregates>),
+          )
+          .route(
+            "/admin/scheduled_task/list",
+            web::get().to(route_get::<ListScheduledTasks>),
+          )
+          .route(
+            "/admin/scheduled_task/run",
+            web::post().to(route_post::<RunScheduledTask>),
+          ),
       )
       .service(
         web::resource("/modlog")
@@ -45,10 +72,43 @@ pub fn config(cfg: &mut web::ServiceConfig, rate_limit: &RateLimit) {
           .route("", web::get().to(route_get::<GetCommunity>))
           .route("", web::put().to(route_post::<EditCommunity>))
           .route("/list", web::get().to(route_get::<ListCommunities>))
+          .route(
+            "/autocomplete",
+            web::get().to(route_get::<CommunityAutocomplete>),
+          )
+          .route(
+            "/federation_status",
+            web::get().to(route_get::<GetCommunityFederationStatus>),
+          )
+          .route(
+            "/refresh",
+            web::post().to(route_post::<RefreshRemoteCommunity>),
+          )
+          .route("/export", web::get().to(route_get::<ExportCommunity>))
+          .route("/import", web::post().to(route_post::<ImportCommunity>))
           .route("/follow", web::post().to(route_post::<FollowCommunity>))
+          .route("/favorite", web::post().to(route_post::<FavoriteCommunity>))
+          .route("/mute", web::post().to(route_post::<MuteCommunity>))
+          .route(
+            "/follow_requests",
+            web::get().to(route_get::<ListFollowRequests>),
+          )
+          .route(
+            "/follow_requests/approve",
+            web::post().to(route_post::<ApproveFollowRequest>),
+          )
           .route("/delete", web::post().to(route_post::<DeleteCommunity>))
           // Mod Actions
           .route("/remove", web::post().to(route_post::<RemoveCommunity>))
+          .route(
+            "/exempt_from_slur_filter",
+            web::post().to(route_post::<ExemptCommunityFromSlurFilter>),
+          )
+          .route("/tag", web::post().to(route_post::<CreateCommunityTag>))
+          .route(
+            "/tag/delete",
+            web::post().to(route_post::<DeleteCommunityTag>),
+          )
           .route("/transfer", web::post().to(route_post::<TransferCommunity>))
           .route("/ban_user", web::post().to(route_post::<BanFromCommunity>))
           .route("/mod", web::post().to(route_post::<AddModToCommunity>))
@@ -63,19 +123,34 @@ pub fn config(cfg: &mut web::ServiceConfig, rate_limit: &RateLimit) {
           .wrap(rate_limit.post())
           .route(web::post().to(route_post::<CreatePost>)),
       )
+      // Each created post counts against the post rate limit
+      .service(
+        web::resource("/post/multi")
+          .guard(guard::Post())
+          .data(rate_limit.clone())
+          .route(web::post().to(route_create_multi_post)),
+      )
       .service(
         web::scope("/post")
           .wrap(rate_limit.message())
-          .route("", web::get().to(route_get::<GetPost>))
+          .route("", web::get().to(route_get_view::<GetPost>))
           .route("", web::put().to(route_post::<EditPost>))
           .route("/delete", web::post().to(route_post::<DeletePost>))
           .route("/remove", web::post().to(route_post::<RemovePost>))
+          .route("/pending", web::get().to(route_get::<ListPendingPosts>))
+          .route("/approve", web::post().to(route_post::<ApprovePost>))
           .route("/lock", web::post().to(route_post::<LockPost>))
           .route("/sticky", web::post().to(route_post::<StickyPost>))
+          .route(
+            "/refetch_metadata",
+            web::post().to(route_post::<RefetchPostMetadata>),
+          )
           .route("/list", web::get().to(route_get::<GetPosts>))
+          .route("/random", web::get().to(route_get::<GetRandomPost>))
           .route("/like", web::post().to(route_post::<CreatePostLike>))
           .route("/save", web::put().to(route_post::<SavePost>))
           .route("/join", web::post().to(route_post::<PostJoin>))
+          .route("/viewed", web::post().to(route_post_view::<MarkPostViewed>))
           .route("/report", web::post().to(route_post::<CreatePostReport>))
           .route(
             "/report/resolve",
@@ -89,8 +164,11 @@ pub fn config(cfg: &mut web::ServiceConfig, rate_limit: &RateLimit) {
           .wrap(rate_limit.message())
           .route("", web::post().to(route_post::<CreateComment>))
           .route("", web::put().to(route_post::<EditComment>))
+          .route("", web::get().to(route_get::<GetComment>))
           .route("/delete", web::post().to(route_post::<DeleteComment>))
           .route("/remove", web::post().to(route_post::<RemoveComment>))
+          .route("/lock", web::post().to(route_post::<LockComment>))
+          .route("/sticky", web::post().to(route_post::<StickyComment>))
           .route(
             "/mark_as_read",
             web::post().to(route_post::<MarkCommentAsRead>),
@@ -131,7 +209,7 @@ pub fn config(cfg: &mut web::ServiceConfig, rate_limit: &RateLimit) {
         web::resource("/user/register")
           .guard(guard::Post())
           .wrap(rate_limit.register())
-          .route(web::post().to(route_post::<Register>)),
+          .route(web::post().to(route_login::<Register>)),
       )
       // User actions
       .service(
@@ -143,7 +221,13 @@ pub fn config(cfg: &mut web::ServiceConfig, rate_limit: &RateLimit) {
             "/mention/mark_as_read",
             web::post().to(route_post::<MarkPersonMentionAsRead>),
           )
+          .route(
+            "/post_mention/mark_as_read",
+            web::post().to(route_post::<MarkPersonPostMentionAsRead>),
+          )
           .route("/replies", web::get().to(route_get::<GetReplies>))
+          .route("/pin_post", web::post().to(route_post::<PinPersonPost>))
+          .route("/note", web::post().to(route_post::<SetPersonNote>))
           .route(
             "/followed_communities",
             web::get().to(route_get::<GetFollowedCommunities>),
@@ -151,9 +235,18 @@ pub fn config(cfg: &mut web::ServiceConfig, rate_limit: &RateLimit) {
           .route("/join", web::post().to(route_post::<UserJoin>))
           // Admin action. I don't like that it's in /user
           .route("/ban", web::post().to(route_post::<BanPerson>))
+          .route("/merge", web::post().to(route_post::<MergePersons>))
           // Account actions. I don't like that they're in /user maybe /accounts
-          .route("/login", web::post().to(route_post::<Login>))
+          .route("/login", web::post().to(route_login::<Login>))
           .route("/get_captcha", web::get().to(route_get::<GetCaptcha>))
+          .route(
+            "/get_registration_challenge",
+            web::get().to(route_get::<GetRegistrationChallenge>),
+          )
+          .route(
+            "/check_username",
+            web::get().to(route_get::<CheckUsernameAvailable>),
+          )
           .route(
             "/delete_account",
             web::post().to(route_post::<DeleteAccount>),
@@ -164,7 +257,18 @@ pub fn config(cfg: &mut web::ServiceConfig, rate_limit: &RateLimit) {
           )
           .route(
             "/password_change",
-            web::post().to(route_post::<PasswordChange>),
+            web::post().to(route_login::<PasswordChange>),
+          )
+          .route("/session/list", web::get().to(route_get::<ListSessions>))
+          .route(
+            "/session/revoke",
+            web::post().to(route_post::<RevokeSession>),
+          )
+          .route("/api_token", web::post().to(route_post::<CreateApiToken>))
+          .route("/api_token/list", web::get().to(route_get::<ListApiTokens>))
+          .route(
+            "/api_token/revoke",
+            web::post().to(route_post::<RevokeApiToken>),
           )
           // mark_all_as_read feels off being in this section as well
           .route(
@@ -194,11 +298,10 @@ where
   Request: Perform,
   Request: Send + 'static,
 {
-  let res = data
-    .perform(&context, None)
-    .await
-    .map(|json| HttpResponse::Ok().json(json))
-    .map_err(ErrorBadRequest)?;
+  let res = match data.perform(&context, None).await {
+    Ok(json) => HttpResponse::Ok().json(json),
+    Err(e) => HttpResponse::BadRequest().json(e.to_api_error()),
+  };
   Ok(res)
 }
 
@@ -221,3 +324,78 @@ where
 {
   perform::<Data>(data.0, context).await
 }
+
+/// For requests which count as post views, and need the ip to count anonymous viewers once
+async fn route_get_view<'a, Data>(
+  data: web::Query<Data>,
+  req: HttpRequest,
+  context: web::Data<LemmyContext>,
+) -> Result<HttpResponse, Error>
+where
+  Data: Deserialize<'a> + Send + 'static + Perform + PostViewRequest,
+{
+  let mut data = data.0;
+  data.set_viewer_ip(get_ip(&req.connection_info()));
+  perform::<Data>(data, context).await
+}
+
+async fn route_post_view<'a, Data>(
+  data: web::Json<Data>,
+  req: HttpRequest,
+  context: web::Data<LemmyContext>,
+) -> Result<HttpResponse, Error>
+where
+  Data: Deserialize<'a> + Send + 'static + Perform + PostViewRequest,
+{
+  let mut data = data.0;
+  data.set_viewer_ip(get_ip(&req.connection_info()));
+  perform::<Data>(data, context).await
+}
+
+/// Rate limited by hand, because only as many posts as the allowance covers are created
+async fn route_create_multi_post(
+  data: web::Json<CreateMultiPost>,
+  req: HttpRequest,
+  rate_limit: web::Data<RateLimit>,
+  context: web::Data<LemmyContext>,
+) -> Result<HttpResponse, Error> {
+  let mut data = data.0;
+  let max_count = data.community_ids.len();
+  let res = rate_limit
+    .post()
+    .wrap_many(
+      get_ip(&req.connection_info()),
+      max_count,
+      move |allowed| {
+        data.rate_limit_allowance = Some(allowed);
+        async move { data.perform(&context, None).await }
+      },
+      CreateMultiPostResponse::created_count,
+    )
+    .await;
+  let res = match res {
+    Ok(json) => HttpResponse::Ok().json(json),
+    Err(e) => HttpResponse::BadRequest().json(e.to_api_error()),
+  };
+  Ok(res)
+}
+
+/// For requests which start a login session, and need to know where the login comes from
+async fn route_login<'a, Data>(
+  data: web::Json<Data>,
+  req: HttpRequest,
+  context: web::Data<LemmyContext>,
+) -> Result<HttpResponse, Error>
+where
+  Data: Deserialize<'a> + Send + 'static + Perform + LoginRequest,
+{
+  let user_agent = req
+    .headers()
+    .get(header::USER_AGENT)
+    .and_then(|h| h.to_str().ok())
+    .map(|h| h.chars().take(MAX_USER_AGENT_LENGTH).collect());
+  let ip = truncate_ip(&get_ip(&req.connection_info()));
+  let mut data = data.0;
+  data.set_client(LoginClient { user_agent, ip });
+  perform::<Data>(data, context).await
+}
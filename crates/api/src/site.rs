@@ -1,32 +1,66 @@
 use crate::{
+  add_federated_instance_details,
   build_federated_instances,
+  check_images_are_local,
+  check_sort_allowed,
   get_local_user_settings_view_from_jwt,
   get_local_user_settings_view_from_jwt_opt,
   get_local_user_view_from_jwt,
   get_local_user_view_from_jwt_opt,
+  get_local_user_view_from_jwt_read_only,
   is_admin,
   Perform,
 };
 use actix_web::web::Data;
 use anyhow::Context;
-use lemmy_api_structs::{blocking, person::Register, site::*};
-use lemmy_apub::fetcher::search::search_by_apub_id;
+use lemmy_api_structs::{
+  blocking,
+  blocking_read,
+  blocking_transaction,
+  hide_downvotes_if_disabled,
+  invalidate_spam_rules,
+  person::{LoginClient, Register},
+  site::*,
+};
+use lemmy_apub::fetcher::{modlog::fetch_remote_modlog, search::search_by_apub_id};
 use lemmy_db_queries::{
+  aggregates::{
+    community_aggregates::CommunityAggregates,
+    person_aggregates::PersonAggregates,
+    post_aggregates::PostAggregates,
+    site_aggregates::SiteAggregates,
+  },
   diesel_option_overwrite_to_url,
-  source::site::Site_,
+  source::{
+    language::Language_,
+    registration_question::RegistrationQuestion_,
+    site::Site_,
+    spam_rule::SpamRule_,
+    task_lock::TaskLock_,
+  },
   Crud,
+  DbPool,
+  ListingType,
+  RegistrationChallenge,
   SearchType,
-  SortType,
+  SpamRuleKind,
 };
 use lemmy_db_schema::{
   naive_now,
   source::{
+    community::Community,
+    language::Language,
     moderator::*,
+    registration_question::{RegistrationQuestion, RegistrationQuestionForm},
     site::{Site, *},
+    spam_rule::{SpamRule, SpamRuleForm},
+    task_lock::TaskLock,
   },
+  LanguageId,
 };
 use lemmy_db_views::{
   comment_view::CommentQueryBuilder,
+  local_user_view::LocalUserSettingsView,
   post_view::PostQueryBuilder,
   site_view::SiteView,
 };
@@ -35,22 +69,37 @@ use lemmy_db_views_actor::{
   person_view::{PersonQueryBuilder, PersonViewSafe},
 };
 use lemmy_db_views_moderator::{
+  admin_auto_remove_view::AdminAutoRemoveView,
   mod_add_community_view::ModAddCommunityView,
   mod_add_view::ModAddView,
   mod_ban_from_community_view::ModBanFromCommunityView,
   mod_ban_view::ModBanView,
+  mod_edit_community_view::ModEditCommunityView,
+  mod_exempt_community_view::ModExemptCommunityView,
+  mod_feature_post_view::ModFeaturePostView,
+  mod_lock_comment_view::ModLockCommentView,
   mod_lock_post_view::ModLockPostView,
   mod_remove_comment_view::ModRemoveCommentView,
   mod_remove_community_view::ModRemoveCommunityView,
   mod_remove_post_view::ModRemovePostView,
+  mod_sticky_comment_view::ModStickyCommentView,
   mod_sticky_post_view::ModStickyPostView,
 };
 use lemmy_utils::{
+  federation_mode::FederationMode,
   location_info,
   settings::structs::Settings,
-  utils::{check_slurs, check_slurs_opt},
+  utils::{
+    build_slur_regex,
+    check_image_is_local,
+    check_slurs,
+    check_slurs_opt,
+    DEFAULT_SLUR_REGEX,
+    SUPPORTED_URL_SCHEMES,
+  },
   version,
   ApiError,
+  ApiErrorCode,
   ConnectionId,
   LemmyError,
 };
@@ -60,7 +109,7 @@ use lemmy_websocket::{
   UserOperation,
 };
 use log::{debug, info};
-use std::str::FromStr;
+use regex::Regex;
 
 #[async_trait::async_trait(?Send)]
 impl Perform for GetModlog {
@@ -77,65 +126,204 @@ impl Perform for GetModlog {
     let mod_person_id = data.mod_person_id;
     let page = data.page;
     let limit = data.limit;
-    let removed_posts = blocking(context.pool(), move |conn| {
+
+    let removed_posts = blocking_read(context.read_pool(), move |conn| {
       ModRemovePostView::list(conn, community_id, mod_person_id, page, limit)
     })
     .await??;
 
-    let locked_posts = blocking(context.pool(), move |conn| {
+    let locked_posts = blocking_read(context.read_pool(), move |conn| {
       ModLockPostView::list(conn, community_id, mod_person_id, page, limit)
     })
     .await??;
 
-    let stickied_posts = blocking(context.pool(), move |conn| {
+    let stickied_posts = blocking_read(context.read_pool(), move |conn| {
       ModStickyPostView::list(conn, community_id, mod_person_id, page, limit)
     })
     .await??;
 
-    let removed_comments = blocking(context.pool(), move |conn| {
+    let removed_comments = blocking_read(context.read_pool(), move |conn| {
       ModRemoveCommentView::list(conn, community_id, mod_person_id, page, limit)
     })
     .await??;
 
-    let banned_from_community = blocking(context.pool(), move |conn| {
+    let locked_comments = blocking_read(context.read_pool(), move |conn| {
+      ModLockCommentView::list(conn, community_id, mod_person_id, page, limit)
+    })
+    .await??;
+
+    let stickied_comments = blocking_read(context.read_pool(), move |conn| {
+      ModStickyCommentView::list(conn, community_id, mod_person_id, page, limit)
+    })
+    .await??;
+
+    let banned_from_community = blocking_read(context.read_pool(), move |conn| {
       ModBanFromCommunityView::list(conn, community_id, mod_person_id, page, limit)
     })
     .await??;
 
-    let added_to_community = blocking(context.pool(), move |conn| {
+    let added_to_community = blocking_read(context.read_pool(), move |conn| {
       ModAddCommunityView::list(conn, community_id, mod_person_id, page, limit)
     })
     .await??;
 
-    // These arrays are only for the full modlog, when a community isn't given
-    let (removed_communities, banned, added) = if data.community_id.is_none() {
-      blocking(context.pool(), move |conn| {
-        Ok((
-          ModRemoveCommunityView::list(conn, mod_person_id, page, limit)?,
-          ModBanView::list(conn, mod_person_id, page, limit)?,
-          ModAddView::list(conn, mod_person_id, page, limit)?,
-        )) as Result<_, LemmyError>
+    let edited_communities = blocking_read(context.read_pool(), move |conn| {
+      ModEditCommunityView::list(conn, community_id, mod_person_id, page, limit)
+    })
+    .await??;
+
+    // Spam rules remove content without a moderator
+    let auto_removed = if mod_person_id.is_none() {
+      blocking_read(context.read_pool(), move |conn| {
+        AdminAutoRemoveView::list(conn, community_id, page, limit)
       })
       .await??
     } else {
-      (Vec::new(), Vec::new(), Vec::new())
+      Vec::new()
     };
 
-    // Return the jwt
-    Ok(GetModlogResponse {
+    // These arrays are only for the full modlog, when a community isn't given
+    let (featured_posts, removed_communities, exempted_communities, banned, added) =
+      if data.community_id.is_none() {
+        blocking_read(context.read_pool(), move |conn| {
+          Ok((
+            ModFeaturePostView::list(conn, mod_person_id, page, limit)?,
+            ModRemoveCommunityView::list(conn, mod_person_id, page, limit)?,
+            ModExemptCommunityView::list(conn, mod_person_id, page, limit)?,
+            ModBanView::list(conn, mod_person_id, page, limit)?,
+            ModAddView::list(conn, mod_person_id, page, limit)?,
+          )) as Result<_, LemmyError>
+        })
+        .await??
+      } else {
+        (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new())
+      };
+
+    let mut modlog = GetModlogResponse {
       removed_posts,
       locked_posts,
       stickied_posts,
+      featured_posts,
       removed_comments,
+      locked_comments,
+      stickied_comments,
       removed_communities,
+      exempted_communities,
+      edited_communities,
       banned_from_community,
       banned,
       added_to_community,
       added,
-    })
+      auto_removed,
+      remote: false,
+    };
+
+    // The modlog of a remote community is also fetched from its instance, if it exposes one
+    if let Some(community_id) = community_id {
+      let community = blocking_read(context.read_pool(), move |conn| {
+        Community::read(conn, community_id)
+      })
+      .await??;
+      if !community.local {
+        fetch_remote_modlog(&mut modlog, &community, mod_person_id, page, context).await?;
+      }
+    }
+
+    Ok(modlog)
   }
 }
 
+/// Validates the post and comment limits of `CreateSite` and `EditSite`, and returns the allowed
+/// url schemes in lowercase.
+fn check_content_limits(
+  max_post_body_length: Option<i32>,
+  max_comment_length: Option<i32>,
+  allowed_url_schemes: &Option<Vec<String>>,
+) -> Result<Option<Vec<String>>, ApiError> {
+  let lengths = [
+    ("max_post_body_length", max_post_body_length),
+    ("max_comment_length", max_comment_length),
+  ];
+  for (field, length) in lengths.iter() {
+    if let Some(length) = length {
+      if *length <= 0 {
+        return Err(ApiError::new(ApiErrorCode::InvalidContentLimits).with_field(field));
+      }
+    }
+  }
+
+  let mut schemes = match allowed_url_schemes {
+    Some(schemes) => schemes
+      .iter()
+      .map(|scheme| scheme.to_lowercase())
+      .collect::<Vec<String>>(),
+    None => return Ok(None),
+  };
+  if let Some(scheme) = schemes
+    .iter()
+    .find(|scheme| !SUPPORTED_URL_SCHEMES.contains(&scheme.as_str()))
+  {
+    return Err(
+      ApiError::new(ApiErrorCode::InvalidContentLimits)
+        .with_field("allowed_url_schemes")
+        .with_detail("scheme", scheme.as_str()),
+    );
+  }
+  schemes.sort();
+  schemes.dedup();
+  Ok(Some(schemes))
+}
+
+/// Only listings which make sense without a login can be the default for anonymous users
+fn check_default_post_listing_type(
+  listing_type: &Option<ListingType>,
+) -> Result<Option<i16>, ApiError> {
+  match listing_type {
+    Some(ListingType::All) | Some(ListingType::Local) | None => {
+      Ok(listing_type.map(|listing_type| listing_type as i16))
+    }
+    Some(_) => Err(ApiError::err("invalid_listing_type").with_field("default_post_listing_type")),
+  }
+}
+
+/// Validates the settings of the registration challenge, and converts the questions into forms
+fn check_registration_challenge(
+  proof_of_work_difficulty: Option<i32>,
+  registration_questions: &Option<Vec<RegistrationQuestionInput>>,
+) -> Result<Option<Vec<RegistrationQuestionForm>>, ApiError> {
+  if let Some(difficulty) = proof_of_work_difficulty {
+    if !(1..=32).contains(&difficulty) {
+      return Err(
+        ApiError::err("invalid_proof_of_work_difficulty").with_field("proof_of_work_difficulty"),
+      );
+    }
+  }
+
+  let questions = match registration_questions {
+    Some(questions) => questions,
+    None => return Ok(None),
+  };
+  let mut forms = Vec::new();
+  for input in questions {
+    let answers: Vec<String> = input
+      .answers
+      .iter()
+      .map(|answer| answer.trim().to_owned())
+      .filter(|answer| !answer.is_empty())
+      .collect();
+    if input.question.trim().is_empty() || answers.is_empty() {
+      return Err(
+        ApiError::err("invalid_registration_question").with_field("registration_questions"),
+      );
+    }
+    forms.push(RegistrationQuestionForm {
+      question: input.question.trim().to_owned(),
+      answers,
+    });
+  }
+  Ok(Some(forms))
+}
+
 #[async_trait::async_trait(?Send)]
 impl Perform for CreateSite {
   type Response = SiteResponse;
@@ -149,22 +337,60 @@ impl Perform for CreateSite {
 
     let read_site = move |conn: &'_ _| Site::read_simple(conn);
     if blocking(context.pool(), read_site).await?.is_ok() {
-      return Err(ApiError::err("site_already_exists").into());
+      return Err(ApiError::new(ApiErrorCode::SiteAlreadyExists).into());
     };
 
     let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
 
-    check_slurs(&data.name)?;
-    check_slurs_opt(&data.description)?;
+    let slur_regex = context.slur_filter().regex();
+    check_slurs(&data.name, &slur_regex)?;
+    check_slurs_opt(&data.description, &slur_regex)?;
 
     // Make sure user is an admin
     is_admin(&local_user_view)?;
 
+    if data.require_local_images == Some(true) {
+      if let Some(icon) = &data.icon {
+        check_image_is_local(icon, "icon")?;
+      }
+      if let Some(banner) = &data.banner {
+        check_image_is_local(banner, "banner")?;
+      }
+    }
+    let allowed_url_schemes = check_content_limits(
+      data.max_post_body_length,
+      data.max_comment_length,
+      &data.allowed_url_schemes,
+    )?;
+    let default_post_listing_type =
+      check_default_post_listing_type(&data.default_post_listing_type)?;
+    let question_forms =
+      check_registration_challenge(data.proof_of_work_difficulty, &data.registration_questions)?;
+    if data.registration_challenge == Some(RegistrationChallenge::Question)
+      && question_forms
+        .as_ref()
+        .map(|q| q.is_empty())
+        .unwrap_or(true)
+    {
+      return Err(ApiError::err("no_registration_questions").into());
+    }
+
     let site_form = SiteForm {
       name: data.name.to_owned(),
       description: data.description.to_owned(),
       icon: Some(data.icon.to_owned().map(|url| url.into())),
       banner: Some(data.banner.to_owned().map(|url| url.into())),
+      slur_filter_regex: Some(Some(DEFAULT_SLUR_REGEX.to_owned())),
+      require_local_images: data.require_local_images,
+      max_post_body_length: data.max_post_body_length,
+      max_comment_length: data.max_comment_length,
+      allowed_url_schemes,
+      default_post_listing_type,
+      show_deleted_usernames_to_admins: data.show_deleted_usernames_to_admins,
+      federation_mode: data.federation_mode.map(|mode| mode as i16),
+      federate_votes_as: data.federate_votes_as.map(|mode| mode as i16),
+      registration_challenge: data.registration_challenge.map(|mode| mode as i16),
+      proof_of_work_difficulty: data.proof_of_work_difficulty,
       creator_id: local_user_view.person.id,
       enable_downvotes: data.enable_downvotes,
       open_registration: data.open_registration,
@@ -174,8 +400,21 @@ impl Perform for CreateSite {
 
     let create_site = move |conn: &'_ _| Site::create(conn, &site_form);
     if blocking(context.pool(), create_site).await?.is_err() {
-      return Err(ApiError::err("site_already_exists").into());
+      return Err(ApiError::new(ApiErrorCode::SiteAlreadyExists).into());
+    }
+    if let Some(question_forms) = question_forms {
+      blocking(context.pool(), move |conn| {
+        RegistrationQuestion::replace_all(conn, &question_forms)
+      })
+      .await??;
     }
+    if let Some(federation_mode) = data.federation_mode {
+      FederationMode::set(federation_mode);
+    }
+    context.site_cache().invalidate().await;
+    context
+      .slur_filter()
+      .set(build_slur_regex(Some(DEFAULT_SLUR_REGEX))?);
 
     let site_view = blocking(context.pool(), move |conn| SiteView::read(conn)).await??;
 
@@ -194,22 +433,73 @@ impl Perform for EditSite {
     let data: &EditSite = &self;
     let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
 
-    check_slurs(&data.name)?;
-    check_slurs_opt(&data.description)?;
-
     // Make sure user is an admin
     is_admin(&local_user_view)?;
 
+    // Validate the new filter before it is stored, and apply it to the other fields already
+    let slur_regex = match &data.slur_filter_regex {
+      Some(slur_filter_regex) => build_slur_regex(Some(slur_filter_regex))?,
+      None => context.slur_filter().regex(),
+    };
+    check_slurs(&data.name, &slur_regex)?;
+    check_slurs_opt(&data.description, &slur_regex)?;
+
     let found_site = blocking(context.pool(), move |conn| Site::read_simple(conn)).await??;
 
     let icon = diesel_option_overwrite_to_url(&data.icon)?;
     let banner = diesel_option_overwrite_to_url(&data.banner)?;
+    // Use the new value of the setting, so it can be enabled together with a local icon
+    if data
+      .require_local_images
+      .unwrap_or(found_site.require_local_images)
+    {
+      check_images_are_local(&[("icon", &icon), ("banner", &banner)])?;
+    }
+    let allowed_url_schemes = check_content_limits(
+      data.max_post_body_length,
+      data.max_comment_length,
+      &data.allowed_url_schemes,
+    )?;
+    let default_post_listing_type =
+      check_default_post_listing_type(&data.default_post_listing_type)?;
+    let question_forms =
+      check_registration_challenge(data.proof_of_work_difficulty, &data.registration_questions)?;
+    // Question challenges need at least one question, either a new one or an existing one
+    let registration_challenge = data
+      .registration_challenge
+      .unwrap_or_else(|| RegistrationChallenge::from_i16(found_site.registration_challenge));
+    if registration_challenge == RegistrationChallenge::Question {
+      let question_count = match &question_forms {
+        Some(question_forms) => question_forms.len(),
+        None => blocking(context.pool(), move |conn| RegistrationQuestion::list(conn))
+          .await??
+          .len(),
+      };
+      if question_count == 0 {
+        return Err(ApiError::err("no_registration_questions").into());
+      }
+    }
 
     let site_form = SiteForm {
       name: data.name.to_owned(),
       description: data.description.to_owned(),
       icon,
       banner,
+      // An empty regex disables the filter, and is stored as null
+      slur_filter_regex: data
+        .slur_filter_regex
+        .to_owned()
+        .map(|regex| Some(regex).filter(|regex| !regex.is_empty())),
+      require_local_images: data.require_local_images,
+      max_post_body_length: data.max_post_body_length,
+      max_comment_length: data.max_comment_length,
+      allowed_url_schemes,
+      default_post_listing_type,
+      show_deleted_usernames_to_admins: data.show_deleted_usernames_to_admins,
+      federation_mode: data.federation_mode.map(|mode| mode as i16),
+      federate_votes_as: data.federate_votes_as.map(|mode| mode as i16),
+      registration_challenge: data.registration_challenge.map(|mode| mode as i16),
+      proof_of_work_difficulty: data.proof_of_work_difficulty,
       creator_id: found_site.creator_id,
       updated: Some(naive_now()),
       enable_downvotes: data.enable_downvotes,
@@ -219,7 +509,21 @@ impl Perform for EditSite {
 
     let update_site = move |conn: &'_ _| Site::update(conn, 1, &site_form);
     if blocking(context.pool(), update_site).await?.is_err() {
-      return Err(ApiError::err("couldnt_update_site").into());
+      return Err(ApiError::new(ApiErrorCode::CouldntUpdateSite).into());
+    }
+    if let Some(question_forms) = question_forms {
+      blocking(context.pool(), move |conn| {
+        RegistrationQuestion::replace_all(conn, &question_forms)
+      })
+      .await??;
+    }
+    // Set the mode first, so that the rebuilt site cache lists the right instances
+    if let Some(federation_mode) = data.federation_mode {
+      FederationMode::set(federation_mode);
+    }
+    context.site_cache().invalidate().await;
+    if data.slur_filter_regex.is_some() {
+      context.slur_filter().set(slur_regex);
     }
 
     let site_view = blocking(context.pool(), move |conn| SiteView::read(conn)).await??;
@@ -247,56 +551,10 @@ impl Perform for GetSite {
   ) -> Result<GetSiteResponse, LemmyError> {
     let data: &GetSite = &self;
 
-    let site_view = match blocking(context.pool(), move |conn| SiteView::read(conn)).await? {
-      Ok(site_view) => Some(site_view),
-      // If the site isn't created yet, check the setup
-      Err(_) => {
-        if let Some(setup) = Settings::get().setup().as_ref() {
-          let register = Register {
-            username: setup.admin_username.to_owned(),
-            email: setup.admin_email.to_owned(),
-            password: setup.admin_password.to_owned(),
-            password_verify: setup.admin_password.to_owned(),
-            show_nsfw: true,
-            captcha_uuid: None,
-            captcha_answer: None,
-          };
-          let login_response = register.perform(context, websocket_id).await?;
-          info!("Admin {} created", setup.admin_username);
-
-          let create_site = CreateSite {
-            name: setup.site_name.to_owned(),
-            description: None,
-            icon: None,
-            banner: None,
-            enable_downvotes: true,
-            open_registration: true,
-            enable_nsfw: true,
-            auth: login_response.jwt,
-          };
-          create_site.perform(context, websocket_id).await?;
-          info!("Site {} created", setup.site_name);
-          Some(blocking(context.pool(), move |conn| SiteView::read(conn)).await??)
-        } else {
-          None
-        }
-      }
-    };
-
-    let mut admins = blocking(context.pool(), move |conn| PersonViewSafe::admins(conn)).await??;
-
-    // Make sure the site creator is the top admin
-    if let Some(site_view) = site_view.to_owned() {
-      let site_creator_id = site_view.creator.id;
-      // TODO investigate why this is sometimes coming back null
-      // Maybe user_.admin isn't being set to true?
-      if let Some(creator_index) = admins.iter().position(|r| r.person.id == site_creator_id) {
-        let creator_person = admins.remove(creator_index);
-        admins.insert(0, creator_person);
-      }
-    }
-
-    let banned = blocking(context.pool(), move |conn| PersonViewSafe::banned(conn)).await??;
+    let cached = context
+      .site_cache()
+      .get_or_refresh(|| read_site(context, websocket_id))
+      .await?;
 
     let online = context
       .chat_server()
@@ -305,20 +563,155 @@ impl Perform for GetSite {
       .unwrap_or(1);
 
     let my_user = get_local_user_settings_view_from_jwt_opt(&data.auth, context.pool()).await?;
-    let federated_instances = build_federated_instances(context.pool()).await?;
+
+    let content_limits = cached
+      .site_view
+      .as_ref()
+      .map(|site_view| ContentLimits::from(&site_view.site))
+      .unwrap_or_default();
+
+    let is_admin = my_user
+      .as_ref()
+      .map(|u| u.local_user.admin)
+      .unwrap_or(false);
+    let federated_instances = if is_admin {
+      add_federated_instance_details(cached.federated_instances, context.read_pool()).await?
+    } else {
+      cached.federated_instances
+    };
+    // The answers are only shown to admins
+    let registration_questions = if is_admin {
+      Some(
+        blocking_read(context.read_pool(), move |conn| {
+          RegistrationQuestion::list(conn)
+        })
+        .await??,
+      )
+    } else {
+      None
+    };
+
+    let discussion_languages = read_discussion_languages(&my_user, context.read_pool()).await?;
 
     Ok(GetSiteResponse {
-      site_view,
-      admins,
-      banned,
+      site_view: cached.site_view,
+      admins: cached.admins,
+      banned: cached.banned,
       online,
       version: version::VERSION.to_string(),
       my_user,
       federated_instances,
+      content_limits,
+      registration_questions,
+      all_languages: cached.all_languages,
+      discussion_languages,
     })
   }
 }
 
+/// The languages which the logged in user reads, empty if nobody is logged in
+async fn read_discussion_languages(
+  my_user: &Option<LocalUserSettingsView>,
+  pool: &DbPool,
+) -> Result<Vec<LanguageId>, LemmyError> {
+  match my_user {
+    Some(my_user) => {
+      let local_user_id = my_user.local_user.id;
+      Ok(
+        blocking(pool, move |conn| {
+          Language::read_local_user_languages(conn, local_user_id)
+        })
+        .await??,
+      )
+    }
+    None => Ok(Vec::new()),
+  }
+}
+
+/// Reads the parts of `GetSite` which are the same for every user, and sets up the site from the
+/// config if it doesn't exist yet. This reads from the primary database, as the result is cached
+/// right after an invalidation, and a lagging replica would put the old state back into the cache.
+pub(crate) async fn read_site(
+  context: &Data<LemmyContext>,
+  websocket_id: Option<ConnectionId>,
+) -> Result<CachedSite, LemmyError> {
+  let site_view = match blocking(context.pool(), move |conn| SiteView::read(conn)).await? {
+    Ok(site_view) => Some(site_view),
+    // If the site isn't created yet, check the setup
+    Err(_) => {
+      if let Some(setup) = Settings::get().setup().as_ref() {
+        let register = Register {
+          username: setup.admin_username.to_owned(),
+          email: setup.admin_email.to_owned(),
+          password: setup.admin_password.to_owned(),
+          password_verify: setup.admin_password.to_owned(),
+          show_nsfw: true,
+          captcha_uuid: None,
+          captcha_answer: None,
+          client: LoginClient::default(),
+        };
+        let login_response = register.perform(context, websocket_id).await?;
+        info!("Admin {} created", setup.admin_username);
+
+        let create_site = CreateSite {
+          name: setup.site_name.to_owned(),
+          description: None,
+          icon: None,
+          banner: None,
+          enable_downvotes: true,
+          open_registration: true,
+          enable_nsfw: true,
+          require_local_images: None,
+          max_post_body_length: None,
+          max_comment_length: None,
+          allowed_url_schemes: None,
+          default_post_listing_type: None,
+          show_deleted_usernames_to_admins: None,
+          federation_mode: None,
+          federate_votes_as: None,
+          registration_challenge: None,
+          proof_of_work_difficulty: None,
+          registration_questions: None,
+          auth: login_response.jwt,
+        };
+        create_site.perform(context, websocket_id).await?;
+        info!("Site {} created", setup.site_name);
+        Some(blocking(context.pool(), move |conn| SiteView::read(conn)).await??)
+      } else {
+        None
+      }
+    }
+  };
+
+  let mut admins = blocking(context.pool(), move |conn| PersonViewSafe::admins(conn)).await??;
+
+  // Make sure the site creator is the top admin
+  if let Some(site_view) = site_view.to_owned() {
+    let site_creator_id = site_view.creator.id;
+    // TODO investigate why this is sometimes coming back null
+    // Maybe user_.admin isn't being set to true?
+    if let Some(creator_index) = admins.iter().position(|r| r.person.id == site_creator_id) {
+      let creator_person = admins.remove(creator_index);
+      admins.insert(0, creator_person);
+    }
+  }
+
+  let banned = blocking(context.pool(), move |conn| PersonViewSafe::banned(conn)).await??;
+
+  let federated_instances = build_federated_instances(context.pool()).await?;
+
+  let all_languages =
+    blocking_read(context.read_pool(), move |conn| Language::read_all(conn)).await??;
+
+  Ok(CachedSite {
+    site_view,
+    admins,
+    banned,
+    federated_instances,
+    all_languages,
+  })
+}
+
 #[async_trait::async_trait(?Send)]
 impl Perform for Search {
   type Response = SearchResponse;
@@ -330,15 +723,15 @@ impl Perform for Search {
   ) -> Result<SearchResponse, LemmyError> {
     let data: &Search = &self;
 
-    match search_by_apub_id(&data.q, context).await {
+    let local_user_view = get_local_user_view_from_jwt_opt(&data.auth, context.pool()).await?;
+    let person_id = local_user_view.map(|u| u.person.id);
+
+    match search_by_apub_id(&data.q, person_id, context).await {
       Ok(r) => return Ok(r),
       Err(e) => debug!("Failed to resolve search query as activitypub ID: {}", e),
     }
 
-    let local_user_view = get_local_user_view_from_jwt_opt(&data.auth, context.pool()).await?;
-    let person_id = local_user_view.map(|u| u.person.id);
-
-    let type_ = SearchType::from_str(&data.type_)?;
+    let type_ = data.type_.unwrap_or_default();
 
     let mut posts = Vec::new();
     let mut comments = Vec::new();
@@ -350,12 +743,20 @@ impl Perform for Search {
     let q = data.q.to_owned();
     let page = data.page;
     let limit = data.limit;
-    let sort = SortType::from_str(&data.sort)?;
+    let sort = data.sort.unwrap_or_default();
     let community_id = data.community_id;
     let community_name = data.community_name.to_owned();
+    check_sort_allowed(
+      sort,
+      community_id,
+      community_name.to_owned(),
+      person_id,
+      context.pool(),
+    )
+    .await?;
     match type_ {
       SearchType::Posts => {
-        posts = blocking(context.pool(), move |conn| {
+        posts = blocking_read(context.read_pool(), move |conn| {
           PostQueryBuilder::create(conn)
             .sort(&sort)
             .show_nsfw(true)
@@ -370,7 +771,7 @@ impl Perform for Search {
         .await??;
       }
       SearchType::Comments => {
-        comments = blocking(context.pool(), move |conn| {
+        comments = blocking_read(context.read_pool(), move |conn| {
           CommentQueryBuilder::create(&conn)
             .sort(&sort)
             .search_term(q)
@@ -382,7 +783,7 @@ impl Perform for Search {
         .await??;
       }
       SearchType::Communities => {
-        communities = blocking(context.pool(), move |conn| {
+        communities = blocking_read(context.read_pool(), move |conn| {
           CommunityQueryBuilder::create(conn)
             .sort(&sort)
             .search_term(q)
@@ -394,7 +795,7 @@ impl Perform for Search {
         .await??;
       }
       SearchType::Users => {
-        users = blocking(context.pool(), move |conn| {
+        users = blocking_read(context.read_pool(), move |conn| {
           PersonQueryBuilder::create(conn)
             .sort(&sort)
             .search_term(q)
@@ -405,7 +806,7 @@ impl Perform for Search {
         .await??;
       }
       SearchType::All => {
-        posts = blocking(context.pool(), move |conn| {
+        posts = blocking_read(context.read_pool(), move |conn| {
           PostQueryBuilder::create(conn)
             .sort(&sort)
             .show_nsfw(true)
@@ -420,9 +821,8 @@ impl Perform for Search {
         .await??;
 
         let q = data.q.to_owned();
-        let sort = SortType::from_str(&data.sort)?;
 
-        comments = blocking(context.pool(), move |conn| {
+        comments = blocking_read(context.read_pool(), move |conn| {
           CommentQueryBuilder::create(conn)
             .sort(&sort)
             .search_term(q)
@@ -434,9 +834,8 @@ impl Perform for Search {
         .await??;
 
         let q = data.q.to_owned();
-        let sort = SortType::from_str(&data.sort)?;
 
-        communities = blocking(context.pool(), move |conn| {
+        communities = blocking_read(context.read_pool(), move |conn| {
           CommunityQueryBuilder::create(conn)
             .sort(&sort)
             .search_term(q)
@@ -448,9 +847,8 @@ impl Perform for Search {
         .await??;
 
         let q = data.q.to_owned();
-        let sort = SortType::from_str(&data.sort)?;
 
-        users = blocking(context.pool(), move |conn| {
+        users = blocking_read(context.read_pool(), move |conn| {
           PersonQueryBuilder::create(conn)
             .sort(&sort)
             .search_term(q)
@@ -461,7 +859,7 @@ impl Perform for Search {
         .await??;
       }
       SearchType::Url => {
-        posts = blocking(context.pool(), move |conn| {
+        posts = blocking_read(context.read_pool(), move |conn| {
           PostQueryBuilder::create(conn)
             .sort(&sort)
             .show_nsfw(true)
@@ -477,9 +875,12 @@ impl Perform for Search {
       }
     };
 
+    hide_downvotes_if_disabled(&mut posts, context.pool()).await?;
+    hide_downvotes_if_disabled(&mut comments, context.pool()).await?;
+
     // Return the jwt
     Ok(SearchResponse {
-      type_: data.type_.to_owned(),
+      type_: type_.to_string(),
       comments,
       posts,
       communities,
@@ -506,23 +907,27 @@ impl Perform for TransferSite {
 
     // Make sure user is the creator
     if read_site.creator_id != local_user_view.person.id {
-      return Err(ApiError::err("not_an_admin").into());
+      return Err(ApiError::new(ApiErrorCode::NotAnAdmin).into());
     }
 
+    // Transfer the site and log it in one transaction
     let new_creator_id = data.person_id;
-    let transfer_site = move |conn: &'_ _| Site::transfer(conn, new_creator_id);
-    if blocking(context.pool(), transfer_site).await?.is_err() {
-      return Err(ApiError::err("couldnt_update_site").into());
-    };
-
-    // Mod tables
     let form = ModAddForm {
       mod_person_id: local_user_view.person.id,
       other_person_id: data.person_id,
       removed: Some(false),
     };
+    blocking_transaction(context.pool(), move |conn| {
+      Site::transfer(conn, new_creator_id)
+        .map_err(|_| ApiError::new(ApiErrorCode::CouldntUpdateSite))?;
 
-    blocking(context.pool(), move |conn| ModAdd::create(conn, &form)).await??;
+      // Mod tables
+      ModAdd::create(conn, &form)?;
+
+      Ok(())
+    })
+    .await?;
+    context.site_cache().invalidate().await;
 
     let site_view = blocking(context.pool(), move |conn| SiteView::read(conn)).await??;
 
@@ -536,8 +941,15 @@ impl Perform for TransferSite {
 
     let banned = blocking(context.pool(), move |conn| PersonViewSafe::banned(conn)).await??;
     let federated_instances = build_federated_instances(context.pool()).await?;
+    let federated_instances =
+      add_federated_instance_details(federated_instances, context.pool()).await?;
 
     let my_user = Some(get_local_user_settings_view_from_jwt(&data.auth, context.pool()).await?);
+    let content_limits = ContentLimits::from(&site_view.site);
+    let registration_questions =
+      Some(blocking(context.pool(), move |conn| RegistrationQuestion::list(conn)).await??);
+    let all_languages = blocking(context.pool(), move |conn| Language::read_all(conn)).await??;
+    let discussion_languages = read_discussion_languages(&my_user, context.pool()).await?;
 
     Ok(GetSiteResponse {
       site_view: Some(site_view),
@@ -547,6 +959,10 @@ impl Perform for TransferSite {
       version: version::VERSION.to_string(),
       my_user,
       federated_instances,
+      content_limits,
+      registration_questions,
+      all_languages,
+      discussion_languages,
     })
   }
 }
@@ -561,7 +977,8 @@ impl Perform for GetSiteConfig {
     _websocket_id: Option<ConnectionId>,
   ) -> Result<GetSiteConfigResponse, LemmyError> {
     let data: &GetSiteConfig = &self;
-    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+    let local_user_view =
+      get_local_user_view_from_jwt_read_only(&data.auth, context.pool()).await?;
 
     // Only let admins read this
     is_admin(&local_user_view)?;
@@ -572,6 +989,219 @@ impl Perform for GetSiteConfig {
   }
 }
 
+/// Validates the spam rules of `EditSpamRules`, and converts them into forms
+fn check_spam_rules(rules: &[SpamRuleInput]) -> Result<Vec<SpamRuleForm>, ApiError> {
+  let mut forms = Vec::new();
+  for input in rules {
+    let (pattern, threshold) = match input.kind {
+      SpamRuleKind::UrlDenylist => {
+        let pattern = input.pattern.as_deref().unwrap_or("").trim();
+        if pattern.is_empty() || Regex::new(pattern).is_err() {
+          return Err(ApiError::err("invalid_spam_rule_pattern").with_field("spam_rules"));
+        }
+        (Some(pattern.to_owned()), None)
+      }
+      // A post may have no links at all, the other thresholds have to be positive
+      SpamRuleKind::MaxLinks => match input.threshold {
+        Some(threshold) if threshold >= 0 => (None, Some(threshold)),
+        _ => return Err(ApiError::err("invalid_spam_rule_threshold").with_field("spam_rules")),
+      },
+      SpamRuleKind::MinAccountAge | SpamRuleKind::DuplicateContent => match input.threshold {
+        Some(threshold) if threshold > 0 => (None, Some(threshold)),
+        _ => return Err(ApiError::err("invalid_spam_rule_threshold").with_field("spam_rules")),
+      },
+    };
+    forms.push(SpamRuleForm {
+      kind: input.kind as i16,
+      pattern,
+      threshold,
+      action: input.action as i16,
+    });
+  }
+  Ok(forms)
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for GetSpamRules {
+  type Response = SpamRulesResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<SpamRulesResponse, LemmyError> {
+    let data: &GetSpamRules = &self;
+    let local_user_view =
+      get_local_user_view_from_jwt_read_only(&data.auth, context.pool()).await?;
+    is_admin(&local_user_view)?;
+
+    let spam_rules = blocking(context.pool(), move |conn| SpamRule::list(conn)).await??;
+    Ok(SpamRulesResponse::new(spam_rules))
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for EditSpamRules {
+  type Response = SpamRulesResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<SpamRulesResponse, LemmyError> {
+    let data: &EditSpamRules = &self;
+    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+    is_admin(&local_user_view)?;
+
+    let forms = check_spam_rules(&data.spam_rules)?;
+    let spam_rules = match blocking(context.pool(), move |conn| {
+      SpamRule::replace_all(conn, &forms)
+    })
+    .await?
+    {
+      Ok(spam_rules) => spam_rules,
+      Err(_) => return Err(ApiError::err("couldnt_update_spam_rules").into()),
+    };
+    invalidate_spam_rules().await;
+    Ok(SpamRulesResponse::new(spam_rules))
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for GetAdminStats {
+  type Response = GetAdminStatsResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<GetAdminStatsResponse, LemmyError> {
+    let data: &GetAdminStats = &self;
+    let local_user_view =
+      get_local_user_view_from_jwt_read_only(&data.auth, context.pool()).await?;
+
+    // Only let admins read this
+    is_admin(&local_user_view)?;
+
+    let federation_fetches = context.fetch_limiter().stats();
+    let primary_pool = DbPoolStats::from_pool(context.pool());
+    let read_replica_pool = context.read_pool.as_ref().map(DbPoolStats::from_pool);
+
+    Ok(GetAdminStatsResponse {
+      federation_fetches,
+      primary_pool,
+      read_replica_pool,
+    })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for RecalculateAggregates {
+  type Response = RecalculateAggregatesResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<RecalculateAggregatesResponse, LemmyError> {
+    let data: &RecalculateAggregates = &self;
+    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+
+    is_admin(&local_user_view)?;
+
+    let corrected = match data.scope {
+      AggregatesScope::Person(person_id) => {
+        blocking(context.pool(), move |conn| {
+          PersonAggregates::recalculate(conn, person_id)
+        })
+        .await?
+      }
+      AggregatesScope::Community(community_id) => {
+        blocking(context.pool(), move |conn| {
+          CommunityAggregates::recalculate(conn, community_id)
+        })
+        .await?
+      }
+      AggregatesScope::Post(post_id) => {
+        blocking(context.pool(), move |conn| {
+          PostAggregates::recalculate(conn, post_id)
+        })
+        .await?
+      }
+      AggregatesScope::Site => {
+        blocking(context.pool(), move |conn| {
+          SiteAggregates::recalculate(conn)
+        })
+        .await?
+      }
+    };
+    let corrected = match corrected {
+      Ok(corrected) => corrected,
+      Err(_e) => return Err(ApiError::err("couldnt_recalculate_aggregates").into()),
+    };
+    info!(
+      "Admin {} recalculated {:?} aggregates, {} rows corrected",
+      local_user_view.person.name, data.scope, corrected
+    );
+
+    if let AggregatesScope::Site = data.scope {
+      context.site_cache().invalidate().await;
+    }
+
+    Ok(RecalculateAggregatesResponse { corrected })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for ListScheduledTasks {
+  type Response = ListScheduledTasksResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<ListScheduledTasksResponse, LemmyError> {
+    let data: &ListScheduledTasks = &self;
+    let local_user_view =
+      get_local_user_view_from_jwt_read_only(&data.auth, context.pool()).await?;
+
+    is_admin(&local_user_view)?;
+
+    let tasks = blocking(context.pool(), move |conn| TaskLock::list(conn)).await??;
+
+    Ok(ListScheduledTasksResponse { tasks })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for RunScheduledTask {
+  type Response = RunScheduledTaskResponse;
+
+  async fn perform(
+    &self,
+    context: &Data<LemmyContext>,
+    _websocket_id: Option<ConnectionId>,
+  ) -> Result<RunScheduledTaskResponse, LemmyError> {
+    let data: &RunScheduledTask = &self;
+    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+
+    is_admin(&local_user_view)?;
+
+    // Only marks the task as due, it runs on whichever server takes the lock first
+    let name = data.name.to_owned();
+    let task = match blocking(context.pool(), move |conn| TaskLock::trigger(conn, &name)).await? {
+      Ok(task) => task,
+      Err(_e) => return Err(ApiError::err("couldnt_find_scheduled_task").into()),
+    };
+    info!(
+      "Admin {} triggered scheduled task {}",
+      local_user_view.person.name, task.name
+    );
+
+    Ok(RunScheduledTaskResponse { task })
+  }
+}
+
 #[async_trait::async_trait(?Send)]
 impl Perform for SaveSiteConfig {
   type Response = GetSiteConfigResponse;
@@ -590,9 +1220,98 @@ impl Perform for SaveSiteConfig {
     // Make sure docker doesn't have :ro at the end of the volume, so its not a read-only filesystem
     let config_hjson = match Settings::save_config_file(&data.config_hjson) {
       Ok(config_hjson) => config_hjson,
-      Err(_e) => return Err(ApiError::err("couldnt_update_site").into()),
+      Err(_e) => return Err(ApiError::new(ApiErrorCode::CouldntUpdateSite).into()),
     };
 
     Ok(GetSiteConfigResponse { config_hjson })
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    test::{create_test_user, test_context},
+    Perform,
+  };
+  use lemmy_api_structs::site::{EditSite, GetSite};
+  use lemmy_db_queries::Crud;
+  use lemmy_db_schema::source::{
+    person::Person,
+    site::{Site, SiteForm},
+  };
+  use serial_test::serial;
+
+  #[actix_rt::test]
+  #[serial]
+  async fn test_edit_site_invalidates_site_cache() {
+    let context = test_context();
+    let conn = context.pool().get().unwrap();
+    let (_, admin, jwt) = create_test_user(&conn, "site_cache_admin", true);
+
+    let site_form = SiteForm {
+      name: "site_cache_site".into(),
+      description: None,
+      creator_id: admin.id,
+      updated: None,
+      enable_downvotes: true,
+      open_registration: true,
+      enable_nsfw: true,
+      icon: None,
+      banner: None,
+      slur_filter_regex: Some(Some("badger".into())),
+      require_local_images: None,
+      max_post_body_length: None,
+      max_comment_length: None,
+      allowed_url_schemes: None,
+      default_post_listing_type: None,
+      show_deleted_usernames_to_admins: None,
+      federation_mode: None,
+      federate_votes_as: None,
+      registration_challenge: None,
+      proof_of_work_difficulty: None,
+    };
+    let inserted_site = Site::create(&conn, &site_form).unwrap();
+
+    let get_site = GetSite { auth: None };
+    let before = get_site.perform(&context, None).await.unwrap();
+
+    let edit_site = EditSite {
+      name: "site_cache_edited".into(),
+      description: None,
+      icon: None,
+      banner: None,
+      enable_downvotes: false,
+      open_registration: true,
+      enable_nsfw: true,
+      slur_filter_regex: Some("".into()),
+      require_local_images: None,
+      max_post_body_length: None,
+      max_comment_length: None,
+      allowed_url_schemes: None,
+      default_post_listing_type: None,
+      show_deleted_usernames_to_admins: None,
+      federation_mode: None,
+      federate_votes_as: None,
+      registration_challenge: None,
+      proof_of_work_difficulty: None,
+      registration_questions: None,
+      auth: jwt,
+    };
+    edit_site.perform(&context, None).await.unwrap();
+    // The cached response isn't expired yet, so it has to be dropped by EditSite
+    let after = get_site.perform(&context, None).await.unwrap();
+
+    Site::delete(&conn, inserted_site.id).unwrap();
+    Person::delete(&conn, admin.id).unwrap();
+
+    let before_site = before.site_view.unwrap().site;
+    assert_eq!("site_cache_site", before_site.name);
+    assert!(before_site.enable_downvotes);
+    let after_site = after.site_view.unwrap().site;
+    assert_eq!("site_cache_edited", after_site.name);
+    assert!(!after_site.enable_downvotes);
+    // Clearing the slur filter stores null
+    assert_eq!(Some("badger".into()), before_site.slur_filter_regex);
+    assert_eq!(None, after_site.slur_filter_regex);
+  }
+}
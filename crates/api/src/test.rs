@@ -0,0 +1,181 @@
+//! Helpers for tests which call the API operations directly, against the test database.
+
+use crate::match_websocket_operation;
+use actix::Actor;
+use actix_web::web::Data;
+use diesel::{
+  r2d2::{ConnectionManager, Pool},
+  PgConnection,
+};
+use lemmy_api_structs::{site::SiteCache, VoteDebouncer};
+use lemmy_apub::activity_queue::create_activity_queue;
+use lemmy_db_queries::{get_database_url_from_env, Crud};
+use lemmy_db_schema::source::{
+  community::{Community, CommunityForm},
+  local_user::{LocalUser, LocalUserForm},
+  login_session::{LoginSession, LoginSessionForm},
+  person::{Person, PersonForm},
+};
+use lemmy_utils::{
+  claims::Claims,
+  fetch_limit::FetchLimiter,
+  rate_limit::{rate_limiter::RateLimiter, RateLimit},
+  settings::structs::Settings,
+  slur_filter::SlurFilter,
+  ApiError,
+  ApiErrorCode,
+  LemmyError,
+};
+use lemmy_websocket::{chat_server::ChatServer, LemmyContext};
+use reqwest::Client;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::Mutex;
+
+/// A context like the one of the server, without a read replica. Has to be called from within an
+/// actix system, eg in an `actix_rt::test`.
+pub(crate) fn test_context() -> Data<LemmyContext> {
+  let settings = Settings::get();
+  let db_url = get_database_url_from_env().unwrap_or_else(|_| settings.get_database_url());
+  let manager = ConnectionManager::<PgConnection>::new(&db_url);
+  let pool = Pool::builder()
+    .max_size(4)
+    .build(manager)
+    .unwrap_or_else(|_| panic!("Error connecting to {}", db_url));
+
+  let rate_limiter = RateLimit {
+    rate_limiter: Arc::new(Mutex::new(RateLimiter::default())),
+  };
+  let activity_queue = create_activity_queue(pool.clone());
+  let fetch_limiter = FetchLimiter::new(settings.fetch_limit());
+  let site_cache = SiteCache::new(Duration::from_secs(60));
+  let slur_filter = SlurFilter::new(None);
+  let vote_debouncer = VoteDebouncer::new(settings.vote_debounce());
+  let chat_server = ChatServer::startup(
+    pool.clone(),
+    None,
+    rate_limiter,
+    |c, i, o, d| Box::pin(match_websocket_operation(c, i, o, d)),
+    Client::default(),
+    activity_queue.clone(),
+    fetch_limiter.clone(),
+    site_cache.clone(),
+    slur_filter.clone(),
+    vote_debouncer.clone(),
+  )
+  .start();
+
+  Data::new(LemmyContext::create(
+    pool,
+    None,
+    chat_server,
+    Client::default(),
+    activity_queue,
+    fetch_limiter,
+    site_cache,
+    slur_filter,
+    vote_debouncer,
+  ))
+}
+
+/// Registers a local user with a login session, and returns it together with the jwt of the
+/// session. Deleting the person removes everything else.
+pub(crate) fn create_test_user(
+  conn: &PgConnection,
+  name: &str,
+  admin: bool,
+) -> (LocalUser, Person, String) {
+  let person_form = PersonForm {
+    name: name.into(),
+    preferred_username: None,
+    avatar: None,
+    banner: None,
+    banned: None,
+    deleted: None,
+    published: None,
+    updated: None,
+    actor_id: None,
+    bio: None,
+    local: Some(true),
+    private_key: None,
+    public_key: None,
+    last_refreshed_at: None,
+    inbox_url: None,
+    shared_inbox_url: None,
+  };
+  let person = Person::create(conn, &person_form).unwrap();
+
+  let local_user_form = LocalUserForm {
+    person_id: person.id,
+    password_encrypted: "pass".to_string(),
+    email: None,
+    matrix_user_id: None,
+    admin: Some(admin),
+    show_nsfw: None,
+    theme: None,
+    default_sort_type: None,
+    default_listing_type: None,
+    lang: None,
+    show_avatars: None,
+    send_notifications_to_email: None,
+    email_digest_frequency: None,
+    hide_last_active: None,
+    hide_profile_from_federation: None,
+  };
+  let local_user = LocalUser::create(conn, &local_user_form).unwrap();
+
+  let session_form = LoginSessionForm {
+    local_user_id: local_user.id,
+    user_agent: None,
+    ip: None,
+  };
+  let session = LoginSession::create(conn, &session_form).unwrap();
+  let jwt = Claims::jwt(local_user.id, session.id, Settings::get().hostname()).unwrap();
+
+  (local_user, person, jwt)
+}
+
+/// A local community of `creator`, who isn't added as moderator
+pub(crate) fn create_test_community(
+  conn: &PgConnection,
+  name: &str,
+  creator: &Person,
+) -> Community {
+  let form = CommunityForm {
+    name: name.into(),
+    title: "nada".to_owned(),
+    description: None,
+    creator_id: creator.id,
+    removed: None,
+    deleted: None,
+    updated: None,
+    nsfw: false,
+    actor_id: None,
+    local: true,
+    private_key: None,
+    public_key: None,
+    last_refreshed_at: None,
+    published: None,
+    icon: None,
+    banner: None,
+    followers_url: None,
+    inbox_url: None,
+    shared_inbox_url: None,
+    downvotes_disabled: None,
+    requires_post_approval: None,
+    theme_color: None,
+    short_description: None,
+    default_comment_sort: None,
+    require_post_tag: None,
+    manual_follow_approval: None,
+  };
+  Community::create(conn, &form).unwrap()
+}
+
+/// The code of the API error which an operation failed with
+pub(crate) fn error_code<T>(res: Result<T, LemmyError>) -> Option<ApiErrorCode> {
+  res.err().and_then(|e| {
+    e.inner
+      .downcast_ref::<ApiError>()
+      .map(|e| e.code.to_owned())
+  })
+}
@@ -1,4 +1,4 @@
-use crate::{get_local_user_view_from_jwt, Perform};
+use crate::{get_local_user_view_from_jwt_opt, get_local_user_view_from_jwt_read_only, Perform};
 use actix_web::web::Data;
 use lemmy_api_structs::websocket::*;
 use lemmy_utils::{ConnectionId, LemmyError};
@@ -17,7 +17,8 @@ impl Perform for UserJoin {
     websocket_id: Option<ConnectionId>,
   ) -> Result<UserJoinResponse, LemmyError> {
     let data: &UserJoin = &self;
-    let local_user_view = get_local_user_view_from_jwt(&data.auth, context.pool()).await?;
+    let local_user_view =
+      get_local_user_view_from_jwt_read_only(&data.auth, context.pool()).await?;
 
     if let Some(ws_id) = websocket_id {
       context.chat_server().do_send(JoinUserRoom {
@@ -40,11 +41,13 @@ impl Perform for CommunityJoin {
     websocket_id: Option<ConnectionId>,
   ) -> Result<CommunityJoinResponse, LemmyError> {
     let data: &CommunityJoin = &self;
+    let local_user_view = get_local_user_view_from_jwt_opt(&data.auth, context.pool()).await?;
 
     if let Some(ws_id) = websocket_id {
       context.chat_server().do_send(JoinCommunityRoom {
         community_id: data.community_id,
         id: ws_id,
+        person_id: local_user_view.map(|l| l.person.id),
       });
     }
 
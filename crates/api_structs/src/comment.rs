@@ -1,48 +1,74 @@
+use crate::FederationStatus;
+use lemmy_db_queries::{ListingType, SortType};
+use lemmy_db_schema::{CommentId, CommunityId, LanguageId, PostId};
 use lemmy_db_views::{comment_report_view::CommentReportView, comment_view::CommentView};
+use lemmy_db_views_actor::person_mention_view::PersonMentionView;
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize)]
 pub struct CreateComment {
   pub content: String,
-  pub parent_id: Option<i32>,
-  pub post_id: i32,
+  pub parent_id: Option<CommentId>,
+  pub post_id: PostId,
   pub form_id: Option<String>,
+  /// Don't notify the persons mentioned in the comment, and don't send it to their instances
+  pub disable_mentions: Option<bool>,
+  /// Undetermined if not given
+  pub language_id: Option<LanguageId>,
   pub auth: String,
 }
 
 #[derive(Deserialize)]
 pub struct EditComment {
   pub content: String,
-  pub comment_id: i32,
+  pub comment_id: CommentId,
   pub form_id: Option<String>,
   pub auth: String,
 }
 
 #[derive(Deserialize)]
 pub struct DeleteComment {
-  pub comment_id: i32,
+  pub comment_id: CommentId,
   pub deleted: bool,
   pub auth: String,
 }
 
 #[derive(Deserialize)]
 pub struct RemoveComment {
-  pub comment_id: i32,
+  pub comment_id: CommentId,
   pub removed: bool,
   pub reason: Option<String>,
   pub auth: String,
 }
 
+/// Locks a comment, so that nobody can reply to it or anywhere below it.
+#[derive(Deserialize)]
+pub struct LockComment {
+  pub comment_id: CommentId,
+  pub locked: bool,
+  pub reason: Option<String>,
+  pub auth: String,
+}
+
+/// Stickies a top-level comment, so that it is shown first in its post. Only one comment per post
+/// can be stickied.
+#[derive(Deserialize)]
+pub struct StickyComment {
+  pub comment_id: CommentId,
+  pub stickied: bool,
+  pub auth: String,
+}
+
 #[derive(Deserialize)]
 pub struct MarkCommentAsRead {
-  pub comment_id: i32,
+  pub comment_id: CommentId,
   pub read: bool,
   pub auth: String,
 }
 
 #[derive(Deserialize)]
 pub struct SaveComment {
-  pub comment_id: i32,
+  pub comment_id: CommentId,
   pub save: bool,
   pub auth: String,
 }
@@ -52,23 +78,41 @@ pub struct CommentResponse {
   pub comment_view: CommentView,
   pub recipient_ids: Vec<i32>, // TODO another way to do this? Maybe a UserMention belongs to Comment
   pub form_id: Option<String>, // An optional front end ID, to tell which is coming back
+  /// The mentions which were created for a new comment, empty for all other operations
+  pub mentions: Vec<PersonMentionView>,
+  /// Only set for a newly created comment
+  pub federation: Option<FederationStatus>,
 }
 
 #[derive(Deserialize)]
 pub struct CreateCommentLike {
-  pub comment_id: i32,
+  pub comment_id: CommentId,
   pub score: i16,
   pub auth: String,
 }
 
+#[derive(Deserialize)]
+pub struct GetComment {
+  pub id: CommentId,
+  pub auth: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct GetCommentResponse {
+  pub comment_view: CommentView,
+  /// Whether the requester moderates the community, so removed and deleted comments are shown
+  pub can_view_removed: bool,
+}
+
 #[derive(Deserialize)]
 pub struct GetComments {
-  pub type_: String,
-  pub sort: String,
+  pub type_: Option<ListingType>,
+  pub sort: Option<SortType>,
   pub page: Option<i64>,
   pub limit: Option<i64>,
-  pub community_id: Option<i32>,
+  pub community_id: Option<CommunityId>,
   pub community_name: Option<String>,
+  pub saved_only: Option<bool>,
   pub auth: Option<String>,
 }
 
@@ -79,7 +123,7 @@ pub struct GetCommentsResponse {
 
 #[derive(Serialize, Deserialize)]
 pub struct CreateCommentReport {
-  pub comment_id: i32,
+  pub comment_id: CommentId,
   pub reason: String,
   pub auth: String,
 }
@@ -108,7 +152,7 @@ pub struct ListCommentReports {
   pub page: Option<i64>,
   pub limit: Option<i64>,
   /// if no community is given, it returns reports for all communities moderated by the auth user
-  pub community: Option<i32>,
+  pub community: Option<CommunityId>,
   pub auth: String,
 }
 
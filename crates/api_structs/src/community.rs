@@ -1,14 +1,23 @@
+use lemmy_db_queries::{ListingType, SortType};
+use lemmy_db_schema::{
+  source::{community_federation_log::CommunityFederationLog, community_tag::CommunityTag},
+  CommunityId,
+  LanguageId,
+  PersonId,
+};
 use lemmy_db_views_actor::{
+  community_autocomplete_view::CommunityAutocompleteView,
   community_follower_view::CommunityFollowerView,
   community_moderator_view::CommunityModeratorView,
   community_view::CommunityView,
   person_view::PersonViewSafe,
 };
 use serde::{Deserialize, Serialize};
+use url::Url;
 
 #[derive(Deserialize)]
 pub struct GetCommunity {
-  pub id: Option<i32>,
+  pub id: Option<CommunityId>,
   pub name: Option<String>,
   pub auth: Option<String>,
 }
@@ -18,6 +27,10 @@ pub struct GetCommunityResponse {
   pub community_view: CommunityView,
   pub moderators: Vec<CommunityModeratorView>,
   pub online: usize,
+  /// The tags which posts of the community can have
+  pub tags: Vec<CommunityTag>,
+  /// The languages which are allowed in the community, empty if all are
+  pub discussion_languages: Vec<LanguageId>,
 }
 
 #[derive(Deserialize)]
@@ -28,6 +41,19 @@ pub struct CreateCommunity {
   pub icon: Option<String>,
   pub banner: Option<String>,
   pub nsfw: bool,
+  pub downvotes_disabled: Option<bool>,
+  /// Posts of non-mods are only visible after a mod approved them
+  pub requires_post_approval: Option<bool>,
+  /// Hex color like `#1e90ff`, an empty string removes it
+  pub theme_color: Option<String>,
+  /// Shown in embeds, at most 150 characters. An empty string removes it
+  pub short_description: Option<String>,
+  /// Comment sort of the post pages, when the client doesn't request one
+  pub default_comment_sort: Option<SortType>,
+  /// New posts need at least one of the community tags
+  pub require_post_tag: Option<bool>,
+  /// Follows need to be approved by a moderator
+  pub manual_follow_approval: Option<bool>,
   pub auth: String,
 }
 
@@ -38,8 +64,14 @@ pub struct CommunityResponse {
 
 #[derive(Deserialize, Debug)]
 pub struct ListCommunities {
-  pub type_: String,
-  pub sort: String,
+  pub type_: Option<ListingType>,
+  pub sort: Option<SortType>,
+  /// Only list the communities which the user moderates, together with their report counts
+  pub moderated_only: Option<bool>,
+  /// Hide the communities without posts or comments in the last 90 days, except followed ones
+  pub hide_inactive: Option<bool>,
+  /// Also count the communities on all pages, which needs another query
+  pub include_total: Option<bool>,
   pub page: Option<i64>,
   pub limit: Option<i64>,
   pub auth: Option<String>,
@@ -48,16 +80,45 @@ pub struct ListCommunities {
 #[derive(Serialize, Debug)]
 pub struct ListCommunitiesResponse {
   pub communities: Vec<CommunityView>,
+  /// Only filled in for `moderated_only`, communities without unresolved reports are left out
+  pub report_counts: Vec<CommunityReportCount>,
+  /// Only filled in for `include_total`
+  pub total_count: Option<i64>,
+  pub has_more: bool,
+}
+
+#[derive(Deserialize)]
+pub struct CommunityAutocomplete {
+  /// Prefix of the community name
+  pub q: String,
+  /// At most 10
+  pub limit: Option<i64>,
+  pub auth: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CommunityAutocompleteResponse {
+  pub communities: Vec<CommunityAutocompleteView>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CommunityReportCount {
+  pub community_id: CommunityId,
+  pub comment_reports: i64,
+  pub post_reports: i64,
 }
 
 #[derive(Deserialize, Clone)]
 pub struct BanFromCommunity {
-  pub community_id: i32,
-  pub person_id: i32,
+  pub community_id: CommunityId,
+  pub person_id: PersonId,
   pub ban: bool,
   pub remove_data: bool,
   pub reason: Option<String>,
+  /// Unix timestamp, at most 100 years from now. Without it, the ban doesn't expire.
   pub expires: Option<i64>,
+  /// Alternative to `expires` as RFC 3339 string, only one of them can be given
+  pub expires_rfc3339: Option<String>,
   pub auth: String,
 }
 
@@ -69,8 +130,8 @@ pub struct BanFromCommunityResponse {
 
 #[derive(Deserialize)]
 pub struct AddModToCommunity {
-  pub community_id: i32,
-  pub person_id: i32,
+  pub community_id: CommunityId,
+  pub person_id: PersonId,
   pub added: bool,
   pub auth: String,
 }
@@ -82,51 +143,267 @@ pub struct AddModToCommunityResponse {
 
 #[derive(Deserialize)]
 pub struct EditCommunity {
-  pub community_id: i32,
+  pub community_id: CommunityId,
   pub title: String,
   pub description: Option<String>,
   pub icon: Option<String>,
   pub banner: Option<String>,
   pub nsfw: bool,
+  pub downvotes_disabled: Option<bool>,
+  /// Replaces the languages which are allowed in the community, an empty list allows all
+  pub discussion_languages: Option<Vec<LanguageId>>,
+  /// Posts of non-mods are only visible after a mod approved them
+  pub requires_post_approval: Option<bool>,
+  /// Hex color like `#1e90ff`, an empty string removes it
+  pub theme_color: Option<String>,
+  /// Shown in embeds, at most 150 characters. An empty string removes it
+  pub short_description: Option<String>,
+  /// Comment sort of the post pages, when the client doesn't request one
+  pub default_comment_sort: Option<SortType>,
+  /// New posts need at least one of the community tags
+  pub require_post_tag: Option<bool>,
+  /// Follows need to be approved by a moderator
+  pub manual_follow_approval: Option<bool>,
   pub auth: String,
 }
 
+#[derive(Deserialize)]
+pub struct CreateCommunityTag {
+  pub community_id: CommunityId,
+  /// A single word of at most 30 characters
+  pub name: String,
+  /// Hex color like `#1e90ff`
+  pub color: Option<String>,
+  pub auth: String,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteCommunityTag {
+  pub tag_id: i32,
+  pub auth: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct CommunityTagResponse {
+  pub tag: CommunityTag,
+}
+
 #[derive(Deserialize)]
 pub struct DeleteCommunity {
-  pub community_id: i32,
+  pub community_id: CommunityId,
   pub deleted: bool,
+  /// Also delete all posts and comments of the community. Restoring the community always restores
+  /// the content which was deleted this way.
+  pub remove_content: Option<bool>,
   pub auth: String,
 }
 
+/// Sent over websocket to the creator while the content of a deleted or restored community is
+/// being updated
+#[derive(Serialize, Clone)]
+pub struct DeleteCommunityProgress {
+  pub community_id: CommunityId,
+  pub deleted: bool,
+  pub total_posts: usize,
+  pub total_comments: usize,
+  pub processed_posts: usize,
+  pub processed_comments: usize,
+  pub done: bool,
+}
+
 #[derive(Deserialize)]
 pub struct RemoveCommunity {
-  pub community_id: i32,
+  pub community_id: CommunityId,
   pub removed: bool,
   pub reason: Option<String>,
+  /// Unix timestamp, at most 100 years from now. Without it, the removal doesn't expire.
   pub expires: Option<i64>,
+  /// Alternative to `expires` as RFC 3339 string, only one of them can be given
+  pub expires_rfc3339: Option<String>,
+  /// Also remove all posts and comments of the community. Restoring the community always restores
+  /// the content which was removed this way.
+  pub remove_content: Option<bool>,
+  pub auth: String,
+}
+
+#[derive(Deserialize)]
+pub struct ExemptCommunityFromSlurFilter {
+  pub community_id: CommunityId,
+  pub exempt: bool,
   pub auth: String,
 }
 
 #[derive(Deserialize)]
 pub struct FollowCommunity {
-  pub community_id: i32,
+  pub community_id: Option<CommunityId>,
+  /// Alternative to `community_id`, either a local name or `!name@instance`
+  pub community_name: Option<String>,
   pub follow: bool,
   pub auth: String,
 }
 
+/// Adds the community to the favorites of the user, or removes it. Favoriting a community which
+/// the user doesn't follow yet also follows it.
+#[derive(Deserialize)]
+pub struct FavoriteCommunity {
+  pub community_id: CommunityId,
+  pub favorite: bool,
+  pub auth: String,
+}
+
+/// Mutes a followed community until `mute_until`, a unix timestamp, or unmutes it without one. Its
+/// new posts don't count as unread meanwhile, but still show up in the subscribed feed.
+#[derive(Deserialize)]
+pub struct MuteCommunity {
+  pub community_id: CommunityId,
+  pub mute_until: Option<i64>,
+  pub auth: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct MuteCommunityResponse {
+  pub community_id: CommunityId,
+  pub mute_until: Option<chrono::NaiveDateTime>,
+}
+
+/// The follow requests of a community with manual follow approval, only for its mods and admins
+#[derive(Deserialize)]
+pub struct ListFollowRequests {
+  pub community_id: CommunityId,
+  pub auth: String,
+}
+
+#[derive(Serialize)]
+pub struct ListFollowRequestsResponse {
+  pub follow_requests: Vec<CommunityFollowerView>,
+}
+
+/// Approves or rejects a follow request. Remote persons are sent an Accept or a Reject.
+#[derive(Deserialize)]
+pub struct ApproveFollowRequest {
+  pub community_id: CommunityId,
+  pub person_id: PersonId,
+  pub approve: bool,
+  pub auth: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ApproveFollowRequestResponse {
+  pub community_id: CommunityId,
+  pub person_id: PersonId,
+  pub approved: bool,
+}
+
 #[derive(Deserialize)]
 pub struct GetFollowedCommunities {
+  pub page: Option<i64>,
+  pub limit: Option<i64>,
+  /// Also count the new posts of each community since it was last viewed
+  pub unread_counts: Option<bool>,
+  /// Also return deleted and removed communities, so that they can be unfollowed
+  pub include_removed: Option<bool>,
+  /// Also count the communities on all pages, which needs another query
+  pub include_total: Option<bool>,
   pub auth: String,
 }
 
 #[derive(Serialize)]
 pub struct GetFollowedCommunitiesResponse {
   pub communities: Vec<CommunityFollowerView>,
+  /// Only filled in for `include_total`
+  pub total_count: Option<i64>,
+  pub has_more: bool,
 }
 
 #[derive(Deserialize)]
 pub struct TransferCommunity {
-  pub community_id: i32,
-  pub person_id: i32,
+  pub community_id: CommunityId,
+  pub person_id: PersonId,
+  pub auth: String,
+}
+
+#[derive(Deserialize)]
+pub struct GetCommunityFederationStatus {
+  pub community_id: CommunityId,
   pub auth: String,
 }
+
+#[derive(Serialize)]
+pub struct GetCommunityFederationStatusResponse {
+  pub inbound: Vec<CommunityFederationLog>,
+  pub outbound: Vec<CommunityFederationLog>,
+}
+
+/// Fetches a remote community again from its instance, together with its moderators. Only for
+/// admins and local mods of the community, at most once every 10 minutes.
+#[derive(Deserialize)]
+pub struct RefreshRemoteCommunity {
+  pub community_id: CommunityId,
+  pub auth: String,
+}
+
+#[derive(Serialize)]
+pub struct RefreshRemoteCommunityResponse {
+  pub community_view: CommunityView,
+  pub moderators: Vec<CommunityModeratorView>,
+  /// Names of the community fields which changed, eg `title` or `icon`
+  pub changed_fields: Vec<String>,
+  pub added_moderators: Vec<PersonId>,
+  pub removed_moderators: Vec<PersonId>,
+}
+
+/// Exports the settings, tags and moderators of a community, so that it can be moved to another
+/// instance with `ImportCommunity`. Posts and comments aren't exported.
+#[derive(Deserialize)]
+pub struct ExportCommunity {
+  pub community_id: CommunityId,
+  pub auth: String,
+}
+
+#[derive(Serialize)]
+pub struct ExportCommunityResponse {
+  /// A serialized `CommunityExport`
+  pub export_json: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CommunityExport {
+  pub name: String,
+  pub title: String,
+  pub description: Option<String>,
+  pub short_description: Option<String>,
+  pub icon: Option<Url>,
+  pub banner: Option<Url>,
+  pub theme_color: Option<String>,
+  pub nsfw: bool,
+  pub downvotes_disabled: bool,
+  pub requires_post_approval: bool,
+  pub default_comment_sort: SortType,
+  pub require_post_tag: bool,
+  #[serde(default)]
+  pub manual_follow_approval: bool,
+  pub tags: Vec<CommunityExportTag>,
+  /// Actor ids of the moderators, starting with the head moderator
+  pub moderators: Vec<Url>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CommunityExportTag {
+  pub name: String,
+  pub color: Option<String>,
+}
+
+/// Creates a new local community from the output of `ExportCommunity`, only for admins. The
+/// moderators are fetched from their instances, the ones which can't be fetched are left out.
+#[derive(Deserialize)]
+pub struct ImportCommunity {
+  pub export_json: String,
+  pub auth: String,
+}
+
+#[derive(Serialize)]
+pub struct ImportCommunityResponse {
+  pub community_view: CommunityView,
+  pub moderators: Vec<CommunityModeratorView>,
+  pub tags: Vec<CommunityTag>,
+}
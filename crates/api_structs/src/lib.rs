@@ -1,3 +1,6 @@
+#[macro_use]
+extern crate lazy_static;
+
 pub mod comment;
 pub mod community;
 pub mod person;
@@ -5,18 +8,69 @@ pub mod post;
 pub mod site;
 pub mod websocket;
 
-use diesel::PgConnection;
-use lemmy_db_queries::{Crud, DbPool};
-use lemmy_db_schema::source::{
-  comment::Comment,
-  person::Person,
-  person_mention::{PersonMention, PersonMentionForm},
-  post::Post,
+use crate::{comment::CommentResponse, post::PostResponse, site::ContentLimits};
+use chrono::NaiveDateTime;
+use diesel::{Connection, PgConnection};
+use lemmy_db_queries::{
+  source::{
+    api_token::ApiToken_,
+    comment::Comment_,
+    local_user::LocalUser_,
+    login_session::LoginSession_,
+    post::Post_,
+    site::Site_,
+    spam_rule::{check_spam, CompiledSpamRule, SpamCheckContent, SpamRule_},
+  },
+  transaction,
+  ApiTokenScope,
+  Crud,
+  DbPool,
+  EmailDigestFrequency,
+  SortType,
+};
+use lemmy_db_schema::{
+  naive_now,
+  source::{
+    api_token::ApiToken,
+    comment::Comment,
+    local_user::LocalUser,
+    login_session::LoginSession,
+    moderator::{AdminAutoRemove, AdminAutoRemoveForm},
+    person::Person,
+    person_mention::{PersonMention, PersonMentionForm},
+    person_post_mention::{PersonPostMention, PersonPostMentionForm},
+    post::{Post, PostMetadataForm},
+    site::Site,
+    spam_rule::SpamRule,
+  },
+  CommentId,
+  DbUrl,
+  PersonId,
+  PostId,
+};
+use lemmy_db_views::{
+  comment_view::{CommentQueryBuilder, CommentView},
+  local_user_view::LocalUserView,
+  post_view::PostView,
+  private_message_view::PrivateMessageQueryBuilder,
+};
+use lemmy_db_views_actor::{
+  person_mention_view::PersonMentionQueryBuilder,
+  person_post_mention_view::PersonPostMentionQueryBuilder,
+};
+use lemmy_utils::{
+  cache::TimedCache,
+  claims::Claims,
+  email::{digest_html, send_email},
+  request::{PostMetadata, PostMetadataError},
+  settings::structs::Settings,
+  utils::{generate_random_string, MentionData},
+  ApiError,
+  LemmyError,
 };
-use lemmy_db_views::local_user_view::LocalUserView;
-use lemmy_utils::{email::send_email, settings::structs::Settings, utils::MentionData, LemmyError};
-use log::error;
+use log::{error, warn};
 use serde::{Deserialize, Serialize};
+use std::{str::FromStr, sync::Arc, time::Duration};
 use url::Url;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -36,6 +90,37 @@ pub struct WebFingerResponse {
   pub links: Vec<WebFingerLink>,
 }
 
+/// How a newly created post or comment was sent out over ActivityPub.
+#[derive(Serialize, Clone, Debug)]
+pub struct FederationStatus {
+  /// Number of remote inboxes the `Create` activity was queued for. Instances with a shared inbox
+  /// count once, others once per recipient.
+  pub federated_to_inboxes: i64,
+  pub federation_queued: bool,
+  /// The community is remote, so the object only becomes canonical once the community announces
+  /// it to its followers
+  pub awaiting_announce: bool,
+}
+
+impl FederationStatus {
+  pub fn new(queued: usize, community_local: bool) -> Self {
+    FederationStatus {
+      federated_to_inboxes: queued as i64,
+      federation_queued: queued > 0,
+      awaiting_announce: !community_local,
+    }
+  }
+}
+
+/// The object of a vote which is held back by the `VoteDebouncer`
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum VoteTarget {
+  Post(PostId),
+  Comment(CommentId),
+}
+
+pub type VoteDebouncer = lemmy_utils::debounce::VoteDebouncer<(PersonId, VoteTarget)>;
+
 pub async fn blocking<F, T>(pool: &DbPool, f: F) -> Result<T, LemmyError>
 where
   F: FnOnce(&diesel::PgConnection) -> T + Send + 'static,
@@ -52,6 +137,226 @@ where
   Ok(res)
 }
 
+/// Same as `blocking`, for closures which only read from the database. Pass
+/// `LemmyContext::read_pool()` so that the query runs on the read replica when one is configured.
+pub async fn blocking_read<F, T>(read_pool: &DbPool, f: F) -> Result<T, LemmyError>
+where
+  F: FnOnce(&diesel::PgConnection) -> T + Send + 'static,
+  T: Send + 'static,
+{
+  blocking(read_pool, f).await
+}
+
+/// Like `blocking`, but runs `f` inside a transaction on a pooled connection, so that multi-step
+/// writes either all succeed or leave the database untouched.
+pub async fn blocking_transaction<F, T>(pool: &DbPool, f: F) -> Result<T, LemmyError>
+where
+  F: FnOnce(&diesel::PgConnection) -> Result<T, LemmyError> + Send + 'static,
+  T: Send + 'static,
+{
+  blocking(pool, move |conn| transaction(conn, f)).await?
+}
+
+/// Decodes the jwt of a login or an API token. Sessions and API tokens are checked against the
+/// database, so that revoked and expired ones are rejected. Returns the local user id, and the
+/// scopes of the token. Logins have no scopes, they may do everything.
+pub async fn decode_jwt(
+  jwt: &str,
+  pool: &DbPool,
+) -> Result<(i32, Option<Vec<ApiTokenScope>>), LemmyError> {
+  let claims = match Claims::decode(&jwt) {
+    Ok(claims) => claims.claims,
+    Err(_e) => return Err(ApiError::err("not_logged_in").into()),
+  };
+  let local_user_id = claims.id;
+  let scopes = match claims.api_token_id {
+    Some(api_token_id) => {
+      let api_token = blocking(pool, move |conn| {
+        ApiToken::read_valid(conn, api_token_id, local_user_id)
+      })
+      .await?
+      .map_err(|_| ApiError::err("not_logged_in"))?;
+      let scopes = api_token
+        .scopes
+        .iter()
+        .filter_map(|s| ApiTokenScope::from_str(s).ok())
+        .collect();
+      Some(scopes)
+    }
+    None => None,
+  };
+  if let Some(session_id) = claims.session_id {
+    blocking(pool, move |conn| {
+      LoginSession::read_and_mark_used(conn, session_id, local_user_id)
+    })
+    .await?
+    .map_err(|_| ApiError::err("not_logged_in"))?;
+  }
+  Ok((local_user_id, scopes))
+}
+
+/// A post or comment view, or a response containing one, whose downvotes can be hidden
+pub trait HideDownvotes {
+  fn hide_downvotes(&mut self);
+}
+
+impl HideDownvotes for PostView {
+  fn hide_downvotes(&mut self) {
+    self.counts.downvotes = 0;
+    // Otherwise the downvotes could be computed from the score
+    self.counts.score = self.counts.upvotes;
+  }
+}
+
+impl HideDownvotes for CommentView {
+  fn hide_downvotes(&mut self) {
+    self.counts.downvotes = 0;
+    self.counts.score = self.counts.upvotes;
+  }
+}
+
+impl HideDownvotes for PostResponse {
+  fn hide_downvotes(&mut self) {
+    self.post_view.hide_downvotes();
+  }
+}
+
+impl HideDownvotes for CommentResponse {
+  fn hide_downvotes(&mut self) {
+    self.comment_view.hide_downvotes();
+  }
+}
+
+/// If downvotes are disabled on the site, reports the downvote counts of all views as 0 and their
+/// scores as the upvote counts, so that clients have nothing to render. The stored counts are left
+/// untouched.
+pub async fn hide_downvotes_if_disabled<T: HideDownvotes>(
+  views: &mut [T],
+  pool: &DbPool,
+) -> Result<(), LemmyError> {
+  let enable_downvotes = blocking(pool, move |conn| Site::read_simple(conn))
+    .await?
+    .map(|site| site.enable_downvotes)
+    // Not set up yet
+    .unwrap_or(true);
+  if !enable_downvotes {
+    views.iter_mut().for_each(HideDownvotes::hide_downvotes);
+  }
+  Ok(())
+}
+
+/// The post and comment limits of the site, or the defaults if it isnt set up yet
+pub async fn get_content_limits(pool: &DbPool) -> Result<ContentLimits, LemmyError> {
+  let limits = blocking(pool, move |conn| Site::read_simple(conn))
+    .await?
+    .map(|site| ContentLimits::from(&site))
+    .unwrap_or_default();
+  Ok(limits)
+}
+
+lazy_static! {
+  /// Editing the spam rules invalidates the cache, the max age only matters for other servers on
+  /// the same database.
+  static ref SPAM_RULES: TimedCache<Arc<Vec<CompiledSpamRule>>> =
+    TimedCache::new(Duration::from_secs(300));
+}
+
+/// Checks new content of `creator` against the spam rules of the site. Returns the rule which
+/// matched, if any.
+pub async fn check_spam_rules(
+  pool: &DbPool,
+  creator: &Person,
+  content: SpamCheckContent,
+) -> Result<Option<SpamRule>, LemmyError> {
+  let rules = SPAM_RULES
+    .get_or_refresh(|| async move {
+      let rules = blocking(pool, move |conn| SpamRule::list(conn)).await??;
+      Ok(Arc::new(CompiledSpamRule::compile(rules))) as Result<_, LemmyError>
+    })
+    .await?;
+  let creator = creator.to_owned();
+  let rule = blocking(pool, move |conn| {
+    check_spam(conn, &rules, &creator, &content)
+  })
+  .await??;
+  Ok(rule)
+}
+
+/// Has to be called whenever the spam rules change
+pub async fn invalidate_spam_rules() {
+  SPAM_RULES.invalidate().await;
+}
+
+/// Logs the removal of a post, or a comment if `comment_id` is given, which matched a spam rule in
+/// the modlog, so that the mods can review it. Local content is created removed, and logged in the
+/// same transaction.
+pub fn log_spam_removal(
+  conn: &PgConnection,
+  rule: &SpamRule,
+  post_id: PostId,
+  comment_id: Option<CommentId>,
+) -> Result<AdminAutoRemove, diesel::result::Error> {
+  let form = AdminAutoRemoveForm {
+    post_id,
+    comment_id,
+    rule_kind: rule.kind,
+  };
+  AdminAutoRemove::create(conn, &form)
+}
+
+/// Removes a federated post, or a comment if `comment_id` is given, which matched a spam rule, and
+/// logs it in the modlog.
+pub async fn remove_as_spam(
+  pool: &DbPool,
+  rule: &SpamRule,
+  post_id: PostId,
+  comment_id: Option<CommentId>,
+) -> Result<(), LemmyError> {
+  let rule = rule.to_owned();
+  blocking(pool, move |conn| {
+    conn.transaction(|| {
+      match comment_id {
+        Some(comment_id) => Comment::update_removed(conn, comment_id, true).map(|_| ())?,
+        None => Post::update_removed(conn, post_id, true).map(|_| ())?,
+      }
+      log_spam_removal(conn, &rule, post_id, comment_id)
+    })
+  })
+  .await??;
+  Ok(())
+}
+
+/// Saves the metadata which was fetched from `fetched_url`. If the fetch failed, this is recorded
+/// on the post instead, so that it is retried later. Returns `None` if the url of the post was
+/// changed in the meantime, then the result is dropped.
+pub fn save_post_metadata(
+  conn: &PgConnection,
+  post_id: PostId,
+  fetched_url: &Url,
+  fetched: Result<PostMetadata, PostMetadataError>,
+) -> Result<Option<Post>, LemmyError> {
+  let fetched_url: DbUrl = fetched_url.to_owned().into();
+  match fetched {
+    Ok(metadata) => {
+      let form = PostMetadataForm {
+        embed_title: metadata.title,
+        embed_description: metadata.description,
+        embed_html: metadata.html,
+        thumbnail_url: metadata.thumbnail_url.map(|u| u.into()),
+      };
+      Ok(Post::update_metadata(conn, post_id, &fetched_url, &form)?)
+    }
+    Err(e) => {
+      warn!("Failed to fetch metadata of post {}: {}", post_id, e);
+      Ok(Post::update_metadata_fetch_failed(
+        conn,
+        post_id,
+        &fetched_url,
+      )?)
+    }
+  }
+}
+
 pub async fn send_local_notifs(
   mentions: Vec<MentionData>,
   comment: Comment,
@@ -80,37 +385,30 @@ fn do_send_local_notifs(
   let mut recipient_ids = Vec::new();
 
   // Send the local mentions
-  for mention in mentions
-    .iter()
-    .filter(|m| m.is_local() && m.name.ne(&person.name))
-    .collect::<Vec<&MentionData>>()
-  {
-    // TODO do a local user fetch
-    if let Ok(mention_user_view) = LocalUserView::read_from_name(&conn, &mention.name) {
-      // TODO
-      // At some point, make it so you can't tag the parent creator either
-      // This can cause two notifications, one for reply and the other for mention
-      recipient_ids.push(mention_user_view.local_user.id);
-
-      let user_mention_form = PersonMentionForm {
-        recipient_id: mention_user_view.person.id,
-        comment_id: comment.id,
-        read: None,
-      };
+  for mention_user_view in mentioned_local_users(conn, mentions, person) {
+    // TODO
+    // At some point, make it so you can't tag the parent creator either
+    // This can cause two notifications, one for reply and the other for mention
+    recipient_ids.push(mention_user_view.local_user.id);
 
-      // Allow this to fail softly, since comment edits might re-update or replace it
-      // Let the uniqueness handle this fail
-      PersonMention::create(&conn, &user_mention_form).ok();
-
-      // Send an email to those local users that have notifications on
-      if do_send_email && mention_user_view.local_user.send_notifications_to_email {
-        send_email_to_user(
-          &mention_user_view,
-          "Mentioned by",
-          "Person Mention",
-          &comment.content,
-        )
-      }
+    let user_mention_form = PersonMentionForm {
+      recipient_id: mention_user_view.person.id,
+      comment_id: comment.id,
+      read: None,
+    };
+
+    // Allow this to fail softly, since comment edits might re-update or replace it
+    // Let the uniqueness handle this fail
+    PersonMention::create(&conn, &user_mention_form).ok();
+
+    // Send an email to those local users that have notifications on
+    if do_send_email && sends_immediate_emails(&mention_user_view) {
+      send_email_to_user(
+        &mention_user_view,
+        "Mentioned by",
+        "Person Mention",
+        &comment.content,
+      )
     }
   }
 
@@ -123,7 +421,7 @@ fn do_send_local_notifs(
           {
             recipient_ids.push(parent_user_view.local_user.id);
 
-            if do_send_email && parent_user_view.local_user.send_notifications_to_email {
+            if do_send_email && sends_immediate_emails(&parent_user_view) {
               send_email_to_user(
                 &parent_user_view,
                 "Reply from",
@@ -141,7 +439,7 @@ fn do_send_local_notifs(
         if let Ok(parent_user_view) = LocalUserView::read_person(&conn, post.creator_id) {
           recipient_ids.push(parent_user_view.local_user.id);
 
-          if do_send_email && parent_user_view.local_user.send_notifications_to_email {
+          if do_send_email && sends_immediate_emails(&parent_user_view) {
             send_email_to_user(
               &parent_user_view,
               "Reply from",
@@ -156,6 +454,89 @@ fn do_send_local_notifs(
   recipient_ids
 }
 
+/// Creates the mentions of local users in the body of a post, and notifies them
+pub async fn send_local_post_notifs(
+  mentions: Vec<MentionData>,
+  post: Post,
+  person: Person,
+  pool: &DbPool,
+  do_send_email: bool,
+) -> Result<Vec<i32>, LemmyError> {
+  let ids = blocking(pool, move |conn| {
+    do_send_local_post_notifs(conn, &mentions, &post, &person, do_send_email)
+  })
+  .await?;
+
+  Ok(ids)
+}
+
+fn do_send_local_post_notifs(
+  conn: &PgConnection,
+  mentions: &[MentionData],
+  post: &Post,
+  person: &Person,
+  do_send_email: bool,
+) -> Vec<i32> {
+  let mut recipient_ids = Vec::new();
+
+  for mention_user_view in mentioned_local_users(conn, mentions, person) {
+    recipient_ids.push(mention_user_view.local_user.id);
+
+    let post_mention_form = PersonPostMentionForm {
+      recipient_id: mention_user_view.person.id,
+      post_id: post.id,
+      read: None,
+    };
+
+    // Allow this to fail softly, post edits might mention the same person again
+    PersonPostMention::create(&conn, &post_mention_form).ok();
+
+    if do_send_email && sends_immediate_emails(&mention_user_view) {
+      send_email_to_user(
+        &mention_user_view,
+        "Mentioned by",
+        "Person Mention",
+        post.body.as_deref().unwrap_or(&post.name),
+      )
+    }
+  }
+
+  recipient_ids
+}
+
+/// The local users mentioned in a post or comment of `person`, without duplicates. Persons can't
+/// mention themselves.
+fn mentioned_local_users(
+  conn: &PgConnection,
+  mentions: &[MentionData],
+  person: &Person,
+) -> Vec<LocalUserView> {
+  let mut local_users: Vec<LocalUserView> = Vec::new();
+  for mention in mentions
+    .iter()
+    .filter(|m| m.is_local() && m.name.ne(&person.name))
+  {
+    // TODO do a local user fetch
+    if let Ok(mention_user_view) = LocalUserView::read_from_name(&conn, &mention.name) {
+      // The same person can be mentioned more than once
+      if local_users
+        .iter()
+        .any(|u| u.local_user.id == mention_user_view.local_user.id)
+      {
+        continue;
+      }
+      local_users.push(mention_user_view);
+    }
+  }
+  local_users
+}
+
+/// Whether the user gets one email per notification, instead of a digest or no emails at all
+pub fn sends_immediate_emails(local_user_view: &LocalUserView) -> bool {
+  EmailDigestFrequency::from_i16(local_user_view.local_user.email_digest_frequency)
+    == EmailDigestFrequency::Immediate
+}
+
 pub fn send_email_to_user(
   local_user_view: &LocalUserView,
   subject_text: &str,
@@ -186,3 +567,121 @@ pub fn send_email_to_user(
     };
   }
 }
+
+/// Number of notifications of each kind which are listed in a digest email
+const MAX_DIGEST_ITEMS: i64 = 20;
+
+/// Emails a single digest of the unread replies, mentions and private messages which were
+/// published since the last digest of the user. Nothing is sent if there are none.
+pub fn send_email_digest(
+  conn: &PgConnection,
+  local_user_view: &LocalUserView,
+) -> Result<(), LemmyError> {
+  let local_user_id = local_user_view.local_user.id;
+  let person_id = local_user_view.person.id;
+  let since = local_user_view.local_user.last_email_digest;
+  let until = naive_now();
+  let is_new = |published: &NaiveDateTime| published > &since && published <= &until;
+  let protocol_and_hostname = Settings::get().get_protocol_and_hostname();
+  let comment_url = |post_id: PostId, comment_id: CommentId| {
+    format!(
+      "{}/post/{}/comment/{}",
+      protocol_and_hostname, post_id, comment_id
+    )
+  };
+
+  let replies = CommentQueryBuilder::create(conn)
+    .recipient_id(person_id)
+    .my_person_id(person_id)
+    .unread_only(true)
+    .sort(&SortType::New)
+    .limit(MAX_DIGEST_ITEMS)
+    .list()?
+    .into_iter()
+    .filter(|r| is_new(&r.comment.published))
+    .map(|r| {
+      let url = comment_url(r.post.id, r.comment.id);
+      (r.creator.name, r.comment.content, url)
+    })
+    .collect::<Vec<_>>();
+
+  let mentions = PersonMentionQueryBuilder::create(conn)
+    .recipient_id(person_id)
+    .my_person_id(person_id)
+    .unread_only(true)
+    .sort(&SortType::New)
+    .limit(MAX_DIGEST_ITEMS)
+    .list()?
+    .into_iter()
+    .filter(|m| is_new(&m.comment.published))
+    .map(|m| {
+      let url = comment_url(m.post.id, m.comment.id);
+      (m.creator.name, m.comment.content, url)
+    })
+    .chain(
+      PersonPostMentionQueryBuilder::create(conn)
+        .recipient_id(person_id)
+        .my_person_id(person_id)
+        .unread_only(true)
+        .sort(&SortType::New)
+        .limit(MAX_DIGEST_ITEMS)
+        .list()?
+        .into_iter()
+        .filter(|m| is_new(&m.post.published))
+        .map(|m| {
+          let url = format!("{}/post/{}", protocol_and_hostname, m.post.id);
+          (m.creator.name, m.post.body.unwrap_or(m.post.name), url)
+        }),
+    )
+    .collect::<Vec<_>>();
+
+  let private_messages = PrivateMessageQueryBuilder::create(conn, person_id)
+    .unread_only(true)
+    .limit(MAX_DIGEST_ITEMS)
+    .list()?
+    .into_iter()
+    .filter(|pm| is_new(&pm.private_message.published))
+    .map(|pm| {
+      let url = format!("{}/inbox", protocol_and_hostname);
+      (pm.creator.name, pm.private_message.content, url)
+    })
+    .collect::<Vec<_>>();
+
+  // Everything up to now is handled, even if sending fails below, so that nothing is sent twice
+  LocalUser::update_last_email_digest(conn, local_user_id, until)?;
+
+  let count = replies.len() + mentions.len() + private_messages.len();
+  let user_email = match &local_user_view.local_user.email {
+    Some(user_email) if count > 0 && !local_user_view.person.banned => user_email,
+    _ => return Ok(()),
+  };
+
+  let unsubscribe_token = match &local_user_view.local_user.email_unsubscribe_token {
+    Some(token) => token.to_owned(),
+    None => {
+      let token = generate_random_string();
+      LocalUser::update_email_unsubscribe_token(conn, local_user_id, &token)?;
+      token
+    }
+  };
+
+  let subject = format!(
+    "{} new notifications - {} {}",
+    count,
+    Settings::get().hostname(),
+    local_user_view.person.name,
+  );
+  let html = digest_html(
+    &[
+      ("Replies", &replies[..]),
+      ("Mentions", &mentions[..]),
+      ("Private Messages", &private_messages[..]),
+    ],
+    &protocol_and_hostname,
+    &unsubscribe_token,
+  );
+  if let Err(e) = send_email(&subject, user_email, &local_user_view.person.name, &html) {
+    error!("{}", e);
+  }
+  Ok(())
+}
@@ -1,3 +1,17 @@
+use lemmy_db_queries::{
+  ApiTokenScope,
+  EmailDigestFrequency,
+  MentionType,
+  RegistrationChallenge,
+  SortType,
+};
+use lemmy_db_schema::{
+  source::{api_token::ApiToken, login_session::LoginSession, person_note::PersonNote},
+  CommunityId,
+  LanguageId,
+  PersonId,
+  PostId,
+};
 use lemmy_db_views::{
   comment_view::CommentView,
   post_view::PostView,
@@ -7,6 +21,7 @@ use lemmy_db_views_actor::{
   community_follower_view::CommunityFollowerView,
   community_moderator_view::CommunityModeratorView,
   person_mention_view::PersonMentionView,
+  person_post_mention_view::PersonPostMentionView,
   person_view::PersonViewSafe,
 };
 use serde::{Deserialize, Serialize};
@@ -15,6 +30,38 @@ use serde::{Deserialize, Serialize};
 pub struct Login {
   pub username_or_email: String,
   pub password: String,
+  #[serde(skip)]
+  pub client: LoginClient,
+}
+
+/// Where a login comes from, this is filled in by the server and shown in the list of sessions
+#[derive(Default, Clone)]
+pub struct LoginClient {
+  pub user_agent: Option<String>,
+  pub ip: Option<String>,
+}
+
+/// Requests which start a new login session
+pub trait LoginRequest {
+  fn set_client(&mut self, client: LoginClient);
+}
+
+impl LoginRequest for Login {
+  fn set_client(&mut self, client: LoginClient) {
+    self.client = client;
+  }
+}
+
+impl LoginRequest for Register {
+  fn set_client(&mut self, client: LoginClient) {
+    self.client = client;
+  }
+}
+
+impl LoginRequest for PasswordChange {
+  fn set_client(&mut self, client: LoginClient) {
+    self.client = client;
+  }
 }
 
 #[derive(Deserialize)]
@@ -24,8 +71,23 @@ pub struct Register {
   pub password: String,
   pub password_verify: String,
   pub show_nsfw: bool,
+  /// The uuid and answer of the registration challenge, whichever type the site uses. For proof
+  /// of work challenges the answer is the nonce.
   pub captcha_uuid: Option<String>,
   pub captcha_answer: Option<String>,
+  #[serde(skip)]
+  pub client: LoginClient,
+}
+
+/// Checks a username before registering, with the same validation as `Register`
+#[derive(Deserialize)]
+pub struct CheckUsernameAvailable {
+  pub username: String,
+}
+
+#[derive(Serialize)]
+pub struct CheckUsernameAvailableResponse {
+  pub available: bool,
 }
 
 #[derive(Deserialize)]
@@ -43,6 +105,33 @@ pub struct CaptchaResponse {
   pub uuid: String,
 }
 
+/// Returns the challenge which the site requires for registering, replaces `GetCaptcha`
+#[derive(Deserialize)]
+pub struct GetRegistrationChallenge {}
+
+#[derive(Serialize)]
+pub struct GetRegistrationChallengeResponse {
+  pub ok: Option<RegistrationChallengeResponse>, // Will be None if there is nothing to solve
+}
+
+/// Exactly one of `captcha`, `proof_of_work` and `question` is set, depending on `type_`
+#[derive(Serialize)]
+pub struct RegistrationChallengeResponse {
+  pub type_: RegistrationChallenge,
+  pub uuid: String,
+  pub captcha: Option<CaptchaResponse>,
+  pub proof_of_work: Option<ProofOfWorkChallenge>,
+  pub question: Option<String>,
+}
+
+/// The client has to find a nonce, so that the SHA-256 hash of `challenge` followed by the nonce
+/// starts with at least `difficulty` zero bits.
+#[derive(Serialize)]
+pub struct ProofOfWorkChallenge {
+  pub challenge: String,
+  pub difficulty: i32,
+}
+
 #[derive(Deserialize)]
 pub struct SaveUserSettings {
   pub show_nsfw: Option<bool>,
@@ -61,6 +150,14 @@ pub struct SaveUserSettings {
   pub old_password: Option<String>,
   pub show_avatars: Option<bool>,
   pub send_notifications_to_email: Option<bool>,
+  /// Takes precedence over `send_notifications_to_email` when both are given
+  pub email_digest_frequency: Option<EmailDigestFrequency>,
+  /// Replaces the languages to read comments in, an empty list shows all languages
+  pub discussion_languages: Option<Vec<LanguageId>>,
+  /// Show nobody when this person was last active
+  pub hide_last_active: Option<bool>,
+  /// Other instances get an empty outbox for this person
+  pub hide_profile_from_federation: Option<bool>,
   pub auth: String,
 }
 
@@ -71,12 +168,12 @@ pub struct LoginResponse {
 
 #[derive(Deserialize)]
 pub struct GetPersonDetails {
-  pub person_id: Option<i32>,
+  pub person_id: Option<PersonId>,
   pub username: Option<String>,
-  pub sort: String,
+  pub sort: Option<SortType>,
   pub page: Option<i64>,
   pub limit: Option<i64>,
-  pub community_id: Option<i32>,
+  pub community_id: Option<CommunityId>,
   pub saved_only: bool,
   pub auth: Option<String>,
 }
@@ -84,10 +181,41 @@ pub struct GetPersonDetails {
 #[derive(Serialize)]
 pub struct GetPersonDetailsResponse {
   pub person_view: PersonViewSafe,
+  /// The private note of the viewer about this person
+  pub note: Option<PersonNote>,
+  /// The posts which the person pinned to their profile, independent of the sort and page
+  pub pinned_posts: Vec<PostView>,
   pub follows: Vec<CommunityFollowerView>,
   pub moderates: Vec<CommunityModeratorView>,
   pub comments: Vec<CommentView>,
   pub posts: Vec<PostView>,
+  /// Not set for saved_only requests
+  pub total_posts: Option<i64>,
+  pub total_comments: Option<i64>,
+}
+
+/// Pins one of your own posts to your profile, or unpins it
+#[derive(Deserialize)]
+pub struct PinPersonPost {
+  pub post_id: PostId,
+  pub pinned: bool,
+  pub auth: String,
+}
+
+/// Sets a private note about another person, which is only shown to yourself. An empty note
+/// removes it.
+#[derive(Deserialize)]
+pub struct SetPersonNote {
+  pub person_id: PersonId,
+  pub note: String,
+  pub color: Option<String>,
+  pub auth: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct SetPersonNoteResponse {
+  pub person_id: PersonId,
+  pub note: Option<PersonNote>,
 }
 
 #[derive(Serialize)]
@@ -97,7 +225,9 @@ pub struct GetRepliesResponse {
 
 #[derive(Serialize)]
 pub struct GetPersonMentionsResponse {
+  pub type_: MentionType,
   pub mentions: Vec<PersonMentionView>,
+  pub post_mentions: Vec<PersonPostMentionView>,
 }
 
 #[derive(Deserialize)]
@@ -119,11 +249,14 @@ pub struct AddAdminResponse {
 
 #[derive(Deserialize)]
 pub struct BanPerson {
-  pub person_id: i32,
+  pub person_id: PersonId,
   pub ban: bool,
   pub remove_data: bool,
   pub reason: Option<String>,
+  /// Unix timestamp, at most 100 years from now. Without it, the ban doesn't expire.
   pub expires: Option<i64>,
+  /// Alternative to `expires` as RFC 3339 string, only one of them can be given
+  pub expires_rfc3339: Option<String>,
   pub auth: String,
 }
 
@@ -133,21 +266,40 @@ pub struct BanPersonResponse {
   pub banned: bool,
 }
 
+/// Moves all content and relations of a duplicate remote person over to another person, and
+/// deletes the duplicate. Admin only.
+#[derive(Deserialize)]
+pub struct MergePersons {
+  pub keep_person_id: PersonId,
+  pub merge_person_id: PersonId,
+  pub auth: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct MergePersonsResponse {
+  pub person_view: PersonViewSafe,
+}
+
 #[derive(Deserialize)]
 pub struct GetReplies {
-  pub sort: String,
+  pub sort: Option<SortType>,
   pub page: Option<i64>,
   pub limit: Option<i64>,
   pub unread_only: bool,
+  /// Only return items from this community
+  pub community_id: Option<CommunityId>,
   pub auth: String,
 }
 
 #[derive(Deserialize)]
 pub struct GetPersonMentions {
-  pub sort: String,
+  pub type_: Option<MentionType>,
+  pub sort: Option<SortType>,
   pub page: Option<i64>,
   pub limit: Option<i64>,
   pub unread_only: bool,
+  /// Only return items from this community
+  pub community_id: Option<CommunityId>,
   pub auth: String,
 }
 
@@ -163,6 +315,18 @@ pub struct PersonMentionResponse {
   pub person_mention_view: PersonMentionView,
 }
 
+#[derive(Deserialize)]
+pub struct MarkPersonPostMentionAsRead {
+  pub person_post_mention_id: i32,
+  pub read: bool,
+  pub auth: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct PersonPostMentionResponse {
+  pub person_post_mention_view: PersonPostMentionView,
+}
+
 #[derive(Deserialize)]
 pub struct DeleteAccount {
   pub password: String,
@@ -182,12 +346,70 @@ pub struct PasswordChange {
   pub token: String,
   pub password: String,
   pub password_verify: String,
+  #[serde(skip)]
+  pub client: LoginClient,
+}
+
+/// Creates a token for a third party app, which can be used in the `auth` field instead of a login.
+/// It can only do what its scopes allow.
+#[derive(Deserialize)]
+pub struct CreateApiToken {
+  pub name: String,
+  pub scopes: Vec<ApiTokenScope>,
+  /// Unix timestamp in the future, at most 100 years from now. The token never expires if this is
+  /// empty.
+  pub expires: Option<i64>,
+  pub auth: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct CreateApiTokenResponse {
+  pub api_token: ApiToken,
+  pub jwt: String,
+}
+
+#[derive(Deserialize)]
+pub struct ListApiTokens {
+  pub auth: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ListApiTokensResponse {
+  pub api_tokens: Vec<ApiToken>,
+}
+
+#[derive(Deserialize)]
+pub struct RevokeApiToken {
+  pub api_token_id: i32,
+  pub auth: String,
 }
 
+#[derive(Serialize, Clone)]
+pub struct RevokeApiTokenResponse {}
+
+#[derive(Deserialize)]
+pub struct ListSessions {
+  pub auth: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ListSessionsResponse {
+  pub sessions: Vec<LoginSession>,
+}
+
+#[derive(Deserialize)]
+pub struct RevokeSession {
+  pub session_id: i32,
+  pub auth: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct RevokeSessionResponse {}
+
 #[derive(Deserialize)]
 pub struct CreatePrivateMessage {
   pub content: String,
-  pub recipient_id: i32,
+  pub recipient_id: PersonId,
   pub auth: String,
 }
 
@@ -232,13 +454,13 @@ pub struct PrivateMessageResponse {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GetReportCount {
-  pub community: Option<i32>,
+  pub community: Option<CommunityId>,
   pub auth: String,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct GetReportCountResponse {
-  pub community: Option<i32>,
+  pub community: Option<CommunityId>,
   pub comment_reports: i64,
   pub post_reports: i64,
 }
@@ -1,3 +1,6 @@
+use crate::FederationStatus;
+use lemmy_db_queries::{ListingType, SortType};
+use lemmy_db_schema::{source::community_tag::CommunityTag, CommunityId, PostId};
 use lemmy_db_views::{
   comment_view::CommentView,
   post_report_view::PostReportView,
@@ -7,6 +10,7 @@ use lemmy_db_views_actor::{
   community_moderator_view::CommunityModeratorView,
   community_view::CommunityView,
 };
+use lemmy_utils::ApiError;
 use serde::{Deserialize, Serialize};
 use url::Url;
 
@@ -16,19 +20,99 @@ pub struct CreatePost {
   pub url: Option<Url>,
   pub body: Option<String>,
   pub nsfw: bool,
-  pub community_id: i32,
+  pub community_id: CommunityId,
+  /// Ids of community tags, required if the community requires post tags
+  pub tags: Option<Vec<i32>>,
   pub auth: String,
 }
 
+/// Creates the same post in several communities at once. The other posts show up as cross posts
+/// of each one, if there is a url.
+#[derive(Deserialize, Debug)]
+pub struct CreateMultiPost {
+  pub community_ids: Vec<CommunityId>,
+  pub name: String,
+  pub url: Option<Url>,
+  pub body: Option<String>,
+  pub nsfw: bool,
+  pub auth: String,
+  /// How many posts the rate limit allows, this is filled in by the server and a value sent by the
+  /// client is replaced
+  #[serde(default)]
+  pub rate_limit_allowance: Option<usize>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct CreateMultiPostResponse {
+  /// In the order of the requested communities
+  pub results: Vec<MultiPostResult>,
+}
+
+impl CreateMultiPostResponse {
+  pub fn created_count(&self) -> usize {
+    self
+      .results
+      .iter()
+      .filter(|r| r.post_view.is_some())
+      .count()
+  }
+}
+
+/// Either the post which was created in the community, or the reason why it wasnt
+#[derive(Serialize, Clone)]
+pub struct MultiPostResult {
+  pub community_id: CommunityId,
+  pub post_view: Option<PostView>,
+  pub error: Option<ApiError>,
+}
+
 #[derive(Serialize, Clone)]
 pub struct PostResponse {
   pub post_view: PostView,
+  /// Only set for a newly created post
+  pub federation: Option<FederationStatus>,
 }
 
 #[derive(Deserialize)]
 pub struct GetPost {
-  pub id: i32,
+  pub id: PostId,
+  /// Sort of the comments, defaults to the one of the community
+  pub sort: Option<SortType>,
+  pub auth: Option<String>,
+  #[serde(skip)]
+  pub viewer_ip: Option<String>,
+}
+
+/// Requests which count as views of posts. The ip is filled in by the server, and only used to
+/// count an anonymous viewer once.
+pub trait PostViewRequest {
+  fn set_viewer_ip(&mut self, ip: String);
+}
+
+impl PostViewRequest for GetPost {
+  fn set_viewer_ip(&mut self, ip: String) {
+    self.viewer_ip = Some(ip);
+  }
+}
+
+/// Counts views of posts which were shown in a listing, without loading them
+#[derive(Deserialize)]
+pub struct MarkPostViewed {
+  pub post_ids: Vec<PostId>,
   pub auth: Option<String>,
+  #[serde(skip)]
+  pub viewer_ip: Option<String>,
+}
+
+impl PostViewRequest for MarkPostViewed {
+  fn set_viewer_ip(&mut self, ip: String) {
+    self.viewer_ip = Some(ip);
+  }
+}
+
+#[derive(Serialize)]
+pub struct MarkPostViewedResponse {
+  pub success: bool,
 }
 
 #[derive(Serialize)]
@@ -38,80 +122,142 @@ pub struct GetPostResponse {
   pub comments: Vec<CommentView>,
   pub moderators: Vec<CommunityModeratorView>,
   pub online: usize,
+  /// Whether the requester moderates the community, so removed and deleted posts are shown
+  pub can_view_removed: bool,
+  pub tags: Vec<CommunityTag>,
+  /// Other posts of the same link, at most 10
+  pub cross_posts: Vec<PostView>,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct GetPosts {
-  pub type_: String,
-  pub sort: String,
+  pub type_: Option<ListingType>,
+  pub sort: Option<SortType>,
   pub page: Option<i64>,
+  /// Opaque cursor from `next_page` of the previous response, takes precedence over `page`
+  pub page_cursor: Option<String>,
   pub limit: Option<i64>,
-  pub community_id: Option<i32>,
+  pub community_id: Option<CommunityId>,
   pub community_name: Option<String>,
+  /// Only list the posts with this community tag
+  pub tag_id: Option<i32>,
+  /// Only list the posts of the communities which the user favorited
+  pub favorites_only: Option<bool>,
   pub auth: Option<String>,
 }
 
 #[derive(Serialize, Debug)]
 pub struct GetPostsResponse {
   pub posts: Vec<PostView>,
+  /// Cursor for the following page, if this page was full
+  pub next_page: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct GetRandomPost {
+  pub community_id: CommunityId,
+  pub auth: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct GetRandomPostResponse {
+  /// `None` if the community has no posts which the user can see
+  pub post_view: Option<PostView>,
 }
 
 #[derive(Deserialize)]
 pub struct CreatePostLike {
-  pub post_id: i32,
+  pub post_id: PostId,
   pub score: i16,
   pub auth: String,
 }
 
 #[derive(Deserialize)]
 pub struct EditPost {
-  pub post_id: i32,
+  pub post_id: PostId,
   pub name: String,
   pub url: Option<Url>,
   pub body: Option<String>,
   pub nsfw: bool,
+  /// Replaces the tags of the post if set
+  pub tags: Option<Vec<i32>>,
   pub auth: String,
 }
 
 #[derive(Deserialize)]
 pub struct DeletePost {
-  pub post_id: i32,
+  pub post_id: PostId,
   pub deleted: bool,
   pub auth: String,
 }
 
 #[derive(Deserialize)]
 pub struct RemovePost {
-  pub post_id: i32,
+  pub post_id: PostId,
   pub removed: bool,
   pub reason: Option<String>,
   pub auth: String,
 }
 
+/// Lists the posts of a community which wait for approval, and the federated posts which were
+/// flagged for review. Only for mods.
+#[derive(Deserialize)]
+pub struct ListPendingPosts {
+  pub community_id: CommunityId,
+  pub page: Option<i64>,
+  pub limit: Option<i64>,
+  pub auth: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ListPendingPostsResponse {
+  pub posts: Vec<PostView>,
+}
+
+/// Approves a pending post, or rejects and removes it
+#[derive(Deserialize)]
+pub struct ApprovePost {
+  pub post_id: PostId,
+  pub approve: bool,
+  pub reason: Option<String>,
+  pub auth: String,
+}
+
 #[derive(Deserialize)]
 pub struct LockPost {
-  pub post_id: i32,
+  pub post_id: PostId,
   pub locked: bool,
   pub auth: String,
 }
 
 #[derive(Deserialize)]
 pub struct StickyPost {
-  pub post_id: i32,
+  pub post_id: PostId,
   pub stickied: bool,
+  /// Feature the post at the top of the Local and All listings of this instance, instead of
+  /// stickying it in its community. Only admins can do this, and it isnt federated.
+  pub site_wide: Option<bool>,
+  pub auth: String,
+}
+
+/// Fetches the embed data and thumbnail of the post url again, eg after the fetch failed. Only the
+/// creator and the mods can do this.
+#[derive(Deserialize)]
+pub struct RefetchPostMetadata {
+  pub post_id: PostId,
   pub auth: String,
 }
 
 #[derive(Deserialize)]
 pub struct SavePost {
-  pub post_id: i32,
+  pub post_id: PostId,
   pub save: bool,
   pub auth: String,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct CreatePostReport {
-  pub post_id: i32,
+  pub post_id: PostId,
   pub reason: String,
   pub auth: String,
 }
@@ -138,7 +284,7 @@ pub struct ResolvePostReportResponse {
 pub struct ListPostReports {
   pub page: Option<i64>,
   pub limit: Option<i64>,
-  pub community: Option<i32>,
+  pub community: Option<CommunityId>,
   pub auth: String,
 }
 
@@ -1,3 +1,27 @@
+use lemmy_db_queries::{
+  DbPool,
+  FederateVotesAs,
+  ListingType,
+  RegistrationChallenge,
+  SearchType,
+  SortType,
+  SpamAction,
+  SpamRuleKind,
+};
+use lemmy_db_schema::{
+  source::{
+    instance::Instance,
+    language::Language,
+    registration_question::RegistrationQuestion,
+    site::Site,
+    spam_rule::SpamRule,
+    task_lock::TaskLock,
+  },
+  CommunityId,
+  LanguageId,
+  PersonId,
+  PostId,
+};
 use lemmy_db_views::{
   comment_view::CommentView,
   local_user_view::LocalUserSettingsView,
@@ -6,26 +30,40 @@ use lemmy_db_views::{
 };
 use lemmy_db_views_actor::{community_view::CommunityView, person_view::PersonViewSafe};
 use lemmy_db_views_moderator::{
+  admin_auto_remove_view::AdminAutoRemoveView,
   mod_add_community_view::ModAddCommunityView,
   mod_add_view::ModAddView,
   mod_ban_from_community_view::ModBanFromCommunityView,
   mod_ban_view::ModBanView,
+  mod_edit_community_view::ModEditCommunityView,
+  mod_exempt_community_view::ModExemptCommunityView,
+  mod_feature_post_view::ModFeaturePostView,
+  mod_lock_comment_view::ModLockCommentView,
   mod_lock_post_view::ModLockPostView,
   mod_remove_comment_view::ModRemoveCommentView,
   mod_remove_community_view::ModRemoveCommunityView,
   mod_remove_post_view::ModRemovePostView,
+  mod_sticky_comment_view::ModStickyCommentView,
   mod_sticky_post_view::ModStickyPostView,
 };
+use lemmy_utils::{
+  cache::TimedCache,
+  federation_mode::FederationMode,
+  fetch_limit::InstanceFetchStats,
+  utils::{check_max_length, is_url_scheme_allowed},
+  ApiError,
+  ApiErrorCode,
+};
 use serde::{Deserialize, Serialize};
 use url::Url;
 
 #[derive(Deserialize, Debug)]
 pub struct Search {
   pub q: String,
-  pub type_: String,
-  pub community_id: Option<i32>,
+  pub type_: Option<SearchType>,
+  pub community_id: Option<CommunityId>,
   pub community_name: Option<String>,
-  pub sort: String,
+  pub sort: Option<SortType>,
   pub page: Option<i64>,
   pub limit: Option<i64>,
   pub auth: Option<String>,
@@ -42,8 +80,8 @@ pub struct SearchResponse {
 
 #[derive(Deserialize)]
 pub struct GetModlog {
-  pub mod_person_id: Option<i32>,
-  pub community_id: Option<i32>,
+  pub mod_person_id: Option<PersonId>,
+  pub community_id: Option<CommunityId>,
   pub page: Option<i64>,
   pub limit: Option<i64>,
 }
@@ -53,12 +91,21 @@ pub struct GetModlogResponse {
   pub removed_posts: Vec<ModRemovePostView>,
   pub locked_posts: Vec<ModLockPostView>,
   pub stickied_posts: Vec<ModStickyPostView>,
+  pub featured_posts: Vec<ModFeaturePostView>,
   pub removed_comments: Vec<ModRemoveCommentView>,
+  pub locked_comments: Vec<ModLockCommentView>,
+  pub stickied_comments: Vec<ModStickyCommentView>,
   pub removed_communities: Vec<ModRemoveCommunityView>,
+  pub exempted_communities: Vec<ModExemptCommunityView>,
+  pub edited_communities: Vec<ModEditCommunityView>,
   pub banned_from_community: Vec<ModBanFromCommunityView>,
   pub banned: Vec<ModBanView>,
   pub added_to_community: Vec<ModAddCommunityView>,
   pub added: Vec<ModAddView>,
+  /// Content removed by spam rules. Empty when filtering by moderator.
+  pub auto_removed: Vec<AdminAutoRemoveView>,
+  /// True if entries which were fetched from the instance of a remote community are included
+  pub remote: bool,
 }
 
 #[derive(Deserialize)]
@@ -70,6 +117,27 @@ pub struct CreateSite {
   pub enable_downvotes: bool,
   pub open_registration: bool,
   pub enable_nsfw: bool,
+  /// Only allow avatars, banners and icons which are hosted by this instance
+  pub require_local_images: Option<bool>,
+  pub max_post_body_length: Option<i32>,
+  pub max_comment_length: Option<i32>,
+  /// Allowed schemes for post links, out of `http`, `https` and `magnet`
+  pub allowed_url_schemes: Option<Vec<String>>,
+  /// Listing type for users who aren't logged in, either `All` or `Local`
+  pub default_post_listing_type: Option<ListingType>,
+  /// Show the profiles of deleted accounts to admins in the report listings
+  pub show_deleted_usernames_to_admins: Option<bool>,
+  /// Who the instance federates with, the federation options of the config still apply
+  pub federation_mode: Option<FederationMode>,
+  /// Whether votes are federated from the actors of the voters, or summed up and sent from a
+  /// service actor of the instance so that remote instances can't see who voted
+  pub federate_votes_as: Option<FederateVotesAs>,
+  /// What new users have to solve when registering
+  pub registration_challenge: Option<RegistrationChallenge>,
+  /// Number of leading zero bits for proof of work challenges, between 1 and 32
+  pub proof_of_work_difficulty: Option<i32>,
+  /// Replaces the questions for question challenges
+  pub registration_questions: Option<Vec<RegistrationQuestionInput>>,
   pub auth: String,
 }
 
@@ -82,9 +150,90 @@ pub struct EditSite {
   pub enable_downvotes: bool,
   pub open_registration: bool,
   pub enable_nsfw: bool,
+  /// Replaces the slur filter, an empty string disables it
+  pub slur_filter_regex: Option<String>,
+  pub require_local_images: Option<bool>,
+  pub max_post_body_length: Option<i32>,
+  pub max_comment_length: Option<i32>,
+  pub allowed_url_schemes: Option<Vec<String>>,
+  pub default_post_listing_type: Option<ListingType>,
+  pub show_deleted_usernames_to_admins: Option<bool>,
+  pub federation_mode: Option<FederationMode>,
+  pub federate_votes_as: Option<FederateVotesAs>,
+  pub registration_challenge: Option<RegistrationChallenge>,
+  pub proof_of_work_difficulty: Option<i32>,
+  pub registration_questions: Option<Vec<RegistrationQuestionInput>>,
   pub auth: String,
 }
 
+/// A question for registration challenges, with all answers that are accepted. Answers are
+/// compared case insensitively.
+#[derive(Deserialize, Clone)]
+pub struct RegistrationQuestionInput {
+  pub question: String,
+  pub answers: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct GetSpamRules {
+  pub auth: String,
+}
+
+/// Replaces all spam rules of the site
+#[derive(Deserialize)]
+pub struct EditSpamRules {
+  pub spam_rules: Vec<SpamRuleInput>,
+  pub auth: String,
+}
+
+/// A spam rule. `pattern` is the regex for url denylists, `threshold` the number of links, hours
+/// or latest submissions for the other kinds.
+#[derive(Deserialize, Clone)]
+pub struct SpamRuleInput {
+  pub kind: SpamRuleKind,
+  pub pattern: Option<String>,
+  pub threshold: Option<i32>,
+  pub action: SpamAction,
+}
+
+/// A stored spam rule, like `SpamRuleInput`
+#[derive(Serialize, Clone)]
+pub struct SpamRuleInfo {
+  pub id: i32,
+  pub kind: SpamRuleKind,
+  pub pattern: Option<String>,
+  pub threshold: Option<i32>,
+  pub action: SpamAction,
+  pub published: chrono::NaiveDateTime,
+}
+
+impl SpamRuleInfo {
+  /// `None` for rules of kinds which this version doesn't know, they aren't checked either
+  pub fn new(rule: SpamRule) -> Option<Self> {
+    Some(SpamRuleInfo {
+      id: rule.id,
+      kind: SpamRuleKind::from_i16(rule.kind)?,
+      pattern: rule.pattern,
+      threshold: rule.threshold,
+      action: SpamAction::from_i16(rule.action),
+      published: rule.published,
+    })
+  }
+}
+
+#[derive(Serialize, Clone)]
+pub struct SpamRulesResponse {
+  pub spam_rules: Vec<SpamRuleInfo>,
+}
+
+impl SpamRulesResponse {
+  pub fn new(rules: Vec<SpamRule>) -> Self {
+    SpamRulesResponse {
+      spam_rules: rules.into_iter().filter_map(SpamRuleInfo::new).collect(),
+    }
+  }
+}
+
 #[derive(Deserialize)]
 pub struct GetSite {
   pub auth: Option<String>,
@@ -104,11 +253,91 @@ pub struct GetSiteResponse {
   pub version: String,
   pub my_user: Option<LocalUserSettingsView>,
   pub federated_instances: Option<FederatedInstances>, // Federation may be disabled
+  pub content_limits: ContentLimits,
+  pub registration_questions: Option<Vec<RegistrationQuestion>>, // Only for admins
+  pub all_languages: Vec<Language>,
+  /// The languages which the logged in user reads, empty if they read all
+  pub discussion_languages: Vec<LanguageId>,
+}
+
+/// Limits for posts and comments, so that clients can validate them before submitting
+#[derive(Serialize, Clone, Debug)]
+pub struct ContentLimits {
+  pub max_post_body_length: i32,
+  pub max_comment_length: i32,
+  pub allowed_url_schemes: Vec<String>,
+}
+
+impl ContentLimits {
+  pub fn check_post(&self, body: &Option<String>, url: Option<&Url>) -> Result<(), ApiError> {
+    if let Some(body) = body {
+      check_max_length(
+        body,
+        self.max_post_body_length,
+        ApiErrorCode::PostBodyTooLong,
+      )?;
+    }
+    if let Some(url) = url {
+      if !self.is_url_allowed(url) {
+        return Err(
+          ApiError::new(ApiErrorCode::UrlSchemeNotAllowed)
+            .with_field("url")
+            .with_detail("scheme", url.scheme()),
+        );
+      }
+    }
+    Ok(())
+  }
+
+  pub fn check_comment(&self, content: &str) -> Result<(), ApiError> {
+    check_max_length(
+      content,
+      self.max_comment_length,
+      ApiErrorCode::CommentTooLong,
+    )
+  }
+
+  pub fn is_url_allowed(&self, url: &Url) -> bool {
+    is_url_scheme_allowed(url, &self.allowed_url_schemes)
+  }
 }
 
+impl From<&Site> for ContentLimits {
+  fn from(site: &Site) -> Self {
+    ContentLimits {
+      max_post_body_length: site.max_post_body_length,
+      max_comment_length: site.max_comment_length,
+      allowed_url_schemes: site.allowed_url_schemes.to_owned(),
+    }
+  }
+}
+
+/// The same values as the column defaults, used before the site is set up
+impl Default for ContentLimits {
+  fn default() -> Self {
+    ContentLimits {
+      max_post_body_length: 50_000,
+      max_comment_length: 10_000,
+      allowed_url_schemes: vec!["http".to_string(), "https".to_string()],
+    }
+  }
+}
+
+/// The parts of `GetSiteResponse` which are the same for every user
+#[derive(Clone)]
+pub struct CachedSite {
+  pub site_view: Option<SiteView>,
+  pub admins: Vec<PersonViewSafe>,
+  pub banned: Vec<PersonViewSafe>,
+  pub federated_instances: Option<FederatedInstances>,
+  pub all_languages: Vec<Language>,
+}
+
+pub type SiteCache = TimedCache<CachedSite>;
+
 #[derive(Deserialize)]
 pub struct TransferSite {
-  pub person_id: i32,
+  pub person_id: PersonId,
   pub auth: String,
 }
 
@@ -128,9 +357,129 @@ pub struct SaveSiteConfig {
   pub auth: String,
 }
 
+#[derive(Deserialize)]
+pub struct GetAdminStats {
+  pub auth: String,
+}
+
+#[derive(Serialize)]
+pub struct GetAdminStatsResponse {
+  pub federation_fetches: Vec<InstanceFetchStats>,
+  pub primary_pool: DbPoolStats,
+  /// Only set if a read replica is configured
+  pub read_replica_pool: Option<DbPoolStats>,
+}
+
+#[derive(Serialize)]
+pub struct DbPoolStats {
+  pub max_size: u32,
+  pub connections: u32,
+  pub idle_connections: u32,
+}
+
+impl DbPoolStats {
+  pub fn from_pool(pool: &DbPool) -> Self {
+    let state = pool.state();
+    DbPoolStats {
+      max_size: pool.max_size(),
+      connections: state.connections,
+      idle_connections: state.idle_connections,
+    }
+  }
+}
+
+#[derive(Deserialize, Debug)]
+pub enum AggregatesScope {
+  Person(PersonId),
+  Community(CommunityId),
+  Post(PostId),
+  Site,
+}
+
+#[derive(Deserialize)]
+pub struct RecalculateAggregates {
+  pub scope: AggregatesScope,
+  pub auth: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct RecalculateAggregatesResponse {
+  /// Number of aggregate rows which were wrong or missing
+  pub corrected: usize,
+}
+
+#[derive(Deserialize)]
+pub struct ListScheduledTasks {
+  pub auth: String,
+}
+
 #[derive(Serialize)]
+pub struct ListScheduledTasksResponse {
+  pub tasks: Vec<TaskLock>,
+}
+
+#[derive(Deserialize)]
+pub struct RunScheduledTask {
+  pub name: String,
+  pub auth: String,
+}
+
+#[derive(Serialize)]
+pub struct RunScheduledTaskResponse {
+  pub task: TaskLock,
+}
+
+#[derive(Serialize, Clone)]
 pub struct FederatedInstances {
+  /// The current mode, so that clients can explain why remote content can't be found
+  pub mode: FederationMode,
   pub linked: Vec<String>,
   pub allowed: Option<Vec<String>>,
   pub blocked: Option<Vec<String>>,
+  /// Only returned to admins
+  pub details: Option<Vec<FederatedInstance>>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct FederatedInstance {
+  pub domain: String,
+  pub software: Option<String>,
+  pub version: Option<String>,
+  /// The last time that the nodeinfo of the instance could be fetched
+  pub last_seen: Option<chrono::NaiveDateTime>,
+  pub linked: bool,
+  pub allowed: bool,
+  pub blocked: bool,
+}
+
+impl FederatedInstances {
+  /// Fills in `details` for every linked, allowed or blocked instance. Instances whose nodeinfo
+  /// wasn't fetched yet have no software.
+  pub fn with_details(mut self, known: Vec<Instance>) -> Self {
+    let allowed = self.allowed.clone().unwrap_or_default();
+    let blocked = self.blocked.clone().unwrap_or_default();
+    let mut domains = self.linked.clone();
+    domains.extend(allowed.iter().cloned());
+    domains.extend(blocked.iter().cloned());
+    domains.sort_unstable();
+    domains.dedup();
+
+    let details = domains
+      .into_iter()
+      .map(|domain| {
+        let instance = known.iter().find(|i| i.domain == domain);
+        FederatedInstance {
+          software: instance.and_then(|i| i.software.to_owned()),
+          version: instance.and_then(|i| i.version.to_owned()),
+          last_seen: instance.and_then(|i| i.last_seen),
+          linked: self.linked.contains(&domain),
+          allowed: allowed.contains(&domain),
+          blocked: blocked.contains(&domain),
+          domain,
+        }
+      })
+      .collect();
+    self.details = Some(details);
+    self
+  }
 }
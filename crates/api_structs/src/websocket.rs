@@ -1,3 +1,4 @@
+use lemmy_db_schema::{CommunityId, PostId};
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Debug)]
@@ -12,7 +13,9 @@ pub struct UserJoinResponse {
 
 #[derive(Deserialize, Debug)]
 pub struct CommunityJoin {
-  pub community_id: i32,
+  pub community_id: CommunityId,
+  /// With a login, the community messages of the room contain the own `subscribed` state
+  pub auth: Option<String>,
 }
 
 #[derive(Serialize, Clone)]
@@ -22,7 +25,7 @@ pub struct CommunityJoinResponse {
 
 #[derive(Deserialize, Debug)]
 pub struct ModJoin {
-  pub community_id: i32,
+  pub community_id: CommunityId,
 }
 
 #[derive(Serialize, Clone)]
@@ -32,10 +35,17 @@ pub struct ModJoinResponse {
 
 #[derive(Deserialize, Debug)]
 pub struct PostJoin {
-  pub post_id: i32,
+  pub post_id: PostId,
 }
 
 #[derive(Serialize, Clone)]
 pub struct PostJoinResponse {
   pub joined: bool,
 }
+
+/// Sent over websocket to the clients in a post room when the number of clients in it changed
+#[derive(Serialize, Clone)]
+pub struct PostUsersOnline {
+  pub post_id: PostId,
+  pub online: usize,
+}
@@ -1,38 +1,90 @@
-use crate::{activities::receive::get_actor_as_person, objects::FromApub, ActorType, NoteExt};
+use crate::{
+  activities::receive::{
+    collect_tags,
+    community::verify_is_community_or_mod,
+    get_actor_as_person,
+    get_mentions,
+    get_new_mentions,
+  },
+  objects::FromApub,
+  ActorType,
+  NoteExt,
+};
 use activitystreams::{
   activity::{ActorAndObjectRefExt, Create, Dislike, Like, Remove, Update},
   base::ExtendsExt,
+  prelude::*,
 };
 use anyhow::Context;
-use lemmy_api_structs::{blocking, comment::CommentResponse, send_local_notifs};
-use lemmy_db_queries::{source::comment::Comment_, Crud, Likeable};
-use lemmy_db_schema::source::{
-  comment::{Comment, CommentLike, CommentLikeForm},
-  post::Post,
+use lemmy_api_structs::{
+  blocking,
+  blocking_transaction,
+  check_spam_rules,
+  comment::CommentResponse,
+  hide_downvotes_if_disabled,
+  remove_as_spam,
+  send_local_notifs,
+};
+use lemmy_db_queries::{
+  source::{comment::Comment_, spam_rule::SpamCheckContent},
+  ApubObject,
+  Crud,
+  Likeable,
+};
+use lemmy_db_schema::{
+  source::{
+    comment::{Comment, CommentLike, CommentLikeForm},
+    community::Community,
+    person::Person,
+    post::Post,
+  },
+  DbUrl,
 };
 use lemmy_db_views::comment_view::CommentView;
-use lemmy_utils::{location_info, utils::scrape_text_for_mentions, LemmyError};
+use lemmy_utils::{location_info, LemmyError};
 use lemmy_websocket::{messages::SendComment, LemmyContext, UserOperation};
+use url::Url;
 
+/// Returns `false` if the comment was removed as spam, so that it isn't announced
 pub(crate) async fn receive_create_comment(
   create: Create,
   context: &LemmyContext,
   request_counter: &mut i32,
-) -> Result<(), LemmyError> {
+) -> Result<bool, LemmyError> {
   let person = get_actor_as_person(&create, context, request_counter).await?;
   let note = NoteExt::from_any_base(create.object().to_owned().one().context(location_info!())?)?
     .context(location_info!())?;
 
+  let comment_ap_id: DbUrl = note
+    .id_unchecked()
+    .context(location_info!())?
+    .to_owned()
+    .into();
+  let is_new = blocking(context.pool(), move |conn| {
+    Comment::read_from_apub_id(conn, &comment_ap_id)
+  })
+  .await?
+  .is_err();
+
   let comment = Comment::from_apub(&note, context, person.actor_id(), request_counter).await?;
 
+  // Federated spam can't be rejected anymore, it is always removed instead
+  if is_new {
+    let spam_content = SpamCheckContent::Comment {
+      id: Some(comment.id),
+      content: comment.content.to_owned(),
+    };
+    if let Some(rule) = check_spam_rules(context.pool(), &person, spam_content).await? {
+      remove_as_spam(context.pool(), &rule, comment.post_id, Some(comment.id)).await?;
+      return Ok(false);
+    }
+  }
+
   let post_id = comment.post_id;
   let post = blocking(context.pool(), move |conn| Post::read(conn, post_id)).await??;
 
-  // Note:
-  // Although mentions could be gotten from the post tags (they are included there), or the ccs,
-  // Its much easier to scrape them from the comment body, since the API has to do that
-  // anyway.
-  let mentions = scrape_text_for_mentions(&comment.content);
+  let tags = collect_tags(create.tag(), note.tag());
+  let mentions = get_mentions(&comment.content, tags, context).await?;
   let recipient_ids = send_local_notifs(
     mentions,
     comment.clone(),
@@ -49,11 +101,14 @@ pub(crate) async fn receive_create_comment(
   })
   .await??;
 
-  let res = CommentResponse {
+  let mut res = CommentResponse {
     comment_view,
     recipient_ids,
     form_id: None,
+    mentions: Vec::new(),
+    federation: None,
   };
+  hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
   context.chat_server().do_send(SendComment {
     op: UserOperation::CreateComment,
@@ -61,7 +116,7 @@ pub(crate) async fn receive_create_comment(
     websocket_id: None,
   });
 
-  Ok(())
+  Ok(true)
 }
 
 pub(crate) async fn receive_update_comment(
@@ -71,15 +126,50 @@ pub(crate) async fn receive_update_comment(
 ) -> Result<(), LemmyError> {
   let note = NoteExt::from_any_base(update.object().to_owned().one().context(location_info!())?)?
     .context(location_info!())?;
-  let person = get_actor_as_person(&update, context, request_counter).await?;
+  let actor_id = update
+    .actor()?
+    .as_single_xsd_any_uri()
+    .context(location_info!())?
+    .to_owned();
+
+  // An update of a known comment by someone else than its creator can only change the sticky state
+  let comment_ap_id: DbUrl = note
+    .id_unchecked()
+    .context(location_info!())?
+    .to_owned()
+    .into();
+  let existing = blocking(context.pool(), move |conn| -> Result<_, LemmyError> {
+    match Comment::read_from_apub_id(conn, &comment_ap_id) {
+      Ok(comment) => {
+        let creator = Person::read(conn, comment.creator_id)?;
+        Ok(Some((comment, creator)))
+      }
+      Err(_) => Ok(None),
+    }
+  })
+  .await??;
+  // Persons who were already mentioned before the edit don't get notified again
+  let old_content = existing
+    .as_ref()
+    .map(|(comment, _)| comment.content.to_owned())
+    .unwrap_or_default();
+  if let Some((comment, creator)) = existing {
+    if creator.actor_id() != actor_id {
+      return receive_sticky_comment(&note, comment, &actor_id, context).await;
+    }
+  }
 
+  let person = get_actor_as_person(&update, context, request_counter).await?;
   let comment = Comment::from_apub(&note, context, person.actor_id(), request_counter).await?;
+  // The creator might be a mod as well
+  let comment = update_stickied_by_mod(&note, comment, &actor_id, context).await?;
 
   let comment_id = comment.id;
   let post_id = comment.post_id;
   let post = blocking(context.pool(), move |conn| Post::read(conn, post_id)).await??;
 
-  let mentions = scrape_text_for_mentions(&comment.content);
+  let tags = collect_tags(update.tag(), note.tag());
+  let mentions = get_new_mentions(&old_content, &comment.content, tags, context).await?;
   let recipient_ids =
     send_local_notifs(mentions, comment, person, post, context.pool(), false).await?;
 
@@ -89,11 +179,14 @@ pub(crate) async fn receive_update_comment(
   })
   .await??;
 
-  let res = CommentResponse {
+  let mut res = CommentResponse {
     comment_view,
     recipient_ids,
     form_id: None,
+    mentions: Vec::new(),
+    federation: None,
   };
+  hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
   context.chat_server().do_send(SendComment {
     op: UserOperation::EditComment,
@@ -104,8 +197,82 @@ pub(crate) async fn receive_update_comment(
   Ok(())
 }
 
+/// Applies the sticky state of the note, if the actor is allowed to change it. Only the mods of the
+/// community and the community itself can sticky comments, and only top-level ones.
+async fn update_stickied_by_mod(
+  note: &NoteExt,
+  comment: Comment,
+  actor_id: &Url,
+  context: &LemmyContext,
+) -> Result<Comment, LemmyError> {
+  let stickied = match note.ext_one.stickied {
+    Some(stickied) if stickied != comment.stickied && comment.parent_id.is_none() => stickied,
+    _ => return Ok(comment),
+  };
+  let post_id = comment.post_id;
+  let community = blocking(context.pool(), move |conn| {
+    let post = Post::read(conn, post_id)?;
+    Community::read(conn, post.community_id)
+  })
+  .await??;
+  if verify_is_community_or_mod(actor_id, &community, context)
+    .await
+    .is_err()
+  {
+    return Ok(comment);
+  }
+
+  // Not part of the form, as stickying a comment unstickies the previous one of the post
+  let comment_id = comment.id;
+  let comment = blocking(context.pool(), move |conn| {
+    Comment::update_stickied(conn, comment_id, stickied)
+  })
+  .await??;
+  Ok(comment)
+}
+
+/// A mod or the community changed the sticky state of a comment by someone else
+async fn receive_sticky_comment(
+  note: &NoteExt,
+  comment: Comment,
+  actor_id: &Url,
+  context: &LemmyContext,
+) -> Result<(), LemmyError> {
+  let post_id = comment.post_id;
+  let community = blocking(context.pool(), move |conn| {
+    let post = Post::read(conn, post_id)?;
+    Community::read(conn, post.community_id)
+  })
+  .await??;
+  verify_is_community_or_mod(actor_id, &community, context).await?;
+  let comment = update_stickied_by_mod(note, comment, actor_id, context).await?;
+
+  let comment_id = comment.id;
+  let comment_view = blocking(context.pool(), move |conn| {
+    CommentView::read(conn, comment_id, None)
+  })
+  .await??;
+  let mut res = CommentResponse {
+    comment_view,
+    recipient_ids: Vec::new(),
+    form_id: None,
+    mentions: Vec::new(),
+    federation: None,
+  };
+  hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
+
+  context.chat_server().do_send(SendComment {
+    op: UserOperation::StickyComment,
+    comment: res,
+    websocket_id: None,
+  });
+
+  Ok(())
+}
+
 pub(crate) async fn receive_like_comment(
   like: Like,
+  votes: i16,
   comment: Comment,
   context: &LemmyContext,
   request_counter: &mut i32,
@@ -117,14 +284,15 @@ pub(crate) async fn receive_like_comment(
     comment_id,
     post_id: comment.post_id,
     person_id: person.id,
-    score: 1,
+    score: votes,
   };
   let person_id = person.id;
-  blocking(context.pool(), move |conn| {
+  // Replaces an earlier vote of the person, in a single transaction so it is never counted twice
+  blocking_transaction(context.pool(), move |conn| {
     CommentLike::remove(conn, person_id, comment_id)?;
-    CommentLike::like(conn, &like_form)
+    Ok(CommentLike::like(conn, &like_form)?)
   })
-  .await??;
+  .await?;
 
   // Refetch the view
   let comment_view = blocking(context.pool(), move |conn| {
@@ -134,11 +302,14 @@ pub(crate) async fn receive_like_comment(
 
   // TODO get those recipient actor ids from somewhere
   let recipient_ids = vec![];
-  let res = CommentResponse {
+  let mut res = CommentResponse {
     comment_view,
     recipient_ids,
     form_id: None,
+    mentions: Vec::new(),
+    federation: None,
   };
+  hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
   context.chat_server().do_send(SendComment {
     op: UserOperation::CreateCommentLike,
@@ -151,6 +322,7 @@ pub(crate) async fn receive_like_comment(
 
 pub(crate) async fn receive_dislike_comment(
   dislike: Dislike,
+  votes: i16,
   comment: Comment,
   context: &LemmyContext,
   request_counter: &mut i32,
@@ -162,14 +334,15 @@ pub(crate) async fn receive_dislike_comment(
     comment_id,
     post_id: comment.post_id,
     person_id: person.id,
-    score: -1,
+    score: -votes,
   };
   let person_id = person.id;
-  blocking(context.pool(), move |conn| {
+  // Replaces an earlier vote of the person, in a single transaction so it is never counted twice
+  blocking_transaction(context.pool(), move |conn| {
     CommentLike::remove(conn, person_id, comment_id)?;
-    CommentLike::like(conn, &like_form)
+    Ok(CommentLike::like(conn, &like_form)?)
   })
-  .await??;
+  .await?;
 
   // Refetch the view
   let comment_view = blocking(context.pool(), move |conn| {
@@ -179,11 +352,14 @@ pub(crate) async fn receive_dislike_comment(
 
   // TODO get those recipient actor ids from somewhere
   let recipient_ids = vec![];
-  let res = CommentResponse {
+  let mut res = CommentResponse {
     comment_view,
     recipient_ids,
     form_id: None,
+    mentions: Vec::new(),
+    federation: None,
   };
+  hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
   context.chat_server().do_send(SendComment {
     op: UserOperation::CreateCommentLike,
@@ -212,11 +388,14 @@ pub(crate) async fn receive_delete_comment(
 
   // TODO get those recipient actor ids from somewhere
   let recipient_ids = vec![];
-  let res = CommentResponse {
+  let mut res = CommentResponse {
     comment_view,
     recipient_ids,
     form_id: None,
+    mentions: Vec::new(),
+    federation: None,
   };
+  hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
   context.chat_server().do_send(SendComment {
     op: UserOperation::EditComment,
     comment: res,
@@ -245,11 +424,14 @@ pub(crate) async fn receive_remove_comment(
 
   // TODO get those recipient actor ids from somewhere
   let recipient_ids = vec![];
-  let res = CommentResponse {
+  let mut res = CommentResponse {
     comment_view,
     recipient_ids,
     form_id: None,
+    mentions: Vec::new(),
+    federation: None,
   };
+  hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
   context.chat_server().do_send(SendComment {
     op: UserOperation::EditComment,
     comment: res,
@@ -1,6 +1,6 @@
 use crate::activities::receive::get_actor_as_person;
 use activitystreams::activity::{Dislike, Like};
-use lemmy_api_structs::{blocking, comment::CommentResponse};
+use lemmy_api_structs::{blocking, comment::CommentResponse, hide_downvotes_if_disabled};
 use lemmy_db_queries::{source::comment::Comment_, Likeable};
 use lemmy_db_schema::source::comment::{Comment, CommentLike};
 use lemmy_db_views::comment_view::CommentView;
@@ -17,8 +17,9 @@ pub(crate) async fn receive_undo_like_comment(
 
   let comment_id = comment.id;
   let person_id = person.id;
+  // A newer dislike stays
   blocking(context.pool(), move |conn| {
-    CommentLike::remove(conn, person_id, comment_id)
+    CommentLike::remove_with_score(conn, person_id, comment_id, 1)
   })
   .await??;
 
@@ -30,11 +31,14 @@ pub(crate) async fn receive_undo_like_comment(
 
   // TODO get those recipient actor ids from somewhere
   let recipient_ids = vec![];
-  let res = CommentResponse {
+  let mut res = CommentResponse {
     comment_view,
     recipient_ids,
     form_id: None,
+    mentions: Vec::new(),
+    federation: None,
   };
+  hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
   context.chat_server().do_send(SendComment {
     op: UserOperation::CreateCommentLike,
@@ -55,8 +59,9 @@ pub(crate) async fn receive_undo_dislike_comment(
 
   let comment_id = comment.id;
   let person_id = person.id;
+  // A newer like stays
   blocking(context.pool(), move |conn| {
-    CommentLike::remove(conn, person_id, comment_id)
+    CommentLike::remove_with_score(conn, person_id, comment_id, -1)
   })
   .await??;
 
@@ -68,11 +73,14 @@ pub(crate) async fn receive_undo_dislike_comment(
 
   // TODO get those recipient actor ids from somewhere
   let recipient_ids = vec![];
-  let res = CommentResponse {
+  let mut res = CommentResponse {
     comment_view,
     recipient_ids,
     form_id: None,
+    mentions: Vec::new(),
+    federation: None,
   };
+  hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
   context.chat_server().do_send(SendComment {
     op: UserOperation::CreateCommentLike,
@@ -101,11 +109,14 @@ pub(crate) async fn receive_undo_delete_comment(
 
   // TODO get those recipient actor ids from somewhere
   let recipient_ids = vec![];
-  let res = CommentResponse {
+  let mut res = CommentResponse {
     comment_view,
     recipient_ids,
     form_id: None,
+    mentions: Vec::new(),
+    federation: None,
   };
+  hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
   context.chat_server().do_send(SendComment {
     op: UserOperation::EditComment,
@@ -134,11 +145,14 @@ pub(crate) async fn receive_undo_remove_comment(
 
   // TODO get those recipient actor ids from somewhere
   let recipient_ids = vec![];
-  let res = CommentResponse {
+  let mut res = CommentResponse {
     comment_view,
     recipient_ids,
     form_id: None,
+    mentions: Vec::new(),
+    federation: None,
   };
+  hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
   context.chat_server().do_send(SendComment {
     op: UserOperation::EditComment,
@@ -1,15 +1,51 @@
-use crate::{activities::receive::verify_activity_domains_valid, inbox::is_addressed_to_public};
+use crate::{
+  activities::receive::verify_activity_domains_valid,
+  fetcher::person::get_or_fetch_and_upsert_person,
+  inbox::is_addressed_to_public,
+  ActorType,
+  BlockExt,
+};
 use activitystreams::{
-  activity::{ActorAndObjectRefExt, Delete, Remove, Undo},
+  activity::{ActorAndObjectRefExt, Block, Delete, Remove, Undo},
   base::{AnyBase, ExtendsExt},
 };
-use anyhow::Context;
-use lemmy_api_structs::{blocking, community::CommunityResponse};
-use lemmy_db_queries::{source::community::Community_, ApubObject};
-use lemmy_db_schema::source::community::Community;
-use lemmy_db_views_actor::community_view::CommunityView;
+use anyhow::{anyhow, Context};
+use lemmy_api_structs::{
+  blocking,
+  community::{BanFromCommunityResponse, CommunityResponse},
+};
+use lemmy_db_queries::{
+  source::{comment::Comment_, community::Community_, post::Post_},
+  ApubObject,
+  Bannable,
+  Followable,
+};
+use lemmy_db_schema::{
+  source::{
+    comment::Comment,
+    community::{
+      Community,
+      CommunityFollower,
+      CommunityFollowerForm,
+      CommunityPersonBan,
+      CommunityPersonBanForm,
+    },
+    person::Person,
+    post::Post,
+  },
+  CommunityId,
+  PersonId,
+};
+use lemmy_db_views::comment_view::CommentQueryBuilder;
+use lemmy_db_views_actor::{community_view::CommunityView, person_view::PersonViewSafe};
 use lemmy_utils::{location_info, LemmyError};
-use lemmy_websocket::{messages::SendCommunityRoomMessage, LemmyContext, UserOperation};
+use lemmy_websocket::{
+  messages::SendCommunityRoomMessage,
+  send::send_community_websocket,
+  LemmyContext,
+  UserOperation,
+};
+use std::collections::HashMap;
 use url::Url;
 
 pub(crate) async fn receive_delete_community(
@@ -29,13 +65,7 @@ pub(crate) async fn receive_delete_community(
     .await??,
   };
 
-  let community_id = res.community_view.community.id;
-  context.chat_server().do_send(SendCommunityRoomMessage {
-    op: UserOperation::EditCommunity,
-    response: res,
-    community_id,
-    websocket_id: None,
-  });
+  send_community_websocket(&res, context, None, UserOperation::EditCommunity).await?;
 
   Ok(())
 }
@@ -72,13 +102,7 @@ pub(crate) async fn receive_remove_community(
     .await??,
   };
 
-  let community_id = res.community_view.community.id;
-  context.chat_server().do_send(SendCommunityRoomMessage {
-    op: UserOperation::EditCommunity,
-    response: res,
-    community_id,
-    websocket_id: None,
-  });
+  send_community_websocket(&res, context, None, UserOperation::EditCommunity).await?;
 
   Ok(())
 }
@@ -108,13 +132,7 @@ pub(crate) async fn receive_undo_delete_community(
     .await??,
   };
 
-  let community_id = res.community_view.community.id;
-  context.chat_server().do_send(SendCommunityRoomMessage {
-    op: UserOperation::EditCommunity,
-    response: res,
-    community_id,
-    websocket_id: None,
-  });
+  send_community_websocket(&res, context, None, UserOperation::EditCommunity).await?;
 
   Ok(())
 }
@@ -154,11 +172,188 @@ pub(crate) async fn receive_undo_remove_community(
     .await??,
   };
 
-  let community_id = res.community_view.community.id;
+  send_community_websocket(&res, context, None, UserOperation::EditCommunity).await?;
+
+  Ok(())
+}
+
+/// A person being banned from a community. The ban can be sent by the community itself, or by one
+/// of its mods.
+pub(crate) async fn receive_block_user_for_community(
+  context: &LemmyContext,
+  activity: AnyBase,
+  community: &Community,
+  request_counter: &mut i32,
+) -> Result<(), LemmyError> {
+  let block = Block::from_any_base(activity.clone())?.context(location_info!())?;
+  let actor_id = block
+    .actor()?
+    .as_single_xsd_any_uri()
+    .context(location_info!())?
+    .to_owned();
+  verify_activity_domains_valid(&block, &actor_id, false)?;
+  is_addressed_to_public(&block)?;
+  verify_is_community_or_mod(&actor_id, community, context).await?;
+
+  let remove_data = BlockExt::from_any_base(activity)?
+    .context(location_info!())?
+    .ext_one
+    .remove_data
+    .unwrap_or(false);
+
+  let blocked_id = block
+    .object()
+    .as_single_xsd_any_uri()
+    .context(location_info!())?;
+  let blocked = get_or_fetch_and_upsert_person(&blocked_id, context, request_counter).await?;
+
+  let community_id = community.id;
+  let blocked_person_id = blocked.id;
+  let community_user_ban_form = CommunityPersonBanForm {
+    community_id,
+    person_id: blocked_person_id,
+  };
+  // This will fail if the person is already banned, but ignore the error.
+  blocking(context.pool(), move |conn: &'_ _| {
+    CommunityPersonBan::ban(conn, &community_user_ban_form).ok()
+  })
+  .await?;
+
+  // Also unsubscribe them from the community, if they are subscribed
+  let community_follower_form = CommunityFollowerForm {
+    community_id,
+    person_id: blocked_person_id,
+    pending: false,
+  };
+  blocking(context.pool(), move |conn: &'_ _| {
+    CommunityFollower::unfollow(conn, &community_follower_form).ok()
+  })
+  .await?;
+
+  if remove_data {
+    remove_person_data_in_community(context, blocked_person_id, community_id).await?;
+  }
+
+  send_ban_from_community_message(context, blocked_person_id, community_id, true).await
+}
+
+/// A community ban being reverted
+pub(crate) async fn receive_undo_block_user_for_community(
+  context: &LemmyContext,
+  undo: Undo,
+  community: &Community,
+  request_counter: &mut i32,
+) -> Result<(), LemmyError> {
+  is_addressed_to_public(&undo)?;
+  let inner = undo.object().to_owned().one().context(location_info!())?;
+  let block = Block::from_any_base(inner)?.context(location_info!())?;
+  let actor_id = block
+    .actor()?
+    .as_single_xsd_any_uri()
+    .context(location_info!())?
+    .to_owned();
+  verify_activity_domains_valid(&undo, &actor_id, true)?;
+  verify_activity_domains_valid(&block, &actor_id, false)?;
+  verify_is_community_or_mod(&actor_id, community, context).await?;
+
+  let blocked_id = block
+    .object()
+    .as_single_xsd_any_uri()
+    .context(location_info!())?;
+  let blocked = get_or_fetch_and_upsert_person(&blocked_id, context, request_counter).await?;
+
+  let community_id = community.id;
+  let blocked_person_id = blocked.id;
+  let community_user_ban_form = CommunityPersonBanForm {
+    community_id,
+    person_id: blocked_person_id,
+  };
+  // This will fail if the person is not banned, but ignore the error.
+  blocking(context.pool(), move |conn: &'_ _| {
+    CommunityPersonBan::unban(conn, &community_user_ban_form).ok()
+  })
+  .await?;
+
+  send_ban_from_community_message(context, blocked_person_id, community_id, false).await
+}
+
+/// Only the community itself and its mods are allowed to ban persons from it, or to sticky comments.
+pub(crate) async fn verify_is_community_or_mod(
+  actor_id: &Url,
+  community: &Community,
+  context: &LemmyContext,
+) -> Result<(), LemmyError> {
+  if actor_id == &community.actor_id() {
+    return Ok(());
+  }
+
+  let actor_id = actor_id.to_owned();
+  let community_id = community.id;
+  let is_mod = blocking(context.pool(), move |conn| {
+    Person::read_from_apub_id(conn, &actor_id.into())
+      .map(|p| CommunityView::is_mod_or_admin(conn, p.id, community_id))
+      .unwrap_or(false)
+  })
+  .await?;
+  if !is_mod {
+    return Err(anyhow!("Not sent by the community or one of its mods").into());
+  }
+  Ok(())
+}
+
+async fn remove_person_data_in_community(
+  context: &LemmyContext,
+  person_id: PersonId,
+  community_id: CommunityId,
+) -> Result<(), LemmyError> {
+  // Posts
+  blocking(context.pool(), move |conn: &'_ _| {
+    Post::update_removed_for_creator(conn, person_id, Some(community_id), true)
+  })
+  .await??;
+
+  // Comments
+  // TODO Diesel doesn't allow updates with joins, so this has to be a loop
+  let comments = blocking(context.pool(), move |conn| {
+    CommentQueryBuilder::create(conn)
+      .creator_id(person_id)
+      .community_id(community_id)
+      .limit(std::i64::MAX)
+      .list()
+  })
+  .await??;
+
+  for comment_view in &comments {
+    let comment_id = comment_view.comment.id;
+    blocking(context.pool(), move |conn: &'_ _| {
+      Comment::update_removed(conn, comment_id, true)
+    })
+    .await??;
+  }
+
+  Ok(())
+}
+
+async fn send_ban_from_community_message(
+  context: &LemmyContext,
+  person_id: PersonId,
+  community_id: CommunityId,
+  banned: bool,
+) -> Result<(), LemmyError> {
+  let person_view = blocking(context.pool(), move |conn| {
+    PersonViewSafe::read(conn, person_id)
+  })
+  .await??;
+
+  let res = BanFromCommunityResponse {
+    person_view,
+    banned,
+  };
 
   context.chat_server().do_send(SendCommunityRoomMessage {
-    op: UserOperation::EditCommunity,
+    op: UserOperation::BanFromCommunity,
     response: res,
+    person_responses: HashMap::new(),
     community_id,
     websocket_id: None,
   });
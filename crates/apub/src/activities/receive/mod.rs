@@ -1,12 +1,23 @@
 use crate::fetcher::person::get_or_fetch_and_upsert_person;
 use activitystreams::{
   activity::{ActorAndObjectRef, ActorAndObjectRefExt},
-  base::{AsBase, BaseExt},
+  base::{AnyBase, AsBase, BaseExt, ExtendsExt},
   error::DomainError,
+  link::Mention,
+  prelude::*,
+  primitives::OneOrMany,
 };
 use anyhow::{anyhow, Context};
-use lemmy_db_schema::source::person::Person;
-use lemmy_utils::{location_info, LemmyError};
+use itertools::Itertools;
+use lemmy_api_structs::blocking;
+use lemmy_db_queries::ApubObject;
+use lemmy_db_schema::{source::person::Person, DbUrl};
+use lemmy_utils::{
+  location_info,
+  settings::structs::Settings,
+  utils::{scrape_text_for_mentions, MentionData, MAX_MENTIONS},
+  LemmyError,
+};
 use lemmy_websocket::LemmyContext;
 use log::debug;
 use std::fmt::Debug;
@@ -15,6 +26,7 @@ use url::Url;
 pub(crate) mod comment;
 pub(crate) mod comment_undo;
 pub(crate) mod community;
+pub(crate) mod person;
 pub(crate) mod post;
 pub(crate) mod post_undo;
 pub(crate) mod private_message;
@@ -79,3 +91,377 @@ where
 
   Ok(())
 }
+
+/// Combines the tags of an activity and of its object, other software puts mentions in either.
+pub(crate) fn collect_tags(
+  activity_tags: Option<&OneOrMany<AnyBase>>,
+  object_tags: Option<&OneOrMany<AnyBase>>,
+) -> Vec<AnyBase> {
+  activity_tags
+    .into_iter()
+    .chain(object_tags)
+    .flat_map(|t| match t.as_one() {
+      Some(one) => vec![one.to_owned()],
+      None => t.as_many().map(|m| m.to_vec()).unwrap_or_default(),
+    })
+    .collect()
+}
+
+/// Returns the persons mentioned in the text of a post or comment, together with local persons
+/// which are only referenced by a `Mention` tag (other software may render mentions differently in
+/// the text).
+pub(crate) async fn get_mentions(
+  text: &str,
+  tags: Vec<AnyBase>,
+  context: &LemmyContext,
+) -> Result<Vec<MentionData>, LemmyError> {
+  let hrefs: Vec<DbUrl> = tags
+    .into_iter()
+    .filter_map(|t| Mention::from_any_base(t).ok().flatten())
+    .filter_map(|m| m.href().map(|h| h.to_owned()))
+    .unique()
+    .take(MAX_MENTIONS)
+    .map(DbUrl::from)
+    .collect();
+  let tagged = blocking(context.pool(), move |conn| {
+    hrefs
+      .iter()
+      .filter_map(|href| Person::read_from_apub_id(conn, href).ok())
+      .filter(|p| p.local)
+      .map(|p| MentionData {
+        name: p.name,
+        domain: Settings::get().hostname(),
+      })
+      .collect::<Vec<MentionData>>()
+  })
+  .await?;
+
+  Ok(
+    scrape_text_for_mentions(text)
+      .into_iter()
+      .chain(tagged)
+      .unique()
+      .take(MAX_MENTIONS)
+      .collect(),
+  )
+}
+
+/// Like `get_mentions()`, but leaves out the persons who were already mentioned in `old_text`, so
+/// that an update only notifies the newly mentioned ones.
+pub(crate) async fn get_new_mentions(
+  old_text: &str,
+  text: &str,
+  tags: Vec<AnyBase>,
+  context: &LemmyContext,
+) -> Result<Vec<MentionData>, LemmyError> {
+  let old_mentions = scrape_text_for_mentions(old_text);
+  Ok(
+    get_mentions(text, tags, context)
+      .await?
+      .into_iter()
+      .filter(|m| !old_mentions.contains(m))
+      .collect(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    activities::receive::{
+      comment::{receive_dislike_comment, receive_like_comment},
+      comment_undo::receive_undo_dislike_comment,
+      post::{receive_dislike_post, receive_like_post},
+      post_undo::{receive_undo_dislike_post, receive_undo_like_post},
+    },
+    test::init_context,
+  };
+  use activitystreams::activity::{Dislike, Like};
+  use lemmy_db_queries::{
+    aggregates::{comment_aggregates::CommentAggregates, post_aggregates::PostAggregates},
+    establish_unpooled_connection,
+    Crud,
+  };
+  use lemmy_db_schema::source::{
+    comment::{Comment, CommentForm},
+    community::{Community, CommunityForm},
+    person::{Person, PersonForm},
+    post::{Post, PostForm},
+  };
+  use serial_test::serial;
+
+  #[test]
+  #[serial]
+  fn test_receive_votes() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "thommy_receive_votes".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let another_person = PersonForm {
+      name: "jerry_receive_votes".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+
+    let another_inserted_person = Person::create(&conn, &another_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "TIL_receive_votes".into(),
+      creator_id: inserted_person.id,
+      title: "nada".to_owned(),
+      description: None,
+      nsfw: false,
+      removed: None,
+      deleted: None,
+      updated: None,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let new_post = PostForm {
+      name: "A test post".into(),
+      url: None,
+      body: None,
+      creator_id: inserted_person.id,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      nsfw: false,
+      updated: None,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      thumbnail_url: None,
+      ap_id: None,
+      local: true,
+      published: None,
+    };
+
+    let inserted_post = Post::create(&conn, &new_post).unwrap();
+
+    let comment_form = CommentForm {
+      content: "A test comment".into(),
+      creator_id: inserted_person.id,
+      post_id: inserted_post.id,
+      removed: None,
+      deleted: None,
+      read: None,
+      parent_id: None,
+      published: None,
+      updated: None,
+      ap_id: None,
+      local: true,
+    };
+
+    let inserted_comment = Comment::create(&conn, &comment_form).unwrap();
+
+    let actor_id = another_inserted_person.actor_id.to_owned().into_inner();
+    let post_ap_id = inserted_post.ap_id.to_owned().into_inner();
+    let comment_ap_id = inserted_comment.ap_id.to_owned().into_inner();
+
+    let mut system = actix_rt::System::new("test_receive_votes");
+    system.block_on(async {
+      let context = init_context();
+      let request_counter = &mut 0;
+
+      // Receiving the same like twice only counts it once
+      let like = Like::new(actor_id.to_owned(), post_ap_id.to_owned());
+      receive_like_post(
+        like.to_owned(),
+        1,
+        inserted_post.to_owned(),
+        &context,
+        request_counter,
+      )
+      .await
+      .unwrap();
+      receive_like_post(
+        like.to_owned(),
+        1,
+        inserted_post.to_owned(),
+        &context,
+        request_counter,
+      )
+      .await
+      .unwrap();
+      let after_like = PostAggregates::read(&conn, inserted_post.id).unwrap();
+      assert_eq!(1, after_like.score);
+      assert_eq!(1, after_like.upvotes);
+      assert_eq!(0, after_like.downvotes);
+
+      // A dislike replaces the earlier like
+      let dislike = Dislike::new(actor_id.to_owned(), post_ap_id.to_owned());
+      receive_dislike_post(
+        dislike.to_owned(),
+        1,
+        inserted_post.to_owned(),
+        &context,
+        request_counter,
+      )
+      .await
+      .unwrap();
+      let after_dislike = PostAggregates::read(&conn, inserted_post.id).unwrap();
+      assert_eq!(-1, after_dislike.score);
+      assert_eq!(0, after_dislike.upvotes);
+      assert_eq!(1, after_dislike.downvotes);
+
+      // Undo like, then like again, as sent by an instance which switches the vote direction
+      receive_undo_dislike_post(
+        &dislike,
+        inserted_post.to_owned(),
+        &context,
+        request_counter,
+      )
+      .await
+      .unwrap();
+      receive_like_post(
+        like.to_owned(),
+        1,
+        inserted_post.to_owned(),
+        &context,
+        request_counter,
+      )
+      .await
+      .unwrap();
+      let after_switch = PostAggregates::read(&conn, inserted_post.id).unwrap();
+      assert_eq!(1, after_switch.score);
+      assert_eq!(1, after_switch.upvotes);
+      assert_eq!(0, after_switch.downvotes);
+
+      receive_undo_like_post(&like, inserted_post.to_owned(), &context, request_counter)
+        .await
+        .unwrap();
+      let after_undo = PostAggregates::read(&conn, inserted_post.id).unwrap();
+      assert_eq!(0, after_undo.score);
+      assert_eq!(0, after_undo.upvotes);
+      assert_eq!(0, after_undo.downvotes);
+
+      // An instance aggregate counts as that many votes, and replaces the previous aggregate
+      receive_like_post(
+        like.to_owned(),
+        5,
+        inserted_post.to_owned(),
+        &context,
+        request_counter,
+      )
+      .await
+      .unwrap();
+      let after_aggregate = PostAggregates::read(&conn, inserted_post.id).unwrap();
+      assert_eq!(5, after_aggregate.score);
+      assert_eq!(5, after_aggregate.upvotes);
+      assert_eq!(0, after_aggregate.downvotes);
+      receive_dislike_post(
+        dislike.to_owned(),
+        3,
+        inserted_post.to_owned(),
+        &context,
+        request_counter,
+      )
+      .await
+      .unwrap();
+      let after_negative_aggregate = PostAggregates::read(&conn, inserted_post.id).unwrap();
+      assert_eq!(-3, after_negative_aggregate.score);
+      assert_eq!(0, after_negative_aggregate.upvotes);
+      assert_eq!(3, after_negative_aggregate.downvotes);
+      receive_undo_like_post(&like, inserted_post.to_owned(), &context, request_counter)
+        .await
+        .unwrap();
+      let after_aggregate_undo = PostAggregates::read(&conn, inserted_post.id).unwrap();
+      assert_eq!(0, after_aggregate_undo.score);
+      assert_eq!(0, after_aggregate_undo.downvotes);
+
+      // The same for comments
+      let like = Like::new(actor_id.to_owned(), comment_ap_id.to_owned());
+      let dislike = Dislike::new(actor_id.to_owned(), comment_ap_id.to_owned());
+      receive_like_comment(
+        like,
+        1,
+        inserted_comment.to_owned(),
+        &context,
+        request_counter,
+      )
+      .await
+      .unwrap();
+      receive_dislike_comment(
+        dislike.to_owned(),
+        1,
+        inserted_comment.to_owned(),
+        &context,
+        request_counter,
+      )
+      .await
+      .unwrap();
+      let comment_after_dislike = CommentAggregates::read(&conn, inserted_comment.id).unwrap();
+      assert_eq!(-1, comment_after_dislike.score);
+      assert_eq!(0, comment_after_dislike.upvotes);
+      assert_eq!(1, comment_after_dislike.downvotes);
+
+      receive_undo_dislike_comment(
+        &dislike,
+        inserted_comment.to_owned(),
+        &context,
+        request_counter,
+      )
+      .await
+      .unwrap();
+      let comment_after_undo = CommentAggregates::read(&conn, inserted_comment.id).unwrap();
+      assert_eq!(0, comment_after_undo.score);
+      assert_eq!(0, comment_after_undo.downvotes);
+    });
+
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, another_inserted_person.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+  }
+}
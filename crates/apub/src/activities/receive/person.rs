@@ -0,0 +1,29 @@
+use crate::{activities::receive::verify_activity_domains_valid, objects::FromApub, PersonExt};
+use activitystreams::{activity::Update, base::AnyBase, prelude::*};
+use anyhow::{anyhow, Context};
+use lemmy_db_schema::source::person::Person;
+use lemmy_utils::{location_info, LemmyError};
+use lemmy_websocket::LemmyContext;
+use url::Url;
+
+/// A remote person changed their profile, so update our copy of it. Persons can only update
+/// themselves.
+pub(crate) async fn receive_update_person(
+  context: &LemmyContext,
+  activity: AnyBase,
+  actor_id: &Url,
+  request_counter: &mut i32,
+) -> Result<(), LemmyError> {
+  let update = Update::from_any_base(activity)?.context(location_info!())?;
+  verify_activity_domains_valid(&update, actor_id, true)?;
+
+  let person =
+    PersonExt::from_any_base(update.object().to_owned().one().context(location_info!())?)?
+      .context(location_info!())?;
+  if person.id_unchecked() != Some(actor_id) {
+    return Err(anyhow!("Person can only update itself").into());
+  }
+
+  Person::from_apub(&person, context, actor_id.to_owned(), request_counter).await?;
+  Ok(())
+}
@@ -1,35 +1,125 @@
-use crate::{activities::receive::get_actor_as_person, objects::FromApub, ActorType, PageExt};
+use crate::{
+  activities::receive::{collect_tags, get_actor_as_person, get_mentions, get_new_mentions},
+  objects::FromApub,
+  ActorType,
+  PageExt,
+};
 use activitystreams::{
   activity::{Create, Dislike, Like, Remove, Update},
   prelude::*,
 };
 use anyhow::Context;
-use lemmy_api_structs::{blocking, post::PostResponse};
-use lemmy_db_queries::{source::post::Post_, Likeable};
-use lemmy_db_schema::source::post::{Post, PostLike, PostLikeForm};
+use lemmy_api_structs::{
+  blocking,
+  blocking_transaction,
+  check_spam_rules,
+  hide_downvotes_if_disabled,
+  post::PostResponse,
+  remove_as_spam,
+  send_local_post_notifs,
+};
+use lemmy_db_queries::{
+  source::{post::Post_, spam_rule::SpamCheckContent},
+  ApubObject,
+  Crud,
+  Likeable,
+};
+use lemmy_db_schema::{
+  source::{
+    community::Community,
+    post::{Post, PostLike, PostLikeForm},
+  },
+  DbUrl,
+};
 use lemmy_db_views::post_view::PostView;
 use lemmy_utils::{location_info, LemmyError};
-use lemmy_websocket::{messages::SendPost, LemmyContext, UserOperation};
+use lemmy_websocket::{
+  messages::{SendPost, SendUserRoomMessage},
+  LemmyContext,
+  UserOperation,
+};
 
+/// Returns `false` if the post was removed as spam, so that it isn't announced
 pub(crate) async fn receive_create_post(
   create: Create,
   context: &LemmyContext,
   request_counter: &mut i32,
-) -> Result<(), LemmyError> {
+) -> Result<bool, LemmyError> {
   let person = get_actor_as_person(&create, context, request_counter).await?;
   let page = PageExt::from_any_base(create.object().to_owned().one().context(location_info!())?)?
     .context(location_info!())?;
 
+  let post_ap_id: DbUrl = page
+    .inner
+    .id_unchecked()
+    .context(location_info!())?
+    .to_owned()
+    .into();
+  let is_new = blocking(context.pool(), move |conn| {
+    Post::read_from_apub_id(conn, &post_ap_id)
+  })
+  .await?
+  .is_err();
+
   let post = Post::from_apub(&page, context, person.actor_id(), request_counter).await?;
 
-  // Refetch the view
+  // Federated posts can't be held back until a mod approves them, so they are only flagged for
+  // review if the community requires approval
+  let post = if is_new {
+    blocking(context.pool(), move |conn| -> Result<Post, LemmyError> {
+      let community = Community::read(conn, post.community_id)?;
+      if community.local && community.requires_post_approval {
+        Ok(Post::update_approved(conn, post.id, None)?)
+      } else {
+        Ok(post)
+      }
+    })
+    .await??
+  } else {
+    post
+  };
+
+  // Federated spam can't be rejected anymore, it is always removed instead
+  if is_new {
+    let spam_content = SpamCheckContent::Post {
+      id: Some(post.id),
+      name: post.name.to_owned(),
+      url: post.url.as_ref().map(|u| u.to_string()),
+      body: post.body.to_owned(),
+    };
+    if let Some(rule) = check_spam_rules(context.pool(), &person, spam_content).await? {
+      remove_as_spam(context.pool(), &rule, post.id, None).await?;
+      return Ok(false);
+    }
+  }
+
+  let tags = collect_tags(create.tag(), page.inner.tag());
+  let body = post.body.to_owned().unwrap_or_default();
+  let mentions = get_mentions(&body, tags, context).await?;
   let post_id = post.id;
+  let recipient_ids = send_local_post_notifs(mentions, post, person, context.pool(), true).await?;
+
+  // Refetch the view
   let post_view = blocking(context.pool(), move |conn| {
     PostView::read(conn, post_id, None)
   })
   .await??;
 
-  let res = PostResponse { post_view };
+  let mut res = PostResponse {
+    post_view,
+    federation: None,
+  };
+  hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
+
+  // Notify the mentioned local users
+  for local_recipient_id in recipient_ids {
+    context.chat_server().do_send(SendUserRoomMessage {
+      op: UserOperation::CreatePost,
+      response: res.clone(),
+      local_recipient_id,
+      websocket_id: None,
+    });
+  }
 
   context.chat_server().do_send(SendPost {
     op: UserOperation::CreatePost,
@@ -37,7 +127,7 @@ pub(crate) async fn receive_create_post(
     websocket_id: None,
   });
 
-  Ok(())
+  Ok(true)
 }
 
 pub(crate) async fn receive_update_post(
@@ -49,16 +139,50 @@ pub(crate) async fn receive_update_post(
   let page = PageExt::from_any_base(update.object().to_owned().one().context(location_info!())?)?
     .context(location_info!())?;
 
+  // Persons who were already mentioned before the edit don't get notified again
+  let post_ap_id: DbUrl = page
+    .inner
+    .id_unchecked()
+    .context(location_info!())?
+    .to_owned()
+    .into();
+  let old_body = blocking(context.pool(), move |conn| {
+    Post::read_from_apub_id(conn, &post_ap_id)
+  })
+  .await?
+  .ok()
+  .and_then(|p| p.body)
+  .unwrap_or_default();
+
   let post = Post::from_apub(&page, context, person.actor_id(), request_counter).await?;
 
+  let tags = collect_tags(update.tag(), page.inner.tag());
+  let body = post.body.to_owned().unwrap_or_default();
+  let mentions = get_new_mentions(&old_body, &body, tags, context).await?;
   let post_id = post.id;
+  let recipient_ids = send_local_post_notifs(mentions, post, person, context.pool(), false).await?;
+
   // Refetch the view
   let post_view = blocking(context.pool(), move |conn| {
     PostView::read(conn, post_id, None)
   })
   .await??;
 
-  let res = PostResponse { post_view };
+  let mut res = PostResponse {
+    post_view,
+    federation: None,
+  };
+  hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
+
+  // Notify the mentioned local users
+  for local_recipient_id in recipient_ids {
+    context.chat_server().do_send(SendUserRoomMessage {
+      op: UserOperation::EditPost,
+      response: res.clone(),
+      local_recipient_id,
+      websocket_id: None,
+    });
+  }
 
   context.chat_server().do_send(SendPost {
     op: UserOperation::EditPost,
@@ -71,6 +195,7 @@ pub(crate) async fn receive_update_post(
 
 pub(crate) async fn receive_like_post(
   like: Like,
+  votes: i16,
   post: Post,
   context: &LemmyContext,
   request_counter: &mut i32,
@@ -81,14 +206,15 @@ pub(crate) async fn receive_like_post(
   let like_form = PostLikeForm {
     post_id,
     person_id: person.id,
-    score: 1,
+    score: votes,
   };
   let person_id = person.id;
-  blocking(context.pool(), move |conn| {
+  // Replaces an earlier vote of the person, in a single transaction so it is never counted twice
+  blocking_transaction(context.pool(), move |conn| {
     PostLike::remove(conn, person_id, post_id)?;
-    PostLike::like(conn, &like_form)
+    Ok(PostLike::like(conn, &like_form)?)
   })
-  .await??;
+  .await?;
 
   // Refetch the view
   let post_view = blocking(context.pool(), move |conn| {
@@ -96,7 +222,11 @@ pub(crate) async fn receive_like_post(
   })
   .await??;
 
-  let res = PostResponse { post_view };
+  let mut res = PostResponse {
+    post_view,
+    federation: None,
+  };
+  hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
   context.chat_server().do_send(SendPost {
     op: UserOperation::CreatePostLike,
@@ -109,6 +239,7 @@ pub(crate) async fn receive_like_post(
 
 pub(crate) async fn receive_dislike_post(
   dislike: Dislike,
+  votes: i16,
   post: Post,
   context: &LemmyContext,
   request_counter: &mut i32,
@@ -119,14 +250,15 @@ pub(crate) async fn receive_dislike_post(
   let like_form = PostLikeForm {
     post_id,
     person_id: person.id,
-    score: -1,
+    score: -votes,
   };
   let person_id = person.id;
-  blocking(context.pool(), move |conn| {
+  // Replaces an earlier vote of the person, in a single transaction so it is never counted twice
+  blocking_transaction(context.pool(), move |conn| {
     PostLike::remove(conn, person_id, post_id)?;
-    PostLike::like(conn, &like_form)
+    Ok(PostLike::like(conn, &like_form)?)
   })
-  .await??;
+  .await?;
 
   // Refetch the view
   let post_view = blocking(context.pool(), move |conn| {
@@ -134,7 +266,11 @@ pub(crate) async fn receive_dislike_post(
   })
   .await??;
 
-  let res = PostResponse { post_view };
+  let mut res = PostResponse {
+    post_view,
+    federation: None,
+  };
+  hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
   context.chat_server().do_send(SendPost {
     op: UserOperation::CreatePostLike,
@@ -161,7 +297,11 @@ pub(crate) async fn receive_delete_post(
   })
   .await??;
 
-  let res = PostResponse { post_view };
+  let mut res = PostResponse {
+    post_view,
+    federation: None,
+  };
+  hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
   context.chat_server().do_send(SendPost {
     op: UserOperation::EditPost,
     post: res,
@@ -188,7 +328,11 @@ pub(crate) async fn receive_remove_post(
   })
   .await??;
 
-  let res = PostResponse { post_view };
+  let mut res = PostResponse {
+    post_view,
+    federation: None,
+  };
+  hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
   context.chat_server().do_send(SendPost {
     op: UserOperation::EditPost,
     post: res,
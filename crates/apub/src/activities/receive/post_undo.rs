@@ -1,6 +1,6 @@
 use crate::activities::receive::get_actor_as_person;
 use activitystreams::activity::{Dislike, Like};
-use lemmy_api_structs::{blocking, post::PostResponse};
+use lemmy_api_structs::{blocking, hide_downvotes_if_disabled, post::PostResponse};
 use lemmy_db_queries::{source::post::Post_, Likeable};
 use lemmy_db_schema::source::post::{Post, PostLike};
 use lemmy_db_views::post_view::PostView;
@@ -17,8 +17,9 @@ pub(crate) async fn receive_undo_like_post(
 
   let post_id = post.id;
   let person_id = person.id;
+  // A newer dislike stays
   blocking(context.pool(), move |conn| {
-    PostLike::remove(conn, person_id, post_id)
+    PostLike::remove_with_score(conn, person_id, post_id, 1)
   })
   .await??;
 
@@ -28,7 +29,11 @@ pub(crate) async fn receive_undo_like_post(
   })
   .await??;
 
-  let res = PostResponse { post_view };
+  let mut res = PostResponse {
+    post_view,
+    federation: None,
+  };
+  hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
   context.chat_server().do_send(SendPost {
     op: UserOperation::CreatePostLike,
@@ -49,8 +54,9 @@ pub(crate) async fn receive_undo_dislike_post(
 
   let post_id = post.id;
   let person_id = person.id;
+  // A newer like stays
   blocking(context.pool(), move |conn| {
-    PostLike::remove(conn, person_id, post_id)
+    PostLike::remove_with_score(conn, person_id, post_id, -1)
   })
   .await??;
 
@@ -60,7 +66,11 @@ pub(crate) async fn receive_undo_dislike_post(
   })
   .await??;
 
-  let res = PostResponse { post_view };
+  let mut res = PostResponse {
+    post_view,
+    federation: None,
+  };
+  hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
   context.chat_server().do_send(SendPost {
     op: UserOperation::CreatePostLike,
@@ -87,7 +97,11 @@ pub(crate) async fn receive_undo_delete_post(
   })
   .await??;
 
-  let res = PostResponse { post_view };
+  let mut res = PostResponse {
+    post_view,
+    federation: None,
+  };
+  hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
   context.chat_server().do_send(SendPost {
     op: UserOperation::EditPost,
     post: res,
@@ -113,7 +127,11 @@ pub(crate) async fn receive_undo_remove_post(
   })
   .await??;
 
-  let res = PostResponse { post_view };
+  let mut res = PostResponse {
+    post_view,
+    federation: None,
+  };
+  hide_downvotes_if_disabled(std::slice::from_mut(&mut res), context.pool()).await?;
 
   context.chat_server().do_send(SendPost {
     op: UserOperation::EditPost,
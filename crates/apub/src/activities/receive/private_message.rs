@@ -2,7 +2,7 @@ use crate::{
   activities::receive::verify_activity_domains_valid,
   check_is_apub_id_valid,
   fetcher::person::get_or_fetch_and_upsert_person,
-  inbox::get_activity_to_and_cc,
+  inbox::{get_activity_to_and_cc, is_public_collection},
   objects::FromApub,
   NoteExt,
 };
@@ -10,7 +10,6 @@ use activitystreams::{
   activity::{ActorAndObjectRefExt, Create, Delete, Undo, Update},
   base::{AsBase, ExtendsExt},
   object::AsObject,
-  public,
 };
 use anyhow::{anyhow, Context};
 use lemmy_api_structs::{blocking, person::PrivateMessageResponse};
@@ -212,7 +211,7 @@ where
   if to_and_cc.len() != 1 {
     return Err(anyhow!("Private message can only be addressed to one person").into());
   }
-  if to_and_cc.contains(&public()) {
+  if to_and_cc.iter().any(is_public_collection) {
     return Err(anyhow!("Private message cant be public").into());
   }
   let person_id = activity
@@ -1,8 +1,11 @@
 use crate::{
-  activities::send::generate_activity_id,
-  activity_queue::{send_comment_mentions, send_to_community},
+  activities::send::{
+    generate_activity_id,
+    mention::{collect_non_local_mentions, MentionsAndAddresses},
+    vote::send_vote_aggregate,
+  },
+  activity_queue::{send_to_community, send_to_mentions},
   extensions::context::lemmy_context,
-  fetcher::person::get_or_fetch_and_upsert_person,
   objects::ToApub,
   ActorType,
   ApubLikeableType,
@@ -19,61 +22,31 @@ use activitystreams::{
     Undo,
     Update,
   },
-  base::AnyBase,
-  link::Mention,
   prelude::*,
   public,
 };
-use anyhow::anyhow;
 use itertools::Itertools;
-use lemmy_api_structs::{blocking, WebFingerResponse};
-use lemmy_db_queries::{Crud, DbPool};
-use lemmy_db_schema::source::{comment::Comment, community::Community, person::Person, post::Post};
-use lemmy_utils::{
-  request::{retry, RecvError},
-  settings::structs::Settings,
-  utils::{scrape_text_for_mentions, MentionData},
-  LemmyError,
+use lemmy_api_structs::blocking;
+use lemmy_db_queries::{Crud, DbPool, Likeable};
+use lemmy_db_schema::source::{
+  comment::{Comment, CommentLike},
+  community::Community,
+  person::Person,
+  post::Post,
 };
+use lemmy_utils::LemmyError;
 use lemmy_websocket::LemmyContext;
-use log::debug;
-use reqwest::Client;
-use serde_json::Error;
-use url::Url;
 
 #[async_trait::async_trait(?Send)]
 impl ApubObjectType for Comment {
   /// Send out information about a newly created comment, to the followers of the community and
   /// mentioned persons.
-  async fn send_create(&self, creator: &Person, context: &LemmyContext) -> Result<(), LemmyError> {
-    let note = self.to_apub(context.pool()).await?;
-
-    let post_id = self.post_id;
-    let post = blocking(context.pool(), move |conn| Post::read(conn, post_id)).await??;
-
-    let community_id = post.community_id;
-    let community = blocking(context.pool(), move |conn| {
-      Community::read(conn, community_id)
-    })
-    .await??;
-
-    let maa = collect_non_local_mentions(&self, &community, context).await?;
-
-    let mut create = Create::new(
-      creator.actor_id.to_owned().into_inner(),
-      note.into_any_base()?,
-    );
-    create
-      .set_many_contexts(lemmy_context()?)
-      .set_id(generate_activity_id(CreateType::Create)?)
-      .set_to(public())
-      .set_many_ccs(maa.ccs.to_owned())
-      // Set the mention tags
-      .set_many_tags(maa.get_tags()?);
-
-    send_to_community(create.clone(), &creator, &community, context).await?;
-    send_comment_mentions(&creator, maa.inboxes, create, context).await?;
-    Ok(())
+  async fn send_create(
+    &self,
+    creator: &Person,
+    context: &LemmyContext,
+  ) -> Result<usize, LemmyError> {
+    send_create_comment(self, creator, true, context).await
   }
 
   /// Send out information about an edited post, to the followers of the community and mentioned
@@ -90,7 +63,7 @@ impl ApubObjectType for Comment {
     })
     .await??;
 
-    let maa = collect_non_local_mentions(&self, &community, context).await?;
+    let maa = collect_comment_addresses(&self, &community, context).await?;
 
     let mut update = Update::new(
       creator.actor_id.to_owned().into_inner(),
@@ -105,7 +78,7 @@ impl ApubObjectType for Comment {
       .set_many_tags(maa.get_tags()?);
 
     send_to_community(update.clone(), &creator, &community, context).await?;
-    send_comment_mentions(&creator, maa.inboxes, update, context).await?;
+    send_to_mentions(&creator, maa.inboxes, update, context).await?;
     Ok(())
   }
 
@@ -326,68 +299,151 @@ impl ApubLikeableType for Comment {
     send_to_community(undo, &creator, &community, context).await?;
     Ok(())
   }
-}
 
-struct MentionsAndAddresses {
-  ccs: Vec<Url>,
-  inboxes: Vec<Url>,
-  tags: Vec<Mention>,
-}
+  async fn send_undo_dislike(
+    &self,
+    creator: &Person,
+    context: &LemmyContext,
+  ) -> Result<(), LemmyError> {
+    let post_id = self.post_id;
+    let post = blocking(context.pool(), move |conn| Post::read(conn, post_id)).await??;
+
+    let community_id = post.community_id;
+    let community = blocking(context.pool(), move |conn| {
+      Community::read(conn, community_id)
+    })
+    .await??;
 
-impl MentionsAndAddresses {
-  fn get_tags(&self) -> Result<Vec<AnyBase>, Error> {
-    self
-      .tags
-      .iter()
-      .map(|t| t.to_owned().into_any_base())
-      .collect::<Result<Vec<AnyBase>, Error>>()
+    let mut dislike = Dislike::new(
+      creator.actor_id.to_owned().into_inner(),
+      self.ap_id.to_owned().into_inner(),
+    );
+    dislike
+      .set_many_contexts(lemmy_context()?)
+      .set_id(generate_activity_id(DislikeType::Dislike)?)
+      .set_to(public())
+      .set_many_ccs(vec![community.actor_id()]);
+
+    // Undo that fake activity
+    let mut undo = Undo::new(
+      creator.actor_id.to_owned().into_inner(),
+      dislike.into_any_base()?,
+    );
+    undo
+      .set_many_contexts(lemmy_context()?)
+      .set_id(generate_activity_id(UndoType::Undo)?)
+      .set_to(public())
+      .set_many_ccs(vec![community.actor_id()]);
+
+    send_to_community(undo, &creator, &community, context).await?;
+    Ok(())
+  }
+
+  async fn send_vote_aggregate(&self, context: &LemmyContext) -> Result<(), LemmyError> {
+    let post_id = self.post_id;
+    let comment_id = self.id;
+    let (community, score) = blocking(context.pool(), move |conn| {
+      let post = Post::read(conn, post_id)?;
+      let community = Community::read(conn, post.community_id)?;
+      let score = CommentLike::local_score(conn, comment_id)?;
+      Ok((community, score)) as Result<_, LemmyError>
+    })
+    .await??;
+
+    send_vote_aggregate(
+      self.ap_id.to_owned().into_inner(),
+      score,
+      &community,
+      context,
+    )
+    .await
   }
 }
 
 /// This takes a comment, and builds a list of to_addresses, inboxes,
 /// and mention tags, so they know where to be sent to.
 /// Addresses are the persons / addresses that go in the cc field.
-async fn collect_non_local_mentions(
+/// Like `send_create()`, but without mention tags, and without sending the comment to the inboxes
+/// of mentioned persons.
+pub async fn send_create_comment_without_mentions(
+  comment: &Comment,
+  creator: &Person,
+  context: &LemmyContext,
+) -> Result<usize, LemmyError> {
+  send_create_comment(comment, creator, false, context).await
+}
+
+async fn send_create_comment(
+  comment: &Comment,
+  creator: &Person,
+  include_mentions: bool,
+  context: &LemmyContext,
+) -> Result<usize, LemmyError> {
+  let note = comment.to_apub(context.pool()).await?;
+
+  let post_id = comment.post_id;
+  let post = blocking(context.pool(), move |conn| Post::read(conn, post_id)).await??;
+
+  let community_id = post.community_id;
+  let community = blocking(context.pool(), move |conn| {
+    Community::read(conn, community_id)
+  })
+  .await??;
+
+  let maa = if include_mentions {
+    collect_comment_addresses(comment, &community, context).await?
+  } else {
+    collect_comment_addresses_without_mentions(comment, &community, context).await?
+  };
+
+  let mut create = Create::new(
+    creator.actor_id.to_owned().into_inner(),
+    note.into_any_base()?,
+  );
+  create
+    .set_many_contexts(lemmy_context()?)
+    .set_id(generate_activity_id(CreateType::Create)?)
+    .set_to(public())
+    .set_many_ccs(maa.ccs.to_owned())
+    // Set the mention tags
+    .set_many_tags(maa.get_tags()?);
+
+  let queued = send_to_community(create.clone(), &creator, &community, context).await?;
+  Ok(queued + send_to_mentions(&creator, maa.inboxes, create, context).await?)
+}
+
+async fn collect_comment_addresses(
   comment: &Comment,
   community: &Community,
   context: &LemmyContext,
 ) -> Result<MentionsAndAddresses, LemmyError> {
   let parent_creator = get_comment_parent_creator(context.pool(), comment).await?;
-  let mut addressed_ccs = vec![community.actor_id(), parent_creator.actor_id()];
+  let mentions = collect_non_local_mentions(&comment.content, context).await?;
+
+  let mut ccs = vec![community.actor_id(), parent_creator.actor_id()];
+  ccs.extend(mentions.ccs);
   // Note: dont include community inbox here, as we send to it separately with `send_to_community()`
   let mut inboxes = vec![parent_creator.get_shared_inbox_or_inbox_url()];
+  inboxes.extend(mentions.inboxes);
 
-  // Add the mention tag
-  let mut tags = Vec::new();
-
-  // Get the person IDs for any mentions
-  let mentions = scrape_text_for_mentions(&comment.content)
-    .into_iter()
-    // Filter only the non-local ones
-    .filter(|m| !m.is_local())
-    .collect::<Vec<MentionData>>();
-
-  for mention in &mentions {
-    // TODO should it be fetching it every time?
-    if let Ok(actor_id) = fetch_webfinger_url(mention, context.client()).await {
-      debug!("mention actor_id: {}", actor_id);
-      addressed_ccs.push(actor_id.to_owned().to_string().parse()?);
-
-      let mention_person = get_or_fetch_and_upsert_person(&actor_id, context, &mut 0).await?;
-      inboxes.push(mention_person.get_shared_inbox_or_inbox_url());
-
-      let mut mention_tag = Mention::new();
-      mention_tag.set_href(actor_id).set_name(mention.full_name());
-      tags.push(mention_tag);
-    }
-  }
-
-  let inboxes = inboxes.into_iter().unique().collect();
+  Ok(MentionsAndAddresses {
+    ccs,
+    inboxes: inboxes.into_iter().unique().collect(),
+    tags: mentions.tags,
+  })
+}
 
+/// Only addresses the community and the creator of the parent post or comment.
+async fn collect_comment_addresses_without_mentions(
+  comment: &Comment,
+  community: &Community,
+  context: &LemmyContext,
+) -> Result<MentionsAndAddresses, LemmyError> {
+  let parent_creator = get_comment_parent_creator(context.pool(), comment).await?;
   Ok(MentionsAndAddresses {
-    ccs: addressed_ccs,
-    inboxes,
-    tags,
+    ccs: vec![community.actor_id(), parent_creator.actor_id()],
+    inboxes: vec![parent_creator.get_shared_inbox_or_inbox_url()],
+    tags: vec![],
   })
 }
 
@@ -408,33 +464,3 @@ async fn get_comment_parent_creator(
   };
   Ok(blocking(pool, move |conn| Person::read(conn, parent_creator_id)).await??)
 }
-
-/// Turns a person id like `@name@example.com` into an apub ID, like `https://example.com/user/name`,
-/// using webfinger.
-async fn fetch_webfinger_url(mention: &MentionData, client: &Client) -> Result<Url, LemmyError> {
-  let fetch_url = format!(
-    "{}://{}/.well-known/webfinger?resource=acct:{}@{}",
-    Settings::get().get_protocol_string(),
-    mention.domain,
-    mention.name,
-    mention.domain
-  );
-  debug!("Fetching webfinger url: {}", &fetch_url);
-
-  let response = retry(|| client.get(&fetch_url).send()).await?;
-
-  let res: WebFingerResponse = response
-    .json()
-    .await
-    .map_err(|e| RecvError(e.to_string()))?;
-
-  let link = res
-    .links
-    .iter()
-    .find(|l| l.type_.eq(&Some("application/activity+json".to_string())))
-    .ok_or_else(|| anyhow!("No application/activity+json link found."))?;
-  link
-    .href
-    .to_owned()
-    .ok_or_else(|| anyhow!("No href found.").into())
-}
@@ -1,19 +1,35 @@
 use crate::{
   activities::send::generate_activity_id,
-  activity_queue::{send_activity_single_dest, send_to_community_followers},
+  activity_queue::{
+    send_activity_multiple_dest,
+    send_activity_single_dest,
+    send_to_community_followers,
+  },
   check_is_apub_id_valid,
-  extensions::context::lemmy_context,
+  extensions::{block_extension::BlockExtension, context::lemmy_context},
   fetcher::person::get_or_fetch_and_upsert_person,
   ActorType,
+  BlockExt,
 };
 use activitystreams::{
   activity::{
-    kind::{AcceptType, AnnounceType, DeleteType, LikeType, RemoveType, UndoType},
+    kind::{
+      AcceptType,
+      AnnounceType,
+      BlockType,
+      DeleteType,
+      LikeType,
+      RejectType,
+      RemoveType,
+      UndoType,
+    },
     Accept,
     ActorAndObjectRefExt,
     Announce,
+    Block,
     Delete,
     Follow,
+    Reject,
     Remove,
     Undo,
   },
@@ -21,11 +37,15 @@ use activitystreams::{
   object::ObjectExt,
   public,
 };
+use activitystreams_ext::Ext1;
 use anyhow::Context;
 use itertools::Itertools;
 use lemmy_api_structs::blocking;
 use lemmy_db_queries::DbPool;
-use lemmy_db_schema::source::community::Community;
+use lemmy_db_schema::{
+  source::{community::Community, person::Person},
+  CommunityId,
+};
 use lemmy_db_views_actor::community_follower_view::CommunityFollowerView;
 use lemmy_utils::{location_info, LemmyError};
 use lemmy_websocket::LemmyContext;
@@ -45,6 +65,9 @@ impl ActorType for Community {
   fn private_key(&self) -> Option<String> {
     self.private_key.to_owned()
   }
+  fn community_id(&self) -> Option<CommunityId> {
+    Some(self.id)
+  }
 
   fn get_shared_inbox_or_inbox_url(&self) -> Url {
     self
@@ -95,6 +118,35 @@ impl ActorType for Community {
     Ok(())
   }
 
+  /// As a local community, reject the follow request from a remote person.
+  async fn send_reject_follow(
+    &self,
+    follow: Follow,
+    context: &LemmyContext,
+  ) -> Result<(), LemmyError> {
+    let actor_uri = follow
+      .actor()?
+      .as_single_xsd_any_uri()
+      .context(location_info!())?;
+    let person = get_or_fetch_and_upsert_person(actor_uri, context, &mut 0).await?;
+
+    let mut reject = Reject::new(
+      self.actor_id.to_owned().into_inner(),
+      follow.into_any_base()?,
+    );
+    reject
+      .set_many_contexts(lemmy_context()?)
+      .set_id(generate_activity_id(RejectType::Reject)?)
+      .set_to(person.actor_id());
+
+    send_activity_single_dest(reject, self, person.inbox_url.into(), context).await?;
+    Ok(())
+  }
+
+  async fn send_update(&self, _context: &LemmyContext) -> Result<(), LemmyError> {
+    unimplemented!()
+  }
+
   /// If the creator of a community deletes the community, send this to all followers.
   async fn send_delete(&self, context: &LemmyContext) -> Result<(), LemmyError> {
     let mut delete = Delete::new(self.actor_id(), self.actor_id());
@@ -104,7 +156,7 @@ impl ActorType for Community {
       .set_to(public())
       .set_many_ccs(vec![self.followers_url.clone().into_inner()]);
 
-    send_to_community_followers(delete, self, context).await?;
+    send_to_community_followers(delete, self, None, context).await?;
     Ok(())
   }
 
@@ -124,7 +176,7 @@ impl ActorType for Community {
       .set_to(public())
       .set_many_ccs(vec![self.followers_url.clone().into_inner()]);
 
-    send_to_community_followers(undo, self, context).await?;
+    send_to_community_followers(undo, self, None, context).await?;
     Ok(())
   }
 
@@ -137,7 +189,7 @@ impl ActorType for Community {
       .set_to(public())
       .set_many_ccs(vec![self.followers_url.clone().into_inner()]);
 
-    send_to_community_followers(remove, self, context).await?;
+    send_to_community_followers(remove, self, None, context).await?;
     Ok(())
   }
 
@@ -158,29 +210,70 @@ impl ActorType for Community {
       .set_to(public())
       .set_many_ccs(vec![self.followers_url.clone().into_inner()]);
 
-    send_to_community_followers(undo, self, context).await?;
+    send_to_community_followers(undo, self, None, context).await?;
     Ok(())
   }
 
-  /// Wraps an activity sent to the community in an announce, and then sends the announce to all
-  /// community followers.
-  async fn send_announce(
+  /// If a mod bans a person from a local community, send this to the instance of the banned person,
+  /// and to all followers so that they can also reject the person's content.
+  async fn send_block_user(
     &self,
-    activity: AnyBase,
+    blocked: &Person,
+    remove_data: bool,
     context: &LemmyContext,
   ) -> Result<(), LemmyError> {
-    let mut announce = Announce::new(self.actor_id.to_owned().into_inner(), activity);
-    announce
+    let block = create_block_user(self, blocked, remove_data)?;
+
+    send_to_community_followers(
+      block,
+      self,
+      Some(blocked.get_shared_inbox_or_inbox_url()),
+      context,
+    )
+    .await?;
+    Ok(())
+  }
+
+  /// If a mod unbans a person from a local community, undo the previous block.
+  async fn send_undo_block_user(
+    &self,
+    blocked: &Person,
+    context: &LemmyContext,
+  ) -> Result<(), LemmyError> {
+    let block = create_block_user(self, blocked, false)?;
+
+    // Undo that fake activity
+    let mut undo = Undo::new(self.actor_id(), block.into_any_base()?);
+    undo
       .set_many_contexts(lemmy_context()?)
-      .set_id(generate_activity_id(AnnounceType::Announce)?)
+      .set_id(generate_activity_id(UndoType::Undo)?)
       .set_to(public())
-      .set_many_ccs(vec![self.followers_url.clone().into_inner()]);
-
-    send_to_community_followers(announce, self, context).await?;
+      .set_many_ccs(vec![
+        self.followers_url.clone().into_inner(),
+        blocked.actor_id(),
+      ]);
 
+    send_to_community_followers(
+      undo,
+      self,
+      Some(blocked.get_shared_inbox_or_inbox_url()),
+      context,
+    )
+    .await?;
     Ok(())
   }
 
+  /// Wraps an activity sent to the community in an announce, and then sends the announce to all
+  /// community followers.
+  async fn send_announce(
+    &self,
+    activity: AnyBase,
+    context: &LemmyContext,
+  ) -> Result<usize, LemmyError> {
+    let inboxes = self.get_follower_inboxes(context.pool()).await?;
+    send_announce_to_inboxes(self, activity, inboxes, context).await
+  }
+
   /// For a given community, returns the inboxes of all followers.
   async fn get_follower_inboxes(&self, pool: &DbPool) -> Result<Vec<Url>, LemmyError> {
     let id = self.id;
@@ -191,7 +284,7 @@ impl ActorType for Community {
     .await??;
     let inboxes = follows
       .into_iter()
-      .filter(|f| !f.follower.local)
+      .filter(|f| !f.follower.local && !f.pending)
       .map(|f| f.follower.shared_inbox_url.unwrap_or(f.follower.inbox_url))
       .map(|i| i.into_inner())
       .unique()
@@ -202,3 +295,61 @@ impl ActorType for Community {
     Ok(inboxes)
   }
 }
+
+/// Answers the follow request of a remote person with an Accept or a Reject. The Follow isn't
+/// stored, so it is rebuilt from its id.
+pub async fn answer_follow_request(
+  community: &Community,
+  follower: &Person,
+  follow_activity_id: Url,
+  approve: bool,
+  context: &LemmyContext,
+) -> Result<(), LemmyError> {
+  let mut follow = Follow::new(follower.actor_id(), community.actor_id());
+  follow.set_id(follow_activity_id);
+  if approve {
+    community.send_accept_follow(follow, context).await
+  } else {
+    community.send_reject_follow(follow, context).await
+  }
+}
+
+/// Wraps an activity in an `Announce` from the community, and sends it to the given inboxes. This
+/// allows sending to only a part of the followers, eg when the same activity is announced by
+/// multiple communities.
+pub(crate) async fn send_announce_to_inboxes(
+  community: &Community,
+  activity: AnyBase,
+  inboxes: Vec<Url>,
+  context: &LemmyContext,
+) -> Result<usize, LemmyError> {
+  let mut announce = Announce::new(community.actor_id.to_owned().into_inner(), activity);
+  announce
+    .set_many_contexts(lemmy_context()?)
+    .set_id(generate_activity_id(AnnounceType::Announce)?)
+    .set_to(public())
+    .set_many_ccs(vec![community.followers_url.clone().into_inner()]);
+
+  send_activity_multiple_dest(announce, community, inboxes, context).await
+}
+
+/// Creates the `Block` activity which is used by a community to ban a person.
+fn create_block_user(
+  community: &Community,
+  blocked: &Person,
+  remove_data: bool,
+) -> Result<BlockExt, LemmyError> {
+  let mut block = Block::new(community.actor_id(), blocked.actor_id());
+  block
+    .set_many_contexts(lemmy_context()?)
+    .set_id(generate_activity_id(BlockType::Block)?)
+    .set_to(public())
+    .set_many_ccs(vec![
+      community.followers_url.clone().into_inner(),
+      blocked.actor_id(),
+    ]);
+  let ext = BlockExtension {
+    remove_data: Some(remove_data),
+  };
+  Ok(Ext1::new(block, ext))
+}
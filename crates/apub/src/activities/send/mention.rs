@@ -0,0 +1,112 @@
+use crate::{fetcher::person::get_or_fetch_and_upsert_person, ActorType};
+use activitystreams::{base::AnyBase, link::Mention, prelude::*};
+use anyhow::anyhow;
+use itertools::Itertools;
+use lemmy_api_structs::WebFingerResponse;
+use lemmy_utils::{
+  request::{retry, RecvError},
+  settings::structs::Settings,
+  utils::{scrape_text_for_mentions, MentionData},
+  LemmyError,
+};
+use lemmy_websocket::LemmyContext;
+use log::debug;
+use reqwest::Client;
+use serde_json::Error;
+use url::Url;
+
+pub(crate) struct MentionsAndAddresses {
+  pub(crate) ccs: Vec<Url>,
+  pub(crate) inboxes: Vec<Url>,
+  pub(crate) tags: Vec<Mention>,
+}
+
+impl MentionsAndAddresses {
+  pub(crate) fn get_tags(&self) -> Result<Vec<AnyBase>, Error> {
+    self
+      .tags
+      .iter()
+      .map(|t| t.to_owned().into_any_base())
+      .collect::<Result<Vec<AnyBase>, Error>>()
+  }
+}
+
+/// Resolves the remote persons mentioned in `text`, and returns their actor ids (for the cc field),
+/// their inboxes and the matching mention tags. Mentions which can't be resolved are skipped, and
+/// all of them share a single fetch budget.
+pub(crate) async fn collect_non_local_mentions(
+  text: &str,
+  context: &LemmyContext,
+) -> Result<MentionsAndAddresses, LemmyError> {
+  let mut ccs = Vec::new();
+  let mut inboxes = Vec::new();
+  let mut tags = Vec::new();
+  let mut request_counter = 0;
+
+  let mentions = scrape_text_for_mentions(text)
+    .into_iter()
+    // Filter only the non-local ones
+    .filter(|m| !m.is_local())
+    .collect::<Vec<MentionData>>();
+
+  for mention in &mentions {
+    let actor_id = match fetch_webfinger_url(mention, context.client()).await {
+      Ok(a) => a,
+      Err(e) => {
+        debug!("Failed to resolve mention {}: {}", mention.full_name(), e);
+        continue;
+      }
+    };
+    debug!("mention actor_id: {}", actor_id);
+    let mention_person =
+      match get_or_fetch_and_upsert_person(&actor_id, context, &mut request_counter).await {
+        Ok(p) => p,
+        Err(e) => {
+          debug!("Failed to fetch mentioned person {}: {}", actor_id, e);
+          continue;
+        }
+      };
+    ccs.push(actor_id.to_owned());
+    inboxes.push(mention_person.get_shared_inbox_or_inbox_url());
+
+    let mut mention_tag = Mention::new();
+    mention_tag.set_href(actor_id).set_name(mention.full_name());
+    tags.push(mention_tag);
+  }
+
+  Ok(MentionsAndAddresses {
+    ccs,
+    inboxes: inboxes.into_iter().unique().collect(),
+    tags,
+  })
+}
+
+/// Turns a person id like `@name@example.com` into an apub ID, like `https://example.com/user/name`,
+/// using webfinger.
+async fn fetch_webfinger_url(mention: &MentionData, client: &Client) -> Result<Url, LemmyError> {
+  let fetch_url = format!(
+    "{}://{}/.well-known/webfinger?resource=acct:{}@{}",
+    Settings::get().get_protocol_string(),
+    mention.domain,
+    mention.name,
+    mention.domain
+  );
+  debug!("Fetching webfinger url: {}", &fetch_url);
+
+  let response = retry(|| client.get(&fetch_url).send()).await?;
+
+  let res: WebFingerResponse = response
+    .json()
+    .await
+    .map_err(|e| RecvError(e.to_string()))?;
+
+  let link = res
+    .links
+    .iter()
+    .find(|l| l.type_.eq(&Some("application/activity+json".to_string())))
+    .ok_or_else(|| anyhow!("No application/activity+json link found."))?;
+  link
+    .href
+    .to_owned()
+    .ok_or_else(|| anyhow!("No href found.").into())
+}
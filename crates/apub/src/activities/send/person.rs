@@ -1,28 +1,46 @@
 use crate::{
   activities::send::generate_activity_id,
-  activity_queue::send_activity_single_dest,
+  activity_queue::{send_activity_multiple_dest, send_activity_single_dest},
   extensions::context::lemmy_context,
+  objects::ToApub,
   ActorType,
 };
 use activitystreams::{
   activity::{
-    kind::{FollowType, UndoType},
+    kind::{FollowType, UndoType, UpdateType},
     Follow,
     Undo,
+    Update,
   },
   base::{AnyBase, BaseExt, ExtendsExt},
   object::ObjectExt,
+  public,
 };
+use chrono::{Duration, Local};
 use lemmy_api_structs::blocking;
-use lemmy_db_queries::{ApubObject, DbPool, Followable};
+use lemmy_db_queries::{
+  source::{community::CommunityFollower_, person::Person_},
+  ApubObject,
+  Crud,
+  DbPool,
+};
 use lemmy_db_schema::source::{
-  community::{Community, CommunityFollower, CommunityFollowerForm},
+  community::{Community, CommunityFollower},
   person::Person,
 };
 use lemmy_utils::LemmyError;
 use lemmy_websocket::LemmyContext;
+use log::warn;
 use url::Url;
 
+/// A Follow which wasn't answered for this long is sent again
+const FOLLOW_RESEND_HOURS: i64 = 1;
+const FOLLOW_MAX_RESENDS: i32 = 3;
+/// How many Follows are taken from the database at once for resending
+const FOLLOW_RESEND_BATCH: i64 = 100;
+/// A Follow which wasn't answered for this long is given up, and shown as failed to the person
+const FOLLOW_FAILED_DAYS: i64 = 7;
+
 #[async_trait::async_trait(?Send)]
 impl ActorType for Person {
   fn is_local(&self) -> bool {
@@ -60,24 +78,14 @@ impl ActorType for Person {
     })
     .await??;
 
-    let community_follower_form = CommunityFollowerForm {
-      community_id: community.id,
-      person_id: self.id,
-      pending: true,
-    };
+    let community_id = community.id;
+    let person_id = self.id;
     blocking(&context.pool(), move |conn| {
-      CommunityFollower::follow(conn, &community_follower_form).ok()
+      CommunityFollower::follow_pending(conn, community_id, person_id)
     })
-    .await?;
-
-    let mut follow = Follow::new(self.actor_id.to_owned().into_inner(), community.actor_id());
-    follow
-      .set_many_contexts(lemmy_context()?)
-      .set_id(generate_activity_id(FollowType::Follow)?)
-      .set_to(community.actor_id());
+    .await??;
 
-    send_activity_single_dest(follow, self, community.inbox_url.into(), context).await?;
-    Ok(())
+    send_follow_activity(self, &community, context).await
   }
 
   async fn send_unfollow(
@@ -119,6 +127,45 @@ impl ActorType for Person {
     unimplemented!()
   }
 
+  async fn send_reject_follow(
+    &self,
+    _follow: Follow,
+    _context: &LemmyContext,
+  ) -> Result<(), LemmyError> {
+    unimplemented!()
+  }
+
+  /// Send out the changed profile of a local person to the instances of all remote communities
+  /// where they participate, so that those can update their copy.
+  async fn send_update(&self, context: &LemmyContext) -> Result<(), LemmyError> {
+    let person = self.to_apub(context.pool()).await?;
+
+    let person_id = self.id;
+    let communities = blocking(context.pool(), move |conn| {
+      Person::remote_communities_participated_in(conn, person_id)
+    })
+    .await??;
+
+    let mut update = Update::new(self.actor_id(), person.into_any_base()?);
+    update
+      .set_many_contexts(lemmy_context()?)
+      .set_id(generate_activity_id(UpdateType::Update)?)
+      .set_to(public())
+      .set_many_ccs(
+        communities
+          .iter()
+          .map(|c| c.actor_id())
+          .collect::<Vec<Url>>(),
+      );
+
+    let inboxes = communities
+      .iter()
+      .map(|c| c.get_shared_inbox_or_inbox_url())
+      .collect();
+    send_activity_multiple_dest(update, self, inboxes, context).await?;
+    Ok(())
+  }
+
   async fn send_delete(&self, _context: &LemmyContext) -> Result<(), LemmyError> {
     unimplemented!()
   }
@@ -135,11 +182,28 @@ impl ActorType for Person {
     unimplemented!()
   }
 
+  async fn send_block_user(
+    &self,
+    _blocked: &Person,
+    _remove_data: bool,
+    _context: &LemmyContext,
+  ) -> Result<(), LemmyError> {
+    unimplemented!()
+  }
+
+  async fn send_undo_block_user(
+    &self,
+    _blocked: &Person,
+    _context: &LemmyContext,
+  ) -> Result<(), LemmyError> {
+    unimplemented!()
+  }
+
   async fn send_announce(
     &self,
     _activity: AnyBase,
     _context: &LemmyContext,
-  ) -> Result<(), LemmyError> {
+  ) -> Result<usize, LemmyError> {
     unimplemented!()
   }
 
@@ -147,3 +211,75 @@ impl ActorType for Person {
     unimplemented!()
   }
 }
+
+async fn send_follow_activity(
+  person: &Person,
+  community: &Community,
+  context: &LemmyContext,
+) -> Result<(), LemmyError> {
+  let mut follow = Follow::new(person.actor_id(), community.actor_id());
+  follow
+    .set_many_contexts(lemmy_context()?)
+    .set_id(generate_activity_id(FollowType::Follow)?)
+    .set_to(community.actor_id());
+
+  send_activity_single_dest(
+    follow,
+    person,
+    community.inbox_url.to_owned().into(),
+    context,
+  )
+  .await?;
+  Ok(())
+}
+
+/// Sends the Follow again for follows of remote communities which weren't accepted within an
+/// hour, as the Follow or the Accept might have been lost. After a week without an Accept, the
+/// follows are marked as failed, and can only be retried by the person following again.
+pub async fn resend_pending_follows(context: &LemmyContext) -> Result<(), LemmyError> {
+  let resend_before = Local::now().naive_local() - Duration::hours(FOLLOW_RESEND_HOURS);
+  loop {
+    let follows = blocking(context.pool(), move |conn| {
+      CommunityFollower::take_for_resend(
+        conn,
+        resend_before,
+        FOLLOW_MAX_RESENDS,
+        FOLLOW_RESEND_BATCH,
+      )
+    })
+    .await??;
+    let last_batch = (follows.len() as i64) < FOLLOW_RESEND_BATCH;
+
+    for follow in follows {
+      let (person_id, community_id) = (follow.person_id, follow.community_id);
+      let actors = blocking(context.pool(), move |conn| {
+        Ok((
+          Person::read(conn, person_id)?,
+          Community::read(conn, community_id)?,
+        )) as Result<_, diesel::result::Error>
+      })
+      .await?;
+      let sent = match actors {
+        Ok((person, community)) => send_follow_activity(&person, &community, context).await,
+        Err(e) => Err(e.into()),
+      };
+      if let Err(e) = sent {
+        warn!(
+          "Failed to resend follow of community {} by person {}: {}",
+          community_id, person_id, e
+        );
+      }
+    }
+
+    if last_batch {
+      break;
+    }
+  }
+
+  let failed_before = Local::now().naive_local() - Duration::days(FOLLOW_FAILED_DAYS);
+  blocking(context.pool(), move |conn| {
+    CommunityFollower::mark_failed(conn, failed_before)
+  })
+  .await??;
+  Ok(())
+}
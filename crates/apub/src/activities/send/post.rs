@@ -1,6 +1,10 @@
 use crate::{
-  activities::send::generate_activity_id,
-  activity_queue::send_to_community,
+  activities::send::{
+    generate_activity_id,
+    mention::collect_non_local_mentions,
+    vote::send_vote_aggregate,
+  },
+  activity_queue::{send_to_community, send_to_mentions},
   extensions::context::lemmy_context,
   objects::ToApub,
   ActorType,
@@ -22,15 +26,29 @@ use activitystreams::{
   public,
 };
 use lemmy_api_structs::blocking;
-use lemmy_db_queries::Crud;
-use lemmy_db_schema::source::{community::Community, person::Person, post::Post};
+use lemmy_db_queries::{Crud, Likeable};
+use lemmy_db_schema::source::{
+  community::Community,
+  person::Person,
+  post::{Post, PostLike},
+};
 use lemmy_utils::LemmyError;
 use lemmy_websocket::LemmyContext;
 
+/// Posts which wait for the approval of a mod are only federated once they are approved, so all
+/// activities about them are skipped until then.
 #[async_trait::async_trait(?Send)]
 impl ApubObjectType for Post {
-  /// Send out information about a newly created post, to the followers of the community.
-  async fn send_create(&self, creator: &Person, context: &LemmyContext) -> Result<(), LemmyError> {
+  /// Send out information about a newly created post, to the followers of the community and
+  /// mentioned persons.
+  async fn send_create(
+    &self,
+    creator: &Person,
+    context: &LemmyContext,
+  ) -> Result<usize, LemmyError> {
+    if self.is_awaiting_approval() {
+      return Ok(0);
+    }
     let page = self.to_apub(context.pool()).await?;
 
     let community_id = self.community_id;
@@ -39,6 +57,11 @@ impl ApubObjectType for Post {
     })
     .await??;
 
+    let body = self.body.to_owned().unwrap_or_default();
+    let mentions = collect_non_local_mentions(&body, context).await?;
+    let mut ccs = vec![community.actor_id()];
+    ccs.extend(mentions.ccs.to_owned());
+
     let mut create = Create::new(
       creator.actor_id.to_owned().into_inner(),
       page.into_any_base()?,
@@ -47,14 +70,20 @@ impl ApubObjectType for Post {
       .set_many_contexts(lemmy_context()?)
       .set_id(generate_activity_id(CreateType::Create)?)
       .set_to(public())
-      .set_many_ccs(vec![community.actor_id()]);
+      .set_many_ccs(ccs)
+      // Set the mention tags
+      .set_many_tags(mentions.get_tags()?);
 
-    send_to_community(create, creator, &community, context).await?;
-    Ok(())
+    let queued = send_to_community(create.clone(), creator, &community, context).await?;
+    Ok(queued + send_to_mentions(creator, mentions.inboxes, create, context).await?)
   }
 
-  /// Send out information about an edited post, to the followers of the community.
+  /// Send out information about an edited post, to the followers of the community and mentioned
+  /// persons.
   async fn send_update(&self, creator: &Person, context: &LemmyContext) -> Result<(), LemmyError> {
+    if self.is_awaiting_approval() {
+      return Ok(());
+    }
     let page = self.to_apub(context.pool()).await?;
 
     let community_id = self.community_id;
@@ -63,6 +92,11 @@ impl ApubObjectType for Post {
     })
     .await??;
 
+    let body = self.body.to_owned().unwrap_or_default();
+    let mentions = collect_non_local_mentions(&body, context).await?;
+    let mut ccs = vec![community.actor_id()];
+    ccs.extend(mentions.ccs.to_owned());
+
     let mut update = Update::new(
       creator.actor_id.to_owned().into_inner(),
       page.into_any_base()?,
@@ -71,13 +105,19 @@ impl ApubObjectType for Post {
       .set_many_contexts(lemmy_context()?)
       .set_id(generate_activity_id(UpdateType::Update)?)
       .set_to(public())
-      .set_many_ccs(vec![community.actor_id()]);
+      .set_many_ccs(ccs)
+      // Set the mention tags
+      .set_many_tags(mentions.get_tags()?);
 
-    send_to_community(update, creator, &community, context).await?;
+    send_to_community(update.clone(), creator, &community, context).await?;
+    send_to_mentions(creator, mentions.inboxes, update, context).await?;
     Ok(())
   }
 
   async fn send_delete(&self, creator: &Person, context: &LemmyContext) -> Result<(), LemmyError> {
+    if self.is_awaiting_approval() {
+      return Ok(());
+    }
     let community_id = self.community_id;
     let community = blocking(context.pool(), move |conn| {
       Community::read(conn, community_id)
@@ -103,6 +143,9 @@ impl ApubObjectType for Post {
     creator: &Person,
     context: &LemmyContext,
   ) -> Result<(), LemmyError> {
+    if self.is_awaiting_approval() {
+      return Ok(());
+    }
     let community_id = self.community_id;
     let community = blocking(context.pool(), move |conn| {
       Community::read(conn, community_id)
@@ -135,6 +178,9 @@ impl ApubObjectType for Post {
   }
 
   async fn send_remove(&self, mod_: &Person, context: &LemmyContext) -> Result<(), LemmyError> {
+    if self.is_awaiting_approval() {
+      return Ok(());
+    }
     let community_id = self.community_id;
     let community = blocking(context.pool(), move |conn| {
       Community::read(conn, community_id)
@@ -160,6 +206,9 @@ impl ApubObjectType for Post {
     mod_: &Person,
     context: &LemmyContext,
   ) -> Result<(), LemmyError> {
+    if self.is_awaiting_approval() {
+      return Ok(());
+    }
     let community_id = self.community_id;
     let community = blocking(context.pool(), move |conn| {
       Community::read(conn, community_id)
@@ -195,6 +244,9 @@ impl ApubObjectType for Post {
 #[async_trait::async_trait(?Send)]
 impl ApubLikeableType for Post {
   async fn send_like(&self, creator: &Person, context: &LemmyContext) -> Result<(), LemmyError> {
+    if self.is_awaiting_approval() {
+      return Ok(());
+    }
     let community_id = self.community_id;
     let community = blocking(context.pool(), move |conn| {
       Community::read(conn, community_id)
@@ -216,6 +268,9 @@ impl ApubLikeableType for Post {
   }
 
   async fn send_dislike(&self, creator: &Person, context: &LemmyContext) -> Result<(), LemmyError> {
+    if self.is_awaiting_approval() {
+      return Ok(());
+    }
     let community_id = self.community_id;
     let community = blocking(context.pool(), move |conn| {
       Community::read(conn, community_id)
@@ -241,6 +296,9 @@ impl ApubLikeableType for Post {
     creator: &Person,
     context: &LemmyContext,
   ) -> Result<(), LemmyError> {
+    if self.is_awaiting_approval() {
+      return Ok(());
+    }
     let community_id = self.community_id;
     let community = blocking(context.pool(), move |conn| {
       Community::read(conn, community_id)
@@ -271,4 +329,65 @@ impl ApubLikeableType for Post {
     send_to_community(undo, &creator, &community, context).await?;
     Ok(())
   }
+
+  async fn send_undo_dislike(
+    &self,
+    creator: &Person,
+    context: &LemmyContext,
+  ) -> Result<(), LemmyError> {
+    if self.is_awaiting_approval() {
+      return Ok(());
+    }
+    let community_id = self.community_id;
+    let community = blocking(context.pool(), move |conn| {
+      Community::read(conn, community_id)
+    })
+    .await??;
+
+    let mut dislike = Dislike::new(
+      creator.actor_id.to_owned().into_inner(),
+      self.ap_id.to_owned().into_inner(),
+    );
+    dislike
+      .set_many_contexts(lemmy_context()?)
+      .set_id(generate_activity_id(DislikeType::Dislike)?)
+      .set_to(public())
+      .set_many_ccs(vec![community.actor_id()]);
+
+    // Undo that fake activity
+    let mut undo = Undo::new(
+      creator.actor_id.to_owned().into_inner(),
+      dislike.into_any_base()?,
+    );
+    undo
+      .set_many_contexts(lemmy_context()?)
+      .set_id(generate_activity_id(UndoType::Undo)?)
+      .set_to(public())
+      .set_many_ccs(vec![community.actor_id()]);
+
+    send_to_community(undo, &creator, &community, context).await?;
+    Ok(())
+  }
+
+  async fn send_vote_aggregate(&self, context: &LemmyContext) -> Result<(), LemmyError> {
+    if self.is_awaiting_approval() {
+      return Ok(());
+    }
+    let community_id = self.community_id;
+    let post_id = self.id;
+    let (community, score) = blocking(context.pool(), move |conn| {
+      let community = Community::read(conn, community_id)?;
+      let score = PostLike::local_score(conn, post_id)?;
+      Ok((community, score)) as Result<_, LemmyError>
+    })
+    .await??;
+
+    send_vote_aggregate(
+      self.ap_id.to_owned().into_inner(),
+      score,
+      &community,
+      context,
+    )
+    .await
+  }
 }
@@ -25,7 +25,11 @@ use lemmy_websocket::LemmyContext;
 #[async_trait::async_trait(?Send)]
 impl ApubObjectType for PrivateMessage {
   /// Send out information about a newly created private message
-  async fn send_create(&self, creator: &Person, context: &LemmyContext) -> Result<(), LemmyError> {
+  async fn send_create(
+    &self,
+    creator: &Person,
+    context: &LemmyContext,
+  ) -> Result<usize, LemmyError> {
     let note = self.to_apub(context.pool()).await?;
 
     let recipient_id = self.recipient_id;
@@ -42,8 +46,7 @@ impl ApubObjectType for PrivateMessage {
       .set_id(generate_activity_id(CreateType::Create)?)
       .set_to(recipient.actor_id());
 
-    send_activity_single_dest(create, creator, recipient.inbox_url.into(), context).await?;
-    Ok(())
+    send_activity_single_dest(create, creator, recipient.inbox_url.into(), context).await
   }
 
   /// Send out information about an edited private message, to the followers of the community.
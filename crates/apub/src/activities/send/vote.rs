@@ -0,0 +1,134 @@
+use crate::{
+  activities::send::generate_activity_id,
+  activity_queue::send_to_community,
+  extensions::{
+    context::lemmy_context,
+    vote_extension::{VoteExtension, VOTE_ACTOR_NAME},
+  },
+  generate_apub_endpoint,
+  generate_inbox_url,
+  generate_shared_inbox_url,
+  ActorType,
+  DislikeExt,
+  EndpointType,
+  LikeExt,
+};
+use activitystreams::{
+  activity::{
+    kind::{DislikeType, LikeType, UndoType},
+    Dislike,
+    Like,
+    Undo,
+  },
+  prelude::*,
+  public,
+};
+use activitystreams_ext::Ext1;
+use lemmy_api_structs::blocking;
+use lemmy_db_queries::{source::person::Person_, Crud, DbPool};
+use lemmy_db_schema::source::{
+  community::Community,
+  person::{Person, PersonForm},
+};
+use lemmy_utils::{apub::generate_actor_keypair, LemmyError};
+use lemmy_websocket::LemmyContext;
+use url::Url;
+
+/// Returns the service actor of the instance for aggregated votes, and creates it on first use.
+async fn get_or_create_vote_actor(pool: &DbPool) -> Result<Person, LemmyError> {
+  let existing = blocking(pool, move |conn| {
+    Person::find_by_name(conn, VOTE_ACTOR_NAME).ok()
+  })
+  .await?;
+  if let Some(actor) = existing {
+    return Ok(actor);
+  }
+
+  let actor_keypair = generate_actor_keypair()?;
+  let actor_id = generate_apub_endpoint(EndpointType::Person, VOTE_ACTOR_NAME)?;
+  let person_form = PersonForm {
+    name: VOTE_ACTOR_NAME.to_string(),
+    avatar: None,
+    banner: None,
+    preferred_username: None,
+    published: None,
+    updated: None,
+    banned: None,
+    deleted: None,
+    actor_id: Some(actor_id.clone()),
+    bio: Some(Some(
+      "Sends the combined votes of the users of this instance.".to_string(),
+    )),
+    local: Some(true),
+    private_key: Some(Some(actor_keypair.private_key)),
+    public_key: Some(Some(actor_keypair.public_key)),
+    last_refreshed_at: None,
+    inbox_url: Some(generate_inbox_url(&actor_id)?),
+    shared_inbox_url: Some(Some(generate_shared_inbox_url(&actor_id)?)),
+  };
+  // A concurrent vote may have created the actor in the meantime
+  let actor = blocking(pool, move |conn| {
+    Person::create(conn, &person_form).or_else(|_| Person::find_by_name(conn, VOTE_ACTOR_NAME))
+  })
+  .await??;
+  Ok(actor)
+}
+
+/// Federates the summed `score` of all local votes on the post or comment `object_id` from the
+/// service actor of the instance, instead of the individual votes. A positive score is sent as a
+/// `Like` and a negative one as a `Dislike`, each with the number of votes in `count`. A score of
+/// zero undoes the last aggregate. Receivers replace the previous aggregate of the instance, so
+/// the latest activity always reflects the current state.
+///
+/// Instances which don't understand the `count` extension see a single vote from the service
+/// actor, so for them all users of this instance together count as one vote in the direction of
+/// the summed score.
+pub(crate) async fn send_vote_aggregate(
+  object_id: Url,
+  score: i64,
+  community: &Community,
+  context: &LemmyContext,
+) -> Result<(), LemmyError> {
+  let actor = get_or_create_vote_actor(context.pool()).await?;
+  let ext = VoteExtension {
+    count: Some(score.abs().min(i32::MAX.into()) as i32),
+  };
+
+  if score > 0 {
+    let mut like = Like::new(actor.actor_id(), object_id);
+    like
+      .set_many_contexts(lemmy_context()?)
+      .set_id(generate_activity_id(LikeType::Like)?)
+      .set_to(public())
+      .set_many_ccs(vec![community.actor_id()]);
+    let like: LikeExt = Ext1::new(like, ext);
+    send_to_community(like, &actor, community, context).await?;
+  } else if score < 0 {
+    let mut dislike = Dislike::new(actor.actor_id(), object_id);
+    dislike
+      .set_many_contexts(lemmy_context()?)
+      .set_id(generate_activity_id(DislikeType::Dislike)?)
+      .set_to(public())
+      .set_many_ccs(vec![community.actor_id()]);
+    let dislike: DislikeExt = Ext1::new(dislike, ext);
+    send_to_community(dislike, &actor, community, context).await?;
+  } else {
+    // Receivers remove the vote of the actor for both undone likes and dislikes
+    let mut like = Like::new(actor.actor_id(), object_id);
+    like
+      .set_many_contexts(lemmy_context()?)
+      .set_id(generate_activity_id(LikeType::Like)?)
+      .set_to(public())
+      .set_many_ccs(vec![community.actor_id()]);
+
+    // Undo that fake activity
+    let mut undo = Undo::new(actor.actor_id(), like.into_any_base()?);
+    undo
+      .set_many_contexts(lemmy_context()?)
+      .set_id(generate_activity_id(UndoType::Undo)?)
+      .set_to(public())
+      .set_many_ccs(vec![community.actor_id()]);
+    send_to_community(undo, &actor, community, context).await?;
+  }
+  Ok(())
+}
@@ -2,6 +2,7 @@ use crate::{
   check_is_apub_id_valid,
   extensions::signatures::sign_and_send,
   insert_activity,
+  log_community_federation,
   ActorType,
   APUB_JSON_CONTENT_TYPE,
 };
@@ -21,7 +22,14 @@ use background_jobs::{
 };
 use itertools::Itertools;
 use lemmy_db_queries::DbPool;
-use lemmy_db_schema::source::{community::Community, person::Person};
+use lemmy_db_schema::{
+  source::{
+    community::Community,
+    community_federation_log::CommunityFederationLogForm,
+    person::Person,
+  },
+  CommunityId,
+};
 use lemmy_utils::{location_info, settings::structs::Settings, LemmyError};
 use lemmy_websocket::LemmyContext;
 use log::{debug, warn};
@@ -35,12 +43,14 @@ use url::Url;
 /// * `activity` the apub activity to be sent
 /// * `creator` the local actor which created the activity
 /// * `inbox` the inbox url where the activity should be delivered to
+///
+/// Returns the number of deliveries which were queued, like all functions sending activities.
 pub(crate) async fn send_activity_single_dest<T, Kind>(
   activity: T,
   creator: &dyn ActorType,
   inbox: Url,
   context: &LemmyContext,
-) -> Result<(), LemmyError>
+) -> Result<usize, LemmyError>
 where
   T: AsObject<Kind> + Extends<Kind> + Debug + BaseExt<Kind>,
   Kind: Serialize,
@@ -57,25 +67,67 @@ where
       activity,
       creator,
       vec![inbox],
+      creator.community_id(),
       context.pool(),
       true,
       true,
     )
-    .await?;
+    .await
+  } else {
+    Ok(0)
   }
+}
 
-  Ok(())
+/// Sends a local activity to multiple remote inboxes, for example to the instances of all
+/// communities a person participates in.
+///
+/// * `activity` the apub activity to be sent
+/// * `creator` the local actor which created the activity
+/// * `inboxes` the inbox urls where the activity should be delivered to
+pub(crate) async fn send_activity_multiple_dest<T, Kind>(
+  activity: T,
+  creator: &dyn ActorType,
+  inboxes: Vec<Url>,
+  context: &LemmyContext,
+) -> Result<usize, LemmyError>
+where
+  T: AsObject<Kind> + Extends<Kind> + Debug + BaseExt<Kind>,
+  Kind: Serialize,
+  <T as Extends<Kind>>::Error: From<serde_json::Error> + Send + Sync + 'static,
+{
+  debug!(
+    "Sending activity {:?} to {:?}",
+    &activity.id_unchecked(),
+    &inboxes
+  );
+  let inboxes = inboxes
+    .into_iter()
+    .filter(|inbox| check_is_apub_id_valid(inbox).is_ok())
+    .unique()
+    .collect();
+  send_activity_internal(
+    context.activity_queue(),
+    activity,
+    creator,
+    inboxes,
+    creator.community_id(),
+    context.pool(),
+    true,
+    false,
+  )
+  .await
 }
 
 /// From a local community, send activity to all remote followers.
 ///
 /// * `activity` the apub activity to send
 /// * `community` the sending community
-/// * `sender_shared_inbox` in case of an announce, this should be the shared inbox of the inner
-///                         activities creator, as receiving a known activity will cause an error
+/// * `extra_inbox` optional inbox of a remote actor which should receive the activity even if it
+///                 doesnt follow the community (eg a person who was banned from the community)
 pub(crate) async fn send_to_community_followers<T, Kind>(
   activity: T,
   community: &Community,
+  extra_inbox: Option<Url>,
   context: &LemmyContext,
 ) -> Result<(), LemmyError>
 where
@@ -83,9 +135,11 @@ where
   Kind: Serialize,
   <T as Extends<Kind>>::Error: From<serde_json::Error> + Send + Sync + 'static,
 {
-  let follower_inboxes: Vec<Url> = community
-    .get_follower_inboxes(context.pool())
-    .await?
+  let mut inboxes = community.get_follower_inboxes(context.pool()).await?;
+  if let Some(extra_inbox) = extra_inbox {
+    inboxes.push(extra_inbox);
+  }
+  let follower_inboxes: Vec<Url> = inboxes
     .iter()
     .unique()
     .filter(|inbox| inbox.host_str() != Some(&Settings::get().hostname()))
@@ -103,6 +157,7 @@ where
     activity,
     community,
     follower_inboxes,
+    Some(community.id),
     context.pool(),
     true,
     false,
@@ -123,7 +178,7 @@ pub(crate) async fn send_to_community<T, Kind>(
   creator: &Person,
   community: &Community,
   context: &LemmyContext,
-) -> Result<(), LemmyError>
+) -> Result<usize, LemmyError>
 where
   T: AsObject<Kind> + Extends<Kind> + Debug + BaseExt<Kind>,
   Kind: Serialize,
@@ -133,7 +188,7 @@ where
   if community.local {
     community
       .send_announce(activity.into_any_base()?, context)
-      .await?;
+      .await
   } else {
     let inbox = community.get_shared_inbox_or_inbox_url();
     check_is_apub_id_valid(&inbox)?;
@@ -147,27 +202,26 @@ where
       activity,
       creator,
       vec![inbox],
+      Some(community.id),
       context.pool(),
       true,
       false,
     )
-    .await?;
+    .await
   }
-
-  Ok(())
 }
 
-/// Sends notification to any persons mentioned in a comment
+/// Sends notification to any persons mentioned in a comment or post
 ///
-/// * `creator` person who created the comment
-/// * `mentions` list of inboxes of persons which are mentioned in the comment
-/// * `activity` either a `Create/Note` or `Update/Note`
-pub(crate) async fn send_comment_mentions<T, Kind>(
+/// * `creator` person who created the comment or post
+/// * `mentions` list of inboxes of persons which are mentioned in the comment or post
+/// * `activity` either a `Create` or `Update` of a `Note` or `Page`
+pub(crate) async fn send_to_mentions<T, Kind>(
   creator: &Person,
   mentions: Vec<Url>,
   activity: T,
   context: &LemmyContext,
-) -> Result<(), LemmyError>
+) -> Result<usize, LemmyError>
 where
   T: AsObject<Kind> + Extends<Kind> + Debug + BaseExt<Kind>,
   Kind: Serialize,
@@ -188,35 +242,38 @@ where
     activity,
     creator,
     mentions,
+    None,
     context.pool(),
     false, // Don't create a new DB row
     false,
   )
-  .await?;
-  Ok(())
+  .await
 }
 
 /// Create new `SendActivityTasks`, which will deliver the given activity to inboxes, as well as
 /// handling signing and retrying failed deliveres.
 ///
 /// The caller of this function needs to remove any blocked domains from `to`,
-/// using `check_is_apub_id_valid()`.
+/// using `check_is_apub_id_valid()`. If `community_id` is set, the deliveries are recorded in the
+/// federation log of that community. Returns the number of deliveries which were queued.
+#[allow(clippy::too_many_arguments)]
 async fn send_activity_internal<T, Kind>(
   activity_sender: &QueueHandle,
   activity: T,
   actor: &dyn ActorType,
   inboxes: Vec<Url>,
+  community_id: Option<CommunityId>,
   pool: &DbPool,
   insert_into_db: bool,
   sensitive: bool,
-) -> Result<(), LemmyError>
+) -> Result<usize, LemmyError>
 where
   T: AsObject<Kind> + Extends<Kind> + Debug,
   Kind: Serialize,
   <T as Extends<Kind>>::Error: From<serde_json::Error> + Send + Sync + 'static,
 {
   if !Settings::get().federation().enabled || inboxes.is_empty() {
-    return Ok(());
+    return Ok(0);
   }
 
   // Don't send anything to ourselves
@@ -228,6 +285,7 @@ where
 
   let activity = activity.into_any_base()?;
   let serialised_activity = serde_json::to_string(&activity)?;
+  let kind = activity.kind_str().map(|k| k.to_owned());
 
   // This is necessary because send_comment and send_comment_mentions
   // might send the same ap_id
@@ -236,21 +294,24 @@ where
     insert_activity(id, activity.clone(), true, sensitive, pool).await?;
   }
 
+  let queued = inboxes.len();
   for i in inboxes {
     let message = SendActivityTask {
       activity: serialised_activity.to_owned(),
       inbox: i.to_owned(),
       actor_id: actor.actor_id(),
       private_key: actor.private_key().context(location_info!())?,
+      community_id,
+      kind: kind.to_owned(),
     };
     if env::var("LEMMY_TEST_SEND_SYNC").is_ok() {
-      do_send(message, &Client::default()).await?;
+      do_send(message, &Client::default(), pool).await?;
     } else {
       activity_sender.queue::<SendActivityTask>(message)?;
     }
   }
 
-  Ok(())
+  Ok(queued)
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -259,6 +320,8 @@ struct SendActivityTask {
   inbox: Url,
   actor_id: Url,
   private_key: String,
+  community_id: Option<CommunityId>,
+  kind: Option<String>,
 }
 
 /// Signs the activity with the sending actor's key, and delivers to the given inbox. Also retries
@@ -272,11 +335,11 @@ impl ActixJob for SendActivityTask {
   const BACKOFF: Backoff = Backoff::Exponential(2);
 
   fn run(self, state: Self::State) -> Self::Future {
-    Box::pin(async move { do_send(self, &state.client).await })
+    Box::pin(async move { do_send(self, &state.client, &state.pool).await })
   }
 }
 
-async fn do_send(task: SendActivityTask, client: &Client) -> Result<(), Error> {
+async fn do_send(task: SendActivityTask, client: &Client, pool: &DbPool) -> Result<(), Error> {
   let mut headers = BTreeMap::<String, String>::new();
   headers.insert("Content-Type".into(), APUB_JSON_CONTENT_TYPE.to_string());
   let result = sign_and_send(
@@ -289,6 +352,17 @@ async fn do_send(task: SendActivityTask, client: &Client) -> Result<(), Error> {
   )
   .await;
 
+  if let Some(community_id) = task.community_id {
+    let form = CommunityFederationLogForm {
+      community_id,
+      inbound: false,
+      kind: task.kind.to_owned(),
+      domain: task.inbox.host_str().unwrap_or_default().to_owned(),
+      error: result.as_ref().err().map(|e| e.to_string()),
+    };
+    log_community_federation(form, pool).await;
+  }
+
   if let Err(e) = result {
     warn!("{}", e);
     return Err(anyhow!(
@@ -300,13 +374,16 @@ async fn do_send(task: SendActivityTask, client: &Client) -> Result<(), Error> {
   Ok(())
 }
 
-pub fn create_activity_queue() -> QueueHandle {
+/// Starts the queue which delivers outgoing activities. The database pool is used to record
+/// deliveries in the community federation logs.
+pub fn create_activity_queue(pool: DbPool) -> QueueHandle {
   // Start the application server. This guards access to to the jobs store
   let queue_handle = create_server(Storage::new());
 
   // Configure and start our workers
-  WorkerConfig::new(|| MyState {
+  WorkerConfig::new(move || MyState {
     client: Client::default(),
+    pool: pool.clone(),
   })
   .register::<SendActivityTask>()
   .start(queue_handle.clone());
@@ -317,4 +394,5 @@ pub fn create_activity_queue() -> QueueHandle {
 #[derive(Clone)]
 struct MyState {
   pub client: Client,
+  pub pool: DbPool,
 }
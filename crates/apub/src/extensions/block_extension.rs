@@ -0,0 +1,29 @@
+use activitystreams::unparsed::UnparsedMutExt;
+use activitystreams_ext::UnparsedExtension;
+use serde::{Deserialize, Serialize};
+
+/// Activitystreams extension to allow (de)serializing the `removeData` flag of a `Block`, which
+/// tells the receiving instance to also remove the posts and comments of the banned person.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockExtension {
+  pub remove_data: Option<bool>,
+}
+
+impl<U> UnparsedExtension<U> for BlockExtension
+where
+  U: UnparsedMutExt,
+{
+  type Error = serde_json::Error;
+
+  fn try_from_unparsed(unparsed_mut: &mut U) -> Result<Self, Self::Error> {
+    Ok(BlockExtension {
+      remove_data: unparsed_mut.remove("removeData")?,
+    })
+  }
+
+  fn try_into_unparsed(self, unparsed_mut: &mut U) -> Result<(), Self::Error> {
+    unparsed_mut.insert("removeData", self.remove_data)?;
+    Ok(())
+  }
+}
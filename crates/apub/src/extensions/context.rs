@@ -8,6 +8,8 @@ pub(crate) fn lemmy_context() -> Result<Vec<AnyBase>, LemmyError> {
     "sc": "http://schema.org#",
     "sensitive": "as:sensitive",
     "stickied": "as:stickied",
+    "removeData": "as:removeData",
+    "downvotesDisabled": "as:downvotesDisabled",
     "comments_enabled": {
       "kind": "sc:Boolean",
       "id": "pt:commentsEnabled"
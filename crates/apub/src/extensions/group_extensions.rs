@@ -2,19 +2,35 @@ use activitystreams::unparsed::UnparsedMutExt;
 use activitystreams_ext::UnparsedExtension;
 use lemmy_utils::LemmyError;
 use serde::{Deserialize, Serialize};
+use url::Url;
 
-/// Activitystreams extension to allow (de)serializing additional Community field
-/// `sensitive` (called 'nsfw' in Lemmy).
+/// Activitystreams extension to allow (de)serializing additional Community fields
+/// `sensitive` (called 'nsfw' in Lemmy), `downvotesDisabled`, `themeColor`, `shortDescription`
+/// and `modlog`, the url of the public modlog collection.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GroupExtension {
   pub sensitive: Option<bool>,
+  pub downvotes_disabled: Option<bool>,
+  pub theme_color: Option<String>,
+  pub short_description: Option<String>,
+  pub modlog: Option<Url>,
 }
 
 impl GroupExtension {
-  pub fn new(sensitive: bool) -> Result<GroupExtension, LemmyError> {
+  pub fn new(
+    sensitive: bool,
+    downvotes_disabled: bool,
+    theme_color: Option<String>,
+    short_description: Option<String>,
+    modlog: Option<Url>,
+  ) -> Result<GroupExtension, LemmyError> {
     Ok(GroupExtension {
       sensitive: Some(sensitive),
+      downvotes_disabled: Some(downvotes_disabled),
+      theme_color,
+      short_description,
+      modlog,
     })
   }
 }
@@ -28,11 +44,19 @@ where
   fn try_from_unparsed(unparsed_mut: &mut U) -> Result<Self, Self::Error> {
     Ok(GroupExtension {
       sensitive: unparsed_mut.remove("sensitive")?,
+      downvotes_disabled: unparsed_mut.remove("downvotesDisabled")?,
+      theme_color: unparsed_mut.remove("themeColor")?,
+      short_description: unparsed_mut.remove("shortDescription")?,
+      modlog: unparsed_mut.remove("modlog")?,
     })
   }
 
   fn try_into_unparsed(self, unparsed_mut: &mut U) -> Result<(), Self::Error> {
     unparsed_mut.insert("sensitive", self.sensitive)?;
+    unparsed_mut.insert("downvotesDisabled", self.downvotes_disabled)?;
+    unparsed_mut.insert("themeColor", self.theme_color)?;
+    unparsed_mut.insert("shortDescription", self.short_description)?;
+    unparsed_mut.insert("modlog", self.modlog)?;
     Ok(())
   }
 }
@@ -1,4 +1,8 @@
+pub(crate) mod block_extension;
 pub(crate) mod context;
 pub(crate) mod group_extensions;
+pub(crate) mod note_extension;
 pub(crate) mod page_extension;
+pub(crate) mod person_extension;
 pub(crate) mod signatures;
+pub(crate) mod vote_extension;
@@ -0,0 +1,31 @@
+use activitystreams::unparsed::UnparsedMutExt;
+use activitystreams_ext::UnparsedExtension;
+use serde::{Deserialize, Serialize};
+
+/// Activitystreams extension to allow (de)serializing the additional Comment field `stickied`.
+/// Private messages are also notes, but don't have it.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteExtension {
+  pub stickied: Option<bool>,
+}
+
+impl<U> UnparsedExtension<U> for NoteExtension
+where
+  U: UnparsedMutExt,
+{
+  type Error = serde_json::Error;
+
+  fn try_from_unparsed(unparsed_mut: &mut U) -> Result<Self, Self::Error> {
+    Ok(NoteExtension {
+      stickied: unparsed_mut.remove("stickied")?,
+    })
+  }
+
+  fn try_into_unparsed(self, unparsed_mut: &mut U) -> Result<(), Self::Error> {
+    if let Some(stickied) = self.stickied {
+      unparsed_mut.insert("stickied", stickied)?;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,32 @@
+use activitystreams::unparsed::UnparsedMutExt;
+use activitystreams_ext::UnparsedExtension;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Activitystreams extension to allow (de)serializing the additional Person field `featured`, the
+/// url of the collection with the posts which the person pinned to their profile.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PersonExtension {
+  pub featured: Option<Url>,
+}
+
+impl<U> UnparsedExtension<U> for PersonExtension
+where
+  U: UnparsedMutExt,
+{
+  type Error = serde_json::Error;
+
+  fn try_from_unparsed(unparsed_mut: &mut U) -> Result<Self, Self::Error> {
+    Ok(PersonExtension {
+      featured: unparsed_mut.remove("featured")?,
+    })
+  }
+
+  fn try_into_unparsed(self, unparsed_mut: &mut U) -> Result<(), Self::Error> {
+    if let Some(featured) = self.featured {
+      unparsed_mut.insert("featured", featured)?;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,52 @@
+use activitystreams::unparsed::UnparsedMutExt;
+use activitystreams_ext::UnparsedExtension;
+use lemmy_db_schema::{source::person::Person, PersonId};
+use serde::{Deserialize, Serialize};
+
+/// Name of the service actor which sends the votes of an instance when they are federated as an
+/// aggregate. It can't be registered, because usernames can't contain a dot. Receivers only use it
+/// to find the actor of a Lemmy instance the first time, after that the actor is stored on the
+/// instance row.
+pub(crate) static VOTE_ACTOR_NAME: &str = "instance.votes";
+
+/// The most votes which a single aggregate activity can stand for
+static MAX_AGGREGATED_VOTES: i32 = 1000;
+
+/// Activitystreams extension to allow (de)serializing the `count` of a `Like` or `Dislike`. It is
+/// set when an instance federates its votes as an aggregate, then the activity stands for that
+/// many votes of the instance. Activities without it are a single vote.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VoteExtension {
+  pub count: Option<i32>,
+}
+
+impl VoteExtension {
+  /// The number of votes which the activity by `actor` stands for. Only the service actor which
+  /// is stored for the instance of `actor` sends aggregates, activities of any other actor are a
+  /// single vote.
+  pub fn votes(&self, actor: &Person, vote_actor_id: Option<PersonId>) -> i16 {
+    if vote_actor_id != Some(actor.id) {
+      return 1;
+    }
+    self.count.unwrap_or(1).max(1).min(MAX_AGGREGATED_VOTES) as i16
+  }
+}
+
+impl<U> UnparsedExtension<U> for VoteExtension
+where
+  U: UnparsedMutExt,
+{
+  type Error = serde_json::Error;
+
+  fn try_from_unparsed(unparsed_mut: &mut U) -> Result<Self, Self::Error> {
+    Ok(VoteExtension {
+      count: unparsed_mut.remove("count")?,
+    })
+  }
+
+  fn try_into_unparsed(self, unparsed_mut: &mut U) -> Result<(), Self::Error> {
+    unparsed_mut.insert("count", self.count)?;
+    Ok(())
+  }
+}
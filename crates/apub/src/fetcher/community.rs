@@ -16,12 +16,16 @@ use activitystreams::{
 };
 use anyhow::Context;
 use diesel::result::Error::NotFound;
-use lemmy_api_structs::blocking;
+use lemmy_api_structs::{blocking, blocking_transaction};
 use lemmy_db_queries::{source::community::Community_, ApubObject, Joinable};
-use lemmy_db_schema::source::community::{Community, CommunityModerator, CommunityModeratorForm};
+use lemmy_db_schema::{
+  source::community::{Community, CommunityModerator, CommunityModeratorForm},
+  PersonId,
+};
+use lemmy_db_views_actor::community_moderator_view::CommunityModeratorView;
 use lemmy_utils::{location_info, LemmyError};
 use lemmy_websocket::LemmyContext;
-use log::debug;
+use log::{debug, warn};
 use url::Url;
 
 /// Get a community from its apub ID.
@@ -62,7 +66,7 @@ async fn fetch_remote_community(
   old_community: Option<Community>,
   recursion_counter: &mut i32,
 ) -> Result<Community, LemmyError> {
-  let group = fetch_remote_object::<GroupExt>(context.client(), apub_id, recursion_counter).await;
+  let group = fetch_remote_object::<GroupExt>(context, apub_id, recursion_counter).await;
 
   if let Some(c) = old_community.to_owned() {
     if is_deleted(&group) {
@@ -77,17 +81,67 @@ async fn fetch_remote_community(
   }
 
   let group = group?;
+  let community = upsert_community_and_moderators(&group, apub_id, context, recursion_counter)
+    .await?
+    .community;
+
+  // only fetch outbox for new communities, otherwise this can create an infinite loop
+  if old_community.is_none() {
+    let outbox = group.inner.outbox()?.context(location_info!())?;
+    fetch_community_outbox(context, outbox, &community, recursion_counter).await?
+  }
+
+  Ok(community)
+}
+
+/// The result of `refresh_remote_community()`
+pub struct CommunityRefresh {
+  pub community: Community,
+  pub added_moderators: Vec<PersonId>,
+  pub removed_moderators: Vec<PersonId>,
+}
+
+/// Fetches a known remote community again, regardless of when it was last refreshed. Unlike the
+/// regular refetch, errors are returned instead of falling back to the stored community.
+pub async fn refresh_remote_community(
+  community: &Community,
+  context: &LemmyContext,
+) -> Result<CommunityRefresh, LemmyError> {
+  let mut recursion_counter = 0;
+  let apub_id: Url = community.actor_id.to_owned().into();
+  let group = fetch_remote_object::<GroupExt>(context, &apub_id, &mut recursion_counter).await?;
+  upsert_community_and_moderators(&group, &apub_id, context, &mut recursion_counter).await
+}
+
+/// Stores the fetched community and replaces its moderators with the ones from `attributedTo`. A
+/// missing or empty `attributedTo` leaves the moderators unchanged, as a community always has at
+/// least one.
+async fn upsert_community_and_moderators(
+  group: &GroupExt,
+  apub_id: &Url,
+  context: &LemmyContext,
+  recursion_counter: &mut i32,
+) -> Result<CommunityRefresh, LemmyError> {
   let community =
-    Community::from_apub(&group, context, apub_id.to_owned(), recursion_counter).await?;
+    Community::from_apub(group, context, apub_id.to_owned(), recursion_counter).await?;
 
   // Also add the community moderators too
-  let attributed_to = group.inner.attributed_to().context(location_info!())?;
-  let creator_and_moderator_uris: Vec<&Url> = attributed_to
-    .as_many()
-    .context(location_info!())?
-    .iter()
-    .map(|a| a.as_xsd_any_uri().context(""))
-    .collect::<Result<Vec<&Url>, anyhow::Error>>()?;
+  let creator_and_moderator_uris: Vec<&Url> = match group.inner.attributed_to() {
+    Some(attributed_to) => attributed_to
+      .as_many()
+      .context(location_info!())?
+      .iter()
+      .map(|a| a.as_xsd_any_uri().context(""))
+      .collect::<Result<Vec<&Url>, anyhow::Error>>()?,
+    None => Vec::new(),
+  };
+  if creator_and_moderator_uris.is_empty() {
+    return Ok(CommunityRefresh {
+      community,
+      added_moderators: Vec::new(),
+      removed_moderators: Vec::new(),
+    });
+  }
 
   let mut creator_and_moderators = Vec::new();
 
@@ -97,30 +151,58 @@ async fn fetch_remote_community(
     creator_and_moderators.push(c_or_m);
   }
 
-  // TODO: need to make this work to update mods of existing communities
-  if old_community.is_none() {
-    let community_id = community.id;
-    blocking(context.pool(), move |conn| {
-      for mod_ in creator_and_moderators {
-        let community_moderator_form = CommunityModeratorForm {
-          community_id,
-          person_id: mod_.id,
-        };
-
-        CommunityModerator::join(conn, &community_moderator_form)?;
-      }
-      Ok(()) as Result<(), LemmyError>
-    })
-    .await??;
-  }
+  // New moderators are joined in the order of the remote community, so they keep their ranks
+  let community_id = community.id;
+  let (added_moderators, removed_moderators) = blocking_transaction(context.pool(), move |conn| {
+    let new_ids: Vec<PersonId> = creator_and_moderators.iter().map(|m| m.id).collect();
+    let old_ids: Vec<PersonId> = CommunityModeratorView::for_community(conn, community_id)?
+      .iter()
+      .map(|m| m.moderator.id)
+      .collect();
+    let (added, removed) = moderator_changes(&old_ids, &new_ids);
 
-  // only fetch outbox for new communities, otherwise this can create an infinite loop
-  if old_community.is_none() {
-    let outbox = group.inner.outbox()?.context(location_info!())?;
-    fetch_community_outbox(context, outbox, &community, recursion_counter).await?
-  }
+    for person_id in &removed {
+      let community_moderator_form = CommunityModeratorForm {
+        community_id,
+        person_id: *person_id,
+      };
+      CommunityModerator::leave(conn, &community_moderator_form)?;
+    }
+    for person_id in &added {
+      let community_moderator_form = CommunityModeratorForm {
+        community_id,
+        person_id: *person_id,
+      };
+      CommunityModerator::join(conn, &community_moderator_form)?;
+    }
+    Ok((added, removed))
+  })
+  .await?;
 
-  Ok(community)
+  Ok(CommunityRefresh {
+    community,
+    added_moderators,
+    removed_moderators,
+  })
+}
+
+/// Returns the moderators which have to be added and removed to get from `old_ids` to `new_ids`,
+/// keeping the order of `new_ids`. An empty `new_ids` changes nothing.
+fn moderator_changes(old_ids: &[PersonId], new_ids: &[PersonId]) -> (Vec<PersonId>, Vec<PersonId>) {
+  if new_ids.is_empty() {
+    return (Vec::new(), Vec::new());
+  }
+  let added = new_ids
+    .iter()
+    .filter(|id| !old_ids.contains(id))
+    .cloned()
+    .collect();
+  let removed = old_ids
+    .iter()
+    .filter(|id| !new_ids.contains(id))
+    .cloned()
+    .collect();
+  (added, removed)
 }
 
 async fn fetch_community_outbox(
@@ -129,17 +211,47 @@ async fn fetch_community_outbox(
   community: &Community,
   recursion_counter: &mut i32,
 ) -> Result<(), LemmyError> {
-  let outbox =
-    fetch_remote_object::<OrderedCollection>(context.client(), outbox, recursion_counter).await?;
+  let outbox = fetch_remote_object::<OrderedCollection>(context, outbox, recursion_counter).await?;
   let outbox_activities = outbox.items().context(location_info!())?.clone();
   let mut outbox_activities = outbox_activities.many().context(location_info!())?;
   if outbox_activities.len() > 20 {
     outbox_activities = outbox_activities[0..20].to_vec();
   }
 
+  // A post which can't be fetched doesn't keep the others from being received
   for activity in outbox_activities {
-    receive_announce(context, activity, community, recursion_counter).await?;
+    if let Err(e) = receive_announce(context, activity, community, recursion_counter).await {
+      warn!(
+        "Failed to receive outbox activity of community {}: {}",
+        community.actor_id, e
+      );
+    }
   }
 
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use super::moderator_changes;
+  use lemmy_db_schema::PersonId;
+
+  #[test]
+  fn test_moderator_changes() {
+    let old = vec![PersonId(1), PersonId(2), PersonId(3)];
+
+    let new = vec![PersonId(4), PersonId(2), PersonId(5)];
+    let (added, removed) = moderator_changes(&old, &new);
+    assert_eq!(vec![PersonId(4), PersonId(5)], added);
+    assert_eq!(vec![PersonId(1), PersonId(3)], removed);
+
+    let (added, removed) = moderator_changes(&old, &old);
+    assert!(added.is_empty());
+    assert!(removed.is_empty());
+
+    // A community without any moderators is never the result of a refresh
+    let (added, removed) = moderator_changes(&old, &[]);
+    assert!(added.is_empty());
+    assert!(removed.is_empty());
+  }
+}
@@ -1,20 +1,13 @@
 use crate::{check_is_apub_id_valid, APUB_JSON_CONTENT_TYPE};
-use anyhow::anyhow;
-use lemmy_utils::{request::retry, LemmyError};
-use reqwest::{Client, StatusCode};
+use anyhow::{anyhow, Context};
+use lemmy_utils::{location_info, request::retry, LemmyError};
+use lemmy_websocket::LemmyContext;
+use reqwest::StatusCode;
 use serde::Deserialize;
 use std::time::Duration;
-use thiserror::Error;
 use url::Url;
 
-/// Maximum number of HTTP requests allowed to handle a single incoming activity (or a single object
-/// fetch through the search).
-///
-/// A community fetch will load the outbox with up to 20 items, and fetch the creator for each item.
-/// So we are looking at a maximum of 22 requests (rounded up just to be safe).
-static MAX_REQUEST_NUMBER: i32 = 25;
-
-#[derive(Debug, Error)]
+#[derive(Debug)]
 pub(in crate::fetcher) struct FetchError {
   pub inner: anyhow::Error,
   pub status_code: Option<StatusCode>,
@@ -41,14 +34,24 @@ impl From<reqwest::Error> for FetchError {
 
 impl std::fmt::Display for FetchError {
   fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-    std::fmt::Display::fmt(&self, f)
+    std::fmt::Display::fmt(&self.inner, f)
+  }
+}
+
+impl std::error::Error for FetchError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    Some(&*self.inner)
   }
 }
 
 /// Fetch any type of ActivityPub object, handling things like HTTP headers, deserialisation,
 /// timeouts etc.
+///
+/// The number of requests made while handling a single incoming activity (or a single object fetch
+/// through the search) is limited by `fetch_limit.per_activity`. Each request also counts against
+/// the hourly budget of the remote instance, and waits for a free concurrent fetch slot.
 pub(in crate::fetcher) async fn fetch_remote_object<Response>(
-  client: &Client,
+  context: &LemmyContext,
   url: &Url,
   recursion_counter: &mut i32,
 ) -> Result<Response, FetchError>
@@ -56,12 +59,20 @@ where
   Response: for<'de> Deserialize<'de> + std::fmt::Debug,
 {
   *recursion_counter += 1;
-  if *recursion_counter > MAX_REQUEST_NUMBER {
+  if *recursion_counter > context.fetch_limiter().per_activity() {
     return Err(LemmyError::from(anyhow!("Maximum recursion depth reached")).into());
   }
   check_is_apub_id_valid(&url)?;
 
+  // The host, so that instances which are addressed by ip are limited too
+  let host = url
+    .host_str()
+    .context(location_info!())
+    .map_err(LemmyError::from)?;
+  let _permit = context.fetch_limiter().acquire(host).await?;
+
   let timeout = Duration::from_secs(60);
+  let client = context.client();
 
   let res = retry(|| {
     client
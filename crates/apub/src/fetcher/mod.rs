@@ -1,7 +1,8 @@
-pub(crate) mod community;
+pub mod community;
 mod fetch;
+pub mod modlog;
 pub(crate) mod objects;
-pub(crate) mod person;
+pub mod person;
 pub mod search;
 
 use crate::{
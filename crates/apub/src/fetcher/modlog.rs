@@ -0,0 +1,207 @@
+use crate::{
+  fetcher::fetch::fetch_remote_object,
+  objects::modlog::{
+    merge_remote_modlog,
+    modlog_from_entries,
+    ModlogEntry,
+    ModlogEntryKind,
+    ModlogPage,
+  },
+  GroupExt,
+};
+use lemmy_api_structs::{blocking, site::GetModlogResponse};
+use lemmy_db_schema::{source::community::Community, CommunityId, PersonId};
+use lemmy_utils::LemmyError;
+use lemmy_websocket::LemmyContext;
+use log::warn;
+use std::{
+  collections::HashMap,
+  hash::Hash,
+  sync::Mutex,
+  time::{Duration, Instant},
+};
+use url::Url;
+
+const MODLOG_CACHE_SECONDS: u64 = 10 * 60;
+/// The most pages, or modlog urls, which are kept in each cache
+const MODLOG_CACHE_CAPACITY: usize = 1000;
+/// The modlog url of a community rarely changes, so its group is only fetched again after a day
+const MODLOG_URL_CACHE_SECONDS: u64 = 24 * 60 * 60;
+/// At most this many entries are read from a remote page, no matter what the instance sends
+const MODLOG_MAX_ENTRIES: usize = 200;
+
+type CachedModlogPage = (Instant, Option<Vec<ModlogEntry>>);
+
+lazy_static! {
+  static ref MODLOG_CACHE: Mutex<HashMap<(CommunityId, i64), CachedModlogPage>> =
+    Mutex::new(HashMap::new());
+  static ref MODLOG_URL_CACHE: Mutex<HashMap<CommunityId, (Instant, Option<Url>)>> =
+    Mutex::new(HashMap::new());
+}
+
+/// Returns the cached value of `key`, if it was stored less than `max_age` ago
+fn read_cached<K, V>(
+  cache: &Mutex<HashMap<K, (Instant, V)>>,
+  key: &K,
+  max_age: Duration,
+) -> Option<V>
+where
+  K: Eq + Hash,
+  V: Clone,
+{
+  cache
+    .lock()
+    .expect("lock modlog cache")
+    .get(key)
+    .filter(|(stored, _)| stored.elapsed() < max_age)
+    .map(|(_, value)| value.to_owned())
+}
+
+/// Stores `value` for `key`. A full cache drops its outdated values first, and all of them if that
+/// isn't enough.
+fn store_cached<K, V>(cache: &Mutex<HashMap<K, (Instant, V)>>, key: K, value: V, max_age: Duration)
+where
+  K: Eq + Hash,
+{
+  let mut cache = cache.lock().expect("lock modlog cache");
+  if cache.len() >= MODLOG_CACHE_CAPACITY {
+    cache.retain(|_, (stored, _)| stored.elapsed() < max_age);
+    if cache.len() >= MODLOG_CACHE_CAPACITY {
+      cache.clear();
+    }
+  }
+  cache.insert(key, (Instant::now(), value));
+}
+
+/// Fetches a page of the modlog of a remote community from its instance, and adds the entries to
+/// `modlog`, the one which was logged locally. Pages are cached for ten minutes, also if the fetch
+/// failed.
+///
+/// Nothing is added if the instance doesn't expose a modlog.
+pub async fn fetch_remote_modlog(
+  modlog: &mut GetModlogResponse,
+  community: &Community,
+  mod_person_id: Option<PersonId>,
+  page: Option<i64>,
+  context: &LemmyContext,
+) -> Result<(), LemmyError> {
+  let key = (community.id, page.unwrap_or(1).max(1));
+  let max_age = Duration::from_secs(MODLOG_CACHE_SECONDS);
+  let cached = read_cached(&MODLOG_CACHE, &key, max_age);
+
+  let entries = match cached {
+    Some(entries) => entries,
+    None => {
+      let entries = match fetch_modlog_page(community, key.1, context).await {
+        Ok(entries) => entries,
+        Err(e) => {
+          warn!("Failed to fetch modlog of {}: {}", community.actor_id, e);
+          None
+        }
+      };
+      store_cached(&MODLOG_CACHE, key, entries.to_owned(), max_age);
+      entries
+    }
+  };
+
+  let entries = match entries {
+    Some(entries) => entries,
+    None => return Ok(()),
+  };
+  let community = community.to_owned();
+  let remote = blocking(context.pool(), move |conn| {
+    modlog_from_entries(conn, &community, mod_person_id, &entries)
+  })
+  .await??;
+  merge_remote_modlog(modlog, remote);
+  Ok(())
+}
+
+/// The modlog url of the community, or `None` if its group has none. The modlog can only be
+/// trusted if it comes from the instance of the community.
+fn trusted_modlog_url(modlog: Option<Url>, actor_id: &Url) -> Option<Url> {
+  modlog.filter(|url| url.domain() == actor_id.domain())
+}
+
+/// Returns the modlog url from the group of the community, which is cached for a day
+async fn get_modlog_url(
+  community: &Community,
+  context: &LemmyContext,
+  recursion_counter: &mut i32,
+) -> Result<Option<Url>, LemmyError> {
+  let max_age = Duration::from_secs(MODLOG_URL_CACHE_SECONDS);
+  if let Some(url) = read_cached(&MODLOG_URL_CACHE, &community.id, max_age) {
+    return Ok(url);
+  }
+  let actor_id = community.actor_id.to_owned().into_inner();
+  let group = fetch_remote_object::<GroupExt>(context, &actor_id, recursion_counter).await?;
+  let url = trusted_modlog_url(group.ext_one.modlog, &actor_id);
+  store_cached(&MODLOG_URL_CACHE, community.id, url.to_owned(), max_age);
+  Ok(url)
+}
+
+/// Returns `None` if the group of the community has no modlog url.
+async fn fetch_modlog_page(
+  community: &Community,
+  page: i64,
+  context: &LemmyContext,
+) -> Result<Option<Vec<ModlogEntry>>, LemmyError> {
+  let mut recursion_counter = 0;
+  let mut modlog_url = match get_modlog_url(community, context, &mut recursion_counter).await? {
+    Some(url) => url,
+    None => return Ok(None),
+  };
+  modlog_url
+    .query_pairs_mut()
+    .append_pair("page", &page.to_string());
+
+  let modlog =
+    fetch_remote_object::<ModlogPage>(context, &modlog_url, &mut recursion_counter).await?;
+  let entries = modlog
+    .ordered_items
+    .into_iter()
+    .filter(|e| e.kind != ModlogEntryKind::Unknown)
+    .take(MODLOG_MAX_ENTRIES)
+    .collect();
+  Ok(Some(entries))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_modlog_cache() {
+    let cache: Mutex<HashMap<i64, (Instant, i64)>> = Mutex::new(HashMap::new());
+    let max_age = Duration::from_secs(60);
+
+    assert_eq!(None, read_cached(&cache, &1, max_age));
+    store_cached(&cache, 1, 10, max_age);
+    assert_eq!(Some(10), read_cached(&cache, &1, max_age));
+    // Outdated values are ignored
+    assert_eq!(None, read_cached(&cache, &1, Duration::from_secs(0)));
+
+    // A full cache is emptied before more values are stored
+    for key in 2..=MODLOG_CACHE_CAPACITY as i64 {
+      store_cached(&cache, key, key * 10, max_age);
+    }
+    assert_eq!(MODLOG_CACHE_CAPACITY, cache.lock().unwrap().len());
+    store_cached(&cache, 0, 0, max_age);
+    assert_eq!(1, cache.lock().unwrap().len());
+    assert_eq!(Some(0), read_cached(&cache, &0, max_age));
+  }
+
+  #[test]
+  fn test_trusted_modlog_url() {
+    let actor_id = Url::parse("https://example.com/c/main").unwrap();
+    let modlog = Url::parse("https://example.com/c/main/modlog").unwrap();
+    let forged = Url::parse("https://forged.example/c/main/modlog").unwrap();
+
+    assert_eq!(
+      Some(modlog.to_owned()),
+      trusted_modlog_url(Some(modlog), &actor_id)
+    );
+    assert_eq!(None, trusted_modlog_url(Some(forged), &actor_id));
+    assert_eq!(None, trusted_modlog_url(None, &actor_id));
+  }
+}
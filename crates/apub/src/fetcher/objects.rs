@@ -28,8 +28,7 @@ pub(crate) async fn get_or_fetch_and_insert_post(
     Ok(p) => Ok(p),
     Err(NotFound {}) => {
       debug!("Fetching and creating remote post: {}", post_ap_id);
-      let page =
-        fetch_remote_object::<PageExt>(context.client(), post_ap_id, recursion_counter).await?;
+      let page = fetch_remote_object::<PageExt>(context, post_ap_id, recursion_counter).await?;
       let post = Post::from_apub(&page, context, post_ap_id.to_owned(), recursion_counter).await?;
 
       Ok(post)
@@ -61,7 +60,7 @@ pub(crate) async fn get_or_fetch_and_insert_comment(
         comment_ap_id
       );
       let comment =
-        fetch_remote_object::<NoteExt>(context.client(), comment_ap_id, recursion_counter).await?;
+        fetch_remote_object::<NoteExt>(context, comment_ap_id, recursion_counter).await?;
       let comment = Comment::from_apub(
         &comment,
         context,
@@ -13,6 +13,13 @@ use lemmy_websocket::LemmyContext;
 use log::debug;
 use url::Url;
 
+/// Reads a person by its actor id, and fetches it from its instance if it isn't known yet or
+/// outdated
+pub async fn resolve_person(apub_id: &Url, context: &LemmyContext) -> Result<Person, LemmyError> {
+  let mut recursion_counter = 0;
+  get_or_fetch_and_upsert_person(apub_id, context, &mut recursion_counter).await
+}
+
 /// Get a person from its apub ID.
 ///
 /// If it exists locally and `!should_refetch_actor()`, it is returned directly from the database.
@@ -32,8 +39,7 @@ pub(crate) async fn get_or_fetch_and_upsert_person(
     // If its older than a day, re-fetch it
     Ok(u) if !u.local && should_refetch_actor(u.last_refreshed_at) => {
       debug!("Fetching and updating from remote person: {}", apub_id);
-      let person =
-        fetch_remote_object::<PersonExt>(context.client(), apub_id, recursion_counter).await;
+      let person = fetch_remote_object::<PersonExt>(context, apub_id, recursion_counter).await;
 
       if is_deleted(&person) {
         // TODO: use Person::update_deleted() once implemented
@@ -60,8 +66,7 @@ pub(crate) async fn get_or_fetch_and_upsert_person(
     Ok(u) => Ok(u),
     Err(NotFound {}) => {
       debug!("Fetching and creating remote person: {}", apub_id);
-      let person =
-        fetch_remote_object::<PersonExt>(context.client(), apub_id, recursion_counter).await?;
+      let person = fetch_remote_object::<PersonExt>(context, apub_id, recursion_counter).await?;
 
       let person =
         Person::from_apub(&person, context, apub_id.to_owned(), recursion_counter).await?;
@@ -26,12 +26,15 @@ use lemmy_db_queries::{
   },
   SearchType,
 };
-use lemmy_db_schema::source::{
-  comment::Comment,
-  community::Community,
-  person::Person,
-  post::Post,
-  private_message::PrivateMessage,
+use lemmy_db_schema::{
+  source::{
+    comment::Comment,
+    community::Community,
+    person::Person,
+    post::Post,
+    private_message::PrivateMessage,
+  },
+  PersonId,
 };
 use lemmy_db_views::{comment_view::CommentView, post_view::PostView};
 use lemmy_db_views_actor::{community_view::CommunityView, person_view::PersonViewSafe};
@@ -59,6 +62,7 @@ enum SearchAcceptedObjects {
 /// http://lemmy_delta:8571/comment/2
 pub async fn search_by_apub_id(
   query: &str,
+  my_person_id: Option<PersonId>,
   context: &LemmyContext,
 ) -> Result<SearchResponse, LemmyError> {
   // Parse the shorthand query url
@@ -94,20 +98,49 @@ pub async fn search_by_apub_id(
 
   let recursion_counter = &mut 0;
   let fetch_response =
-    fetch_remote_object::<SearchAcceptedObjects>(context.client(), &query_url, recursion_counter)
-      .await;
+    fetch_remote_object::<SearchAcceptedObjects>(context, &query_url, recursion_counter).await;
   if is_deleted(&fetch_response) {
     delete_object_locally(&query_url, context).await?;
   }
 
   // Necessary because we get a stack overflow using FetchError
   let fet_res = fetch_response.map_err(|e| LemmyError::from(e.inner))?;
-  build_response(fet_res, query_url, recursion_counter, context).await
+  build_response(fet_res, query_url, my_person_id, recursion_counter, context).await
+}
+
+/// Resolves a community from `!name@instance`, fetching it from the remote instance if it isnt
+/// known yet. A plain name, or one with the hostname of this instance, is looked up among the local
+/// communities.
+pub async fn resolve_community_name(
+  name: &str,
+  context: &LemmyContext,
+) -> Result<Community, LemmyError> {
+  let mut split = name.trim_start_matches('!').splitn(2, '@');
+  let community_name = split.next().unwrap_or_default().to_owned();
+  match split.next() {
+    Some(instance) if instance != Settings::get().hostname() => {
+      let actor_id = Url::parse(&format!(
+        "{}://{}/c/{}",
+        Settings::get().get_protocol_string(),
+        instance,
+        community_name
+      ))?;
+      let recursion_counter = &mut 0;
+      get_or_fetch_and_upsert_community(&actor_id, context, recursion_counter).await
+    }
+    _ => Ok(
+      blocking(context.pool(), move |conn| {
+        Community::read_from_name(conn, &community_name)
+      })
+      .await??,
+    ),
+  }
 }
 
 async fn build_response(
   fetch_response: SearchAcceptedObjects,
   query_url: Url,
+  my_person_id: Option<PersonId>,
   recursion_counter: &mut i32,
   context: &LemmyContext,
 ) -> Result<SearchResponse, LemmyError> {
@@ -141,7 +174,7 @@ async fn build_response(
 
       response.communities = vec![
         blocking(context.pool(), move |conn| {
-          CommunityView::read(conn, community.id, None)
+          CommunityView::read(conn, community.id, my_person_id)
         })
         .await??,
       ];
@@ -149,15 +182,19 @@ async fn build_response(
     SearchAcceptedObjects::Page(p) => {
       let p = Post::from_apub(&p, context, query_url, recursion_counter).await?;
 
-      response.posts =
-        vec![blocking(context.pool(), move |conn| PostView::read(conn, p.id, None)).await??];
+      response.posts = vec![
+        blocking(context.pool(), move |conn| {
+          PostView::read(conn, p.id, my_person_id)
+        })
+        .await??,
+      ];
     }
     SearchAcceptedObjects::Comment(c) => {
       let c = Comment::from_apub(&c, context, query_url, recursion_counter).await?;
 
       response.comments = vec![
         blocking(context.pool(), move |conn| {
-          CommentView::read(conn, c.id, None)
+          CommentView::read(conn, c.id, my_person_id)
         })
         .await??,
       ];
@@ -1,7 +1,7 @@
 use crate::{
   extensions::context::lemmy_context,
   http::{create_apub_response, create_apub_tombstone_response},
-  objects::ToApub,
+  objects::{modlog::read_modlog_page, ToApub},
   ActorType,
 };
 use activitystreams::{
@@ -22,6 +22,11 @@ pub struct CommunityQuery {
   community_name: String,
 }
 
+#[derive(Deserialize)]
+pub struct ModlogQuery {
+  page: Option<i64>,
+}
+
 /// Return the ActivityPub json representation of a local community over HTTP.
 pub async fn get_apub_community_http(
   info: web::Path<CommunityQuery>,
@@ -96,6 +101,22 @@ pub async fn get_apub_community_outbox(
   Ok(create_apub_response(&collection))
 }
 
+/// Returns a page of the public community modlog, with the removed, locked and stickied posts,
+/// removed comments, bans and added moderators.
+pub async fn get_apub_community_modlog(
+  info: web::Path<CommunityQuery>,
+  query: web::Query<ModlogQuery>,
+  context: web::Data<LemmyContext>,
+) -> Result<HttpResponse<Body>, LemmyError> {
+  let page = query.page.unwrap_or(1).max(1);
+  let modlog = blocking(context.pool(), move |conn| {
+    let community = Community::read_from_name(&conn, &info.community_name)?;
+    read_modlog_page(&conn, &community, page)
+  })
+  .await??;
+  Ok(create_apub_response(&modlog))
+}
+
 pub async fn get_apub_community_inbox(
   info: web::Path<CommunityQuery>,
   context: web::Data<LemmyContext>,
@@ -1,17 +1,23 @@
 use crate::{
   extensions::context::lemmy_context,
+  generate_featured_url,
   http::{create_apub_response, create_apub_tombstone_response},
-  objects::ToApub,
+  objects::{
+    person_outbox::{count_outbox_items, outbox_page_url, read_outbox_page},
+    post::posts_to_apub,
+    ToApub,
+  },
   ActorType,
 };
 use activitystreams::{
-  base::BaseExt,
+  base::{AnyBase, BaseExt, ExtendsExt},
   collection::{CollectionExt, OrderedCollection},
 };
 use actix_web::{body::Body, web, HttpResponse};
 use lemmy_api_structs::blocking;
 use lemmy_db_queries::source::person::Person_;
 use lemmy_db_schema::source::person::Person;
+use lemmy_db_views::{local_user_view::LocalUserView, post_view::PostView};
 use lemmy_utils::LemmyError;
 use lemmy_websocket::LemmyContext;
 use serde::Deserialize;
@@ -22,6 +28,11 @@ pub struct PersonQuery {
   user_name: String,
 }
 
+#[derive(Deserialize)]
+pub struct OutboxQuery {
+  page: Option<i64>,
+}
+
 /// Return the ActivityPub json representation of a local person over HTTP.
 pub async fn get_apub_person_http(
   info: web::Path<PersonQuery>,
@@ -43,7 +54,51 @@ pub async fn get_apub_person_http(
   }
 }
 
+/// Returns the outbox of a local person. Without a page, only the size and the link to the first
+/// page are returned. The outbox stays empty if the person hides their profile from federation.
 pub async fn get_apub_person_outbox(
+  info: web::Path<PersonQuery>,
+  query: web::Query<OutboxQuery>,
+  context: web::Data<LemmyContext>,
+) -> Result<HttpResponse<Body>, LemmyError> {
+  let person = blocking(context.pool(), move |conn| {
+    Person::find_by_name(&conn, &info.user_name)
+  })
+  .await??;
+
+  let person_id = person.id;
+  let local_user_view = blocking(context.pool(), move |conn| {
+    LocalUserView::read_person(&conn, person_id)
+  })
+  .await??;
+  let outbox_url = person.get_outbox_url()?;
+  let mut collection = OrderedCollection::new();
+  collection
+    .set_many_contexts(lemmy_context()?)
+    .set_id(outbox_url.to_owned());
+
+  if local_user_view.local_user.hide_profile_from_federation {
+    collection
+      .set_many_items(Vec::<Url>::new())
+      .set_total_items(0_u64);
+    return Ok(create_apub_response(&collection));
+  }
+
+  if let Some(page) = query.page {
+    let page = read_outbox_page(&person, page.max(1), context.pool()).await?;
+    return Ok(create_apub_response(&page));
+  }
+
+  let total_items = count_outbox_items(person_id, context.pool()).await?;
+  collection
+    .set_total_items(total_items as u64)
+    .set_first(outbox_page_url(&outbox_url, 1)?);
+  Ok(create_apub_response(&collection))
+}
+
+/// Returns the posts which the person pinned to their profile, as the `featured` collection which
+/// is also shown by Mastodon.
+pub async fn get_apub_person_featured(
   info: web::Path<PersonQuery>,
   context: web::Data<LemmyContext>,
 ) -> Result<HttpResponse<Body>, LemmyError> {
@@ -51,13 +106,33 @@ pub async fn get_apub_person_outbox(
     Person::find_by_name(&conn, &info.user_name)
   })
   .await??;
-  // TODO: populate the person outbox
+
+  let person_id = person.id;
+  let (local_user_view, pinned_posts) = blocking(context.pool(), move |conn| {
+    let local_user_view = LocalUserView::read_person(&conn, person_id)?;
+    let pinned_posts = PostView::list_pinned_for_person(&conn, person_id, None)?;
+    Ok((local_user_view, pinned_posts)) as Result<_, LemmyError>
+  })
+  .await??;
+
+  // Empty like the outbox, if the person hides their profile from other instances
+  let items = if local_user_view.local_user.hide_profile_from_federation {
+    vec![]
+  } else {
+    let posts = pinned_posts.into_iter().map(|p| p.post).collect();
+    posts_to_apub(posts, context.pool())
+      .await?
+      .into_iter()
+      .map(|page| page.into_any_base())
+      .collect::<Result<Vec<AnyBase>, _>>()?
+  };
+  let len = items.len();
   let mut collection = OrderedCollection::new();
   collection
-    .set_many_items(Vec::<Url>::new())
+    .set_many_items(items)
     .set_many_contexts(lemmy_context()?)
-    .set_id(person.get_outbox_url()?)
-    .set_total_items(0_u64);
+    .set_id(generate_featured_url(&person.actor_id)?)
+    .set_total_items(len as u64);
   Ok(create_apub_response(&collection))
 }
 
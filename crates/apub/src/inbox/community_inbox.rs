@@ -1,12 +1,21 @@
 use crate::{
-  activities::receive::verify_activity_domains_valid,
+  activities::{
+    receive::{
+      community::{receive_block_user_for_community, receive_undo_block_user_for_community},
+      verify_activity_domains_valid,
+    },
+    send::community::send_announce_to_inboxes,
+  },
   inbox::{
     assert_activity_not_local,
+    forget_activity_if_retryable,
     get_activity_id,
     get_activity_to_and_cc,
     inbox_verify_http_signature,
     is_activity_already_known,
     is_addressed_to_public,
+    log_rejected_community_activity,
+    mark_actor_active,
     receive_for_community::{
       receive_create_for_community,
       receive_delete_for_community,
@@ -17,20 +26,35 @@ use crate::{
     },
   },
   insert_activity,
+  log_community_federation,
   ActorType,
 };
 use activitystreams::{
-  activity::{kind::FollowType, ActorAndObject, Follow, Undo},
+  activity::{
+    kind::{BlockType, FollowType},
+    ActorAndObject,
+    Follow,
+    Undo,
+  },
   base::AnyBase,
   prelude::*,
 };
 use actix_web::{web, HttpRequest, HttpResponse};
 use anyhow::{anyhow, Context};
 use lemmy_api_structs::blocking;
-use lemmy_db_queries::{source::community::Community_, ApubObject, DbPool, Followable};
-use lemmy_db_schema::source::{
-  community::{Community, CommunityFollower, CommunityFollowerForm},
-  person::Person,
+use lemmy_db_queries::{
+  source::community::{CommunityFollower_, Community_},
+  ApubObject,
+  DbPool,
+  Followable,
+};
+use lemmy_db_schema::{
+  source::{
+    community::{Community, CommunityFollower, CommunityFollowerForm},
+    community_federation_log::CommunityFederationLogForm,
+    person::Person,
+  },
+  CommunityId,
 };
 use lemmy_db_views_actor::community_person_ban_view::CommunityPersonBanView;
 use lemmy_utils::{location_info, LemmyError};
@@ -52,6 +76,7 @@ pub enum CommunityValidTypes {
   Dislike, // downvote post or comment
   Delete,  // post or comment deleted by creator
   Remove,  // post or comment removed by mod or admin
+  Block,   // person banned from community by remote mod
 }
 
 pub type CommunityAcceptedActivities = ActorAndObject<CommunityValidTypes>;
@@ -64,9 +89,22 @@ pub async fn community_inbox(
   context: web::Data<LemmyContext>,
 ) -> Result<HttpResponse, LemmyError> {
   let activity = input.into_inner();
+  let path = path.into_inner();
+  let community = blocking(&context.pool(), move |conn| {
+    Community::read_from_name(&conn, &path)
+  })
+  .await??;
+
   // First of all check the http signature
   let request_counter = &mut 0;
-  let actor = inbox_verify_http_signature(&activity, &context, request, request_counter).await?;
+  let actor = match inbox_verify_http_signature(&activity, &context, request, request_counter).await
+  {
+    Ok(actor) => actor,
+    Err(e) => {
+      log_rejected_community_activity(&activity, vec![community.id], &e, context.pool()).await;
+      return Err(e);
+    }
+  };
 
   // Do nothing if we received the same activity before
   let activity_id = get_activity_id(&activity, &actor.actor_id())?;
@@ -75,18 +113,15 @@ pub async fn community_inbox(
   }
 
   // Check if the activity is actually meant for us
-  let path = path.into_inner();
-  let community = blocking(&context.pool(), move |conn| {
-    Community::read_from_name(&conn, &path)
-  })
-  .await??;
   let to_and_cc = get_activity_to_and_cc(&activity);
-  if !to_and_cc.contains(&&community.actor_id()) {
+  let followers_url: Url = community.followers_url.to_owned().into();
+  if !is_addressed_to_community(&to_and_cc, &community.actor_id(), &followers_url) {
     return Err(anyhow!("Activity delivered to wrong community").into());
   }
 
   assert_activity_not_local(&activity)?;
   insert_activity(&activity_id, activity.clone(), false, true, context.pool()).await?;
+  mark_actor_active(&actor.actor_id(), context.pool()).await;
 
   info!(
     "Community {} received activity {:?} from {}",
@@ -95,14 +130,44 @@ pub async fn community_inbox(
     &actor.actor_id()
   );
 
-  community_receive_message(
+  let res = community_receive_message(
     activity.clone(),
     community.clone(),
     actor.as_ref(),
     &context,
     request_counter,
+    &mut CommunityFanout::default(),
   )
-  .await
+  .await;
+  forget_activity_if_retryable(res, &activity_id, context.pool()).await
+}
+
+/// Whether an activity with these recipients is meant for the community. Some software addresses
+/// the followers collection of the community instead of the community itself.
+fn is_addressed_to_community(to_and_cc: &[Url], actor_id: &Url, followers_url: &Url) -> bool {
+  to_and_cc
+    .iter()
+    .any(|url| url == actor_id || url == followers_url)
+}
+
+/// State which is shared between all local communities that receive the same activity, so that
+/// the activity is only processed once, and every remote follower gets only one announce.
+#[derive(Default)]
+pub(crate) struct CommunityFanout {
+  object_received: bool,
+  announced_inboxes: Vec<Url>,
+}
+
+impl CommunityFanout {
+  /// Returns those of the inboxes which didnt get an announce yet, and marks them as announced.
+  fn take_new_inboxes(&mut self, inboxes: Vec<Url>) -> Vec<Url> {
+    let new_inboxes: Vec<Url> = inboxes
+      .into_iter()
+      .filter(|i| !self.announced_inboxes.contains(i))
+      .collect();
+    self.announced_inboxes.extend(new_inboxes.to_owned());
+    new_inboxes
+  }
 }
 
 /// Receives Follow, Undo/Follow, post actions, comment actions (including votes)
@@ -112,6 +177,39 @@ pub(crate) async fn community_receive_message(
   actor: &dyn ActorType,
   context: &LemmyContext,
   request_counter: &mut i32,
+  fanout: &mut CommunityFanout,
+) -> Result<HttpResponse, LemmyError> {
+  let community_id = to_community.id;
+  let kind = activity.kind().map(|k| format!("{:?}", k));
+  let actor_id = actor.actor_id();
+  let res = handle_community_activity(
+    activity,
+    to_community,
+    actor,
+    context,
+    request_counter,
+    fanout,
+  )
+  .await;
+
+  let form = CommunityFederationLogForm {
+    community_id,
+    inbound: true,
+    kind,
+    domain: actor_id.host_str().unwrap_or_default().to_owned(),
+    error: res.as_ref().err().map(|e| e.to_string()),
+  };
+  log_community_federation(form, context.pool()).await;
+  res
+}
+
+async fn handle_community_activity(
+  activity: CommunityAcceptedActivities,
+  to_community: Community,
+  actor: &dyn ActorType,
+  context: &LemmyContext,
+  request_counter: &mut i32,
+  fanout: &mut CommunityFanout,
 ) -> Result<HttpResponse, LemmyError> {
   // Only persons can send activities to the community, so we can get the actor as person
   // unconditionally.
@@ -124,6 +222,7 @@ pub(crate) async fn community_receive_message(
 
   let any_base = activity.clone().into_any_base()?;
   let actor_url = actor.actor_id();
+  let origin_url = actor_url.to_owned();
   let activity_kind = activity.kind().context(location_info!())?;
   let do_announce = match activity_kind {
     CommunityValidTypes::Follow => {
@@ -140,46 +239,65 @@ pub(crate) async fn community_receive_message(
       )
       .await?
     }
-    CommunityValidTypes::Create => {
-      receive_create_for_community(context, any_base.clone(), &actor_url, request_counter).await?;
-      true
+    CommunityValidTypes::Remove => {
+      // TODO: we dont support remote mods, so this is ignored for now
+      //receive_remove_for_community(context, any_base.clone(), &person_url).await?
+      false
     }
-    CommunityValidTypes::Update => {
-      receive_update_for_community(context, any_base.clone(), &actor_url, request_counter).await?;
+    CommunityValidTypes::Block => {
+      receive_block_user_for_community(context, any_base.clone(), &to_community, request_counter)
+        .await?;
       true
     }
+    // Votes are checked for every community, as downvotes may be disabled in some of them. Storing
+    // the same vote again doesn't change anything.
     CommunityValidTypes::Like => {
       receive_like_for_community(context, any_base.clone(), &actor_url, request_counter).await?;
       true
     }
     CommunityValidTypes::Dislike => {
-      receive_dislike_for_community(context, any_base.clone(), &actor_url, request_counter).await?;
+      receive_dislike_for_community(context, any_base.clone(), &actor_url, request_counter).await?
+    }
+    // The post or comment was already handled for another local community, only announce it
+    _ if fanout.object_received => true,
+    CommunityValidTypes::Create => {
+      receive_create_for_community(context, any_base.clone(), &actor_url, request_counter).await?
+    }
+    CommunityValidTypes::Update => {
+      receive_update_for_community(context, any_base.clone(), &actor_url, request_counter).await?;
       true
     }
     CommunityValidTypes::Delete => {
       receive_delete_for_community(context, any_base.clone(), &actor_url).await?;
       true
     }
-    CommunityValidTypes::Remove => {
-      // TODO: we dont support remote mods, so this is ignored for now
-      //receive_remove_for_community(context, any_base.clone(), &person_url).await?
-      false
-    }
   };
 
+  // A dropped activity is checked again for the other local communities, and dropped there too
   if do_announce {
+    fanout.object_received = true;
     // Check again that the activity is public, just to be sure
     is_addressed_to_public(&activity)?;
-    to_community
-      .send_announce(activity.into_any_base()?, context)
-      .await?;
+    let inboxes = to_community.get_follower_inboxes(context.pool()).await?;
+    let inboxes = fanout.take_new_inboxes(without_origin_inboxes(inboxes, &origin_url));
+    send_announce_to_inboxes(&to_community, activity.into_any_base()?, inboxes, context).await?;
   }
 
   Ok(HttpResponse::Ok().finish())
 }
 
+/// Removes the inboxes on the instance of the actor. That instance sent the activity, so it doesnt
+/// need to get it back in an announce.
+fn without_origin_inboxes(inboxes: Vec<Url>, actor_url: &Url) -> Vec<Url> {
+  inboxes
+    .into_iter()
+    .filter(|i| i.host_str() != actor_url.host_str() || i.port() != actor_url.port())
+    .collect()
+}
+
 /// Handle a follow request from a remote person, adding the person as follower and returning an
-/// Accept activity.
+/// Accept activity. If the community has manual follow approval, the follow waits for a moderator
+/// instead, unless the person already follows it.
 async fn handle_follow(
   activity: AnyBase,
   person: Person,
@@ -189,17 +307,30 @@ async fn handle_follow(
   let follow = Follow::from_any_base(activity)?.context(location_info!())?;
   verify_activity_domains_valid(&follow, &person.actor_id(), false)?;
 
-  let community_follower_form = CommunityFollowerForm {
-    community_id: community.id,
-    person_id: person.id,
-    pending: false,
-  };
+  if community.manual_follow_approval {
+    let community_id = community.id;
+    let person_id = person.id;
+    let follow_activity_id = follow.id_unchecked().map(|id| id.to_owned().into());
+    let follower = blocking(&context.pool(), move |conn| {
+      CommunityFollower::follow_requested(conn, community_id, person_id, follow_activity_id)
+    })
+    .await??;
+    if follower.pending != Some(false) {
+      return Ok(HttpResponse::Ok().finish());
+    }
+  } else {
+    let community_follower_form = CommunityFollowerForm {
+      community_id: community.id,
+      person_id: person.id,
+      pending: false,
+    };
 
-  // This will fail if they're already a follower, but ignore the error.
-  blocking(&context.pool(), move |conn| {
-    CommunityFollower::follow(&conn, &community_follower_form).ok()
-  })
-  .await?;
+    // This will fail if they're already a follower, but ignore the error.
+    blocking(&context.pool(), move |conn| {
+      CommunityFollower::follow(&conn, &community_follower_form).ok()
+    })
+    .await?;
+  }
 
   community.send_accept_follow(follow, context).await?;
 
@@ -216,10 +347,17 @@ async fn handle_undo(
   let inner_kind = activity
     .object()
     .is_single_kind(&FollowType::Follow.to_string());
+  let is_undo_block = activity
+    .object()
+    .is_single_kind(&BlockType::Block.to_string());
   let any_base = activity.into_any_base()?;
   if inner_kind {
     handle_undo_follow(any_base, actor_url, to_community, &context).await?;
     Ok(false)
+  } else if is_undo_block {
+    let undo = Undo::from_any_base(any_base)?.context(location_info!())?;
+    receive_undo_block_user_for_community(context, undo, to_community, request_counter).await?;
+    Ok(true)
   } else {
     receive_undo_for_community(context, any_base, &actor_url, request_counter).await?;
     Ok(true)
@@ -261,7 +399,7 @@ async fn handle_undo_follow(
 
 pub(crate) async fn check_community_or_site_ban(
   person: &Person,
-  community_id: i32,
+  community_id: CommunityId,
   pool: &DbPool,
 ) -> Result<(), LemmyError> {
   if person.banned {
@@ -276,3 +414,92 @@ pub(crate) async fn check_community_or_site_ban(
 
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use super::{is_addressed_to_community, without_origin_inboxes, CommunityFanout};
+  use crate::inbox::{get_activity_to_and_cc, is_addressed_to_public};
+  use activitystreams::{activity::Create, prelude::*};
+  use url::Url;
+
+  fn create_addressed_to(to: &[&str], cc: &[&str]) -> Create {
+    let parse =
+      |urls: &[&str]| -> Vec<Url> { urls.iter().map(|u| Url::parse(u).unwrap()).collect() };
+    let mut create = Create::new(
+      Url::parse("https://remote.tld/u/alice").unwrap(),
+      Url::parse("https://remote.tld/post/1").unwrap(),
+    );
+    create.set_many_tos(parse(to)).set_many_ccs(parse(cc));
+    create
+  }
+
+  #[test]
+  fn test_community_addressing() {
+    let community = Url::parse("https://lemmy.tld/c/main").unwrap();
+    let followers = Url::parse("https://lemmy.tld/c/main/followers").unwrap();
+    let public = "https://www.w3.org/ns/activitystreams#Public";
+    let is_addressed = |create: Create| {
+      is_addressed_to_community(&get_activity_to_and_cc(&create), &community, &followers)
+    };
+
+    let to_community = create_addressed_to(&[public], &["https://lemmy.tld/c/main"]);
+    assert!(is_addressed(to_community));
+
+    let to_followers = create_addressed_to(&["https://lemmy.tld/c/main/followers"], &[public]);
+    assert!(is_addressed(to_followers));
+
+    let to_other_community = create_addressed_to(
+      &[public],
+      &[
+        "https://lemmy.tld/c/other",
+        "https://lemmy.tld/c/other/followers",
+        "https://other.tld/c/main",
+      ],
+    );
+    assert!(!is_addressed(to_other_community));
+  }
+
+  #[test]
+  fn test_public_addressing() {
+    let community = "https://lemmy.tld/c/main";
+    let is_public = |to: &str| is_addressed_to_public(&create_addressed_to(&[to], &[community]));
+
+    assert!(is_public("https://www.w3.org/ns/activitystreams#Public").is_ok());
+    assert!(is_public("as:Public").is_ok());
+    assert!(is_public("http://www.w3.org/ns/activitystreams#Public").is_ok());
+    assert!(is_public("https://lemmy.tld/c/main/followers").is_err());
+  }
+
+  #[test]
+  fn test_fanout_announces_once_per_inbox() {
+    let inbox = |s: &str| Url::parse(&format!("https://{}/inbox", s)).unwrap();
+    let mut fanout = CommunityFanout::default();
+
+    // Followers of the first community get the announce
+    let first = fanout.take_new_inboxes(vec![inbox("a.tld"), inbox("b.tld")]);
+    assert_eq!(vec![inbox("a.tld"), inbox("b.tld")], first);
+
+    // Instances following both communities are skipped for the second one
+    let second = fanout.take_new_inboxes(vec![inbox("b.tld"), inbox("c.tld")]);
+    assert_eq!(vec![inbox("c.tld")], second);
+  }
+
+  #[test]
+  fn test_announce_skips_origin_instance() {
+    let inbox = |s: &str| Url::parse(&format!("https://{}/inbox", s)).unwrap();
+    let actor = Url::parse("https://origin.tld/u/alice").unwrap();
+    let followers = vec![
+      inbox("a.tld"),
+      inbox("origin.tld"),
+      Url::parse("https://origin.tld/u/bob/inbox").unwrap(),
+      inbox("origin.tld:8536"),
+      inbox("b.tld"),
+    ];
+
+    let recipients = without_origin_inboxes(followers, &actor);
+    assert_eq!(
+      vec![inbox("a.tld"), inbox("origin.tld:8536"), inbox("b.tld")],
+      recipients
+    );
+  }
+}
@@ -2,6 +2,7 @@ use crate::{
   check_is_apub_id_valid,
   extensions::signatures::verify_signature,
   fetcher::get_or_fetch_and_upsert_actor,
+  log_community_federation,
   ActorType,
 };
 use activitystreams::{
@@ -14,15 +15,29 @@ use actix_web::HttpRequest;
 use anyhow::{anyhow, Context};
 use lemmy_api_structs::blocking;
 use lemmy_db_queries::{
-  source::{activity::Activity_, community::Community_},
+  source::{community::Community_, person::Person_, received_activity::ReceivedActivity_},
   ApubObject,
   DbPool,
 };
-use lemmy_db_schema::source::{activity::Activity, community::Community, person::Person};
+use lemmy_db_schema::{
+  source::{
+    community::Community,
+    community_federation_log::CommunityFederationLogForm,
+    person::Person,
+    received_activity::ReceivedActivity,
+  },
+  CommunityId,
+};
 use lemmy_utils::{location_info, settings::structs::Settings, LemmyError};
 use lemmy_websocket::LemmyContext;
+use log::error;
 use serde::Serialize;
-use std::fmt::Debug;
+use std::{
+  collections::HashMap,
+  fmt::Debug,
+  sync::Mutex,
+  time::{Duration, Instant},
+};
 use url::Url;
 
 pub mod community_inbox;
@@ -30,6 +45,14 @@ pub mod person_inbox;
 mod receive_for_community;
 pub mod shared_inbox;
 
+/// Rejected activities are logged at most once per this interval for each claimed actor domain
+static REJECTED_LOG_INTERVAL_SECONDS: u64 = 60;
+static REJECTED_LOG_MAX_DOMAINS: usize = 10_000;
+
+lazy_static! {
+  static ref REJECTED_LOGGED_AT: Mutex<HashMap<String, Instant>> = Mutex::new(HashMap::new());
+}
+
 pub(crate) fn get_activity_id<T, Kind>(activity: &T, creator_uri: &Url) -> Result<Url, LemmyError>
 where
   T: BaseExt<Kind> + Extends<Kind> + Debug,
@@ -46,16 +69,43 @@ pub(crate) async fn is_activity_already_known(
   activity_id: &Url,
 ) -> Result<bool, LemmyError> {
   let activity_id = activity_id.to_owned().into();
-  let existing = blocking(pool, move |conn| {
-    Activity::read_from_apub_id(&conn, &activity_id)
+  Ok(
+    blocking(pool, move |conn| {
+      ReceivedActivity::is_known(&conn, &activity_id)
+    })
+    .await??,
+  )
+}
+
+/// Updates the last activity of a remote person from an activity they sent. Activities sent by
+/// communities are ignored. Failures are only logged, as the activity can be handled regardless.
+pub(crate) async fn mark_actor_active(actor_id: &Url, pool: &DbPool) {
+  let apub_id = actor_id.to_owned().into();
+  let res = blocking(pool, move |conn| {
+    Person::mark_active_by_apub_id(&conn, &apub_id)
   })
-  .await?;
-  match existing {
-    Ok(_) => Ok(true),
-    Err(_) => Ok(false),
+  .await;
+  match res {
+    Ok(Ok(_)) => {}
+    Ok(Err(e)) => error!("Failed to mark {} as active: {}", actor_id, e),
+    Err(e) => error!("Failed to mark {} as active: {}", actor_id, e),
   }
 }
 
+/// Remembers the id of an activity which was received indirectly (eg inside an announce), so that
+/// the same activity announced by another community is not processed again.
+pub(crate) async fn remember_received_activity(
+  pool: &DbPool,
+  activity_id: &Url,
+) -> Result<(), LemmyError> {
+  let activity_id = activity_id.to_owned().into();
+  blocking(pool, move |conn| {
+    ReceivedActivity::insert(&conn, activity_id)
+  })
+  .await??;
+  Ok(())
+}
+
 pub(crate) fn get_activity_to_and_cc<T, Kind>(activity: &T) -> Vec<Url>
 where
   T: AsBase<Kind> + AsObject<Kind> + ActorAndObjectRefExt,
@@ -84,12 +134,20 @@ where
   to_and_cc
 }
 
+/// Other ways to write the public collection, which some software uses
+const PUBLIC_COLLECTION_SYNONYMS: [&str; 2] =
+  ["as:Public", "http://www.w3.org/ns/activitystreams#Public"];
+
+pub(crate) fn is_public_collection(url: &Url) -> bool {
+  url == &public() || PUBLIC_COLLECTION_SYNONYMS.contains(&url.as_str())
+}
+
 pub(crate) fn is_addressed_to_public<T, Kind>(activity: &T) -> Result<(), LemmyError>
 where
   T: AsBase<Kind> + AsObject<Kind> + ActorAndObjectRefExt,
 {
   let to_and_cc = get_activity_to_and_cc(activity);
-  if to_and_cc.contains(&public()) {
+  if to_and_cc.iter().any(is_public_collection) {
     Ok(())
   } else {
     Err(anyhow!("Activity is not addressed to public").into())
@@ -118,6 +176,64 @@ where
   Ok(actor)
 }
 
+/// Records an activity for local communities which was rejected before it could be handled, eg
+/// because of an invalid signature. The actor domain is taken from the unverified activity, so
+/// anyone can send these. To keep them from flooding the log, only one rejection per domain is
+/// recorded every minute.
+pub(in crate::inbox) async fn log_rejected_community_activity<T, Kind>(
+  activity: &T,
+  community_ids: Vec<CommunityId>,
+  error: &LemmyError,
+  pool: &DbPool,
+) where
+  T: AsBase<Kind> + ActorAndObjectRefExt,
+  Kind: Debug,
+{
+  let kind = activity.kind().map(|k| format!("{:?}", k));
+  let domain = activity
+    .actor()
+    .ok()
+    .and_then(|a| a.as_single_xsd_any_uri())
+    .and_then(|u| u.host_str())
+    .unwrap_or_default()
+    .to_owned();
+  if !rejection_log_allowed(&domain) {
+    return;
+  }
+  for community_id in community_ids {
+    let form = CommunityFederationLogForm {
+      community_id,
+      inbound: true,
+      kind: kind.to_owned(),
+      domain: domain.to_owned(),
+      error: Some(error.to_string()),
+    };
+    log_community_federation(form, pool).await;
+  }
+}
+
+/// Returns true if no rejection from `domain` was logged within the interval, and remembers this
+/// one.
+fn rejection_log_allowed(domain: &str) -> bool {
+  let interval = Duration::from_secs(REJECTED_LOG_INTERVAL_SECONDS);
+  let mut logged_at = REJECTED_LOGGED_AT
+    .lock()
+    .expect("lock rejected activity log");
+  if let Some(last) = logged_at.get(domain) {
+    if last.elapsed() < interval {
+      return false;
+    }
+  }
+  if logged_at.len() >= REJECTED_LOG_MAX_DOMAINS {
+    logged_at.retain(|_, last| last.elapsed() < interval);
+    if logged_at.len() >= REJECTED_LOG_MAX_DOMAINS {
+      return false;
+    }
+  }
+  logged_at.insert(domain.to_owned(), Instant::now());
+  true
+}
+
 /// Returns true if `to_and_cc` contains at least one local user.
 pub(crate) async fn is_addressed_to_local_person(
   to_and_cc: &[Url],
@@ -160,6 +276,26 @@ pub(crate) async fn is_addressed_to_community_followers(
   Ok(None)
 }
 
+/// Receiving an activity can fail because the fetch budget for one of the involved instances is used
+/// up. In that case the activity is removed from the database again, so that it gets processed when
+/// the sending instance retries the delivery, instead of being ignored as already known.
+pub(in crate::inbox) async fn forget_activity_if_retryable<T>(
+  result: Result<T, LemmyError>,
+  activity_id: &Url,
+  pool: &DbPool,
+) -> Result<T, LemmyError> {
+  if let Err(e) = &result {
+    if e.is_fetch_budget_exhausted() {
+      let activity_id = activity_id.to_owned().into();
+      blocking(pool, move |conn| {
+        ReceivedActivity::delete(&conn, &activity_id)
+      })
+      .await??;
+    }
+  }
+  result
+}
+
 pub(in crate::inbox) fn assert_activity_not_local<T, Kind>(activity: &T) -> Result<(), LemmyError>
 where
   T: BaseExt<Kind> + Debug,
@@ -178,3 +314,16 @@ where
   }
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use super::rejection_log_allowed;
+
+  #[test]
+  fn test_rejection_log_is_rate_limited_per_domain() {
+    assert!(rejection_log_allowed("flood.tld"));
+    assert!(!rejection_log_allowed("flood.tld"));
+    assert!(!rejection_log_allowed("flood.tld"));
+    assert!(rejection_log_allowed("other.tld"));
+  }
+}
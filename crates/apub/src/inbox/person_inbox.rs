@@ -2,11 +2,14 @@ use crate::{
   activities::receive::{
     comment::{receive_create_comment, receive_update_comment},
     community::{
+      receive_block_user_for_community,
       receive_delete_community,
       receive_remove_community,
+      receive_undo_block_user_for_community,
       receive_undo_delete_community,
       receive_undo_remove_community,
     },
+    person::receive_update_person,
     private_message::{
       receive_create_private_message,
       receive_delete_private_message,
@@ -20,6 +23,7 @@ use crate::{
   fetcher::community::get_or_fetch_and_upsert_community,
   inbox::{
     assert_activity_not_local,
+    forget_activity_if_retryable,
     get_activity_id,
     get_activity_to_and_cc,
     inbox_verify_http_signature,
@@ -27,6 +31,7 @@ use crate::{
     is_addressed_to_community_followers,
     is_addressed_to_local_person,
     is_addressed_to_public,
+    mark_actor_active,
     receive_for_community::{
       receive_create_for_community,
       receive_delete_for_community,
@@ -36,12 +41,25 @@ use crate::{
       receive_undo_for_community,
       receive_update_for_community,
     },
+    remember_received_activity,
   },
   insert_activity,
+  log_community_federation,
   ActorType,
 };
 use activitystreams::{
-  activity::{Accept, ActorAndObject, Announce, Create, Delete, Follow, Undo, Update},
+  activity::{
+    kind::BlockType,
+    Accept,
+    ActorAndObject,
+    Announce,
+    Create,
+    Delete,
+    Follow,
+    Reject,
+    Undo,
+    Update,
+  },
   base::AnyBase,
   prelude::*,
 };
@@ -50,10 +68,14 @@ use anyhow::{anyhow, Context};
 use diesel::NotFound;
 use lemmy_api_structs::blocking;
 use lemmy_db_queries::{source::person::Person_, ApubObject, Followable};
-use lemmy_db_schema::source::{
-  community::{Community, CommunityFollower},
-  person::Person,
-  private_message::PrivateMessage,
+use lemmy_db_schema::{
+  source::{
+    community::{Community, CommunityFollower, CommunityFollowerForm},
+    community_federation_log::CommunityFederationLogForm,
+    person::Person,
+    private_message::PrivateMessage,
+  },
+  DbUrl,
 };
 use lemmy_utils::{location_info, LemmyError};
 use lemmy_websocket::LemmyContext;
@@ -68,12 +90,14 @@ use url::Url;
 #[serde(rename_all = "PascalCase")]
 pub enum PersonValidTypes {
   Accept,   // community accepted our follow request
+  Reject,   // community rejected our follow request
   Create,   // create private message
-  Update,   // edit private message
+  Update,   // edit private message, or person profile changed
   Delete,   // private message or community deleted by creator
   Undo,     // private message or community restored
   Remove,   // community removed by admin
   Announce, // post, comment or vote in community
+  Block,    // person banned from community
 }
 
 pub type PersonAcceptedActivities = ActorAndObject<PersonValidTypes>;
@@ -110,6 +134,7 @@ pub async fn person_inbox(
 
   assert_activity_not_local(&activity)?;
   insert_activity(&activity_id, activity.clone(), false, true, context.pool()).await?;
+  mark_actor_active(&actor.actor_id(), context.pool()).await;
 
   debug!(
     "Person {} received activity {:?} from {}",
@@ -118,17 +143,18 @@ pub async fn person_inbox(
     &actor.actor_id()
   );
 
-  person_receive_message(
+  let res = person_receive_message(
     activity.clone(),
     Some(person.clone()),
     actor.as_ref(),
     &context,
     request_counter,
   )
-  .await
+  .await;
+  forget_activity_if_retryable(res, &activity_id, context.pool()).await
 }
 
-/// Receives Accept/Follow, Announce, private messages and community (undo) remove, (undo) delete
+/// Receives Accept/Follow, Reject/Follow, Announce, private messages and community (undo) remove, (undo) delete
 pub(crate) async fn person_receive_message(
   activity: PersonAcceptedActivities,
   to_person: Option<Person>,
@@ -152,6 +178,16 @@ pub(crate) async fn person_receive_message(
       )
       .await?;
     }
+    PersonValidTypes::Reject => {
+      receive_reject(
+        &context,
+        any_base,
+        actor,
+        to_person.expect("person provided"),
+        request_counter,
+      )
+      .await?;
+    }
     PersonValidTypes::Announce => {
       receive_announce(&context, any_base, actor, request_counter).await?
     }
@@ -166,6 +202,11 @@ pub(crate) async fn person_receive_message(
     }
     PersonValidTypes::Undo => receive_undo(context, any_base, &actor_url, request_counter).await?,
     PersonValidTypes::Remove => receive_remove_community(&context, any_base, &actor_url).await?,
+    PersonValidTypes::Block => {
+      let community =
+        get_or_fetch_and_upsert_community(&actor_url, context, request_counter).await?;
+      receive_block_user_for_community(context, any_base, &community, request_counter).await?
+    }
   };
 
   // TODO: would be logical to move websocket notification code here
@@ -243,6 +284,43 @@ async fn receive_accept(
   Ok(())
 }
 
+/// Handle rejected follows, by removing the pending follow.
+async fn receive_reject(
+  context: &LemmyContext,
+  activity: AnyBase,
+  actor: &dyn ActorType,
+  person: Person,
+  request_counter: &mut i32,
+) -> Result<(), LemmyError> {
+  let reject = Reject::from_any_base(activity)?.context(location_info!())?;
+  verify_activity_domains_valid(&reject, &actor.actor_id(), false)?;
+
+  let object = reject.object().to_owned().one().context(location_info!())?;
+  let follow = Follow::from_any_base(object)?.context(location_info!())?;
+  verify_activity_domains_valid(&follow, &person.actor_id(), false)?;
+
+  let community_uri = reject
+    .actor()?
+    .to_owned()
+    .single_xsd_any_uri()
+    .context(location_info!())?;
+
+  let community =
+    get_or_fetch_and_upsert_community(&community_uri, context, request_counter).await?;
+
+  let community_follower_form = CommunityFollowerForm {
+    community_id: community.id,
+    person_id: person.id,
+    pending: false,
+  };
+  blocking(&context.pool(), move |conn| {
+    CommunityFollower::unfollow(conn, &community_follower_form)
+  })
+  .await??;
+
+  Ok(())
+}
+
 #[derive(EnumString)]
 enum AnnouncableActivities {
   Create,
@@ -252,14 +330,45 @@ enum AnnouncableActivities {
   Delete,
   Remove,
   Undo,
+  Block,
 }
 
-/// Takes an announce and passes the inner activity to the appropriate handler.
+/// Takes an announce and passes the inner activity to the appropriate handler. Remote communities
+/// deliver their activities as announces, so these are recorded in the federation log of the
+/// community, accepted or not.
 pub async fn receive_announce(
   context: &LemmyContext,
   activity: AnyBase,
   actor: &dyn ActorType,
   request_counter: &mut i32,
+) -> Result<(), LemmyError> {
+  let kind = activity.kind_str().map(|k| k.to_owned());
+  let actor_id = actor.actor_id();
+  let res = handle_announce(context, activity, actor, request_counter).await;
+
+  let community_apub_id: DbUrl = actor_id.to_owned().into();
+  let community = blocking(context.pool(), move |conn| {
+    Community::read_from_apub_id(conn, &community_apub_id)
+  })
+  .await?;
+  if let Ok(community) = community {
+    let form = CommunityFederationLogForm {
+      community_id: community.id,
+      inbound: true,
+      kind,
+      domain: actor_id.host_str().unwrap_or_default().to_owned(),
+      error: res.as_ref().err().map(|e| e.to_string()),
+    };
+    log_community_federation(form, context.pool()).await;
+  }
+  res
+}
+
+async fn handle_announce(
+  context: &LemmyContext,
+  activity: AnyBase,
+  actor: &dyn ActorType,
+  request_counter: &mut i32,
 ) -> Result<(), LemmyError> {
   let announce = Announce::from_any_base(activity)?.context(location_info!())?;
   verify_activity_domains_valid(&announce, &actor.actor_id(), false)?;
@@ -282,9 +391,11 @@ pub async fn receive_announce(
   }
 
   use AnnouncableActivities::*;
-  match kind {
+  let res = match kind {
     Some(Create) => {
-      receive_create_for_community(context, inner_activity, &inner_id, request_counter).await
+      receive_create_for_community(context, inner_activity, &inner_id, request_counter)
+        .await
+        .map(|_| ())
     }
     Some(Update) => {
       receive_update_for_community(context, inner_activity, &inner_id, request_counter).await
@@ -293,15 +404,34 @@ pub async fn receive_announce(
       receive_like_for_community(context, inner_activity, &inner_id, request_counter).await
     }
     Some(Dislike) => {
-      receive_dislike_for_community(context, inner_activity, &inner_id, request_counter).await
+      receive_dislike_for_community(context, inner_activity, &inner_id, request_counter)
+        .await
+        .map(|_| ())
     }
     Some(Delete) => receive_delete_for_community(context, inner_activity, &inner_id).await,
     Some(Remove) => receive_remove_for_community(context, inner_activity, &inner_id).await,
     Some(Undo) => {
-      receive_undo_for_community(context, inner_activity, &inner_id, request_counter).await
+      let undo = activitystreams::activity::Undo::from_any_base(inner_activity.clone())?
+        .context(location_info!())?;
+      if undo.object().is_single_kind(&BlockType::Block.to_string()) {
+        let community =
+          get_or_fetch_and_upsert_community(&actor.actor_id(), context, request_counter).await?;
+        receive_undo_block_user_for_community(context, undo, &community, request_counter).await
+      } else {
+        receive_undo_for_community(context, inner_activity, &inner_id, request_counter).await
+      }
+    }
+    Some(Block) => {
+      let community =
+        get_or_fetch_and_upsert_community(&actor.actor_id(), context, request_counter).await?;
+      receive_block_user_for_community(context, inner_activity, &community, request_counter).await
     }
     _ => receive_unhandled_activity(inner_activity),
+  };
+  if res.is_ok() {
+    remember_received_activity(context.pool(), &inner_id).await?;
   }
+  res
 }
 
 async fn receive_create(
@@ -313,7 +443,9 @@ async fn receive_create(
   let create = Create::from_any_base(activity)?.context(location_info!())?;
   verify_activity_domains_valid(&create, &expected_domain, true)?;
   if is_addressed_to_public(&create).is_ok() {
-    receive_create_comment(create, context, request_counter).await
+    receive_create_comment(create, context, request_counter)
+      .await
+      .map(|_| ())
   } else {
     receive_create_private_message(&context, create, expected_domain, request_counter).await
   }
@@ -325,7 +457,10 @@ async fn receive_update(
   expected_domain: Url,
   request_counter: &mut i32,
 ) -> Result<(), LemmyError> {
-  let update = Update::from_any_base(activity)?.context(location_info!())?;
+  let update = Update::from_any_base(activity.clone())?.context(location_info!())?;
+  if update.object().is_single_kind("Person") {
+    return receive_update_person(context, activity, &expected_domain, request_counter).await;
+  }
   verify_activity_domains_valid(&update, &expected_domain, true)?;
   if is_addressed_to_public(&update).is_ok() {
     receive_update_comment(update, context, request_counter).await
@@ -386,6 +521,11 @@ async fn receive_undo(
       }
     }
     Some("Remove") => receive_undo_remove_community(context, undo, expected_domain).await,
+    Some("Block") => {
+      let community =
+        get_or_fetch_and_upsert_community(expected_domain, context, request_counter).await?;
+      receive_undo_block_user_for_community(context, undo, &community, request_counter).await
+    }
     _ => receive_unhandled_activity(undo),
   }
 }
@@ -418,3 +558,132 @@ async fn find_community_or_private_message_by_id(
 
   Err(NotFound.into())
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::{inbox::person_inbox::receive_reject, test::init_context, ActorType};
+  use activitystreams::{
+    activity::{Follow, Reject},
+    prelude::*,
+  };
+  use lemmy_db_queries::{
+    establish_unpooled_connection,
+    source::community::CommunityFollower_,
+    Crud,
+    Followable,
+  };
+  use lemmy_db_schema::source::{
+    community::{Community, CommunityFollower, CommunityFollowerForm, CommunityForm},
+    person::{Person, PersonForm},
+  };
+  use serial_test::serial;
+
+  #[test]
+  #[serial]
+  fn test_receive_reject() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "rejected_follower".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: Some(true),
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "rejecting_community".into(),
+      creator_id: inserted_person.id,
+      title: "nada".to_owned(),
+      description: None,
+      nsfw: false,
+      removed: None,
+      deleted: None,
+      updated: None,
+      actor_id: None,
+      local: false,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+    let follower_form = CommunityFollowerForm {
+      community_id: inserted_community.id,
+      person_id: inserted_person.id,
+      pending: true,
+    };
+    CommunityFollower::follow(&conn, &follower_form).unwrap();
+
+    let reject = |follow_id: &str| {
+      let mut follow = Follow::new(inserted_person.actor_id(), inserted_community.actor_id());
+      follow.set_id(inserted_person.actor_id().join(follow_id).unwrap());
+      let mut reject = Reject::new(
+        inserted_community.actor_id(),
+        follow.into_any_base().unwrap(),
+      );
+      reject.set_id(
+        inserted_community
+          .actor_id()
+          .join("activities/reject/1")
+          .unwrap(),
+      );
+      reject.into_any_base().unwrap()
+    };
+
+    let mut system = actix_rt::System::new("test_receive_reject");
+    let (foreign_follow, rejected) = system.block_on(async {
+      let context = init_context();
+      // Only our own follows can be rejected
+      let foreign_follow = receive_reject(
+        &context,
+        reject("https://example.com/activities/follow/1"),
+        &inserted_community,
+        inserted_person.to_owned(),
+        &mut 0,
+      )
+      .await;
+      let rejected = receive_reject(
+        &context,
+        reject("activities/follow/1"),
+        &inserted_community,
+        inserted_person.to_owned(),
+        &mut 0,
+      )
+      .await;
+      (foreign_follow, rejected)
+    });
+    let follow = CommunityFollower::read_follow(&conn, inserted_community.id, inserted_person.id);
+
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert!(foreign_follow.is_err());
+    assert!(rejected.is_ok());
+    assert!(matches!(follow, Err(diesel::NotFound)));
+  }
+}
@@ -14,6 +14,7 @@ use crate::{
       receive_undo_like_comment,
       receive_undo_remove_comment,
     },
+    get_actor_as_person,
     post::{
       receive_create_post,
       receive_delete_post,
@@ -31,9 +32,12 @@ use crate::{
     receive_unhandled_activity,
     verify_activity_domains_valid,
   },
+  extensions::vote_extension::{VoteExtension, VOTE_ACTOR_NAME},
   fetcher::objects::{get_or_fetch_and_insert_comment, get_or_fetch_and_insert_post},
   find_post_or_comment_by_id,
   inbox::is_addressed_to_public,
+  DislikeExt,
+  LikeExt,
   PostOrComment,
 };
 use activitystreams::{
@@ -44,8 +48,11 @@ use activitystreams::{
 use anyhow::Context;
 use diesel::result::Error::NotFound;
 use lemmy_api_structs::blocking;
-use lemmy_db_queries::Crud;
-use lemmy_db_schema::source::site::Site;
+use lemmy_db_queries::{source::instance::Instance_, Crud};
+use lemmy_db_schema::{
+  source::{community::Community, instance::Instance, person::Person, post::Post, site::Site},
+  PersonId,
+};
 use lemmy_utils::{location_info, LemmyError};
 use lemmy_websocket::LemmyContext;
 use strum_macros::EnumString;
@@ -60,13 +67,14 @@ enum PageOrNote {
 /// This file is for post/comment activities received by the community, and for post/comment
 ///       activities announced by the community and received by the person.
 
-/// A post or comment being created
+/// A post or comment being created. Returns `false` if it was removed as spam, so that it isn't
+/// announced.
 pub(in crate::inbox) async fn receive_create_for_community(
   context: &LemmyContext,
   activity: AnyBase,
   expected_domain: &Url,
   request_counter: &mut i32,
-) -> Result<(), LemmyError> {
+) -> Result<bool, LemmyError> {
   let create = Create::from_any_base(activity)?.context(location_info!())?;
   verify_activity_domains_valid(&create, &expected_domain, true)?;
   is_addressed_to_public(&create)?;
@@ -78,7 +86,7 @@ pub(in crate::inbox) async fn receive_create_for_community(
   match kind {
     Some(PageOrNote::Page) => receive_create_post(create, context, request_counter).await,
     Some(PageOrNote::Note) => receive_create_comment(create, context, request_counter).await,
-    _ => receive_unhandled_activity(create),
+    _ => receive_unhandled_activity(create).map(|_| true),
   }
 }
 
@@ -90,7 +98,6 @@ pub(in crate::inbox) async fn receive_update_for_community(
   request_counter: &mut i32,
 ) -> Result<(), LemmyError> {
   let update = Update::from_any_base(activity)?.context(location_info!())?;
-  verify_activity_domains_valid(&update, &expected_domain, true)?;
   is_addressed_to_public(&update)?;
 
   let kind = update
@@ -98,12 +105,62 @@ pub(in crate::inbox) async fn receive_update_for_community(
     .as_single_kind_str()
     .and_then(|s| s.parse().ok());
   match kind {
-    Some(PageOrNote::Page) => receive_update_post(update, context, request_counter).await,
-    Some(PageOrNote::Note) => receive_update_comment(update, context, request_counter).await,
+    Some(PageOrNote::Page) => {
+      verify_activity_domains_valid(&update, &expected_domain, true)?;
+      receive_update_post(update, context, request_counter).await
+    }
+    // Mods can sticky the comments of persons from other instances, this is checked when receiving
+    Some(PageOrNote::Note) => {
+      verify_activity_domains_valid(&update, &expected_domain, false)?;
+      receive_update_comment(update, context, request_counter).await
+    }
     _ => receive_unhandled_activity(update),
   }
 }
 
+/// Returns the vote actor which is stored for the instance of `actor`, if the activity is an
+/// aggregate. A Lemmy instance without one gets `actor` stored if it has the name of the vote
+/// actor, which no user of Lemmy can register. Other software may allow that name, so their
+/// aggregates are never counted.
+async fn get_vote_actor_id(
+  ext: &VoteExtension,
+  actor: &Person,
+  context: &LemmyContext,
+) -> Result<Option<PersonId>, LemmyError> {
+  if ext.count.is_none() {
+    return Ok(None);
+  }
+  let domain = actor
+    .actor_id
+    .to_owned()
+    .into_inner()
+    .domain()
+    .context(location_info!())?
+    .to_owned();
+  let instance = blocking(context.pool(), move |conn| {
+    Instance::read_from_domain(conn, &domain).ok()
+  })
+  .await?;
+  let instance = match instance {
+    Some(instance) => instance,
+    None => return Ok(None),
+  };
+  if instance.vote_actor_id.is_some() {
+    return Ok(instance.vote_actor_id);
+  }
+  if instance.software.as_deref() != Some("lemmy") || actor.name != VOTE_ACTOR_NAME {
+    return Ok(None);
+  }
+  // A concurrent aggregate may have stored another actor in the meantime
+  let person_id = actor.id;
+  let instance = blocking(context.pool(), move |conn| {
+    Instance::set_vote_actor(conn, instance.id, person_id)?;
+    Instance::read_from_domain(conn, &instance.domain)
+  })
+  .await??;
+  Ok(instance.vote_actor_id)
+}
+
 /// A post or comment being upvoted
 pub(in crate::inbox) async fn receive_like_for_community(
   context: &LemmyContext,
@@ -111,53 +168,80 @@ pub(in crate::inbox) async fn receive_like_for_community(
   expected_domain: &Url,
   request_counter: &mut i32,
 ) -> Result<(), LemmyError> {
-  let like = Like::from_any_base(activity)?.context(location_info!())?;
+  let like = LikeExt::from_any_base(activity)?.context(location_info!())?;
+  let (like, ext) = (like.inner, like.ext_one);
   verify_activity_domains_valid(&like, &expected_domain, false)?;
   is_addressed_to_public(&like)?;
+  let actor = get_actor_as_person(&like, context, request_counter).await?;
+  let vote_actor_id = get_vote_actor_id(&ext, &actor, context).await?;
+  let votes = ext.votes(&actor, vote_actor_id);
 
   let object_id = like
     .object()
     .as_single_xsd_any_uri()
     .context(location_info!())?;
   match fetch_post_or_comment_by_id(&object_id, context, request_counter).await? {
-    PostOrComment::Post(post) => receive_like_post(like, *post, context, request_counter).await,
+    PostOrComment::Post(post) => {
+      receive_like_post(like, votes, *post, context, request_counter).await
+    }
     PostOrComment::Comment(comment) => {
-      receive_like_comment(like, *comment, context, request_counter).await
+      receive_like_comment(like, votes, *comment, context, request_counter).await
     }
   }
 }
 
-/// A post or comment being downvoted
+/// A post or comment being downvoted. If downvotes are disabled on this instance or in the
+/// community of the post, the activity is dropped and `false` is returned, so that it isn't
+/// announced either.
 pub(in crate::inbox) async fn receive_dislike_for_community(
   context: &LemmyContext,
   activity: AnyBase,
   expected_domain: &Url,
   request_counter: &mut i32,
-) -> Result<(), LemmyError> {
+) -> Result<bool, LemmyError> {
   let enable_downvotes = blocking(context.pool(), move |conn| {
     Site::read(conn, 1).map(|s| s.enable_downvotes)
   })
   .await??;
   if !enable_downvotes {
-    return Ok(());
+    return Ok(false);
   }
 
-  let dislike = Dislike::from_any_base(activity)?.context(location_info!())?;
+  let dislike = DislikeExt::from_any_base(activity)?.context(location_info!())?;
+  let (dislike, ext) = (dislike.inner, dislike.ext_one);
   verify_activity_domains_valid(&dislike, &expected_domain, false)?;
   is_addressed_to_public(&dislike)?;
+  let actor = get_actor_as_person(&dislike, context, request_counter).await?;
+  let vote_actor_id = get_vote_actor_id(&ext, &actor, context).await?;
+  let votes = ext.votes(&actor, vote_actor_id);
 
   let object_id = dislike
     .object()
     .as_single_xsd_any_uri()
     .context(location_info!())?;
-  match fetch_post_or_comment_by_id(&object_id, context, request_counter).await? {
+  let post_or_comment = fetch_post_or_comment_by_id(&object_id, context, request_counter).await?;
+  let post_id = match &post_or_comment {
+    PostOrComment::Post(post) => post.id,
+    PostOrComment::Comment(comment) => comment.post_id,
+  };
+  let community_downvotes_disabled = blocking(context.pool(), move |conn| {
+    let post = Post::read(conn, post_id)?;
+    Community::read(conn, post.community_id).map(|c| c.downvotes_disabled)
+  })
+  .await??;
+  if community_downvotes_disabled {
+    return Ok(false);
+  }
+
+  match post_or_comment {
     PostOrComment::Post(post) => {
-      receive_dislike_post(dislike, *post, context, request_counter).await
+      receive_dislike_post(dislike, votes, *post, context, request_counter).await?
     }
     PostOrComment::Comment(comment) => {
-      receive_dislike_comment(dislike, *comment, context, request_counter).await
+      receive_dislike_comment(dislike, votes, *comment, context, request_counter).await?
     }
   }
+  Ok(true)
 }
 
 /// A post or comment being deleted by its creator
@@ -1,22 +1,27 @@
 use crate::{
+  activities::receive::person::receive_update_person,
   inbox::{
     assert_activity_not_local,
-    community_inbox::{community_receive_message, CommunityAcceptedActivities},
+    community_inbox::{community_receive_message, CommunityAcceptedActivities, CommunityFanout},
+    forget_activity_if_retryable,
     get_activity_id,
     get_activity_to_and_cc,
     inbox_verify_http_signature,
     is_activity_already_known,
     is_addressed_to_community_followers,
     is_addressed_to_local_person,
+    log_rejected_community_activity,
+    mark_actor_active,
     person_inbox::{person_receive_message, PersonAcceptedActivities},
   },
   insert_activity,
+  ActorType,
 };
 use activitystreams::{activity::ActorAndObject, prelude::*};
 use actix_web::{web, HttpRequest, HttpResponse};
 use anyhow::Context;
 use lemmy_api_structs::blocking;
-use lemmy_db_queries::{ApubObject, DbPool};
+use lemmy_db_queries::{source::community::Community_, DbPool};
 use lemmy_db_schema::source::community::Community;
 use lemmy_utils::{location_info, LemmyError};
 use lemmy_websocket::LemmyContext;
@@ -36,6 +41,7 @@ pub enum ValidTypes {
   Undo,
   Remove,
   Announce,
+  Block,
 }
 
 // TODO: this isnt entirely correct, cause some of these receive are not ActorAndObject,
@@ -51,7 +57,20 @@ pub async fn shared_inbox(
   let activity = input.into_inner();
   // First of all check the http signature
   let request_counter = &mut 0;
-  let actor = inbox_verify_http_signature(&activity, &context, request, request_counter).await?;
+  let actor = match inbox_verify_http_signature(&activity, &context, request, request_counter).await
+  {
+    Ok(actor) => actor,
+    Err(e) => {
+      let to_and_cc = get_activity_to_and_cc(&activity);
+      if let Ok(communities) =
+        extract_local_communities_from_destinations(&to_and_cc, context.pool()).await
+      {
+        let community_ids = communities.iter().map(|c| c.id).collect();
+        log_rejected_community_activity(&activity, community_ids, &e, context.pool()).await;
+      }
+      return Err(e);
+    }
+  };
 
   // Do nothing if we received the same activity before
   let actor_id = actor.actor_id();
@@ -64,8 +83,33 @@ pub async fn shared_inbox(
   // Log the activity, so we avoid receiving and parsing it twice. Note that this could still happen
   // if we receive the same activity twice in very quick succession.
   insert_activity(&activity_id, activity.clone(), false, true, context.pool()).await?;
+  mark_actor_active(&actor_id, context.pool()).await;
 
+  let res = receive_shared_activity(activity, actor.as_ref(), &context, request_counter).await;
+  forget_activity_if_retryable(res, &activity_id, context.pool()).await
+}
+
+/// Passes an activity from the shared inbox on to the community or person inbox handler, depending
+/// on who it is addressed to.
+async fn receive_shared_activity(
+  activity: AcceptedActivities,
+  actor: &dyn ActorType,
+  context: &LemmyContext,
+  request_counter: &mut i32,
+) -> Result<HttpResponse, LemmyError> {
   let activity_any_base = activity.clone().into_any_base()?;
+  // Profile updates are only addressed to public, so they cant be routed by their recipients
+  if activity.kind() == Some(&ValidTypes::Update) && activity.object().is_single_kind("Person") {
+    receive_update_person(
+      context,
+      activity_any_base,
+      &actor.actor_id(),
+      request_counter,
+    )
+    .await?;
+    return Ok(HttpResponse::Ok().finish());
+  }
+
   let mut res: Option<HttpResponse> = None;
   let to_and_cc = get_activity_to_and_cc(&activity);
   // Handle community first, so in case the sender is banned by the community, it will error out.
@@ -74,49 +118,40 @@ pub async fn shared_inbox(
   // Note that an activity can be addressed to a community and to a person (or multiple persons) at the
   // same time. In this case we still only handle it once, to avoid duplicate websocket
   // notifications.
-  let community = extract_local_community_from_destinations(&to_and_cc, context.pool()).await?;
-  if let Some(community) = community {
-    let community_activity = CommunityAcceptedActivities::from_any_base(activity_any_base.clone())?
-      .context(location_info!())?;
-    res = Some(
-      community_receive_message(
-        community_activity,
-        community,
-        actor.as_ref(),
-        &context,
-        request_counter,
-      )
-      .await?,
-    );
+  let communities = extract_local_communities_from_destinations(&to_and_cc, context.pool()).await?;
+  if !communities.is_empty() {
+    // The activity is processed once, and then announced by each of the addressed communities
+    let mut fanout = CommunityFanout::default();
+    for community in communities {
+      let community_activity =
+        CommunityAcceptedActivities::from_any_base(activity_any_base.clone())?
+          .context(location_info!())?;
+      res = Some(
+        community_receive_message(
+          community_activity,
+          community,
+          actor,
+          context,
+          request_counter,
+          &mut fanout,
+        )
+        .await?,
+      );
+    }
   } else if is_addressed_to_local_person(&to_and_cc, context.pool()).await? {
     let person_activity = PersonAcceptedActivities::from_any_base(activity_any_base.clone())?
       .context(location_info!())?;
     // `to_person` is only used for follow activities (which we dont receive here), so no need to pass
     // it in
-    person_receive_message(
-      person_activity,
-      None,
-      actor.as_ref(),
-      &context,
-      request_counter,
-    )
-    .await?;
+    person_receive_message(person_activity, None, actor, context, request_counter).await?;
   } else if is_addressed_to_community_followers(&to_and_cc, context.pool())
     .await?
     .is_some()
   {
     let person_activity = PersonAcceptedActivities::from_any_base(activity_any_base.clone())?
       .context(location_info!())?;
-    res = Some(
-      person_receive_message(
-        person_activity,
-        None,
-        actor.as_ref(),
-        &context,
-        request_counter,
-      )
-      .await?,
-    );
+    res =
+      Some(person_receive_message(person_activity, None, actor, context, request_counter).await?);
   }
 
   // If none of those, throw an error
@@ -127,26 +162,16 @@ pub async fn shared_inbox(
   }
 }
 
-/// If `to_and_cc` contains the ID of a local community, return that community, otherwise return
-/// None.
-///
-/// This doesnt handle the case where an activity is addressed to multiple communities (because
-/// Lemmy doesnt generate such activities).
-async fn extract_local_community_from_destinations(
+/// Returns all local communities which are contained in `to_and_cc`. Other software may address a
+/// single activity to multiple communities.
+async fn extract_local_communities_from_destinations(
   to_and_cc: &[Url],
   pool: &DbPool,
-) -> Result<Option<Community>, LemmyError> {
-  for url in to_and_cc {
-    let url = url.to_owned();
-    let community = blocking(&pool, move |conn| {
-      Community::read_from_apub_id(&conn, &url.into())
-    })
-    .await?;
-    if let Ok(c) = community {
-      if c.local {
-        return Ok(Some(c));
-      }
-    }
-  }
-  Ok(None)
+) -> Result<Vec<Community>, LemmyError> {
+  let apub_ids = to_and_cc.iter().map(|u| u.to_owned().into()).collect();
+  let communities = blocking(&pool, move |conn| {
+    Community::read_local_from_apub_ids(&conn, apub_ids)
+  })
+  .await??;
+  Ok(communities)
 }
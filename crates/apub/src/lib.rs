@@ -9,14 +9,26 @@ pub mod http;
 pub mod inbox;
 pub mod objects;
 pub mod routes;
+#[cfg(test)]
+mod test;
+
+pub use crate::activities::send::{
+  comment::send_create_comment_without_mentions,
+  community::answer_follow_request,
+  person::resend_pending_follows,
+};
 
 use crate::extensions::{
+  block_extension::BlockExtension,
   group_extensions::GroupExtension,
+  note_extension::NoteExtension,
   page_extension::PageExtension,
+  person_extension::PersonExtension,
   signatures::{PublicKey, PublicKeyExtension},
+  vote_extension::VoteExtension,
 };
 use activitystreams::{
-  activity::Follow,
+  activity::{Block, Dislike, Follow, Like},
   actor::{ApActor, Group, Person},
   base::AnyBase,
   object::{ApObject, Note, Page},
@@ -25,20 +37,37 @@ use activitystreams_ext::{Ext1, Ext2};
 use anyhow::{anyhow, Context};
 use diesel::NotFound;
 use lemmy_api_structs::blocking;
-use lemmy_db_queries::{source::activity::Activity_, ApubObject, DbPool};
+use lemmy_db_queries::{
+  source::{
+    activity::Activity_,
+    community_federation_log::CommunityFederationLog_,
+    received_activity::ReceivedActivity_,
+  },
+  ApubObject,
+  DbPool,
+};
 use lemmy_db_schema::{
   source::{
     activity::Activity,
     comment::Comment,
     community::Community,
+    community_federation_log::{CommunityFederationLog, CommunityFederationLogForm},
     person::Person as DbPerson,
     post::Post,
     private_message::PrivateMessage,
+    received_activity::ReceivedActivity,
   },
+  CommunityId,
   DbUrl,
 };
-use lemmy_utils::{location_info, settings::structs::Settings, LemmyError};
+use lemmy_utils::{
+  federation_mode::FederationMode,
+  location_info,
+  settings::structs::Settings,
+  LemmyError,
+};
 use lemmy_websocket::LemmyContext;
+use log::warn;
 use serde::Serialize;
 use std::net::IpAddr;
 use url::{ParseError, Url};
@@ -46,19 +75,26 @@ use url::{ParseError, Url};
 /// Activitystreams type for community
 type GroupExt = Ext2<ApActor<ApObject<Group>>, GroupExtension, PublicKeyExtension>;
 /// Activitystreams type for person
-type PersonExt = Ext1<ApActor<ApObject<Person>>, PublicKeyExtension>;
+type PersonExt = Ext2<ApActor<ApObject<Person>>, PersonExtension, PublicKeyExtension>;
 /// Activitystreams type for post
 type PageExt = Ext1<ApObject<Page>, PageExtension>;
-type NoteExt = ApObject<Note>;
+/// Activitystreams type for comment and private message
+type NoteExt = Ext1<ApObject<Note>, NoteExtension>;
+/// Activitystreams type for community ban
+type BlockExt = Ext1<Block, BlockExtension>;
+/// Activitystreams types for votes, which can stand for several votes of an instance
+type LikeExt = Ext1<Like, VoteExtension>;
+type DislikeExt = Ext1<Dislike, VoteExtension>;
 
 pub static APUB_JSON_CONTENT_TYPE: &str = "application/activity+json";
 
 /// Checks if the ID is allowed for sending or receiving.
 ///
 /// In particular, it checks for:
-/// - federation being enabled (if its disabled, only local URLs are allowed)
+/// - federation being enabled (if its disabled in the config or by the federation mode of the
+///   site, only local URLs are allowed)
 /// - the correct scheme (either http or https)
-/// - URL being in the allowlist (if it is active)
+/// - URL being in the allowlist (if it is active, or the site is in allowlist mode)
 /// - URL not being in the blocklist (if it is active)
 ///
 /// Note that only one of allowlist and blacklist can be enabled, not both.
@@ -66,8 +102,9 @@ fn check_is_apub_id_valid(apub_id: &Url) -> Result<(), LemmyError> {
   let settings = Settings::get();
   let domain = apub_id.domain().context(location_info!())?.to_string();
   let local_instance = settings.get_hostname_without_port()?;
+  let federation_mode = FederationMode::effective();
 
-  if !settings.federation().enabled {
+  if federation_mode == FederationMode::Disabled {
     return if domain == local_instance {
       Ok(())
     } else {
@@ -91,7 +128,11 @@ fn check_is_apub_id_valid(apub_id: &Url) -> Result<(), LemmyError> {
     return Err(anyhow!("invalid apub id scheme {}: {}", apub_id.scheme(), apub_id).into());
   }
 
-  let allowed_instances = Settings::get().get_allowed_instances();
+  // In allowlist mode, an empty allowlist only allows the local instance
+  let allowed_instances = match federation_mode {
+    FederationMode::Allowlist => Some(Settings::get().get_allowed_instances().unwrap_or_default()),
+    _ => Settings::get().get_allowed_instances(),
+  };
   let blocked_instances = Settings::get().get_blocked_instances();
 
   if allowed_instances.is_none() && blocked_instances.is_none() {
@@ -117,12 +158,26 @@ fn check_is_apub_id_valid(apub_id: &Url) -> Result<(), LemmyError> {
   }
 }
 
+/// Whether federation with the instance at `domain` is refused, by the same checks which are
+/// applied to the ids of incoming and outgoing activities.
+pub fn is_instance_blocked(domain: &str) -> bool {
+  let protocol = Settings::get().get_protocol_string();
+  match Url::parse(&format!("{}://{}", protocol, domain)) {
+    Ok(url) => check_is_apub_id_valid(&url).is_err(),
+    Err(_) => true,
+  }
+}
+
 /// Common functions for ActivityPub objects, which are implemented by most (but not all) objects
 /// and actors in Lemmy.
 #[async_trait::async_trait(?Send)]
 pub trait ApubObjectType {
-  async fn send_create(&self, creator: &DbPerson, context: &LemmyContext)
-    -> Result<(), LemmyError>;
+  /// Returns the number of deliveries which were queued
+  async fn send_create(
+    &self,
+    creator: &DbPerson,
+    context: &LemmyContext,
+  ) -> Result<usize, LemmyError>;
   async fn send_update(&self, creator: &DbPerson, context: &LemmyContext)
     -> Result<(), LemmyError>;
   async fn send_delete(&self, creator: &DbPerson, context: &LemmyContext)
@@ -153,6 +208,14 @@ pub trait ApubLikeableType {
     creator: &DbPerson,
     context: &LemmyContext,
   ) -> Result<(), LemmyError>;
+  async fn send_undo_dislike(
+    &self,
+    creator: &DbPerson,
+    context: &LemmyContext,
+  ) -> Result<(), LemmyError>;
+  /// Sends the summed score of all local votes from the service actor of the instance, which
+  /// replaces the individual votes when the site federates votes as an instance aggregate
+  async fn send_vote_aggregate(&self, context: &LemmyContext) -> Result<(), LemmyError>;
 }
 
 /// Common methods provided by ActivityPub actors (community and person). Not all methods are
@@ -166,6 +229,11 @@ pub trait ActorType {
   fn public_key(&self) -> Option<String>;
   fn private_key(&self) -> Option<String>;
 
+  /// The community in whose federation log the activities sent by this actor are recorded
+  fn community_id(&self) -> Option<CommunityId> {
+    None
+  }
+
   async fn send_follow(
     &self,
     follow_actor_id: &Url,
@@ -183,17 +251,38 @@ pub trait ActorType {
     context: &LemmyContext,
   ) -> Result<(), LemmyError>;
 
+  async fn send_reject_follow(
+    &self,
+    follow: Follow,
+    context: &LemmyContext,
+  ) -> Result<(), LemmyError>;
+
+  async fn send_update(&self, context: &LemmyContext) -> Result<(), LemmyError>;
+
   async fn send_delete(&self, context: &LemmyContext) -> Result<(), LemmyError>;
   async fn send_undo_delete(&self, context: &LemmyContext) -> Result<(), LemmyError>;
 
   async fn send_remove(&self, context: &LemmyContext) -> Result<(), LemmyError>;
   async fn send_undo_remove(&self, context: &LemmyContext) -> Result<(), LemmyError>;
 
+  async fn send_block_user(
+    &self,
+    blocked: &DbPerson,
+    remove_data: bool,
+    context: &LemmyContext,
+  ) -> Result<(), LemmyError>;
+  async fn send_undo_block_user(
+    &self,
+    blocked: &DbPerson,
+    context: &LemmyContext,
+  ) -> Result<(), LemmyError>;
+
+  /// Returns the number of deliveries which were queued
   async fn send_announce(
     &self,
     activity: AnyBase,
     context: &LemmyContext,
-  ) -> Result<(), LemmyError>;
+  ) -> Result<usize, LemmyError>;
 
   /// For a given community, returns the inboxes of all followers.
   async fn get_follower_inboxes(&self, pool: &DbPool) -> Result<Vec<Url>, LemmyError>;
@@ -257,6 +346,16 @@ pub fn generate_followers_url(actor_id: &DbUrl) -> Result<DbUrl, ParseError> {
   Ok(Url::parse(&format!("{}/followers", actor_id))?.into())
 }
 
+/// The public modlog collection of a local community
+pub fn generate_modlog_url(actor_id: &DbUrl) -> Result<Url, ParseError> {
+  Url::parse(&format!("{}/modlog", actor_id))
+}
+
+/// The collection of the posts which a local person pinned to their profile
+pub fn generate_featured_url(actor_id: &DbUrl) -> Result<Url, ParseError> {
+  Url::parse(&format!("{}/featured", actor_id))
+}
+
 pub fn generate_inbox_url(actor_id: &DbUrl) -> Result<DbUrl, ParseError> {
   Ok(Url::parse(&format!("{}/inbox", actor_id))?.into())
 }
@@ -288,14 +387,34 @@ pub(crate) async fn insert_activity<T>(
 where
   T: Serialize + std::fmt::Debug + Send + 'static,
 {
-  let ap_id = ap_id.to_owned().into();
-  blocking(pool, move |conn| {
-    Activity::insert(conn, ap_id, &activity, local, sensitive)
+  let ap_id: DbUrl = ap_id.to_owned().into();
+  blocking(pool, move |conn| -> Result<(), LemmyError> {
+    // Remember the ids of received activities separately, so that duplicates can be detected
+    // quickly
+    if !local {
+      ReceivedActivity::insert(conn, ap_id.to_owned())?;
+    }
+    Activity::insert(conn, ap_id, &activity, local, sensitive)?;
+    Ok(())
   })
   .await??;
   Ok(())
 }
 
+/// Records an activity which a community received or sent, so that admins can debug federation
+/// problems. A failure to write the record is only logged, it doesn't affect the activity.
+pub(crate) async fn log_community_federation(form: CommunityFederationLogForm, pool: &DbPool) {
+  match blocking(pool, move |conn| {
+    CommunityFederationLog::insert(conn, &form)
+  })
+  .await
+  {
+    Ok(Ok(_)) => {}
+    Ok(Err(e)) => warn!("Failed to write community federation log: {}", e),
+    Err(e) => warn!("Failed to write community federation log: {}", e),
+  }
+}
+
 pub(crate) enum PostOrComment {
   Comment(Box<Comment>),
   Post(Box<Post>),
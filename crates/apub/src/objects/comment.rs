@@ -1,5 +1,5 @@
 use crate::{
-  extensions::context::lemmy_context,
+  extensions::{context::lemmy_context, note_extension::NoteExtension},
   fetcher::objects::{get_or_fetch_and_insert_comment, get_or_fetch_and_insert_post},
   objects::{
     check_object_domain,
@@ -19,21 +19,45 @@ use activitystreams::{
   object::{kind::NoteType, ApObject, Note, Tombstone},
   prelude::*,
   public,
+  unparsed::UnparsedMutExt,
 };
+use activitystreams_ext::Ext1;
 use anyhow::{anyhow, Context};
-use lemmy_api_structs::blocking;
-use lemmy_db_queries::{Crud, DbPool};
-use lemmy_db_schema::source::{
-  comment::{Comment, CommentForm},
-  person::Person,
-  post::Post,
+use itertools::Itertools;
+use lemmy_api_structs::{blocking, get_content_limits};
+use lemmy_db_queries::{
+  source::{
+    comment::Comment_,
+    language::{Language_, UNDETERMINED_LANGUAGE_ID},
+    person::Person_,
+    post::Post_,
+  },
+  ApubObject,
+  Crud,
+  DbPool,
+};
+use lemmy_db_schema::{
+  source::{
+    comment::{Comment, CommentForm},
+    community::Community,
+    language::Language,
+    person::Person,
+    post::Post,
+  },
+  CommentId,
+  LanguageId,
+  PersonId,
+  PostId,
 };
 use lemmy_utils::{
   location_info,
-  utils::{convert_datetime, remove_slurs},
+  request::proxy_markdown_images,
+  utils::{convert_datetime, markdown_to_html, remove_slurs, truncate_to_max_length},
   LemmyError,
 };
 use lemmy_websocket::LemmyContext;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
 use url::Url;
 
 #[async_trait::async_trait(?Send)]
@@ -41,40 +65,31 @@ impl ToApub for Comment {
   type ApubType = NoteExt;
 
   async fn to_apub(&self, pool: &DbPool) -> Result<NoteExt, LemmyError> {
-    let mut comment = ApObject::new(Note::new());
-
     let creator_id = self.creator_id;
     let creator = blocking(pool, move |conn| Person::read(conn, creator_id)).await??;
 
     let post_id = self.post_id;
     let post = blocking(pool, move |conn| Post::read(conn, post_id)).await??;
 
-    // Add a vector containing some important info to the "in_reply_to" field
-    // [post_ap_id, Option(parent_comment_ap_id)]
-    let mut in_reply_to_vec = vec![post.ap_id.into_inner()];
-
-    if let Some(parent_id) = self.parent_id {
-      let parent_comment = blocking(pool, move |conn| Comment::read(conn, parent_id)).await??;
-
-      in_reply_to_vec.push(parent_comment.ap_id.into_inner());
-    }
-
-    comment
-      // Not needed when the Post is embedded in a collection (like for community outbox)
-      .set_many_contexts(lemmy_context()?)
-      .set_id(self.ap_id.to_owned().into_inner())
-      .set_published(convert_datetime(self.published))
-      .set_to(public())
-      .set_many_in_reply_tos(in_reply_to_vec)
-      .set_attributed_to(creator.actor_id.into_inner());
-
-    set_content_and_source(&mut comment, &self.content)?;
+    let parent_comment = match self.parent_id {
+      Some(parent_id) => Some(blocking(pool, move |conn| Comment::read(conn, parent_id)).await??),
+      None => None,
+    };
 
-    if let Some(u) = self.updated {
-      comment.set_updated(convert_datetime(u));
-    }
+    let language = if self.language_id != UNDETERMINED_LANGUAGE_ID {
+      let language_id = self.language_id;
+      Some(blocking(pool, move |conn| Language::read_from_id(conn, language_id)).await??)
+    } else {
+      None
+    };
 
-    Ok(comment)
+    comment_to_note(
+      self,
+      &creator,
+      &post,
+      parent_comment.as_ref(),
+      language.as_ref(),
+    )
   }
 
   fn to_tombstone(&self) -> Result<Tombstone, LemmyError> {
@@ -87,6 +102,104 @@ impl ToApub for Comment {
   }
 }
 
+/// Converts the comments of a collection like `to_apub()`. Their creators, posts, parent comments
+/// and languages are read with one query each, instead of separately for every comment.
+pub(crate) async fn comments_to_apub(
+  comments: Vec<Comment>,
+  pool: &DbPool,
+) -> Result<Vec<NoteExt>, LemmyError> {
+  blocking(pool, move |conn| {
+    let creator_ids = comments.iter().map(|c| c.creator_id).unique().collect();
+    let creators: HashMap<PersonId, Person> = Person::read_many(conn, creator_ids)?
+      .into_iter()
+      .map(|p| (p.id, p))
+      .collect();
+    let post_ids = comments.iter().map(|c| c.post_id).unique().collect();
+    let posts: HashMap<PostId, Post> = Post::read_many(conn, post_ids)?
+      .into_iter()
+      .map(|p| (p.id, p))
+      .collect();
+    let parent_ids = comments
+      .iter()
+      .filter_map(|c| c.parent_id)
+      .unique()
+      .collect();
+    let parents: HashMap<CommentId, Comment> = Comment::read_many(conn, parent_ids)?
+      .into_iter()
+      .map(|c| (c.id, c))
+      .collect();
+    let languages: HashMap<LanguageId, Language> = Language::read_all(conn)?
+      .into_iter()
+      .map(|l| (l.id, l))
+      .collect();
+
+    comments
+      .iter()
+      .map(|comment| {
+        let creator = creators
+          .get(&comment.creator_id)
+          .context(location_info!())?;
+        let post = posts.get(&comment.post_id).context(location_info!())?;
+        let parent_comment = match comment.parent_id {
+          Some(parent_id) => Some(parents.get(&parent_id).context(location_info!())?),
+          None => None,
+        };
+        let language = languages.get(&comment.language_id);
+        comment_to_note(comment, creator, post, parent_comment, language)
+      })
+      .collect()
+  })
+  .await?
+}
+
+fn comment_to_note(
+  comment: &Comment,
+  creator: &Person,
+  post: &Post,
+  parent_comment: Option<&Comment>,
+  language: Option<&Language>,
+) -> Result<NoteExt, LemmyError> {
+  let mut note = ApObject::new(Note::new());
+
+  // Add a vector containing some important info to the "in_reply_to" field
+  // [post_ap_id, Option(parent_comment_ap_id)]
+  let mut in_reply_to_vec = vec![post.ap_id.to_owned().into_inner()];
+
+  if let Some(parent_comment) = parent_comment {
+    in_reply_to_vec.push(parent_comment.ap_id.to_owned().into_inner());
+  }
+
+  note
+    // Not needed when the Post is embedded in a collection (like for community outbox)
+    .set_many_contexts(lemmy_context()?)
+    .set_id(comment.ap_id.to_owned().into_inner())
+    .set_published(convert_datetime(comment.published))
+    .set_to(public())
+    .set_many_in_reply_tos(in_reply_to_vec)
+    .set_attributed_to(creator.actor_id.to_owned().into_inner());
+
+  set_content_and_source(&mut note, &comment.content)?;
+
+  // The language is given as the key of the content in contentMap
+  if let Some(language) = language.filter(|l| l.id != UNDETERMINED_LANGUAGE_ID) {
+    let mut content_map = Map::new();
+    content_map.insert(
+      language.code.to_owned(),
+      markdown_to_html(&comment.content).into(),
+    );
+    note.insert("contentMap", content_map)?;
+  }
+
+  if let Some(u) = comment.updated {
+    note.set_updated(convert_datetime(u));
+  }
+
+  let ext = NoteExtension {
+    stickied: Some(comment.stickied),
+  };
+  Ok(Ext1::new(note, ext))
+}
+
 #[async_trait::async_trait(?Send)]
 impl FromApub for Comment {
   type ApubType = NoteExt;
@@ -116,11 +229,37 @@ impl FromApub for Comment {
       .await??;
       Err(anyhow!("Post is locked").into())
     } else {
+      // A language which the community doesn't allow is dropped, instead of rejecting the comment
+      let language_code = content_map_language(note)?;
+      let community_id = post.community_id;
+      let comment_id = comment.id;
+      let comment = blocking(context.pool(), move |conn| -> Result<Comment, LemmyError> {
+        let language_id = language_code
+          // Region subtags like en-US aren't stored, only the language itself
+          .and_then(|code| Language::read_from_code(conn, code.split('-').next()?).ok())
+          .map(|language| language.id)
+          .unwrap_or(UNDETERMINED_LANGUAGE_ID);
+        let language_id = if Language::is_allowed_in_community(conn, community_id, language_id)? {
+          language_id
+        } else {
+          UNDETERMINED_LANGUAGE_ID
+        };
+        Ok(Comment::update_language(conn, comment_id, language_id)?)
+      })
+      .await??;
+
+      // The sticky state is only accepted from mods, see `receive_update_comment()`
       Ok(comment)
     }
   }
 }
 
+/// The language code of the first entry in the contentMap of the note, if it has one
+fn content_map_language(note: &NoteExt) -> Result<Option<String>, LemmyError> {
+  let content_map: Option<Map<String, Value>> = note.inner.clone().remove("contentMap")?;
+  Ok(content_map.and_then(|content_map| content_map.into_iter().next().map(|(code, _)| code)))
+}
+
 #[async_trait::async_trait(?Send)]
 impl FromApubToForm<NoteExt> for CommentForm {
   async fn from_apub(
@@ -153,7 +292,7 @@ impl FromApubToForm<NoteExt> for CommentForm {
 
     // The 2nd item, if it exists, is the parent comment apub_id
     // For deeply nested comments, FromApub automatically gets called recursively
-    let parent_id: Option<i32> = match in_reply_tos.next() {
+    let parent_id: Option<CommentId> = match in_reply_tos.next() {
       Some(parent_comment_uri) => {
         let parent_comment_ap_id = &parent_comment_uri?;
         let parent_comment =
@@ -164,8 +303,33 @@ impl FromApubToForm<NoteExt> for CommentForm {
       None => None,
     };
 
+    // New replies below a locked comment are rejected before they are stored, like comments on a
+    // locked post, while existing ones can still be updated
+    let ap_id = check_object_domain(note, expected_domain)?;
+    if let Some(parent_id) = parent_id {
+      let ap_id = ap_id.clone();
+      let rejected = blocking(context.pool(), move |conn| -> Result<bool, LemmyError> {
+        let is_new = Comment::read_from_apub_id(conn, &ap_id).is_err();
+        Ok(is_new && Comment::in_locked_thread(conn, parent_id)?)
+      })
+      .await??;
+      if rejected {
+        return Err(anyhow!("Comment is locked").into());
+      }
+    }
+
     let content = get_source_markdown_value(note)?.context(location_info!())?;
-    let content_slurs_removed = remove_slurs(&content);
+    let max_comment_length = get_content_limits(context.pool()).await?.max_comment_length;
+    let content = truncate_to_max_length(&content, max_comment_length);
+    let community_id = post.community_id;
+    let community = blocking(context.pool(), move |conn| {
+      Community::read(conn, community_id)
+    })
+    .await??;
+    let slur_regex = context
+      .slur_filter()
+      .regex_for_community(community.exempt_from_slur_filter);
+    let content_slurs_removed = proxy_markdown_images(&remove_slurs(&content, &slur_regex));
 
     Ok(CommentForm {
       creator_id: creator.id,
@@ -177,7 +341,7 @@ impl FromApubToForm<NoteExt> for CommentForm {
       published: note.published().map(|u| u.to_owned().naive_local()),
       updated: note.updated().map(|u| u.to_owned().naive_local()),
       deleted: None,
-      ap_id: Some(check_object_domain(note, expected_domain)?),
+      ap_id: Some(ap_id),
       local: false,
     })
   }
@@ -1,6 +1,7 @@
 use crate::{
   extensions::{context::lemmy_context, group_extensions::GroupExtension},
   fetcher::person::get_or_fetch_and_upsert_person,
+  generate_modlog_url,
   objects::{
     check_object_domain,
     create_tombstone,
@@ -31,7 +32,13 @@ use lemmy_db_schema::{
 use lemmy_db_views_actor::community_moderator_view::CommunityModeratorView;
 use lemmy_utils::{
   location_info,
-  utils::{check_slurs, check_slurs_opt, convert_datetime},
+  utils::{
+    check_slurs,
+    check_slurs_opt,
+    convert_datetime,
+    is_valid_theme_color,
+    MAX_SHORT_DESCRIPTION_LENGTH,
+  },
   LemmyError,
 };
 use lemmy_websocket::LemmyContext;
@@ -95,7 +102,13 @@ impl ToApub for Community {
 
     Ok(Ext2::new(
       ap_actor,
-      GroupExtension::new(self.nsfw)?,
+      GroupExtension::new(
+        self.nsfw,
+        self.downvotes_disabled,
+        self.theme_color.to_owned(),
+        self.short_description.to_owned(),
+        Some(generate_modlog_url(&self.actor_id)?),
+      )?,
       self.get_public_key_ext()?,
     ))
   }
@@ -161,9 +174,23 @@ impl FromApubToForm<GroupExt> for CommunityForm {
 
     let description = get_source_markdown_value(group)?;
 
-    check_slurs(&name)?;
-    check_slurs(&title)?;
-    check_slurs_opt(&description)?;
+    let slur_regex = context.slur_filter().regex();
+    check_slurs(&name, &slur_regex)?;
+    check_slurs(&title, &slur_regex)?;
+    check_slurs_opt(&description, &slur_regex)?;
+
+    // Invalid values from other instances are dropped instead of rejecting the whole community
+    let theme_color = group
+      .ext_one
+      .theme_color
+      .to_owned()
+      .filter(|c| is_valid_theme_color(c));
+    let short_description = group
+      .ext_one
+      .short_description
+      .to_owned()
+      .filter(|d| d.chars().count() <= MAX_SHORT_DESCRIPTION_LENGTH);
+    check_slurs_opt(&short_description, &slur_regex)?;
 
     let icon = match group.icon() {
       Some(any_image) => Some(
@@ -223,6 +250,13 @@ impl FromApubToForm<GroupExt> for CommunityForm {
       ),
       inbox_url: Some(group.inner.inbox()?.to_owned().into()),
       shared_inbox_url: Some(shared_inbox),
+      downvotes_disabled: Some(group.ext_one.downvotes_disabled.unwrap_or(false)),
+      requires_post_approval: None,
+      theme_color: Some(theme_color),
+      short_description: Some(short_description),
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
     })
   }
 }
@@ -0,0 +1,692 @@
+use crate::{extensions::context::lemmy_context, generate_modlog_url};
+use activitystreams::collection::kind::OrderedCollectionPageType;
+use chrono::{DateTime, FixedOffset, NaiveDateTime};
+use diesel::{result::Error, *};
+use lemmy_api_structs::site::GetModlogResponse;
+use lemmy_db_queries::{ApubObject, Crud, ToSafe};
+use lemmy_db_schema::{
+  schema::{community, person, person_alias_1},
+  source::{
+    comment::Comment,
+    community::{Community, CommunitySafe},
+    moderator::*,
+    person::{Person, PersonAlias1, PersonSafe, PersonSafeAlias1},
+    post::Post,
+  },
+  CommunityId,
+  DbUrl,
+  PersonId,
+};
+use lemmy_db_views_actor::community_moderator_view::CommunityModeratorView;
+use lemmy_db_views_moderator::{
+  mod_add_community_view::ModAddCommunityView,
+  mod_ban_from_community_view::ModBanFromCommunityView,
+  mod_lock_post_view::ModLockPostView,
+  mod_remove_comment_view::ModRemoveCommentView,
+  mod_remove_post_view::ModRemovePostView,
+  mod_sticky_comment_view::ModStickyCommentView,
+  mod_sticky_post_view::ModStickyPostView,
+};
+use lemmy_utils::{utils::convert_datetime, LemmyError};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Number of entries of each kind on a page of the modlog collection
+pub(crate) const MODLOG_PAGE_SIZE: i64 = 20;
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub enum ModlogEntryKind {
+  RemovePost,
+  LockPost,
+  StickyPost,
+  RemoveComment,
+  StickyComment,
+  BanFromCommunity,
+  AddModerator,
+  /// Kinds which were added by newer versions are skipped
+  #[serde(other)]
+  Unknown,
+}
+
+/// A single moderator action in the public modlog collection of a community
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModlogEntry {
+  #[serde(rename = "type")]
+  pub kind: ModlogEntryKind,
+  /// The moderator
+  pub actor: Url,
+  /// The post, comment or person which the action applies to
+  pub object: Url,
+  /// The reason given by the moderator
+  pub summary: Option<String>,
+  /// False if the action was reverted, eg the post was restored or the person unbanned
+  pub active: bool,
+  /// When a ban expires
+  pub end_time: Option<DateTime<FixedOffset>>,
+  pub published: DateTime<FixedOffset>,
+}
+
+/// A page of the modlog collection, with up to `MODLOG_PAGE_SIZE` entries of each kind
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModlogPage {
+  #[serde(rename = "@context", default)]
+  pub context: serde_json::Value,
+  #[serde(rename = "type")]
+  pub kind: OrderedCollectionPageType,
+  pub id: Url,
+  pub part_of: Url,
+  pub next: Option<Url>,
+  pub ordered_items: Vec<ModlogEntry>,
+}
+
+fn to_entry(
+  kind: ModlogEntryKind,
+  actor: DbUrl,
+  object: DbUrl,
+  summary: Option<String>,
+  active: bool,
+  end_time: Option<NaiveDateTime>,
+  published: NaiveDateTime,
+) -> ModlogEntry {
+  ModlogEntry {
+    kind,
+    actor: actor.into(),
+    object: object.into(),
+    summary,
+    active,
+    end_time: end_time.map(convert_datetime),
+    published: convert_datetime(published),
+  }
+}
+
+/// Reads a page of the modlog of a local community, with the entries of all kinds ordered by time.
+pub(crate) fn read_modlog_page(
+  conn: &PgConnection,
+  community: &Community,
+  page: i64,
+) -> Result<ModlogPage, LemmyError> {
+  let community_id = Some(community.id);
+  let limit = Some(MODLOG_PAGE_SIZE);
+  let removed_posts = ModRemovePostView::list(conn, community_id, None, Some(page), limit)?;
+  let locked_posts = ModLockPostView::list(conn, community_id, None, Some(page), limit)?;
+  let stickied_posts = ModStickyPostView::list(conn, community_id, None, Some(page), limit)?;
+  let removed_comments = ModRemoveCommentView::list(conn, community_id, None, Some(page), limit)?;
+  let stickied_comments = ModStickyCommentView::list(conn, community_id, None, Some(page), limit)?;
+  let banned = ModBanFromCommunityView::list(conn, community_id, None, Some(page), limit)?;
+  let added = ModAddCommunityView::list(conn, community_id, None, Some(page), limit)?;
+
+  let has_next = [
+    removed_posts.len(),
+    locked_posts.len(),
+    stickied_posts.len(),
+    removed_comments.len(),
+    stickied_comments.len(),
+    banned.len(),
+    added.len(),
+  ]
+  .iter()
+  .any(|len| *len as i64 == MODLOG_PAGE_SIZE);
+
+  let mut entries = Vec::new();
+  entries.extend(removed_posts.into_iter().map(|v| {
+    let m = v.mod_remove_post;
+    to_entry(
+      ModlogEntryKind::RemovePost,
+      v.moderator.actor_id,
+      v.post.ap_id,
+      m.reason,
+      m.removed.unwrap_or(true),
+      None,
+      m.when_,
+    )
+  }));
+  entries.extend(locked_posts.into_iter().map(|v| {
+    let m = v.mod_lock_post;
+    to_entry(
+      ModlogEntryKind::LockPost,
+      v.moderator.actor_id,
+      v.post.ap_id,
+      None,
+      m.locked.unwrap_or(true),
+      None,
+      m.when_,
+    )
+  }));
+  entries.extend(stickied_posts.into_iter().map(|v| {
+    let m = v.mod_sticky_post;
+    to_entry(
+      ModlogEntryKind::StickyPost,
+      v.moderator.actor_id,
+      v.post.ap_id,
+      None,
+      m.stickied.unwrap_or(true),
+      None,
+      m.when_,
+    )
+  }));
+  entries.extend(removed_comments.into_iter().map(|v| {
+    let m = v.mod_remove_comment;
+    to_entry(
+      ModlogEntryKind::RemoveComment,
+      v.moderator.actor_id,
+      v.comment.ap_id,
+      m.reason,
+      m.removed.unwrap_or(true),
+      None,
+      m.when_,
+    )
+  }));
+  entries.extend(stickied_comments.into_iter().map(|v| {
+    let m = v.mod_sticky_comment;
+    to_entry(
+      ModlogEntryKind::StickyComment,
+      v.moderator.actor_id,
+      v.comment.ap_id,
+      None,
+      m.stickied.unwrap_or(true),
+      None,
+      m.when_,
+    )
+  }));
+  entries.extend(banned.into_iter().map(|v| {
+    let m = v.mod_ban_from_community;
+    to_entry(
+      ModlogEntryKind::BanFromCommunity,
+      v.moderator.actor_id,
+      v.banned_person.actor_id,
+      m.reason,
+      m.banned.unwrap_or(true),
+      m.expires,
+      m.when_,
+    )
+  }));
+  entries.extend(added.into_iter().map(|v| {
+    let m = v.mod_add_community;
+    to_entry(
+      ModlogEntryKind::AddModerator,
+      v.moderator.actor_id,
+      v.modded_person.actor_id,
+      None,
+      !m.removed.unwrap_or(false),
+      None,
+      m.when_,
+    )
+  }));
+  entries.sort_by(|a, b| b.published.cmp(&a.published));
+
+  let modlog_url = generate_modlog_url(&community.actor_id)?;
+  let page_url = |page: i64| Url::parse(&format!("{}?page={}", modlog_url, page));
+  Ok(ModlogPage {
+    context: serde_json::to_value(lemmy_context()?)?,
+    kind: OrderedCollectionPageType::OrderedCollectionPage,
+    id: page_url(page)?,
+    part_of: modlog_url.to_owned(),
+    next: if has_next {
+      Some(page_url(page + 1)?)
+    } else {
+      None
+    },
+    ordered_items: entries,
+  })
+}
+
+fn read_person(conn: &PgConnection, actor_id: &Url) -> Result<Option<PersonSafe>, Error> {
+  let actor_id: DbUrl = actor_id.to_owned().into();
+  person::table
+    .filter(person::actor_id.eq(actor_id))
+    .select(Person::safe_columns_tuple())
+    .first::<PersonSafe>(conn)
+    .optional()
+}
+
+fn read_person_alias(
+  conn: &PgConnection,
+  actor_id: &Url,
+) -> Result<Option<PersonSafeAlias1>, Error> {
+  let actor_id: DbUrl = actor_id.to_owned().into();
+  person_alias_1::table
+    .filter(person_alias_1::actor_id.eq(actor_id))
+    .select(PersonAlias1::safe_columns_tuple())
+    .first::<PersonSafeAlias1>(conn)
+    .optional()
+}
+
+/// Only posts in the given community are accepted, so that an instance can't add entries for
+/// other communities.
+fn read_post(
+  conn: &PgConnection,
+  ap_id: &Url,
+  community_id: CommunityId,
+) -> Result<Option<Post>, Error> {
+  let post = Post::read_from_apub_id(conn, &ap_id.to_owned().into()).optional()?;
+  Ok(post.filter(|p| p.community_id == community_id))
+}
+
+/// Reads the comment with its creator and post, if it is in the given community
+fn read_comment(
+  conn: &PgConnection,
+  ap_id: &Url,
+  community_id: CommunityId,
+) -> Result<Option<(Comment, PersonSafeAlias1, Post)>, Error> {
+  let comment = match Comment::read_from_apub_id(conn, &ap_id.to_owned().into()).optional()? {
+    Some(c) => c,
+    None => return Ok(None),
+  };
+  let post = Post::read(conn, comment.post_id)?;
+  if post.community_id != community_id {
+    return Ok(None);
+  }
+  let commenter = person_alias_1::table
+    .find(comment.creator_id)
+    .select(PersonAlias1::safe_columns_tuple())
+    .first::<PersonSafeAlias1>(conn)?;
+  Ok(Some((comment, commenter, post)))
+}
+
+/// Only the moderators of the community and the persons of its instance, eg its admins or former
+/// moderators, can be the actor of a modlog entry. Otherwise an instance could attribute actions to
+/// anyone.
+fn is_allowed_actor(moderator: &PersonSafe, community: &Community, mod_ids: &[PersonId]) -> bool {
+  mod_ids.contains(&moderator.id)
+    || moderator.actor_id.to_owned().into_inner().domain()
+      == community.actor_id.to_owned().into_inner().domain()
+}
+
+/// Maps the entries of a remote modlog page to the local modlog views. Entries whose moderator,
+/// post, comment or person isn't known locally are skipped, as nothing is fetched for them, and so
+/// are the ones with an actor who can't moderate the community. As the entries don't exist in the
+/// local database, their ids are 0, and they are marked as remote.
+pub(crate) fn modlog_from_entries(
+  conn: &PgConnection,
+  community: &Community,
+  mod_person_id: Option<PersonId>,
+  entries: &[ModlogEntry],
+) -> Result<GetModlogResponse, LemmyError> {
+  let community_safe = community::table
+    .find(community.id)
+    .select(Community::safe_columns_tuple())
+    .first::<CommunitySafe>(conn)?;
+  let mod_ids: Vec<PersonId> = CommunityModeratorView::for_community(conn, community.id)?
+    .into_iter()
+    .map(|m| m.moderator.id)
+    .collect();
+  let mut modlog = GetModlogResponse {
+    removed_posts: Vec::new(),
+    locked_posts: Vec::new(),
+    stickied_posts: Vec::new(),
+    featured_posts: Vec::new(),
+    removed_comments: Vec::new(),
+    locked_comments: Vec::new(),
+    stickied_comments: Vec::new(),
+    removed_communities: Vec::new(),
+    exempted_communities: Vec::new(),
+    edited_communities: Vec::new(),
+    banned_from_community: Vec::new(),
+    banned: Vec::new(),
+    added_to_community: Vec::new(),
+    added: Vec::new(),
+    auto_removed: Vec::new(),
+    remote: true,
+  };
+
+  for entry in entries {
+    let moderator = match read_person(conn, &entry.actor)? {
+      Some(m) if is_allowed_actor(&m, community, &mod_ids) => m,
+      _ => continue,
+    };
+    if mod_person_id.map(|id| id != moderator.id).unwrap_or(false) {
+      continue;
+    }
+    let mod_person_id = moderator.id;
+    let when_ = entry.published.naive_utc();
+    let community = community_safe.to_owned();
+    match entry.kind {
+      ModlogEntryKind::RemovePost => {
+        if let Some(post) = read_post(conn, &entry.object, community.id)? {
+          modlog.removed_posts.push(ModRemovePostView {
+            mod_remove_post: ModRemovePost {
+              id: 0,
+              mod_person_id,
+              post_id: post.id,
+              reason: entry.summary.to_owned(),
+              removed: Some(entry.active),
+              when_,
+            },
+            moderator,
+            post,
+            community,
+            remote: true,
+          });
+        }
+      }
+      ModlogEntryKind::LockPost => {
+        if let Some(post) = read_post(conn, &entry.object, community.id)? {
+          modlog.locked_posts.push(ModLockPostView {
+            mod_lock_post: ModLockPost {
+              id: 0,
+              mod_person_id,
+              post_id: post.id,
+              locked: Some(entry.active),
+              when_,
+            },
+            moderator,
+            post,
+            community,
+            remote: true,
+          });
+        }
+      }
+      ModlogEntryKind::StickyPost => {
+        if let Some(post) = read_post(conn, &entry.object, community.id)? {
+          modlog.stickied_posts.push(ModStickyPostView {
+            mod_sticky_post: ModStickyPost {
+              id: 0,
+              mod_person_id,
+              post_id: post.id,
+              stickied: Some(entry.active),
+              when_,
+            },
+            moderator,
+            post,
+            community,
+            remote: true,
+          });
+        }
+      }
+      ModlogEntryKind::RemoveComment => {
+        if let Some((comment, commenter, post)) = read_comment(conn, &entry.object, community.id)? {
+          modlog.removed_comments.push(ModRemoveCommentView {
+            mod_remove_comment: ModRemoveComment {
+              id: 0,
+              mod_person_id,
+              comment_id: comment.id,
+              reason: entry.summary.to_owned(),
+              removed: Some(entry.active),
+              when_,
+            },
+            moderator,
+            comment,
+            commenter,
+            post,
+            community,
+            remote: true,
+          });
+        }
+      }
+      ModlogEntryKind::StickyComment => {
+        if let Some((comment, commenter, post)) = read_comment(conn, &entry.object, community.id)? {
+          modlog.stickied_comments.push(ModStickyCommentView {
+            mod_sticky_comment: ModStickyComment {
+              id: 0,
+              mod_person_id,
+              comment_id: comment.id,
+              stickied: Some(entry.active),
+              when_,
+            },
+            moderator,
+            comment,
+            commenter,
+            post,
+            community,
+            remote: true,
+          });
+        }
+      }
+      ModlogEntryKind::BanFromCommunity => {
+        if let Some(banned_person) = read_person_alias(conn, &entry.object)? {
+          modlog.banned_from_community.push(ModBanFromCommunityView {
+            mod_ban_from_community: ModBanFromCommunity {
+              id: 0,
+              mod_person_id,
+              other_person_id: banned_person.id,
+              community_id: community.id,
+              reason: entry.summary.to_owned(),
+              banned: Some(entry.active),
+              expires: entry.end_time.map(|e| e.naive_utc()),
+              when_,
+            },
+            moderator,
+            community,
+            banned_person,
+            remote: true,
+          });
+        }
+      }
+      ModlogEntryKind::AddModerator => {
+        if let Some(modded_person) = read_person_alias(conn, &entry.object)? {
+          modlog.added_to_community.push(ModAddCommunityView {
+            mod_add_community: ModAddCommunity {
+              id: 0,
+              mod_person_id,
+              other_person_id: modded_person.id,
+              community_id: community.id,
+              removed: Some(!entry.active),
+              when_,
+            },
+            moderator,
+            community,
+            modded_person,
+            remote: true,
+          });
+        }
+      }
+      ModlogEntryKind::Unknown => {}
+    }
+  }
+  Ok(modlog)
+}
+
+/// Adds the entries of a remote modlog to the ones which were logged locally for the community, eg
+/// removals by local admins, newest first. Each entry keeps its own `remote` flag, so clients can
+/// tell the two apart.
+pub(crate) fn merge_remote_modlog(modlog: &mut GetModlogResponse, remote: GetModlogResponse) {
+  fn merge<T>(local: &mut Vec<T>, remote: Vec<T>, when_: impl Fn(&T) -> NaiveDateTime) {
+    local.extend(remote);
+    local.sort_by(|a, b| when_(b).cmp(&when_(a)));
+  }
+  merge(&mut modlog.removed_posts, remote.removed_posts, |v| {
+    v.mod_remove_post.when_
+  });
+  merge(&mut modlog.locked_posts, remote.locked_posts, |v| {
+    v.mod_lock_post.when_
+  });
+  merge(&mut modlog.stickied_posts, remote.stickied_posts, |v| {
+    v.mod_sticky_post.when_
+  });
+  merge(&mut modlog.removed_comments, remote.removed_comments, |v| {
+    v.mod_remove_comment.when_
+  });
+  merge(
+    &mut modlog.stickied_comments,
+    remote.stickied_comments,
+    |v| v.mod_sticky_comment.when_,
+  );
+  merge(
+    &mut modlog.banned_from_community,
+    remote.banned_from_community,
+    |v| v.mod_ban_from_community.when_,
+  );
+  merge(
+    &mut modlog.added_to_community,
+    remote.added_to_community,
+    |v| v.mod_add_community.when_,
+  );
+  modlog.remote = true;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use lemmy_db_queries::{establish_unpooled_connection, Joinable};
+  use lemmy_db_schema::source::{
+    community::{CommunityForm, CommunityModerator, CommunityModeratorForm},
+    person::PersonForm,
+    post::PostForm,
+  };
+  use serial_test::serial;
+
+  fn person_form(name: &str) -> PersonForm {
+    PersonForm {
+      name: name.into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    }
+  }
+
+  fn community_form(name: &str, creator_id: PersonId) -> CommunityForm {
+    CommunityForm {
+      name: name.into(),
+      creator_id,
+      title: "nada".to_owned(),
+      description: None,
+      nsfw: false,
+      removed: None,
+      deleted: None,
+      updated: None,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    }
+  }
+
+  fn post_form(creator_id: PersonId, community_id: CommunityId) -> PostForm {
+    PostForm {
+      name: "A test post".into(),
+      url: None,
+      body: None,
+      creator_id,
+      community_id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      nsfw: false,
+      updated: None,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      thumbnail_url: None,
+      ap_id: None,
+      local: true,
+      published: None,
+    }
+  }
+
+  #[test]
+  #[serial]
+  fn test_modlog_entries() {
+    let conn = establish_unpooled_connection();
+
+    let moderator = Person::create(&conn, &person_form("modlog_mod")).unwrap();
+    let other_person = Person::create(&conn, &person_form("modlog_other")).unwrap();
+    let community =
+      Community::create(&conn, &community_form("modlog_community", moderator.id)).unwrap();
+    let other_community = Community::create(
+      &conn,
+      &community_form("modlog_other_community", moderator.id),
+    )
+    .unwrap();
+    let moderator_form = CommunityModeratorForm {
+      community_id: community.id,
+      person_id: moderator.id,
+    };
+    CommunityModerator::join(&conn, &moderator_form).unwrap();
+    let post = Post::create(&conn, &post_form(other_person.id, community.id)).unwrap();
+    let other_post = Post::create(&conn, &post_form(other_person.id, other_community.id)).unwrap();
+    let remove_form = ModRemovePostForm {
+      mod_person_id: moderator.id,
+      post_id: post.id,
+      reason: Some("spam".into()),
+      removed: Some(true),
+    };
+    ModRemovePost::create(&conn, &remove_form).unwrap();
+
+    let page = read_modlog_page(&conn, &community, 1).unwrap();
+    let entries = page.ordered_items;
+
+    // The served entries are read back into the same views
+    let modlog = modlog_from_entries(&conn, &community, None, &entries).unwrap();
+    let only_other =
+      modlog_from_entries(&conn, &community, Some(other_person.id), &entries).unwrap();
+
+    // Entries of persons who can't moderate the community, or for other communities, are dropped
+    let forged_actor = ModlogEntry {
+      actor: other_person.actor_id.to_owned().into(),
+      ..entries[0].to_owned()
+    };
+    let other_community_post = ModlogEntry {
+      object: other_post.ap_id.to_owned().into(),
+      ..entries[0].to_owned()
+    };
+    let forged = modlog_from_entries(
+      &conn,
+      &community,
+      None,
+      &[forged_actor, other_community_post],
+    )
+    .unwrap();
+
+    // Remote entries are added to the local ones
+    let mut merged = modlog_from_entries(&conn, &community, None, &[]).unwrap();
+    merged.remote = false;
+    merged.removed_posts =
+      ModRemovePostView::list(&conn, Some(community.id), None, None, None).unwrap();
+    let remote = modlog_from_entries(&conn, &community, None, &entries).unwrap();
+    merge_remote_modlog(&mut merged, remote);
+
+    Post::delete(&conn, post.id).unwrap();
+    Post::delete(&conn, other_post.id).unwrap();
+    Community::delete(&conn, community.id).unwrap();
+    Community::delete(&conn, other_community.id).unwrap();
+    Person::delete(&conn, moderator.id).unwrap();
+    Person::delete(&conn, other_person.id).unwrap();
+
+    assert_eq!(1, entries.len());
+    assert_eq!(ModlogEntryKind::RemovePost, entries[0].kind);
+    assert_eq!(Some("spam".to_string()), entries[0].summary);
+    assert!(entries[0].active);
+    assert!(page.next.is_none());
+
+    assert_eq!(1, modlog.removed_posts.len());
+    assert_eq!(post.id, modlog.removed_posts[0].post.id);
+    assert!(modlog.removed_posts[0].remote);
+    assert_eq!(moderator.id, modlog.removed_posts[0].moderator.id);
+    assert!(modlog.remote);
+    assert!(only_other.removed_posts.is_empty());
+    assert!(forged.removed_posts.is_empty());
+
+    assert_eq!(2, merged.removed_posts.len());
+    assert_eq!(1, merged.removed_posts.iter().filter(|v| v.remote).count());
+    assert!(merged.remote);
+  }
+}
@@ -1,5 +1,6 @@
 use crate::{
-  extensions::context::lemmy_context,
+  extensions::{context::lemmy_context, person_extension::PersonExtension},
+  generate_featured_url,
   objects::{
     check_object_domain,
     get_source_markdown_value,
@@ -16,7 +17,7 @@ use activitystreams::{
   object::{ApObject, Image, Tombstone},
   prelude::*,
 };
-use activitystreams_ext::Ext1;
+use activitystreams_ext::Ext2;
 use anyhow::Context;
 use lemmy_api_structs::blocking;
 use lemmy_db_queries::{ApubObject, DbPool};
@@ -77,7 +78,10 @@ impl ToApub for DbPerson {
         ..Default::default()
       });
 
-    Ok(Ext1::new(ap_actor, self.get_public_key_ext()?))
+    let person_ext = PersonExtension {
+      featured: Some(generate_featured_url(&self.actor_id)?),
+    };
+    Ok(Ext2::new(ap_actor, person_ext, self.get_public_key_ext()?))
   }
   fn to_tombstone(&self) -> Result<Tombstone, LemmyError> {
     unimplemented!()
@@ -118,7 +122,7 @@ impl FromApub for DbPerson {
 impl FromApubToForm<PersonExt> for PersonForm {
   async fn from_apub(
     person: &PersonExt,
-    _context: &LemmyContext,
+    context: &LemmyContext,
     expected_domain: Url,
     _request_counter: &mut i32,
   ) -> Result<Self, LemmyError> {
@@ -166,9 +170,10 @@ impl FromApubToForm<PersonExt> for PersonForm {
       .flatten()
       .map(|s| s.to_owned().into());
 
-    check_slurs(&name)?;
-    check_slurs_opt(&preferred_username)?;
-    check_slurs_opt(&bio)?;
+    let slur_regex = context.slur_filter().regex();
+    check_slurs(&name, &slur_regex)?;
+    check_slurs_opt(&preferred_username, &slur_regex)?;
+    check_slurs_opt(&bio, &slur_regex)?;
 
     Ok(PersonForm {
       name,
@@ -183,7 +188,7 @@ impl FromApubToForm<PersonExt> for PersonForm {
       bio: Some(bio),
       local: Some(false),
       private_key: None,
-      public_key: Some(Some(person.ext_one.public_key.to_owned().public_key_pem)),
+      public_key: Some(Some(person.ext_two.public_key.to_owned().public_key_pem)),
       last_refreshed_at: Some(naive_now()),
       inbox_url: Some(person.inner.inbox()?.to_owned().into()),
       shared_inbox_url: Some(shared_inbox),
@@ -0,0 +1,216 @@
+use crate::{
+  extensions::context::lemmy_context,
+  objects::{comment::comments_to_apub, post::posts_to_apub},
+  ActorType,
+};
+use activitystreams::{
+  activity::Create,
+  base::AnyBase,
+  collection::kind::OrderedCollectionPageType,
+  prelude::*,
+  public,
+};
+use anyhow::Context;
+use chrono::NaiveDateTime;
+use lemmy_api_structs::blocking;
+use lemmy_db_queries::{
+  source::{
+    comment::Comment_,
+    person::{OutboxItemId, Person_},
+    post::Post_,
+  },
+  DbPool,
+};
+use lemmy_db_schema::{
+  source::{comment::Comment, person::Person, post::Post},
+  DbUrl,
+  PersonId,
+};
+use lemmy_utils::{location_info, utils::convert_datetime, LemmyError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use url::{ParseError, Url};
+
+/// Number of activities on a page of the outbox of a person
+pub(crate) const OUTBOX_PAGE_SIZE: i64 = 20;
+
+/// A page of the outbox of a local person, with the `Create` activities of their posts and
+/// comments, newest first
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PersonOutboxPage {
+  #[serde(rename = "@context", default)]
+  pub context: serde_json::Value,
+  #[serde(rename = "type")]
+  pub kind: OrderedCollectionPageType,
+  pub id: Url,
+  pub part_of: Url,
+  pub next: Option<Url>,
+  pub ordered_items: Vec<AnyBase>,
+}
+
+pub(crate) fn outbox_page_url(outbox_url: &Url, page: i64) -> Result<Url, ParseError> {
+  Url::parse(&format!("{}?page={}", outbox_url, page))
+}
+
+/// The number of posts and comments in the outbox of the person
+pub(crate) async fn count_outbox_items(
+  person_id: PersonId,
+  pool: &DbPool,
+) -> Result<i64, LemmyError> {
+  let count = blocking(pool, move |conn| {
+    Person::count_outbox_items(conn, person_id)
+  })
+  .await??;
+  Ok(count)
+}
+
+/// Reads a page of the outbox of a local person, with its posts and comments merged by time
+pub(crate) async fn read_outbox_page(
+  person: &Person,
+  page: i64,
+  pool: &DbPool,
+) -> Result<PersonOutboxPage, LemmyError> {
+  let outbox_url = person.get_outbox_url()?;
+  let offset = (page - 1).saturating_mul(OUTBOX_PAGE_SIZE);
+  let person_id = person.id;
+  let (mut items, posts, comments) = blocking(pool, move |conn| {
+    // One more than needed, to find out if there is a next page
+    let items = Person::list_outbox_items(conn, person_id, OUTBOX_PAGE_SIZE + 1, offset)?;
+    let post_ids = items
+      .iter()
+      .filter_map(|i| match i {
+        OutboxItemId::Post(id) => Some(*id),
+        OutboxItemId::Comment(_) => None,
+      })
+      .collect();
+    let comment_ids = items
+      .iter()
+      .filter_map(|i| match i {
+        OutboxItemId::Comment(id) => Some(*id),
+        OutboxItemId::Post(_) => None,
+      })
+      .collect();
+    let posts = Post::read_many(conn, post_ids)?;
+    let comments = Comment::read_many(conn, comment_ids)?;
+    Ok((items, posts, comments)) as Result<_, LemmyError>
+  })
+  .await??;
+  let has_next = items.len() > OUTBOX_PAGE_SIZE as usize;
+  items.truncate(OUTBOX_PAGE_SIZE as usize);
+
+  // Converted in one batch per kind, then matched up with the items by their ids
+  let post_keys: Vec<_> = posts
+    .iter()
+    .map(|p| (p.id, p.ap_id.to_owned(), p.published))
+    .collect();
+  let mut objects = HashMap::new();
+  for ((id, ap_id, published), page) in post_keys.into_iter().zip(posts_to_apub(posts, pool).await?)
+  {
+    let object = (page.into_any_base()?, ap_id, published);
+    objects.insert(OutboxItemId::Post(id), object);
+  }
+  let comment_keys: Vec<_> = comments
+    .iter()
+    .map(|c| (c.id, c.ap_id.to_owned(), c.published))
+    .collect();
+  for ((id, ap_id, published), note) in comment_keys
+    .into_iter()
+    .zip(comments_to_apub(comments, pool).await?)
+  {
+    let object = (note.into_any_base()?, ap_id, published);
+    objects.insert(OutboxItemId::Comment(id), object);
+  }
+
+  let actor = person.actor_id.to_owned().into_inner();
+  let mut activities = Vec::with_capacity(items.len());
+  for item in items {
+    let (object, ap_id, published) = objects.remove(&item).context(location_info!())?;
+    activities.push(to_create(actor.to_owned(), object, &ap_id, published)?);
+  }
+  to_outbox_page(&outbox_url, page, activities, has_next)
+}
+
+/// Wraps a post or comment into a `Create`. The activity which was sent out originally might be
+/// deleted already, so its id is derived from the object instead.
+fn to_create(
+  actor: Url,
+  object: AnyBase,
+  ap_id: &DbUrl,
+  published: NaiveDateTime,
+) -> Result<AnyBase, LemmyError> {
+  let mut create = Create::new(actor, object);
+  create
+    .set_id(Url::parse(&format!("{}#create", ap_id))?)
+    .set_to(public())
+    .set_published(convert_datetime(published));
+  Ok(create.into_any_base()?)
+}
+
+fn to_outbox_page(
+  outbox_url: &Url,
+  page: i64,
+  activities: Vec<AnyBase>,
+  has_next: bool,
+) -> Result<PersonOutboxPage, LemmyError> {
+  Ok(PersonOutboxPage {
+    context: serde_json::to_value(lemmy_context()?)?,
+    kind: OrderedCollectionPageType::OrderedCollectionPage,
+    id: outbox_page_url(outbox_url, page)?,
+    part_of: outbox_url.to_owned(),
+    next: if has_next {
+      Some(outbox_page_url(outbox_url, page + 1)?)
+    } else {
+      None
+    },
+    ordered_items: activities,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{to_create, to_outbox_page, PersonOutboxPage};
+  use activitystreams::base::AnyBase;
+  use chrono::NaiveDate;
+  use lemmy_db_schema::DbUrl;
+  use serde_json::json;
+  use url::Url;
+
+  #[test]
+  fn test_outbox_page_serialization() {
+    let outbox_url = Url::parse("https://lemmy.tld/u/alice/outbox").unwrap();
+    let actor = Url::parse("https://lemmy.tld/u/alice").unwrap();
+    let post_id: DbUrl = Url::parse("https://lemmy.tld/post/1").unwrap().into();
+    let object = AnyBase::from_xsd_any_uri(post_id.to_owned().into_inner());
+    let published = NaiveDate::from_ymd(2021, 4, 30).and_hms(12, 0, 0);
+    let create = to_create(actor, object, &post_id, published).unwrap();
+
+    let page = to_outbox_page(&outbox_url, 2, vec![create], true).unwrap();
+    let json = serde_json::to_value(&page).unwrap();
+    assert_eq!(json!("OrderedCollectionPage"), json["type"]);
+    assert_eq!(json!("https://lemmy.tld/u/alice/outbox?page=2"), json["id"]);
+    assert_eq!(json!("https://lemmy.tld/u/alice/outbox"), json["partOf"]);
+    assert_eq!(
+      json!("https://lemmy.tld/u/alice/outbox?page=3"),
+      json["next"]
+    );
+
+    let item = &json["orderedItems"][0];
+    assert_eq!(json!("Create"), item["type"]);
+    assert_eq!(json!("https://lemmy.tld/post/1#create"), item["id"]);
+    assert_eq!(json!("https://lemmy.tld/u/alice"), item["actor"]);
+    assert_eq!(json!("https://lemmy.tld/post/1"), item["object"]);
+    assert_eq!(
+      json!("https://www.w3.org/ns/activitystreams#Public"),
+      item["to"]
+    );
+
+    // The last page has no next page, and survives a round trip
+    let last_page = to_outbox_page(&outbox_url, 3, vec![], false).unwrap();
+    let json = serde_json::to_string(&last_page).unwrap();
+    assert!(json.contains("\"next\":null"));
+    let parsed: PersonOutboxPage = serde_json::from_str(&json).unwrap();
+    assert!(parsed.ordered_items.is_empty());
+    assert_eq!(last_page.id, parsed.id);
+  }
+}
@@ -1,4 +1,5 @@
 use crate::{
+  activities::receive::collect_tags,
   extensions::{context::lemmy_context, page_extension::PageExtension},
   fetcher::person::get_or_fetch_and_upsert_person,
   objects::{
@@ -16,83 +17,100 @@ use crate::{
   PageExt,
 };
 use activitystreams::{
+  base::AnyBase,
   object::{kind::PageType, ApObject, Image, Page, Tombstone},
   prelude::*,
   public,
 };
 use activitystreams_ext::Ext1;
 use anyhow::Context;
-use lemmy_api_structs::blocking;
-use lemmy_db_queries::{Crud, DbPool};
+use itertools::Itertools;
+use lemmy_api_structs::{blocking, get_content_limits};
+use lemmy_db_queries::{
+  source::{
+    community::Community_,
+    community_tag::{CommunityTag_, MAX_POST_TAGS},
+    person::Person_,
+  },
+  Crud,
+  DbPool,
+};
 use lemmy_db_schema::{
   self,
   source::{
     community::Community,
+    community_tag::{CommunityTag, CommunityTagForm},
     person::Person,
     post::{Post, PostForm},
   },
+  CommunityId,
+  PersonId,
+  PostId,
 };
 use lemmy_utils::{
   location_info,
-  request::fetch_iframely_and_pictrs_data,
-  utils::{check_slurs, convert_datetime, remove_slurs},
+  request::{fetch_iframely_and_pictrs_data, localize_remote_image, proxy_markdown_images},
+  utils::{
+    check_slurs,
+    convert_datetime,
+    is_valid_post_tag_name,
+    remove_slurs,
+    truncate_to_max_length,
+  },
   LemmyError,
 };
 use lemmy_websocket::LemmyContext;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use url::Url;
 
+/// A `Hashtag` tag like Mastodon sends them, which activitystreams has no type for. The tags of a
+/// post are federated this way, named after the community tag.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct Hashtag {
+  #[serde(rename = "type")]
+  kind: String,
+  name: String,
+}
+
+impl Hashtag {
+  fn new(tag_name: &str) -> Self {
+    Hashtag {
+      kind: "Hashtag".to_string(),
+      name: format!("#{}", tag_name),
+    }
+  }
+
+  fn to_any_base(&self) -> Result<AnyBase, serde_json::Error> {
+    AnyBase::from_arbitrary_json(self)
+  }
+
+  /// Returns the tag name without the leading `#`, if the tag is a hashtag
+  fn tag_name(any_base: &AnyBase) -> Option<String> {
+    let hashtag: Hashtag = serde_json::from_value(serde_json::to_value(any_base).ok()?).ok()?;
+    if hashtag.kind != "Hashtag" {
+      return None;
+    }
+    Some(hashtag.name.trim_start_matches('#').to_string())
+  }
+}
+
 #[async_trait::async_trait(?Send)]
 impl ToApub for Post {
   type ApubType = PageExt;
 
   // Turn a Lemmy post into an ActivityPub page that can be sent out over the network.
   async fn to_apub(&self, pool: &DbPool) -> Result<PageExt, LemmyError> {
-    let mut page = ApObject::new(Page::new());
-
     let creator_id = self.creator_id;
     let creator = blocking(pool, move |conn| Person::read(conn, creator_id)).await??;
 
     let community_id = self.community_id;
     let community = blocking(pool, move |conn| Community::read(conn, community_id)).await??;
 
-    page
-      // Not needed when the Post is embedded in a collection (like for community outbox)
-      // TODO: need to set proper context defining sensitive/commentsEnabled fields
-      // https://git.asonix.dog/Aardwolf/activitystreams/issues/5
-      .set_many_contexts(lemmy_context()?)
-      .set_id(self.ap_id.to_owned().into_inner())
-      .set_name(self.name.to_owned())
-      // `summary` field for compatibility with lemmy v0.9.9 and older,
-      // TODO: remove this after some time
-      .set_summary(self.name.to_owned())
-      .set_published(convert_datetime(self.published))
-      .set_many_tos(vec![community.actor_id.into_inner(), public()])
-      .set_attributed_to(creator.actor_id.into_inner());
-
-    if let Some(body) = &self.body {
-      set_content_and_source(&mut page, &body)?;
-    }
-
-    if let Some(url) = &self.url {
-      page.set_url::<Url>(url.to_owned().into());
-    }
+    let post_id = self.id;
+    let tags = blocking(pool, move |conn| CommunityTag::list_for_post(conn, post_id)).await??;
 
-    if let Some(thumbnail_url) = &self.thumbnail_url {
-      let mut image = Image::new();
-      image.set_url::<Url>(thumbnail_url.to_owned().into());
-      page.set_image(image.into_any_base()?);
-    }
-
-    if let Some(u) = self.updated {
-      page.set_updated(convert_datetime(u));
-    }
-
-    let ext = PageExtension {
-      comments_enabled: Some(!self.locked),
-      sensitive: Some(self.nsfw),
-      stickied: Some(self.stickied),
-    };
-    Ok(Ext1::new(page, ext))
+    post_to_page(self, &creator, &community, &tags)
   }
 
   fn to_tombstone(&self) -> Result<Tombstone, LemmyError> {
@@ -105,6 +123,101 @@ impl ToApub for Post {
   }
 }
 
+/// Converts the posts of a collection like `to_apub()`. Their creators, communities and tags are
+/// read with one query each, instead of separately for every post.
+pub(crate) async fn posts_to_apub(
+  posts: Vec<Post>,
+  pool: &DbPool,
+) -> Result<Vec<PageExt>, LemmyError> {
+  blocking(pool, move |conn| {
+    let creator_ids = posts.iter().map(|p| p.creator_id).unique().collect();
+    let creators: HashMap<PersonId, Person> = Person::read_many(conn, creator_ids)?
+      .into_iter()
+      .map(|p| (p.id, p))
+      .collect();
+    let community_ids = posts.iter().map(|p| p.community_id).unique().collect();
+    let communities: HashMap<CommunityId, Community> = Community::read_many(conn, community_ids)?
+      .into_iter()
+      .map(|c| (c.id, c))
+      .collect();
+    let mut tags: HashMap<PostId, Vec<CommunityTag>> = HashMap::new();
+    let post_ids = posts.iter().map(|p| p.id).collect();
+    for (post_id, tag) in CommunityTag::list_for_posts(conn, post_ids)? {
+      tags.entry(post_id).or_default().push(tag);
+    }
+
+    posts
+      .iter()
+      .map(|post| {
+        let creator = creators.get(&post.creator_id).context(location_info!())?;
+        let community = communities
+          .get(&post.community_id)
+          .context(location_info!())?;
+        let post_tags = tags.get(&post.id).map(Vec::as_slice).unwrap_or_default();
+        post_to_page(post, creator, community, post_tags)
+      })
+      .collect()
+  })
+  .await?
+}
+
+fn post_to_page(
+  post: &Post,
+  creator: &Person,
+  community: &Community,
+  tags: &[CommunityTag],
+) -> Result<PageExt, LemmyError> {
+  let mut page = ApObject::new(Page::new());
+
+  page
+    // Not needed when the Post is embedded in a collection (like for community outbox)
+    // TODO: need to set proper context defining sensitive/commentsEnabled fields
+    // https://git.asonix.dog/Aardwolf/activitystreams/issues/5
+    .set_many_contexts(lemmy_context()?)
+    .set_id(post.ap_id.to_owned().into_inner())
+    .set_name(post.name.to_owned())
+    // `summary` field for compatibility with lemmy v0.9.9 and older,
+    // TODO: remove this after some time
+    .set_summary(post.name.to_owned())
+    .set_published(convert_datetime(post.published))
+    .set_many_tos(vec![community.actor_id.to_owned().into_inner(), public()])
+    .set_attributed_to(creator.actor_id.to_owned().into_inner());
+
+  if let Some(body) = &post.body {
+    set_content_and_source(&mut page, &body)?;
+  }
+
+  if let Some(url) = &post.url {
+    page.set_url::<Url>(url.to_owned().into());
+  }
+
+  if let Some(thumbnail_url) = &post.thumbnail_url {
+    let mut image = Image::new();
+    image.set_url::<Url>(thumbnail_url.to_owned().into());
+    page.set_image(image.into_any_base()?);
+  }
+
+  if let Some(u) = post.updated {
+    page.set_updated(convert_datetime(u));
+  }
+
+  if !tags.is_empty() {
+    page.set_many_tags(
+      tags
+        .iter()
+        .map(|t| Hashtag::new(&t.name).to_any_base())
+        .collect::<Result<Vec<AnyBase>, serde_json::Error>>()?,
+    );
+  }
+
+  let ext = PageExtension {
+    comments_enabled: Some(!post.locked),
+    sensitive: Some(post.nsfw),
+    stickied: Some(post.stickied),
+  };
+  Ok(Ext1::new(page, ext))
+}
+
 #[async_trait::async_trait(?Send)]
 impl FromApub for Post {
   type ApubType = PageExt;
@@ -121,10 +234,66 @@ impl FromApub for Post {
     let post: Post = get_object_from_apub(page, context, expected_domain, request_counter).await?;
     check_object_for_community_or_site_ban(page, post.community_id, context, request_counter)
       .await?;
+    if !post.local {
+      set_tags_from_apub(page, &post, context).await?;
+    }
     Ok(post)
   }
 }
 
+/// Tags the post with the hashtags of the page which match a tag of its community. Remote
+/// communities don't federate their tags otherwise, so the missing ones are created for them.
+async fn set_tags_from_apub(
+  page: &PageExt,
+  post: &Post,
+  context: &LemmyContext,
+) -> Result<(), LemmyError> {
+  let mut names = collect_tags(None, page.inner.tag())
+    .iter()
+    .filter_map(Hashtag::tag_name)
+    .filter(|n| is_valid_post_tag_name(n))
+    .collect::<Vec<String>>();
+  names.sort();
+  names.dedup();
+  names.truncate(MAX_POST_TAGS);
+
+  let community_id = post.community_id;
+  let community = blocking(context.pool(), move |conn| {
+    Community::read(conn, community_id)
+  })
+  .await??;
+  let slur_regex = context
+    .slur_filter()
+    .regex_for_community(community.exempt_from_slur_filter);
+
+  let post_id = post.id;
+  blocking(context.pool(), move |conn| -> Result<(), LemmyError> {
+    let community_tags = CommunityTag::list_for_community(conn, community_id)?;
+    let mut tag_ids = Vec::new();
+    for name in names {
+      if let Some(tag) = community_tags
+        .iter()
+        .find(|t| t.name.eq_ignore_ascii_case(&name))
+      {
+        tag_ids.push(tag.id);
+      } else if !community.local && check_slurs(&name, &slur_regex).is_ok() {
+        let form = CommunityTagForm {
+          community_id,
+          name,
+          color: None,
+        };
+        if let Ok(tag) = CommunityTag::create(conn, &form) {
+          tag_ids.push(tag.id);
+        }
+      }
+    }
+    CommunityTag::set_for_post(conn, post_id, &tag_ids)?;
+    Ok(())
+  })
+  .await??;
+  Ok(())
+}
+
 #[async_trait::async_trait(?Send)]
 impl FromApubToForm<PageExt> for PostForm {
   async fn from_apub(
@@ -146,6 +315,7 @@ impl FromApubToForm<PageExt> for PostForm {
       get_or_fetch_and_upsert_person(creator_actor_id, context, request_counter).await?;
 
     let community = get_to_community(page, context, request_counter).await?;
+    let content_limits = get_content_limits(context.pool()).await?;
 
     let thumbnail_url: Option<Url> = match &page.inner.image() {
       Some(any_image) => Image::from_any_base(
@@ -167,13 +337,18 @@ impl FromApubToForm<PageExt> for PostForm {
       .url()
       .map(|u| u.as_single_xsd_any_uri())
       .flatten()
+      // Drop links like `javascript:` instead of rejecting the whole post
+      .filter(|u| content_limits.is_url_allowed(u))
       .map(|u| u.to_owned());
 
     let (iframely_title, iframely_description, iframely_html, pictrs_thumbnail) =
       if let Some(url) = &url {
         fetch_iframely_and_pictrs_data(context.client(), Some(url)).await
+      } else if let Some(thumbnail_url) = &thumbnail_url {
+        let thumbnail = localize_remote_image(context.client(), thumbnail_url).await;
+        (None, None, None, thumbnail)
       } else {
-        (None, None, None, thumbnail_url)
+        (None, None, None, None)
       };
 
     let name = page
@@ -187,10 +362,14 @@ impl FromApubToForm<PageExt> for PostForm {
       .as_single_xsd_string()
       .context(location_info!())?
       .to_string();
-    let body = get_source_markdown_value(page)?;
+    let body = get_source_markdown_value(page)?
+      .map(|b| truncate_to_max_length(&b, content_limits.max_post_body_length));
 
-    check_slurs(&name)?;
-    let body_slurs_removed = body.map(|b| remove_slurs(&b));
+    let slur_regex = context
+      .slur_filter()
+      .regex_for_community(community.exempt_from_slur_filter);
+    check_slurs(&name, &slur_regex)?;
+    let body_slurs_removed = body.map(|b| proxy_markdown_images(&remove_slurs(&b, &slur_regex)));
     Ok(PostForm {
       name,
       url: url.map(|u| u.into()),
@@ -221,3 +400,177 @@ impl FromApubToForm<PageExt> for PostForm {
     })
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::objects::{post::posts_to_apub, ToApub};
+  use diesel::{
+    r2d2::{
+      event::{CheckoutEvent, HandleEvent},
+      ConnectionManager,
+      Pool,
+    },
+    PgConnection,
+  };
+  use lemmy_db_queries::{
+    establish_unpooled_connection,
+    get_database_url_from_env,
+    source::community_tag::CommunityTag_,
+    Crud,
+  };
+  use lemmy_db_schema::source::{
+    community::{Community, CommunityForm},
+    community_tag::{CommunityTag, CommunityTagForm},
+    person::{Person, PersonForm},
+    post::{Post, PostForm},
+  };
+  use serial_test::serial;
+  use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+  };
+
+  /// Counts how often a connection is taken from the pool
+  #[derive(Debug)]
+  struct CheckoutCounter(Arc<AtomicUsize>);
+
+  impl HandleEvent for CheckoutCounter {
+    fn handle_checkout(&self, _event: CheckoutEvent) {
+      self.0.fetch_add(1, Ordering::SeqCst);
+    }
+  }
+
+  #[test]
+  #[serial]
+  fn test_posts_to_apub_reads_in_batches() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "posts_to_apub_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "posts_to_apub_community".into(),
+      creator_id: inserted_person.id,
+      title: "nada".to_owned(),
+      description: None,
+      nsfw: false,
+      removed: None,
+      deleted: None,
+      updated: None,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let posts = (0..20)
+      .map(|i| {
+        let new_post = PostForm {
+          name: format!("Collection post {}", i),
+          url: None,
+          body: None,
+          creator_id: inserted_person.id,
+          community_id: inserted_community.id,
+          removed: None,
+          deleted: None,
+          locked: None,
+          stickied: None,
+          nsfw: false,
+          updated: None,
+          embed_title: None,
+          embed_description: None,
+          embed_html: None,
+          thumbnail_url: None,
+          ap_id: None,
+          local: true,
+          published: None,
+        };
+        Post::create(&conn, &new_post).unwrap()
+      })
+      .collect::<Vec<Post>>();
+
+    let tag = CommunityTag::create(
+      &conn,
+      &CommunityTagForm {
+        community_id: inserted_community.id,
+        name: "meta".into(),
+        color: None,
+      },
+    )
+    .unwrap();
+    CommunityTag::set_for_post(&conn, posts[3].id, &[tag.id]).unwrap();
+
+    let checkouts = Arc::new(AtomicUsize::new(0));
+    let manager = ConnectionManager::<PgConnection>::new(get_database_url_from_env().unwrap());
+    let pool = Pool::builder()
+      .max_size(2)
+      .event_handler(Box::new(CheckoutCounter(checkouts.clone())))
+      .build(manager)
+      .unwrap();
+
+    let mut system = actix_rt::System::new("test_posts_to_apub_reads_in_batches");
+    system.block_on(async {
+      // Each post on its own reads its creator, community and tags separately
+      let single_page = posts[3].to_apub(&pool).await.unwrap();
+      assert_eq!(3, checkouts.swap(0, Ordering::SeqCst));
+
+      let pages = posts_to_apub(posts.to_owned(), &pool).await.unwrap();
+      assert!(checkouts.load(Ordering::SeqCst) < 5);
+
+      // Same result as converting the posts one by one, in the same order
+      assert_eq!(20, pages.len());
+      assert_eq!(
+        serde_json::to_value(&single_page).unwrap(),
+        serde_json::to_value(&pages[3]).unwrap()
+      );
+      let page_ids = pages
+        .iter()
+        .map(|p| p.inner.id_unchecked().unwrap().to_owned())
+        .collect::<Vec<_>>();
+      let post_ids = posts
+        .iter()
+        .map(|p| p.ap_id.to_owned().into_inner())
+        .collect::<Vec<_>>();
+      assert_eq!(post_ids, page_ids);
+    });
+
+    for post in &posts {
+      Post::delete(&conn, post.id).unwrap();
+    }
+    CommunityTag::delete(&conn, tag.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+  }
+}
@@ -1,6 +1,6 @@
 use crate::{
   check_is_apub_id_valid,
-  extensions::context::lemmy_context,
+  extensions::{context::lemmy_context, note_extension::NoteExtension},
   fetcher::person::get_or_fetch_and_upsert_person,
   objects::{
     check_object_domain,
@@ -18,6 +18,7 @@ use activitystreams::{
   object::{kind::NoteType, ApObject, Note, Tombstone},
   prelude::*,
 };
+use activitystreams_ext::Ext1;
 use anyhow::Context;
 use lemmy_api_structs::blocking;
 use lemmy_db_queries::{Crud, DbPool};
@@ -55,7 +56,7 @@ impl ToApub for PrivateMessage {
       private_message.set_updated(convert_datetime(u));
     }
 
-    Ok(private_message)
+    Ok(Ext1::new(private_message, NoteExtension::default()))
   }
 
   fn to_tombstone(&self) -> Result<Tombstone, LemmyError> {
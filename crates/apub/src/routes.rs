@@ -5,10 +5,16 @@ use crate::{
       get_apub_community_followers,
       get_apub_community_http,
       get_apub_community_inbox,
+      get_apub_community_modlog,
       get_apub_community_outbox,
     },
     get_activity,
-    person::{get_apub_person_http, get_apub_person_inbox, get_apub_person_outbox},
+    person::{
+      get_apub_person_featured,
+      get_apub_person_http,
+      get_apub_person_inbox,
+      get_apub_person_outbox,
+    },
     post::get_apub_post,
   },
   inbox::{
@@ -57,11 +63,19 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             "/c/{community_name}/inbox",
             web::get().to(get_apub_community_inbox),
           )
+          .route(
+            "/c/{community_name}/modlog",
+            web::get().to(get_apub_community_modlog),
+          )
           .route("/u/{user_name}", web::get().to(get_apub_person_http))
           .route(
             "/u/{user_name}/outbox",
             web::get().to(get_apub_person_outbox),
           )
+          .route(
+            "/u/{user_name}/featured",
+            web::get().to(get_apub_person_featured),
+          )
           .route("/u/{user_name}/inbox", web::get().to(get_apub_person_inbox))
           .route("/post/{post_id}", web::get().to(get_apub_post))
           .route("/comment/{comment_id}", web::get().to(get_apub_comment))
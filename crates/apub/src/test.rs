@@ -0,0 +1,69 @@
+//! Helpers for tests which receive activities, against the test database.
+
+use crate::activity_queue::create_activity_queue;
+use actix::Actor;
+use diesel::{
+  r2d2::{ConnectionManager, Pool},
+  PgConnection,
+};
+use lemmy_api_structs::{site::SiteCache, VoteDebouncer};
+use lemmy_db_queries::get_database_url_from_env;
+use lemmy_utils::{
+  fetch_limit::FetchLimiter,
+  rate_limit::{rate_limiter::RateLimiter, RateLimit},
+  settings::structs::Settings,
+  slur_filter::SlurFilter,
+  ConnectionId,
+  LemmyError,
+};
+use lemmy_websocket::{chat_server::ChatServer, LemmyContext, UserOperation};
+use reqwest::Client;
+use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
+use tokio::sync::Mutex;
+
+fn message_handler(
+  _context: LemmyContext,
+  _id: ConnectionId,
+  _op: UserOperation,
+  _data: &str,
+) -> Pin<Box<dyn Future<Output = Result<String, LemmyError>> + '_>> {
+  Box::pin(async { Ok(String::new()) })
+}
+
+/// Has to be called inside of a running actix system
+pub(crate) fn init_context() -> LemmyContext {
+  let manager = ConnectionManager::<PgConnection>::new(get_database_url_from_env().unwrap());
+  let pool = Pool::builder().max_size(2).build(manager).unwrap();
+  let rate_limiter = RateLimit {
+    rate_limiter: Arc::new(Mutex::new(RateLimiter::default())),
+  };
+  let activity_queue = create_activity_queue(pool.clone());
+  let fetch_limiter = FetchLimiter::new(Settings::get().fetch_limit());
+  let site_cache = SiteCache::new(Duration::from_secs(60));
+  let slur_filter = SlurFilter::new(None);
+  let vote_debouncer = VoteDebouncer::new(Settings::get().vote_debounce());
+  let chat_server = ChatServer::startup(
+    pool.clone(),
+    None,
+    rate_limiter,
+    message_handler,
+    Client::default(),
+    activity_queue.clone(),
+    fetch_limiter.clone(),
+    site_cache.clone(),
+    slur_filter.clone(),
+    vote_debouncer.clone(),
+  )
+  .start();
+  LemmyContext::create(
+    pool,
+    None,
+    chat_server,
+    Client::default(),
+    activity_queue,
+    fetch_limiter,
+    site_cache,
+    slur_filter,
+    vote_debouncer,
+  )
+}
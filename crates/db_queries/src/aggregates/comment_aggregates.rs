@@ -1,24 +1,56 @@
-use diesel::{result::Error, *};
-use lemmy_db_schema::schema::comment_aggregates;
+use crate::functions::hot_rank;
+use diesel::{
+  dsl::{now, IntervalDsl},
+  result::Error,
+  *,
+};
+use lemmy_db_schema::{schema::comment_aggregates, CommentId};
 use serde::Serialize;
 
 #[derive(Queryable, Associations, Identifiable, PartialEq, Debug, Serialize, Clone)]
 #[table_name = "comment_aggregates"]
 pub struct CommentAggregates {
   pub id: i32,
-  pub comment_id: i32,
+  pub comment_id: CommentId,
   pub score: i64,
   pub upvotes: i64,
   pub downvotes: i64,
   pub published: chrono::NaiveDateTime,
+  pub hot_rank: i32,
+  /// Number of replies, including the replies to replies, which are neither deleted nor removed
+  pub child_count: i32,
 }
 
 impl CommentAggregates {
-  pub fn read(conn: &PgConnection, comment_id: i32) -> Result<Self, Error> {
+  pub fn read(conn: &PgConnection, comment_id: CommentId) -> Result<Self, Error> {
     comment_aggregates::table
       .filter(comment_aggregates::comment_id.eq(comment_id))
       .first::<Self>(conn)
   }
+
+  /// Recalculates the hot ranks of up to `batch_size` comments from the last week, which have an
+  /// id larger than `after_id`. Returns the largest updated id, or `None` once all comments are
+  /// done. Older comments keep the rank which they got on insert.
+  pub fn update_hot_ranks(
+    conn: &PgConnection,
+    after_id: i32,
+    batch_size: i64,
+  ) -> Result<Option<i32>, Error> {
+    let ids = comment_aggregates::table
+      .select(comment_aggregates::id)
+      .filter(comment_aggregates::published.gt(now - 1.weeks()))
+      .filter(comment_aggregates::id.gt(after_id))
+      .order_by(comment_aggregates::id)
+      .limit(batch_size)
+      .load::<i32>(conn)?;
+    diesel::update(comment_aggregates::table.filter(comment_aggregates::id.eq_any(&ids)))
+      .set(comment_aggregates::hot_rank.eq(hot_rank(
+        comment_aggregates::score,
+        comment_aggregates::published,
+      )))
+      .execute(conn)?;
+    Ok(ids.last().copied())
+  }
 }
 
 #[cfg(test)]
@@ -26,6 +58,7 @@ mod tests {
   use crate::{
     aggregates::comment_aggregates::CommentAggregates,
     establish_unpooled_connection,
+    source::comment::Comment_,
     Crud,
     Likeable,
   };
@@ -104,6 +137,13 @@ mod tests {
       followers_url: None,
       inbox_url: None,
       shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
     };
 
     let inserted_community = Community::create(&conn, &new_community).unwrap();
@@ -161,7 +201,60 @@ mod tests {
       local: true,
     };
 
-    let _inserted_child_comment = Comment::create(&conn, &child_comment_form).unwrap();
+    let inserted_child_comment = Comment::create(&conn, &child_comment_form).unwrap();
+
+    let grandchild_comment_form = CommentForm {
+      parent_id: Some(inserted_child_comment.id),
+      ..child_comment_form
+    };
+    let inserted_grandchild_comment = Comment::create(&conn, &grandchild_comment_form).unwrap();
+
+    let parent_aggs = CommentAggregates::read(&conn, inserted_comment.id).unwrap();
+    let child_aggs = CommentAggregates::read(&conn, inserted_child_comment.id).unwrap();
+    assert_eq!(2, parent_aggs.child_count);
+    assert_eq!(1, child_aggs.child_count);
+
+    // Deleted and removed replies aren't counted, restoring them counts them again
+    Comment::update_deleted(&conn, inserted_grandchild_comment.id, true).unwrap();
+    let parent_aggs = CommentAggregates::read(&conn, inserted_comment.id).unwrap();
+    assert_eq!(1, parent_aggs.child_count);
+    Comment::update_removed(&conn, inserted_grandchild_comment.id, true).unwrap();
+    Comment::update_deleted(&conn, inserted_grandchild_comment.id, false).unwrap();
+    let parent_aggs = CommentAggregates::read(&conn, inserted_comment.id).unwrap();
+    assert_eq!(1, parent_aggs.child_count);
+    Comment::update_removed(&conn, inserted_grandchild_comment.id, false).unwrap();
+    let parent_aggs = CommentAggregates::read(&conn, inserted_comment.id).unwrap();
+    let child_aggs = CommentAggregates::read(&conn, inserted_child_comment.id).unwrap();
+    assert_eq!(2, parent_aggs.child_count);
+    assert_eq!(1, child_aggs.child_count);
+
+    // A deleted reply keeps counting the replies to it
+    Comment::update_deleted(&conn, inserted_child_comment.id, true).unwrap();
+    let parent_aggs = CommentAggregates::read(&conn, inserted_comment.id).unwrap();
+    assert_eq!(1, parent_aggs.child_count);
+    Comment::update_deleted(&conn, inserted_child_comment.id, false).unwrap();
+
+    // Deleting a reply only counts for its ancestors
+    Comment::delete(&conn, inserted_grandchild_comment.id).unwrap();
+    let parent_aggs = CommentAggregates::read(&conn, inserted_comment.id).unwrap();
+    let child_aggs = CommentAggregates::read(&conn, inserted_child_comment.id).unwrap();
+    assert_eq!(1, parent_aggs.child_count);
+    assert_eq!(0, child_aggs.child_count);
+
+    // Deleting a removed reply doesn't take it from the count again
+    let removed_grandchild_comment = Comment::create(&conn, &grandchild_comment_form).unwrap();
+    Comment::update_removed(&conn, removed_grandchild_comment.id, true).unwrap();
+    Comment::delete(&conn, removed_grandchild_comment.id).unwrap();
+    let parent_aggs = CommentAggregates::read(&conn, inserted_comment.id).unwrap();
+    assert_eq!(1, parent_aggs.child_count);
+
+    // Deleting a comment also deletes its replies
+    Comment::create(&conn, &grandchild_comment_form).unwrap();
+    let parent_aggs = CommentAggregates::read(&conn, inserted_comment.id).unwrap();
+    assert_eq!(2, parent_aggs.child_count);
+    Comment::delete(&conn, inserted_child_comment.id).unwrap();
+    let parent_aggs = CommentAggregates::read(&conn, inserted_comment.id).unwrap();
+    assert_eq!(0, parent_aggs.child_count);
 
     let comment_like = CommentLikeForm {
       comment_id: inserted_comment.id,
@@ -1,12 +1,13 @@
-use diesel::{result::Error, *};
-use lemmy_db_schema::schema::community_aggregates;
+use crate::functions::hot_rank;
+use diesel::{result::Error, sql_types::Integer, *};
+use lemmy_db_schema::{schema::community_aggregates, CommunityId};
 use serde::Serialize;
 
 #[derive(Queryable, Associations, Identifiable, PartialEq, Debug, Serialize, Clone)]
 #[table_name = "community_aggregates"]
 pub struct CommunityAggregates {
   pub id: i32,
-  pub community_id: i32,
+  pub community_id: CommunityId,
   pub subscribers: i64,
   pub posts: i64,
   pub comments: i64,
@@ -15,14 +16,123 @@ pub struct CommunityAggregates {
   pub users_active_week: i64,
   pub users_active_month: i64,
   pub users_active_half_year: i64,
+  pub hot_rank: i32,
+  /// Time of the newest post or comment, or the creation of the community if there is none
+  pub last_activity: chrono::NaiveDateTime,
 }
 
 impl CommunityAggregates {
-  pub fn read(conn: &PgConnection, community_id: i32) -> Result<Self, Error> {
+  pub fn read(conn: &PgConnection, community_id: CommunityId) -> Result<Self, Error> {
     community_aggregates::table
       .filter(community_aggregates::community_id.eq(community_id))
       .first::<Self>(conn)
   }
+
+  /// Recomputes the aggregates of one community from the base tables, returns 1 if the stored row
+  /// was wrong or missing.
+  pub fn recalculate(conn: &PgConnection, community_id: CommunityId) -> Result<usize, Error> {
+    Self::recalculate_range(conn, community_id.0, community_id.0)
+  }
+
+  /// Recomputes the subscriber, post and comment counts of all communities with an id between
+  /// `from_id` and `to_id` (inclusive) in a single statement. The active user counts are left to
+  /// the scheduled task. Returns the number of rows which were corrected.
+  pub fn recalculate_range(conn: &PgConnection, from_id: i32, to_id: i32) -> Result<usize, Error> {
+    sql_query(
+      "insert into community_aggregates (community_id, subscribers, posts, comments, published)
+      select c.id,
+        (select count(*) from community_follower cf where cf.community_id = c.id),
+        (select count(*) from post where post.community_id = c.id),
+        (select count(*) from comment
+          inner join post on post.id = comment.post_id where post.community_id = c.id),
+        c.published
+      from community c
+      where c.id between $1 and $2
+      on conflict (community_id) do update set
+        subscribers = excluded.subscribers,
+        posts = excluded.posts,
+        comments = excluded.comments,
+        published = excluded.published
+      where (community_aggregates.subscribers, community_aggregates.posts,
+        community_aggregates.comments, community_aggregates.published)
+        is distinct from (excluded.subscribers, excluded.posts,
+        excluded.comments, excluded.published)",
+    )
+    .bind::<Integer, _>(from_id)
+    .bind::<Integer, _>(to_id)
+    .execute(conn)
+  }
+
+  /// Counts the distinct people who posted, commented or voted in each community during the last
+  /// day, week, month and half year, and stores the counts. All four windows are computed by a
+  /// single grouped query over the activity of the last half year. Returns the number of
+  /// communities whose counts changed.
+  pub fn update_active_counts(conn: &PgConnection) -> Result<usize, Error> {
+    sql_query(
+      "with activity as (
+        select community_id,
+          count(distinct person_id) filter (
+            where published > ('now'::timestamp - '1 day'::interval)) as day_,
+          count(distinct person_id) filter (
+            where published > ('now'::timestamp - '1 week'::interval)) as week_,
+          count(distinct person_id) filter (
+            where published > ('now'::timestamp - '1 month'::interval)) as month_,
+          count(distinct person_id) as half_year_
+        from (
+          select p.community_id, p.creator_id as person_id, p.published from post p
+          where p.published > ('now'::timestamp - '6 months'::interval)
+          union all
+          select p.community_id, c.creator_id, c.published from comment c
+          inner join post p on p.id = c.post_id
+          where c.published > ('now'::timestamp - '6 months'::interval)
+          union all
+          select p.community_id, pl.person_id, pl.published from post_like pl
+          inner join post p on p.id = pl.post_id
+          where pl.published > ('now'::timestamp - '6 months'::interval)
+          union all
+          select p.community_id, cl.person_id, cl.published from comment_like cl
+          inner join post p on p.id = cl.post_id
+          where cl.published > ('now'::timestamp - '6 months'::interval)
+        ) a
+        group by community_id
+      )
+      update community_aggregates ca set
+        users_active_day = coalesce(activity.day_, 0),
+        users_active_week = coalesce(activity.week_, 0),
+        users_active_month = coalesce(activity.month_, 0),
+        users_active_half_year = coalesce(activity.half_year_, 0)
+      from community_aggregates existing
+      left join activity on activity.community_id = existing.community_id
+      where ca.id = existing.id
+      and (ca.users_active_day, ca.users_active_week, ca.users_active_month,
+        ca.users_active_half_year)
+        is distinct from (coalesce(activity.day_, 0), coalesce(activity.week_, 0),
+        coalesce(activity.month_, 0), coalesce(activity.half_year_, 0))",
+    )
+    .execute(conn)
+  }
+
+  /// Recalculates the hot ranks of up to `batch_size` communities, which have an id larger than
+  /// `after_id`. Returns the largest updated id, or `None` once all communities are done.
+  pub fn update_hot_ranks(
+    conn: &PgConnection,
+    after_id: i32,
+    batch_size: i64,
+  ) -> Result<Option<i32>, Error> {
+    let ids = community_aggregates::table
+      .select(community_aggregates::id)
+      .filter(community_aggregates::id.gt(after_id))
+      .order_by(community_aggregates::id)
+      .limit(batch_size)
+      .load::<i32>(conn)?;
+    diesel::update(community_aggregates::table.filter(community_aggregates::id.eq_any(&ids)))
+      .set(community_aggregates::hot_rank.eq(hot_rank(
+        community_aggregates::subscribers,
+        community_aggregates::published,
+      )))
+      .execute(conn)?;
+    Ok(ids.last().copied())
+  }
 }
 
 #[cfg(test)]
@@ -108,6 +218,13 @@ mod tests {
       followers_url: None,
       inbox_url: None,
       shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
     };
 
     let inserted_community = Community::create(&conn, &new_community).unwrap();
@@ -132,6 +249,13 @@ mod tests {
       followers_url: None,
       inbox_url: None,
       shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
     };
 
     let another_inserted_community = Community::create(&conn, &another_community).unwrap();
@@ -213,7 +337,9 @@ mod tests {
       local: true,
     };
 
-    let _inserted_child_comment = Comment::create(&conn, &child_comment_form).unwrap();
+    let inserted_child_comment = Comment::create(&conn, &child_comment_form).unwrap();
+
+    CommunityAggregates::update_active_counts(&conn).unwrap();
 
     let community_aggregates_before_delete =
       CommunityAggregates::read(&conn, inserted_community.id).unwrap();
@@ -221,6 +347,12 @@ mod tests {
     assert_eq!(2, community_aggregates_before_delete.subscribers);
     assert_eq!(1, community_aggregates_before_delete.posts);
     assert_eq!(2, community_aggregates_before_delete.comments);
+    assert_eq!(1, community_aggregates_before_delete.users_active_day);
+    assert_eq!(1, community_aggregates_before_delete.users_active_half_year);
+    assert_eq!(
+      inserted_child_comment.published,
+      community_aggregates_before_delete.last_activity
+    );
 
     // Test the other community
     let another_community_aggs =
@@ -228,6 +360,8 @@ mod tests {
     assert_eq!(1, another_community_aggs.subscribers);
     assert_eq!(0, another_community_aggs.posts);
     assert_eq!(0, another_community_aggs.comments);
+    assert_eq!(0, another_community_aggs.users_active_day);
+    assert!(another_community_aggs.last_activity < inserted_post.published);
 
     // Unfollow test
     CommunityFollower::unfollow(&conn, &second_person_follow).unwrap();
@@ -1,5 +1,77 @@
+use crate::aggregates::{
+  community_aggregates::CommunityAggregates,
+  person_aggregates::PersonAggregates,
+  post_aggregates::PostAggregates,
+  site_aggregates::SiteAggregates,
+};
+use diesel::{result::Error, *};
+use lemmy_db_schema::{
+  schema::{community, person, post},
+  CommunityId,
+  PersonId,
+  PostId,
+};
+
 pub mod comment_aggregates;
 pub mod community_aggregates;
 pub mod person_aggregates;
 pub mod post_aggregates;
 pub mod site_aggregates;
+
+/// Recomputes the site aggregates, and the aggregates of every person, community and post. Each
+/// statement only covers `batch_size` ids, so that no single statement holds its locks for long.
+/// Returns the number of rows which were corrected.
+pub fn recalculate_all(conn: &PgConnection, batch_size: i32) -> Result<usize, Error> {
+  let mut corrected = SiteAggregates::recalculate(conn)?;
+
+  let max_person_id = person::table
+    .select(person::id)
+    .order_by(person::id.desc())
+    .first::<PersonId>(conn)
+    .optional()?;
+  if let Some(max_id) = max_person_id {
+    corrected += in_batches(max_id.0, batch_size, |from, to| {
+      PersonAggregates::recalculate_range(conn, from, to)
+    })?;
+  }
+
+  let max_community_id = community::table
+    .select(community::id)
+    .order_by(community::id.desc())
+    .first::<CommunityId>(conn)
+    .optional()?;
+  if let Some(max_id) = max_community_id {
+    corrected += in_batches(max_id.0, batch_size, |from, to| {
+      CommunityAggregates::recalculate_range(conn, from, to)
+    })?;
+  }
+
+  let max_post_id = post::table
+    .select(post::id)
+    .order_by(post::id.desc())
+    .first::<PostId>(conn)
+    .optional()?;
+  if let Some(max_id) = max_post_id {
+    corrected += in_batches(max_id.0, batch_size, |from, to| {
+      PostAggregates::recalculate_range(conn, from, to)
+    })?;
+  }
+
+  Ok(corrected)
+}
+
+fn in_batches<F>(max_id: i32, batch_size: i32, recalculate: F) -> Result<usize, Error>
+where
+  F: Fn(i32, i32) -> Result<usize, Error>,
+{
+  let mut corrected = 0;
+  let mut from = 1;
+  loop {
+    let to = from.saturating_add(batch_size - 1);
+    corrected += recalculate(from, to)?;
+    if to >= max_id {
+      return Ok(corrected);
+    }
+    from = to + 1;
+  }
+}
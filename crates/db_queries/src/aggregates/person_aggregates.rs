@@ -1,12 +1,12 @@
-use diesel::{result::Error, *};
-use lemmy_db_schema::schema::person_aggregates;
+use diesel::{result::Error, sql_types::Integer, *};
+use lemmy_db_schema::{schema::person_aggregates, PersonId};
 use serde::Serialize;
 
 #[derive(Queryable, Associations, Identifiable, PartialEq, Debug, Serialize, Clone)]
 #[table_name = "person_aggregates"]
 pub struct PersonAggregates {
   pub id: i32,
-  pub person_id: i32,
+  pub person_id: PersonId,
   pub post_count: i64,
   pub post_score: i64,
   pub comment_count: i64,
@@ -14,11 +14,46 @@ pub struct PersonAggregates {
 }
 
 impl PersonAggregates {
-  pub fn read(conn: &PgConnection, person_id: i32) -> Result<Self, Error> {
+  pub fn read(conn: &PgConnection, person_id: PersonId) -> Result<Self, Error> {
     person_aggregates::table
       .filter(person_aggregates::person_id.eq(person_id))
       .first::<Self>(conn)
   }
+
+  /// Recomputes the aggregates of one person from the base tables, returns 1 if the stored row was
+  /// wrong or missing.
+  pub fn recalculate(conn: &PgConnection, person_id: PersonId) -> Result<usize, Error> {
+    Self::recalculate_range(conn, person_id.0, person_id.0)
+  }
+
+  /// Recomputes the aggregates of all persons with an id between `from_id` and `to_id` (inclusive)
+  /// in a single statement. Returns the number of rows which were corrected.
+  pub fn recalculate_range(conn: &PgConnection, from_id: i32, to_id: i32) -> Result<usize, Error> {
+    sql_query(
+      "insert into person_aggregates (person_id, post_count, post_score, comment_count, comment_score)
+      select p.id,
+        (select count(*) from post where post.creator_id = p.id),
+        (select coalesce(sum(pl.score), 0) from post_like pl
+          inner join post on post.id = pl.post_id where post.creator_id = p.id),
+        (select count(*) from comment where comment.creator_id = p.id),
+        (select coalesce(sum(cl.score), 0) from comment_like cl
+          inner join comment on comment.id = cl.comment_id where comment.creator_id = p.id)
+      from person p
+      where p.id between $1 and $2
+      on conflict (person_id) do update set
+        post_count = excluded.post_count,
+        post_score = excluded.post_score,
+        comment_count = excluded.comment_count,
+        comment_score = excluded.comment_score
+      where (person_aggregates.post_count, person_aggregates.post_score,
+        person_aggregates.comment_count, person_aggregates.comment_score)
+        is distinct from (excluded.post_count, excluded.post_score,
+        excluded.comment_count, excluded.comment_score)",
+    )
+    .bind::<Integer, _>(from_id)
+    .bind::<Integer, _>(to_id)
+    .execute(conn)
+  }
 }
 
 #[cfg(test)]
@@ -29,11 +64,15 @@ mod tests {
     Crud,
     Likeable,
   };
-  use lemmy_db_schema::source::{
-    comment::{Comment, CommentForm, CommentLike, CommentLikeForm},
-    community::{Community, CommunityForm},
-    person::{Person, PersonForm},
-    post::{Post, PostForm, PostLike, PostLikeForm},
+  use diesel::*;
+  use lemmy_db_schema::{
+    schema::person_aggregates,
+    source::{
+      comment::{Comment, CommentForm, CommentLike, CommentLikeForm},
+      community::{Community, CommunityForm},
+      person::{Person, PersonForm},
+      post::{Post, PostForm, PostLike, PostLikeForm},
+    },
   };
   use serial_test::serial;
 
@@ -104,6 +143,13 @@ mod tests {
       followers_url: None,
       inbox_url: None,
       shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
     };
 
     let inserted_community = Community::create(&conn, &new_community).unwrap();
@@ -234,4 +280,130 @@ mod tests {
     let after_delete = PersonAggregates::read(&conn, inserted_person.id);
     assert!(after_delete.is_err());
   }
+
+  #[test]
+  #[serial]
+  fn test_recalculate() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "thommy_recalculate_agg".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "TIL_recalculate_agg".into(),
+      creator_id: inserted_person.id,
+      title: "nada".to_owned(),
+      description: None,
+      nsfw: false,
+      removed: None,
+      deleted: None,
+      updated: None,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let new_post = PostForm {
+      name: "A test post".into(),
+      url: None,
+      body: None,
+      creator_id: inserted_person.id,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      nsfw: false,
+      updated: None,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      thumbnail_url: None,
+      ap_id: None,
+      local: true,
+      published: None,
+    };
+    let inserted_post = Post::create(&conn, &new_post).unwrap();
+
+    let post_like = PostLikeForm {
+      post_id: inserted_post.id,
+      person_id: inserted_person.id,
+      score: 1,
+    };
+    PostLike::like(&conn, &post_like).unwrap();
+    let expected = PersonAggregates::read(&conn, inserted_person.id).unwrap();
+
+    // Nothing to correct yet
+    let unchanged = PersonAggregates::recalculate(&conn, inserted_person.id).unwrap();
+
+    // Corrupt the row, like a broken migration would
+    diesel::update(
+      person_aggregates::table.filter(person_aggregates::person_id.eq(inserted_person.id)),
+    )
+    .set((
+      person_aggregates::post_count.eq(5000),
+      person_aggregates::comment_score.eq(-3),
+    ))
+    .execute(&conn)
+    .unwrap();
+    let corrected = PersonAggregates::recalculate(&conn, inserted_person.id).unwrap();
+    let after_corrupt = PersonAggregates::read(&conn, inserted_person.id).unwrap();
+
+    // A missing row gets inserted again
+    diesel::delete(
+      person_aggregates::table.filter(person_aggregates::person_id.eq(inserted_person.id)),
+    )
+    .execute(&conn)
+    .unwrap();
+    let inserted =
+      PersonAggregates::recalculate_range(&conn, inserted_person.id.0, inserted_person.id.0)
+        .unwrap();
+    let after_missing = PersonAggregates::read(&conn, inserted_person.id).unwrap();
+
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(1, expected.post_count);
+    assert_eq!(1, expected.post_score);
+    assert_eq!(0, unchanged);
+    assert_eq!(1, corrected);
+    assert_eq!(expected, after_corrupt);
+    assert_eq!(1, inserted);
+    assert_eq!(expected.post_count, after_missing.post_count);
+    assert_eq!(expected.post_score, after_missing.post_score);
+    assert_eq!(expected.comment_count, after_missing.comment_count);
+    assert_eq!(expected.comment_score, after_missing.comment_score);
+  }
 }
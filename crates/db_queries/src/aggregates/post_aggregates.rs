@@ -1,12 +1,23 @@
-use diesel::{result::Error, *};
-use lemmy_db_schema::schema::post_aggregates;
+use crate::{functions::hot_rank, source::post_view_log::PostViewLog_};
+use diesel::{
+  dsl::{now, IntervalDsl},
+  result::Error,
+  sql_types::Integer,
+  *,
+};
+use lemmy_db_schema::{
+  schema::{post, post_aggregates},
+  source::post_view_log::{PostViewLog, PostViewLogForm},
+  PostId,
+};
 use serde::Serialize;
+use std::collections::HashMap;
 
 #[derive(Queryable, Associations, Identifiable, PartialEq, Debug, Serialize, Clone)]
 #[table_name = "post_aggregates"]
 pub struct PostAggregates {
   pub id: i32,
-  pub post_id: i32,
+  pub post_id: PostId,
   pub comments: i64,
   pub score: i64,
   pub upvotes: i64,
@@ -15,14 +26,143 @@ pub struct PostAggregates {
   pub published: chrono::NaiveDateTime,
   pub newest_comment_time_necro: chrono::NaiveDateTime, // A newest comment time, limited to 2 days, to prevent necrobumping
   pub newest_comment_time: chrono::NaiveDateTime,
+  pub hot_rank: i32,
+  pub hot_rank_active: i32,
+  pub featured_local: bool,
+  pub views: i64,
 }
 
 impl PostAggregates {
-  pub fn read(conn: &PgConnection, post_id: i32) -> Result<Self, Error> {
+  pub fn read(conn: &PgConnection, post_id: PostId) -> Result<Self, Error> {
     post_aggregates::table
       .filter(post_aggregates::post_id.eq(post_id))
       .first::<Self>(conn)
   }
+
+  /// Recomputes the aggregates of one post from the base tables, returns 1 if the stored row was
+  /// wrong or missing.
+  pub fn recalculate(conn: &PgConnection, post_id: PostId) -> Result<usize, Error> {
+    Self::recalculate_range(conn, post_id.0, post_id.0)
+  }
+
+  /// Recomputes the aggregates of all posts with an id between `from_id` and `to_id` (inclusive)
+  /// in a single statement. The hot ranks are left to the scheduled task. Returns the number of
+  /// rows which were corrected.
+  pub fn recalculate_range(conn: &PgConnection, from_id: i32, to_id: i32) -> Result<usize, Error> {
+    sql_query(
+      "insert into post_aggregates (post_id, comments, score, upvotes, downvotes, stickied,
+        published, newest_comment_time_necro, newest_comment_time)
+      select p.id,
+        coalesce(ct.comments, 0),
+        coalesce(pl.score, 0),
+        coalesce(pl.upvotes, 0),
+        coalesce(pl.downvotes, 0),
+        p.stickied,
+        p.published,
+        greatest(ct.newest_comment_time_necro, p.published),
+        greatest(ct.newest_comment_time, p.published)
+      from post p
+      left join lateral (
+        select count(*) as comments,
+          -- deleted and removed comments dont bump a post
+          max(c.published) filter (where not c.deleted and not c.removed)
+            as newest_comment_time,
+          -- comments only bump a post during its first two days
+          max(c.published) filter (where not c.deleted and not c.removed
+            and c.published < p.published + interval '2 days')
+            as newest_comment_time_necro
+        from comment c where c.post_id = p.id
+      ) ct on true
+      left join lateral (
+        select sum(l.score) as score,
+          count(*) filter (where l.score = 1) as upvotes,
+          count(*) filter (where l.score = -1) as downvotes
+        from post_like l where l.post_id = p.id
+      ) pl on true
+      where p.id between $1 and $2
+      on conflict (post_id) do update set
+        comments = excluded.comments,
+        score = excluded.score,
+        upvotes = excluded.upvotes,
+        downvotes = excluded.downvotes,
+        stickied = excluded.stickied,
+        published = excluded.published,
+        newest_comment_time_necro = excluded.newest_comment_time_necro,
+        newest_comment_time = excluded.newest_comment_time
+      where (post_aggregates.comments, post_aggregates.score, post_aggregates.upvotes,
+        post_aggregates.downvotes, post_aggregates.stickied, post_aggregates.published,
+        post_aggregates.newest_comment_time_necro, post_aggregates.newest_comment_time)
+        is distinct from (excluded.comments, excluded.score, excluded.upvotes,
+        excluded.downvotes, excluded.stickied, excluded.published,
+        excluded.newest_comment_time_necro, excluded.newest_comment_time)",
+    )
+    .bind::<Integer, _>(from_id)
+    .bind::<Integer, _>(to_id)
+    .execute(conn)
+  }
+
+  /// Recalculates the hot ranks of up to `batch_size` posts from the last week, which have an id
+  /// larger than `after_id`. Returns the largest updated id, or `None` once all posts are done.
+  /// Older posts keep the rank which they got on insert, it barely changes anymore.
+  pub fn update_hot_ranks(
+    conn: &PgConnection,
+    after_id: i32,
+    batch_size: i64,
+  ) -> Result<Option<i32>, Error> {
+    let ids = post_aggregates::table
+      .select(post_aggregates::id)
+      .filter(post_aggregates::published.gt(now - 1.weeks()))
+      .filter(post_aggregates::id.gt(after_id))
+      .order_by(post_aggregates::id)
+      .limit(batch_size)
+      .load::<i32>(conn)?;
+    diesel::update(post_aggregates::table.filter(post_aggregates::id.eq_any(&ids)))
+      .set((
+        post_aggregates::hot_rank.eq(hot_rank(post_aggregates::score, post_aggregates::published)),
+        post_aggregates::hot_rank_active.eq(hot_rank(
+          post_aggregates::score,
+          post_aggregates::newest_comment_time_necro,
+        )),
+      ))
+      .execute(conn)?;
+    Ok(ids.last().copied())
+  }
+
+  /// Adds a batch of views to the view counts. Anonymous views are counted as given, the views of
+  /// persons only if they didn't view the same post yet today. Returns the number of added views.
+  pub fn record_views(
+    conn: &PgConnection,
+    anonymous_views: &HashMap<PostId, i64>,
+    person_views: &[PostViewLogForm],
+  ) -> Result<i64, Error> {
+    conn.transaction(|| {
+      // Posts can be deleted between the view and the next batch
+      let post_ids: Vec<PostId> = person_views.iter().map(|view| view.post_id).collect();
+      let existing_post_ids = post::table
+        .select(post::id)
+        .filter(post::id.eq_any(post_ids))
+        .load::<PostId>(conn)?;
+      let person_views: Vec<PostViewLogForm> = person_views
+        .iter()
+        .filter(|view| existing_post_ids.contains(&view.post_id))
+        .map(|view| PostViewLogForm {
+          post_id: view.post_id,
+          person_id: view.person_id,
+        })
+        .collect();
+
+      let mut views = anonymous_views.to_owned();
+      for post_id in PostViewLog::insert_new(conn, &person_views)? {
+        *views.entry(post_id).or_insert(0) += 1;
+      }
+      for (post_id, count) in &views {
+        diesel::update(post_aggregates::table.filter(post_aggregates::post_id.eq(*post_id)))
+          .set(post_aggregates::views.eq(post_aggregates::views + *count))
+          .execute(conn)?;
+      }
+      Ok(views.values().sum())
+    })
+  }
 }
 
 #[cfg(test)]
@@ -30,16 +170,26 @@ mod tests {
   use crate::{
     aggregates::post_aggregates::PostAggregates,
     establish_unpooled_connection,
+    source::comment::Comment_,
     Crud,
     Likeable,
   };
-  use lemmy_db_schema::source::{
-    comment::{Comment, CommentForm},
-    community::{Community, CommunityForm},
-    person::{Person, PersonForm},
-    post::{Post, PostForm, PostLike, PostLikeForm},
+  use chrono::Duration;
+  use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+  use lemmy_db_schema::{
+    naive_now,
+    schema::post_aggregates,
+    source::{
+      comment::{Comment, CommentForm},
+      community::{Community, CommunityForm},
+      person::{Person, PersonForm},
+      post::{Post, PostForm, PostLike, PostLikeForm},
+      post_view_log::PostViewLogForm,
+    },
+    PostId,
   };
   use serial_test::serial;
+  use std::collections::HashMap;
 
   #[test]
   #[serial]
@@ -108,6 +258,13 @@ mod tests {
       followers_url: None,
       inbox_url: None,
       shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
     };
 
     let inserted_community = Community::create(&conn, &new_community).unwrap();
@@ -223,4 +380,351 @@ mod tests {
     let after_delete = PostAggregates::read(&conn, inserted_post.id);
     assert!(after_delete.is_err());
   }
+
+  #[test]
+  #[serial]
+  fn test_update_hot_ranks() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "thommy_hot_rank".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "TIL_hot_rank".into(),
+      creator_id: inserted_person.id,
+      title: "nada".to_owned(),
+      description: None,
+      nsfw: false,
+      removed: None,
+      deleted: None,
+      updated: None,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let post_form = |name: &str, age: Duration| PostForm {
+      name: name.into(),
+      url: None,
+      body: None,
+      creator_id: inserted_person.id,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      nsfw: false,
+      updated: None,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      thumbnail_url: None,
+      ap_id: None,
+      local: true,
+      published: Some(naive_now() - age),
+    };
+    let recent_post = Post::create(&conn, &post_form("recent", Duration::days(2))).unwrap();
+    let old_post = Post::create(&conn, &post_form("old", Duration::days(10))).unwrap();
+
+    // The rank is calculated on insert, so that old federated posts don't show up as new
+    let inserted_recent_agg = PostAggregates::read(&conn, recent_post.id).unwrap();
+    let inserted_old_agg = PostAggregates::read(&conn, old_post.id).unwrap();
+    assert!(inserted_recent_agg.hot_rank < 1728);
+    assert!(inserted_recent_agg.hot_rank_active < 1728);
+    assert!(inserted_old_agg.hot_rank <= inserted_recent_agg.hot_rank);
+
+    // Outdated ranks are recalculated, but only for the posts of the last week
+    diesel::update(
+      post_aggregates::table
+        .filter(post_aggregates::post_id.eq_any(vec![recent_post.id, old_post.id])),
+    )
+    .set((
+      post_aggregates::hot_rank.eq(1728),
+      post_aggregates::hot_rank_active.eq(1728),
+    ))
+    .execute(&conn)
+    .unwrap();
+    let mut after_id = 0;
+    let mut batches = 0;
+    while let Some(last_id) = PostAggregates::update_hot_ranks(&conn, after_id, 1).unwrap() {
+      assert!(last_id > after_id);
+      after_id = last_id;
+      batches += 1;
+    }
+
+    let recent_agg = PostAggregates::read(&conn, recent_post.id).unwrap();
+    let old_agg = PostAggregates::read(&conn, old_post.id).unwrap();
+
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert!(batches >= 1);
+    assert!(recent_agg.hot_rank < 1728);
+    assert!(recent_agg.hot_rank_active < 1728);
+    assert_eq!(1728, old_agg.hot_rank);
+  }
+
+  #[test]
+  #[serial]
+  fn test_newest_comment_time() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "thommy_newest_comment".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "TIL_newest_comment".into(),
+      creator_id: inserted_person.id,
+      title: "nada".to_owned(),
+      description: None,
+      nsfw: false,
+      removed: None,
+      deleted: None,
+      updated: None,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let new_post = PostForm {
+      name: "A test post".into(),
+      url: None,
+      body: None,
+      creator_id: inserted_person.id,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      nsfw: false,
+      updated: None,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      thumbnail_url: None,
+      ap_id: None,
+      local: true,
+      published: Some(naive_now() - Duration::hours(3)),
+    };
+    let inserted_post = Post::create(&conn, &new_post).unwrap();
+
+    let comment_form = |age: Duration| CommentForm {
+      content: "A test comment".into(),
+      creator_id: inserted_person.id,
+      post_id: inserted_post.id,
+      removed: None,
+      deleted: None,
+      read: None,
+      parent_id: None,
+      published: Some(naive_now() - age),
+      updated: None,
+      ap_id: None,
+      local: true,
+    };
+    let older_comment = Comment::create(&conn, &comment_form(Duration::hours(2))).unwrap();
+    let newest_comment = Comment::create(&conn, &comment_form(Duration::hours(1))).unwrap();
+
+    let before_delete = PostAggregates::read(&conn, inserted_post.id).unwrap();
+    assert_eq!(newest_comment.published, before_delete.newest_comment_time);
+    assert_eq!(
+      newest_comment.published,
+      before_delete.newest_comment_time_necro
+    );
+
+    // Deleting the newest comment reverts to the next-newest one
+    Comment::update_deleted(&conn, newest_comment.id, true).unwrap();
+    let after_delete = PostAggregates::read(&conn, inserted_post.id).unwrap();
+    assert_eq!(older_comment.published, after_delete.newest_comment_time);
+    assert_eq!(
+      older_comment.published,
+      after_delete.newest_comment_time_necro
+    );
+
+    // Restoring it bumps the post again
+    Comment::update_deleted(&conn, newest_comment.id, false).unwrap();
+    let after_restore = PostAggregates::read(&conn, inserted_post.id).unwrap();
+    assert_eq!(newest_comment.published, after_restore.newest_comment_time);
+
+    // Without any visible comments, the post's own time is used
+    Comment::update_removed(&conn, newest_comment.id, true).unwrap();
+    Comment::update_removed(&conn, older_comment.id, true).unwrap();
+    let after_remove_all = PostAggregates::read(&conn, inserted_post.id).unwrap();
+    assert_eq!(
+      inserted_post.published,
+      after_remove_all.newest_comment_time
+    );
+    assert_eq!(
+      inserted_post.published,
+      after_remove_all.newest_comment_time_necro
+    );
+
+    // The recalculation agrees with the triggers
+    assert_eq!(
+      0,
+      PostAggregates::recalculate(&conn, inserted_post.id).unwrap()
+    );
+
+    Person::delete(&conn, inserted_person.id).unwrap();
+  }
+
+  #[test]
+  #[serial]
+  fn test_record_views() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "thommy_post_views".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "TIL_post_views".into(),
+      creator_id: inserted_person.id,
+      title: "nada".to_owned(),
+      description: None,
+      nsfw: false,
+      removed: None,
+      deleted: None,
+      updated: None,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let new_post = PostForm {
+      name: "A viewed post".into(),
+      url: None,
+      body: None,
+      creator_id: inserted_person.id,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      nsfw: false,
+      updated: None,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      thumbnail_url: None,
+      ap_id: None,
+      local: true,
+      published: None,
+    };
+    let inserted_post = Post::create(&conn, &new_post).unwrap();
+
+    let anonymous_views: HashMap<PostId, i64> = vec![(inserted_post.id, 2)].into_iter().collect();
+    let person_view = || PostViewLogForm {
+      post_id: inserted_post.id,
+      person_id: inserted_person.id,
+    };
+    let added = PostAggregates::record_views(&conn, &anonymous_views, &[person_view()]).unwrap();
+    assert_eq!(3, added);
+
+    // The person already viewed the post today
+    let added = PostAggregates::record_views(&conn, &HashMap::new(), &[person_view()]).unwrap();
+    assert_eq!(0, added);
+
+    let views = PostAggregates::read(&conn, inserted_post.id).unwrap().views;
+
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(3, views);
+  }
 }
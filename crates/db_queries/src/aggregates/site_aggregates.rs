@@ -21,6 +21,28 @@ impl SiteAggregates {
   pub fn read(conn: &PgConnection) -> Result<Self, Error> {
     site_aggregates::table.first::<Self>(conn)
   }
+
+  /// Recomputes the local user, post, comment and community counts from the base tables. Returns
+  /// the number of rows which were corrected.
+  pub fn recalculate(conn: &PgConnection) -> Result<usize, Error> {
+    sql_query(
+      "update site_aggregates sa set
+        users = counts.users,
+        posts = counts.posts,
+        comments = counts.comments,
+        communities = counts.communities
+      from (
+        select
+          (select count(*) from person where local = true) as users,
+          (select count(*) from post where local = true) as posts,
+          (select count(*) from comment where local = true) as comments,
+          (select count(*) from community where local = true) as communities
+      ) counts
+      where (sa.users, sa.posts, sa.comments, sa.communities)
+        is distinct from (counts.users, counts.posts, counts.comments, counts.communities)",
+    )
+    .execute(conn)
+  }
 }
 
 #[cfg(test)]
@@ -66,6 +88,17 @@ mod tests {
       description: None,
       icon: None,
       banner: None,
+      slur_filter_regex: None,
+      require_local_images: None,
+      max_post_body_length: None,
+      max_comment_length: None,
+      allowed_url_schemes: None,
+      default_post_listing_type: None,
+      show_deleted_usernames_to_admins: None,
+      federation_mode: None,
+      federate_votes_as: None,
+      registration_challenge: None,
+      proof_of_work_difficulty: None,
       creator_id: inserted_person.id,
       enable_downvotes: true,
       open_registration: true,
@@ -95,6 +128,13 @@ mod tests {
       followers_url: None,
       inbox_url: None,
       shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
     };
 
     let inserted_community = Community::create(&conn, &new_community).unwrap();
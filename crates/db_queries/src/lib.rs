@@ -12,12 +12,13 @@ extern crate diesel_migrations;
 #[cfg(test)]
 extern crate serial_test;
 
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime};
 use diesel::{result::Error, *};
-use lemmy_db_schema::DbUrl;
-use lemmy_utils::ApiError;
+use lemmy_db_schema::{naive_now, CommunityId, DbUrl, PersonId};
+use lemmy_utils::{ApiError, LemmyError};
 use regex::Regex;
-use serde::{Deserialize, Serialize};
-use std::{env, env::VarError};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::{env, env::VarError, str::FromStr};
 use url::Url;
 
 pub mod aggregates;
@@ -25,17 +26,26 @@ pub mod source;
 
 pub type DbPool = diesel::r2d2::Pool<diesel::r2d2::ConnectionManager<diesel::PgConnection>>;
 
-pub trait Crud<T> {
-  fn create(conn: &PgConnection, form: &T) -> Result<Self, Error>
+/// Runs `f` inside a database transaction. If it returns an error, everything it wrote is rolled
+/// back and the error is passed through unchanged.
+pub fn transaction<F, T>(conn: &PgConnection, f: F) -> Result<T, LemmyError>
+where
+  F: FnOnce(&PgConnection) -> Result<T, LemmyError>,
+{
+  conn.transaction(|| f(conn))
+}
+
+pub trait Crud<Form, IdType> {
+  fn create(conn: &PgConnection, form: &Form) -> Result<Self, Error>
   where
     Self: Sized;
-  fn read(conn: &PgConnection, id: i32) -> Result<Self, Error>
+  fn read(conn: &PgConnection, id: IdType) -> Result<Self, Error>
   where
     Self: Sized;
-  fn update(conn: &PgConnection, id: i32, form: &T) -> Result<Self, Error>
+  fn update(conn: &PgConnection, id: IdType, form: &Form) -> Result<Self, Error>
   where
     Self: Sized;
-  fn delete(_conn: &PgConnection, _id: i32) -> Result<usize, Error>
+  fn delete(_conn: &PgConnection, _id: IdType) -> Result<usize, Error>
   where
     Self: Sized,
   {
@@ -47,13 +57,17 @@ pub trait Followable<T> {
   fn follow(conn: &PgConnection, form: &T) -> Result<Self, Error>
   where
     Self: Sized;
-  fn follow_accepted(conn: &PgConnection, community_id: i32, person_id: i32) -> Result<Self, Error>
+  fn follow_accepted(
+    conn: &PgConnection,
+    community_id: CommunityId,
+    person_id: PersonId,
+  ) -> Result<Self, Error>
   where
     Self: Sized;
   fn unfollow(conn: &PgConnection, form: &T) -> Result<usize, Error>
   where
     Self: Sized;
-  fn has_local_followers(conn: &PgConnection, community_id: i32) -> Result<bool, Error>;
+  fn has_local_followers(conn: &PgConnection, community_id: CommunityId) -> Result<bool, Error>;
 }
 
 pub trait Joinable<T> {
@@ -65,11 +79,41 @@ pub trait Joinable<T> {
     Self: Sized;
 }
 
-pub trait Likeable<T> {
-  fn like(conn: &PgConnection, form: &T) -> Result<Self, Error>
+pub trait Likeable<Form, IdType> {
+  fn like(conn: &PgConnection, form: &Form) -> Result<Self, Error>
+  where
+    Self: Sized;
+  fn remove(conn: &PgConnection, person_id: PersonId, item_id: IdType) -> Result<usize, Error>
+  where
+    Self: Sized;
+  /// Removes the vote of the person only if it has the given score, so that undoing an older vote
+  /// doesn't remove a newer one in the other direction
+  fn remove_with_score(
+    conn: &PgConnection,
+    person_id: PersonId,
+    item_id: IdType,
+    score: i16,
+  ) -> Result<usize, Error>
+  where
+    Self: Sized;
+  /// The score of the person's current vote on the item, if there is one
+  fn read_score(
+    conn: &PgConnection,
+    person_id: PersonId,
+    item_id: IdType,
+  ) -> Result<Option<i16>, Error>
   where
     Self: Sized;
-  fn remove(conn: &PgConnection, person_id: i32, item_id: i32) -> Result<usize, Error>
+  /// The summed score of all votes by local persons on the item
+  fn local_score(conn: &PgConnection, item_id: IdType) -> Result<i64, Error>
+  where
+    Self: Sized;
+  /// The scores of the given persons' votes on the item, persons without a vote are left out
+  fn scores_among(
+    conn: &PgConnection,
+    item_id: IdType,
+    person_ids: &[PersonId],
+  ) -> Result<Vec<(PersonId, i16)>, Error>
   where
     Self: Sized;
 }
@@ -105,10 +149,10 @@ pub trait Reportable<T> {
   fn report(conn: &PgConnection, form: &T) -> Result<Self, Error>
   where
     Self: Sized;
-  fn resolve(conn: &PgConnection, report_id: i32, resolver_id: i32) -> Result<usize, Error>
+  fn resolve(conn: &PgConnection, report_id: i32, resolver_id: PersonId) -> Result<usize, Error>
   where
     Self: Sized;
-  fn unresolve(conn: &PgConnection, report_id: i32, resolver_id: i32) -> Result<usize, Error>
+  fn unresolve(conn: &PgConnection, report_id: i32, resolver_id: PersonId) -> Result<usize, Error>
   where
     Self: Sized;
 }
@@ -159,7 +203,7 @@ pub fn get_database_url_from_env() -> Result<String, VarError> {
   env::var("LEMMY_DATABASE_URL")
 }
 
-#[derive(EnumString, ToString, Debug, Serialize, Deserialize)]
+#[derive(EnumString, ToString, Debug, Serialize, Clone, Copy, PartialEq)]
 pub enum SortType {
   Active,
   Hot,
@@ -171,17 +215,32 @@ pub enum SortType {
   TopAll,
   MostComments,
   NewComments,
+  ActiveDaily,
+  ActiveWeekly,
+  ActiveMonthly,
+  ActiveSixMonths,
+  /// Oldest first. Only for comments, other listings treat it like `New`
+  Old,
+  /// Top-level comments by score, replies oldest first. Only for comments, other listings treat it
+  /// like `New`
+  QandA,
+  /// Most viewed first. Only for the posts of a community, and only for its mods and the admins,
+  /// other post listings reject it. Comment, community and person listings treat it like `New`.
+  /// It can't be the default sort of a user.
+  MostViews,
 }
 
-#[derive(EnumString, ToString, Debug, Serialize, Deserialize, Clone)]
+#[derive(EnumString, ToString, Debug, Serialize, Clone, Copy, PartialEq)]
 pub enum ListingType {
   All,
+  /// Content which originated on this instance. For communities, the ones hosted here
   Local,
+  /// Content of the communities which the user follows, both local and remote ones
   Subscribed,
   Community,
 }
 
-#[derive(EnumString, ToString, Debug, Serialize, Deserialize)]
+#[derive(EnumString, ToString, Debug, Serialize, Clone, Copy, PartialEq)]
 pub enum SearchType {
   All,
   Comments,
@@ -191,6 +250,284 @@ pub enum SearchType {
   Url,
 }
 
+/// Which mentions `GetPersonMentions` returns, those in comments, in post bodies, or both
+#[derive(EnumString, ToString, Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum MentionType {
+  All,
+  Comments,
+  Posts,
+}
+
+/// What an API token may be used for. Logins may do everything.
+#[derive(EnumString, ToString, Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum ApiTokenScope {
+  Read,
+  Write,
+}
+
+/// How a local user is emailed about new replies, mentions and private messages. `Immediate` sends
+/// one email per notification, the digests batch them into a single email.
+#[derive(EnumString, ToString, Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum EmailDigestFrequency {
+  Off,
+  Immediate,
+  Hourly,
+  Daily,
+}
+
+/// How the votes of local users are federated. `Actor` sends one vote per user from their own
+/// actor, which lets remote admins see who voted. `InstanceAggregate` instead sends the summed
+/// score of all local votes on a post or comment from a single service actor of the instance.
+#[derive(EnumString, ToString, Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum FederateVotesAs {
+  Actor,
+  InstanceAggregate,
+}
+
+/// What new users have to solve when registering. `Captcha` shows an image (if captchas are
+/// enabled in the config), `ProofOfWork` makes the client find a hash with leading zero bits, and
+/// `Question` asks one of the questions which the admins configured.
+#[derive(EnumString, ToString, Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum RegistrationChallenge {
+  Captcha,
+  ProofOfWork,
+  Question,
+}
+
+/// What a spam rule checks. `UrlDenylist` matches the links of the content against a regex,
+/// `MaxLinks` limits the number of links, `MinAccountAge` forbids links from accounts younger than
+/// the threshold in hours, and `DuplicateContent` compares the content with the threshold number
+/// of latest submissions of the creator.
+#[derive(EnumString, ToString, Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum SpamRuleKind {
+  UrlDenylist = 0,
+  MaxLinks = 1,
+  MinAccountAge = 2,
+  DuplicateContent = 3,
+}
+
+/// What happens to content which matches a spam rule. `Remove` creates it, but removed right away
+/// so that the mods can review it. Federated content is always removed instead of rejected.
+#[derive(EnumString, ToString, Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum SpamAction {
+  Reject = 0,
+  Remove = 1,
+}
+
+impl Default for SortType {
+  fn default() -> Self {
+    SortType::Hot
+  }
+}
+
+impl Default for ListingType {
+  fn default() -> Self {
+    ListingType::Local
+  }
+}
+
+impl Default for SearchType {
+  fn default() -> Self {
+    SearchType::All
+  }
+}
+
+impl Default for MentionType {
+  fn default() -> Self {
+    MentionType::All
+  }
+}
+
+impl Default for EmailDigestFrequency {
+  fn default() -> Self {
+    EmailDigestFrequency::Immediate
+  }
+}
+
+impl Default for FederateVotesAs {
+  fn default() -> Self {
+    FederateVotesAs::Actor
+  }
+}
+
+impl Default for RegistrationChallenge {
+  fn default() -> Self {
+    RegistrationChallenge::Captcha
+  }
+}
+
+impl SortType {
+  /// Converts the number which is stored in the user settings, unknown values give the default
+  pub fn from_i16(value: i16) -> Self {
+    use SortType::*;
+    match value {
+      0 => Active,
+      1 => Hot,
+      2 => New,
+      3 => TopDay,
+      4 => TopWeek,
+      5 => TopMonth,
+      6 => TopYear,
+      7 => TopAll,
+      8 => MostComments,
+      9 => NewComments,
+      10 => ActiveDaily,
+      11 => ActiveWeekly,
+      12 => ActiveMonthly,
+      13 => ActiveSixMonths,
+      14 => Old,
+      15 => QandA,
+      16 => MostViews,
+      _ => SortType::default(),
+    }
+  }
+
+  /// For the top sorts with a time window, the oldest `published` time which is still part of the
+  /// window. It is calculated in UTC like the `published` columns, so it doesn't depend on the
+  /// time zone of the requester or of the database session.
+  pub fn top_window_start(&self) -> Option<NaiveDateTime> {
+    let now = naive_now();
+    match self {
+      SortType::TopDay => Some(now - Duration::days(1)),
+      SortType::TopWeek => Some(now - Duration::weeks(1)),
+      SortType::TopMonth => Some(months_before(now, 1)),
+      SortType::TopYear => Some(months_before(now, 12)),
+      _ => None,
+    }
+  }
+
+  /// For the active sorts with a time window, the oldest time of the last activity, like the newest
+  /// comment of a post, which is still part of the window. Calculated in UTC like
+  /// `top_window_start()`.
+  pub fn active_window_start(&self) -> Option<NaiveDateTime> {
+    let now = naive_now();
+    match self {
+      SortType::ActiveDaily => Some(now - Duration::days(1)),
+      SortType::ActiveWeekly => Some(now - Duration::weeks(1)),
+      SortType::ActiveMonthly => Some(months_before(now, 1)),
+      SortType::ActiveSixMonths => Some(months_before(now, 6)),
+      _ => None,
+    }
+  }
+}
+
+/// Same calendar day a number of months earlier, or the last day of that month if it is shorter
+fn months_before(time: NaiveDateTime, months: u32) -> NaiveDateTime {
+  let total_months = time.year() * 12 + time.month0() as i32 - months as i32;
+  let (year, month) = (
+    total_months.div_euclid(12),
+    total_months.rem_euclid(12) as u32 + 1,
+  );
+  let date = (1..=time.day())
+    .rev()
+    .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+    .unwrap_or_else(|| time.date());
+  date.and_time(time.time())
+}
+
+impl ListingType {
+  /// Converts the number which is stored in the user and site settings, unknown values give the
+  /// default
+  pub fn from_i16(value: i16) -> Self {
+    use ListingType::*;
+    match value {
+      0 => All,
+      1 => Local,
+      2 => Subscribed,
+      3 => Community,
+      _ => ListingType::default(),
+    }
+  }
+}
+
+impl EmailDigestFrequency {
+  /// Converts the number which is stored in the user settings, unknown values give the default
+  pub fn from_i16(value: i16) -> Self {
+    use EmailDigestFrequency::*;
+    match value {
+      0 => Off,
+      1 => Immediate,
+      2 => Hourly,
+      3 => Daily,
+      _ => EmailDigestFrequency::default(),
+    }
+  }
+}
+
+impl FederateVotesAs {
+  /// Converts the number which is stored in the site settings, unknown values give the default
+  pub fn from_i16(value: i16) -> Self {
+    use FederateVotesAs::*;
+    match value {
+      1 => InstanceAggregate,
+      _ => FederateVotesAs::default(),
+    }
+  }
+}
+
+impl SpamRuleKind {
+  /// Converts the number which is stored in the spam rule, unknown values give `None`
+  pub fn from_i16(value: i16) -> Option<Self> {
+    use SpamRuleKind::*;
+    match value {
+      0 => Some(UrlDenylist),
+      1 => Some(MaxLinks),
+      2 => Some(MinAccountAge),
+      3 => Some(DuplicateContent),
+      _ => None,
+    }
+  }
+}
+
+impl SpamAction {
+  /// Converts the number which is stored in the spam rule, unknown values remove the content
+  pub fn from_i16(value: i16) -> Self {
+    match value {
+      0 => SpamAction::Reject,
+      _ => SpamAction::Remove,
+    }
+  }
+}
+
+impl RegistrationChallenge {
+  /// Converts the number which is stored in the site settings, unknown values give the default
+  pub fn from_i16(value: i16) -> Self {
+    use RegistrationChallenge::*;
+    match value {
+      1 => ProofOfWork,
+      2 => Question,
+      _ => RegistrationChallenge::default(),
+    }
+  }
+}
+
+/// Deserializes the enum from the same strings that `FromStr` accepts, and fails with a plain
+/// error code instead of serde's list of expected variants.
+macro_rules! deserialize_from_str {
+  ($type:ident, $error:expr) => {
+    impl<'de> Deserialize<'de> for $type {
+      fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+      where
+        D: Deserializer<'de>,
+      {
+        let value = String::deserialize(deserializer)?;
+        $type::from_str(&value).map_err(|_| de::Error::custom($error))
+      }
+    }
+  };
+}
+
+deserialize_from_str!(SortType, "invalid_sort_type");
+deserialize_from_str!(ListingType, "invalid_listing_type");
+deserialize_from_str!(SearchType, "invalid_search_type");
+deserialize_from_str!(MentionType, "invalid_mention_type");
+deserialize_from_str!(ApiTokenScope, "invalid_scope");
+deserialize_from_str!(EmailDigestFrequency, "invalid_email_digest_frequency");
+deserialize_from_str!(FederateVotesAs, "invalid_federate_votes_as");
+deserialize_from_str!(RegistrationChallenge, "invalid_registration_challenge");
+deserialize_from_str!(SpamRuleKind, "invalid_spam_rule_kind");
+deserialize_from_str!(SpamAction, "invalid_spam_action");
+
 pub fn fuzzy_search(q: &str) -> String {
   let replaced = q.replace(" ", "%");
   format!("%{}%", replaced)
@@ -263,6 +600,10 @@ pub mod functions {
   sql_function! {
     fn hot_rank(score: BigInt, time: Timestamp) -> Integer;
   }
+
+  sql_function! {
+    fn lower(x: Text) -> Text;
+  }
 }
 
 #[cfg(test)]
@@ -276,6 +617,79 @@ mod tests {
     assert_eq!(fuzzy_search(test), "%This%is%a%fuzzy%search%".to_string());
   }
 
+  #[test]
+  fn test_listing_settings_from_i16() {
+    assert_eq!(
+      SortType::from_i16(SortType::TopWeek as i16),
+      SortType::TopWeek
+    );
+    assert_eq!(
+      SortType::from_i16(SortType::ActiveSixMonths as i16),
+      SortType::ActiveSixMonths
+    );
+    assert_eq!(SortType::from_i16(SortType::QandA as i16), SortType::QandA);
+    assert_eq!(
+      SortType::from_i16(SortType::MostViews as i16),
+      SortType::MostViews
+    );
+    assert_eq!(SortType::from_i16(-1), SortType::Hot);
+    assert_eq!(
+      ListingType::from_i16(ListingType::Subscribed as i16),
+      ListingType::Subscribed
+    );
+    assert_eq!(ListingType::from_i16(42), ListingType::Local);
+    assert_eq!(
+      FederateVotesAs::from_i16(FederateVotesAs::InstanceAggregate as i16),
+      FederateVotesAs::InstanceAggregate
+    );
+    assert_eq!(FederateVotesAs::from_i16(7), FederateVotesAs::Actor);
+    assert_eq!(
+      RegistrationChallenge::from_i16(RegistrationChallenge::Question as i16),
+      RegistrationChallenge::Question
+    );
+    assert_eq!(
+      RegistrationChallenge::from_i16(3),
+      RegistrationChallenge::Captcha
+    );
+  }
+
+  #[test]
+  fn test_top_window_start() {
+    let date = |y, m, d| NaiveDate::from_ymd(y, m, d).and_hms(13, 30, 0);
+    assert_eq!(date(2021, 2, 28), months_before(date(2021, 3, 31), 1));
+    assert_eq!(date(2020, 2, 29), months_before(date(2020, 3, 29), 1));
+    assert_eq!(date(2020, 12, 15), months_before(date(2021, 1, 15), 1));
+    assert_eq!(date(2019, 2, 28), months_before(date(2020, 2, 29), 12));
+
+    // The window ends now in UTC, regardless of the local time zone
+    let day_start = SortType::TopDay.top_window_start().unwrap();
+    let expected = chrono::Utc::now().naive_utc() - Duration::days(1);
+    assert!((expected - day_start).num_seconds().abs() < 5);
+    assert!(
+      SortType::TopWeek.top_window_start() < SortType::TopDay.top_window_start()
+        && SortType::TopMonth.top_window_start() < SortType::TopWeek.top_window_start()
+        && SortType::TopYear.top_window_start() < SortType::TopMonth.top_window_start()
+    );
+    assert_eq!(None, SortType::TopAll.top_window_start());
+    assert_eq!(None, SortType::Hot.top_window_start());
+
+    assert_eq!(
+      SortType::TopDay.top_window_start().map(|t| t.date()),
+      SortType::ActiveDaily
+        .active_window_start()
+        .map(|t| t.date())
+    );
+    assert!(
+      SortType::ActiveWeekly.active_window_start() < SortType::ActiveDaily.active_window_start()
+        && SortType::ActiveMonthly.active_window_start()
+          < SortType::ActiveWeekly.active_window_start()
+        && SortType::ActiveSixMonths.active_window_start()
+          < SortType::ActiveMonthly.active_window_start()
+    );
+    assert_eq!(None, SortType::Active.active_window_start());
+    assert_eq!(None, SortType::TopDay.active_window_start());
+  }
+
   #[test]
   fn test_email() {
     assert!(is_email_regex("gush@gmail.com"));
@@ -309,4 +723,30 @@ mod tests {
       Ok(Some(Some(url))) if url == Url::parse(&example_url).unwrap().into()
     ));
   }
+
+  #[test]
+  fn test_sort_and_listing_type_deserialize() {
+    let sort: SortType = serde_json::from_str("\"TopWeek\"").unwrap();
+    assert_eq!(SortType::TopWeek, sort);
+    let listing: ListingType = serde_json::from_str("\"Subscribed\"").unwrap();
+    assert_eq!(ListingType::Subscribed, listing);
+    let search: SearchType = serde_json::from_str("\"Url\"").unwrap();
+    assert_eq!(SearchType::Url, search);
+    let mention: MentionType = serde_json::from_str("\"Posts\"").unwrap();
+    assert_eq!(MentionType::Posts, mention);
+    assert_eq!(
+      "\"NewComments\"",
+      serde_json::to_string(&SortType::NewComments).unwrap()
+    );
+
+    let err = serde_json::from_str::<SortType>("\"Best\"").unwrap_err();
+    assert!(err.to_string().starts_with("invalid_sort_type"));
+    let err = serde_json::from_str::<ListingType>("\"all\"").unwrap_err();
+    assert!(err.to_string().starts_with("invalid_listing_type"));
+
+    assert_eq!(SortType::Hot, SortType::default());
+    assert_eq!(ListingType::Local, ListingType::default());
+    assert_eq!(SearchType::All, SearchType::default());
+    assert_eq!(MentionType::All, MentionType::default());
+  }
 }
@@ -9,7 +9,7 @@ use std::{
   io::{Error as IoError, ErrorKind},
 };
 
-impl Crud<ActivityForm> for Activity {
+impl Crud<ActivityForm, i32> for Activity {
   fn read(conn: &PgConnection, activity_id: i32) -> Result<Self, Error> {
     use lemmy_db_schema::schema::activity::dsl::*;
     activity.find(activity_id).first::<Self>(conn)
@@ -50,6 +50,7 @@ pub trait Activity_ {
     T: Serialize + Debug;
 
   fn read_from_apub_id(conn: &PgConnection, object_id: &DbUrl) -> Result<Activity, Error>;
+  fn delete_by_apub_id(conn: &PgConnection, object_id: &DbUrl) -> Result<usize, Error>;
   fn delete_olds(conn: &PgConnection) -> Result<usize, Error>;
 
   /// Returns up to 20 activities of type `Announce/Create/Page` from the community
@@ -93,6 +94,11 @@ impl Activity_ for Activity {
     activity.filter(ap_id.eq(object_id)).first::<Self>(conn)
   }
 
+  fn delete_by_apub_id(conn: &PgConnection, object_id: &DbUrl) -> Result<usize, Error> {
+    use lemmy_db_schema::schema::activity::dsl::*;
+    diesel::delete(activity.filter(ap_id.eq(object_id))).execute(conn)
+  }
+
   fn delete_olds(conn: &PgConnection) -> Result<usize, Error> {
     use lemmy_db_schema::schema::activity::dsl::*;
     diesel::delete(activity.filter(published.lt(now - 6.months()))).execute(conn)
@@ -0,0 +1,169 @@
+use crate::Crud;
+use diesel::{dsl::*, result::Error, *};
+use lemmy_db_schema::{
+  schema::api_token::dsl::*,
+  source::api_token::{ApiToken, ApiTokenForm},
+};
+
+impl Crud<ApiTokenForm, i32> for ApiToken {
+  fn read(conn: &PgConnection, api_token_id: i32) -> Result<Self, Error> {
+    api_token.find(api_token_id).first::<Self>(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &ApiTokenForm) -> Result<Self, Error> {
+    insert_into(api_token).values(form).get_result::<Self>(conn)
+  }
+
+  fn update(conn: &PgConnection, api_token_id: i32, form: &ApiTokenForm) -> Result<Self, Error> {
+    diesel::update(api_token.find(api_token_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, api_token_id: i32) -> Result<usize, Error> {
+    diesel::delete(api_token.find(api_token_id)).execute(conn)
+  }
+}
+
+pub trait ApiToken_ {
+  /// Reads the token if it belongs to the local user and isn't expired. Revoked tokens are deleted,
+  /// so they aren't found either.
+  fn read_valid(
+    conn: &PgConnection,
+    api_token_id: i32,
+    for_local_user_id: i32,
+  ) -> Result<ApiToken, Error>;
+  fn list_for_local_user(
+    conn: &PgConnection,
+    for_local_user_id: i32,
+  ) -> Result<Vec<ApiToken>, Error>;
+  /// Deletes the token, if it belongs to the local user
+  fn revoke(conn: &PgConnection, api_token_id: i32, for_local_user_id: i32)
+    -> Result<usize, Error>;
+}
+
+impl ApiToken_ for ApiToken {
+  fn read_valid(
+    conn: &PgConnection,
+    api_token_id: i32,
+    for_local_user_id: i32,
+  ) -> Result<ApiToken, Error> {
+    api_token
+      .find(api_token_id)
+      .filter(local_user_id.eq(for_local_user_id))
+      .filter(expires.is_null().or(expires.gt(now)))
+      .first::<Self>(conn)
+  }
+
+  fn list_for_local_user(
+    conn: &PgConnection,
+    for_local_user_id: i32,
+  ) -> Result<Vec<ApiToken>, Error> {
+    api_token
+      .filter(local_user_id.eq(for_local_user_id))
+      .order_by(published.desc())
+      .load::<Self>(conn)
+  }
+
+  fn revoke(
+    conn: &PgConnection,
+    api_token_id: i32,
+    for_local_user_id: i32,
+  ) -> Result<usize, Error> {
+    diesel::delete(
+      api_token
+        .find(api_token_id)
+        .filter(local_user_id.eq(for_local_user_id)),
+    )
+    .execute(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{establish_unpooled_connection, source::api_token::ApiToken_, Crud};
+  use chrono::{Duration, Utc};
+  use lemmy_db_schema::source::{
+    api_token::{ApiToken, ApiTokenForm},
+    local_user::{LocalUser, LocalUserForm},
+    person::*,
+  };
+  use serial_test::serial;
+
+  #[test]
+  #[serial]
+  fn test_api_token() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "api_token_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_local_user = LocalUserForm {
+      person_id: inserted_person.id,
+      password_encrypted: "pass".to_string(),
+      email: None,
+      matrix_user_id: None,
+      admin: None,
+      show_nsfw: None,
+      theme: None,
+      default_sort_type: None,
+      default_listing_type: None,
+      lang: None,
+      show_avatars: None,
+      send_notifications_to_email: None,
+      email_digest_frequency: None,
+      hide_last_active: None,
+      hide_profile_from_federation: None,
+    };
+
+    let inserted_local_user = LocalUser::create(&conn, &new_local_user).unwrap();
+
+    let create_token = |expires| {
+      let form = ApiTokenForm {
+        local_user_id: inserted_local_user.id,
+        name: "bot".into(),
+        scopes: vec!["Read".into()],
+        expires,
+      };
+      ApiToken::create(&conn, &form).unwrap()
+    };
+    let token = create_token(None);
+    let expired_token = create_token(Some(Utc::now().naive_utc() - Duration::days(1)));
+
+    let read_token = ApiToken::read_valid(&conn, token.id, inserted_local_user.id).unwrap();
+    let read_expired_token = ApiToken::read_valid(&conn, expired_token.id, inserted_local_user.id);
+    let read_other_user = ApiToken::read_valid(&conn, token.id, inserted_local_user.id + 1);
+    let listed = ApiToken::list_for_local_user(&conn, inserted_local_user.id).unwrap();
+    let revoked_other_user = ApiToken::revoke(&conn, token.id, inserted_local_user.id + 1).unwrap();
+    let revoked = ApiToken::revoke(&conn, token.id, inserted_local_user.id).unwrap();
+    let read_revoked_token = ApiToken::read_valid(&conn, token.id, inserted_local_user.id);
+    let num_deleted = Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(token, read_token);
+    assert!(read_expired_token.is_err());
+    assert!(read_other_user.is_err());
+    assert_eq!(2, listed.len());
+    assert_eq!(0, revoked_other_user);
+    assert_eq!(1, revoked);
+    assert!(read_revoked_token.is_err());
+    assert_eq!(1, num_deleted);
+  }
+}
@@ -1,5 +1,11 @@
 use crate::{ApubObject, Crud, Likeable, Saveable};
-use diesel::{dsl::*, result::Error, *};
+use chrono::NaiveDateTime;
+use diesel::{
+  dsl::*,
+  result::Error,
+  sql_types::{Bool, Integer, Timestamp},
+  *,
+};
 use lemmy_db_schema::{
   naive_now,
   source::comment::{
@@ -10,40 +16,102 @@ use lemmy_db_schema::{
     CommentSaved,
     CommentSavedForm,
   },
+  CommentId,
+  CommunityId,
   DbUrl,
+  LanguageId,
+  PersonId,
+  PostId,
 };
 
 pub trait Comment_ {
-  fn update_ap_id(conn: &PgConnection, comment_id: i32, apub_id: DbUrl) -> Result<Comment, Error>;
+  /// Reads the comments with the given ids in one query, in no particular order
+  fn read_many(conn: &PgConnection, comment_ids: Vec<CommentId>) -> Result<Vec<Comment>, Error>;
+  fn update_ap_id(
+    conn: &PgConnection,
+    comment_id: CommentId,
+    apub_id: DbUrl,
+  ) -> Result<Comment, Error>;
   fn permadelete_for_creator(
     conn: &PgConnection,
-    for_creator_id: i32,
+    for_creator_id: PersonId,
   ) -> Result<Vec<Comment>, Error>;
   fn update_deleted(
     conn: &PgConnection,
-    comment_id: i32,
+    comment_id: CommentId,
     new_deleted: bool,
   ) -> Result<Comment, Error>;
   fn update_removed(
     conn: &PgConnection,
-    comment_id: i32,
+    comment_id: CommentId,
     new_removed: bool,
   ) -> Result<Comment, Error>;
   fn update_removed_for_creator(
     conn: &PgConnection,
-    for_creator_id: i32,
+    for_creator_id: PersonId,
     new_removed: bool,
   ) -> Result<Vec<Comment>, Error>;
-  fn update_read(conn: &PgConnection, comment_id: i32, new_read: bool) -> Result<Comment, Error>;
+  fn update_read(
+    conn: &PgConnection,
+    comment_id: CommentId,
+    new_read: bool,
+  ) -> Result<Comment, Error>;
   fn update_content(
     conn: &PgConnection,
-    comment_id: i32,
+    comment_id: CommentId,
     new_content: &str,
   ) -> Result<Comment, Error>;
+  fn update_locked(
+    conn: &PgConnection,
+    comment_id: CommentId,
+    new_locked: bool,
+  ) -> Result<Comment, Error>;
+  /// Only one comment per post can be stickied, so stickying a comment unstickies the previous one.
+  fn update_stickied(
+    conn: &PgConnection,
+    comment_id: CommentId,
+    new_stickied: bool,
+  ) -> Result<Comment, Error>;
+  /// Whether the comment itself or any of its ancestors is locked, so no replies can go below it.
+  fn in_locked_thread(conn: &PgConnection, comment_id: CommentId) -> Result<bool, Error>;
+  fn count_for_creator(conn: &PgConnection, for_creator_id: PersonId) -> Result<i64, Error>;
+  fn purge_expired(
+    conn: &PgConnection,
+    deleted_before: NaiveDateTime,
+    removed_before: NaiveDateTime,
+  ) -> Result<usize, Error>;
+  /// Deletes all comments in the posts of a community which arent deleted yet and marks them, or
+  /// restores only the marked comments. Returns the changed comments.
+  fn update_deleted_for_community_delete(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    new_deleted: bool,
+  ) -> Result<Vec<Comment>, Error>;
+  /// Removes the visible comments in the posts of a community together with the community, or
+  /// restores only the comments which were removed that way.
+  fn update_removed_for_community_remove(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    new_removed: bool,
+  ) -> Result<Vec<Comment>, Error>;
+  fn update_language(
+    conn: &PgConnection,
+    comment_id: CommentId,
+    new_language_id: LanguageId,
+  ) -> Result<Comment, Error>;
 }
 
 impl Comment_ for Comment {
-  fn update_ap_id(conn: &PgConnection, comment_id: i32, apub_id: DbUrl) -> Result<Self, Error> {
+  fn read_many(conn: &PgConnection, comment_ids: Vec<CommentId>) -> Result<Vec<Self>, Error> {
+    use lemmy_db_schema::schema::comment::dsl::*;
+    comment.filter(id.eq_any(comment_ids)).load::<Self>(conn)
+  }
+
+  fn update_ap_id(
+    conn: &PgConnection,
+    comment_id: CommentId,
+    apub_id: DbUrl,
+  ) -> Result<Self, Error> {
     use lemmy_db_schema::schema::comment::dsl::*;
 
     diesel::update(comment.find(comment_id))
@@ -51,7 +119,10 @@ impl Comment_ for Comment {
       .get_result::<Self>(conn)
   }
 
-  fn permadelete_for_creator(conn: &PgConnection, for_creator_id: i32) -> Result<Vec<Self>, Error> {
+  fn permadelete_for_creator(
+    conn: &PgConnection,
+    for_creator_id: PersonId,
+  ) -> Result<Vec<Self>, Error> {
     use lemmy_db_schema::schema::comment::dsl::*;
     diesel::update(comment.filter(creator_id.eq(for_creator_id)))
       .set((
@@ -64,7 +135,7 @@ impl Comment_ for Comment {
 
   fn update_deleted(
     conn: &PgConnection,
-    comment_id: i32,
+    comment_id: CommentId,
     new_deleted: bool,
   ) -> Result<Self, Error> {
     use lemmy_db_schema::schema::comment::dsl::*;
@@ -75,7 +146,7 @@ impl Comment_ for Comment {
 
   fn update_removed(
     conn: &PgConnection,
-    comment_id: i32,
+    comment_id: CommentId,
     new_removed: bool,
   ) -> Result<Self, Error> {
     use lemmy_db_schema::schema::comment::dsl::*;
@@ -86,7 +157,7 @@ impl Comment_ for Comment {
 
   fn update_removed_for_creator(
     conn: &PgConnection,
-    for_creator_id: i32,
+    for_creator_id: PersonId,
     new_removed: bool,
   ) -> Result<Vec<Self>, Error> {
     use lemmy_db_schema::schema::comment::dsl::*;
@@ -95,7 +166,11 @@ impl Comment_ for Comment {
       .get_results::<Self>(conn)
   }
 
-  fn update_read(conn: &PgConnection, comment_id: i32, new_read: bool) -> Result<Self, Error> {
+  fn update_read(
+    conn: &PgConnection,
+    comment_id: CommentId,
+    new_read: bool,
+  ) -> Result<Self, Error> {
     use lemmy_db_schema::schema::comment::dsl::*;
     diesel::update(comment.find(comment_id))
       .set(read.eq(new_read))
@@ -104,7 +179,7 @@ impl Comment_ for Comment {
 
   fn update_content(
     conn: &PgConnection,
-    comment_id: i32,
+    comment_id: CommentId,
     new_content: &str,
   ) -> Result<Self, Error> {
     use lemmy_db_schema::schema::comment::dsl::*;
@@ -112,15 +187,176 @@ impl Comment_ for Comment {
       .set((content.eq(new_content), updated.eq(naive_now())))
       .get_result::<Self>(conn)
   }
+
+  fn update_locked(
+    conn: &PgConnection,
+    comment_id: CommentId,
+    new_locked: bool,
+  ) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::comment::dsl::*;
+    diesel::update(comment.find(comment_id))
+      .set(locked.eq(new_locked))
+      .get_result::<Self>(conn)
+  }
+
+  fn update_stickied(
+    conn: &PgConnection,
+    comment_id: CommentId,
+    new_stickied: bool,
+  ) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::comment::dsl::*;
+    conn.transaction(|| {
+      if new_stickied {
+        let comment_post_id = comment
+          .find(comment_id)
+          .select(post_id)
+          .first::<PostId>(conn)?;
+        diesel::update(
+          comment
+            .filter(post_id.eq(comment_post_id))
+            .filter(stickied.eq(true))
+            .filter(id.ne(comment_id)),
+        )
+        .set(stickied.eq(false))
+        .execute(conn)?;
+      }
+      diesel::update(comment.find(comment_id))
+        .set(stickied.eq(new_stickied))
+        .get_result::<Self>(conn)
+    })
+  }
+
+  fn in_locked_thread(conn: &PgConnection, comment_id: CommentId) -> Result<bool, Error> {
+    sql::<Bool>(
+      "with recursive thread(id, parent_id, locked) as (
+        select id, parent_id, locked from comment where id = ",
+    )
+    .bind::<Integer, _>(comment_id.0)
+    .sql(
+      "
+        union all
+        select c.id, c.parent_id, c.locked from comment c join thread t on c.id = t.parent_id
+      )
+      select coalesce(bool_or(locked), false) from thread",
+    )
+    .get_result(conn)
+  }
+
+  /// Number of comments by the person which are neither removed nor deleted
+  fn count_for_creator(conn: &PgConnection, for_creator_id: PersonId) -> Result<i64, Error> {
+    use lemmy_db_schema::schema::comment::dsl::*;
+    comment
+      .filter(creator_id.eq(for_creator_id))
+      .filter(removed.eq(false))
+      .filter(deleted.eq(false))
+      .count()
+      .get_result(conn)
+  }
+
+  /// Blanks the content of comments which were deleted before `deleted_before`, or removed by a
+  /// mod before `removed_before`. Works like `Post::purge_expired()`.
+  fn purge_expired(
+    conn: &PgConnection,
+    deleted_before: NaiveDateTime,
+    removed_before: NaiveDateTime,
+  ) -> Result<usize, Error> {
+    sql_query(
+      "update comment set content = '*deleted*'
+      where ((deleted and not removed and coalesce(updated, published) < $1)
+        or (removed and coalesce(updated, published) < $2))
+      and not deleted_by_community_delete and not removed_by_community_remove
+      and content <> '*deleted*'",
+    )
+    .bind::<Timestamp, _>(deleted_before)
+    .bind::<Timestamp, _>(removed_before)
+    .execute(conn)
+  }
+
+  fn update_deleted_for_community_delete(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    new_deleted: bool,
+  ) -> Result<Vec<Self>, Error> {
+    use lemmy_db_schema::schema::{comment::dsl::*, post};
+    let community_posts = post::table
+      .select(post::id)
+      .filter(post::community_id.eq(for_community_id));
+    let values = (
+      deleted.eq(new_deleted),
+      deleted_by_community_delete.eq(new_deleted),
+      updated.eq(naive_now()),
+    );
+    if new_deleted {
+      diesel::update(
+        comment
+          .filter(post_id.eq_any(community_posts))
+          .filter(deleted.eq(false)),
+      )
+      .set(values)
+      .get_results::<Self>(conn)
+    } else {
+      diesel::update(
+        comment
+          .filter(post_id.eq_any(community_posts))
+          .filter(deleted_by_community_delete.eq(true)),
+      )
+      .set(values)
+      .get_results::<Self>(conn)
+    }
+  }
+
+  fn update_removed_for_community_remove(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    new_removed: bool,
+  ) -> Result<Vec<Self>, Error> {
+    use lemmy_db_schema::schema::{comment::dsl::*, post};
+    let community_posts = post::table
+      .select(post::id)
+      .filter(post::community_id.eq(for_community_id));
+    let values = (
+      removed.eq(new_removed),
+      removed_by_community_remove.eq(new_removed),
+      updated.eq(naive_now()),
+    );
+    if new_removed {
+      diesel::update(
+        comment
+          .filter(post_id.eq_any(community_posts))
+          .filter(removed.eq(false)),
+      )
+      .set(values)
+      .get_results::<Self>(conn)
+    } else {
+      diesel::update(
+        comment
+          .filter(post_id.eq_any(community_posts))
+          .filter(removed_by_community_remove.eq(true)),
+      )
+      .set(values)
+      .get_results::<Self>(conn)
+    }
+  }
+
+  fn update_language(
+    conn: &PgConnection,
+    comment_id: CommentId,
+    new_language_id: LanguageId,
+  ) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::comment::dsl::*;
+    diesel::update(comment.find(comment_id))
+      .set(language_id.eq(new_language_id))
+      .get_result::<Self>(conn)
+  }
 }
 
-impl Crud<CommentForm> for Comment {
-  fn read(conn: &PgConnection, comment_id: i32) -> Result<Self, Error> {
+impl Crud<CommentForm, CommentId> for Comment {
+  fn read(conn: &PgConnection, comment_id: CommentId) -> Result<Self, Error> {
     use lemmy_db_schema::schema::comment::dsl::*;
     comment.find(comment_id).first::<Self>(conn)
   }
 
-  fn delete(conn: &PgConnection, comment_id: i32) -> Result<usize, Error> {
+  fn delete(conn: &PgConnection, comment_id: CommentId) -> Result<usize, Error> {
     use lemmy_db_schema::schema::comment::dsl::*;
     diesel::delete(comment.find(comment_id)).execute(conn)
   }
@@ -134,7 +370,7 @@ impl Crud<CommentForm> for Comment {
 
   fn update(
     conn: &PgConnection,
-    comment_id: i32,
+    comment_id: CommentId,
     comment_form: &CommentForm,
   ) -> Result<Self, Error> {
     use lemmy_db_schema::schema::comment::dsl::*;
@@ -161,7 +397,7 @@ impl ApubObject<CommentForm> for Comment {
   }
 }
 
-impl Likeable<CommentLikeForm> for CommentLike {
+impl Likeable<CommentLikeForm, CommentId> for CommentLike {
   fn like(conn: &PgConnection, comment_like_form: &CommentLikeForm) -> Result<Self, Error> {
     use lemmy_db_schema::schema::comment_like::dsl::*;
     insert_into(comment_like)
@@ -171,7 +407,11 @@ impl Likeable<CommentLikeForm> for CommentLike {
       .set(comment_like_form)
       .get_result::<Self>(conn)
   }
-  fn remove(conn: &PgConnection, person_id: i32, comment_id: i32) -> Result<usize, Error> {
+  fn remove(
+    conn: &PgConnection,
+    person_id: PersonId,
+    comment_id: CommentId,
+  ) -> Result<usize, Error> {
     use lemmy_db_schema::schema::comment_like::dsl;
     diesel::delete(
       dsl::comment_like
@@ -180,6 +420,56 @@ impl Likeable<CommentLikeForm> for CommentLike {
     )
     .execute(conn)
   }
+  fn remove_with_score(
+    conn: &PgConnection,
+    person_id: PersonId,
+    comment_id: CommentId,
+    score: i16,
+  ) -> Result<usize, Error> {
+    use lemmy_db_schema::schema::comment_like::dsl;
+    diesel::delete(
+      dsl::comment_like
+        .filter(dsl::comment_id.eq(comment_id))
+        .filter(dsl::person_id.eq(person_id))
+        .filter(dsl::score.eq(score)),
+    )
+    .execute(conn)
+  }
+  fn read_score(
+    conn: &PgConnection,
+    person_id: PersonId,
+    comment_id: CommentId,
+  ) -> Result<Option<i16>, Error> {
+    use lemmy_db_schema::schema::comment_like::dsl;
+    dsl::comment_like
+      .select(dsl::score)
+      .filter(dsl::comment_id.eq(comment_id))
+      .filter(dsl::person_id.eq(person_id))
+      .first::<i16>(conn)
+      .optional()
+  }
+  fn local_score(conn: &PgConnection, comment_id: CommentId) -> Result<i64, Error> {
+    use lemmy_db_schema::schema::{comment_like::dsl, person};
+    dsl::comment_like
+      .inner_join(person::table)
+      .filter(dsl::comment_id.eq(comment_id))
+      .filter(person::local.eq(true))
+      .select(sum(dsl::score))
+      .first::<Option<i64>>(conn)
+      .map(|score| score.unwrap_or(0))
+  }
+  fn scores_among(
+    conn: &PgConnection,
+    comment_id: CommentId,
+    person_ids: &[PersonId],
+  ) -> Result<Vec<(PersonId, i16)>, Error> {
+    use lemmy_db_schema::schema::comment_like::dsl;
+    dsl::comment_like
+      .select((dsl::person_id, dsl::score))
+      .filter(dsl::comment_id.eq(comment_id))
+      .filter(dsl::person_id.eq_any(person_ids))
+      .load::<(PersonId, i16)>(conn)
+  }
 }
 
 impl Saveable<CommentSavedForm> for CommentSaved {
@@ -205,12 +495,21 @@ impl Saveable<CommentSavedForm> for CommentSaved {
 
 #[cfg(test)]
 mod tests {
-  use crate::{establish_unpooled_connection, Crud, Likeable, Saveable};
-  use lemmy_db_schema::source::{
-    comment::*,
-    community::{Community, CommunityForm},
-    person::{Person, PersonForm},
-    post::*,
+  use crate::{
+    establish_unpooled_connection,
+    source::{comment::Comment_, language::UNDETERMINED_LANGUAGE_ID},
+    Crud,
+    Likeable,
+    Saveable,
+  };
+  use lemmy_db_schema::{
+    source::{
+      comment::*,
+      community::{Community, CommunityForm},
+      person::{Person, PersonForm},
+      post::*,
+    },
+    PersonId,
   };
   use serial_test::serial;
 
@@ -260,6 +559,13 @@ mod tests {
       inbox_url: None,
       shared_inbox_url: None,
       followers_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
     };
 
     let inserted_community = Community::create(&conn, &new_community).unwrap();
@@ -316,6 +622,11 @@ mod tests {
       updated: None,
       ap_id: inserted_comment.ap_id.to_owned(),
       local: true,
+      deleted_by_community_delete: false,
+      removed_by_community_remove: false,
+      language_id: UNDETERMINED_LANGUAGE_ID,
+      locked: false,
+      stickied: false,
     };
 
     let child_comment_form = CommentForm {
@@ -370,8 +681,22 @@ mod tests {
 
     let read_comment = Comment::read(&conn, inserted_comment.id).unwrap();
     let updated_comment = Comment::update(&conn, inserted_comment.id, &comment_form).unwrap();
+    let scores = CommentLike::scores_among(
+      &conn,
+      inserted_comment.id,
+      &[inserted_person.id, PersonId(-1)],
+    )
+    .unwrap();
     let like_removed = CommentLike::remove(&conn, inserted_person.id, inserted_comment.id).unwrap();
     let saved_removed = CommentSaved::unsave(&conn, &comment_saved_form).unwrap();
+    let child_in_unlocked_thread =
+      Comment::in_locked_thread(&conn, inserted_child_comment.id).unwrap();
+    let locked_comment = Comment::update_locked(&conn, inserted_comment.id, true).unwrap();
+    let child_in_locked_thread =
+      Comment::in_locked_thread(&conn, inserted_child_comment.id).unwrap();
+    let stickied_comment = Comment::update_stickied(&conn, inserted_comment.id, true).unwrap();
+    let stickied_child = Comment::update_stickied(&conn, inserted_child_comment.id, true).unwrap();
+    let previously_stickied = Comment::read(&conn, inserted_comment.id).unwrap();
     let num_deleted = Comment::delete(&conn, inserted_comment.id).unwrap();
     Comment::delete(&conn, inserted_child_comment.id).unwrap();
     Post::delete(&conn, inserted_post.id).unwrap();
@@ -387,8 +712,15 @@ mod tests {
       expected_comment.id,
       inserted_child_comment.parent_id.unwrap()
     );
+    assert_eq!(vec![(inserted_person.id, 1)], scores);
     assert_eq!(1, like_removed);
     assert_eq!(1, saved_removed);
+    assert!(!child_in_unlocked_thread);
+    assert!(locked_comment.locked);
+    assert!(child_in_locked_thread);
+    assert!(stickied_comment.stickied);
+    assert!(stickied_child.stickied);
+    assert!(!previously_stickied.stickied);
     assert_eq!(1, num_deleted);
   }
 }
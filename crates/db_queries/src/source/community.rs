@@ -4,15 +4,21 @@ use lemmy_db_schema::{
   naive_now,
   source::community::{
     Community,
+    CommunityFavorite,
+    CommunityFavoriteForm,
     CommunityFollower,
     CommunityFollowerForm,
     CommunityForm,
+    CommunityLastViewed,
+    CommunityLastViewedForm,
     CommunityModerator,
     CommunityModeratorForm,
     CommunityPersonBan,
     CommunityPersonBanForm,
   },
+  CommunityId,
   DbUrl,
+  PersonId,
 };
 
 mod safe_type {
@@ -34,6 +40,14 @@ mod safe_type {
     local,
     icon,
     banner,
+    exempt_from_slur_filter,
+    downvotes_disabled,
+    requires_post_approval,
+    theme_color,
+    short_description,
+    default_comment_sort,
+    require_post_tag,
+    manual_follow_approval,
   );
 
   impl ToSafe for Community {
@@ -54,18 +68,26 @@ mod safe_type {
         local,
         icon,
         banner,
+        exempt_from_slur_filter,
+        downvotes_disabled,
+        requires_post_approval,
+        theme_color,
+        short_description,
+        default_comment_sort,
+        require_post_tag,
+        manual_follow_approval,
       )
     }
   }
 }
 
-impl Crud<CommunityForm> for Community {
-  fn read(conn: &PgConnection, community_id: i32) -> Result<Self, Error> {
+impl Crud<CommunityForm, CommunityId> for Community {
+  fn read(conn: &PgConnection, community_id: CommunityId) -> Result<Self, Error> {
     use lemmy_db_schema::schema::community::dsl::*;
     community.find(community_id).first::<Self>(conn)
   }
 
-  fn delete(conn: &PgConnection, community_id: i32) -> Result<usize, Error> {
+  fn delete(conn: &PgConnection, community_id: CommunityId) -> Result<usize, Error> {
     use lemmy_db_schema::schema::community::dsl::*;
     diesel::delete(community.find(community_id)).execute(conn)
   }
@@ -79,7 +101,7 @@ impl Crud<CommunityForm> for Community {
 
   fn update(
     conn: &PgConnection,
-    community_id: i32,
+    community_id: CommunityId,
     new_community: &CommunityForm,
   ) -> Result<Self, Error> {
     use lemmy_db_schema::schema::community::dsl::*;
@@ -112,29 +134,50 @@ pub trait Community_ {
   fn read_from_name(conn: &PgConnection, community_name: &str) -> Result<Community, Error>;
   fn update_deleted(
     conn: &PgConnection,
-    community_id: i32,
+    community_id: CommunityId,
     new_deleted: bool,
   ) -> Result<Community, Error>;
   fn update_removed(
     conn: &PgConnection,
-    community_id: i32,
+    community_id: CommunityId,
     new_removed: bool,
   ) -> Result<Community, Error>;
   fn update_removed_for_creator(
     conn: &PgConnection,
-    for_creator_id: i32,
+    for_creator_id: PersonId,
     new_removed: bool,
   ) -> Result<Vec<Community>, Error>;
+  fn update_exempt_from_slur_filter(
+    conn: &PgConnection,
+    community_id: CommunityId,
+    new_exempt: bool,
+  ) -> Result<Community, Error>;
   fn update_creator(
     conn: &PgConnection,
-    community_id: i32,
-    new_creator_id: i32,
+    community_id: CommunityId,
+    new_creator_id: PersonId,
   ) -> Result<Community, Error>;
+  /// Sets `manually_refreshed_at` to now, unless the community was already refreshed manually after
+  /// `refreshed_before`. Returns whether it was set.
+  fn mark_manually_refreshed(
+    conn: &PgConnection,
+    community_id: CommunityId,
+    refreshed_before: chrono::NaiveDateTime,
+  ) -> Result<bool, Error>;
   fn distinct_federated_communities(conn: &PgConnection) -> Result<Vec<String>, Error>;
   fn read_from_followers_url(
     conn: &PgConnection,
     followers_url: &DbUrl,
   ) -> Result<Community, Error>;
+  fn read_local_from_apub_ids(
+    conn: &PgConnection,
+    apub_ids: Vec<DbUrl>,
+  ) -> Result<Vec<Community>, Error>;
+  /// Reads the communities with the given ids in one query, in no particular order
+  fn read_many(
+    conn: &PgConnection,
+    community_ids: Vec<CommunityId>,
+  ) -> Result<Vec<Community>, Error>;
 }
 
 impl Community_ for Community {
@@ -148,7 +191,7 @@ impl Community_ for Community {
 
   fn update_deleted(
     conn: &PgConnection,
-    community_id: i32,
+    community_id: CommunityId,
     new_deleted: bool,
   ) -> Result<Community, Error> {
     use lemmy_db_schema::schema::community::dsl::*;
@@ -159,7 +202,7 @@ impl Community_ for Community {
 
   fn update_removed(
     conn: &PgConnection,
-    community_id: i32,
+    community_id: CommunityId,
     new_removed: bool,
   ) -> Result<Community, Error> {
     use lemmy_db_schema::schema::community::dsl::*;
@@ -168,9 +211,39 @@ impl Community_ for Community {
       .get_result::<Self>(conn)
   }
 
+  fn update_exempt_from_slur_filter(
+    conn: &PgConnection,
+    community_id: CommunityId,
+    new_exempt: bool,
+  ) -> Result<Community, Error> {
+    use lemmy_db_schema::schema::community::dsl::*;
+    diesel::update(community.find(community_id))
+      .set(exempt_from_slur_filter.eq(new_exempt))
+      .get_result::<Self>(conn)
+  }
+
+  fn mark_manually_refreshed(
+    conn: &PgConnection,
+    community_id: CommunityId,
+    refreshed_before: chrono::NaiveDateTime,
+  ) -> Result<bool, Error> {
+    use lemmy_db_schema::schema::community::dsl::*;
+    // A single update, so that concurrent refreshes can't both pass
+    diesel::update(
+      community.find(community_id).filter(
+        manually_refreshed_at
+          .is_null()
+          .or(manually_refreshed_at.lt(refreshed_before)),
+      ),
+    )
+    .set(manually_refreshed_at.eq(naive_now()))
+    .execute(conn)
+    .map(|updated| updated > 0)
+  }
+
   fn update_removed_for_creator(
     conn: &PgConnection,
-    for_creator_id: i32,
+    for_creator_id: PersonId,
     new_removed: bool,
   ) -> Result<Vec<Community>, Error> {
     use lemmy_db_schema::schema::community::dsl::*;
@@ -181,8 +254,8 @@ impl Community_ for Community {
 
   fn update_creator(
     conn: &PgConnection,
-    community_id: i32,
-    new_creator_id: i32,
+    community_id: CommunityId,
+    new_creator_id: PersonId,
   ) -> Result<Community, Error> {
     use lemmy_db_schema::schema::community::dsl::*;
     diesel::update(community.find(community_id))
@@ -204,9 +277,36 @@ impl Community_ for Community {
       .filter(followers_url.eq(followers_url_))
       .first::<Self>(conn)
   }
+
+  /// Returns the local communities among the given apub ids, eg all local communities to which an
+  /// activity is addressed. Some software addresses the followers collection of a community
+  /// instead of the community, so those match as well.
+  fn read_local_from_apub_ids(
+    conn: &PgConnection,
+    apub_ids: Vec<DbUrl>,
+  ) -> Result<Vec<Community>, Error> {
+    use lemmy_db_schema::schema::community::dsl::*;
+    community
+      .filter(local.eq(true))
+      .filter(
+        actor_id
+          .eq_any(apub_ids.to_owned())
+          .or(followers_url.eq_any(apub_ids)),
+      )
+      .order_by(id)
+      .load::<Self>(conn)
+  }
+
+  fn read_many(conn: &PgConnection, community_ids: Vec<CommunityId>) -> Result<Vec<Self>, Error> {
+    use lemmy_db_schema::schema::community::dsl::*;
+    community
+      .filter(id.eq_any(community_ids))
+      .load::<Self>(conn)
+  }
 }
 
 impl Joinable<CommunityModeratorForm> for CommunityModerator {
+  /// Adding an existing moderator again returns the existing row, with its rank unchanged.
   fn join(
     conn: &PgConnection,
     community_moderator_form: &CommunityModeratorForm,
@@ -214,6 +314,9 @@ impl Joinable<CommunityModeratorForm> for CommunityModerator {
     use lemmy_db_schema::schema::community_moderator::dsl::*;
     insert_into(community_moderator)
       .values(community_moderator_form)
+      .on_conflict((community_id, person_id))
+      .do_update()
+      .set(community_moderator_form)
       .get_result::<Self>(conn)
   }
 
@@ -232,32 +335,73 @@ impl Joinable<CommunityModeratorForm> for CommunityModerator {
 }
 
 pub trait CommunityModerator_ {
-  fn delete_for_community(conn: &PgConnection, for_community_id: i32) -> Result<usize, Error>;
+  fn delete_for_community(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+  ) -> Result<usize, Error>;
   fn get_person_moderated_communities(
     conn: &PgConnection,
-    for_person_id: i32,
-  ) -> Result<Vec<i32>, Error>;
+    for_person_id: PersonId,
+  ) -> Result<Vec<CommunityId>, Error>;
+  fn make_head_moderator(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    for_person_id: PersonId,
+  ) -> Result<usize, Error>;
 }
 
 impl CommunityModerator_ for CommunityModerator {
-  fn delete_for_community(conn: &PgConnection, for_community_id: i32) -> Result<usize, Error> {
+  fn delete_for_community(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+  ) -> Result<usize, Error> {
     use lemmy_db_schema::schema::community_moderator::dsl::*;
     diesel::delete(community_moderator.filter(community_id.eq(for_community_id))).execute(conn)
   }
 
   fn get_person_moderated_communities(
     conn: &PgConnection,
-    for_person_id: i32,
-  ) -> Result<Vec<i32>, Error> {
+    for_person_id: PersonId,
+  ) -> Result<Vec<CommunityId>, Error> {
     use lemmy_db_schema::schema::community_moderator::dsl::*;
     community_moderator
       .filter(person_id.eq(for_person_id))
       .select(community_id)
-      .load::<i32>(conn)
+      .load::<CommunityId>(conn)
+  }
+
+  /// Moves the moderator to rank 0, and the moderators who were ranked above them down by one.
+  /// Should run in a transaction, so that the ranks stay unique.
+  fn make_head_moderator(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    for_person_id: PersonId,
+  ) -> Result<usize, Error> {
+    use lemmy_db_schema::schema::community_moderator::dsl::*;
+    let old_rank = community_moderator
+      .filter(community_id.eq(for_community_id))
+      .filter(person_id.eq(for_person_id))
+      .select(rank)
+      .first::<i32>(conn)?;
+    diesel::update(
+      community_moderator
+        .filter(community_id.eq(for_community_id))
+        .filter(rank.lt(old_rank)),
+    )
+    .set(rank.eq(rank + 1))
+    .execute(conn)?;
+    diesel::update(
+      community_moderator
+        .filter(community_id.eq(for_community_id))
+        .filter(person_id.eq(for_person_id)),
+    )
+    .set(rank.eq(0))
+    .execute(conn)
   }
 }
 
 impl Bannable<CommunityPersonBanForm> for CommunityPersonBan {
+  /// Banning a person who is already banned returns the existing ban.
   fn ban(
     conn: &PgConnection,
     community_person_ban_form: &CommunityPersonBanForm,
@@ -265,6 +409,9 @@ impl Bannable<CommunityPersonBanForm> for CommunityPersonBan {
     use lemmy_db_schema::schema::community_person_ban::dsl::*;
     insert_into(community_person_ban)
       .values(community_person_ban_form)
+      .on_conflict((community_id, person_id))
+      .do_update()
+      .set(community_person_ban_form)
       .get_result::<Self>(conn)
   }
 
@@ -282,6 +429,248 @@ impl Bannable<CommunityPersonBanForm> for CommunityPersonBan {
   }
 }
 
+pub trait CommunityFollower_ {
+  fn follow_multiple(
+    conn: &PgConnection,
+    community_follower_forms: &[CommunityFollowerForm],
+  ) -> Result<usize, Error>;
+  fn unfollow_multiple(
+    conn: &PgConnection,
+    for_person_id: PersonId,
+    community_ids: &[CommunityId],
+  ) -> Result<usize, Error>;
+  /// Stores a follow of a remote community, which waits for the Accept. An existing pending or
+  /// failed follow is reused, and its resends start over.
+  fn follow_pending(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    for_person_id: PersonId,
+  ) -> Result<CommunityFollower, Error>;
+  /// Stores a follow request for a community with manual follow approval, which waits for a
+  /// moderator. `follow_activity_id` is the Follow of a remote person. An existing follow is left
+  /// as it is, apart from the activity id.
+  fn follow_requested(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    for_person_id: PersonId,
+    for_follow_activity_id: Option<DbUrl>,
+  ) -> Result<CommunityFollower, Error>;
+  fn read_follow(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    for_person_id: PersonId,
+  ) -> Result<CommunityFollower, Error>;
+  /// Returns the pending follows of remote communities whose Follow was last sent before
+  /// `sent_before`, and which were resent less than `max_resends` times, at most `limit` of them.
+  /// They are counted as resent right away, so that each of them is resent by only one server.
+  fn take_for_resend(
+    conn: &PgConnection,
+    sent_before: chrono::NaiveDateTime,
+    max_resends: i32,
+    limit: i64,
+  ) -> Result<Vec<CommunityFollower>, Error>;
+  /// Gives up on the pending follows of remote communities whose Follow was last sent before
+  /// `sent_before`
+  fn mark_failed(conn: &PgConnection, sent_before: chrono::NaiveDateTime) -> Result<usize, Error>;
+  /// Mutes the followed community until the given time, or unmutes it with `None`
+  fn mute(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    for_person_id: PersonId,
+    until: Option<chrono::NaiveDateTime>,
+  ) -> Result<CommunityFollower, Error>;
+  /// Unmutes the follows whose mute is over. They are ignored anyway, this only tidies up.
+  fn clear_expired_mutes(conn: &PgConnection) -> Result<usize, Error>;
+  /// Which of the persons follow the community, not counting pending follows
+  fn followers_among(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    person_ids: &[PersonId],
+  ) -> Result<Vec<PersonId>, Error>;
+}
+
+impl CommunityFollower_ for CommunityFollower {
+  /// Inserts all follows with a single statement. Existing follows are left as they are, so
+  /// following a community twice is not an error. Returns the number of new follows.
+  fn follow_multiple(
+    conn: &PgConnection,
+    community_follower_forms: &[CommunityFollowerForm],
+  ) -> Result<usize, Error> {
+    use lemmy_db_schema::schema::community_follower::dsl::*;
+    if community_follower_forms.is_empty() {
+      return Ok(0);
+    }
+    insert_into(community_follower)
+      .values(community_follower_forms)
+      .on_conflict((community_id, person_id))
+      .do_nothing()
+      .execute(conn)
+  }
+
+  fn unfollow_multiple(
+    conn: &PgConnection,
+    for_person_id: PersonId,
+    community_ids: &[CommunityId],
+  ) -> Result<usize, Error> {
+    use lemmy_db_schema::schema::community_follower::dsl::*;
+    diesel::delete(
+      community_follower
+        .filter(person_id.eq(for_person_id))
+        .filter(community_id.eq_any(community_ids)),
+    )
+    .execute(conn)
+  }
+
+  fn follow_pending(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    for_person_id: PersonId,
+  ) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::community_follower::dsl::*;
+    let form = CommunityFollowerForm {
+      community_id: for_community_id,
+      person_id: for_person_id,
+      pending: true,
+    };
+    insert_into(community_follower)
+      .values(&form)
+      .on_conflict((community_id, person_id))
+      .do_update()
+      .set((
+        pending.eq(true),
+        follow_sent.eq(naive_now()),
+        follow_resends.eq(0),
+        follow_failed.eq(false),
+      ))
+      .get_result::<Self>(conn)
+  }
+
+  fn follow_requested(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    for_person_id: PersonId,
+    for_follow_activity_id: Option<DbUrl>,
+  ) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::community_follower::dsl::*;
+    insert_into(community_follower)
+      .values((
+        community_id.eq(for_community_id),
+        person_id.eq(for_person_id),
+        pending.eq(true),
+        follow_activity_id.eq(&for_follow_activity_id),
+      ))
+      .on_conflict((community_id, person_id))
+      .do_update()
+      .set(follow_activity_id.eq(&for_follow_activity_id))
+      .get_result::<Self>(conn)
+  }
+
+  fn read_follow(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    for_person_id: PersonId,
+  ) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::community_follower::dsl::*;
+    community_follower
+      .filter(community_id.eq(for_community_id))
+      .filter(person_id.eq(for_person_id))
+      .first::<Self>(conn)
+  }
+
+  fn take_for_resend(
+    conn: &PgConnection,
+    sent_before: chrono::NaiveDateTime,
+    max_resends: i32,
+    limit: i64,
+  ) -> Result<Vec<Self>, Error> {
+    use lemmy_db_schema::schema::{community, community_follower::dsl::*};
+    // Follow requests of local communities wait for a moderator instead
+    let remote_communities = || {
+      community::table
+        .select(community::id)
+        .filter(community::local.eq(false))
+    };
+    let due = community_follower
+      .select(id)
+      .filter(community_id.eq_any(remote_communities()))
+      .filter(pending.eq(true))
+      .filter(follow_failed.eq(false))
+      .filter(follow_sent.lt(sent_before))
+      .filter(follow_resends.lt(max_resends))
+      .order_by(follow_sent.asc())
+      .limit(limit);
+    // The conditions are repeated, so that a follow which another server took in the meantime is
+    // skipped
+    diesel::update(
+      community_follower
+        .filter(id.eq_any(due))
+        .filter(community_id.eq_any(remote_communities()))
+        .filter(pending.eq(true))
+        .filter(follow_failed.eq(false))
+        .filter(follow_sent.lt(sent_before))
+        .filter(follow_resends.lt(max_resends)),
+    )
+    .set((
+      follow_sent.eq(naive_now()),
+      follow_resends.eq(follow_resends + 1),
+    ))
+    .get_results::<Self>(conn)
+  }
+
+  fn mark_failed(conn: &PgConnection, sent_before: chrono::NaiveDateTime) -> Result<usize, Error> {
+    use lemmy_db_schema::schema::{community, community_follower::dsl::*};
+    let remote_communities = community::table
+      .select(community::id)
+      .filter(community::local.eq(false));
+    diesel::update(
+      community_follower
+        .filter(community_id.eq_any(remote_communities))
+        .filter(pending.eq(true))
+        .filter(follow_failed.eq(false))
+        .filter(follow_sent.lt(sent_before)),
+    )
+    .set(follow_failed.eq(true))
+    .execute(conn)
+  }
+
+  fn mute(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    for_person_id: PersonId,
+    until: Option<chrono::NaiveDateTime>,
+  ) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::community_follower::dsl::*;
+    diesel::update(
+      community_follower
+        .filter(community_id.eq(for_community_id))
+        .filter(person_id.eq(for_person_id)),
+    )
+    .set(mute_until.eq(until))
+    .get_result::<Self>(conn)
+  }
+
+  fn clear_expired_mutes(conn: &PgConnection) -> Result<usize, Error> {
+    use lemmy_db_schema::schema::community_follower::dsl::*;
+    diesel::update(community_follower.filter(mute_until.lt(naive_now())))
+      .set(mute_until.eq(None::<chrono::NaiveDateTime>))
+      .execute(conn)
+  }
+
+  fn followers_among(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    person_ids: &[PersonId],
+  ) -> Result<Vec<PersonId>, Error> {
+    use lemmy_db_schema::schema::community_follower::dsl::*;
+    community_follower
+      .filter(community_id.eq(for_community_id))
+      .filter(person_id.eq_any(person_ids))
+      .filter(pending.is_distinct_from(true))
+      .select(person_id)
+      .load::<PersonId>(conn)
+  }
+}
+
 impl Followable<CommunityFollowerForm> for CommunityFollower {
   fn follow(
     conn: &PgConnection,
@@ -297,8 +686,8 @@ impl Followable<CommunityFollowerForm> for CommunityFollower {
   }
   fn follow_accepted(
     conn: &PgConnection,
-    community_id_: i32,
-    person_id_: i32,
+    community_id_: CommunityId,
+    person_id_: PersonId,
   ) -> Result<Self, Error>
   where
     Self: Sized,
@@ -309,7 +698,7 @@ impl Followable<CommunityFollowerForm> for CommunityFollower {
         .filter(community_id.eq(community_id_))
         .filter(person_id.eq(person_id_)),
     )
-    .set(pending.eq(true))
+    .set((pending.eq(false), follow_failed.eq(false)))
     .get_result::<Self>(conn)
   }
   fn unfollow(
@@ -326,7 +715,7 @@ impl Followable<CommunityFollowerForm> for CommunityFollower {
   }
   // TODO: this function name only makes sense if you call it with a remote community. for a local
   //       community, it will also return true if only remote followers exist
-  fn has_local_followers(conn: &PgConnection, community_id_: i32) -> Result<bool, Error> {
+  fn has_local_followers(conn: &PgConnection, community_id_: CommunityId) -> Result<bool, Error> {
     use lemmy_db_schema::schema::community_follower::dsl::*;
     diesel::select(exists(
       community_follower.filter(community_id.eq(community_id_)),
@@ -335,11 +724,111 @@ impl Followable<CommunityFollowerForm> for CommunityFollower {
   }
 }
 
+pub trait CommunityFavorite_ {
+  /// Stars the community for the person, doing nothing if it already is a favorite
+  fn favorite(conn: &PgConnection, form: &CommunityFavoriteForm) -> Result<usize, Error>;
+  fn unfavorite(conn: &PgConnection, form: &CommunityFavoriteForm) -> Result<usize, Error>;
+  /// Which of the persons have the community as favorite
+  fn favorited_among(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    person_ids: &[PersonId],
+  ) -> Result<Vec<PersonId>, Error>;
+}
+
+impl CommunityFavorite_ for CommunityFavorite {
+  fn favorite(conn: &PgConnection, form: &CommunityFavoriteForm) -> Result<usize, Error> {
+    use lemmy_db_schema::schema::community_favorite::dsl::*;
+    insert_into(community_favorite)
+      .values(form)
+      .on_conflict((community_id, person_id))
+      .do_nothing()
+      .execute(conn)
+  }
+
+  fn unfavorite(conn: &PgConnection, form: &CommunityFavoriteForm) -> Result<usize, Error> {
+    use lemmy_db_schema::schema::community_favorite::dsl::*;
+    diesel::delete(
+      community_favorite
+        .filter(community_id.eq(&form.community_id))
+        .filter(person_id.eq(&form.person_id)),
+    )
+    .execute(conn)
+  }
+
+  fn favorited_among(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    person_ids: &[PersonId],
+  ) -> Result<Vec<PersonId>, Error> {
+    use lemmy_db_schema::schema::community_favorite::dsl::*;
+    community_favorite
+      .filter(community_id.eq(for_community_id))
+      .filter(person_id.eq_any(person_ids))
+      .select(person_id)
+      .load::<PersonId>(conn)
+  }
+}
+
+pub trait CommunityLastViewed_ {
+  /// Stores the current time as the last time the person opened the community
+  fn mark_viewed(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    for_person_id: PersonId,
+  ) -> Result<CommunityLastViewed, Error>;
+}
+
+impl CommunityLastViewed_ for CommunityLastViewed {
+  fn mark_viewed(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    for_person_id: PersonId,
+  ) -> Result<CommunityLastViewed, Error> {
+    use lemmy_db_schema::schema::community_last_viewed::dsl::*;
+    let form = CommunityLastViewedForm {
+      community_id: for_community_id,
+      person_id: for_person_id,
+      last_viewed: naive_now(),
+    };
+    insert_into(community_last_viewed)
+      .values(&form)
+      .on_conflict((community_id, person_id))
+      .do_update()
+      .set(&form)
+      .get_result::<Self>(conn)
+  }
+}
+
 #[cfg(test)]
 mod tests {
-  use crate::{establish_unpooled_connection, Bannable, Crud, Followable, Joinable};
-  use lemmy_db_schema::source::{community::*, person::*};
+  use crate::{
+    establish_unpooled_connection,
+    source::community::{
+      CommunityFavorite_,
+      CommunityFollower_,
+      CommunityLastViewed_,
+      CommunityModerator_,
+      Community_,
+    },
+    transaction,
+    Bannable,
+    Crud,
+    Followable,
+    Joinable,
+    SortType,
+  };
+  use diesel::prelude::*;
+  use lemmy_db_schema::{
+    naive_now,
+    source::{community::*, person::*},
+    CommunityId,
+    DbUrl,
+    PersonId,
+  };
+  use lemmy_utils::{ApiError, ApiErrorCode, LemmyError};
   use serial_test::serial;
+  use url::Url;
 
   #[test]
   #[serial]
@@ -387,6 +876,13 @@ mod tests {
       followers_url: None,
       inbox_url: None,
       shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
     };
 
     let inserted_community = Community::create(&conn, &new_community).unwrap();
@@ -412,6 +908,15 @@ mod tests {
       followers_url: inserted_community.followers_url.to_owned(),
       inbox_url: inserted_community.inbox_url.to_owned(),
       shared_inbox_url: None,
+      exempt_from_slur_filter: false,
+      downvotes_disabled: false,
+      requires_post_approval: false,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: SortType::New as i16,
+      require_post_tag: false,
+      manual_follow_approval: false,
+      manually_refreshed_at: None,
     };
 
     let community_follower_form = CommunityFollowerForm {
@@ -422,6 +927,8 @@ mod tests {
 
     let inserted_community_follower =
       CommunityFollower::follow(&conn, &community_follower_form).unwrap();
+    // Following, joining and banning again succeed and return the existing rows
+    let refollowed_community = CommunityFollower::follow(&conn, &community_follower_form).unwrap();
 
     let expected_community_follower = CommunityFollower {
       id: inserted_community_follower.id,
@@ -429,6 +936,11 @@ mod tests {
       person_id: inserted_person.id,
       pending: Some(false),
       published: inserted_community_follower.published,
+      follow_sent: inserted_community_follower.follow_sent,
+      follow_resends: 0,
+      follow_failed: false,
+      mute_until: None,
+      follow_activity_id: None,
     };
 
     let community_moderator_form = CommunityModeratorForm {
@@ -438,12 +950,15 @@ mod tests {
 
     let inserted_community_moderator =
       CommunityModerator::join(&conn, &community_moderator_form).unwrap();
+    let rejoined_community_moderator =
+      CommunityModerator::join(&conn, &community_moderator_form).unwrap();
 
     let expected_community_moderator = CommunityModerator {
       id: inserted_community_moderator.id,
       community_id: inserted_community.id,
       person_id: inserted_person.id,
       published: inserted_community_moderator.published,
+      rank: 0,
     };
 
     let community_person_ban_form = CommunityPersonBanForm {
@@ -453,6 +968,8 @@ mod tests {
 
     let inserted_community_person_ban =
       CommunityPersonBan::ban(&conn, &community_person_ban_form).unwrap();
+    let rebanned_community_person =
+      CommunityPersonBan::ban(&conn, &community_person_ban_form).unwrap();
 
     let expected_community_person_ban = CommunityPersonBan {
       id: inserted_community_person_ban.id,
@@ -467,6 +984,12 @@ mod tests {
     let ignored_community = CommunityFollower::unfollow(&conn, &community_follower_form).unwrap();
     let left_community = CommunityModerator::leave(&conn, &community_moderator_form).unwrap();
     let unban = CommunityPersonBan::unban(&conn, &community_person_ban_form).unwrap();
+    let repeated_unban = CommunityPersonBan::unban(&conn, &community_person_ban_form).unwrap();
+    let refreshed_before = naive_now() - chrono::Duration::minutes(10);
+    let marked_refreshed =
+      Community::mark_manually_refreshed(&conn, inserted_community.id, refreshed_before).unwrap();
+    let marked_refreshed_again =
+      Community::mark_manually_refreshed(&conn, inserted_community.id, refreshed_before).unwrap();
     let num_deleted = Community::delete(&conn, inserted_community.id).unwrap();
     Person::delete(&conn, inserted_person.id).unwrap();
 
@@ -476,10 +999,703 @@ mod tests {
     assert_eq!(expected_community_follower, inserted_community_follower);
     assert_eq!(expected_community_moderator, inserted_community_moderator);
     assert_eq!(expected_community_person_ban, inserted_community_person_ban);
+    assert_eq!(expected_community_follower, refollowed_community);
+    assert_eq!(expected_community_moderator, rejoined_community_moderator);
+    assert_eq!(expected_community_person_ban, rebanned_community_person);
     assert_eq!(1, ignored_community);
     assert_eq!(1, left_community);
     assert_eq!(1, unban);
+    assert_eq!(0, repeated_unban);
+    // Manual refreshes are limited
+    assert!(marked_refreshed);
+    assert!(!marked_refreshed_again);
     // assert_eq!(2, loaded_count);
     assert_eq!(1, num_deleted);
   }
+
+  #[test]
+  #[serial]
+  fn test_follow_multiple() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "bulk_follower".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let community_ids: Vec<CommunityId> = (0..3)
+      .map(|i| {
+        let community_form = CommunityForm {
+          name: format!("bulk_follow_{}", i),
+          creator_id: inserted_person.id,
+          title: "nada".to_owned(),
+          description: None,
+          nsfw: false,
+          removed: None,
+          deleted: None,
+          updated: None,
+          actor_id: None,
+          local: true,
+          private_key: None,
+          public_key: None,
+          last_refreshed_at: None,
+          published: None,
+          icon: None,
+          banner: None,
+          followers_url: None,
+          inbox_url: None,
+          shared_inbox_url: None,
+          downvotes_disabled: None,
+          requires_post_approval: None,
+          theme_color: None,
+          short_description: None,
+          default_comment_sort: None,
+          require_post_tag: None,
+          manual_follow_approval: None,
+        };
+        Community::create(&conn, &community_form).unwrap().id
+      })
+      .collect();
+    let follow_forms: Vec<CommunityFollowerForm> = community_ids
+      .iter()
+      .map(|community_id| CommunityFollowerForm {
+        community_id: *community_id,
+        person_id: inserted_person.id,
+        pending: false,
+      })
+      .collect();
+
+    // Following one community first, the bulk insert has to skip it
+    CommunityFollower::follow(&conn, &follow_forms[0]).unwrap();
+    let followed = CommunityFollower::follow_multiple(&conn, &follow_forms).unwrap();
+    let followed_again = CommunityFollower::follow_multiple(&conn, &follow_forms).unwrap();
+    let followed_none = CommunityFollower::follow_multiple(&conn, &[]).unwrap();
+    let unfollowed =
+      CommunityFollower::unfollow_multiple(&conn, inserted_person.id, &community_ids[1..]).unwrap();
+    let unfollowed_again =
+      CommunityFollower::unfollow_multiple(&conn, inserted_person.id, &community_ids[1..]).unwrap();
+
+    for community_id in &community_ids {
+      Community::delete(&conn, *community_id).unwrap();
+    }
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(2, followed);
+    assert_eq!(0, followed_again);
+    assert_eq!(0, followed_none);
+    assert_eq!(2, unfollowed);
+    assert_eq!(0, unfollowed_again);
+  }
+
+  #[test]
+  #[serial]
+  fn test_resend_pending_follow() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "pending_follower".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "pending_follow".into(),
+      creator_id: inserted_person.id,
+      title: "nada".to_owned(),
+      description: None,
+      nsfw: false,
+      removed: None,
+      deleted: None,
+      updated: None,
+      actor_id: None,
+      local: false,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+    let (community_id, person_id) = (inserted_community.id, inserted_person.id);
+
+    let local_community_form = CommunityForm {
+      name: "pending_follow_local".into(),
+      local: true,
+      manual_follow_approval: Some(true),
+      ..new_community
+    };
+    let local_community = Community::create(&conn, &local_community_form).unwrap();
+    let requested =
+      CommunityFollower::follow_requested(&conn, local_community.id, person_id, None).unwrap();
+
+    // Cutoffs in the future, so that the follow which was just sent is already due
+    let later = naive_now() + chrono::Duration::hours(1);
+    CommunityFollower::follow_pending(&conn, community_id, person_id).unwrap();
+    let resent_none = CommunityFollower::take_for_resend(&conn, later, 1, 0).unwrap();
+    let resent = CommunityFollower::take_for_resend(&conn, later, 1, 10).unwrap();
+    let resent_again = CommunityFollower::take_for_resend(&conn, later, 1, 10).unwrap();
+    let failed = CommunityFollower::mark_failed(&conn, later).unwrap();
+    let retried = CommunityFollower::follow_pending(&conn, community_id, person_id).unwrap();
+    let accepted = CommunityFollower::follow_accepted(&conn, community_id, person_id).unwrap();
+    let expired_mute = naive_now() - chrono::Duration::hours(1);
+    let muted =
+      CommunityFollower::mute(&conn, community_id, person_id, Some(expired_mute)).unwrap();
+    let cleared_mutes = CommunityFollower::clear_expired_mutes(&conn).unwrap();
+    let requested_again =
+      CommunityFollower::follow_requested(&conn, community_id, person_id, None).unwrap();
+
+    Community::delete(&conn, community_id).unwrap();
+    Community::delete(&conn, local_community.id).unwrap();
+    Person::delete(&conn, person_id).unwrap();
+
+    assert!(resent_none.is_empty());
+    assert_eq!(1, resent.len());
+    assert_eq!(1, resent[0].follow_resends);
+    assert!(resent_again.is_empty());
+    assert_eq!(1, failed);
+    assert_eq!(resent[0].id, retried.id);
+    assert_eq!(0, retried.follow_resends);
+    assert!(!retried.follow_failed);
+    assert_eq!(Some(false), accepted.pending);
+    assert_eq!(Some(expired_mute), muted.mute_until);
+    assert_eq!(1, cleared_mutes);
+    assert_eq!(Some(true), requested.pending);
+    assert_eq!(Some(false), requested_again.pending);
+  }
+
+  #[test]
+  #[serial]
+  fn test_follow_request() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "follow_request_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "follow_request".into(),
+      creator_id: inserted_person.id,
+      title: "nada".to_owned(),
+      description: None,
+      nsfw: false,
+      removed: None,
+      deleted: None,
+      updated: None,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: Some(true),
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+    let (community_id, person_id) = (inserted_community.id, inserted_person.id);
+
+    let not_requested = CommunityFollower::read_follow(&conn, community_id, person_id);
+    let first_follow: DbUrl = Url::parse("https://example.com/activities/follow/1")
+      .unwrap()
+      .into();
+    let second_follow: DbUrl = Url::parse("https://example.com/activities/follow/2")
+      .unwrap()
+      .into();
+    let requested =
+      CommunityFollower::follow_requested(&conn, community_id, person_id, Some(first_follow))
+        .unwrap();
+    // The person sent the Follow again, only the activity id changes
+    let requested_again = CommunityFollower::follow_requested(
+      &conn,
+      community_id,
+      person_id,
+      Some(second_follow.to_owned()),
+    )
+    .unwrap();
+    let read = CommunityFollower::read_follow(&conn, community_id, person_id).unwrap();
+    // A pending follow doesn't count as following yet
+    let followers_pending =
+      CommunityFollower::followers_among(&conn, community_id, &[person_id]).unwrap();
+    CommunityFollower::follow_accepted(&conn, community_id, person_id).unwrap();
+    let approved = CommunityFollower::read_follow(&conn, community_id, person_id).unwrap();
+    let followers_approved =
+      CommunityFollower::followers_among(&conn, community_id, &[person_id]).unwrap();
+
+    Community::delete(&conn, community_id).unwrap();
+    Person::delete(&conn, person_id).unwrap();
+
+    assert!(matches!(not_requested, Err(diesel::NotFound)));
+    assert_eq!(Some(true), requested.pending);
+    assert_eq!(requested.id, requested_again.id);
+    assert_eq!(Some(true), requested_again.pending);
+    assert_eq!(requested_again, read);
+    assert_eq!(Some(second_follow), read.follow_activity_id);
+    assert!(followers_pending.is_empty());
+    assert_eq!(Some(false), approved.pending);
+    assert_eq!(vec![person_id], followers_approved);
+  }
+
+  #[test]
+  #[serial]
+  fn test_mark_viewed() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "last_viewer".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let community_form = CommunityForm {
+      name: "last_viewed".into(),
+      creator_id: inserted_person.id,
+      title: "nada".to_owned(),
+      description: None,
+      nsfw: false,
+      removed: None,
+      deleted: None,
+      updated: None,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &community_form).unwrap();
+
+    let first =
+      CommunityLastViewed::mark_viewed(&conn, inserted_community.id, inserted_person.id).unwrap();
+    // Viewing again updates the existing row
+    let second =
+      CommunityLastViewed::mark_viewed(&conn, inserted_community.id, inserted_person.id).unwrap();
+
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(first.id, second.id);
+    assert!(second.last_viewed >= first.last_viewed);
+  }
+
+  #[test]
+  #[serial]
+  fn test_favorite() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "favoriter".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let community_form = CommunityForm {
+      name: "favorite_community".into(),
+      creator_id: inserted_person.id,
+      title: "nada".to_owned(),
+      description: None,
+      nsfw: false,
+      removed: None,
+      deleted: None,
+      updated: None,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &community_form).unwrap();
+
+    let favorite_form = CommunityFavoriteForm {
+      community_id: inserted_community.id,
+      person_id: inserted_person.id,
+    };
+    let favorited = CommunityFavorite::favorite(&conn, &favorite_form).unwrap();
+    // Favoriting again keeps the existing row
+    let favorited_again = CommunityFavorite::favorite(&conn, &favorite_form).unwrap();
+    let person_ids = [inserted_person.id];
+    let favorited_among =
+      CommunityFavorite::favorited_among(&conn, inserted_community.id, &person_ids).unwrap();
+    let followers_among =
+      CommunityFollower::followers_among(&conn, inserted_community.id, &person_ids).unwrap();
+    let unfavorited = CommunityFavorite::unfavorite(&conn, &favorite_form).unwrap();
+
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(1, favorited);
+    assert_eq!(0, favorited_again);
+    assert_eq!(vec![inserted_person.id], favorited_among);
+    assert!(followers_among.is_empty());
+    assert_eq!(1, unfavorited);
+  }
+
+  #[test]
+  #[serial]
+  fn test_read_local_from_apub_ids() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "fanout_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let community_form = |name: &str, local: bool| CommunityForm {
+      name: name.into(),
+      creator_id: inserted_person.id,
+      title: "nada".to_owned(),
+      description: None,
+      nsfw: false,
+      removed: None,
+      deleted: None,
+      updated: None,
+      actor_id: None,
+      local,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let local_one = Community::create(&conn, &community_form("fanout_one", true)).unwrap();
+    let local_two = Community::create(&conn, &community_form("fanout_two", true)).unwrap();
+    let remote = Community::create(&conn, &community_form("fanout_remote", false)).unwrap();
+
+    // An activity addressed to two local communities, one of them by its followers collection, a
+    // remote community and a person
+    let addressed = vec![
+      local_one.actor_id.to_owned(),
+      Url::parse("https://example.com/u/someone").unwrap().into(),
+      remote.actor_id.to_owned(),
+      remote.followers_url.to_owned(),
+      local_two.followers_url.to_owned(),
+    ];
+    let read_communities = Community::read_local_from_apub_ids(&conn, addressed).unwrap();
+
+    Community::delete(&conn, local_one.id).unwrap();
+    Community::delete(&conn, local_two.id).unwrap();
+    Community::delete(&conn, remote.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(vec![local_one, local_two], read_communities);
+  }
+
+  #[test]
+  #[serial]
+  fn test_transaction_rolls_back_on_error() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "rollback_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "rollback_community".into(),
+      creator_id: inserted_person.id,
+      title: "nada".to_owned(),
+      description: None,
+      nsfw: false,
+      removed: None,
+      deleted: None,
+      updated: None,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+
+    // Fails after the community and its moderator were written, like CreateCommunity would if
+    // following the new community failed
+    let mut inserted_community_id = None;
+    let res: Result<(), LemmyError> = transaction(&conn, |conn| {
+      let inserted_community = Community::create(conn, &new_community)?;
+      inserted_community_id = Some(inserted_community.id);
+      let community_moderator_form = CommunityModeratorForm {
+        community_id: inserted_community.id,
+        person_id: inserted_person.id,
+      };
+      CommunityModerator::join(conn, &community_moderator_form)?;
+      Err(ApiError::new(ApiErrorCode::CommunityFollowerAlreadyExists).into())
+    });
+
+    let read_community = Community::read(&conn, inserted_community_id.unwrap());
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    let api_error = res.unwrap_err().to_api_error();
+    assert_eq!("community_follower_already_exists", api_error.message);
+    assert!(read_community.is_err());
+  }
+
+  #[test]
+  #[serial]
+  fn test_moderator_ranks() {
+    let conn = establish_unpooled_connection();
+
+    let person_form = |name: &str| PersonForm {
+      name: name.into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let first = Person::create(&conn, &person_form("rank_first")).unwrap();
+    let second = Person::create(&conn, &person_form("rank_second")).unwrap();
+    let third = Person::create(&conn, &person_form("rank_third")).unwrap();
+
+    let new_community = CommunityForm {
+      name: "rank_community".into(),
+      creator_id: first.id,
+      title: "nada".to_owned(),
+      description: None,
+      nsfw: false,
+      removed: None,
+      deleted: None,
+      updated: None,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let moderator_form = |person_id: PersonId| CommunityModeratorForm {
+      community_id: inserted_community.id,
+      person_id,
+    };
+    let ranks = || {
+      use lemmy_db_schema::schema::community_moderator::dsl::*;
+      community_moderator
+        .filter(community_id.eq(inserted_community.id))
+        .order_by(rank)
+        .select((person_id, rank))
+        .load::<(PersonId, i32)>(&conn)
+        .unwrap()
+    };
+
+    // New moderators are ranked below the existing ones
+    CommunityModerator::join(&conn, &moderator_form(first.id)).unwrap();
+    CommunityModerator::join(&conn, &moderator_form(second.id)).unwrap();
+    CommunityModerator::join(&conn, &moderator_form(third.id)).unwrap();
+    let after_join = ranks();
+
+    CommunityModerator::make_head_moderator(&conn, inserted_community.id, third.id).unwrap();
+    let after_transfer = ranks();
+
+    // The moderators below close the gap
+    CommunityModerator::leave(&conn, &moderator_form(first.id)).unwrap();
+    let after_leave = ranks();
+
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, first.id).unwrap();
+    Person::delete(&conn, second.id).unwrap();
+    Person::delete(&conn, third.id).unwrap();
+
+    assert_eq!(
+      vec![(first.id, 0), (second.id, 1), (third.id, 2)],
+      after_join
+    );
+    assert_eq!(
+      vec![(third.id, 0), (first.id, 1), (second.id, 2)],
+      after_transfer
+    );
+    assert_eq!(vec![(third.id, 0), (second.id, 1)], after_leave);
+  }
 }
@@ -0,0 +1,151 @@
+use diesel::{dsl::*, result::Error, *};
+use lemmy_db_schema::{
+  source::community_federation_log::{CommunityFederationLog, CommunityFederationLogForm},
+  CommunityId,
+};
+
+pub trait CommunityFederationLog_ {
+  fn insert(
+    conn: &PgConnection,
+    form: &CommunityFederationLogForm,
+  ) -> Result<CommunityFederationLog, Error>;
+  /// The latest inbound or outbound activities of the community, newest first
+  fn list_for_community(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    for_inbound: bool,
+    limit: i64,
+  ) -> Result<Vec<CommunityFederationLog>, Error>;
+  fn delete_older_than_days(conn: &PgConnection, days: i64) -> Result<usize, Error>;
+}
+
+impl CommunityFederationLog_ for CommunityFederationLog {
+  fn insert(
+    conn: &PgConnection,
+    form: &CommunityFederationLogForm,
+  ) -> Result<CommunityFederationLog, Error> {
+    use lemmy_db_schema::schema::community_federation_log::dsl::*;
+    insert_into(community_federation_log)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn list_for_community(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    for_inbound: bool,
+    limit: i64,
+  ) -> Result<Vec<CommunityFederationLog>, Error> {
+    use lemmy_db_schema::schema::community_federation_log::dsl::*;
+    community_federation_log
+      .filter(community_id.eq(for_community_id))
+      .filter(inbound.eq(for_inbound))
+      .order_by(published.desc())
+      .then_order_by(id.desc())
+      .limit(limit)
+      .load::<Self>(conn)
+  }
+
+  fn delete_older_than_days(conn: &PgConnection, days: i64) -> Result<usize, Error> {
+    use lemmy_db_schema::schema::community_federation_log::dsl::*;
+    diesel::delete(community_federation_log.filter(published.lt(now - days.days()))).execute(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    establish_unpooled_connection,
+    source::community_federation_log::CommunityFederationLog_,
+    Crud,
+  };
+  use lemmy_db_schema::source::{
+    community::{Community, CommunityForm},
+    community_federation_log::{CommunityFederationLog, CommunityFederationLogForm},
+    person::{Person, PersonForm},
+  };
+  use serial_test::serial;
+
+  #[test]
+  #[serial]
+  fn test_community_federation_log() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "federation_log_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "federation_log_community".into(),
+      creator_id: inserted_person.id,
+      title: "nada".to_owned(),
+      description: None,
+      nsfw: false,
+      removed: None,
+      deleted: None,
+      updated: None,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let log_form = |inbound: bool, error: Option<&str>| CommunityFederationLogForm {
+      community_id: inserted_community.id,
+      inbound,
+      kind: Some("Create".into()),
+      domain: "example.com".into(),
+      error: error.map(|e| e.to_owned()),
+    };
+    let accepted = CommunityFederationLog::insert(&conn, &log_form(true, None)).unwrap();
+    let rejected =
+      CommunityFederationLog::insert(&conn, &log_form(true, Some("Invalid signature"))).unwrap();
+    let delivered = CommunityFederationLog::insert(&conn, &log_form(false, None)).unwrap();
+
+    let inbound =
+      CommunityFederationLog::list_for_community(&conn, inserted_community.id, true, 50).unwrap();
+    let outbound =
+      CommunityFederationLog::list_for_community(&conn, inserted_community.id, false, 1).unwrap();
+
+    // Nothing is old enough yet
+    let deleted = CommunityFederationLog::delete_older_than_days(&conn, 7).unwrap();
+
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(vec![rejected, accepted], inbound);
+    assert_eq!(vec![delivered], outbound);
+    assert_eq!(0, deleted);
+  }
+}
@@ -0,0 +1,250 @@
+use crate::Crud;
+use diesel::{result::Error, *};
+use lemmy_db_schema::{
+  source::community_tag::{CommunityTag, CommunityTagForm, PostTagForm},
+  CommunityId,
+  PostId,
+};
+
+/// The maximum number of tags of a single post
+pub const MAX_POST_TAGS: usize = 5;
+
+impl Crud<CommunityTagForm, i32> for CommunityTag {
+  fn read(conn: &PgConnection, tag_id: i32) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::community_tag::dsl::*;
+    community_tag.find(tag_id).first::<Self>(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &CommunityTagForm) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::community_tag::dsl::*;
+    insert_into(community_tag)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(conn: &PgConnection, tag_id: i32, form: &CommunityTagForm) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::community_tag::dsl::*;
+    diesel::update(community_tag.find(tag_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, tag_id: i32) -> Result<usize, Error> {
+    use lemmy_db_schema::schema::community_tag::dsl::*;
+    diesel::delete(community_tag.find(tag_id)).execute(conn)
+  }
+}
+
+pub trait CommunityTag_ {
+  fn list_for_community(
+    conn: &PgConnection,
+    community_id: CommunityId,
+  ) -> Result<Vec<CommunityTag>, Error>;
+  fn list_for_post(conn: &PgConnection, post_id: PostId) -> Result<Vec<CommunityTag>, Error>;
+  /// The tags of several posts in one query, each with the id of its post
+  fn list_for_posts(
+    conn: &PgConnection,
+    post_ids: Vec<PostId>,
+  ) -> Result<Vec<(PostId, CommunityTag)>, Error>;
+  /// Replaces the tags of a post. The tags have to belong to the community of the post.
+  fn set_for_post(conn: &PgConnection, post_id: PostId, tag_ids: &[i32]) -> Result<usize, Error>;
+}
+
+impl CommunityTag_ for CommunityTag {
+  fn list_for_community(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+  ) -> Result<Vec<CommunityTag>, Error> {
+    use lemmy_db_schema::schema::community_tag::dsl::*;
+    community_tag
+      .filter(community_id.eq(for_community_id))
+      .order_by(name)
+      .load::<Self>(conn)
+  }
+
+  fn list_for_post(conn: &PgConnection, for_post_id: PostId) -> Result<Vec<CommunityTag>, Error> {
+    use lemmy_db_schema::schema::{community_tag, post_tag};
+    community_tag::table
+      .filter(
+        community_tag::id.eq_any(
+          post_tag::table
+            .select(post_tag::tag_id)
+            .filter(post_tag::post_id.eq(for_post_id)),
+        ),
+      )
+      .order_by(community_tag::name)
+      .load::<Self>(conn)
+  }
+
+  fn list_for_posts(
+    conn: &PgConnection,
+    post_ids: Vec<PostId>,
+  ) -> Result<Vec<(PostId, CommunityTag)>, Error> {
+    use lemmy_db_schema::schema::{community_tag, post_tag};
+    post_tag::table
+      .inner_join(community_tag::table)
+      .filter(post_tag::post_id.eq_any(post_ids))
+      .select((post_tag::post_id, community_tag::all_columns))
+      .order_by(community_tag::name)
+      .load::<(PostId, CommunityTag)>(conn)
+  }
+
+  fn set_for_post(
+    conn: &PgConnection,
+    for_post_id: PostId,
+    tag_ids: &[i32],
+  ) -> Result<usize, Error> {
+    use lemmy_db_schema::schema::post_tag::dsl::*;
+    conn.transaction(|| {
+      diesel::delete(post_tag.filter(post_id.eq(for_post_id))).execute(conn)?;
+      if tag_ids.is_empty() {
+        return Ok(0);
+      }
+      let forms = tag_ids
+        .iter()
+        .map(|t| PostTagForm {
+          post_id: for_post_id,
+          tag_id: *t,
+        })
+        .collect::<Vec<PostTagForm>>();
+      insert_into(post_tag)
+        .values(forms)
+        .on_conflict_do_nothing()
+        .execute(conn)
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{establish_unpooled_connection, source::community_tag::CommunityTag_, Crud};
+  use lemmy_db_schema::source::{
+    community::{Community, CommunityForm},
+    community_tag::{CommunityTag, CommunityTagForm},
+    person::{Person, PersonForm},
+    post::{Post, PostForm},
+  };
+  use serial_test::serial;
+
+  #[test]
+  #[serial]
+  fn test_post_tags() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "tag_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "tag_community".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: Some(true),
+      manual_follow_approval: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let new_post = PostForm {
+      name: "A tagged post".into(),
+      url: None,
+      body: None,
+      creator_id: inserted_person.id,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      nsfw: false,
+      updated: None,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      thumbnail_url: None,
+      ap_id: None,
+      local: true,
+      published: None,
+    };
+
+    let inserted_post = Post::create(&conn, &new_post).unwrap();
+
+    let meta = CommunityTag::create(
+      &conn,
+      &CommunityTagForm {
+        community_id: inserted_community.id,
+        name: "meta".into(),
+        color: Some("#1e90ff".into()),
+      },
+    )
+    .unwrap();
+    let question = CommunityTag::create(
+      &conn,
+      &CommunityTagForm {
+        community_id: inserted_community.id,
+        name: "question".into(),
+        color: None,
+      },
+    )
+    .unwrap();
+
+    let listed = CommunityTag::list_for_community(&conn, inserted_community.id).unwrap();
+    assert_eq!(vec![meta.clone(), question.clone()], listed);
+
+    CommunityTag::set_for_post(&conn, inserted_post.id, &[question.id, meta.id]).unwrap();
+    let post_tags = CommunityTag::list_for_post(&conn, inserted_post.id).unwrap();
+    assert_eq!(vec![meta.clone(), question.clone()], post_tags);
+
+    CommunityTag::set_for_post(&conn, inserted_post.id, &[question.id]).unwrap();
+    let post_tags = CommunityTag::list_for_post(&conn, inserted_post.id).unwrap();
+    assert_eq!(vec![question.clone()], post_tags);
+
+    // Deleting a tag removes it from the posts
+    let num_deleted = CommunityTag::delete(&conn, question.id).unwrap();
+    assert_eq!(1, num_deleted);
+    assert!(CommunityTag::list_for_post(&conn, inserted_post.id)
+      .unwrap()
+      .is_empty());
+
+    Post::delete(&conn, inserted_post.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+  }
+}
@@ -0,0 +1,219 @@
+use diesel::{
+  dsl::*,
+  result::Error,
+  sql_types::{Nullable, Timestamp},
+  *,
+};
+use lemmy_db_schema::{
+  source::instance::{Instance, InstanceForm},
+  PersonId,
+};
+
+pub trait Instance_ {
+  /// Creates rows for the domains which aren't known yet
+  fn insert_missing(conn: &PgConnection, domains: &[String]) -> Result<usize, Error>;
+  /// Returns up to `limit` of the given instances, those which were fetched least recently first.
+  fn list_for_refresh(
+    conn: &PgConnection,
+    domains: &[String],
+    limit: i64,
+  ) -> Result<Vec<Instance>, Error>;
+  fn update_software(
+    conn: &PgConnection,
+    instance_id: i32,
+    software: &str,
+    version: Option<String>,
+  ) -> Result<Instance, Error>;
+  /// Records a failed nodeinfo fetch, the previously known software is kept.
+  fn update_fetch_failed(conn: &PgConnection, instance_id: i32) -> Result<Instance, Error>;
+  fn list(conn: &PgConnection) -> Result<Vec<Instance>, Error>;
+  fn list_for_domains(conn: &PgConnection, domains: &[String]) -> Result<Vec<Instance>, Error>;
+  fn read_from_domain(conn: &PgConnection, domain: &str) -> Result<Instance, Error>;
+  /// Stores the service actor for aggregated votes, unless the instance already has one. Returns
+  /// the number of updated rows.
+  fn set_vote_actor(
+    conn: &PgConnection,
+    instance_id: i32,
+    person_id: PersonId,
+  ) -> Result<usize, Error>;
+}
+
+impl Instance_ for Instance {
+  fn insert_missing(conn: &PgConnection, domains: &[String]) -> Result<usize, Error> {
+    use lemmy_db_schema::schema::instance::dsl::*;
+    let forms = domains
+      .iter()
+      .map(|d| InstanceForm {
+        domain: d.to_owned(),
+      })
+      .collect::<Vec<InstanceForm>>();
+    insert_into(instance)
+      .values(&forms)
+      .on_conflict_do_nothing()
+      .execute(conn)
+  }
+
+  fn list_for_refresh(
+    conn: &PgConnection,
+    domains: &[String],
+    limit: i64,
+  ) -> Result<Vec<Instance>, Error> {
+    use lemmy_db_schema::schema::instance::dsl::*;
+    instance
+      .filter(domain.eq_any(domains))
+      .order_by(last_fetched.asc().nulls_first())
+      .then_order_by(id)
+      .limit(limit)
+      .load::<Self>(conn)
+  }
+
+  fn update_software(
+    conn: &PgConnection,
+    instance_id: i32,
+    new_software: &str,
+    new_version: Option<String>,
+  ) -> Result<Instance, Error> {
+    use lemmy_db_schema::schema::instance::dsl::*;
+    diesel::update(instance.find(instance_id))
+      .set((
+        software.eq(new_software),
+        version.eq(new_version),
+        last_seen.eq(now.nullable()),
+        last_fetched.eq(now.nullable()),
+        unreachable_since.eq::<Option<chrono::NaiveDateTime>>(None),
+      ))
+      .get_result::<Self>(conn)
+  }
+
+  fn update_fetch_failed(conn: &PgConnection, instance_id: i32) -> Result<Instance, Error> {
+    use lemmy_db_schema::schema::instance::dsl::*;
+    diesel::update(instance.find(instance_id))
+      .set((
+        last_fetched.eq(now.nullable()),
+        unreachable_since.eq(sql::<Nullable<Timestamp>>(
+          "coalesce(unreachable_since, now())",
+        )),
+      ))
+      .get_result::<Self>(conn)
+  }
+
+  fn list(conn: &PgConnection) -> Result<Vec<Instance>, Error> {
+    use lemmy_db_schema::schema::instance::dsl::*;
+    instance.order_by(domain).load::<Self>(conn)
+  }
+
+  fn list_for_domains(conn: &PgConnection, domains: &[String]) -> Result<Vec<Instance>, Error> {
+    use lemmy_db_schema::schema::instance::dsl::*;
+    instance.filter(domain.eq_any(domains)).load::<Self>(conn)
+  }
+
+  fn read_from_domain(conn: &PgConnection, from_domain: &str) -> Result<Instance, Error> {
+    use lemmy_db_schema::schema::instance::dsl::*;
+    instance.filter(domain.eq(from_domain)).first::<Self>(conn)
+  }
+
+  fn set_vote_actor(
+    conn: &PgConnection,
+    instance_id: i32,
+    person_id: PersonId,
+  ) -> Result<usize, Error> {
+    use lemmy_db_schema::schema::instance::dsl::*;
+    diesel::update(instance.find(instance_id).filter(vote_actor_id.is_null()))
+      .set(vote_actor_id.eq(person_id))
+      .execute(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{establish_unpooled_connection, source::instance::Instance_, Crud};
+  use diesel::*;
+  use lemmy_db_schema::{
+    schema::instance,
+    source::{
+      instance::Instance,
+      person::{Person, PersonForm},
+    },
+  };
+  use serial_test::serial;
+
+  #[test]
+  #[serial]
+  fn test_instance() {
+    let conn = establish_unpooled_connection();
+    let domains = vec!["instance_a.test".to_string(), "instance_b.test".to_string()];
+
+    assert_eq!(2, Instance::insert_missing(&conn, &domains).unwrap());
+    assert_eq!(0, Instance::insert_missing(&conn, &domains).unwrap());
+
+    // Instances which were never fetched come first
+    let to_refresh = Instance::list_for_refresh(&conn, &domains, 2).unwrap();
+    assert_eq!(2, to_refresh.len());
+    let updated =
+      Instance::update_software(&conn, to_refresh[0].id, "lemmy", Some("0.10.0".into())).unwrap();
+    assert_eq!(Some("lemmy".to_string()), updated.software);
+    assert!(updated.last_seen.is_some());
+
+    let failed = Instance::update_fetch_failed(&conn, to_refresh[1].id).unwrap();
+    assert_eq!(None, failed.software);
+    assert_eq!(None, failed.last_seen);
+    assert!(failed.last_fetched.is_some());
+    assert!(failed.unreachable_since.is_some());
+    assert_eq!(None, updated.unreachable_since);
+
+    // The time of the first failure is kept
+    let failed_twice = Instance::update_fetch_failed(&conn, failed.id).unwrap();
+    assert_eq!(failed.unreachable_since, failed_twice.unreachable_since);
+
+    let next = Instance::list_for_refresh(&conn, &domains, 1).unwrap();
+    assert_eq!(to_refresh[0].id, next[0].id);
+
+    // A failed fetch keeps the previously known software
+    let failed_again = Instance::update_fetch_failed(&conn, updated.id).unwrap();
+    assert_eq!(Some("lemmy".to_string()), failed_again.software);
+    assert_eq!(updated.last_seen, failed_again.last_seen);
+    assert!(failed_again.unreachable_since.is_some());
+
+    let reachable_again =
+      Instance::update_software(&conn, updated.id, "lemmy", Some("0.10.0".into())).unwrap();
+    assert_eq!(None, reachable_again.unreachable_since);
+
+    // The first vote actor of an instance is kept
+    let vote_actor_form = |name: &str| PersonForm {
+      name: name.into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let vote_actor = Person::create(&conn, &vote_actor_form("instance_vote_actor")).unwrap();
+    let other_actor = Person::create(&conn, &vote_actor_form("instance_other_actor")).unwrap();
+    assert_eq!(
+      1,
+      Instance::set_vote_actor(&conn, updated.id, vote_actor.id).unwrap()
+    );
+    assert_eq!(
+      0,
+      Instance::set_vote_actor(&conn, updated.id, other_actor.id).unwrap()
+    );
+    let with_vote_actor = Instance::read_from_domain(&conn, &updated.domain).unwrap();
+    assert_eq!(Some(vote_actor.id), with_vote_actor.vote_actor_id);
+    Person::delete(&conn, vote_actor.id).unwrap();
+    Person::delete(&conn, other_actor.id).unwrap();
+
+    diesel::delete(instance::table.filter(instance::domain.eq_any(&domains)))
+      .execute(&conn)
+      .unwrap();
+  }
+}
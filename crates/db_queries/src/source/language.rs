@@ -0,0 +1,245 @@
+use diesel::{dsl::*, result::Error, *};
+use lemmy_db_schema::{
+  schema::{community_language, language, local_user_language},
+  source::language::{CommunityLanguageForm, Language, LocalUserLanguageForm},
+  CommunityId,
+  LanguageId,
+};
+
+/// Content whose language isn't known. It passes every language filter and restriction.
+pub const UNDETERMINED_LANGUAGE_ID: LanguageId = LanguageId(0);
+
+pub trait Language_ {
+  fn read_all(conn: &PgConnection) -> Result<Vec<Language>, Error>;
+  fn read_from_id(conn: &PgConnection, language_id: LanguageId) -> Result<Language, Error>;
+  /// The language with the ISO 639-1 code, in any letter case
+  fn read_from_code(conn: &PgConnection, code: &str) -> Result<Language, Error>;
+  /// The languages which the local user wants to read, empty if they read all languages
+  fn read_local_user_languages(
+    conn: &PgConnection,
+    local_user_id: i32,
+  ) -> Result<Vec<LanguageId>, Error>;
+  /// Replaces the languages which the local user wants to read
+  fn update_local_user_languages(
+    conn: &PgConnection,
+    local_user_id: i32,
+    language_ids: &[LanguageId],
+  ) -> Result<(), Error>;
+  /// The languages which are allowed in the community, empty if all languages are allowed
+  fn read_community_languages(
+    conn: &PgConnection,
+    community_id: CommunityId,
+  ) -> Result<Vec<LanguageId>, Error>;
+  /// Replaces the languages which are allowed in the community
+  fn update_community_languages(
+    conn: &PgConnection,
+    community_id: CommunityId,
+    language_ids: &[LanguageId],
+  ) -> Result<(), Error>;
+  /// Whether content in the language may be posted in the community
+  fn is_allowed_in_community(
+    conn: &PgConnection,
+    community_id: CommunityId,
+    language_id: LanguageId,
+  ) -> Result<bool, Error>;
+}
+
+impl Language_ for Language {
+  fn read_all(conn: &PgConnection) -> Result<Vec<Self>, Error> {
+    language::table.order_by(language::id).load::<Self>(conn)
+  }
+
+  fn read_from_id(conn: &PgConnection, language_id: LanguageId) -> Result<Self, Error> {
+    language::table.find(language_id).first::<Self>(conn)
+  }
+
+  fn read_from_code(conn: &PgConnection, code: &str) -> Result<Self, Error> {
+    language::table
+      .filter(language::code.eq(code.to_lowercase()))
+      .first::<Self>(conn)
+  }
+
+  fn read_local_user_languages(
+    conn: &PgConnection,
+    local_user_id: i32,
+  ) -> Result<Vec<LanguageId>, Error> {
+    local_user_language::table
+      .filter(local_user_language::local_user_id.eq(local_user_id))
+      .select(local_user_language::language_id)
+      .order_by(local_user_language::language_id)
+      .load::<LanguageId>(conn)
+  }
+
+  fn update_local_user_languages(
+    conn: &PgConnection,
+    local_user_id: i32,
+    language_ids: &[LanguageId],
+  ) -> Result<(), Error> {
+    let forms: Vec<_> = language_ids
+      .iter()
+      .map(|language_id| LocalUserLanguageForm {
+        local_user_id,
+        language_id: *language_id,
+      })
+      .collect();
+    conn.transaction(|| {
+      delete(
+        local_user_language::table.filter(local_user_language::local_user_id.eq(local_user_id)),
+      )
+      .execute(conn)?;
+      insert_into(local_user_language::table)
+        .values(forms)
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+      Ok(())
+    })
+  }
+
+  fn read_community_languages(
+    conn: &PgConnection,
+    community_id: CommunityId,
+  ) -> Result<Vec<LanguageId>, Error> {
+    community_language::table
+      .filter(community_language::community_id.eq(community_id))
+      .select(community_language::language_id)
+      .order_by(community_language::language_id)
+      .load::<LanguageId>(conn)
+  }
+
+  fn update_community_languages(
+    conn: &PgConnection,
+    community_id: CommunityId,
+    language_ids: &[LanguageId],
+  ) -> Result<(), Error> {
+    let forms: Vec<_> = language_ids
+      .iter()
+      .map(|language_id| CommunityLanguageForm {
+        community_id,
+        language_id: *language_id,
+      })
+      .collect();
+    conn.transaction(|| {
+      delete(community_language::table.filter(community_language::community_id.eq(community_id)))
+        .execute(conn)?;
+      insert_into(community_language::table)
+        .values(forms)
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+      Ok(())
+    })
+  }
+
+  fn is_allowed_in_community(
+    conn: &PgConnection,
+    community_id: CommunityId,
+    language_id: LanguageId,
+  ) -> Result<bool, Error> {
+    if language_id == UNDETERMINED_LANGUAGE_ID {
+      return Ok(true);
+    }
+    let allowed = Self::read_community_languages(conn, community_id)?;
+    Ok(allowed.is_empty() || allowed.contains(&language_id))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    establish_unpooled_connection,
+    source::language::{Language_, UNDETERMINED_LANGUAGE_ID},
+    Crud,
+  };
+  use lemmy_db_schema::source::{
+    community::{Community, CommunityForm},
+    language::Language,
+    person::{Person, PersonForm},
+  };
+  use serial_test::serial;
+
+  #[test]
+  #[serial]
+  fn test_community_languages() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "language_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "language_community".into(),
+      creator_id: inserted_person.id,
+      title: "nada".to_owned(),
+      description: None,
+      nsfw: false,
+      removed: None,
+      deleted: None,
+      updated: None,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+    let community_id = inserted_community.id;
+
+    let english = Language::read_from_code(&conn, "EN").unwrap();
+    let german = Language::read_from_code(&conn, "de").unwrap();
+    let undetermined = Language::read_from_id(&conn, UNDETERMINED_LANGUAGE_ID).unwrap();
+
+    // Without a restriction every language is allowed
+    let allowed_unrestricted =
+      Language::is_allowed_in_community(&conn, community_id, german.id).unwrap();
+    Language::update_community_languages(&conn, community_id, &[english.id, english.id]).unwrap();
+    let languages = Language::read_community_languages(&conn, community_id).unwrap();
+    let allowed_english =
+      Language::is_allowed_in_community(&conn, community_id, english.id).unwrap();
+    let allowed_german = Language::is_allowed_in_community(&conn, community_id, german.id).unwrap();
+    let allowed_undetermined =
+      Language::is_allowed_in_community(&conn, community_id, undetermined.id).unwrap();
+    // Replacing the languages with none lifts the restriction
+    Language::update_community_languages(&conn, community_id, &[]).unwrap();
+    let languages_cleared = Language::read_community_languages(&conn, community_id).unwrap();
+
+    Community::delete(&conn, community_id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!("en", english.code);
+    assert_eq!("und", undetermined.code);
+    assert!(allowed_unrestricted);
+    assert_eq!(vec![english.id], languages);
+    assert!(allowed_english);
+    assert!(!allowed_german);
+    assert!(allowed_undetermined);
+    assert!(languages_cleared.is_empty());
+  }
+}
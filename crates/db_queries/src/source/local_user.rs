@@ -1,4 +1,4 @@
-use crate::Crud;
+use crate::{Crud, EmailDigestFrequency};
 use bcrypt::{hash, DEFAULT_COST};
 use diesel::{dsl::*, result::Error, *};
 use lemmy_db_schema::{
@@ -37,6 +37,9 @@ mod safe_settings_type {
     show_avatars,
     send_notifications_to_email,
     matrix_user_id,
+    email_digest_frequency,
+    hide_last_active,
+    hide_profile_from_federation,
   );
 
   impl ToSafeSettings for LocalUser {
@@ -57,6 +60,9 @@ mod safe_settings_type {
         show_avatars,
         send_notifications_to_email,
         matrix_user_id,
+        email_digest_frequency,
+        hide_last_active,
+        hide_profile_from_federation,
       )
     }
   }
@@ -70,6 +76,21 @@ pub trait LocalUser_ {
     new_password: &str,
   ) -> Result<LocalUser, Error>;
   fn add_admin(conn: &PgConnection, local_user_id: i32, added: bool) -> Result<LocalUser, Error>;
+  /// The users with an hourly or daily email digest, whose last digest is at least that old
+  fn list_due_for_email_digest(conn: &PgConnection) -> Result<Vec<LocalUser>, Error>;
+  /// Notifications which were published up to `sent_until` won't be part of another digest
+  fn update_last_email_digest(
+    conn: &PgConnection,
+    local_user_id: i32,
+    sent_until: chrono::NaiveDateTime,
+  ) -> Result<LocalUser, Error>;
+  fn update_email_unsubscribe_token(
+    conn: &PgConnection,
+    local_user_id: i32,
+    token: &str,
+  ) -> Result<LocalUser, Error>;
+  /// Turns off all notification emails of the user with this unsubscribe token
+  fn unsubscribe_from_emails(conn: &PgConnection, token: &str) -> Result<LocalUser, Error>;
 }
 
 impl LocalUser_ for LocalUser {
@@ -99,9 +120,54 @@ impl LocalUser_ for LocalUser {
       .set(admin.eq(added))
       .get_result::<Self>(conn)
   }
+
+  fn list_due_for_email_digest(conn: &PgConnection) -> Result<Vec<Self>, Error> {
+    local_user
+      .filter(email.is_not_null())
+      .filter(
+        email_digest_frequency
+          .eq(EmailDigestFrequency::Hourly as i16)
+          .and(last_email_digest.le(now - 1.hours()))
+          .or(
+            email_digest_frequency
+              .eq(EmailDigestFrequency::Daily as i16)
+              .and(last_email_digest.le(now - 1.days())),
+          ),
+      )
+      .load::<Self>(conn)
+  }
+
+  fn update_last_email_digest(
+    conn: &PgConnection,
+    local_user_id: i32,
+    sent_until: chrono::NaiveDateTime,
+  ) -> Result<Self, Error> {
+    diesel::update(local_user.find(local_user_id))
+      .set(last_email_digest.eq(sent_until))
+      .get_result::<Self>(conn)
+  }
+
+  fn update_email_unsubscribe_token(
+    conn: &PgConnection,
+    local_user_id: i32,
+    token: &str,
+  ) -> Result<Self, Error> {
+    diesel::update(local_user.find(local_user_id))
+      .set(email_unsubscribe_token.eq(token))
+      .get_result::<Self>(conn)
+  }
+
+  fn unsubscribe_from_emails(conn: &PgConnection, token: &str) -> Result<Self, Error> {
+    diesel::update(local_user.filter(email_unsubscribe_token.eq(token)))
+      .set((
+        email_digest_frequency.eq(EmailDigestFrequency::Off as i16),
+        send_notifications_to_email.eq(false),
+      ))
+      .get_result::<Self>(conn)
+  }
 }
 
-impl Crud<LocalUserForm> for LocalUser {
+impl Crud<LocalUserForm, i32> for LocalUser {
   fn read(conn: &PgConnection, local_user_id: i32) -> Result<Self, Error> {
     local_user.find(local_user_id).first::<Self>(conn)
   }
@@ -119,3 +185,92 @@ impl Crud<LocalUserForm> for LocalUser {
       .get_result::<Self>(conn)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    establish_unpooled_connection,
+    source::local_user::LocalUser_,
+    Crud,
+    EmailDigestFrequency,
+  };
+  use lemmy_db_schema::{
+    naive_now,
+    source::{local_user::*, person::*},
+  };
+  use serial_test::serial;
+
+  #[test]
+  #[serial]
+  fn test_email_digest_and_unsubscribe() {
+    let conn = establish_unpooled_connection();
+
+    let person_form = PersonForm {
+      name: "digest_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: Some(true),
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let person = Person::create(&conn, &person_form).unwrap();
+    let local_user_form = LocalUserForm {
+      person_id: person.id,
+      password_encrypted: "pass".to_string(),
+      email: Some(Some("digest@example.com".into())),
+      matrix_user_id: None,
+      admin: None,
+      show_nsfw: None,
+      theme: None,
+      default_sort_type: None,
+      default_listing_type: None,
+      lang: None,
+      show_avatars: None,
+      send_notifications_to_email: Some(true),
+      email_digest_frequency: Some(EmailDigestFrequency::Hourly as i16),
+      hide_last_active: None,
+      hide_profile_from_federation: None,
+    };
+    let local_user = LocalUser::create(&conn, &local_user_form).unwrap();
+    let is_due = || {
+      LocalUser::list_due_for_email_digest(&conn)
+        .unwrap()
+        .iter()
+        .any(|u| u.id == local_user.id)
+    };
+
+    let two_hours_ago = naive_now() - chrono::Duration::hours(2);
+    LocalUser::update_last_email_digest(&conn, local_user.id, two_hours_ago).unwrap();
+    let due_after_two_hours = is_due();
+    LocalUser::update_last_email_digest(&conn, local_user.id, naive_now()).unwrap();
+    let due_after_sending = is_due();
+
+    LocalUser::update_email_unsubscribe_token(&conn, local_user.id, "digest_token").unwrap();
+    let wrong_token = LocalUser::unsubscribe_from_emails(&conn, "other_token");
+    let unsubscribed = LocalUser::unsubscribe_from_emails(&conn, "digest_token").unwrap();
+    LocalUser::update_last_email_digest(&conn, local_user.id, two_hours_ago).unwrap();
+    let due_after_unsubscribe = is_due();
+
+    Person::delete(&conn, person.id).unwrap();
+
+    assert!(due_after_two_hours);
+    assert!(!due_after_sending);
+    assert!(wrong_token.is_err());
+    assert_eq!(
+      EmailDigestFrequency::Off as i16,
+      unsubscribed.email_digest_frequency
+    );
+    assert!(!unsubscribed.send_notifications_to_email);
+    assert!(!due_after_unsubscribe);
+  }
+}
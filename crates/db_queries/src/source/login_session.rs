@@ -0,0 +1,227 @@
+use crate::Crud;
+use chrono::Duration;
+use diesel::{dsl::*, result::Error, *};
+use lemmy_db_schema::{
+  naive_now,
+  schema::login_session::dsl::*,
+  source::login_session::{LoginSession, LoginSessionForm},
+};
+
+/// How long `last_used` may be out of date, so that not every request writes to the database
+const LAST_USED_PRECISION_HOURS: i64 = 1;
+
+impl Crud<LoginSessionForm, i32> for LoginSession {
+  fn read(conn: &PgConnection, login_session_id: i32) -> Result<Self, Error> {
+    login_session.find(login_session_id).first::<Self>(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &LoginSessionForm) -> Result<Self, Error> {
+    insert_into(login_session)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    login_session_id: i32,
+    form: &LoginSessionForm,
+  ) -> Result<Self, Error> {
+    diesel::update(login_session.find(login_session_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, login_session_id: i32) -> Result<usize, Error> {
+    diesel::delete(login_session.find(login_session_id)).execute(conn)
+  }
+}
+
+pub trait LoginSession_ {
+  /// Reads the session if it belongs to the local user, and updates `last_used` if it is out of
+  /// date. Revoked sessions are deleted, so they aren't found.
+  fn read_and_mark_used(
+    conn: &PgConnection,
+    login_session_id: i32,
+    for_local_user_id: i32,
+  ) -> Result<LoginSession, Error>;
+  fn list_for_local_user(
+    conn: &PgConnection,
+    for_local_user_id: i32,
+  ) -> Result<Vec<LoginSession>, Error>;
+  /// Deletes the session, if it belongs to the local user
+  fn revoke(
+    conn: &PgConnection,
+    login_session_id: i32,
+    for_local_user_id: i32,
+  ) -> Result<usize, Error>;
+  /// Deletes all sessions of the local user, except `keep_session_id`
+  fn revoke_all(
+    conn: &PgConnection,
+    for_local_user_id: i32,
+    keep_session_id: Option<i32>,
+  ) -> Result<usize, Error>;
+}
+
+impl LoginSession_ for LoginSession {
+  fn read_and_mark_used(
+    conn: &PgConnection,
+    login_session_id: i32,
+    for_local_user_id: i32,
+  ) -> Result<LoginSession, Error> {
+    let session = login_session
+      .find(login_session_id)
+      .filter(local_user_id.eq(for_local_user_id))
+      .first::<Self>(conn)?;
+    if session.last_used < naive_now() - Duration::hours(LAST_USED_PRECISION_HOURS) {
+      diesel::update(login_session.find(login_session_id))
+        .set(last_used.eq(naive_now()))
+        .get_result::<Self>(conn)
+    } else {
+      Ok(session)
+    }
+  }
+
+  fn list_for_local_user(
+    conn: &PgConnection,
+    for_local_user_id: i32,
+  ) -> Result<Vec<LoginSession>, Error> {
+    login_session
+      .filter(local_user_id.eq(for_local_user_id))
+      .order_by(last_used.desc())
+      .load::<Self>(conn)
+  }
+
+  fn revoke(
+    conn: &PgConnection,
+    login_session_id: i32,
+    for_local_user_id: i32,
+  ) -> Result<usize, Error> {
+    diesel::delete(
+      login_session
+        .find(login_session_id)
+        .filter(local_user_id.eq(for_local_user_id)),
+    )
+    .execute(conn)
+  }
+
+  fn revoke_all(
+    conn: &PgConnection,
+    for_local_user_id: i32,
+    keep_session_id: Option<i32>,
+  ) -> Result<usize, Error> {
+    diesel::delete(
+      login_session
+        .filter(local_user_id.eq(for_local_user_id))
+        .filter(id.ne(keep_session_id.unwrap_or(-1))),
+    )
+    .execute(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{establish_unpooled_connection, source::login_session::LoginSession_, Crud};
+  use chrono::Duration;
+  use diesel::*;
+  use lemmy_db_schema::{
+    naive_now,
+    schema::login_session,
+    source::{
+      local_user::{LocalUser, LocalUserForm},
+      login_session::{LoginSession, LoginSessionForm},
+      person::*,
+    },
+  };
+  use serial_test::serial;
+
+  #[test]
+  #[serial]
+  fn test_login_session() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "login_session_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_local_user = LocalUserForm {
+      person_id: inserted_person.id,
+      password_encrypted: "pass".to_string(),
+      email: None,
+      matrix_user_id: None,
+      admin: None,
+      show_nsfw: None,
+      theme: None,
+      default_sort_type: None,
+      default_listing_type: None,
+      lang: None,
+      show_avatars: None,
+      send_notifications_to_email: None,
+      email_digest_frequency: None,
+      hide_last_active: None,
+      hide_profile_from_federation: None,
+    };
+
+    let inserted_local_user = LocalUser::create(&conn, &new_local_user).unwrap();
+
+    let form = LoginSessionForm {
+      local_user_id: inserted_local_user.id,
+      user_agent: Some("Firefox".into()),
+      ip: Some("10.0.0.0".into()),
+    };
+    let session = LoginSession::create(&conn, &form).unwrap();
+    let other_session = LoginSession::create(&conn, &form).unwrap();
+    let third_session = LoginSession::create(&conn, &form).unwrap();
+
+    // A recent use isn't written again
+    let read_session =
+      LoginSession::read_and_mark_used(&conn, session.id, inserted_local_user.id).unwrap();
+    let two_hours_ago = naive_now() - Duration::hours(2);
+    diesel::update(login_session::table.find(session.id))
+      .set(login_session::last_used.eq(two_hours_ago))
+      .execute(&conn)
+      .unwrap();
+    let marked_session =
+      LoginSession::read_and_mark_used(&conn, session.id, inserted_local_user.id).unwrap();
+    let read_other_user =
+      LoginSession::read_and_mark_used(&conn, session.id, inserted_local_user.id + 1);
+
+    let listed = LoginSession::list_for_local_user(&conn, inserted_local_user.id).unwrap();
+    let revoked = LoginSession::revoke(&conn, third_session.id, inserted_local_user.id).unwrap();
+    let revoked_all =
+      LoginSession::revoke_all(&conn, inserted_local_user.id, Some(session.id)).unwrap();
+    let read_revoked =
+      LoginSession::read_and_mark_used(&conn, other_session.id, inserted_local_user.id);
+    let remaining = LoginSession::list_for_local_user(&conn, inserted_local_user.id).unwrap();
+    let num_deleted = Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(session, read_session);
+    assert!(marked_session.last_used > two_hours_ago);
+    assert!(read_other_user.is_err());
+    assert_eq!(3, listed.len());
+    assert_eq!(1, revoked);
+    assert_eq!(1, revoked_all);
+    assert!(read_revoked.is_err());
+    assert_eq!(
+      vec![session.id],
+      remaining.iter().map(|s| s.id).collect::<Vec<_>>()
+    );
+    assert_eq!(1, num_deleted);
+  }
+}
@@ -1,13 +1,26 @@
 pub mod activity;
+pub mod api_token;
 pub mod comment;
 pub mod comment_report;
 pub mod community;
+pub mod community_federation_log;
+pub mod community_tag;
+pub mod instance;
+pub mod language;
 pub mod local_user;
+pub mod login_session;
 pub mod moderator;
 pub mod password_reset_request;
 pub mod person;
 pub mod person_mention;
+pub mod person_note;
+pub mod person_post_mention;
 pub mod post;
 pub mod post_report;
+pub mod post_view_log;
 pub mod private_message;
+pub mod received_activity;
+pub mod registration_question;
 pub mod site;
+pub mod spam_rule;
+pub mod task_lock;
@@ -2,7 +2,7 @@ use crate::Crud;
 use diesel::{dsl::*, result::Error, *};
 use lemmy_db_schema::source::moderator::*;
 
-impl Crud<ModRemovePostForm> for ModRemovePost {
+impl Crud<ModRemovePostForm, i32> for ModRemovePost {
   fn read(conn: &PgConnection, from_id: i32) -> Result<Self, Error> {
     use lemmy_db_schema::schema::mod_remove_post::dsl::*;
     mod_remove_post.find(from_id).first::<Self>(conn)
@@ -23,7 +23,7 @@ impl Crud<ModRemovePostForm> for ModRemovePost {
   }
 }
 
-impl Crud<ModLockPostForm> for ModLockPost {
+impl Crud<ModLockPostForm, i32> for ModLockPost {
   fn read(conn: &PgConnection, from_id: i32) -> Result<Self, Error> {
     use lemmy_db_schema::schema::mod_lock_post::dsl::*;
     mod_lock_post.find(from_id).first::<Self>(conn)
@@ -44,7 +44,49 @@ impl Crud<ModLockPostForm> for ModLockPost {
   }
 }
 
-impl Crud<ModStickyPostForm> for ModStickyPost {
+impl Crud<ModLockCommentForm, i32> for ModLockComment {
+  fn read(conn: &PgConnection, from_id: i32) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::mod_lock_comment::dsl::*;
+    mod_lock_comment.find(from_id).first::<Self>(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &ModLockCommentForm) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::mod_lock_comment::dsl::*;
+    insert_into(mod_lock_comment)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(conn: &PgConnection, from_id: i32, form: &ModLockCommentForm) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::mod_lock_comment::dsl::*;
+    diesel::update(mod_lock_comment.find(from_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl Crud<ModStickyCommentForm, i32> for ModStickyComment {
+  fn read(conn: &PgConnection, from_id: i32) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::mod_sticky_comment::dsl::*;
+    mod_sticky_comment.find(from_id).first::<Self>(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &ModStickyCommentForm) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::mod_sticky_comment::dsl::*;
+    insert_into(mod_sticky_comment)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(conn: &PgConnection, from_id: i32, form: &ModStickyCommentForm) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::mod_sticky_comment::dsl::*;
+    diesel::update(mod_sticky_comment.find(from_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl Crud<ModStickyPostForm, i32> for ModStickyPost {
   fn read(conn: &PgConnection, from_id: i32) -> Result<Self, Error> {
     use lemmy_db_schema::schema::mod_sticky_post::dsl::*;
     mod_sticky_post.find(from_id).first::<Self>(conn)
@@ -65,7 +107,28 @@ impl Crud<ModStickyPostForm> for ModStickyPost {
   }
 }
 
-impl Crud<ModRemoveCommentForm> for ModRemoveComment {
+impl Crud<ModFeaturePostForm, i32> for ModFeaturePost {
+  fn read(conn: &PgConnection, from_id: i32) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::mod_feature_post::dsl::*;
+    mod_feature_post.find(from_id).first::<Self>(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &ModFeaturePostForm) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::mod_feature_post::dsl::*;
+    insert_into(mod_feature_post)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(conn: &PgConnection, from_id: i32, form: &ModFeaturePostForm) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::mod_feature_post::dsl::*;
+    diesel::update(mod_feature_post.find(from_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl Crud<ModRemoveCommentForm, i32> for ModRemoveComment {
   fn read(conn: &PgConnection, from_id: i32) -> Result<Self, Error> {
     use lemmy_db_schema::schema::mod_remove_comment::dsl::*;
     mod_remove_comment.find(from_id).first::<Self>(conn)
@@ -86,7 +149,7 @@ impl Crud<ModRemoveCommentForm> for ModRemoveComment {
   }
 }
 
-impl Crud<ModRemoveCommunityForm> for ModRemoveCommunity {
+impl Crud<ModRemoveCommunityForm, i32> for ModRemoveCommunity {
   fn read(conn: &PgConnection, from_id: i32) -> Result<Self, Error> {
     use lemmy_db_schema::schema::mod_remove_community::dsl::*;
     mod_remove_community.find(from_id).first::<Self>(conn)
@@ -111,7 +174,53 @@ impl Crud<ModRemoveCommunityForm> for ModRemoveCommunity {
   }
 }
 
-impl Crud<ModBanFromCommunityForm> for ModBanFromCommunity {
+impl Crud<ModExemptCommunityForm, i32> for ModExemptCommunity {
+  fn read(conn: &PgConnection, from_id: i32) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::mod_exempt_community::dsl::*;
+    mod_exempt_community.find(from_id).first::<Self>(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &ModExemptCommunityForm) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::mod_exempt_community::dsl::*;
+    insert_into(mod_exempt_community)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    from_id: i32,
+    form: &ModExemptCommunityForm,
+  ) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::mod_exempt_community::dsl::*;
+    diesel::update(mod_exempt_community.find(from_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl Crud<ModEditCommunityForm, i32> for ModEditCommunity {
+  fn read(conn: &PgConnection, from_id: i32) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::mod_edit_community::dsl::*;
+    mod_edit_community.find(from_id).first::<Self>(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &ModEditCommunityForm) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::mod_edit_community::dsl::*;
+    insert_into(mod_edit_community)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(conn: &PgConnection, from_id: i32, form: &ModEditCommunityForm) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::mod_edit_community::dsl::*;
+    diesel::update(mod_edit_community.find(from_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl Crud<ModBanFromCommunityForm, i32> for ModBanFromCommunity {
   fn read(conn: &PgConnection, from_id: i32) -> Result<Self, Error> {
     use lemmy_db_schema::schema::mod_ban_from_community::dsl::*;
     mod_ban_from_community.find(from_id).first::<Self>(conn)
@@ -136,7 +245,7 @@ impl Crud<ModBanFromCommunityForm> for ModBanFromCommunity {
   }
 }
 
-impl Crud<ModBanForm> for ModBan {
+impl Crud<ModBanForm, i32> for ModBan {
   fn read(conn: &PgConnection, from_id: i32) -> Result<Self, Error> {
     use lemmy_db_schema::schema::mod_ban::dsl::*;
     mod_ban.find(from_id).first::<Self>(conn)
@@ -155,7 +264,7 @@ impl Crud<ModBanForm> for ModBan {
   }
 }
 
-impl Crud<ModAddCommunityForm> for ModAddCommunity {
+impl Crud<ModAddCommunityForm, i32> for ModAddCommunity {
   fn read(conn: &PgConnection, from_id: i32) -> Result<Self, Error> {
     use lemmy_db_schema::schema::mod_add_community::dsl::*;
     mod_add_community.find(from_id).first::<Self>(conn)
@@ -176,7 +285,7 @@ impl Crud<ModAddCommunityForm> for ModAddCommunity {
   }
 }
 
-impl Crud<ModAddForm> for ModAdd {
+impl Crud<ModAddForm, i32> for ModAdd {
   fn read(conn: &PgConnection, from_id: i32) -> Result<Self, Error> {
     use lemmy_db_schema::schema::mod_add::dsl::*;
     mod_add.find(from_id).first::<Self>(conn)
@@ -195,6 +304,27 @@ impl Crud<ModAddForm> for ModAdd {
   }
 }
 
+impl Crud<AdminAutoRemoveForm, i32> for AdminAutoRemove {
+  fn read(conn: &PgConnection, from_id: i32) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::admin_auto_remove::dsl::*;
+    admin_auto_remove.find(from_id).first::<Self>(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &AdminAutoRemoveForm) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::admin_auto_remove::dsl::*;
+    insert_into(admin_auto_remove)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(conn: &PgConnection, from_id: i32, form: &AdminAutoRemoveForm) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::admin_auto_remove::dsl::*;
+    diesel::update(admin_auto_remove.find(from_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use crate::{establish_unpooled_connection, Crud};
@@ -269,6 +399,13 @@ mod tests {
       followers_url: None,
       inbox_url: None,
       shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
     };
 
     let inserted_community = Community::create(&conn, &new_community).unwrap();
@@ -366,6 +503,23 @@ mod tests {
       when_: inserted_mod_sticky_post.when_,
     };
 
+    // feature post
+
+    let mod_feature_post_form = ModFeaturePostForm {
+      mod_person_id: inserted_mod.id,
+      post_id: inserted_post.id,
+      featured: None,
+    };
+    let inserted_mod_feature_post = ModFeaturePost::create(&conn, &mod_feature_post_form).unwrap();
+    let read_mod_feature_post = ModFeaturePost::read(&conn, inserted_mod_feature_post.id).unwrap();
+    let expected_mod_feature_post = ModFeaturePost {
+      id: inserted_mod_feature_post.id,
+      post_id: inserted_post.id,
+      mod_person_id: inserted_mod.id,
+      featured: Some(true),
+      when_: inserted_mod_feature_post.when_,
+    };
+
     // comment
 
     let mod_remove_comment_form = ModRemoveCommentForm {
@@ -387,6 +541,40 @@ mod tests {
       when_: inserted_mod_remove_comment.when_,
     };
 
+    let mod_lock_comment_form = ModLockCommentForm {
+      mod_person_id: inserted_mod.id,
+      comment_id: inserted_comment.id,
+      locked: None,
+      reason: Some("off topic".into()),
+    };
+    let inserted_mod_lock_comment = ModLockComment::create(&conn, &mod_lock_comment_form).unwrap();
+    let read_mod_lock_comment = ModLockComment::read(&conn, inserted_mod_lock_comment.id).unwrap();
+    let expected_mod_lock_comment = ModLockComment {
+      id: inserted_mod_lock_comment.id,
+      comment_id: inserted_comment.id,
+      mod_person_id: inserted_mod.id,
+      locked: Some(true),
+      reason: Some("off topic".into()),
+      when_: inserted_mod_lock_comment.when_,
+    };
+
+    let mod_sticky_comment_form = ModStickyCommentForm {
+      mod_person_id: inserted_mod.id,
+      comment_id: inserted_comment.id,
+      stickied: None,
+    };
+    let inserted_mod_sticky_comment =
+      ModStickyComment::create(&conn, &mod_sticky_comment_form).unwrap();
+    let read_mod_sticky_comment =
+      ModStickyComment::read(&conn, inserted_mod_sticky_comment.id).unwrap();
+    let expected_mod_sticky_comment = ModStickyComment {
+      id: inserted_mod_sticky_comment.id,
+      comment_id: inserted_comment.id,
+      mod_person_id: inserted_mod.id,
+      stickied: Some(true),
+      when_: inserted_mod_sticky_comment.when_,
+    };
+
     // community
 
     let mod_remove_community_form = ModRemoveCommunityForm {
@@ -395,6 +583,7 @@ mod tests {
       reason: None,
       removed: None,
       expires: None,
+      remove_content: None,
     };
     let inserted_mod_remove_community =
       ModRemoveCommunity::create(&conn, &mod_remove_community_form).unwrap();
@@ -408,6 +597,43 @@ mod tests {
       removed: Some(true),
       expires: None,
       when_: inserted_mod_remove_community.when_,
+      remove_content: false,
+    };
+
+    // exempt community
+
+    let mod_exempt_community_form = ModExemptCommunityForm {
+      mod_person_id: inserted_mod.id,
+      community_id: inserted_community.id,
+      exempt: None,
+    };
+    let inserted_mod_exempt_community =
+      ModExemptCommunity::create(&conn, &mod_exempt_community_form).unwrap();
+    let read_mod_exempt_community =
+      ModExemptCommunity::read(&conn, inserted_mod_exempt_community.id).unwrap();
+    let expected_mod_exempt_community = ModExemptCommunity {
+      id: inserted_mod_exempt_community.id,
+      community_id: inserted_community.id,
+      mod_person_id: inserted_mod.id,
+      exempt: Some(true),
+      when_: inserted_mod_exempt_community.when_,
+    };
+
+    let mod_edit_community_form = ModEditCommunityForm {
+      mod_person_id: inserted_mod.id,
+      community_id: inserted_community.id,
+      changed_fields: vec!["title".into(), "nsfw".into()],
+    };
+    let inserted_mod_edit_community =
+      ModEditCommunity::create(&conn, &mod_edit_community_form).unwrap();
+    let read_mod_edit_community =
+      ModEditCommunity::read(&conn, inserted_mod_edit_community.id).unwrap();
+    let expected_mod_edit_community = ModEditCommunity {
+      id: inserted_mod_edit_community.id,
+      community_id: inserted_community.id,
+      mod_person_id: inserted_mod.id,
+      changed_fields: vec!["title".into(), "nsfw".into()],
+      when_: inserted_mod_edit_community.when_,
     };
 
     // ban from community
@@ -503,8 +729,13 @@ mod tests {
     assert_eq!(expected_mod_remove_post, read_mod_remove_post);
     assert_eq!(expected_mod_lock_post, read_mod_lock_post);
     assert_eq!(expected_mod_sticky_post, read_mod_sticky_post);
+    assert_eq!(expected_mod_feature_post, read_mod_feature_post);
     assert_eq!(expected_mod_remove_comment, read_mod_remove_comment);
+    assert_eq!(expected_mod_lock_comment, read_mod_lock_comment);
+    assert_eq!(expected_mod_sticky_comment, read_mod_sticky_comment);
     assert_eq!(expected_mod_remove_community, read_mod_remove_community);
+    assert_eq!(expected_mod_exempt_community, read_mod_exempt_community);
+    assert_eq!(expected_mod_edit_community, read_mod_edit_community);
     assert_eq!(expected_mod_ban_from_community, read_mod_ban_from_community);
     assert_eq!(expected_mod_ban, read_mod_ban);
     assert_eq!(expected_mod_add_community, read_mod_add_community);
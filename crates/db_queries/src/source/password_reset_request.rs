@@ -3,7 +3,7 @@ use diesel::{dsl::*, result::Error, PgConnection, *};
 use lemmy_db_schema::{schema::password_reset_request::dsl::*, source::password_reset_request::*};
 use sha2::{Digest, Sha256};
 
-impl Crud<PasswordResetRequestForm> for PasswordResetRequest {
+impl Crud<PasswordResetRequestForm, i32> for PasswordResetRequest {
   fn read(conn: &PgConnection, password_reset_request_id: i32) -> Result<Self, Error> {
     password_reset_request
       .find(password_reset_request_id)
@@ -123,6 +123,9 @@ mod tests {
       lang: None,
       show_avatars: None,
       send_notifications_to_email: None,
+      email_digest_frequency: None,
+      hide_last_active: None,
+      hide_profile_from_federation: None,
     };
 
     let inserted_local_user = LocalUser::create(&conn, &new_local_user).unwrap();
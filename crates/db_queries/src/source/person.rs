@@ -1,10 +1,21 @@
-use crate::{ApubObject, Crud};
-use diesel::{dsl::*, result::Error, *};
+use crate::{aggregates::person_aggregates::PersonAggregates, functions::lower, ApubObject, Crud};
+use diesel::{
+  dsl::*,
+  result::Error,
+  sql_types::{BigInt, Bool, Integer},
+  *,
+};
 use lemmy_db_schema::{
   naive_now,
   schema::person::dsl::*,
-  source::person::{Person, PersonForm},
+  source::{
+    community::Community,
+    person::{Person, PersonForm},
+  },
+  CommentId,
   DbUrl,
+  PersonId,
+  PostId,
 };
 
 mod safe_type {
@@ -139,20 +150,20 @@ mod safe_type_alias_2 {
   }
 }
 
-impl Crud<PersonForm> for Person {
-  fn read(conn: &PgConnection, person_id: i32) -> Result<Self, Error> {
+impl Crud<PersonForm, PersonId> for Person {
+  fn read(conn: &PgConnection, person_id: PersonId) -> Result<Self, Error> {
     person
       .filter(deleted.eq(false))
       .find(person_id)
       .first::<Self>(conn)
   }
-  fn delete(conn: &PgConnection, person_id: i32) -> Result<usize, Error> {
+  fn delete(conn: &PgConnection, person_id: PersonId) -> Result<usize, Error> {
     diesel::delete(person.find(person_id)).execute(conn)
   }
   fn create(conn: &PgConnection, form: &PersonForm) -> Result<Self, Error> {
     insert_into(person).values(form).get_result::<Self>(conn)
   }
-  fn update(conn: &PgConnection, person_id: i32, form: &PersonForm) -> Result<Self, Error> {
+  fn update(conn: &PgConnection, person_id: PersonId, form: &PersonForm) -> Result<Self, Error> {
     diesel::update(person.find(person_id))
       .set(form)
       .get_result::<Self>(conn)
@@ -162,10 +173,24 @@ impl Crud<PersonForm> for Person {
 impl ApubObject<PersonForm> for Person {
   fn read_from_apub_id(conn: &PgConnection, object_id: &DbUrl) -> Result<Self, Error> {
     use lemmy_db_schema::schema::person::dsl::*;
-    person
+    let exact = person
       .filter(deleted.eq(false))
       .filter(actor_id.eq(object_id))
       .first::<Self>(conn)
+      .optional()?;
+    if let Some(exact) = exact {
+      return Ok(exact);
+    }
+
+    // Older rows may have been stored with a different host case. The host is lowercased when the
+    // column is parsed into a url, so comparing the parsed values ignores only the host case.
+    person
+      .filter(deleted.eq(false))
+      .filter(lower(actor_id).eq(object_id.to_string().to_lowercase()))
+      .load::<Self>(conn)?
+      .into_iter()
+      .find(|p| &p.actor_id == object_id)
+      .ok_or(Error::NotFound)
   }
 
   fn upsert(conn: &PgConnection, person_form: &PersonForm) -> Result<Person, Error> {
@@ -178,15 +203,135 @@ impl ApubObject<PersonForm> for Person {
   }
 }
 
+/// How long `last_activity_at` may be out of date, so that not every request writes to the database
+pub const LAST_ACTIVITY_PRECISION_MINUTES: i64 = 15;
+
+/// Whether a `last_activity_at` value is older than its precision, and should be updated
+pub fn is_last_activity_outdated(last_activity: Option<chrono::NaiveDateTime>) -> bool {
+  match last_activity {
+    Some(last_activity) => {
+      last_activity < naive_now() - chrono::Duration::minutes(LAST_ACTIVITY_PRECISION_MINUTES)
+    }
+    None => true,
+  }
+}
+
 pub trait Person_ {
-  fn ban_person(conn: &PgConnection, person_id: i32, ban: bool) -> Result<Person, Error>;
+  fn ban_person(conn: &PgConnection, person_id: PersonId, ban: bool) -> Result<Person, Error>;
   fn find_by_name(conn: &PgConnection, name: &str) -> Result<Person, Error>;
-  fn mark_as_updated(conn: &PgConnection, person_id: i32) -> Result<Person, Error>;
-  fn delete_account(conn: &PgConnection, person_id: i32) -> Result<Person, Error>;
+  /// Reads the persons with the given ids in one query, in no particular order
+  fn read_many(conn: &PgConnection, person_ids: Vec<PersonId>) -> Result<Vec<Person>, Error>;
+  /// Whether a local person, including deleted ones, already uses the name in any letter case
+  fn local_name_exists(conn: &PgConnection, name: &str) -> Result<bool, Error>;
+  fn mark_as_updated(conn: &PgConnection, person_id: PersonId) -> Result<Person, Error>;
+  /// Sets `last_activity_at` to now, if it is out of date. Returns the number of updated rows.
+  fn mark_active(conn: &PgConnection, person_id: PersonId) -> Result<usize, Error>;
+  /// Like `mark_active`, for a remote person who sent an activity
+  fn mark_active_by_apub_id(conn: &PgConnection, person_actor_id: &DbUrl) -> Result<usize, Error>;
+  fn delete_account(conn: &PgConnection, person_id: PersonId) -> Result<Person, Error>;
+  fn remote_communities_participated_in(
+    conn: &PgConnection,
+    person_id: PersonId,
+  ) -> Result<Vec<Community>, Error>;
+  fn merge(
+    conn: &PgConnection,
+    keep_person_id: PersonId,
+    merge_person_id: PersonId,
+  ) -> Result<Person, Error>;
+  /// The number of posts and comments in the outbox of the person
+  fn count_outbox_items(conn: &PgConnection, person_id: PersonId) -> Result<i64, Error>;
+  /// A page of the posts and comments in the outbox of the person, newest first
+  fn list_outbox_items(
+    conn: &PgConnection,
+    person_id: PersonId,
+    limit: i64,
+    offset: i64,
+  ) -> Result<Vec<OutboxItemId>, Error>;
+}
+
+/// A post or comment in the outbox of a person
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum OutboxItemId {
+  Post(PostId),
+  Comment(CommentId),
 }
 
+/// The posts and comments of person `$1` which other instances can see. Posts awaiting approval
+/// aren't federated yet.
+const OUTBOX_ITEMS: &str = "select true as is_post, id, published from post
+  where creator_id = $1 and not removed and not deleted and (approved or not local)
+  union all
+  select false as is_post, id, published from comment
+  where creator_id = $1 and not removed and not deleted";
+
+#[derive(QueryableByName)]
+struct OutboxItemRow {
+  #[sql_type = "Bool"]
+  is_post: bool,
+  #[sql_type = "Integer"]
+  id: i32,
+}
+
+#[derive(QueryableByName)]
+struct OutboxCount {
+  #[sql_type = "BigInt"]
+  count: i64,
+}
+
+/// Columns referencing a person which are part of a unique constraint together with the second
+/// column, as (table, person column, other column).
+const UNIQUE_PERSON_REFERENCES: &[(&str, &str, &str)] = &[
+  ("post_like", "person_id", "post_id"),
+  ("post_read", "person_id", "post_id"),
+  ("post_saved", "person_id", "post_id"),
+  ("person_pinned_post", "person_id", "post_id"),
+  ("post_report", "creator_id", "post_id"),
+  ("comment_like", "person_id", "comment_id"),
+  ("comment_saved", "person_id", "comment_id"),
+  ("comment_report", "creator_id", "comment_id"),
+  ("community_follower", "person_id", "community_id"),
+  ("community_moderator", "person_id", "community_id"),
+  ("community_person_ban", "person_id", "community_id"),
+  ("community_last_viewed", "person_id", "community_id"),
+  ("community_favorite", "person_id", "community_id"),
+  ("person_mention", "recipient_id", "comment_id"),
+  ("person_post_mention", "recipient_id", "post_id"),
+  ("person_note", "creator_person_id", "target_person_id"),
+  ("person_note", "target_person_id", "creator_person_id"),
+];
+
+/// Columns referencing a person without a unique constraint, as (table, person column).
+const PERSON_REFERENCES: &[(&str, &str)] = &[
+  ("post", "creator_id"),
+  ("comment", "creator_id"),
+  ("community", "creator_id"),
+  ("site", "creator_id"),
+  ("private_message", "creator_id"),
+  ("private_message", "recipient_id"),
+  ("post_report", "resolver_id"),
+  ("comment_report", "resolver_id"),
+  ("mod_add", "mod_person_id"),
+  ("mod_add", "other_person_id"),
+  ("mod_add_community", "mod_person_id"),
+  ("mod_add_community", "other_person_id"),
+  ("mod_ban", "mod_person_id"),
+  ("mod_ban", "other_person_id"),
+  ("mod_ban_from_community", "mod_person_id"),
+  ("mod_ban_from_community", "other_person_id"),
+  ("mod_edit_community", "mod_person_id"),
+  ("mod_exempt_community", "mod_person_id"),
+  ("mod_feature_post", "mod_person_id"),
+  ("mod_lock_comment", "mod_person_id"),
+  ("mod_lock_post", "mod_person_id"),
+  ("mod_remove_comment", "mod_person_id"),
+  ("mod_remove_community", "mod_person_id"),
+  ("mod_remove_post", "mod_person_id"),
+  ("mod_sticky_comment", "mod_person_id"),
+  ("mod_sticky_post", "mod_person_id"),
+];
+
 impl Person_ for Person {
-  fn ban_person(conn: &PgConnection, person_id: i32, ban: bool) -> Result<Self, Error> {
+  fn ban_person(conn: &PgConnection, person_id: PersonId, ban: bool) -> Result<Self, Error> {
     diesel::update(person.find(person_id))
       .set(banned.eq(ban))
       .get_result::<Self>(conn)
@@ -200,13 +345,56 @@ impl Person_ for Person {
       .first::<Person>(conn)
   }
 
-  fn mark_as_updated(conn: &PgConnection, person_id: i32) -> Result<Person, Error> {
+  fn read_many(conn: &PgConnection, person_ids: Vec<PersonId>) -> Result<Vec<Self>, Error> {
+    person.filter(id.eq_any(person_ids)).load::<Self>(conn)
+  }
+
+  fn local_name_exists(conn: &PgConnection, from_name: &str) -> Result<bool, Error> {
+    select(exists(
+      person
+        .filter(local.eq(true))
+        .filter(lower(name).eq(from_name.to_lowercase())),
+    ))
+    .get_result(conn)
+  }
+
+  fn mark_as_updated(conn: &PgConnection, person_id: PersonId) -> Result<Person, Error> {
     diesel::update(person.find(person_id))
       .set((last_refreshed_at.eq(naive_now()),))
       .get_result::<Self>(conn)
   }
 
-  fn delete_account(conn: &PgConnection, person_id: i32) -> Result<Person, Error> {
+  fn mark_active(conn: &PgConnection, person_id: PersonId) -> Result<usize, Error> {
+    let last_activity = person
+      .find(person_id)
+      .select(last_activity_at)
+      .first::<Option<chrono::NaiveDateTime>>(conn)?;
+    if !is_last_activity_outdated(last_activity) {
+      return Ok(0);
+    }
+    diesel::update(person.find(person_id))
+      .set(last_activity_at.eq(naive_now()))
+      .execute(conn)
+  }
+
+  fn mark_active_by_apub_id(conn: &PgConnection, person_actor_id: &DbUrl) -> Result<usize, Error> {
+    // Activities of communities and unknown actors are ignored
+    let found = person
+      .filter(actor_id.eq(person_actor_id))
+      .select((id, last_activity_at))
+      .first::<(PersonId, Option<chrono::NaiveDateTime>)>(conn)
+      .optional()?;
+    match found {
+      Some((person_id, last_activity)) if is_last_activity_outdated(last_activity) => {
+        diesel::update(person.find(person_id))
+          .set(last_activity_at.eq(naive_now()))
+          .execute(conn)
+      }
+      _ => Ok(0),
+    }
+  }
+
+  fn delete_account(conn: &PgConnection, person_id: PersonId) -> Result<Person, Error> {
     use lemmy_db_schema::schema::local_user;
 
     // Set the local user info to none
@@ -226,13 +414,153 @@ impl Person_ for Person {
       ))
       .get_result::<Self>(conn)
   }
+
+  /// Remote communities which the person follows, or has posted or commented in.
+  fn remote_communities_participated_in(
+    conn: &PgConnection,
+    person_id: PersonId,
+  ) -> Result<Vec<Community>, Error> {
+    use lemmy_db_schema::schema::{comment, community, community_follower, post};
+
+    let followed = community_follower::table
+      .filter(community_follower::person_id.eq(person_id))
+      .select(community_follower::community_id);
+    let posted = post::table
+      .filter(post::creator_id.eq(person_id))
+      .select(post::community_id);
+    let commented = comment::table
+      .inner_join(post::table)
+      .filter(comment::creator_id.eq(person_id))
+      .select(post::community_id);
+
+    community::table
+      .filter(community::local.eq(false))
+      .filter(
+        community::id
+          .eq_any(followed)
+          .or(community::id.eq_any(posted))
+          .or(community::id.eq_any(commented)),
+      )
+      .load::<Community>(conn)
+  }
+
+  /// Moves everything which references `merge_person_id` over to `keep_person_id`, then deletes the
+  /// merged person. Where both have a row for the same thing (eg a vote on the same post), the row
+  /// of the merged person is dropped. Should be run inside a transaction.
+  fn merge(
+    conn: &PgConnection,
+    keep_person_id: PersonId,
+    merge_person_id: PersonId,
+  ) -> Result<Person, Error> {
+    // The notes of the two about each other would become notes about oneself
+    sql_query(
+      "delete from person_note
+      where creator_person_id in ($1, $2) and target_person_id in ($1, $2)",
+    )
+    .bind::<Integer, _>(keep_person_id.0)
+    .bind::<Integer, _>(merge_person_id.0)
+    .execute(conn)?;
+    for (table, column, other_column) in UNIQUE_PERSON_REFERENCES {
+      sql_query(format!(
+        "delete from {table} m using {table} k
+        where m.{column} = $2 and k.{column} = $1 and m.{other} = k.{other}",
+        table = table,
+        column = column,
+        other = other_column
+      ))
+      .bind::<Integer, _>(keep_person_id.0)
+      .bind::<Integer, _>(merge_person_id.0)
+      .execute(conn)?;
+    }
+    sql_query(
+      "delete from person_ban
+      where person_id = $2 and exists (select 1 from person_ban where person_id = $1)",
+    )
+    .bind::<Integer, _>(keep_person_id.0)
+    .bind::<Integer, _>(merge_person_id.0)
+    .execute(conn)?;
+
+    let references = UNIQUE_PERSON_REFERENCES
+      .iter()
+      .map(|(table, column, _)| (*table, *column))
+      .chain(PERSON_REFERENCES.iter().cloned())
+      .chain(std::iter::once(("person_ban", "person_id")));
+    for (table, column) in references {
+      sql_query(format!(
+        "update {table} set {column} = $1 where {column} = $2",
+        table = table,
+        column = column
+      ))
+      .bind::<Integer, _>(keep_person_id.0)
+      .bind::<Integer, _>(merge_person_id.0)
+      .execute(conn)?;
+    }
+
+    diesel::delete(person.find(merge_person_id)).execute(conn)?;
+    PersonAggregates::recalculate(conn, keep_person_id)?;
+    person.find(keep_person_id).first::<Self>(conn)
+  }
+
+  fn count_outbox_items(conn: &PgConnection, person_id: PersonId) -> Result<i64, Error> {
+    let outbox_count = sql_query(format!(
+      "select count(*) as count from ({}) items",
+      OUTBOX_ITEMS
+    ))
+    .bind::<Integer, _>(person_id.0)
+    .get_result::<OutboxCount>(conn)?;
+    Ok(outbox_count.count)
+  }
+
+  fn list_outbox_items(
+    conn: &PgConnection,
+    person_id: PersonId,
+    limit: i64,
+    offset: i64,
+  ) -> Result<Vec<OutboxItemId>, Error> {
+    let rows = sql_query(format!(
+      "select is_post, id from ({}) items
+      order by published desc, is_post, id desc
+      limit $2 offset $3",
+      OUTBOX_ITEMS
+    ))
+    .bind::<Integer, _>(person_id.0)
+    .bind::<BigInt, _>(limit)
+    .bind::<BigInt, _>(offset)
+    .load::<OutboxItemRow>(conn)?;
+    Ok(
+      rows
+        .into_iter()
+        .map(|row| {
+          if row.is_post {
+            OutboxItemId::Post(PostId(row.id))
+          } else {
+            OutboxItemId::Comment(CommentId(row.id))
+          }
+        })
+        .collect(),
+    )
+  }
 }
 
 #[cfg(test)]
 mod tests {
-  use crate::{establish_unpooled_connection, source::person::*};
+  use crate::{
+    establish_unpooled_connection,
+    source::{person::*, person_note::PersonNote_, post::Post_},
+    Followable,
+    Likeable,
+  };
+  use chrono::NaiveDate;
+  use lemmy_db_schema::source::{
+    comment::{Comment, CommentForm},
+    community::{CommunityFollower, CommunityFollowerForm, CommunityForm},
+    person_note::{PersonNote, PersonNoteForm},
+    post::{Post, PostForm, PostLike, PostLikeForm},
+  };
+  use serial_test::serial;
 
   #[test]
+  #[serial]
   fn test_crud() {
     let conn = establish_unpooled_connection();
 
@@ -275,15 +603,322 @@ mod tests {
       last_refreshed_at: inserted_person.published,
       inbox_url: inserted_person.inbox_url.to_owned(),
       shared_inbox_url: None,
+      last_activity_at: None,
     };
 
     let read_person = Person::read(&conn, inserted_person.id).unwrap();
     let updated_person = Person::update(&conn, inserted_person.id, &new_person).unwrap();
+    let name_exists = Person::local_name_exists(&conn, "HoLLy").unwrap();
+    let similar_name_exists = Person::local_name_exists(&conn, "h_lly").unwrap();
+    let first_activity = Person::mark_active(&conn, inserted_person.id).unwrap();
+    // Another activity right after is within the precision, so nothing is written
+    let second_activity = Person::mark_active_by_apub_id(&conn, &inserted_person.actor_id).unwrap();
+    let active_person = Person::read(&conn, inserted_person.id).unwrap();
     let num_deleted = Person::delete(&conn, inserted_person.id).unwrap();
 
     assert_eq!(expected_person, read_person);
     assert_eq!(expected_person, inserted_person);
     assert_eq!(expected_person, updated_person);
+    assert!(name_exists);
+    assert!(!similar_name_exists);
+    assert_eq!(1, first_activity);
+    assert_eq!(0, second_activity);
+    assert!(active_person.last_activity_at.is_some());
     assert_eq!(1, num_deleted);
   }
+
+  #[test]
+  #[serial]
+  fn test_merge() {
+    let conn = establish_unpooled_connection();
+
+    let keep_person = Person::create(
+      &conn,
+      &PersonForm {
+        name: "merge_keep".into(),
+        preferred_username: None,
+        avatar: None,
+        banner: None,
+        banned: None,
+        deleted: None,
+        published: None,
+        updated: None,
+        actor_id: None,
+        bio: None,
+        local: None,
+        private_key: None,
+        public_key: None,
+        last_refreshed_at: None,
+        inbox_url: None,
+        shared_inbox_url: None,
+      },
+    )
+    .unwrap();
+    let merge_person = Person::create(
+      &conn,
+      &PersonForm {
+        name: "merge_duplicate".into(),
+        preferred_username: None,
+        avatar: None,
+        banner: None,
+        banned: None,
+        deleted: None,
+        published: None,
+        updated: None,
+        actor_id: None,
+        bio: None,
+        local: None,
+        private_key: None,
+        public_key: None,
+        last_refreshed_at: None,
+        inbox_url: None,
+        shared_inbox_url: None,
+      },
+    )
+    .unwrap();
+    let noted_person = Person::create(
+      &conn,
+      &PersonForm {
+        name: "merge_noted".into(),
+        preferred_username: None,
+        avatar: None,
+        banner: None,
+        banned: None,
+        deleted: None,
+        published: None,
+        updated: None,
+        actor_id: None,
+        bio: None,
+        local: None,
+        private_key: None,
+        public_key: None,
+        last_refreshed_at: None,
+        inbox_url: None,
+        shared_inbox_url: None,
+      },
+    )
+    .unwrap();
+
+    let new_community = CommunityForm {
+      name: "test_merge_community".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: merge_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let new_post = PostForm {
+      name: "A test post".into(),
+      creator_id: merge_person.id,
+      url: None,
+      body: None,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      updated: None,
+      nsfw: false,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      thumbnail_url: None,
+      ap_id: None,
+      local: true,
+      published: None,
+    };
+    let inserted_post = Post::create(&conn, &new_post).unwrap();
+
+    // Both voted on the post and follow the community, only one of each may remain
+    for person_id in &[keep_person.id, merge_person.id] {
+      let like_form = PostLikeForm {
+        post_id: inserted_post.id,
+        person_id: *person_id,
+        score: 1,
+      };
+      PostLike::like(&conn, &like_form).unwrap();
+      let follower_form = CommunityFollowerForm {
+        community_id: inserted_community.id,
+        person_id: *person_id,
+        pending: false,
+      };
+      CommunityFollower::follow(&conn, &follower_form).unwrap();
+    }
+
+    // Both have a note about the third person, and a note about each other
+    let note_forms = [
+      (keep_person.id, noted_person.id, "kept"),
+      (merge_person.id, noted_person.id, "dropped"),
+      (keep_person.id, merge_person.id, "duplicate"),
+      (merge_person.id, keep_person.id, "duplicate"),
+    ];
+    for (creator_person_id, target_person_id, note) in &note_forms {
+      let note_form = PersonNoteForm {
+        creator_person_id: *creator_person_id,
+        target_person_id: *target_person_id,
+        note: (*note).into(),
+        color: None,
+        updated: None,
+      };
+      PersonNote::set(&conn, &note_form).unwrap();
+    }
+
+    let merged = Person::merge(&conn, keep_person.id, merge_person.id).unwrap();
+
+    let read_post = Post::read(&conn, inserted_post.id).unwrap();
+    let read_community = Community::read(&conn, inserted_community.id).unwrap();
+    let num_likes = PostLike::remove(&conn, keep_person.id, inserted_post.id).unwrap();
+    let merge_person_gone = Person::read(&conn, merge_person.id).is_err();
+    let read_note = PersonNote::read(&conn, keep_person.id, noted_person.id).unwrap();
+    let self_note = PersonNote::read(&conn, keep_person.id, keep_person.id);
+    Post::delete(&conn, inserted_post.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, keep_person.id).unwrap();
+    Person::delete(&conn, noted_person.id).unwrap();
+
+    assert_eq!(keep_person.id, merged.id);
+    assert_eq!(keep_person.id, read_post.creator_id);
+    assert_eq!(keep_person.id, read_community.creator_id);
+    assert_eq!(1, num_likes);
+    assert!(merge_person_gone);
+    assert_eq!("kept", read_note.note);
+    assert!(matches!(self_note, Err(diesel::NotFound)));
+  }
+
+  #[test]
+  #[serial]
+  fn test_outbox_items() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "outbox_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "test_outbox_community".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let time = |hour: u32| NaiveDate::from_ymd(2021, 4, 30).and_hms(hour, 0, 0);
+    let post_form = |name: &str, hour: u32| PostForm {
+      name: name.into(),
+      creator_id: inserted_person.id,
+      url: None,
+      body: None,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      updated: None,
+      nsfw: false,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      thumbnail_url: None,
+      ap_id: None,
+      local: true,
+      published: Some(time(hour)),
+    };
+    let post = Post::create(&conn, &post_form("outbox post", 1)).unwrap();
+    // Awaiting approval, so it isn't federated yet
+    let pending_post = Post::create(&conn, &post_form("pending outbox post", 3)).unwrap();
+    Post::update_approved(&conn, pending_post.id, None).unwrap();
+
+    let comment_form = |hour: u32, deleted: bool| CommentForm {
+      content: "A test comment".into(),
+      creator_id: inserted_person.id,
+      post_id: post.id,
+      removed: None,
+      deleted: Some(deleted),
+      read: None,
+      parent_id: None,
+      published: Some(time(hour)),
+      updated: None,
+      ap_id: None,
+      local: true,
+    };
+    let comment = Comment::create(&conn, &comment_form(2, false)).unwrap();
+    Comment::create(&conn, &comment_form(4, true)).unwrap();
+
+    let count = Person::count_outbox_items(&conn, inserted_person.id).unwrap();
+    let items = Person::list_outbox_items(&conn, inserted_person.id, 10, 0).unwrap();
+    let second_page = Person::list_outbox_items(&conn, inserted_person.id, 1, 1).unwrap();
+
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(2, count);
+    assert_eq!(
+      vec![
+        OutboxItemId::Comment(comment.id),
+        OutboxItemId::Post(post.id)
+      ],
+      items
+    );
+    assert_eq!(vec![OutboxItemId::Post(post.id)], second_page);
+  }
 }
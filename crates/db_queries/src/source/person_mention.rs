@@ -1,8 +1,8 @@
 use crate::Crud;
 use diesel::{dsl::*, result::Error, *};
-use lemmy_db_schema::source::person_mention::*;
+use lemmy_db_schema::{source::person_mention::*, CommentId, PersonId};
 
-impl Crud<PersonMentionForm> for PersonMention {
+impl Crud<PersonMentionForm, i32> for PersonMention {
   fn read(conn: &PgConnection, person_mention_id: i32) -> Result<Self, Error> {
     use lemmy_db_schema::schema::person_mention::dsl::*;
     person_mention.find(person_mention_id).first::<Self>(conn)
@@ -40,7 +40,11 @@ pub trait PersonMention_ {
   ) -> Result<PersonMention, Error>;
   fn mark_all_as_read(
     conn: &PgConnection,
-    for_recipient_id: i32,
+    for_recipient_id: PersonId,
+  ) -> Result<Vec<PersonMention>, Error>;
+  fn list_for_comment(
+    conn: &PgConnection,
+    for_comment_id: CommentId,
   ) -> Result<Vec<PersonMention>, Error>;
 }
 
@@ -58,7 +62,7 @@ impl PersonMention_ for PersonMention {
 
   fn mark_all_as_read(
     conn: &PgConnection,
-    for_recipient_id: i32,
+    for_recipient_id: PersonId,
   ) -> Result<Vec<PersonMention>, Error> {
     use lemmy_db_schema::schema::person_mention::dsl::*;
     diesel::update(
@@ -69,11 +73,22 @@ impl PersonMention_ for PersonMention {
     .set(read.eq(true))
     .get_results::<Self>(conn)
   }
+
+  fn list_for_comment(
+    conn: &PgConnection,
+    for_comment_id: CommentId,
+  ) -> Result<Vec<PersonMention>, Error> {
+    use lemmy_db_schema::schema::person_mention::dsl::*;
+    person_mention
+      .filter(comment_id.eq(for_comment_id))
+      .order_by(id)
+      .load::<Self>(conn)
+  }
 }
 
 #[cfg(test)]
 mod tests {
-  use crate::{establish_unpooled_connection, Crud};
+  use crate::{establish_unpooled_connection, source::person_mention::PersonMention_, Crud};
   use lemmy_db_schema::source::{
     comment::*,
     community::{Community, CommunityForm},
@@ -150,6 +165,13 @@ mod tests {
       followers_url: None,
       inbox_url: None,
       shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
     };
 
     let inserted_community = Community::create(&conn, &new_community).unwrap();
@@ -212,6 +234,7 @@ mod tests {
     let read_mention = PersonMention::read(&conn, inserted_mention.id).unwrap();
     let updated_mention =
       PersonMention::update(&conn, inserted_mention.id, &person_mention_form).unwrap();
+    let comment_mentions = PersonMention::list_for_comment(&conn, inserted_comment.id).unwrap();
     Comment::delete(&conn, inserted_comment.id).unwrap();
     Post::delete(&conn, inserted_post.id).unwrap();
     Community::delete(&conn, inserted_community.id).unwrap();
@@ -221,5 +244,6 @@ mod tests {
     assert_eq!(expected_mention, read_mention);
     assert_eq!(expected_mention, inserted_mention);
     assert_eq!(expected_mention, updated_mention);
+    assert_eq!(vec![expected_mention], comment_mentions);
   }
 }
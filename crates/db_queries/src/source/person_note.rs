@@ -0,0 +1,141 @@
+use diesel::{result::Error, *};
+use lemmy_db_schema::{
+  source::person_note::{PersonNote, PersonNoteForm},
+  PersonId,
+};
+
+/// The maximum length of a note, same as the database column
+pub const MAX_PERSON_NOTE_LENGTH: i32 = 50;
+
+pub trait PersonNote_ {
+  /// Creates the note of a person about another person, or replaces the existing one
+  fn set(conn: &PgConnection, form: &PersonNoteForm) -> Result<PersonNote, Error>;
+  fn remove(
+    conn: &PgConnection,
+    creator_person_id: PersonId,
+    target_person_id: PersonId,
+  ) -> Result<usize, Error>;
+  fn read(
+    conn: &PgConnection,
+    creator_person_id: PersonId,
+    target_person_id: PersonId,
+  ) -> Result<PersonNote, Error>;
+}
+
+impl PersonNote_ for PersonNote {
+  fn set(conn: &PgConnection, form: &PersonNoteForm) -> Result<PersonNote, Error> {
+    use lemmy_db_schema::schema::person_note::dsl::*;
+    insert_into(person_note)
+      .values(form)
+      .on_conflict((creator_person_id, target_person_id))
+      .do_update()
+      // Set explicitly, so that a removed color gets cleared
+      .set((
+        note.eq(&form.note),
+        color.eq(&form.color),
+        updated.eq(&form.updated),
+      ))
+      .get_result::<Self>(conn)
+  }
+
+  fn remove(
+    conn: &PgConnection,
+    for_creator_person_id: PersonId,
+    for_target_person_id: PersonId,
+  ) -> Result<usize, Error> {
+    use lemmy_db_schema::schema::person_note::dsl::*;
+    diesel::delete(
+      person_note
+        .filter(creator_person_id.eq(for_creator_person_id))
+        .filter(target_person_id.eq(for_target_person_id)),
+    )
+    .execute(conn)
+  }
+
+  fn read(
+    conn: &PgConnection,
+    for_creator_person_id: PersonId,
+    for_target_person_id: PersonId,
+  ) -> Result<PersonNote, Error> {
+    use lemmy_db_schema::schema::person_note::dsl::*;
+    person_note
+      .filter(creator_person_id.eq(for_creator_person_id))
+      .filter(target_person_id.eq(for_target_person_id))
+      .first::<Self>(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{establish_unpooled_connection, source::person_note::PersonNote_, Crud};
+  use lemmy_db_schema::source::{
+    person::{Person, PersonForm},
+    person_note::{PersonNote, PersonNoteForm},
+  };
+  use serial_test::serial;
+
+  fn person_form(name: &str) -> PersonForm {
+    PersonForm {
+      name: name.into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    }
+  }
+
+  #[test]
+  #[serial]
+  fn test_person_note() {
+    let conn = establish_unpooled_connection();
+
+    let creator = Person::create(&conn, &person_form("note_creator")).unwrap();
+    let target = Person::create(&conn, &person_form("note_target")).unwrap();
+
+    let form = PersonNoteForm {
+      creator_person_id: creator.id,
+      target_person_id: target.id,
+      note: "troll".into(),
+      color: Some("#ff0000".into()),
+      updated: None,
+    };
+    let inserted_note = PersonNote::set(&conn, &form).unwrap();
+    assert_eq!("troll", inserted_note.note);
+
+    // Setting it again replaces the note instead of adding a second one
+    let updated_form = PersonNoteForm {
+      note: "helpful".into(),
+      color: None,
+      updated: Some(chrono::Utc::now().naive_utc()),
+      ..form
+    };
+    let updated_note = PersonNote::set(&conn, &updated_form).unwrap();
+    assert_eq!(inserted_note.id, updated_note.id);
+    assert_eq!("helpful", updated_note.note);
+    assert_eq!(None, updated_note.color);
+
+    let read_note = PersonNote::read(&conn, creator.id, target.id).unwrap();
+    assert_eq!(updated_note, read_note);
+
+    // Notes only go one way
+    assert!(PersonNote::read(&conn, target.id, creator.id).is_err());
+
+    let num_removed = PersonNote::remove(&conn, creator.id, target.id).unwrap();
+    assert_eq!(1, num_removed);
+    assert!(PersonNote::read(&conn, creator.id, target.id).is_err());
+
+    Person::delete(&conn, creator.id).unwrap();
+    Person::delete(&conn, target.id).unwrap();
+  }
+}
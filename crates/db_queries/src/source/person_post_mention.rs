@@ -0,0 +1,239 @@
+use crate::Crud;
+use diesel::{dsl::*, result::Error, *};
+use lemmy_db_schema::{source::person_post_mention::*, PersonId, PostId};
+
+impl Crud<PersonPostMentionForm, i32> for PersonPostMention {
+  fn read(conn: &PgConnection, person_post_mention_id: i32) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::person_post_mention::dsl::*;
+    person_post_mention
+      .find(person_post_mention_id)
+      .first::<Self>(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &PersonPostMentionForm) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::person_post_mention::dsl::*;
+    // Post edits may mention the same person again
+    insert_into(person_post_mention)
+      .values(form)
+      .on_conflict((recipient_id, post_id))
+      .do_update()
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    person_post_mention_id: i32,
+    form: &PersonPostMentionForm,
+  ) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::person_post_mention::dsl::*;
+    diesel::update(person_post_mention.find(person_post_mention_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+pub trait PersonPostMention_ {
+  fn update_read(
+    conn: &PgConnection,
+    person_post_mention_id: i32,
+    new_read: bool,
+  ) -> Result<PersonPostMention, Error>;
+  fn mark_all_as_read(
+    conn: &PgConnection,
+    for_recipient_id: PersonId,
+  ) -> Result<Vec<PersonPostMention>, Error>;
+  fn list_for_post(
+    conn: &PgConnection,
+    for_post_id: PostId,
+  ) -> Result<Vec<PersonPostMention>, Error>;
+}
+
+impl PersonPostMention_ for PersonPostMention {
+  fn update_read(
+    conn: &PgConnection,
+    person_post_mention_id: i32,
+    new_read: bool,
+  ) -> Result<PersonPostMention, Error> {
+    use lemmy_db_schema::schema::person_post_mention::dsl::*;
+    diesel::update(person_post_mention.find(person_post_mention_id))
+      .set(read.eq(new_read))
+      .get_result::<Self>(conn)
+  }
+
+  fn mark_all_as_read(
+    conn: &PgConnection,
+    for_recipient_id: PersonId,
+  ) -> Result<Vec<PersonPostMention>, Error> {
+    use lemmy_db_schema::schema::person_post_mention::dsl::*;
+    diesel::update(
+      person_post_mention
+        .filter(recipient_id.eq(for_recipient_id))
+        .filter(read.eq(false)),
+    )
+    .set(read.eq(true))
+    .get_results::<Self>(conn)
+  }
+
+  fn list_for_post(
+    conn: &PgConnection,
+    for_post_id: PostId,
+  ) -> Result<Vec<PersonPostMention>, Error> {
+    use lemmy_db_schema::schema::person_post_mention::dsl::*;
+    person_post_mention
+      .filter(post_id.eq(for_post_id))
+      .order_by(id)
+      .load::<Self>(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    establish_unpooled_connection,
+    source::person_post_mention::PersonPostMention_,
+    Crud,
+  };
+  use lemmy_db_schema::source::{
+    community::{Community, CommunityForm},
+    person::*,
+    person_post_mention::*,
+    post::*,
+  };
+  use serial_test::serial;
+
+  #[test]
+  #[serial]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "terrylake_post".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let recipient_form = PersonForm {
+      name: "terrylakes post recipient".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+
+    let inserted_recipient = Person::create(&conn, &recipient_form).unwrap();
+
+    let new_community = CommunityForm {
+      name: "test community lake post".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let new_post = PostForm {
+      name: "A test post".into(),
+      creator_id: inserted_person.id,
+      url: None,
+      body: Some("Hello @terrylakes".into()),
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      updated: None,
+      nsfw: false,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      thumbnail_url: None,
+      ap_id: None,
+      local: true,
+      published: None,
+    };
+
+    let inserted_post = Post::create(&conn, &new_post).unwrap();
+
+    let person_post_mention_form = PersonPostMentionForm {
+      recipient_id: inserted_recipient.id,
+      post_id: inserted_post.id,
+      read: None,
+    };
+
+    let inserted_mention = PersonPostMention::create(&conn, &person_post_mention_form).unwrap();
+
+    let expected_mention = PersonPostMention {
+      id: inserted_mention.id,
+      recipient_id: inserted_mention.recipient_id,
+      post_id: inserted_mention.post_id,
+      read: false,
+      published: inserted_mention.published,
+    };
+
+    let read_mention = PersonPostMention::read(&conn, inserted_mention.id).unwrap();
+    let updated_mention =
+      PersonPostMention::update(&conn, inserted_mention.id, &person_post_mention_form).unwrap();
+    let post_mentions = PersonPostMention::list_for_post(&conn, inserted_post.id).unwrap();
+    let marked_read = PersonPostMention::mark_all_as_read(&conn, inserted_recipient.id).unwrap();
+    Post::delete(&conn, inserted_post.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+    Person::delete(&conn, inserted_recipient.id).unwrap();
+
+    assert_eq!(expected_mention, read_mention);
+    assert_eq!(expected_mention, inserted_mention);
+    assert_eq!(expected_mention, updated_mention);
+    assert_eq!(vec![expected_mention], post_mentions);
+    assert_eq!(1, marked_read.len());
+    assert!(marked_read[0].read);
+  }
+}
@@ -1,64 +1,163 @@
 use crate::{ApubObject, Crud, Likeable, Readable, Saveable};
-use diesel::{dsl::*, result::Error, *};
+use chrono::NaiveDateTime;
+use diesel::{dsl::*, result::Error, sql_types::Timestamp, *};
 use lemmy_db_schema::{
   naive_now,
   source::post::{
+    PersonPinnedPost,
+    PersonPinnedPostForm,
     Post,
     PostForm,
     PostLike,
     PostLikeForm,
+    PostMetadataForm,
     PostRead,
     PostReadForm,
     PostSaved,
     PostSavedForm,
   },
+  CommunityId,
   DbUrl,
+  PersonId,
+  PostId,
 };
+use lemmy_utils::utils::normalize_url;
 
-impl Crud<PostForm> for Post {
-  fn read(conn: &PgConnection, post_id: i32) -> Result<Self, Error> {
+/// The value of `url_normalized` for a saved post form. It is only updated together with the url.
+fn url_normalized_for(form: &PostForm) -> Option<String> {
+  form.url.to_owned().map(|u| normalize_url(&u.into_inner()))
+}
+
+impl Crud<PostForm, PostId> for Post {
+  fn read(conn: &PgConnection, post_id: PostId) -> Result<Self, Error> {
     use lemmy_db_schema::schema::post::dsl::*;
     post.find(post_id).first::<Self>(conn)
   }
 
-  fn delete(conn: &PgConnection, post_id: i32) -> Result<usize, Error> {
+  fn delete(conn: &PgConnection, post_id: PostId) -> Result<usize, Error> {
     use lemmy_db_schema::schema::post::dsl::*;
     diesel::delete(post.find(post_id)).execute(conn)
   }
 
   fn create(conn: &PgConnection, new_post: &PostForm) -> Result<Self, Error> {
     use lemmy_db_schema::schema::post::dsl::*;
-    insert_into(post).values(new_post).get_result::<Self>(conn)
+    insert_into(post)
+      .values((new_post, url_normalized.eq(url_normalized_for(new_post))))
+      .get_result::<Self>(conn)
   }
 
-  fn update(conn: &PgConnection, post_id: i32, new_post: &PostForm) -> Result<Self, Error> {
+  fn update(conn: &PgConnection, post_id: PostId, new_post: &PostForm) -> Result<Self, Error> {
     use lemmy_db_schema::schema::post::dsl::*;
     diesel::update(post.find(post_id))
-      .set(new_post)
+      .set((
+        new_post,
+        url_normalized_for(new_post).map(|u| url_normalized.eq(u)),
+      ))
       .get_result::<Self>(conn)
   }
 }
 
 pub trait Post_ {
   //fn read(conn: &PgConnection, post_id: i32) -> Result<Post, Error>;
-  fn list_for_community(conn: &PgConnection, the_community_id: i32) -> Result<Vec<Post>, Error>;
-  fn update_ap_id(conn: &PgConnection, post_id: i32, apub_id: DbUrl) -> Result<Post, Error>;
-  fn permadelete_for_creator(conn: &PgConnection, for_creator_id: i32) -> Result<Vec<Post>, Error>;
-  fn update_deleted(conn: &PgConnection, post_id: i32, new_deleted: bool) -> Result<Post, Error>;
-  fn update_removed(conn: &PgConnection, post_id: i32, new_removed: bool) -> Result<Post, Error>;
+  /// Reads the posts with the given ids in one query, in no particular order
+  fn read_many(conn: &PgConnection, post_ids: Vec<PostId>) -> Result<Vec<Post>, Error>;
+  fn list_for_community(
+    conn: &PgConnection,
+    the_community_id: CommunityId,
+  ) -> Result<Vec<Post>, Error>;
+  fn update_ap_id(conn: &PgConnection, post_id: PostId, apub_id: DbUrl) -> Result<Post, Error>;
+  fn permadelete_for_creator(
+    conn: &PgConnection,
+    for_creator_id: PersonId,
+  ) -> Result<Vec<Post>, Error>;
+  fn update_deleted(conn: &PgConnection, post_id: PostId, new_deleted: bool)
+    -> Result<Post, Error>;
+  fn update_removed(conn: &PgConnection, post_id: PostId, new_removed: bool)
+    -> Result<Post, Error>;
   fn update_removed_for_creator(
     conn: &PgConnection,
-    for_creator_id: i32,
-    for_community_id: Option<i32>,
+    for_creator_id: PersonId,
+    for_community_id: Option<CommunityId>,
+    new_removed: bool,
+  ) -> Result<Vec<Post>, Error>;
+  fn update_locked(conn: &PgConnection, post_id: PostId, new_locked: bool) -> Result<Post, Error>;
+  fn update_stickied(
+    conn: &PgConnection,
+    post_id: PostId,
+    new_stickied: bool,
+  ) -> Result<Post, Error>;
+  fn update_featured_local(
+    conn: &PgConnection,
+    post_id: PostId,
+    new_featured_local: bool,
+  ) -> Result<Post, Error>;
+  /// Sets the approval of a post by the mods, `None` means that it waits for a decision
+  fn update_approved(
+    conn: &PgConnection,
+    post_id: PostId,
+    new_approved: Option<bool>,
+  ) -> Result<Post, Error>;
+  fn count_featured_local(conn: &PgConnection) -> Result<i64, Error>;
+  fn is_post_creator(person_id: PersonId, post_creator_id: PersonId) -> bool;
+  fn count_for_creator(
+    conn: &PgConnection,
+    for_creator_id: PersonId,
+    for_community_id: Option<CommunityId>,
+  ) -> Result<i64, Error>;
+  fn purge_expired(
+    conn: &PgConnection,
+    deleted_before: NaiveDateTime,
+    removed_before: NaiveDateTime,
+  ) -> Result<usize, Error>;
+  /// Saves the metadata which was fetched from `fetched_url`, and clears a previous fetch failure.
+  /// Returns `None` if the url of the post was changed in the meantime, the metadata of the new
+  /// url is fetched separately.
+  fn update_metadata(
+    conn: &PgConnection,
+    post_id: PostId,
+    fetched_url: &DbUrl,
+    form: &PostMetadataForm,
+  ) -> Result<Option<Post>, Error>;
+  /// Records that fetching the metadata of `fetched_url` failed, the previous metadata is kept.
+  /// Returns `None` if the url of the post was changed in the meantime.
+  fn update_metadata_fetch_failed(
+    conn: &PgConnection,
+    post_id: PostId,
+    fetched_url: &DbUrl,
+  ) -> Result<Option<Post>, Error>;
+  /// Posts published after `published_after` whose metadata fetch failed, least recently tried
+  /// first
+  fn list_for_metadata_refetch(
+    conn: &PgConnection,
+    published_after: NaiveDateTime,
+    limit: i64,
+  ) -> Result<Vec<Post>, Error>;
+  /// Deletes all posts of a community which arent deleted yet and marks them, or restores only the
+  /// marked posts. Returns the changed posts.
+  fn update_deleted_for_community_delete(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    new_deleted: bool,
+  ) -> Result<Vec<Post>, Error>;
+  /// Removes the visible posts of a community together with the community, or restores only the
+  /// posts which were removed that way.
+  fn update_removed_for_community_remove(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
     new_removed: bool,
   ) -> Result<Vec<Post>, Error>;
-  fn update_locked(conn: &PgConnection, post_id: i32, new_locked: bool) -> Result<Post, Error>;
-  fn update_stickied(conn: &PgConnection, post_id: i32, new_stickied: bool) -> Result<Post, Error>;
-  fn is_post_creator(person_id: i32, post_creator_id: i32) -> bool;
 }
 
 impl Post_ for Post {
-  fn list_for_community(conn: &PgConnection, the_community_id: i32) -> Result<Vec<Self>, Error> {
+  fn read_many(conn: &PgConnection, post_ids: Vec<PostId>) -> Result<Vec<Self>, Error> {
+    use lemmy_db_schema::schema::post::dsl::*;
+    post.filter(id.eq_any(post_ids)).load::<Self>(conn)
+  }
+
+  fn list_for_community(
+    conn: &PgConnection,
+    the_community_id: CommunityId,
+  ) -> Result<Vec<Self>, Error> {
     use lemmy_db_schema::schema::post::dsl::*;
     post
       .filter(community_id.eq(the_community_id))
@@ -68,7 +167,7 @@ impl Post_ for Post {
       .load::<Self>(conn)
   }
 
-  fn update_ap_id(conn: &PgConnection, post_id: i32, apub_id: DbUrl) -> Result<Self, Error> {
+  fn update_ap_id(conn: &PgConnection, post_id: PostId, apub_id: DbUrl) -> Result<Self, Error> {
     use lemmy_db_schema::schema::post::dsl::*;
 
     diesel::update(post.find(post_id))
@@ -76,7 +175,10 @@ impl Post_ for Post {
       .get_result::<Self>(conn)
   }
 
-  fn permadelete_for_creator(conn: &PgConnection, for_creator_id: i32) -> Result<Vec<Self>, Error> {
+  fn permadelete_for_creator(
+    conn: &PgConnection,
+    for_creator_id: PersonId,
+  ) -> Result<Vec<Self>, Error> {
     use lemmy_db_schema::schema::post::dsl::*;
 
     let perma_deleted = "*Permananently Deleted*";
@@ -93,14 +195,22 @@ impl Post_ for Post {
       .get_results::<Self>(conn)
   }
 
-  fn update_deleted(conn: &PgConnection, post_id: i32, new_deleted: bool) -> Result<Self, Error> {
+  fn update_deleted(
+    conn: &PgConnection,
+    post_id: PostId,
+    new_deleted: bool,
+  ) -> Result<Self, Error> {
     use lemmy_db_schema::schema::post::dsl::*;
     diesel::update(post.find(post_id))
       .set((deleted.eq(new_deleted), updated.eq(naive_now())))
       .get_result::<Self>(conn)
   }
 
-  fn update_removed(conn: &PgConnection, post_id: i32, new_removed: bool) -> Result<Self, Error> {
+  fn update_removed(
+    conn: &PgConnection,
+    post_id: PostId,
+    new_removed: bool,
+  ) -> Result<Self, Error> {
     use lemmy_db_schema::schema::post::dsl::*;
     diesel::update(post.find(post_id))
       .set((removed.eq(new_removed), updated.eq(naive_now())))
@@ -109,8 +219,8 @@ impl Post_ for Post {
 
   fn update_removed_for_creator(
     conn: &PgConnection,
-    for_creator_id: i32,
-    for_community_id: Option<i32>,
+    for_creator_id: PersonId,
+    for_community_id: Option<CommunityId>,
     new_removed: bool,
   ) -> Result<Vec<Self>, Error> {
     use lemmy_db_schema::schema::post::dsl::*;
@@ -127,21 +237,206 @@ impl Post_ for Post {
       .get_results::<Self>(conn)
   }
 
-  fn update_locked(conn: &PgConnection, post_id: i32, new_locked: bool) -> Result<Self, Error> {
+  fn update_locked(conn: &PgConnection, post_id: PostId, new_locked: bool) -> Result<Self, Error> {
     use lemmy_db_schema::schema::post::dsl::*;
     diesel::update(post.find(post_id))
       .set(locked.eq(new_locked))
       .get_result::<Self>(conn)
   }
 
-  fn update_stickied(conn: &PgConnection, post_id: i32, new_stickied: bool) -> Result<Self, Error> {
+  fn update_stickied(
+    conn: &PgConnection,
+    post_id: PostId,
+    new_stickied: bool,
+  ) -> Result<Self, Error> {
     use lemmy_db_schema::schema::post::dsl::*;
     diesel::update(post.find(post_id))
       .set(stickied.eq(new_stickied))
       .get_result::<Self>(conn)
   }
 
-  fn is_post_creator(person_id: i32, post_creator_id: i32) -> bool {
+  fn update_featured_local(
+    conn: &PgConnection,
+    post_id: PostId,
+    new_featured_local: bool,
+  ) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::post::dsl::*;
+    diesel::update(post.find(post_id))
+      .set(featured_local.eq(new_featured_local))
+      .get_result::<Self>(conn)
+  }
+
+  fn update_approved(
+    conn: &PgConnection,
+    post_id: PostId,
+    new_approved: Option<bool>,
+  ) -> Result<Self, Error> {
+    use lemmy_db_schema::schema::post::dsl::*;
+    diesel::update(post.find(post_id))
+      .set(approved.eq(new_approved))
+      .get_result::<Self>(conn)
+  }
+
+  /// Number of posts which are featured on the front page of this instance
+  fn count_featured_local(conn: &PgConnection) -> Result<i64, Error> {
+    use lemmy_db_schema::schema::post::dsl::*;
+    post
+      .filter(featured_local.eq(true))
+      .count()
+      .get_result(conn)
+  }
+
+  /// Number of posts by the person which are neither removed nor deleted
+  fn count_for_creator(
+    conn: &PgConnection,
+    for_creator_id: PersonId,
+    for_community_id: Option<CommunityId>,
+  ) -> Result<i64, Error> {
+    use lemmy_db_schema::schema::post::dsl::*;
+    let mut query = post
+      .filter(creator_id.eq(for_creator_id))
+      .filter(removed.eq(false))
+      .filter(deleted.eq(false))
+      .into_boxed();
+    if let Some(for_community_id) = for_community_id {
+      query = query.filter(community_id.eq(for_community_id));
+    }
+    query.count().get_result(conn)
+  }
+
+  fn update_metadata(
+    conn: &PgConnection,
+    post_id: PostId,
+    fetched_url: &DbUrl,
+    form: &PostMetadataForm,
+  ) -> Result<Option<Self>, Error> {
+    use lemmy_db_schema::schema::post::dsl::*;
+    diesel::update(post.find(post_id).filter(url.eq(fetched_url)))
+      .set((form, metadata_fetch_failed_at.eq(None::<NaiveDateTime>)))
+      .get_result::<Self>(conn)
+      .optional()
+  }
+
+  fn update_metadata_fetch_failed(
+    conn: &PgConnection,
+    post_id: PostId,
+    fetched_url: &DbUrl,
+  ) -> Result<Option<Self>, Error> {
+    use lemmy_db_schema::schema::post::dsl::*;
+    diesel::update(post.find(post_id).filter(url.eq(fetched_url)))
+      .set(metadata_fetch_failed_at.eq(naive_now()))
+      .get_result::<Self>(conn)
+      .optional()
+  }
+
+  fn list_for_metadata_refetch(
+    conn: &PgConnection,
+    published_after: NaiveDateTime,
+    limit: i64,
+  ) -> Result<Vec<Self>, Error> {
+    use lemmy_db_schema::schema::post::dsl::*;
+    post
+      .filter(metadata_fetch_failed_at.is_not_null())
+      .filter(published.gt(published_after))
+      .filter(url.is_not_null())
+      .filter(removed.eq(false))
+      .filter(deleted.eq(false))
+      .order_by(metadata_fetch_failed_at.asc())
+      .limit(limit)
+      .load::<Self>(conn)
+  }
+
+  /// Blanks the content of posts which were deleted before `deleted_before`, or removed by a mod
+  /// before `removed_before`. The rows are kept, so that comment threads stay intact. The last
+  /// update time is used as the time of deletion or removal. Posts which were deleted or removed
+  /// together with their community are skipped, because they are restored with it. Returns the
+  /// number of purged posts.
+  fn purge_expired(
+    conn: &PgConnection,
+    deleted_before: NaiveDateTime,
+    removed_before: NaiveDateTime,
+  ) -> Result<usize, Error> {
+    sql_query(
+      "update post set
+        name = '*deleted*',
+        url = null,
+        body = null,
+        embed_title = null,
+        embed_description = null,
+        embed_html = null,
+        thumbnail_url = null
+      where ((deleted and not removed and coalesce(updated, published) < $1)
+        or (removed and coalesce(updated, published) < $2))
+      and not deleted_by_community_delete and not removed_by_community_remove
+      and not (name = '*deleted*' and url is null and body is null and embed_title is null
+        and embed_description is null and embed_html is null and thumbnail_url is null)",
+    )
+    .bind::<Timestamp, _>(deleted_before)
+    .bind::<Timestamp, _>(removed_before)
+    .execute(conn)
+  }
+
+  fn update_deleted_for_community_delete(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    new_deleted: bool,
+  ) -> Result<Vec<Self>, Error> {
+    use lemmy_db_schema::schema::post::dsl::*;
+    let values = (
+      deleted.eq(new_deleted),
+      deleted_by_community_delete.eq(new_deleted),
+      updated.eq(naive_now()),
+    );
+    if new_deleted {
+      diesel::update(
+        post
+          .filter(community_id.eq(for_community_id))
+          .filter(deleted.eq(false)),
+      )
+      .set(values)
+      .get_results::<Self>(conn)
+    } else {
+      diesel::update(
+        post
+          .filter(community_id.eq(for_community_id))
+          .filter(deleted_by_community_delete.eq(true)),
+      )
+      .set(values)
+      .get_results::<Self>(conn)
+    }
+  }
+
+  fn update_removed_for_community_remove(
+    conn: &PgConnection,
+    for_community_id: CommunityId,
+    new_removed: bool,
+  ) -> Result<Vec<Self>, Error> {
+    use lemmy_db_schema::schema::post::dsl::*;
+    let values = (
+      removed.eq(new_removed),
+      removed_by_community_remove.eq(new_removed),
+      updated.eq(naive_now()),
+    );
+    if new_removed {
+      diesel::update(
+        post
+          .filter(community_id.eq(for_community_id))
+          .filter(removed.eq(false)),
+      )
+      .set(values)
+      .get_results::<Self>(conn)
+    } else {
+      diesel::update(
+        post
+          .filter(community_id.eq(for_community_id))
+          .filter(removed_by_community_remove.eq(true)),
+      )
+      .set(values)
+      .get_results::<Self>(conn)
+    }
+  }
+
+  fn is_post_creator(person_id: PersonId, post_creator_id: PersonId) -> bool {
     person_id == post_creator_id
   }
 }
@@ -155,15 +450,18 @@ impl ApubObject<PostForm> for Post {
   fn upsert(conn: &PgConnection, post_form: &PostForm) -> Result<Post, Error> {
     use lemmy_db_schema::schema::post::dsl::*;
     insert_into(post)
-      .values(post_form)
+      .values((post_form, url_normalized.eq(url_normalized_for(post_form))))
       .on_conflict(ap_id)
       .do_update()
-      .set(post_form)
+      .set((
+        post_form,
+        url_normalized_for(post_form).map(|u| url_normalized.eq(u)),
+      ))
       .get_result::<Self>(conn)
   }
 }
 
-impl Likeable<PostLikeForm> for PostLike {
+impl Likeable<PostLikeForm, PostId> for PostLike {
   fn like(conn: &PgConnection, post_like_form: &PostLikeForm) -> Result<Self, Error> {
     use lemmy_db_schema::schema::post_like::dsl::*;
     insert_into(post_like)
@@ -173,7 +471,7 @@ impl Likeable<PostLikeForm> for PostLike {
       .set(post_like_form)
       .get_result::<Self>(conn)
   }
-  fn remove(conn: &PgConnection, person_id: i32, post_id: i32) -> Result<usize, Error> {
+  fn remove(conn: &PgConnection, person_id: PersonId, post_id: PostId) -> Result<usize, Error> {
     use lemmy_db_schema::schema::post_like::dsl;
     diesel::delete(
       dsl::post_like
@@ -182,6 +480,56 @@ impl Likeable<PostLikeForm> for PostLike {
     )
     .execute(conn)
   }
+  fn remove_with_score(
+    conn: &PgConnection,
+    person_id: PersonId,
+    post_id: PostId,
+    score: i16,
+  ) -> Result<usize, Error> {
+    use lemmy_db_schema::schema::post_like::dsl;
+    diesel::delete(
+      dsl::post_like
+        .filter(dsl::post_id.eq(post_id))
+        .filter(dsl::person_id.eq(person_id))
+        .filter(dsl::score.eq(score)),
+    )
+    .execute(conn)
+  }
+  fn read_score(
+    conn: &PgConnection,
+    person_id: PersonId,
+    post_id: PostId,
+  ) -> Result<Option<i16>, Error> {
+    use lemmy_db_schema::schema::post_like::dsl;
+    dsl::post_like
+      .select(dsl::score)
+      .filter(dsl::post_id.eq(post_id))
+      .filter(dsl::person_id.eq(person_id))
+      .first::<i16>(conn)
+      .optional()
+  }
+  fn local_score(conn: &PgConnection, post_id: PostId) -> Result<i64, Error> {
+    use lemmy_db_schema::schema::{person, post_like::dsl};
+    dsl::post_like
+      .inner_join(person::table)
+      .filter(dsl::post_id.eq(post_id))
+      .filter(person::local.eq(true))
+      .select(sum(dsl::score))
+      .first::<Option<i64>>(conn)
+      .map(|score| score.unwrap_or(0))
+  }
+  fn scores_among(
+    conn: &PgConnection,
+    post_id: PostId,
+    person_ids: &[PersonId],
+  ) -> Result<Vec<(PersonId, i16)>, Error> {
+    use lemmy_db_schema::schema::post_like::dsl;
+    dsl::post_like
+      .select((dsl::person_id, dsl::score))
+      .filter(dsl::post_id.eq(post_id))
+      .filter(dsl::person_id.eq_any(person_ids))
+      .load::<(PersonId, i16)>(conn)
+  }
 }
 
 impl Saveable<PostSavedForm> for PostSaved {
@@ -224,14 +572,81 @@ impl Readable<PostReadForm> for PostRead {
   }
 }
 
+/// How many posts a person can pin to their profile
+pub const MAX_PINNED_POSTS: i64 = 5;
+
+pub trait PersonPinnedPost_ {
+  /// Pins the post, or returns `None` if the person already pinned `MAX_PINNED_POSTS` other posts.
+  /// Pinning a post again is allowed at the limit. Pinned posts which were removed or deleted since
+  /// don't count towards the limit, as they aren't shown.
+  fn pin(
+    conn: &PgConnection,
+    form: &PersonPinnedPostForm,
+  ) -> Result<Option<PersonPinnedPost>, Error>;
+  fn unpin(conn: &PgConnection, form: &PersonPinnedPostForm) -> Result<usize, Error>;
+  fn count_for_person(conn: &PgConnection, for_person_id: PersonId) -> Result<i64, Error>;
+}
+
+impl PersonPinnedPost_ for PersonPinnedPost {
+  fn pin(conn: &PgConnection, form: &PersonPinnedPostForm) -> Result<Option<Self>, Error> {
+    use lemmy_db_schema::schema::{person, person_pinned_post::dsl::*, post};
+    conn.transaction(|| {
+      // Concurrent pins of the same person wait for each other, so the limit holds
+      person::table
+        .find(form.person_id)
+        .select(person::id)
+        .for_update()
+        .first::<PersonId>(conn)?;
+      let other_pinned = person_pinned_post
+        .inner_join(post::table)
+        .filter(person_id.eq(form.person_id))
+        .filter(post_id.ne(form.post_id))
+        .filter(post::removed.eq(false))
+        .filter(post::deleted.eq(false))
+        .count()
+        .get_result::<i64>(conn)?;
+      if other_pinned >= MAX_PINNED_POSTS {
+        return Ok(None);
+      }
+      insert_into(person_pinned_post)
+        .values(form)
+        .on_conflict((person_id, post_id))
+        .do_update()
+        .set(form)
+        .get_result::<Self>(conn)
+        .map(Some)
+    })
+  }
+
+  fn unpin(conn: &PgConnection, form: &PersonPinnedPostForm) -> Result<usize, Error> {
+    use lemmy_db_schema::schema::person_pinned_post::dsl::*;
+    diesel::delete(
+      person_pinned_post
+        .filter(person_id.eq(form.person_id))
+        .filter(post_id.eq(form.post_id)),
+    )
+    .execute(conn)
+  }
+
+  fn count_for_person(conn: &PgConnection, for_person_id: PersonId) -> Result<i64, Error> {
+    use lemmy_db_schema::schema::person_pinned_post::dsl::*;
+    person_pinned_post
+      .filter(person_id.eq(for_person_id))
+      .count()
+      .get_result::<i64>(conn)
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use crate::{establish_unpooled_connection, source::post::*};
+  use chrono::Duration;
   use lemmy_db_schema::source::{
     community::{Community, CommunityForm},
     person::*,
   };
   use serial_test::serial;
+  use url::Url;
 
   #[test]
   #[serial]
@@ -279,6 +694,13 @@ mod tests {
       followers_url: None,
       inbox_url: None,
       shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
     };
 
     let inserted_community = Community::create(&conn, &new_community).unwrap();
@@ -326,6 +748,12 @@ mod tests {
       thumbnail_url: None,
       ap_id: inserted_post.ap_id.to_owned(),
       local: true,
+      deleted_by_community_delete: false,
+      featured_local: false,
+      removed_by_community_remove: false,
+      approved: Some(true),
+      url_normalized: None,
+      metadata_fetch_failed_at: None,
     };
 
     // Post Like
@@ -375,11 +803,31 @@ mod tests {
       published: inserted_post_read.published,
     };
 
+    // Pinned post
+    let pinned_post_form = PersonPinnedPostForm {
+      person_id: inserted_person.id,
+      post_id: inserted_post.id,
+    };
+    let inserted_pinned_post = PersonPinnedPost::pin(&conn, &pinned_post_form)
+      .unwrap()
+      .unwrap();
+    let repinned_post = PersonPinnedPost::pin(&conn, &pinned_post_form)
+      .unwrap()
+      .unwrap();
+    let pinned_count = PersonPinnedPost::count_for_person(&conn, inserted_person.id).unwrap();
+
     let read_post = Post::read(&conn, inserted_post.id).unwrap();
     let updated_post = Post::update(&conn, inserted_post.id, &new_post).unwrap();
+    let local_score = PostLike::local_score(&conn, inserted_post.id).unwrap();
+    let scores =
+      PostLike::scores_among(&conn, inserted_post.id, &[inserted_person.id, PersonId(-1)]).unwrap();
+    // Undoing a dislike leaves the like alone
+    let dislike_removed =
+      PostLike::remove_with_score(&conn, inserted_person.id, inserted_post.id, -1).unwrap();
     let like_removed = PostLike::remove(&conn, inserted_person.id, inserted_post.id).unwrap();
     let saved_removed = PostSaved::unsave(&conn, &post_saved_form).unwrap();
     let read_removed = PostRead::mark_as_unread(&conn, &post_read_form).unwrap();
+    let unpinned = PersonPinnedPost::unpin(&conn, &pinned_post_form).unwrap();
     let num_deleted = Post::delete(&conn, inserted_post.id).unwrap();
     Community::delete(&conn, inserted_community.id).unwrap();
     Person::delete(&conn, inserted_person.id).unwrap();
@@ -388,11 +836,605 @@ mod tests {
     assert_eq!(expected_post, inserted_post);
     assert_eq!(expected_post, updated_post);
     assert_eq!(expected_post_like, inserted_post_like);
+    assert_eq!(1, local_score);
     assert_eq!(expected_post_saved, inserted_post_saved);
     assert_eq!(expected_post_read, inserted_post_read);
+    assert_eq!(vec![(inserted_person.id, 1)], scores);
+    assert_eq!(0, dislike_removed);
     assert_eq!(1, like_removed);
     assert_eq!(1, saved_removed);
     assert_eq!(1, read_removed);
+    assert_eq!(inserted_pinned_post, repinned_post);
+    assert_eq!(1, pinned_count);
+    assert_eq!(1, unpinned);
     assert_eq!(1, num_deleted);
   }
+
+  #[test]
+  #[serial]
+  fn test_pin_limit() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "pin_limit_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "pin_limit_community".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let posts = (0..=MAX_PINNED_POSTS)
+      .map(|_| {
+        let form = PostForm {
+          name: "A test post".into(),
+          url: None,
+          body: None,
+          creator_id: inserted_person.id,
+          community_id: inserted_community.id,
+          removed: None,
+          deleted: None,
+          locked: None,
+          stickied: None,
+          nsfw: false,
+          updated: None,
+          embed_title: None,
+          embed_description: None,
+          embed_html: None,
+          thumbnail_url: None,
+          ap_id: None,
+          local: true,
+          published: None,
+        };
+        Post::create(&conn, &form).unwrap()
+      })
+      .collect::<Vec<Post>>();
+    let pin = |post: &Post| {
+      let form = PersonPinnedPostForm {
+        person_id: inserted_person.id,
+        post_id: post.id,
+      };
+      PersonPinnedPost::pin(&conn, &form).unwrap()
+    };
+
+    let (allowed, over_limit) = posts.split_at(MAX_PINNED_POSTS as usize);
+    let pinned = allowed.iter().map(pin).collect::<Vec<_>>();
+    let repinned = pin(&allowed[0]);
+    let rejected = pin(&over_limit[0]);
+    let pinned_count = PersonPinnedPost::count_for_person(&conn, inserted_person.id).unwrap();
+    // Removed and deleted posts make room for other pins
+    Post::update_removed(&conn, allowed[0].id, true).unwrap();
+    let pinned_after_remove = pin(&over_limit[0]);
+    let unpin_form = PersonPinnedPostForm {
+      person_id: inserted_person.id,
+      post_id: over_limit[0].id,
+    };
+    PersonPinnedPost::unpin(&conn, &unpin_form).unwrap();
+    Post::update_removed(&conn, allowed[0].id, false).unwrap();
+    let rejected_after_restore = pin(&over_limit[0]);
+    Post::update_deleted(&conn, allowed[1].id, true).unwrap();
+    let pinned_after_delete = pin(&over_limit[0]);
+
+    for post in &posts {
+      Post::delete(&conn, post.id).unwrap();
+    }
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert!(pinned.iter().all(Option::is_some));
+    // Pinning a post again at the limit is allowed
+    assert!(repinned.is_some());
+    assert!(rejected.is_none());
+    assert_eq!(MAX_PINNED_POSTS, pinned_count);
+    assert!(pinned_after_remove.is_some());
+    assert!(rejected_after_restore.is_none());
+    assert!(pinned_after_delete.is_some());
+  }
+
+  #[test]
+  #[serial]
+  fn test_purge_expired() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "purge_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "purge_community".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let now = naive_now();
+    let deleted_before = now - Duration::days(30);
+    let removed_before = now - Duration::days(180);
+    let second = Duration::seconds(1);
+    let create_post = |deleted: bool, removed: bool, updated: NaiveDateTime| {
+      let form = PostForm {
+        name: "A post to purge".into(),
+        url: Some(Url::parse("https://example.com/image.png").unwrap().into()),
+        body: Some("body".into()),
+        creator_id: inserted_person.id,
+        community_id: inserted_community.id,
+        removed: Some(removed),
+        deleted: Some(deleted),
+        locked: None,
+        stickied: None,
+        nsfw: false,
+        updated: Some(updated),
+        embed_title: Some("title".into()),
+        embed_description: None,
+        embed_html: None,
+        thumbnail_url: None,
+        ap_id: None,
+        local: true,
+        published: Some(removed_before - Duration::days(1)),
+      };
+      Post::create(&conn, &form).unwrap()
+    };
+
+    let deleted_expired = create_post(true, false, deleted_before - second);
+    let deleted_recent = create_post(true, false, deleted_before + second);
+    let deleted_and_removed = create_post(true, true, deleted_before - second);
+    let removed_expired = create_post(false, true, removed_before - second);
+    let removed_recent = create_post(false, true, removed_before + second);
+    let not_deleted = create_post(false, false, removed_before - second);
+    let removed_with_community = create_post(false, true, removed_before - second);
+    let removed_with_community = diesel::update(&removed_with_community)
+      .set(lemmy_db_schema::schema::post::removed_by_community_remove.eq(true))
+      .get_result::<Post>(&conn)
+      .unwrap();
+
+    let purged = Post::purge_expired(&conn, deleted_before, removed_before).unwrap();
+    let purged_again = Post::purge_expired(&conn, deleted_before, removed_before).unwrap();
+    let read = |post: &Post| Post::read(&conn, post.id).unwrap();
+    let read_deleted_expired = read(&deleted_expired);
+    let read_deleted_recent = read(&deleted_recent);
+    let read_deleted_and_removed = read(&deleted_and_removed);
+    let read_removed_expired = read(&removed_expired);
+    let read_removed_recent = read(&removed_recent);
+    let read_not_deleted = read(&not_deleted);
+    let read_removed_with_community = read(&removed_with_community);
+
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(2, purged);
+    assert_eq!(0, purged_again);
+    for purged_post in &[&read_deleted_expired, &read_removed_expired] {
+      assert_eq!("*deleted*", purged_post.name);
+      assert_eq!(None, purged_post.url);
+      assert_eq!(None, purged_post.body);
+      assert_eq!(None, purged_post.embed_title);
+    }
+    assert_eq!(deleted_recent, read_deleted_recent);
+    assert_eq!(deleted_and_removed, read_deleted_and_removed);
+    assert_eq!(removed_recent, read_removed_recent);
+    assert_eq!(not_deleted, read_not_deleted);
+    assert_eq!(removed_with_community, read_removed_with_community);
+  }
+
+  #[test]
+  #[serial]
+  fn test_update_deleted_for_community_delete() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "community_delete_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "community_delete".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let create_post = |deleted: bool| {
+      let form = PostForm {
+        name: "A post in a deleted community".into(),
+        url: None,
+        body: None,
+        creator_id: inserted_person.id,
+        community_id: inserted_community.id,
+        removed: None,
+        deleted: Some(deleted),
+        locked: None,
+        stickied: None,
+        nsfw: false,
+        updated: None,
+        embed_title: None,
+        embed_description: None,
+        embed_html: None,
+        thumbnail_url: None,
+        ap_id: None,
+        local: true,
+        published: None,
+      };
+      Post::create(&conn, &form).unwrap()
+    };
+    let visible_post = create_post(false);
+    let deleted_post = create_post(true);
+
+    let deleted =
+      Post::update_deleted_for_community_delete(&conn, inserted_community.id, true).unwrap();
+    let restored =
+      Post::update_deleted_for_community_delete(&conn, inserted_community.id, false).unwrap();
+    let read_visible_post = Post::read(&conn, visible_post.id).unwrap();
+    let read_deleted_post = Post::read(&conn, deleted_post.id).unwrap();
+
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    // The post which was deleted before is left alone
+    assert_eq!(
+      vec![visible_post.id],
+      deleted.iter().map(|p| p.id).collect::<Vec<_>>()
+    );
+    assert!(deleted[0].deleted);
+    assert!(deleted[0].deleted_by_community_delete);
+    assert_eq!(
+      vec![visible_post.id],
+      restored.iter().map(|p| p.id).collect::<Vec<_>>()
+    );
+    assert!(!read_visible_post.deleted);
+    assert!(!read_visible_post.deleted_by_community_delete);
+    assert!(read_deleted_post.deleted);
+    assert!(!read_deleted_post.deleted_by_community_delete);
+  }
+
+  #[test]
+  #[serial]
+  fn test_update_removed_for_community_remove() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "community_remove_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "community_remove".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let create_post = |removed: bool| {
+      let form = PostForm {
+        name: "A post in a removed community".into(),
+        url: None,
+        body: None,
+        creator_id: inserted_person.id,
+        community_id: inserted_community.id,
+        removed: Some(removed),
+        deleted: None,
+        locked: None,
+        stickied: None,
+        nsfw: false,
+        updated: None,
+        embed_title: None,
+        embed_description: None,
+        embed_html: None,
+        thumbnail_url: None,
+        ap_id: None,
+        local: true,
+        published: None,
+      };
+      Post::create(&conn, &form).unwrap()
+    };
+    let visible_post = create_post(false);
+    let removed_post = create_post(true);
+
+    let removed =
+      Post::update_removed_for_community_remove(&conn, inserted_community.id, true).unwrap();
+    let restored =
+      Post::update_removed_for_community_remove(&conn, inserted_community.id, false).unwrap();
+    let read_visible_post = Post::read(&conn, visible_post.id).unwrap();
+    let read_removed_post = Post::read(&conn, removed_post.id).unwrap();
+
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    // The post which was removed before is left alone
+    assert_eq!(
+      vec![visible_post.id],
+      removed.iter().map(|p| p.id).collect::<Vec<_>>()
+    );
+    assert!(removed[0].removed);
+    assert!(removed[0].removed_by_community_remove);
+    assert_eq!(
+      vec![visible_post.id],
+      restored.iter().map(|p| p.id).collect::<Vec<_>>()
+    );
+    assert!(!read_visible_post.removed);
+    assert!(!read_visible_post.removed_by_community_remove);
+    assert!(read_removed_post.removed);
+    assert!(!read_removed_post.removed_by_community_remove);
+  }
+
+  #[test]
+  #[serial]
+  fn test_metadata_fetch_failed() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "metadata_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "metadata_community".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let now = naive_now();
+    let create_post = |published: NaiveDateTime| {
+      let form = PostForm {
+        name: "A post with a slow url".into(),
+        url: Some(Url::parse("https://example.com/slow").unwrap().into()),
+        body: None,
+        creator_id: inserted_person.id,
+        community_id: inserted_community.id,
+        removed: None,
+        deleted: None,
+        locked: None,
+        stickied: None,
+        nsfw: false,
+        updated: None,
+        embed_title: Some("old title".into()),
+        embed_description: None,
+        embed_html: None,
+        thumbnail_url: None,
+        ap_id: None,
+        local: true,
+        published: Some(published),
+      };
+      Post::create(&conn, &form).unwrap()
+    };
+    let recent_post = create_post(now - Duration::hours(1));
+    let old_post = create_post(now - Duration::days(2));
+    let fetched_url: DbUrl = Url::parse("https://example.com/slow").unwrap().into();
+    let other_url: DbUrl = Url::parse("https://example.com/edited").unwrap().into();
+
+    let failed = Post::update_metadata_fetch_failed(&conn, recent_post.id, &fetched_url)
+      .unwrap()
+      .unwrap();
+    Post::update_metadata_fetch_failed(&conn, old_post.id, &fetched_url).unwrap();
+    let to_refetch = Post::list_for_metadata_refetch(&conn, now - Duration::days(1), 10).unwrap();
+
+    let form = PostMetadataForm {
+      embed_title: None,
+      embed_description: Some("description".into()),
+      embed_html: None,
+      thumbnail_url: None,
+    };
+    let superseded = Post::update_metadata(&conn, recent_post.id, &other_url, &form).unwrap();
+    let superseded_failure =
+      Post::update_metadata_fetch_failed(&conn, recent_post.id, &other_url).unwrap();
+    let fetched = Post::update_metadata(&conn, recent_post.id, &fetched_url, &form)
+      .unwrap()
+      .unwrap();
+    let to_refetch_after =
+      Post::list_for_metadata_refetch(&conn, now - Duration::days(1), 10).unwrap();
+
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    // A failure keeps the previous metadata
+    assert!(failed.metadata_fetch_failed_at.is_some());
+    assert_eq!(Some("old title".to_string()), failed.embed_title);
+    // Posts which are too old aren't retried anymore
+    assert_eq!(
+      vec![recent_post.id],
+      to_refetch.iter().map(|p| p.id).collect::<Vec<_>>()
+    );
+    // Metadata of a url which the post doesn't have anymore is dropped
+    assert!(superseded.is_none());
+    assert!(superseded_failure.is_none());
+    // The fetched metadata replaces the previous one completely
+    assert_eq!(None, fetched.metadata_fetch_failed_at);
+    assert_eq!(None, fetched.embed_title);
+    assert_eq!(Some("description".to_string()), fetched.embed_description);
+    assert!(to_refetch_after.is_empty());
+  }
 }
@@ -0,0 +1,31 @@
+use diesel::{dsl::*, result::Error, *};
+use lemmy_db_schema::{
+  source::post_view_log::{PostViewLog, PostViewLogForm},
+  PostId,
+};
+
+pub trait PostViewLog_ {
+  /// Logs the views of today, and returns the posts of those views which weren't logged yet
+  fn insert_new(conn: &PgConnection, forms: &[PostViewLogForm]) -> Result<Vec<PostId>, Error>;
+  /// Only the views of today are needed to count each person once per day
+  fn delete_before_today(conn: &PgConnection) -> Result<usize, Error>;
+}
+
+impl PostViewLog_ for PostViewLog {
+  fn insert_new(conn: &PgConnection, forms: &[PostViewLogForm]) -> Result<Vec<PostId>, Error> {
+    use lemmy_db_schema::schema::post_view_log::dsl::*;
+    if forms.is_empty() {
+      return Ok(Vec::new());
+    }
+    insert_into(post_view_log)
+      .values(forms)
+      .on_conflict_do_nothing()
+      .returning(post_id)
+      .get_results::<PostId>(conn)
+  }
+
+  fn delete_before_today(conn: &PgConnection) -> Result<usize, Error> {
+    use lemmy_db_schema::schema::post_view_log::dsl::*;
+    diesel::delete(post_view_log.filter(viewed_on.lt(today))).execute(conn)
+  }
+}
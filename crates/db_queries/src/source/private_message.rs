@@ -1,8 +1,8 @@
 use crate::{ApubObject, Crud};
 use diesel::{dsl::*, result::Error, *};
-use lemmy_db_schema::{naive_now, source::private_message::*, DbUrl};
+use lemmy_db_schema::{naive_now, source::private_message::*, DbUrl, PersonId};
 
-impl Crud<PrivateMessageForm> for PrivateMessage {
+impl Crud<PrivateMessageForm, i32> for PrivateMessage {
   fn read(conn: &PgConnection, private_message_id: i32) -> Result<Self, Error> {
     use lemmy_db_schema::schema::private_message::dsl::*;
     private_message.find(private_message_id).first::<Self>(conn)
@@ -72,7 +72,7 @@ pub trait PrivateMessage_ {
   ) -> Result<PrivateMessage, Error>;
   fn mark_all_as_read(
     conn: &PgConnection,
-    for_recipient_id: i32,
+    for_recipient_id: PersonId,
   ) -> Result<Vec<PrivateMessage>, Error>;
 }
 
@@ -124,7 +124,7 @@ impl PrivateMessage_ for PrivateMessage {
 
   fn mark_all_as_read(
     conn: &PgConnection,
-    for_recipient_id: i32,
+    for_recipient_id: PersonId,
   ) -> Result<Vec<PrivateMessage>, Error> {
     use lemmy_db_schema::schema::private_message::dsl::*;
     diesel::update(
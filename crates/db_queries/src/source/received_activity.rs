@@ -0,0 +1,70 @@
+use diesel::{dsl::*, result::Error, *};
+use lemmy_db_schema::{
+  source::received_activity::{ReceivedActivity, ReceivedActivityForm},
+  DbUrl,
+};
+
+pub trait ReceivedActivity_ {
+  /// Stores the id of a received activity. Returns false if it was already stored before.
+  fn insert(conn: &PgConnection, ap_id: DbUrl) -> Result<bool, Error>;
+  fn is_known(conn: &PgConnection, ap_id: &DbUrl) -> Result<bool, Error>;
+  fn delete(conn: &PgConnection, ap_id: &DbUrl) -> Result<usize, Error>;
+  fn delete_older_than_days(conn: &PgConnection, days: i64) -> Result<usize, Error>;
+}
+
+impl ReceivedActivity_ for ReceivedActivity {
+  fn insert(conn: &PgConnection, ap_id_: DbUrl) -> Result<bool, Error> {
+    use lemmy_db_schema::schema::received_activity::dsl::*;
+    let form = ReceivedActivityForm { ap_id: ap_id_ };
+    let inserted = insert_into(received_activity)
+      .values(&form)
+      .on_conflict_do_nothing()
+      .execute(conn)?;
+    Ok(inserted > 0)
+  }
+
+  fn is_known(conn: &PgConnection, ap_id_: &DbUrl) -> Result<bool, Error> {
+    use lemmy_db_schema::schema::received_activity::dsl::*;
+    select(exists(received_activity.filter(ap_id.eq(ap_id_)))).get_result(conn)
+  }
+
+  fn delete(conn: &PgConnection, ap_id_: &DbUrl) -> Result<usize, Error> {
+    use lemmy_db_schema::schema::received_activity::dsl::*;
+    diesel::delete(received_activity.filter(ap_id.eq(ap_id_))).execute(conn)
+  }
+
+  fn delete_older_than_days(conn: &PgConnection, days: i64) -> Result<usize, Error> {
+    use lemmy_db_schema::schema::received_activity::dsl::*;
+    diesel::delete(received_activity.filter(published.lt(now - days.days()))).execute(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{establish_unpooled_connection, source::received_activity::ReceivedActivity_};
+  use lemmy_db_schema::source::received_activity::ReceivedActivity;
+  use serial_test::serial;
+  use url::Url;
+
+  #[test]
+  #[serial]
+  fn test_received_activity() {
+    let conn = establish_unpooled_connection();
+
+    let ap_id = Url::parse("https://example.com/activities/received/1")
+      .unwrap()
+      .into();
+    assert!(!ReceivedActivity::is_known(&conn, &ap_id).unwrap());
+    assert!(ReceivedActivity::insert(&conn, ap_id.to_owned()).unwrap());
+    assert!(ReceivedActivity::is_known(&conn, &ap_id).unwrap());
+    // Inserting the same id again is a no-op
+    assert!(!ReceivedActivity::insert(&conn, ap_id.to_owned()).unwrap());
+
+    assert_eq!(
+      0,
+      ReceivedActivity::delete_older_than_days(&conn, 1).unwrap()
+    );
+    assert_eq!(1, ReceivedActivity::delete(&conn, &ap_id).unwrap());
+    assert!(!ReceivedActivity::is_known(&conn, &ap_id).unwrap());
+  }
+}
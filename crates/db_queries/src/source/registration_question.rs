@@ -0,0 +1,91 @@
+use diesel::{result::Error, *};
+use lemmy_db_schema::source::registration_question::{
+  RegistrationQuestion,
+  RegistrationQuestionForm,
+};
+
+pub trait RegistrationQuestion_ {
+  fn read(conn: &PgConnection, question_id: i32) -> Result<RegistrationQuestion, Error>;
+  fn list(conn: &PgConnection) -> Result<Vec<RegistrationQuestion>, Error>;
+  /// Replaces all registration questions with the given ones.
+  fn replace_all(
+    conn: &PgConnection,
+    forms: &[RegistrationQuestionForm],
+  ) -> Result<Vec<RegistrationQuestion>, Error>;
+}
+
+impl RegistrationQuestion_ for RegistrationQuestion {
+  fn read(conn: &PgConnection, question_id: i32) -> Result<RegistrationQuestion, Error> {
+    use lemmy_db_schema::schema::registration_question::dsl::*;
+    registration_question.find(question_id).first::<Self>(conn)
+  }
+
+  fn list(conn: &PgConnection) -> Result<Vec<RegistrationQuestion>, Error> {
+    use lemmy_db_schema::schema::registration_question::dsl::*;
+    registration_question.order_by(id).load::<Self>(conn)
+  }
+
+  fn replace_all(
+    conn: &PgConnection,
+    forms: &[RegistrationQuestionForm],
+  ) -> Result<Vec<RegistrationQuestion>, Error> {
+    use lemmy_db_schema::schema::registration_question::dsl::*;
+    conn.transaction(|| {
+      diesel::delete(registration_question).execute(conn)?;
+      if forms.is_empty() {
+        return Ok(Vec::new());
+      }
+      insert_into(registration_question)
+        .values(forms)
+        .get_results::<Self>(conn)
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    establish_unpooled_connection,
+    source::registration_question::RegistrationQuestion_,
+  };
+  use lemmy_db_schema::source::registration_question::{
+    RegistrationQuestion,
+    RegistrationQuestionForm,
+  };
+  use serial_test::serial;
+
+  #[test]
+  #[serial]
+  fn test_registration_questions() {
+    let conn = establish_unpooled_connection();
+
+    let forms = vec![RegistrationQuestionForm {
+      question: "What is the name of this software?".into(),
+      answers: vec!["lemmy".into()],
+    }];
+    let inserted = RegistrationQuestion::replace_all(&conn, &forms).unwrap();
+    assert_eq!(1, inserted.len());
+    assert_eq!(
+      inserted[0],
+      RegistrationQuestion::read(&conn, inserted[0].id).unwrap()
+    );
+
+    let forms = vec![
+      RegistrationQuestionForm {
+        question: "2 + 2 = ?".into(),
+        answers: vec!["4".into(), "four".into()],
+      },
+      RegistrationQuestionForm {
+        question: "What colour is the sky?".into(),
+        answers: vec!["blue".into()],
+      },
+    ];
+    RegistrationQuestion::replace_all(&conn, &forms).unwrap();
+    let listed = RegistrationQuestion::list(&conn).unwrap();
+    let questions: Vec<String> = listed.into_iter().map(|q| q.question).collect();
+    assert_eq!(vec!["2 + 2 = ?", "What colour is the sky?"], questions);
+
+    RegistrationQuestion::replace_all(&conn, &[]).unwrap();
+    assert!(RegistrationQuestion::list(&conn).unwrap().is_empty());
+  }
+}
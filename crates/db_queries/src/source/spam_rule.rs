@@ -0,0 +1,282 @@
+use crate::{SpamAction, SpamRuleKind};
+use chrono::Duration;
+use diesel::{result::Error, *};
+use lemmy_db_schema::{
+  naive_now,
+  schema::{comment, post},
+  source::{
+    person::Person,
+    spam_rule::{SpamRule, SpamRuleForm},
+  },
+  CommentId,
+  PersonId,
+  PostId,
+};
+use regex::{Regex, RegexBuilder};
+
+lazy_static! {
+  static ref LINK_REGEX: Regex =
+    Regex::new(r#"https?://[^\s<>()\[\]"']+"#).expect("compile link regex");
+}
+
+pub trait SpamRule_ {
+  fn list(conn: &PgConnection) -> Result<Vec<SpamRule>, Error>;
+  /// Replaces all spam rules with the given ones.
+  fn replace_all(conn: &PgConnection, forms: &[SpamRuleForm]) -> Result<Vec<SpamRule>, Error>;
+}
+
+impl SpamRule_ for SpamRule {
+  fn list(conn: &PgConnection) -> Result<Vec<SpamRule>, Error> {
+    use lemmy_db_schema::schema::spam_rule::dsl::*;
+    spam_rule.order_by(id).load::<Self>(conn)
+  }
+
+  fn replace_all(conn: &PgConnection, forms: &[SpamRuleForm]) -> Result<Vec<SpamRule>, Error> {
+    use lemmy_db_schema::schema::spam_rule::dsl::*;
+    conn.transaction(|| {
+      diesel::delete(spam_rule).execute(conn)?;
+      if forms.is_empty() {
+        return Ok(Vec::new());
+      }
+      insert_into(spam_rule)
+        .values(forms)
+        .get_results::<Self>(conn)
+    })
+  }
+}
+
+/// A spam rule, with the kind and url pattern already parsed so that they aren't parsed again for
+/// every post and comment
+#[derive(Clone, Debug)]
+pub struct CompiledSpamRule {
+  pub rule: SpamRule,
+  kind: SpamRuleKind,
+  regex: Option<Regex>,
+}
+
+impl CompiledSpamRule {
+  /// Rules of unknown kinds or with an invalid pattern are left out
+  pub fn compile(rules: Vec<SpamRule>) -> Vec<Self> {
+    rules
+      .into_iter()
+      .filter_map(|rule| {
+        let kind = SpamRuleKind::from_i16(rule.kind)?;
+        let regex = match kind {
+          SpamRuleKind::UrlDenylist => Some(
+            RegexBuilder::new(rule.pattern.as_deref()?)
+              .case_insensitive(true)
+              .build()
+              .ok()?,
+          ),
+          _ => None,
+        };
+        Some(CompiledSpamRule { rule, kind, regex })
+      })
+      .collect()
+  }
+}
+
+/// A new post or comment which is checked against the spam rules. `id` is only set if the content
+/// was already stored, like federated content, so that it isn't taken for a duplicate of itself.
+#[derive(Clone, Debug)]
+pub enum SpamCheckContent {
+  Post {
+    id: Option<PostId>,
+    name: String,
+    url: Option<String>,
+    body: Option<String>,
+  },
+  Comment {
+    id: Option<CommentId>,
+    content: String,
+  },
+}
+
+impl SpamCheckContent {
+  /// The post url and all links in the text
+  fn links(&self) -> Vec<&str> {
+    let (url, text) = match self {
+      SpamCheckContent::Post { url, body, .. } => (url.as_deref(), body.as_deref()),
+      SpamCheckContent::Comment { content, .. } => (None, Some(content.as_str())),
+    };
+    url
+      .into_iter()
+      .chain(
+        text
+          .into_iter()
+          .flat_map(|t| LINK_REGEX.find_iter(t).map(|m| m.as_str())),
+      )
+      .collect()
+  }
+}
+
+/// Returns the rule which the content of `creator` matches, if any. Rules which reject the content
+/// take precedence over those which remove it.
+pub fn check_spam(
+  conn: &PgConnection,
+  rules: &[CompiledSpamRule],
+  creator: &Person,
+  content: &SpamCheckContent,
+) -> Result<Option<SpamRule>, Error> {
+  let links = content.links();
+  let mut matched: Option<&SpamRule> = None;
+  for CompiledSpamRule { rule, kind, regex } in rules {
+    let threshold = rule.threshold.unwrap_or(0);
+    let is_match = match kind {
+      SpamRuleKind::UrlDenylist => match regex {
+        Some(regex) => links.iter().any(|l| regex.is_match(l)),
+        None => false,
+      },
+      SpamRuleKind::MaxLinks => links.len() > threshold.max(0) as usize,
+      SpamRuleKind::MinAccountAge => {
+        !links.is_empty() && creator.published > naive_now() - Duration::hours(threshold.into())
+      }
+      SpamRuleKind::DuplicateContent => {
+        threshold > 0 && is_duplicate(conn, creator.id, content, threshold.into())?
+      }
+    };
+    if !is_match {
+      continue;
+    }
+    if SpamAction::from_i16(rule.action) == SpamAction::Reject {
+      return Ok(Some(rule.to_owned()));
+    }
+    matched = matched.or(Some(rule));
+  }
+  Ok(matched.cloned())
+}
+
+/// Whether one of the `count` latest posts or comments of the creator has the same content
+fn is_duplicate(
+  conn: &PgConnection,
+  creator_id: PersonId,
+  content: &SpamCheckContent,
+  count: i64,
+) -> Result<bool, Error> {
+  match content {
+    SpamCheckContent::Post {
+      id,
+      name,
+      url,
+      body,
+    } => {
+      let latest = post::table
+        .filter(post::creator_id.eq(creator_id))
+        .filter(post::id.ne(id.unwrap_or(PostId(-1))))
+        .select((post::name, post::url, post::body))
+        .order_by(post::published.desc())
+        .limit(count)
+        .load::<(String, Option<String>, Option<String>)>(conn)?;
+      Ok(
+        latest
+          .iter()
+          .any(|(n, u, b)| n == name && u == url && b == body),
+      )
+    }
+    SpamCheckContent::Comment { id, content } => {
+      let latest = comment::table
+        .filter(comment::creator_id.eq(creator_id))
+        .filter(comment::id.ne(id.unwrap_or(CommentId(-1))))
+        .select(comment::content)
+        .order_by(comment::published.desc())
+        .limit(count)
+        .load::<String>(conn)?;
+      Ok(latest.iter().any(|c| c == content))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    establish_unpooled_connection,
+    source::spam_rule::{check_spam, CompiledSpamRule, SpamCheckContent, SpamRule_},
+    Crud,
+    SpamAction,
+    SpamRuleKind,
+  };
+  use lemmy_db_schema::source::{
+    person::{Person, PersonForm},
+    spam_rule::{SpamRule, SpamRuleForm},
+  };
+  use serial_test::serial;
+
+  #[test]
+  #[serial]
+  fn test_check_spam() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "spam_rule_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let forms = vec![
+      SpamRuleForm {
+        kind: SpamRuleKind::MaxLinks as i16,
+        pattern: None,
+        threshold: Some(2),
+        action: SpamAction::Remove as i16,
+      },
+      SpamRuleForm {
+        kind: SpamRuleKind::UrlDenylist as i16,
+        pattern: Some(r"spam\.example".into()),
+        threshold: None,
+        action: SpamAction::Reject as i16,
+      },
+    ];
+    let rules = SpamRule::replace_all(&conn, &forms).unwrap();
+    let compiled = CompiledSpamRule::compile(rules.to_owned());
+
+    let comment = |content: &str| SpamCheckContent::Comment {
+      id: None,
+      content: content.into(),
+    };
+    let clean = check_spam(
+      &conn,
+      &compiled,
+      &inserted_person,
+      &comment("see https://a.org"),
+    )
+    .unwrap();
+    let too_many_links = check_spam(
+      &conn,
+      &compiled,
+      &inserted_person,
+      &comment("https://a.org https://b.org https://c.org"),
+    )
+    .unwrap();
+    let denied = check_spam(
+      &conn,
+      &compiled,
+      &inserted_person,
+      &comment("https://a.org https://b.org https://SPAM.example/buy"),
+    )
+    .unwrap();
+
+    SpamRule::replace_all(&conn, &[]).unwrap();
+    let listed = SpamRule::list(&conn).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(None, clean);
+    assert_eq!(Some(rules[0].id), too_many_links.map(|r| r.id));
+    // The rejecting rule takes precedence
+    assert_eq!(Some(rules[1].id), denied.map(|r| r.id));
+    assert!(listed.is_empty());
+  }
+}
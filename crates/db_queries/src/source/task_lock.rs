@@ -0,0 +1,174 @@
+use diesel::{dsl::*, result::Error, *};
+use lemmy_db_schema::source::task_lock::{TaskLock, TaskLockForm};
+
+pub trait TaskLock_ {
+  /// Creates the row of a task, if it doesn't exist yet. New tasks are due immediately.
+  fn register(conn: &PgConnection, name: &str) -> Result<usize, Error>;
+  /// Takes the lock of a task which is due, for `lock_seconds`. Returns false if the task isn't
+  /// due yet, or if another server holds the lock.
+  fn try_lock(
+    conn: &PgConnection,
+    name: &str,
+    owner: &str,
+    lock_seconds: i64,
+  ) -> Result<bool, Error>;
+  /// Keeps the lock of a running task for another `lock_seconds`. Returns false if `owner` doesn't
+  /// hold the lock anymore.
+  fn extend_lock(
+    conn: &PgConnection,
+    name: &str,
+    owner: &str,
+    lock_seconds: i64,
+  ) -> Result<bool, Error>;
+  /// Releases the lock after a run, and schedules the next run in `interval_seconds`.
+  fn finish(
+    conn: &PgConnection,
+    name: &str,
+    owner: &str,
+    error: Option<String>,
+    interval_seconds: i64,
+  ) -> Result<usize, Error>;
+  /// Makes a task due immediately, so that the next server which checks it runs it.
+  fn trigger(conn: &PgConnection, name: &str) -> Result<TaskLock, Error>;
+  fn list(conn: &PgConnection) -> Result<Vec<TaskLock>, Error>;
+}
+
+impl TaskLock_ for TaskLock {
+  fn register(conn: &PgConnection, name_: &str) -> Result<usize, Error> {
+    use lemmy_db_schema::schema::task_lock::dsl::*;
+    let form = TaskLockForm {
+      name: name_.to_owned(),
+    };
+    insert_into(task_lock)
+      .values(&form)
+      .on_conflict_do_nothing()
+      .execute(conn)
+  }
+
+  fn try_lock(
+    conn: &PgConnection,
+    name_: &str,
+    owner: &str,
+    lock_seconds: i64,
+  ) -> Result<bool, Error> {
+    use lemmy_db_schema::schema::task_lock::dsl::*;
+    // A single update, so that two servers can't both take the lock
+    let locked = diesel::update(
+      task_lock
+        .filter(name.eq(name_))
+        .filter(next_run.le(now))
+        .filter(locked_until.is_null().or(locked_until.lt(now.nullable()))),
+    )
+    .set((
+      locked_by.eq(owner),
+      locked_until.eq((now + lock_seconds.seconds()).nullable()),
+    ))
+    .execute(conn)?;
+    Ok(locked == 1)
+  }
+
+  fn extend_lock(
+    conn: &PgConnection,
+    name_: &str,
+    owner: &str,
+    lock_seconds: i64,
+  ) -> Result<bool, Error> {
+    use lemmy_db_schema::schema::task_lock::dsl::*;
+    let extended = diesel::update(task_lock.filter(name.eq(name_)).filter(locked_by.eq(owner)))
+      .set(locked_until.eq((now + lock_seconds.seconds()).nullable()))
+      .execute(conn)?;
+    Ok(extended == 1)
+  }
+
+  fn finish(
+    conn: &PgConnection,
+    name_: &str,
+    owner: &str,
+    error: Option<String>,
+    interval_seconds: i64,
+  ) -> Result<usize, Error> {
+    use lemmy_db_schema::schema::task_lock::dsl::*;
+    diesel::update(task_lock.filter(name.eq(name_)).filter(locked_by.eq(owner)))
+      .set((
+        locked_by.eq(None::<String>),
+        locked_until.eq(None::<chrono::NaiveDateTime>),
+        last_run.eq(now.nullable()),
+        last_error.eq(error),
+        next_run.eq(now + interval_seconds.seconds()),
+      ))
+      .execute(conn)
+  }
+
+  fn trigger(conn: &PgConnection, name_: &str) -> Result<TaskLock, Error> {
+    use lemmy_db_schema::schema::task_lock::dsl::*;
+    diesel::update(task_lock.filter(name.eq(name_)))
+      .set(next_run.eq(now))
+      .get_result::<Self>(conn)
+  }
+
+  fn list(conn: &PgConnection) -> Result<Vec<TaskLock>, Error> {
+    use lemmy_db_schema::schema::task_lock::dsl::*;
+    task_lock.order_by(name).load::<Self>(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{establish_unpooled_connection, source::task_lock::TaskLock_};
+  use diesel::*;
+  use lemmy_db_schema::{schema::task_lock, source::task_lock::TaskLock};
+  use serial_test::serial;
+
+  #[test]
+  #[serial]
+  fn test_task_lock() {
+    let conn = establish_unpooled_connection();
+    let name = "test_task_lock";
+
+    assert_eq!(1, TaskLock::register(&conn, name).unwrap());
+    assert_eq!(0, TaskLock::register(&conn, name).unwrap());
+
+    // Only one server gets the lock
+    assert!(TaskLock::try_lock(&conn, name, "server_a", 60).unwrap());
+    assert!(!TaskLock::try_lock(&conn, name, "server_b", 60).unwrap());
+
+    // Only the lock owner can extend it
+    assert!(TaskLock::extend_lock(&conn, name, "server_a", 60).unwrap());
+    assert!(!TaskLock::extend_lock(&conn, name, "server_b", 60).unwrap());
+
+    // Only the lock owner can release it, after that the task isn't due for an hour
+    assert_eq!(
+      0,
+      TaskLock::finish(&conn, name, "server_b", None, 3600).unwrap()
+    );
+    assert_eq!(
+      1,
+      TaskLock::finish(&conn, name, "server_a", Some("failed".into()), 3600).unwrap()
+    );
+    assert!(!TaskLock::try_lock(&conn, name, "server_b", 60).unwrap());
+
+    let finished = TaskLock::list(&conn)
+      .unwrap()
+      .into_iter()
+      .find(|t| t.name == name)
+      .unwrap();
+    assert_eq!(None, finished.locked_by);
+    assert_eq!(Some("failed".to_string()), finished.last_error);
+    assert!(finished.last_run.is_some());
+
+    // A manual trigger makes it due again
+    TaskLock::trigger(&conn, name).unwrap();
+    assert!(TaskLock::try_lock(&conn, name, "server_b", 60).unwrap());
+
+    // An expired lock can be taken over
+    TaskLock::finish(&conn, name, "server_b", None, 0).unwrap();
+    assert!(TaskLock::try_lock(&conn, name, "server_a", -1).unwrap());
+    assert!(TaskLock::try_lock(&conn, name, "server_b", 60).unwrap());
+
+    assert!(TaskLock::trigger(&conn, "unknown_task").is_err());
+
+    diesel::delete(task_lock::table.filter(task_lock::name.eq(name)))
+      .execute(&conn)
+      .unwrap();
+  }
+}
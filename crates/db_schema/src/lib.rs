@@ -6,7 +6,7 @@ use diesel::{
   backend::Backend,
   deserialize::FromSql,
   serialize::{Output, ToSql},
-  sql_types::Text,
+  sql_types::{Integer, Text},
 };
 use serde::{Deserialize, Serialize};
 use std::{
@@ -18,6 +18,61 @@ use url::Url;
 pub mod schema;
 pub mod source;
 
+/// Declares a typed wrapper around an integer primary key. On the wire and in the database it
+/// stays a plain integer, so only the Rust side can tell the different ids apart.
+macro_rules! id_newtype {
+  ($name:ident) => {
+    #[derive(
+      Copy,
+      Clone,
+      Hash,
+      Eq,
+      PartialEq,
+      Ord,
+      PartialOrd,
+      Default,
+      Debug,
+      Serialize,
+      Deserialize,
+      AsExpression,
+      FromSqlRow,
+    )]
+    #[serde(transparent)]
+    #[sql_type = "Integer"]
+    pub struct $name(pub i32);
+
+    impl<DB: Backend> ToSql<Integer, DB> for $name
+    where
+      i32: ToSql<Integer, DB>,
+    {
+      fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> diesel::serialize::Result {
+        self.0.to_sql(out)
+      }
+    }
+
+    impl<DB: Backend> FromSql<Integer, DB> for $name
+    where
+      i32: FromSql<Integer, DB>,
+    {
+      fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        i32::from_sql(bytes).map($name)
+      }
+    }
+
+    impl Display for $name {
+      fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+      }
+    }
+  };
+}
+
+id_newtype!(PersonId);
+id_newtype!(CommunityId);
+id_newtype!(PostId);
+id_newtype!(CommentId);
+id_newtype!(LanguageId);
+
 #[repr(transparent)]
 #[derive(Clone, PartialEq, Serialize, Deserialize, Debug, AsExpression, FromSqlRow)]
 #[sql_type = "Text"]
@@ -70,3 +125,19 @@ impl From<Url> for DbUrl {
 pub fn naive_now() -> NaiveDateTime {
   chrono::prelude::Utc::now().naive_utc()
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::{CommentId, PersonId, PostId};
+
+  #[test]
+  fn test_id_serializes_as_bare_integer() {
+    assert_eq!("42", serde_json::to_string(&PostId(42)).unwrap());
+    assert_eq!(
+      "[1,null]",
+      serde_json::to_string(&vec![Some(CommentId(1)), None]).unwrap()
+    );
+    let person_id: PersonId = serde_json::from_str("7").unwrap();
+    assert_eq!(PersonId(7), person_id);
+  }
+}
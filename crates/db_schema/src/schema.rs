@@ -10,6 +10,27 @@ table! {
     }
 }
 
+table! {
+    admin_auto_remove (id) {
+        id -> Int4,
+        post_id -> Int4,
+        comment_id -> Nullable<Int4>,
+        rule_kind -> Int2,
+        when_ -> Timestamp,
+    }
+}
+
+table! {
+    api_token (id) {
+        id -> Int4,
+        local_user_id -> Int4,
+        name -> Text,
+        scopes -> Array<Text>,
+        expires -> Nullable<Timestamp>,
+        published -> Timestamp,
+    }
+}
+
 table! {
     comment (id) {
         id -> Int4,
@@ -24,6 +45,11 @@ table! {
         deleted -> Bool,
         ap_id -> Varchar,
         local -> Bool,
+        deleted_by_community_delete -> Bool,
+        removed_by_community_remove -> Bool,
+        language_id -> Int4,
+        locked -> Bool,
+        stickied -> Bool,
     }
 }
 
@@ -35,6 +61,8 @@ table! {
         upvotes -> Int8,
         downvotes -> Int8,
         published -> Timestamp,
+        hot_rank -> Int4,
+        child_count -> Int4,
     }
 }
 
@@ -94,6 +122,15 @@ table! {
         followers_url -> Varchar,
         inbox_url -> Varchar,
         shared_inbox_url -> Nullable<Varchar>,
+        exempt_from_slur_filter -> Bool,
+        downvotes_disabled -> Bool,
+        requires_post_approval -> Bool,
+        theme_color -> Nullable<Varchar>,
+        short_description -> Nullable<Varchar>,
+        default_comment_sort -> Int2,
+        require_post_tag -> Bool,
+        manual_follow_approval -> Bool,
+        manually_refreshed_at -> Nullable<Timestamp>,
     }
 }
 
@@ -109,6 +146,29 @@ table! {
         users_active_week -> Int8,
         users_active_month -> Int8,
         users_active_half_year -> Int8,
+        hot_rank -> Int4,
+        last_activity -> Timestamp,
+    }
+}
+
+table! {
+    community_federation_log (id) {
+        id -> Int8,
+        community_id -> Int4,
+        inbound -> Bool,
+        kind -> Nullable<Text>,
+        domain -> Text,
+        error -> Nullable<Text>,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    community_favorite (id) {
+        id -> Int4,
+        community_id -> Int4,
+        person_id -> Int4,
+        published -> Timestamp,
     }
 }
 
@@ -119,6 +179,28 @@ table! {
         person_id -> Int4,
         published -> Timestamp,
         pending -> Nullable<Bool>,
+        follow_sent -> Timestamp,
+        follow_resends -> Int4,
+        follow_failed -> Bool,
+        mute_until -> Nullable<Timestamp>,
+        follow_activity_id -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    community_language (id) {
+        id -> Int4,
+        community_id -> Int4,
+        language_id -> Int4,
+    }
+}
+
+table! {
+    community_last_viewed (id) {
+        id -> Int4,
+        community_id -> Int4,
+        person_id -> Int4,
+        last_viewed -> Timestamp,
     }
 }
 
@@ -128,6 +210,17 @@ table! {
         community_id -> Int4,
         person_id -> Int4,
         published -> Timestamp,
+        rank -> Int4,
+    }
+}
+
+table! {
+    community_tag (id) {
+        id -> Int4,
+        community_id -> Int4,
+        name -> Varchar,
+        color -> Nullable<Varchar>,
+        published -> Timestamp,
     }
 }
 
@@ -140,6 +233,28 @@ table! {
     }
 }
 
+table! {
+    instance (id) {
+        id -> Int4,
+        domain -> Varchar,
+        software -> Nullable<Varchar>,
+        version -> Nullable<Varchar>,
+        last_seen -> Nullable<Timestamp>,
+        last_fetched -> Nullable<Timestamp>,
+        published -> Timestamp,
+        unreachable_since -> Nullable<Timestamp>,
+        vote_actor_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    language (id) {
+        id -> Int4,
+        code -> Varchar,
+        name -> Text,
+    }
+}
+
 table! {
     local_user (id) {
         id -> Int4,
@@ -155,6 +270,30 @@ table! {
         show_avatars -> Bool,
         send_notifications_to_email -> Bool,
         matrix_user_id -> Nullable<Text>,
+        email_digest_frequency -> Int2,
+        last_email_digest -> Timestamp,
+        email_unsubscribe_token -> Nullable<Text>,
+        hide_last_active -> Bool,
+        hide_profile_from_federation -> Bool,
+    }
+}
+
+table! {
+    local_user_language (id) {
+        id -> Int4,
+        local_user_id -> Int4,
+        language_id -> Int4,
+    }
+}
+
+table! {
+    login_session (id) {
+        id -> Int4,
+        local_user_id -> Int4,
+        user_agent -> Nullable<Text>,
+        ip -> Nullable<Text>,
+        issued_at -> Timestamp,
+        last_used -> Timestamp,
     }
 }
 
@@ -204,6 +343,47 @@ table! {
     }
 }
 
+table! {
+    mod_edit_community (id) {
+        id -> Int4,
+        mod_person_id -> Int4,
+        community_id -> Int4,
+        changed_fields -> Array<Text>,
+        when_ -> Timestamp,
+    }
+}
+
+table! {
+    mod_exempt_community (id) {
+        id -> Int4,
+        mod_person_id -> Int4,
+        community_id -> Int4,
+        exempt -> Nullable<Bool>,
+        when_ -> Timestamp,
+    }
+}
+
+table! {
+    mod_feature_post (id) {
+        id -> Int4,
+        mod_person_id -> Int4,
+        post_id -> Int4,
+        featured -> Nullable<Bool>,
+        when_ -> Timestamp,
+    }
+}
+
+table! {
+    mod_lock_comment (id) {
+        id -> Int4,
+        mod_person_id -> Int4,
+        comment_id -> Int4,
+        locked -> Nullable<Bool>,
+        reason -> Nullable<Text>,
+        when_ -> Timestamp,
+    }
+}
+
 table! {
     mod_lock_post (id) {
         id -> Int4,
@@ -234,6 +414,7 @@ table! {
         removed -> Nullable<Bool>,
         expires -> Nullable<Timestamp>,
         when_ -> Timestamp,
+        remove_content -> Bool,
     }
 }
 
@@ -248,6 +429,16 @@ table! {
     }
 }
 
+table! {
+    mod_sticky_comment (id) {
+        id -> Int4,
+        mod_person_id -> Int4,
+        comment_id -> Int4,
+        stickied -> Nullable<Bool>,
+        when_ -> Timestamp,
+    }
+}
+
 table! {
     mod_sticky_post (id) {
         id -> Int4,
@@ -286,6 +477,7 @@ table! {
         deleted -> Bool,
         inbox_url -> Varchar,
         shared_inbox_url -> Nullable<Varchar>,
+        last_activity_at -> Nullable<Timestamp>,
     }
 }
 
@@ -318,6 +510,37 @@ table! {
     }
 }
 
+table! {
+    person_note (id) {
+        id -> Int4,
+        creator_person_id -> Int4,
+        target_person_id -> Int4,
+        note -> Varchar,
+        color -> Nullable<Varchar>,
+        published -> Timestamp,
+        updated -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    person_pinned_post (id) {
+        id -> Int4,
+        person_id -> Int4,
+        post_id -> Int4,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    person_post_mention (id) {
+        id -> Int4,
+        recipient_id -> Int4,
+        post_id -> Int4,
+        read -> Bool,
+        published -> Timestamp,
+    }
+}
+
 table! {
     post (id) {
         id -> Int4,
@@ -339,6 +562,12 @@ table! {
         thumbnail_url -> Nullable<Text>,
         ap_id -> Varchar,
         local -> Bool,
+        deleted_by_community_delete -> Bool,
+        featured_local -> Bool,
+        removed_by_community_remove -> Bool,
+        approved -> Nullable<Bool>,
+        url_normalized -> Nullable<Text>,
+        metadata_fetch_failed_at -> Nullable<Timestamp>,
     }
 }
 
@@ -354,6 +583,10 @@ table! {
         published -> Timestamp,
         newest_comment_time_necro -> Timestamp,
         newest_comment_time -> Timestamp,
+        hot_rank -> Int4,
+        hot_rank_active -> Int4,
+        featured_local -> Bool,
+        views -> Int8,
     }
 }
 
@@ -401,6 +634,22 @@ table! {
     }
 }
 
+table! {
+    post_tag (id) {
+        id -> Int4,
+        post_id -> Int4,
+        tag_id -> Int4,
+    }
+}
+
+table! {
+    post_view_log (post_id, person_id, viewed_on) {
+        post_id -> Int4,
+        person_id -> Int4,
+        viewed_on -> Date,
+    }
+}
+
 table! {
     private_message (id) {
         id -> Int4,
@@ -416,6 +665,23 @@ table! {
     }
 }
 
+table! {
+    received_activity (id) {
+        id -> Int8,
+        ap_id -> Text,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    registration_question (id) {
+        id -> Int4,
+        question -> Text,
+        answers -> Array<Text>,
+        published -> Timestamp,
+    }
+}
+
 table! {
     site (id) {
         id -> Int4,
@@ -429,6 +695,17 @@ table! {
         enable_nsfw -> Bool,
         icon -> Nullable<Varchar>,
         banner -> Nullable<Varchar>,
+        slur_filter_regex -> Nullable<Text>,
+        require_local_images -> Bool,
+        max_post_body_length -> Int4,
+        max_comment_length -> Int4,
+        allowed_url_schemes -> Array<Text>,
+        default_post_listing_type -> Int2,
+        show_deleted_usernames_to_admins -> Bool,
+        federation_mode -> Int2,
+        federate_votes_as -> Int2,
+        registration_challenge -> Int2,
+        proof_of_work_difficulty -> Int4,
     }
 }
 
@@ -447,6 +724,29 @@ table! {
     }
 }
 
+table! {
+    spam_rule (id) {
+        id -> Int4,
+        kind -> Int2,
+        pattern -> Nullable<Text>,
+        threshold -> Nullable<Int4>,
+        action -> Int2,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    task_lock (id) {
+        id -> Int4,
+        name -> Text,
+        locked_by -> Nullable<Text>,
+        locked_until -> Nullable<Timestamp>,
+        last_run -> Nullable<Timestamp>,
+        last_error -> Nullable<Text>,
+        next_run -> Timestamp,
+    }
+}
+
 // These are necessary since diesel doesn't have self joins / aliases
 table! {
     comment_alias_1 (id) {
@@ -462,6 +762,11 @@ table! {
         deleted -> Bool,
         ap_id -> Varchar,
         local -> Bool,
+        deleted_by_community_delete -> Bool,
+        removed_by_community_remove -> Bool,
+        language_id -> Int4,
+        locked -> Bool,
+        stickied -> Bool,
     }
 }
 
@@ -512,13 +817,18 @@ table! {
 joinable!(comment_alias_1 -> person_alias_1 (creator_id));
 joinable!(comment -> comment_alias_1 (parent_id));
 joinable!(person_mention -> person_alias_1 (recipient_id));
+joinable!(person_post_mention -> person_alias_1 (recipient_id));
 joinable!(post -> person_alias_1 (creator_id));
+joinable!(api_token -> local_user (local_user_id));
 joinable!(comment -> person_alias_1 (creator_id));
 
 joinable!(post_report -> person_alias_2 (resolver_id));
 joinable!(comment_report -> person_alias_2 (resolver_id));
 
+joinable!(admin_auto_remove -> comment (comment_id));
+joinable!(admin_auto_remove -> post (post_id));
 joinable!(comment -> person (creator_id));
+joinable!(comment -> language (language_id));
 joinable!(comment -> post (post_id));
 joinable!(comment_aggregates -> comment (comment_id));
 joinable!(comment_like -> comment (comment_id));
@@ -529,15 +839,34 @@ joinable!(comment_saved -> comment (comment_id));
 joinable!(comment_saved -> person (person_id));
 joinable!(community -> person (creator_id));
 joinable!(community_aggregates -> community (community_id));
+joinable!(community_federation_log -> community (community_id));
+joinable!(community_favorite -> community (community_id));
+joinable!(community_favorite -> person (person_id));
 joinable!(community_follower -> community (community_id));
+joinable!(community_language -> community (community_id));
+joinable!(community_language -> language (language_id));
 joinable!(community_follower -> person (person_id));
+joinable!(community_last_viewed -> community (community_id));
+joinable!(community_last_viewed -> person (person_id));
 joinable!(community_moderator -> community (community_id));
 joinable!(community_moderator -> person (person_id));
 joinable!(community_person_ban -> community (community_id));
 joinable!(community_person_ban -> person (person_id));
+joinable!(community_tag -> community (community_id));
 joinable!(local_user -> person (person_id));
+joinable!(local_user_language -> language (language_id));
+joinable!(local_user_language -> local_user (local_user_id));
+joinable!(login_session -> local_user (local_user_id));
 joinable!(mod_add_community -> community (community_id));
 joinable!(mod_ban_from_community -> community (community_id));
+joinable!(mod_edit_community -> community (community_id));
+joinable!(mod_edit_community -> person (mod_person_id));
+joinable!(mod_exempt_community -> community (community_id));
+joinable!(mod_exempt_community -> person (mod_person_id));
+joinable!(mod_feature_post -> person (mod_person_id));
+joinable!(mod_feature_post -> post (post_id));
+joinable!(mod_lock_comment -> comment (comment_id));
+joinable!(mod_lock_comment -> person (mod_person_id));
 joinable!(mod_lock_post -> person (mod_person_id));
 joinable!(mod_lock_post -> post (post_id));
 joinable!(mod_remove_comment -> comment (comment_id));
@@ -546,6 +875,8 @@ joinable!(mod_remove_community -> community (community_id));
 joinable!(mod_remove_community -> person (mod_person_id));
 joinable!(mod_remove_post -> person (mod_person_id));
 joinable!(mod_remove_post -> post (post_id));
+joinable!(mod_sticky_comment -> comment (comment_id));
+joinable!(mod_sticky_comment -> person (mod_person_id));
 joinable!(mod_sticky_post -> person (mod_person_id));
 joinable!(mod_sticky_post -> post (post_id));
 joinable!(password_reset_request -> local_user (local_user_id));
@@ -553,6 +884,10 @@ joinable!(person_aggregates -> person (person_id));
 joinable!(person_ban -> person (person_id));
 joinable!(person_mention -> comment (comment_id));
 joinable!(person_mention -> person (recipient_id));
+joinable!(person_pinned_post -> person (person_id));
+joinable!(person_pinned_post -> post (post_id));
+joinable!(person_post_mention -> person (recipient_id));
+joinable!(person_post_mention -> post (post_id));
 joinable!(post -> community (community_id));
 joinable!(post -> person (creator_id));
 joinable!(post_aggregates -> post (post_id));
@@ -563,11 +898,17 @@ joinable!(post_read -> post (post_id));
 joinable!(post_report -> post (post_id));
 joinable!(post_saved -> person (person_id));
 joinable!(post_saved -> post (post_id));
+joinable!(post_tag -> community_tag (tag_id));
+joinable!(post_tag -> post (post_id));
+joinable!(post_view_log -> person (person_id));
+joinable!(post_view_log -> post (post_id));
 joinable!(site -> person (creator_id));
 joinable!(site_aggregates -> site (site_id));
 
 allow_tables_to_appear_in_same_query!(
   activity,
+  admin_auto_remove,
+  api_token,
   comment,
   comment_aggregates,
   comment_like,
@@ -575,33 +916,56 @@ allow_tables_to_appear_in_same_query!(
   comment_saved,
   community,
   community_aggregates,
+  community_federation_log,
+  community_favorite,
   community_follower,
+  community_language,
+  community_last_viewed,
   community_moderator,
   community_person_ban,
+  community_tag,
+  instance,
+  language,
   local_user,
+  local_user_language,
+  login_session,
   mod_add,
   mod_add_community,
   mod_ban,
   mod_ban_from_community,
+  mod_edit_community,
+  mod_exempt_community,
+  mod_feature_post,
+  mod_lock_comment,
   mod_lock_post,
   mod_remove_comment,
   mod_remove_community,
   mod_remove_post,
+  mod_sticky_comment,
   mod_sticky_post,
   password_reset_request,
   person,
   person_aggregates,
   person_ban,
   person_mention,
+  person_note,
+  person_pinned_post,
+  person_post_mention,
   post,
   post_aggregates,
   post_like,
   post_read,
   post_report,
   post_saved,
+  post_tag,
+  post_view_log,
   private_message,
+  received_activity,
+  registration_question,
   site,
   site_aggregates,
+  spam_rule,
+  task_lock,
   comment_alias_1,
   person_alias_1,
   person_alias_2,
@@ -0,0 +1,23 @@
+use crate::schema::api_token;
+use serde::Serialize;
+
+/// A token with limited scopes which a local user created for a third party app
+#[derive(Queryable, Identifiable, PartialEq, Debug, Clone, Serialize)]
+#[table_name = "api_token"]
+pub struct ApiToken {
+  pub id: i32,
+  pub local_user_id: i32,
+  pub name: String,
+  pub scopes: Vec<String>,
+  pub expires: Option<chrono::NaiveDateTime>,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "api_token"]
+pub struct ApiTokenForm {
+  pub local_user_id: i32,
+  pub name: String,
+  pub scopes: Vec<String>,
+  pub expires: Option<chrono::NaiveDateTime>,
+}
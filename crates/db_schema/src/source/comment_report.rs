@@ -1,4 +1,4 @@
-use crate::{schema::comment_report, source::comment::Comment};
+use crate::{schema::comment_report, source::comment::Comment, CommentId, PersonId};
 use serde::{Deserialize, Serialize};
 
 #[derive(
@@ -8,12 +8,12 @@ use serde::{Deserialize, Serialize};
 #[table_name = "comment_report"]
 pub struct CommentReport {
   pub id: i32,
-  pub creator_id: i32,
-  pub comment_id: i32,
+  pub creator_id: PersonId,
+  pub comment_id: CommentId,
   pub original_comment_text: String,
   pub reason: String,
   pub resolved: bool,
-  pub resolver_id: Option<i32>,
+  pub resolver_id: Option<PersonId>,
   pub published: chrono::NaiveDateTime,
   pub updated: Option<chrono::NaiveDateTime>,
 }
@@ -21,8 +21,8 @@ pub struct CommentReport {
 #[derive(Insertable, AsChangeset, Clone)]
 #[table_name = "comment_report"]
 pub struct CommentReportForm {
-  pub creator_id: i32,
-  pub comment_id: i32,
+  pub creator_id: PersonId,
+  pub comment_id: CommentId,
   pub original_comment_text: String,
   pub reason: String,
 }
@@ -1,17 +1,25 @@
 use crate::{
-  schema::{community, community_follower, community_moderator, community_person_ban},
+  schema::{
+    community,
+    community_follower,
+    community_last_viewed,
+    community_moderator,
+    community_person_ban,
+  },
+  CommunityId,
   DbUrl,
+  PersonId,
 };
 use serde::Serialize;
 
 #[derive(Clone, Queryable, Identifiable, PartialEq, Debug, Serialize)]
 #[table_name = "community"]
 pub struct Community {
-  pub id: i32,
+  pub id: CommunityId,
   pub name: String,
   pub title: String,
   pub description: Option<String>,
-  pub creator_id: i32,
+  pub creator_id: PersonId,
   pub removed: bool,
   pub published: chrono::NaiveDateTime,
   pub updated: Option<chrono::NaiveDateTime>,
@@ -27,17 +35,30 @@ pub struct Community {
   pub followers_url: DbUrl,
   pub inbox_url: DbUrl,
   pub shared_inbox_url: Option<DbUrl>,
+  pub exempt_from_slur_filter: bool,
+  pub downvotes_disabled: bool,
+  pub requires_post_approval: bool,
+  pub theme_color: Option<String>,
+  pub short_description: Option<String>,
+  /// Comment sort of the post pages, stored like `SortType`
+  pub default_comment_sort: i16,
+  /// New posts need at least one of the community tags
+  pub require_post_tag: bool,
+  /// Follows need to be approved by a moderator
+  pub manual_follow_approval: bool,
+  /// When an admin or mod last refreshed the remote community with `RefreshRemoteCommunity`
+  pub manually_refreshed_at: Option<chrono::NaiveDateTime>,
 }
 
 /// A safe representation of community, without the sensitive info
 #[derive(Clone, Queryable, Identifiable, PartialEq, Debug, Serialize)]
 #[table_name = "community"]
 pub struct CommunitySafe {
-  pub id: i32,
+  pub id: CommunityId,
   pub name: String,
   pub title: String,
   pub description: Option<String>,
-  pub creator_id: i32,
+  pub creator_id: PersonId,
   pub removed: bool,
   pub published: chrono::NaiveDateTime,
   pub updated: Option<chrono::NaiveDateTime>,
@@ -47,6 +68,17 @@ pub struct CommunitySafe {
   pub local: bool,
   pub icon: Option<DbUrl>,
   pub banner: Option<DbUrl>,
+  pub exempt_from_slur_filter: bool,
+  pub downvotes_disabled: bool,
+  pub requires_post_approval: bool,
+  pub theme_color: Option<String>,
+  pub short_description: Option<String>,
+  /// Comment sort of the post pages, stored like `SortType`
+  pub default_comment_sort: i16,
+  /// New posts need at least one of the community tags
+  pub require_post_tag: bool,
+  /// Follows need to be approved by a moderator
+  pub manual_follow_approval: bool,
 }
 
 #[derive(Insertable, AsChangeset, Debug)]
@@ -55,7 +87,7 @@ pub struct CommunityForm {
   pub name: String,
   pub title: String,
   pub description: Option<String>,
-  pub creator_id: i32,
+  pub creator_id: PersonId,
   pub removed: Option<bool>,
   pub published: Option<chrono::NaiveDateTime>,
   pub updated: Option<chrono::NaiveDateTime>,
@@ -71,6 +103,13 @@ pub struct CommunityForm {
   pub followers_url: Option<DbUrl>,
   pub inbox_url: Option<DbUrl>,
   pub shared_inbox_url: Option<Option<DbUrl>>,
+  pub downvotes_disabled: Option<bool>,
+  pub requires_post_approval: Option<bool>,
+  pub theme_color: Option<Option<String>>,
+  pub short_description: Option<Option<String>>,
+  pub default_comment_sort: Option<i16>,
+  pub require_post_tag: Option<bool>,
+  pub manual_follow_approval: Option<bool>,
 }
 
 #[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
@@ -78,16 +117,19 @@ pub struct CommunityForm {
 #[table_name = "community_moderator"]
 pub struct CommunityModerator {
   pub id: i32,
-  pub community_id: i32,
-  pub person_id: i32,
+  pub community_id: CommunityId,
+  pub person_id: PersonId,
   pub published: chrono::NaiveDateTime,
+  /// Position in the moderator hierarchy, 0 is the head moderator. Maintained by triggers when
+  /// moderators join or leave.
+  pub rank: i32,
 }
 
 #[derive(Insertable, AsChangeset, Clone)]
 #[table_name = "community_moderator"]
 pub struct CommunityModeratorForm {
-  pub community_id: i32,
-  pub person_id: i32,
+  pub community_id: CommunityId,
+  pub person_id: PersonId,
 }
 
 #[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
@@ -95,16 +137,16 @@ pub struct CommunityModeratorForm {
 #[table_name = "community_person_ban"]
 pub struct CommunityPersonBan {
   pub id: i32,
-  pub community_id: i32,
-  pub person_id: i32,
+  pub community_id: CommunityId,
+  pub person_id: PersonId,
   pub published: chrono::NaiveDateTime,
 }
 
 #[derive(Insertable, AsChangeset, Clone)]
 #[table_name = "community_person_ban"]
 pub struct CommunityPersonBanForm {
-  pub community_id: i32,
-  pub person_id: i32,
+  pub community_id: CommunityId,
+  pub person_id: PersonId,
 }
 
 #[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
@@ -112,16 +154,61 @@ pub struct CommunityPersonBanForm {
 #[table_name = "community_follower"]
 pub struct CommunityFollower {
   pub id: i32,
-  pub community_id: i32,
-  pub person_id: i32,
+  pub community_id: CommunityId,
+  pub person_id: PersonId,
   pub published: chrono::NaiveDateTime,
   pub pending: Option<bool>,
+  /// When the Follow activity was last sent to a remote community
+  pub follow_sent: chrono::NaiveDateTime,
+  /// How often the Follow was sent again, because the remote community didn't accept it
+  pub follow_resends: i32,
+  /// The remote community never accepted the follow
+  pub follow_failed: bool,
+  /// Until when the community doesn't count towards the unread posts of the follower
+  pub mute_until: Option<chrono::NaiveDateTime>,
+  /// The Follow activity of a remote person, to answer their follow request later
+  pub follow_activity_id: Option<DbUrl>,
 }
 
 #[derive(Insertable, AsChangeset, Clone)]
 #[table_name = "community_follower"]
 pub struct CommunityFollowerForm {
-  pub community_id: i32,
-  pub person_id: i32,
+  pub community_id: CommunityId,
+  pub person_id: PersonId,
   pub pending: bool,
 }
+
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
+#[belongs_to(Community)]
+#[table_name = "community_favorite"]
+pub struct CommunityFavorite {
+  pub id: i32,
+  pub community_id: CommunityId,
+  pub person_id: PersonId,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "community_favorite"]
+pub struct CommunityFavoriteForm {
+  pub community_id: CommunityId,
+  pub person_id: PersonId,
+}
+
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
+#[belongs_to(Community)]
+#[table_name = "community_last_viewed"]
+pub struct CommunityLastViewed {
+  pub id: i32,
+  pub community_id: CommunityId,
+  pub person_id: PersonId,
+  pub last_viewed: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "community_last_viewed"]
+pub struct CommunityLastViewedForm {
+  pub community_id: CommunityId,
+  pub person_id: PersonId,
+  pub last_viewed: chrono::NaiveDateTime,
+}
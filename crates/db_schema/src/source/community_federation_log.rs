@@ -0,0 +1,27 @@
+use crate::{schema::community_federation_log, CommunityId};
+use serde::Serialize;
+
+/// An activity which a local community received or sent. Kept for a few days to debug federation.
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Clone)]
+#[table_name = "community_federation_log"]
+pub struct CommunityFederationLog {
+  pub id: i64,
+  pub community_id: CommunityId,
+  pub inbound: bool,
+  pub kind: Option<String>,
+  /// The domain of the sending actor for inbound activities, or of the inbox for outbound ones
+  pub domain: String,
+  /// Why the activity was rejected or couldn't be delivered, `None` on success
+  pub error: Option<String>,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "community_federation_log"]
+pub struct CommunityFederationLogForm {
+  pub community_id: CommunityId,
+  pub inbound: bool,
+  pub kind: Option<String>,
+  pub domain: String,
+  pub error: Option<String>,
+}
@@ -0,0 +1,40 @@
+use crate::{
+  schema::{community_tag, post_tag},
+  CommunityId,
+  PostId,
+};
+use serde::Serialize;
+
+/// A tag which the mods of a community offer for its posts
+#[derive(Clone, Queryable, Identifiable, PartialEq, Debug, Serialize)]
+#[table_name = "community_tag"]
+pub struct CommunityTag {
+  pub id: i32,
+  pub community_id: CommunityId,
+  pub name: String,
+  pub color: Option<String>,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "community_tag"]
+pub struct CommunityTagForm {
+  pub community_id: CommunityId,
+  pub name: String,
+  pub color: Option<String>,
+}
+
+#[derive(Queryable, Identifiable, PartialEq, Debug)]
+#[table_name = "post_tag"]
+pub struct PostTag {
+  pub id: i32,
+  pub post_id: PostId,
+  pub tag_id: i32,
+}
+
+#[derive(Insertable, Clone)]
+#[table_name = "post_tag"]
+pub struct PostTagForm {
+  pub post_id: PostId,
+  pub tag_id: i32,
+}
@@ -0,0 +1,27 @@
+use crate::{schema::instance, PersonId};
+use serde::Serialize;
+
+/// A remote instance which we federate with, and the software it runs according to its nodeinfo
+#[derive(Queryable, Identifiable, PartialEq, Debug, Clone, Serialize)]
+#[table_name = "instance"]
+pub struct Instance {
+  pub id: i32,
+  pub domain: String,
+  pub software: Option<String>,
+  pub version: Option<String>,
+  /// The last time that the nodeinfo of the instance could be fetched
+  pub last_seen: Option<chrono::NaiveDateTime>,
+  /// The last time that fetching the nodeinfo was attempted, successful or not
+  pub last_fetched: Option<chrono::NaiveDateTime>,
+  pub published: chrono::NaiveDateTime,
+  /// When fetching the nodeinfo started to fail, none while the instance is reachable
+  pub unreachable_since: Option<chrono::NaiveDateTime>,
+  /// The service actor which sends the aggregated votes of the instance
+  pub vote_actor_id: Option<PersonId>,
+}
+
+#[derive(Insertable)]
+#[table_name = "instance"]
+pub struct InstanceForm {
+  pub domain: String,
+}
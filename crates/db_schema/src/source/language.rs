@@ -0,0 +1,31 @@
+use crate::{
+  schema::{community_language, language, local_user_language},
+  CommunityId,
+  LanguageId,
+};
+use serde::Serialize;
+
+/// A language which content can be written in, by its ISO 639-1 code
+#[derive(Clone, Queryable, Identifiable, PartialEq, Debug, Serialize)]
+#[table_name = "language"]
+pub struct Language {
+  pub id: LanguageId,
+  pub code: String,
+  pub name: String,
+}
+
+/// A language which a local user wants to read
+#[derive(Insertable, Clone)]
+#[table_name = "local_user_language"]
+pub struct LocalUserLanguageForm {
+  pub local_user_id: i32,
+  pub language_id: LanguageId,
+}
+
+/// A language which is allowed in a community
+#[derive(Insertable, Clone)]
+#[table_name = "community_language"]
+pub struct CommunityLanguageForm {
+  pub community_id: CommunityId,
+  pub language_id: LanguageId,
+}
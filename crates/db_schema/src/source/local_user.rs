@@ -1,11 +1,11 @@
-use crate::schema::local_user;
+use crate::{schema::local_user, PersonId};
 use serde::Serialize;
 
 #[derive(Clone, Queryable, Identifiable, PartialEq, Debug, Serialize)]
 #[table_name = "local_user"]
 pub struct LocalUser {
   pub id: i32,
-  pub person_id: i32,
+  pub person_id: PersonId,
   pub password_encrypted: String,
   pub email: Option<String>,
   pub admin: bool,
@@ -17,13 +17,18 @@ pub struct LocalUser {
   pub show_avatars: bool,
   pub send_notifications_to_email: bool,
   pub matrix_user_id: Option<String>,
+  pub email_digest_frequency: i16,
+  pub last_email_digest: chrono::NaiveDateTime,
+  pub email_unsubscribe_token: Option<String>,
+  pub hide_last_active: bool,
+  pub hide_profile_from_federation: bool,
 }
 
 // TODO redo these, check table defaults
 #[derive(Insertable, AsChangeset, Clone)]
 #[table_name = "local_user"]
 pub struct LocalUserForm {
-  pub person_id: i32,
+  pub person_id: PersonId,
   pub password_encrypted: String,
   pub email: Option<Option<String>>,
   pub admin: Option<bool>,
@@ -35,6 +40,9 @@ pub struct LocalUserForm {
   pub show_avatars: Option<bool>,
   pub send_notifications_to_email: Option<bool>,
   pub matrix_user_id: Option<Option<String>>,
+  pub email_digest_frequency: Option<i16>,
+  pub hide_last_active: Option<bool>,
+  pub hide_profile_from_federation: Option<bool>,
 }
 
 /// A local user view that removes password encrypted
@@ -42,7 +50,7 @@ pub struct LocalUserForm {
 #[table_name = "local_user"]
 pub struct LocalUserSettings {
   pub id: i32,
-  pub person_id: i32,
+  pub person_id: PersonId,
   pub email: Option<String>,
   pub admin: bool,
   pub show_nsfw: bool,
@@ -53,4 +61,7 @@ pub struct LocalUserSettings {
   pub show_avatars: bool,
   pub send_notifications_to_email: bool,
   pub matrix_user_id: Option<String>,
+  pub email_digest_frequency: i16,
+  pub hide_last_active: bool,
+  pub hide_profile_from_federation: bool,
 }
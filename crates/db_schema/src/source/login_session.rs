@@ -0,0 +1,24 @@
+use crate::schema::login_session;
+use serde::Serialize;
+
+/// A login of a local user, so that they can see where they are logged in and revoke it
+#[derive(Queryable, Identifiable, PartialEq, Debug, Clone, Serialize)]
+#[table_name = "login_session"]
+pub struct LoginSession {
+  pub id: i32,
+  pub local_user_id: i32,
+  pub user_agent: Option<String>,
+  /// Only the network part of the address
+  pub ip: Option<String>,
+  pub issued_at: chrono::NaiveDateTime,
+  /// Updated at most once per hour
+  pub last_used: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "login_session"]
+pub struct LoginSessionForm {
+  pub local_user_id: i32,
+  pub user_agent: Option<String>,
+  pub ip: Option<String>,
+}
@@ -1,13 +1,25 @@
-use crate::schema::{
-  mod_add,
-  mod_add_community,
-  mod_ban,
-  mod_ban_from_community,
-  mod_lock_post,
-  mod_remove_comment,
-  mod_remove_community,
-  mod_remove_post,
-  mod_sticky_post,
+use crate::{
+  schema::{
+    admin_auto_remove,
+    mod_add,
+    mod_add_community,
+    mod_ban,
+    mod_ban_from_community,
+    mod_edit_community,
+    mod_exempt_community,
+    mod_feature_post,
+    mod_lock_comment,
+    mod_lock_post,
+    mod_remove_comment,
+    mod_remove_community,
+    mod_remove_post,
+    mod_sticky_comment,
+    mod_sticky_post,
+  },
+  CommentId,
+  CommunityId,
+  PersonId,
+  PostId,
 };
 use serde::Serialize;
 
@@ -15,8 +27,8 @@ use serde::Serialize;
 #[table_name = "mod_remove_post"]
 pub struct ModRemovePost {
   pub id: i32,
-  pub mod_person_id: i32,
-  pub post_id: i32,
+  pub mod_person_id: PersonId,
+  pub post_id: PostId,
   pub reason: Option<String>,
   pub removed: Option<bool>,
   pub when_: chrono::NaiveDateTime,
@@ -25,8 +37,8 @@ pub struct ModRemovePost {
 #[derive(Insertable, AsChangeset)]
 #[table_name = "mod_remove_post"]
 pub struct ModRemovePostForm {
-  pub mod_person_id: i32,
-  pub post_id: i32,
+  pub mod_person_id: PersonId,
+  pub post_id: PostId,
   pub reason: Option<String>,
   pub removed: Option<bool>,
 }
@@ -35,8 +47,8 @@ pub struct ModRemovePostForm {
 #[table_name = "mod_lock_post"]
 pub struct ModLockPost {
   pub id: i32,
-  pub mod_person_id: i32,
-  pub post_id: i32,
+  pub mod_person_id: PersonId,
+  pub post_id: PostId,
   pub locked: Option<bool>,
   pub when_: chrono::NaiveDateTime,
 }
@@ -44,8 +56,8 @@ pub struct ModLockPost {
 #[derive(Insertable, AsChangeset)]
 #[table_name = "mod_lock_post"]
 pub struct ModLockPostForm {
-  pub mod_person_id: i32,
-  pub post_id: i32,
+  pub mod_person_id: PersonId,
+  pub post_id: PostId,
   pub locked: Option<bool>,
 }
 
@@ -53,8 +65,8 @@ pub struct ModLockPostForm {
 #[table_name = "mod_sticky_post"]
 pub struct ModStickyPost {
   pub id: i32,
-  pub mod_person_id: i32,
-  pub post_id: i32,
+  pub mod_person_id: PersonId,
+  pub post_id: PostId,
   pub stickied: Option<bool>,
   pub when_: chrono::NaiveDateTime,
 }
@@ -62,8 +74,46 @@ pub struct ModStickyPost {
 #[derive(Insertable, AsChangeset)]
 #[table_name = "mod_sticky_post"]
 pub struct ModStickyPostForm {
-  pub mod_person_id: i32,
-  pub post_id: i32,
+  pub mod_person_id: PersonId,
+  pub post_id: PostId,
+  pub stickied: Option<bool>,
+}
+
+#[derive(Clone, Queryable, Identifiable, PartialEq, Debug, Serialize)]
+#[table_name = "mod_lock_comment"]
+pub struct ModLockComment {
+  pub id: i32,
+  pub mod_person_id: PersonId,
+  pub comment_id: CommentId,
+  pub locked: Option<bool>,
+  pub reason: Option<String>,
+  pub when_: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "mod_lock_comment"]
+pub struct ModLockCommentForm {
+  pub mod_person_id: PersonId,
+  pub comment_id: CommentId,
+  pub locked: Option<bool>,
+  pub reason: Option<String>,
+}
+
+#[derive(Clone, Queryable, Identifiable, PartialEq, Debug, Serialize)]
+#[table_name = "mod_sticky_comment"]
+pub struct ModStickyComment {
+  pub id: i32,
+  pub mod_person_id: PersonId,
+  pub comment_id: CommentId,
+  pub stickied: Option<bool>,
+  pub when_: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "mod_sticky_comment"]
+pub struct ModStickyCommentForm {
+  pub mod_person_id: PersonId,
+  pub comment_id: CommentId,
   pub stickied: Option<bool>,
 }
 
@@ -71,8 +121,8 @@ pub struct ModStickyPostForm {
 #[table_name = "mod_remove_comment"]
 pub struct ModRemoveComment {
   pub id: i32,
-  pub mod_person_id: i32,
-  pub comment_id: i32,
+  pub mod_person_id: PersonId,
+  pub comment_id: CommentId,
   pub reason: Option<String>,
   pub removed: Option<bool>,
   pub when_: chrono::NaiveDateTime,
@@ -81,8 +131,8 @@ pub struct ModRemoveComment {
 #[derive(Insertable, AsChangeset)]
 #[table_name = "mod_remove_comment"]
 pub struct ModRemoveCommentForm {
-  pub mod_person_id: i32,
-  pub comment_id: i32,
+  pub mod_person_id: PersonId,
+  pub comment_id: CommentId,
   pub reason: Option<String>,
   pub removed: Option<bool>,
 }
@@ -91,31 +141,33 @@ pub struct ModRemoveCommentForm {
 #[table_name = "mod_remove_community"]
 pub struct ModRemoveCommunity {
   pub id: i32,
-  pub mod_person_id: i32,
-  pub community_id: i32,
+  pub mod_person_id: PersonId,
+  pub community_id: CommunityId,
   pub reason: Option<String>,
   pub removed: Option<bool>,
   pub expires: Option<chrono::NaiveDateTime>,
   pub when_: chrono::NaiveDateTime,
+  pub remove_content: bool,
 }
 
 #[derive(Insertable, AsChangeset)]
 #[table_name = "mod_remove_community"]
 pub struct ModRemoveCommunityForm {
-  pub mod_person_id: i32,
-  pub community_id: i32,
+  pub mod_person_id: PersonId,
+  pub community_id: CommunityId,
   pub reason: Option<String>,
   pub removed: Option<bool>,
   pub expires: Option<chrono::NaiveDateTime>,
+  pub remove_content: Option<bool>,
 }
 
 #[derive(Clone, Queryable, Identifiable, PartialEq, Debug, Serialize)]
 #[table_name = "mod_ban_from_community"]
 pub struct ModBanFromCommunity {
   pub id: i32,
-  pub mod_person_id: i32,
-  pub other_person_id: i32,
-  pub community_id: i32,
+  pub mod_person_id: PersonId,
+  pub other_person_id: PersonId,
+  pub community_id: CommunityId,
   pub reason: Option<String>,
   pub banned: Option<bool>,
   pub expires: Option<chrono::NaiveDateTime>,
@@ -125,9 +177,9 @@ pub struct ModBanFromCommunity {
 #[derive(Insertable, AsChangeset)]
 #[table_name = "mod_ban_from_community"]
 pub struct ModBanFromCommunityForm {
-  pub mod_person_id: i32,
-  pub other_person_id: i32,
-  pub community_id: i32,
+  pub mod_person_id: PersonId,
+  pub other_person_id: PersonId,
+  pub community_id: CommunityId,
   pub reason: Option<String>,
   pub banned: Option<bool>,
   pub expires: Option<chrono::NaiveDateTime>,
@@ -137,8 +189,8 @@ pub struct ModBanFromCommunityForm {
 #[table_name = "mod_ban"]
 pub struct ModBan {
   pub id: i32,
-  pub mod_person_id: i32,
-  pub other_person_id: i32,
+  pub mod_person_id: PersonId,
+  pub other_person_id: PersonId,
   pub reason: Option<String>,
   pub banned: Option<bool>,
   pub expires: Option<chrono::NaiveDateTime>,
@@ -148,8 +200,8 @@ pub struct ModBan {
 #[derive(Insertable, AsChangeset)]
 #[table_name = "mod_ban"]
 pub struct ModBanForm {
-  pub mod_person_id: i32,
-  pub other_person_id: i32,
+  pub mod_person_id: PersonId,
+  pub other_person_id: PersonId,
   pub reason: Option<String>,
   pub banned: Option<bool>,
   pub expires: Option<chrono::NaiveDateTime>,
@@ -159,9 +211,9 @@ pub struct ModBanForm {
 #[table_name = "mod_add_community"]
 pub struct ModAddCommunity {
   pub id: i32,
-  pub mod_person_id: i32,
-  pub other_person_id: i32,
-  pub community_id: i32,
+  pub mod_person_id: PersonId,
+  pub other_person_id: PersonId,
+  pub community_id: CommunityId,
   pub removed: Option<bool>,
   pub when_: chrono::NaiveDateTime,
 }
@@ -169,9 +221,9 @@ pub struct ModAddCommunity {
 #[derive(Insertable, AsChangeset)]
 #[table_name = "mod_add_community"]
 pub struct ModAddCommunityForm {
-  pub mod_person_id: i32,
-  pub other_person_id: i32,
-  pub community_id: i32,
+  pub mod_person_id: PersonId,
+  pub other_person_id: PersonId,
+  pub community_id: CommunityId,
   pub removed: Option<bool>,
 }
 
@@ -179,8 +231,8 @@ pub struct ModAddCommunityForm {
 #[table_name = "mod_add"]
 pub struct ModAdd {
   pub id: i32,
-  pub mod_person_id: i32,
-  pub other_person_id: i32,
+  pub mod_person_id: PersonId,
+  pub other_person_id: PersonId,
   pub removed: Option<bool>,
   pub when_: chrono::NaiveDateTime,
 }
@@ -188,7 +240,81 @@ pub struct ModAdd {
 #[derive(Insertable, AsChangeset)]
 #[table_name = "mod_add"]
 pub struct ModAddForm {
-  pub mod_person_id: i32,
-  pub other_person_id: i32,
+  pub mod_person_id: PersonId,
+  pub other_person_id: PersonId,
   pub removed: Option<bool>,
 }
+
+#[derive(Clone, Queryable, Identifiable, PartialEq, Debug, Serialize)]
+#[table_name = "mod_exempt_community"]
+pub struct ModExemptCommunity {
+  pub id: i32,
+  pub mod_person_id: PersonId,
+  pub community_id: CommunityId,
+  pub exempt: Option<bool>,
+  pub when_: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "mod_exempt_community"]
+pub struct ModExemptCommunityForm {
+  pub mod_person_id: PersonId,
+  pub community_id: CommunityId,
+  pub exempt: Option<bool>,
+}
+
+#[derive(Clone, Queryable, Identifiable, PartialEq, Debug, Serialize)]
+#[table_name = "mod_feature_post"]
+pub struct ModFeaturePost {
+  pub id: i32,
+  pub mod_person_id: PersonId,
+  pub post_id: PostId,
+  pub featured: Option<bool>,
+  pub when_: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "mod_feature_post"]
+pub struct ModFeaturePostForm {
+  pub mod_person_id: PersonId,
+  pub post_id: PostId,
+  pub featured: Option<bool>,
+}
+
+#[derive(Clone, Queryable, Identifiable, PartialEq, Debug, Serialize)]
+#[table_name = "mod_edit_community"]
+pub struct ModEditCommunity {
+  pub id: i32,
+  pub mod_person_id: PersonId,
+  pub community_id: CommunityId,
+  pub changed_fields: Vec<String>,
+  pub when_: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "mod_edit_community"]
+pub struct ModEditCommunityForm {
+  pub mod_person_id: PersonId,
+  pub community_id: CommunityId,
+  pub changed_fields: Vec<String>,
+}
+
+/// A post or comment which was removed by a spam rule. `comment_id` is only set for comments, then
+/// `post_id` is the post of the comment.
+#[derive(Clone, Queryable, Identifiable, PartialEq, Debug, Serialize)]
+#[table_name = "admin_auto_remove"]
+pub struct AdminAutoRemove {
+  pub id: i32,
+  pub post_id: PostId,
+  pub comment_id: Option<CommentId>,
+  pub rule_kind: i16,
+  pub when_: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "admin_auto_remove"]
+pub struct AdminAutoRemoveForm {
+  pub post_id: PostId,
+  pub comment_id: Option<CommentId>,
+  pub rule_kind: i16,
+}
@@ -1,13 +1,14 @@
 use crate::{
   schema::{person, person_alias_1, person_alias_2},
   DbUrl,
+  PersonId,
 };
 use serde::Serialize;
 
 #[derive(Clone, Queryable, Identifiable, PartialEq, Debug, Serialize)]
 #[table_name = "person"]
 pub struct Person {
-  pub id: i32,
+  pub id: PersonId,
   pub name: String,
   pub preferred_username: Option<String>,
   pub avatar: Option<DbUrl>,
@@ -24,13 +25,15 @@ pub struct Person {
   pub deleted: bool,
   pub inbox_url: DbUrl,
   pub shared_inbox_url: Option<DbUrl>,
+  /// Only exposed as a coarse `LastActive` bucket
+  pub last_activity_at: Option<chrono::NaiveDateTime>,
 }
 
 /// A safe representation of person, without the sensitive info
 #[derive(Clone, Queryable, Identifiable, PartialEq, Debug, Serialize)]
 #[table_name = "person"]
 pub struct PersonSafe {
-  pub id: i32,
+  pub id: PersonId,
   pub name: String,
   pub preferred_username: Option<String>,
   pub avatar: Option<DbUrl>,
@@ -49,7 +52,7 @@ pub struct PersonSafe {
 #[derive(Clone, Queryable, Identifiable, PartialEq, Debug, Serialize)]
 #[table_name = "person_alias_1"]
 pub struct PersonAlias1 {
-  pub id: i32,
+  pub id: PersonId,
   pub name: String,
   pub preferred_username: Option<String>,
   pub avatar: Option<DbUrl>,
@@ -71,7 +74,7 @@ pub struct PersonAlias1 {
 #[derive(Clone, Queryable, Identifiable, PartialEq, Debug, Serialize)]
 #[table_name = "person_alias_1"]
 pub struct PersonSafeAlias1 {
-  pub id: i32,
+  pub id: PersonId,
   pub name: String,
   pub preferred_username: Option<String>,
   pub avatar: Option<DbUrl>,
@@ -90,7 +93,7 @@ pub struct PersonSafeAlias1 {
 #[derive(Clone, Queryable, Identifiable, PartialEq, Debug, Serialize)]
 #[table_name = "person_alias_2"]
 pub struct PersonAlias2 {
-  pub id: i32,
+  pub id: PersonId,
   pub name: String,
   pub preferred_username: Option<String>,
   pub avatar: Option<DbUrl>,
@@ -112,7 +115,7 @@ pub struct PersonAlias2 {
 #[derive(Clone, Queryable, Identifiable, PartialEq, Debug, Serialize)]
 #[table_name = "person_alias_1"]
 pub struct PersonSafeAlias2 {
-  pub id: i32,
+  pub id: PersonId,
   pub name: String,
   pub preferred_username: Option<String>,
   pub avatar: Option<DbUrl>,
@@ -128,6 +131,46 @@ pub struct PersonSafeAlias2 {
   pub shared_inbox_url: Option<DbUrl>,
 }
 
+/// Shown instead of the name of a deleted account in post and comment views
+pub const DELETED_PERSON_NAME: &str = "[deleted]";
+
+/// The url without path, query and fragment, eg `https://example.com/`
+fn instance_root(url: DbUrl) -> DbUrl {
+  let mut url = url.into_inner();
+  url.set_path("");
+  url.set_query(None);
+  url.set_fragment(None);
+  url.into()
+}
+
+macro_rules! impl_hide_if_deleted {
+  ($type:ident) => {
+    impl $type {
+      /// Hides the profile of a deleted account, so that its old name and avatar aren't shown
+      /// forever. The urls would give away the name too, so they only point to the instance. The
+      /// id is kept, so that comment threads stay intact.
+      ///
+      /// Admin endpoints skip this if the site lets admins see deleted accounts.
+      pub fn hide_if_deleted(mut self) -> Self {
+        if self.deleted {
+          self.name = DELETED_PERSON_NAME.to_string();
+          self.preferred_username = None;
+          self.avatar = None;
+          self.banner = None;
+          self.bio = None;
+          self.actor_id = instance_root(self.actor_id);
+          self.inbox_url = instance_root(self.inbox_url);
+        }
+        self
+      }
+    }
+  };
+}
+
+impl_hide_if_deleted!(PersonSafe);
+impl_hide_if_deleted!(PersonSafeAlias1);
+impl_hide_if_deleted!(PersonSafeAlias2);
+
 #[derive(Insertable, AsChangeset, Clone)]
 #[table_name = "person"]
 pub struct PersonForm {
@@ -148,3 +191,53 @@ pub struct PersonForm {
   pub inbox_url: Option<DbUrl>,
   pub shared_inbox_url: Option<Option<DbUrl>>,
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    naive_now,
+    source::person::{PersonSafe, DELETED_PERSON_NAME},
+    PersonId,
+  };
+  use url::Url;
+
+  #[test]
+  fn test_hide_if_deleted() {
+    let actor_id = Url::parse("https://example.com/u/gerald").unwrap();
+    let person = PersonSafe {
+      id: PersonId(3),
+      name: "gerald".into(),
+      preferred_username: Some("Gerald".into()),
+      avatar: Some(
+        Url::parse("https://example.com/pictrs/image/a.png")
+          .unwrap()
+          .into(),
+      ),
+      banned: false,
+      published: naive_now(),
+      updated: None,
+      actor_id: actor_id.to_owned().into(),
+      bio: Some("hello".into()),
+      local: true,
+      banner: None,
+      deleted: false,
+      inbox_url: actor_id.join("inbox").unwrap().into(),
+      shared_inbox_url: None,
+    };
+
+    assert_eq!(person.to_owned().hide_if_deleted(), person);
+
+    let deleted = PersonSafe {
+      deleted: true,
+      ..person
+    }
+    .hide_if_deleted();
+    assert_eq!(PersonId(3), deleted.id);
+    assert_eq!(DELETED_PERSON_NAME, deleted.name);
+    assert_eq!(None, deleted.preferred_username);
+    assert_eq!(None, deleted.avatar);
+    assert_eq!(None, deleted.bio);
+    assert_eq!("https://example.com/", deleted.actor_id.to_string());
+    assert_eq!("https://example.com/", deleted.inbox_url.to_string());
+  }
+}
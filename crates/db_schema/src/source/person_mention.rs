@@ -1,4 +1,4 @@
-use crate::{schema::person_mention, source::comment::Comment};
+use crate::{schema::person_mention, source::comment::Comment, CommentId, PersonId};
 use serde::Serialize;
 
 #[derive(Clone, Queryable, Associations, Identifiable, PartialEq, Debug, Serialize)]
@@ -6,8 +6,8 @@ use serde::Serialize;
 #[table_name = "person_mention"]
 pub struct PersonMention {
   pub id: i32,
-  pub recipient_id: i32,
-  pub comment_id: i32,
+  pub recipient_id: PersonId,
+  pub comment_id: CommentId,
   pub read: bool,
   pub published: chrono::NaiveDateTime,
 }
@@ -15,7 +15,7 @@ pub struct PersonMention {
 #[derive(Insertable, AsChangeset)]
 #[table_name = "person_mention"]
 pub struct PersonMentionForm {
-  pub recipient_id: i32,
-  pub comment_id: i32,
+  pub recipient_id: PersonId,
+  pub comment_id: CommentId,
   pub read: Option<bool>,
 }
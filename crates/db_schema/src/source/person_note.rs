@@ -0,0 +1,25 @@
+use crate::{schema::person_note, PersonId};
+use serde::Serialize;
+
+/// A private label which a person gave to another person. Only visible to its creator.
+#[derive(Clone, Queryable, Identifiable, PartialEq, Debug, Serialize)]
+#[table_name = "person_note"]
+pub struct PersonNote {
+  pub id: i32,
+  pub creator_person_id: PersonId,
+  pub target_person_id: PersonId,
+  pub note: String,
+  pub color: Option<String>,
+  pub published: chrono::NaiveDateTime,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable, Clone)]
+#[table_name = "person_note"]
+pub struct PersonNoteForm {
+  pub creator_person_id: PersonId,
+  pub target_person_id: PersonId,
+  pub note: String,
+  pub color: Option<String>,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
@@ -0,0 +1,21 @@
+use crate::{schema::person_post_mention, source::post::Post, PersonId, PostId};
+use serde::Serialize;
+
+#[derive(Clone, Queryable, Associations, Identifiable, PartialEq, Debug, Serialize)]
+#[belongs_to(Post)]
+#[table_name = "person_post_mention"]
+pub struct PersonPostMention {
+  pub id: i32,
+  pub recipient_id: PersonId,
+  pub post_id: PostId,
+  pub read: bool,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "person_post_mention"]
+pub struct PersonPostMentionForm {
+  pub recipient_id: PersonId,
+  pub post_id: PostId,
+  pub read: Option<bool>,
+}
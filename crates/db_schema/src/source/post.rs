@@ -1,18 +1,21 @@
 use crate::{
-  schema::{post, post_like, post_read, post_saved},
+  schema::{person_pinned_post, post, post_like, post_read, post_saved},
+  CommunityId,
   DbUrl,
+  PersonId,
+  PostId,
 };
 use serde::Serialize;
 
 #[derive(Clone, Queryable, Identifiable, PartialEq, Debug, Serialize)]
 #[table_name = "post"]
 pub struct Post {
-  pub id: i32,
+  pub id: PostId,
   pub name: String,
   pub url: Option<DbUrl>,
   pub body: Option<String>,
-  pub creator_id: i32,
-  pub community_id: i32,
+  pub creator_id: PersonId,
+  pub community_id: CommunityId,
   pub removed: bool,
   pub locked: bool,
   pub published: chrono::NaiveDateTime,
@@ -26,6 +29,23 @@ pub struct Post {
   pub thumbnail_url: Option<DbUrl>,
   pub ap_id: DbUrl,
   pub local: bool,
+  pub deleted_by_community_delete: bool,
+  pub featured_local: bool,
+  pub removed_by_community_remove: bool,
+  pub approved: Option<bool>,
+  /// The url as compared for cross posts, maintained when the post is saved
+  #[serde(skip)]
+  pub url_normalized: Option<String>,
+  /// When fetching the embed data and thumbnail of the url failed the last time
+  pub metadata_fetch_failed_at: Option<chrono::NaiveDateTime>,
+}
+
+impl Post {
+  /// A local post which no mod approved yet. It is only visible to its creator and the mods, and
+  /// isn't federated. Remote posts are never held back, they can only be flagged for review.
+  pub fn is_awaiting_approval(&self) -> bool {
+    self.local && self.approved != Some(true)
+  }
 }
 
 #[derive(Insertable, AsChangeset)]
@@ -34,8 +54,8 @@ pub struct PostForm {
   pub name: String,
   pub url: Option<DbUrl>,
   pub body: Option<String>,
-  pub creator_id: i32,
-  pub community_id: i32,
+  pub creator_id: PersonId,
+  pub community_id: CommunityId,
   pub removed: Option<bool>,
   pub locked: Option<bool>,
   pub published: Option<chrono::NaiveDateTime>,
@@ -51,13 +71,24 @@ pub struct PostForm {
   pub local: bool,
 }
 
+/// The fetched embed data and thumbnail of the post url, missing values clear the previous ones
+#[derive(AsChangeset, Clone, Default)]
+#[table_name = "post"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct PostMetadataForm {
+  pub embed_title: Option<String>,
+  pub embed_description: Option<String>,
+  pub embed_html: Option<String>,
+  pub thumbnail_url: Option<DbUrl>,
+}
+
 #[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
 #[belongs_to(Post)]
 #[table_name = "post_like"]
 pub struct PostLike {
   pub id: i32,
-  pub post_id: i32,
-  pub person_id: i32,
+  pub post_id: PostId,
+  pub person_id: PersonId,
   pub score: i16,
   pub published: chrono::NaiveDateTime,
 }
@@ -65,8 +96,8 @@ pub struct PostLike {
 #[derive(Insertable, AsChangeset, Clone)]
 #[table_name = "post_like"]
 pub struct PostLikeForm {
-  pub post_id: i32,
-  pub person_id: i32,
+  pub post_id: PostId,
+  pub person_id: PersonId,
   pub score: i16,
 }
 
@@ -75,16 +106,34 @@ pub struct PostLikeForm {
 #[table_name = "post_saved"]
 pub struct PostSaved {
   pub id: i32,
-  pub post_id: i32,
-  pub person_id: i32,
+  pub post_id: PostId,
+  pub person_id: PersonId,
   pub published: chrono::NaiveDateTime,
 }
 
 #[derive(Insertable, AsChangeset)]
 #[table_name = "post_saved"]
 pub struct PostSavedForm {
-  pub post_id: i32,
-  pub person_id: i32,
+  pub post_id: PostId,
+  pub person_id: PersonId,
+}
+
+/// A post which its creator pinned to the top of their profile
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
+#[belongs_to(Post)]
+#[table_name = "person_pinned_post"]
+pub struct PersonPinnedPost {
+  pub id: i32,
+  pub person_id: PersonId,
+  pub post_id: PostId,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "person_pinned_post"]
+pub struct PersonPinnedPostForm {
+  pub person_id: PersonId,
+  pub post_id: PostId,
 }
 
 #[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
@@ -92,14 +141,14 @@ pub struct PostSavedForm {
 #[table_name = "post_read"]
 pub struct PostRead {
   pub id: i32,
-  pub post_id: i32,
-  pub person_id: i32,
+  pub post_id: PostId,
+  pub person_id: PersonId,
   pub published: chrono::NaiveDateTime,
 }
 
 #[derive(Insertable, AsChangeset)]
 #[table_name = "post_read"]
 pub struct PostReadForm {
-  pub post_id: i32,
-  pub person_id: i32,
+  pub post_id: PostId,
+  pub person_id: PersonId,
 }
@@ -1,4 +1,4 @@
-use crate::{schema::post_report, source::post::Post, DbUrl};
+use crate::{schema::post_report, source::post::Post, DbUrl, PersonId, PostId};
 use serde::{Deserialize, Serialize};
 
 #[derive(
@@ -8,14 +8,14 @@ use serde::{Deserialize, Serialize};
 #[table_name = "post_report"]
 pub struct PostReport {
   pub id: i32,
-  pub creator_id: i32,
-  pub post_id: i32,
+  pub creator_id: PersonId,
+  pub post_id: PostId,
   pub original_post_name: String,
   pub original_post_url: Option<DbUrl>,
   pub original_post_body: Option<String>,
   pub reason: String,
   pub resolved: bool,
-  pub resolver_id: Option<i32>,
+  pub resolver_id: Option<PersonId>,
   pub published: chrono::NaiveDateTime,
   pub updated: Option<chrono::NaiveDateTime>,
 }
@@ -23,8 +23,8 @@ pub struct PostReport {
 #[derive(Insertable, AsChangeset, Clone)]
 #[table_name = "post_report"]
 pub struct PostReportForm {
-  pub creator_id: i32,
-  pub post_id: i32,
+  pub creator_id: PersonId,
+  pub post_id: PostId,
   pub original_post_name: String,
   pub original_post_url: Option<DbUrl>,
   pub original_post_body: Option<String>,
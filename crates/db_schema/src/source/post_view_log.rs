@@ -0,0 +1,16 @@
+use crate::{schema::post_view_log, PersonId, PostId};
+
+/// A post which a person viewed on a day. Only the first view of each day counts.
+#[derive(Queryable, PartialEq, Debug, Clone)]
+pub struct PostViewLog {
+  pub post_id: PostId,
+  pub person_id: PersonId,
+  pub viewed_on: chrono::NaiveDate,
+}
+
+#[derive(Insertable)]
+#[table_name = "post_view_log"]
+pub struct PostViewLogForm {
+  pub post_id: PostId,
+  pub person_id: PersonId,
+}
@@ -0,0 +1,18 @@
+use crate::{schema::received_activity, DbUrl};
+use std::fmt::Debug;
+
+/// Id of an activity which was received from a remote instance, used to detect duplicate
+/// deliveries.
+#[derive(Queryable, Identifiable, PartialEq, Debug)]
+#[table_name = "received_activity"]
+pub struct ReceivedActivity {
+  pub id: i64,
+  pub ap_id: DbUrl,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "received_activity"]
+pub struct ReceivedActivityForm {
+  pub ap_id: DbUrl,
+}
@@ -0,0 +1,20 @@
+use crate::schema::registration_question;
+use serde::Serialize;
+
+/// A question which new users have to answer, if the site uses question challenges
+#[derive(Queryable, Identifiable, PartialEq, Debug, Clone, Serialize)]
+#[table_name = "registration_question"]
+pub struct RegistrationQuestion {
+  pub id: i32,
+  pub question: String,
+  /// All accepted answers
+  pub answers: Vec<String>,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "registration_question"]
+pub struct RegistrationQuestionForm {
+  pub question: String,
+  pub answers: Vec<String>,
+}
@@ -1,4 +1,4 @@
-use crate::{schema::site, DbUrl};
+use crate::{schema::site, DbUrl, PersonId};
 use serde::Serialize;
 
 #[derive(Queryable, Identifiable, PartialEq, Debug, Clone, Serialize)]
@@ -7,7 +7,7 @@ pub struct Site {
   pub id: i32,
   pub name: String,
   pub description: Option<String>,
-  pub creator_id: i32,
+  pub creator_id: PersonId,
   pub published: chrono::NaiveDateTime,
   pub updated: Option<chrono::NaiveDateTime>,
   pub enable_downvotes: bool,
@@ -15,6 +15,17 @@ pub struct Site {
   pub enable_nsfw: bool,
   pub icon: Option<DbUrl>,
   pub banner: Option<DbUrl>,
+  pub slur_filter_regex: Option<String>,
+  pub require_local_images: bool,
+  pub max_post_body_length: i32,
+  pub max_comment_length: i32,
+  pub allowed_url_schemes: Vec<String>,
+  pub default_post_listing_type: i16,
+  pub show_deleted_usernames_to_admins: bool,
+  pub federation_mode: i16,
+  pub federate_votes_as: i16,
+  pub registration_challenge: i16,
+  pub proof_of_work_difficulty: i32,
 }
 
 #[derive(Insertable, AsChangeset)]
@@ -22,7 +33,7 @@ pub struct Site {
 pub struct SiteForm {
   pub name: String,
   pub description: Option<String>,
-  pub creator_id: i32,
+  pub creator_id: PersonId,
   pub updated: Option<chrono::NaiveDateTime>,
   pub enable_downvotes: bool,
   pub open_registration: bool,
@@ -30,4 +41,15 @@ pub struct SiteForm {
   // when you want to null out a column, you have to send Some(None)), since sending None means you just don't want to update that column.
   pub icon: Option<Option<DbUrl>>,
   pub banner: Option<Option<DbUrl>>,
+  pub slur_filter_regex: Option<Option<String>>,
+  pub require_local_images: Option<bool>,
+  pub max_post_body_length: Option<i32>,
+  pub max_comment_length: Option<i32>,
+  pub allowed_url_schemes: Option<Vec<String>>,
+  pub default_post_listing_type: Option<i16>,
+  pub show_deleted_usernames_to_admins: Option<bool>,
+  pub federation_mode: Option<i16>,
+  pub federate_votes_as: Option<i16>,
+  pub registration_challenge: Option<i16>,
+  pub proof_of_work_difficulty: Option<i32>,
 }
@@ -0,0 +1,24 @@
+use crate::schema::spam_rule;
+use serde::Serialize;
+
+/// A rule which new posts and comments are checked against. `kind` and `action` are stored as
+/// numbers, `pattern` is only set for url denylists and `threshold` only for the other kinds.
+#[derive(Queryable, Identifiable, PartialEq, Debug, Clone, Serialize)]
+#[table_name = "spam_rule"]
+pub struct SpamRule {
+  pub id: i32,
+  pub kind: i16,
+  pub pattern: Option<String>,
+  pub threshold: Option<i32>,
+  pub action: i16,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "spam_rule"]
+pub struct SpamRuleForm {
+  pub kind: i16,
+  pub pattern: Option<String>,
+  pub threshold: Option<i32>,
+  pub action: i16,
+}
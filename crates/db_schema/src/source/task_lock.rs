@@ -0,0 +1,23 @@
+use crate::schema::task_lock;
+use serde::Serialize;
+
+/// Lock and run times of a scheduled task
+#[derive(Queryable, Identifiable, PartialEq, Debug, Clone, Serialize)]
+#[table_name = "task_lock"]
+pub struct TaskLock {
+  pub id: i32,
+  pub name: String,
+  /// The server which is currently running the task
+  pub locked_by: Option<String>,
+  pub locked_until: Option<chrono::NaiveDateTime>,
+  pub last_run: Option<chrono::NaiveDateTime>,
+  /// Set if the last run failed
+  pub last_error: Option<String>,
+  pub next_run: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "task_lock"]
+pub struct TaskLockForm {
+  pub name: String,
+}
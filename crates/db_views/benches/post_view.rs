@@ -0,0 +1,115 @@
+//! Benchmarks the post listings which are requested most often, with the fixed queries and with
+//! the boxed query which is used for all other listings. They need a seeded database at
+//! `LEMMY_DATABASE_URL`, see `seed.sql`, and use the community with the most posts and the person
+//! with the most subscriptions in it.
+//!
+//! Criterion reports the mean and median only, so the 99th percentile of every listing is printed
+//! before the benchmarks run.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use diesel::{dsl::count_star, *};
+use lemmy_db_queries::{establish_unpooled_connection, ListingType, SortType};
+use lemmy_db_schema::{
+  schema::{community_follower, post},
+  CommunityId,
+  PersonId,
+};
+use lemmy_db_views::post_view::PostQueryBuilder;
+use std::time::{Duration, Instant};
+
+/// How many times each listing is loaded to find the 99th percentile
+const PERCENTILE_SAMPLES: usize = 1000;
+
+struct Listing {
+  name: &'static str,
+  listing_type: ListingType,
+  sort: SortType,
+  community_id: Option<CommunityId>,
+}
+
+fn load(conn: &PgConnection, listing: &Listing, person_id: PersonId, fixed_queries: bool) {
+  PostQueryBuilder::create(conn)
+    .listing_type(&listing.listing_type)
+    .sort(&listing.sort)
+    .community_id(listing.community_id)
+    .my_person_id(person_id)
+    .fixed_queries(fixed_queries)
+    .list()
+    .unwrap();
+}
+
+fn p99(mut f: impl FnMut()) -> Duration {
+  let mut durations = (0..PERCENTILE_SAMPLES)
+    .map(|_| {
+      let start = Instant::now();
+      f();
+      start.elapsed()
+    })
+    .collect::<Vec<Duration>>();
+  durations.sort();
+  durations[PERCENTILE_SAMPLES * 99 / 100]
+}
+
+fn post_listings(c: &mut Criterion) {
+  let conn = establish_unpooled_connection();
+  let community_id = post::table
+    .group_by(post::community_id)
+    .select(post::community_id)
+    .order_by(count_star().desc())
+    .first::<CommunityId>(&conn)
+    .expect("database has no posts");
+  let person_id = community_follower::table
+    .group_by(community_follower::person_id)
+    .select(community_follower::person_id)
+    .order_by(count_star().desc())
+    .first::<PersonId>(&conn)
+    .expect("database has no subscriptions");
+
+  let listings = [
+    Listing {
+      name: "community hot",
+      listing_type: ListingType::Community,
+      sort: SortType::Hot,
+      community_id: Some(community_id),
+    },
+    Listing {
+      name: "community new",
+      listing_type: ListingType::Community,
+      sort: SortType::New,
+      community_id: Some(community_id),
+    },
+    Listing {
+      name: "subscribed hot",
+      listing_type: ListingType::Subscribed,
+      sort: SortType::Hot,
+      community_id: None,
+    },
+    Listing {
+      name: "subscribed new",
+      listing_type: ListingType::Subscribed,
+      sort: SortType::New,
+      community_id: None,
+    },
+  ];
+
+  for listing in &listings {
+    let fixed = p99(|| load(&conn, listing, person_id, true));
+    let boxed = p99(|| load(&conn, listing, person_id, false));
+    println!(
+      "{}: p99 {:?} with the fixed query, {:?} with the boxed query",
+      listing.name, fixed, boxed
+    );
+  }
+
+  for listing in &listings {
+    c.bench_function(listing.name, |b| {
+      b.iter(|| load(&conn, listing, person_id, true))
+    });
+    c.bench_function(&format!("{}, boxed query", listing.name), |b| {
+      b.iter(|| load(&conn, listing, person_id, false))
+    });
+  }
+}
+
+criterion_group!(benches, post_listings);
+criterion_main!(benches);
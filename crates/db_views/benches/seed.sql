@@ -0,0 +1,28 @@
+-- Seeds an empty database for the post listing benchmark with 10000 persons, 1000 communities
+-- and 1M posts from the last year. Every person follows 20 communities.
+--
+--   psql "$LEMMY_DATABASE_URL" -f crates/db_views/benches/seed.sql
+--   cargo bench -p lemmy_db_views
+
+insert into person (name)
+select 'bench_person_' || i
+from generate_series(1, 10000) as i;
+
+insert into community (name, title, creator_id)
+select 'bench_community_' || i, 'Bench community ' || i, (select min(id) from person)
+from generate_series(1, 1000) as i;
+
+insert into community_follower (community_id, person_id)
+select c.id, p.id
+from person p
+join community c on (c.id + p.id) % 50 = 0;
+
+insert into post (name, creator_id, community_id, published)
+select
+  'Bench post ' || i,
+  (select min(id) from person) + i % 10000,
+  (select min(id) from community) + (i * 7) % 1000,
+  now() - (i * interval '30 seconds')
+from generate_series(1, 1000000) as i;
+
+analyze;
@@ -1,9 +1,14 @@
-use diesel::{result::Error, *};
+use diesel::{
+  dsl::{exists, not, sql},
+  result::Error,
+  sql_types::BigInt,
+  *,
+};
 use lemmy_db_queries::{
   aggregates::comment_aggregates::CommentAggregates,
-  functions::hot_rank,
   fuzzy_search,
   limit_and_offset,
+  source::language::UNDETERMINED_LANGUAGE_ID,
   ListingType,
   MaybeOptional,
   SortType,
@@ -20,16 +25,24 @@ use lemmy_db_schema::{
     community,
     community_follower,
     community_person_ban,
+    local_user,
+    local_user_language,
     person,
     person_alias_1,
+    person_note,
     post,
   },
   source::{
     comment::{Comment, CommentAlias1, CommentSaved},
     community::{Community, CommunityFollower, CommunityPersonBan, CommunitySafe},
     person::{Person, PersonAlias1, PersonSafe, PersonSafeAlias1},
+    person_note::PersonNote,
     post::Post,
   },
+  CommentId,
+  CommunityId,
+  PersonId,
+  PostId,
 };
 use serde::Serialize;
 
@@ -45,6 +58,8 @@ pub struct CommentView {
   pub subscribed: bool,                    // Left join to CommunityFollower
   pub saved: bool,                         // Left join to CommentSaved
   pub my_vote: Option<i16>,                // Left join to CommentLike
+  /// The private note of the viewer about the creator
+  pub creator_note: Option<PersonNote>, // Left join to PersonNote
 }
 
 type CommentViewTuple = (
@@ -59,16 +74,17 @@ type CommentViewTuple = (
   Option<CommunityFollower>,
   Option<CommentSaved>,
   Option<i16>,
+  Option<PersonNote>,
 );
 
 impl CommentView {
   pub fn read(
     conn: &PgConnection,
-    comment_id: i32,
-    my_person_id: Option<i32>,
+    comment_id: CommentId,
+    my_person_id: Option<PersonId>,
   ) -> Result<Self, Error> {
     // The left join below will return None in this case
-    let person_id_join = my_person_id.unwrap_or(-1);
+    let person_id_join = my_person_id.unwrap_or(PersonId(-1));
 
     let (
       comment,
@@ -82,6 +98,7 @@ impl CommentView {
       subscribed,
       saved,
       comment_like,
+      creator_note,
     ) = comment::table
       .find(comment_id)
       .inner_join(person::table)
@@ -119,6 +136,13 @@ impl CommentView {
             .and(comment_like::person_id.eq(person_id_join)),
         ),
       )
+      .left_join(
+        person_note::table.on(
+          comment::creator_id
+            .eq(person_note::target_person_id)
+            .and(person_note::creator_person_id.eq(person_id_join)),
+        ),
+      )
       .select((
         comment::all_columns,
         Person::safe_columns_tuple(),
@@ -131,6 +155,7 @@ impl CommentView {
         community_follower::all_columns.nullable(),
         comment_saved::all_columns.nullable(),
         comment_like::score.nullable(),
+        person_note::all_columns.nullable(),
       ))
       .first::<CommentViewTuple>(conn)?;
 
@@ -144,21 +169,22 @@ impl CommentView {
 
     Ok(CommentView {
       comment,
-      recipient,
+      recipient: recipient.map(PersonSafeAlias1::hide_if_deleted),
       post,
-      creator,
+      creator: creator.hide_if_deleted(),
       community,
       counts,
       creator_banned_from_community: creator_banned_from_community.is_some(),
       subscribed: subscribed.is_some(),
       saved: saved.is_some(),
       my_vote,
+      creator_note,
     })
   }
 
   /// Gets the recipient person id.
   /// If there is no parent comment, its the post creator
-  pub fn get_recipient_id(&self) -> i32 {
+  pub fn get_recipient_id(&self) -> PersonId {
     match &self.recipient {
       Some(parent_commenter) => parent_commenter.id,
       None => self.post.creator_id,
@@ -166,17 +192,174 @@ impl CommentView {
   }
 }
 
+/// The boxed query of a `CommentQueryBuilder` with all of its filters, but without order and
+/// limit. Shared by `list()` and `count()`, so that the count matches the listing.
+macro_rules! filtered_comment_list {
+  ($builder:expr, $person_id_join:expr) => {{
+    let builder = &$builder;
+    let person_id_join = $person_id_join;
+    let mut query = comment::table
+      .inner_join(person::table)
+      // recipient here
+      .left_join(comment_alias_1::table.on(comment_alias_1::id.nullable().eq(comment::parent_id)))
+      .left_join(person_alias_1::table.on(person_alias_1::id.eq(comment_alias_1::creator_id)))
+      .inner_join(post::table)
+      .inner_join(community::table.on(post::community_id.eq(community::id)))
+      .inner_join(comment_aggregates::table)
+      .left_join(
+        community_person_ban::table.on(
+          community::id
+            .eq(community_person_ban::community_id)
+            .and(community_person_ban::person_id.eq(comment::creator_id)),
+        ),
+      )
+      .left_join(
+        community_follower::table.on(
+          post::community_id
+            .eq(community_follower::community_id)
+            .and(community_follower::person_id.eq(person_id_join)),
+        ),
+      )
+      .left_join(
+        comment_saved::table.on(
+          comment::id
+            .eq(comment_saved::comment_id)
+            .and(comment_saved::person_id.eq(person_id_join)),
+        ),
+      )
+      .left_join(
+        comment_like::table.on(
+          comment::id
+            .eq(comment_like::comment_id)
+            .and(comment_like::person_id.eq(person_id_join)),
+        ),
+      )
+      .left_join(
+        person_note::table.on(
+          comment::creator_id
+            .eq(person_note::target_person_id)
+            .and(person_note::creator_person_id.eq(person_id_join)),
+        ),
+      )
+      .select((
+        comment::all_columns,
+        Person::safe_columns_tuple(),
+        comment_alias_1::all_columns.nullable(),
+        PersonAlias1::safe_columns_tuple().nullable(),
+        post::all_columns,
+        Community::safe_columns_tuple(),
+        comment_aggregates::all_columns,
+        community_person_ban::all_columns.nullable(),
+        community_follower::all_columns.nullable(),
+        comment_saved::all_columns.nullable(),
+        comment_like::score.nullable(),
+        person_note::all_columns.nullable(),
+      ))
+      .into_boxed();
+
+    // The replies
+    if let Some(recipient_id) = builder.recipient_id {
+      query = query
+        // TODO needs lots of testing
+        .filter(person_alias_1::id.eq(recipient_id)) // Gets the comment replies
+        .or_filter(
+          comment::parent_id
+            .is_null()
+            .and(post::creator_id.eq(recipient_id)),
+        ) // Gets the top level replies
+        .filter(comment::deleted.eq(false))
+        .filter(comment::removed.eq(false));
+    }
+
+    if builder.unread_only {
+      query = query.filter(comment::read.eq(false));
+    }
+
+    if let Some(creator_id) = builder.creator_id {
+      query = query.filter(comment::creator_id.eq(creator_id));
+    };
+
+    if let Some(community_id) = builder.community_id {
+      query = query.filter(post::community_id.eq(community_id));
+    }
+
+    if let Some(community_name) = builder.community_name.to_owned() {
+      query = query
+        .filter(community::name.eq(community_name))
+        .filter(comment::local.eq(true));
+    }
+
+    if let Some(post_id) = builder.post_id {
+      query = query.filter(comment::post_id.eq(post_id));
+    };
+
+    if let Some(search_term) = &builder.search_term {
+      query = query.filter(comment::content.ilike(fuzzy_search(search_term)));
+    };
+
+    query = match builder.listing_type {
+      // ListingType::Subscribed => query.filter(community_follower::subscribed.eq(true)),
+      // Pending follows don't count yet
+      ListingType::Subscribed => query.filter(
+        community_follower::person_id
+          .is_not_null()
+          .and(community_follower::pending.eq(false)),
+      ),
+      ListingType::Local => query.filter(comment::local.eq(true)),
+      _ => query,
+    };
+
+    if !builder.show_nsfw {
+      query = query
+        .filter(post::nsfw.eq(false))
+        .filter(community::nsfw.eq(false));
+    };
+
+    if builder.saved_only {
+      query = query.filter(comment_saved::id.is_not_null());
+    }
+
+    // Only the languages which the viewer reads, if they picked any. Comments in an undetermined
+    // language are always shown.
+    if let Some(my_person_id) = builder.my_person_id {
+      let my_languages = || {
+        local_user_language::table
+          .inner_join(local_user::table)
+          .filter(local_user::person_id.eq(my_person_id))
+          .select(local_user_language::language_id)
+      };
+      query = query.filter(
+        comment::language_id
+          .eq(UNDETERMINED_LANGUAGE_ID)
+          .or(comment::language_id.eq_any(my_languages()))
+          .or(not(exists(my_languages()))),
+      );
+    }
+
+    if let Some(window_start) = builder
+      .sort
+      .top_window_start()
+      .or_else(|| builder.sort.active_window_start())
+    {
+      query = query.filter(comment_aggregates::published.gt(window_start));
+    }
+
+    query
+  }};
+}
+
 pub struct CommentQueryBuilder<'a> {
   conn: &'a PgConnection,
   listing_type: ListingType,
   sort: &'a SortType,
-  community_id: Option<i32>,
+  community_id: Option<CommunityId>,
   community_name: Option<String>,
-  post_id: Option<i32>,
-  creator_id: Option<i32>,
-  recipient_id: Option<i32>,
-  my_person_id: Option<i32>,
+  post_id: Option<PostId>,
+  creator_id: Option<PersonId>,
+  recipient_id: Option<PersonId>,
+  my_person_id: Option<PersonId>,
   search_term: Option<String>,
+  show_nsfw: bool,
   saved_only: bool,
   unread_only: bool,
   page: Option<i64>,
@@ -196,6 +379,7 @@ impl<'a> CommentQueryBuilder<'a> {
       recipient_id: None,
       my_person_id: None,
       search_term: None,
+      show_nsfw: true,
       saved_only: false,
       unread_only: false,
       page: None,
@@ -213,27 +397,27 @@ impl<'a> CommentQueryBuilder<'a> {
     self
   }
 
-  pub fn post_id<T: MaybeOptional<i32>>(mut self, post_id: T) -> Self {
+  pub fn post_id<T: MaybeOptional<PostId>>(mut self, post_id: T) -> Self {
     self.post_id = post_id.get_optional();
     self
   }
 
-  pub fn creator_id<T: MaybeOptional<i32>>(mut self, creator_id: T) -> Self {
+  pub fn creator_id<T: MaybeOptional<PersonId>>(mut self, creator_id: T) -> Self {
     self.creator_id = creator_id.get_optional();
     self
   }
 
-  pub fn recipient_id<T: MaybeOptional<i32>>(mut self, recipient_id: T) -> Self {
+  pub fn recipient_id<T: MaybeOptional<PersonId>>(mut self, recipient_id: T) -> Self {
     self.recipient_id = recipient_id.get_optional();
     self
   }
 
-  pub fn community_id<T: MaybeOptional<i32>>(mut self, community_id: T) -> Self {
+  pub fn community_id<T: MaybeOptional<CommunityId>>(mut self, community_id: T) -> Self {
     self.community_id = community_id.get_optional();
     self
   }
 
-  pub fn my_person_id<T: MaybeOptional<i32>>(mut self, my_person_id: T) -> Self {
+  pub fn my_person_id<T: MaybeOptional<PersonId>>(mut self, my_person_id: T) -> Self {
     self.my_person_id = my_person_id.get_optional();
     self
   }
@@ -248,6 +432,11 @@ impl<'a> CommentQueryBuilder<'a> {
     self
   }
 
+  pub fn show_nsfw(mut self, show_nsfw: bool) -> Self {
+    self.show_nsfw = show_nsfw;
+    self
+  }
+
   pub fn saved_only(mut self, saved_only: bool) -> Self {
     self.saved_only = saved_only;
     self
@@ -268,135 +457,55 @@ impl<'a> CommentQueryBuilder<'a> {
     self
   }
 
-  pub fn list(self) -> Result<Vec<CommentView>, Error> {
-    use diesel::dsl::*;
-
+  /// The number of comments in the listing, on all pages together
+  pub fn count(self) -> Result<i64, Error> {
     // The left join below will return None in this case
-    let person_id_join = self.my_person_id.unwrap_or(-1);
-
-    let mut query = comment::table
-      .inner_join(person::table)
-      // recipient here
-      .left_join(comment_alias_1::table.on(comment_alias_1::id.nullable().eq(comment::parent_id)))
-      .left_join(person_alias_1::table.on(person_alias_1::id.eq(comment_alias_1::creator_id)))
-      .inner_join(post::table)
-      .inner_join(community::table.on(post::community_id.eq(community::id)))
-      .inner_join(comment_aggregates::table)
-      .left_join(
-        community_person_ban::table.on(
-          community::id
-            .eq(community_person_ban::community_id)
-            .and(community_person_ban::person_id.eq(comment::creator_id)),
-        ),
-      )
-      .left_join(
-        community_follower::table.on(
-          post::community_id
-            .eq(community_follower::community_id)
-            .and(community_follower::person_id.eq(person_id_join)),
-        ),
-      )
-      .left_join(
-        comment_saved::table.on(
-          comment::id
-            .eq(comment_saved::comment_id)
-            .and(comment_saved::person_id.eq(person_id_join)),
-        ),
-      )
-      .left_join(
-        comment_like::table.on(
-          comment::id
-            .eq(comment_like::comment_id)
-            .and(comment_like::person_id.eq(person_id_join)),
-        ),
-      )
-      .select((
-        comment::all_columns,
-        Person::safe_columns_tuple(),
-        comment_alias_1::all_columns.nullable(),
-        PersonAlias1::safe_columns_tuple().nullable(),
-        post::all_columns,
-        Community::safe_columns_tuple(),
-        comment_aggregates::all_columns,
-        community_person_ban::all_columns.nullable(),
-        community_follower::all_columns.nullable(),
-        comment_saved::all_columns.nullable(),
-        comment_like::score.nullable(),
-      ))
-      .into_boxed();
-
-    // The replies
-    if let Some(recipient_id) = self.recipient_id {
-      query = query
-        // TODO needs lots of testing
-        .filter(person_alias_1::id.eq(recipient_id)) // Gets the comment replies
-        .or_filter(
-          comment::parent_id
-            .is_null()
-            .and(post::creator_id.eq(recipient_id)),
-        ) // Gets the top level replies
-        .filter(comment::deleted.eq(false))
-        .filter(comment::removed.eq(false));
-    }
-
-    if self.unread_only {
-      query = query.filter(comment::read.eq(false));
-    }
-
-    if let Some(creator_id) = self.creator_id {
-      query = query.filter(comment::creator_id.eq(creator_id));
-    };
-
-    if let Some(community_id) = self.community_id {
-      query = query.filter(post::community_id.eq(community_id));
-    }
-
-    if let Some(community_name) = self.community_name {
-      query = query
-        .filter(community::name.eq(community_name))
-        .filter(comment::local.eq(true));
-    }
-
-    if let Some(post_id) = self.post_id {
-      query = query.filter(comment::post_id.eq(post_id));
-    };
+    let person_id_join = self.my_person_id.unwrap_or(PersonId(-1));
+    filtered_comment_list!(self, person_id_join)
+      .count()
+      .get_result(self.conn)
+  }
 
-    if let Some(search_term) = self.search_term {
-      query = query.filter(comment::content.ilike(fuzzy_search(&search_term)));
-    };
+  pub fn list(self) -> Result<Vec<CommentView>, Error> {
+    // The left join below will return None in this case
+    let person_id_join = self.my_person_id.unwrap_or(PersonId(-1));
 
-    query = match self.listing_type {
-      // ListingType::Subscribed => query.filter(community_follower::subscribed.eq(true)),
-      ListingType::Subscribed => query.filter(community_follower::person_id.is_not_null()), // TODO could be this: and(community_follower::person_id.eq(person_id_join)),
-      ListingType::Local => query.filter(community::local.eq(true)),
-      _ => query,
-    };
+    let mut query = filtered_comment_list!(self, person_id_join);
 
-    if self.saved_only {
-      query = query.filter(comment_saved::id.is_not_null());
+    // The stickied comment comes first among the top-level comments of a post, whatever the sort
+    if self.post_id.is_some() {
+      query = query.order_by(comment::stickied.and(comment::parent_id.is_null()).desc());
     }
 
     query = match self.sort {
-      SortType::Hot | SortType::Active => query
-        .order_by(hot_rank(comment_aggregates::score, comment_aggregates::published).desc())
+      SortType::Hot
+      | SortType::Active
+      | SortType::ActiveDaily
+      | SortType::ActiveWeekly
+      | SortType::ActiveMonthly
+      | SortType::ActiveSixMonths => query
+        .then_order_by(comment_aggregates::hot_rank.desc())
         .then_order_by(comment_aggregates::published.desc()),
-      SortType::New | SortType::MostComments | SortType::NewComments => {
-        query.order_by(comment::published.desc())
+      SortType::New | SortType::MostComments | SortType::NewComments | SortType::MostViews => {
+        query.then_order_by(comment::published.desc())
       }
-      SortType::TopAll => query.order_by(comment_aggregates::score.desc()),
-      SortType::TopYear => query
-        .filter(comment::published.gt(now - 1.years()))
-        .order_by(comment_aggregates::score.desc()),
-      SortType::TopMonth => query
-        .filter(comment::published.gt(now - 1.months()))
-        .order_by(comment_aggregates::score.desc()),
-      SortType::TopWeek => query
-        .filter(comment::published.gt(now - 1.weeks()))
-        .order_by(comment_aggregates::score.desc()),
-      SortType::TopDay => query
-        .filter(comment::published.gt(now - 1.days()))
-        .order_by(comment_aggregates::score.desc()),
+      SortType::Old => query.then_order_by(comment::published.asc()),
+      // All top-level comments come first, so that every page of a post continues the same order
+      SortType::QandA => query
+        .then_order_by(comment::parent_id.is_not_null())
+        .then_order_by(
+          sql::<BigInt>("case when comment.parent_id is null then comment_aggregates.score end")
+            .desc(),
+        )
+        .then_order_by(comment::published.asc()),
+      SortType::TopAll
+      | SortType::TopYear
+      | SortType::TopMonth
+      | SortType::TopWeek
+      | SortType::TopDay => query.then_order_by(comment_aggregates::score.desc()),
     };
+    // Makes the order unique, so that pages don't overlap
+    query = query.then_order_by(comment::id);
 
     let (limit, offset) = limit_and_offset(self.page, self.limit);
 
@@ -417,8 +526,8 @@ impl ViewToVec for CommentView {
       .iter()
       .map(|a| Self {
         comment: a.0.to_owned(),
-        creator: a.1.to_owned(),
-        recipient: a.3.to_owned(),
+        creator: a.1.to_owned().hide_if_deleted(),
+        recipient: a.3.to_owned().map(PersonSafeAlias1::hide_if_deleted),
         post: a.4.to_owned(),
         community: a.5.to_owned(),
         counts: a.6.to_owned(),
@@ -426,6 +535,7 @@ impl ViewToVec for CommentView {
         subscribed: a.8.is_some(),
         saved: a.9.is_some(),
         my_vote: a.10,
+        creator_note: a.11.to_owned(),
       })
       .collect::<Vec<Self>>()
   }
@@ -437,6 +547,7 @@ mod tests {
   use lemmy_db_queries::{
     aggregates::comment_aggregates::CommentAggregates,
     establish_unpooled_connection,
+    source::comment::Comment_,
     Crud,
     Likeable,
   };
@@ -489,6 +600,13 @@ mod tests {
       followers_url: None,
       inbox_url: None,
       shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
     };
 
     let inserted_community = Community::create(&conn, &new_community).unwrap();
@@ -546,6 +664,7 @@ mod tests {
     let expected_comment_view_no_person = CommentView {
       creator_banned_from_community: false,
       my_vote: None,
+      creator_note: None,
       subscribed: false,
       saved: false,
       comment: Comment {
@@ -561,6 +680,11 @@ mod tests {
         ap_id: inserted_comment.ap_id,
         updated: None,
         local: true,
+        deleted_by_community_delete: false,
+        removed_by_community_remove: false,
+        language_id: UNDETERMINED_LANGUAGE_ID,
+        locked: false,
+        stickied: false,
       },
       creator: PersonSafe {
         id: inserted_person.id,
@@ -599,6 +723,12 @@ mod tests {
         thumbnail_url: None,
         ap_id: inserted_post.ap_id.to_owned(),
         local: true,
+        deleted_by_community_delete: false,
+        featured_local: false,
+        removed_by_community_remove: false,
+        approved: Some(true),
+        url_normalized: None,
+        metadata_fetch_failed_at: None,
       },
       community: CommunitySafe {
         id: inserted_community.id,
@@ -615,6 +745,14 @@ mod tests {
         updated: None,
         banner: None,
         published: inserted_community.published,
+        exempt_from_slur_filter: false,
+        downvotes_disabled: false,
+        requires_post_approval: false,
+        theme_color: None,
+        short_description: None,
+        default_comment_sort: SortType::New as i16,
+        require_post_tag: false,
+        manual_follow_approval: false,
       },
       counts: CommentAggregates {
         id: agg.id,
@@ -623,6 +761,8 @@ mod tests {
         upvotes: 1,
         downvotes: 0,
         published: agg.published,
+        hot_rank: agg.hot_rank,
+        child_count: 0,
       },
     };
 
@@ -657,4 +797,267 @@ mod tests {
     assert_eq!(1, num_deleted);
     assert_eq!(1, like_removed);
   }
+
+  #[test]
+  #[serial]
+  fn test_local_listing() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "local_listing_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "local_listing_community".into(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let new_post = PostForm {
+      name: "A test post".into(),
+      url: None,
+      body: None,
+      creator_id: inserted_person.id,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      updated: None,
+      nsfw: false,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      thumbnail_url: None,
+      ap_id: None,
+      local: true,
+      published: None,
+    };
+    let inserted_post = Post::create(&conn, &new_post).unwrap();
+
+    let comment_form = |content: &str, local: bool| CommentForm {
+      content: content.into(),
+      creator_id: inserted_person.id,
+      post_id: inserted_post.id,
+      parent_id: None,
+      removed: None,
+      deleted: None,
+      read: None,
+      published: None,
+      updated: None,
+      ap_id: None,
+      local,
+    };
+    let local_comment = Comment::create(&conn, &comment_form("local comment", true)).unwrap();
+    // Federated in from another instance, although the community is local
+    let federated_comment =
+      Comment::create(&conn, &comment_form("federated comment", false)).unwrap();
+
+    let list = |listing_type: ListingType| {
+      let mut ids = CommentQueryBuilder::create(&conn)
+        .listing_type(listing_type)
+        .post_id(inserted_post.id)
+        .list()
+        .unwrap()
+        .iter()
+        .map(|c| c.comment.id)
+        .collect::<Vec<_>>();
+      ids.sort_by_key(|id| id.0);
+      ids
+    };
+    let all_listing = list(ListingType::All);
+    let local_listing = list(ListingType::Local);
+
+    Comment::delete(&conn, local_comment.id).unwrap();
+    Comment::delete(&conn, federated_comment.id).unwrap();
+    Post::delete(&conn, inserted_post.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(vec![local_comment.id, federated_comment.id], all_listing);
+    assert_eq!(vec![local_comment.id], local_listing);
+  }
+
+  #[test]
+  #[serial]
+  fn test_old_and_qanda_sort() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "qanda_sort_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "qanda_sort_community".into(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: Some(SortType::QandA as i16),
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let new_post = PostForm {
+      name: "An AMA".into(),
+      url: None,
+      body: None,
+      creator_id: inserted_person.id,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      updated: None,
+      nsfw: false,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      thumbnail_url: None,
+      ap_id: None,
+      local: true,
+      published: None,
+    };
+    let inserted_post = Post::create(&conn, &new_post).unwrap();
+
+    let comment_form = |content: &str, parent_id: Option<CommentId>| CommentForm {
+      content: content.into(),
+      creator_id: inserted_person.id,
+      post_id: inserted_post.id,
+      parent_id,
+      removed: None,
+      deleted: None,
+      read: None,
+      published: None,
+      updated: None,
+      ap_id: None,
+      local: true,
+    };
+    let question_1 = Comment::create(&conn, &comment_form("question 1", None)).unwrap();
+    let question_2 = Comment::create(&conn, &comment_form("question 2", None)).unwrap();
+    let answer_1 = Comment::create(&conn, &comment_form("answer 1", Some(question_1.id))).unwrap();
+    let answer_2 = Comment::create(&conn, &comment_form("answer 2", Some(question_1.id))).unwrap();
+
+    // The second question got more votes
+    let comment_like_form = CommentLikeForm {
+      comment_id: question_2.id,
+      post_id: inserted_post.id,
+      person_id: inserted_person.id,
+      score: 1,
+    };
+    CommentLike::like(&conn, &comment_like_form).unwrap();
+
+    let list = |sort: SortType| {
+      CommentQueryBuilder::create(&conn)
+        .sort(&sort)
+        .post_id(inserted_post.id)
+        .list()
+        .unwrap()
+        .iter()
+        .map(|c| c.comment.id)
+        .collect::<Vec<_>>()
+    };
+    let old_listing = list(SortType::Old);
+    let qanda_listing = list(SortType::QandA);
+    let default_comment_sort = Community::read(&conn, inserted_community.id)
+      .unwrap()
+      .default_comment_sort;
+
+    // A stickied comment goes first, whatever the sort
+    Comment::update_stickied(&conn, question_2.id, true).unwrap();
+    let stickied_old_listing = list(SortType::Old);
+
+    Post::delete(&conn, inserted_post.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(
+      vec![question_1.id, question_2.id, answer_1.id, answer_2.id],
+      old_listing
+    );
+    assert_eq!(
+      vec![question_2.id, question_1.id, answer_1.id, answer_2.id],
+      qanda_listing
+    );
+    assert_eq!(SortType::QandA, SortType::from_i16(default_comment_sort));
+    assert_eq!(
+      vec![question_2.id, question_1.id, answer_1.id, answer_2.id],
+      stickied_old_listing
+    );
+  }
 }
@@ -6,6 +6,7 @@ use lemmy_db_schema::{
     local_user::{LocalUser, LocalUserSettings},
     person::{Person, PersonSafe},
   },
+  PersonId,
 };
 use serde::Serialize;
 
@@ -37,7 +38,7 @@ impl LocalUserView {
     })
   }
 
-  pub fn read_person(conn: &PgConnection, person_id: i32) -> Result<Self, Error> {
+  pub fn read_person(conn: &PgConnection, person_id: PersonId) -> Result<Self, Error> {
     let (local_user, person, counts) = local_user::table
       .filter(person::id.eq(person_id))
       .inner_join(person::table)
@@ -8,6 +8,7 @@ use lemmy_db_schema::{
     post::Post,
     post_report::PostReport,
   },
+  CommunityId,
 };
 use serde::Serialize;
 
@@ -34,7 +35,12 @@ impl PostReportView {
   /// returns the PostReportView for the provided report_id
   ///
   /// * `report_id` - the report id to obtain
-  pub fn read(conn: &PgConnection, report_id: i32) -> Result<Self, Error> {
+  /// * `show_deleted_persons` - whether to show the profiles of deleted accounts, for admins
+  pub fn read(
+    conn: &PgConnection,
+    report_id: i32,
+    show_deleted_persons: bool,
+  ) -> Result<Self, Error> {
     let (post_report, post, community, creator, post_creator, resolver) = post_report::table
       .find(report_id)
       .inner_join(post::table)
@@ -54,22 +60,40 @@ impl PostReportView {
       ))
       .first::<PostReportViewTuple>(conn)?;
 
-    Ok(Self {
+    let report = Self {
       post_report,
       post,
       community,
       creator,
       post_creator,
       resolver,
-    })
+    };
+    if show_deleted_persons {
+      Ok(report)
+    } else {
+      Ok(report.hide_deleted_persons())
+    }
+  }
+
+  /// Hides the profiles of deleted accounts, like in post and comment views
+  fn hide_deleted_persons(self) -> Self {
+    Self {
+      creator: self.creator.hide_if_deleted(),
+      post_creator: self.post_creator.hide_if_deleted(),
+      resolver: self.resolver.map(PersonSafeAlias2::hide_if_deleted),
+      ..self
+    }
   }
 
   /// returns the current unresolved post report count for the supplied community ids
   ///
-  /// * `community_ids` - a Vec<i32> of community_ids to get a count for
+  /// * `community_ids` - a Vec<CommunityId> of community_ids to get a count for
   /// TODO this eq_any is a bad way to do this, would be better to join to communitymoderator
   /// for a person id
-  pub fn get_report_count(conn: &PgConnection, community_ids: &[i32]) -> Result<i64, Error> {
+  pub fn get_report_count(
+    conn: &PgConnection,
+    community_ids: &[CommunityId],
+  ) -> Result<i64, Error> {
     use diesel::dsl::*;
     post_report::table
       .inner_join(post::table)
@@ -81,14 +105,34 @@ impl PostReportView {
       .select(count(post_report::id))
       .first::<i64>(conn)
   }
+
+  /// Returns the number of unresolved post reports of each community. Communities without reports
+  /// are left out.
+  pub fn get_report_counts_by_community(
+    conn: &PgConnection,
+    community_ids: &[CommunityId],
+  ) -> Result<Vec<(CommunityId, i64)>, Error> {
+    use diesel::dsl::*;
+    post_report::table
+      .inner_join(post::table)
+      .filter(
+        post_report::resolved
+          .eq(false)
+          .and(post::community_id.eq_any(community_ids)),
+      )
+      .group_by(post::community_id)
+      .select((post::community_id, count(post_report::id)))
+      .load::<(CommunityId, i64)>(conn)
+  }
 }
 
 pub struct PostReportQueryBuilder<'a> {
   conn: &'a PgConnection,
-  community_ids: Option<Vec<i32>>, // TODO bad way to do this
+  community_ids: Option<Vec<CommunityId>>, // TODO bad way to do this
   page: Option<i64>,
   limit: Option<i64>,
   resolved: Option<bool>,
+  show_deleted_persons: bool,
 }
 
 impl<'a> PostReportQueryBuilder<'a> {
@@ -99,10 +143,11 @@ impl<'a> PostReportQueryBuilder<'a> {
       page: None,
       limit: None,
       resolved: Some(false),
+      show_deleted_persons: false,
     }
   }
 
-  pub fn community_ids<T: MaybeOptional<Vec<i32>>>(mut self, community_ids: T) -> Self {
+  pub fn community_ids<T: MaybeOptional<Vec<CommunityId>>>(mut self, community_ids: T) -> Self {
     self.community_ids = community_ids.get_optional();
     self
   }
@@ -122,6 +167,12 @@ impl<'a> PostReportQueryBuilder<'a> {
     self
   }
 
+  /// Shows the profiles of deleted accounts unchanged, which only admins can see
+  pub fn show_deleted_persons(mut self, show_deleted_persons: bool) -> Self {
+    self.show_deleted_persons = show_deleted_persons;
+    self
+  }
+
   pub fn list(self) -> Result<Vec<PostReportView>, Error> {
     let mut query = post_report::table
       .inner_join(post::table)
@@ -157,7 +208,14 @@ impl<'a> PostReportQueryBuilder<'a> {
       .offset(offset)
       .load::<PostReportViewTuple>(self.conn)?;
 
-    Ok(PostReportView::from_tuple_to_vec(res))
+    let mut reports = PostReportView::from_tuple_to_vec(res);
+    if !self.show_deleted_persons {
+      reports = reports
+        .into_iter()
+        .map(PostReportView::hide_deleted_persons)
+        .collect();
+    }
+    Ok(reports)
   }
 }
 
@@ -1,7 +1,12 @@
-use diesel::{pg::Pg, result::Error, *};
+use chrono::NaiveDateTime;
+use diesel::{
+  dsl::{max, min, not},
+  pg::Pg,
+  result::Error,
+  *,
+};
 use lemmy_db_queries::{
   aggregates::post_aggregates::PostAggregates,
-  functions::hot_rank,
   fuzzy_search,
   limit_and_offset,
   ListingType,
@@ -13,23 +18,34 @@ use lemmy_db_queries::{
 use lemmy_db_schema::{
   schema::{
     community,
+    community_favorite,
     community_follower,
+    community_moderator,
     community_person_ban,
+    local_user,
     person,
+    person_note,
+    person_pinned_post,
     post,
     post_aggregates,
     post_like,
     post_read,
     post_saved,
+    post_tag,
   },
   source::{
     community::{Community, CommunityFollower, CommunityPersonBan, CommunitySafe},
     person::{Person, PersonSafe},
+    person_note::PersonNote,
     post::{Post, PostRead, PostSaved},
   },
+  CommunityId,
+  PersonId,
+  PostId,
 };
 use log::debug;
-use serde::Serialize;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, PartialEq, Serialize, Clone)]
 pub struct PostView {
@@ -42,6 +58,8 @@ pub struct PostView {
   pub saved: bool,          // Left join to PostSaved
   pub read: bool,           // Left join to PostRead
   pub my_vote: Option<i16>, // Left join to PostLike
+  /// The private note of the viewer about the creator
+  pub creator_note: Option<PersonNote>, // Left join to PersonNote
 }
 
 type PostViewTuple = (
@@ -54,12 +72,127 @@ type PostViewTuple = (
   Option<PostSaved>,
   Option<PostRead>,
   Option<i16>,
+  Option<PersonNote>,
 );
 
+/// The joins and columns of a post listing, shared by the boxed and the fixed queries
+macro_rules! post_list_query {
+  ($person_id_join:expr) => {{
+    let person_id_join = $person_id_join;
+    post::table
+      .inner_join(person::table)
+      .inner_join(community::table)
+      .left_join(
+        community_person_ban::table.on(
+          post::community_id
+            .eq(community_person_ban::community_id)
+            .and(community_person_ban::person_id.eq(post::creator_id)),
+        ),
+      )
+      .inner_join(post_aggregates::table)
+      .left_join(
+        community_follower::table.on(
+          post::community_id
+            .eq(community_follower::community_id)
+            .and(community_follower::person_id.eq(person_id_join)),
+        ),
+      )
+      .left_join(
+        post_saved::table.on(
+          post::id
+            .eq(post_saved::post_id)
+            .and(post_saved::person_id.eq(person_id_join)),
+        ),
+      )
+      .left_join(
+        post_read::table.on(
+          post::id
+            .eq(post_read::post_id)
+            .and(post_read::person_id.eq(person_id_join)),
+        ),
+      )
+      .left_join(
+        post_like::table.on(
+          post::id
+            .eq(post_like::post_id)
+            .and(post_like::person_id.eq(person_id_join)),
+        ),
+      )
+      .left_join(
+        person_note::table.on(
+          post::creator_id
+            .eq(person_note::target_person_id)
+            .and(person_note::creator_person_id.eq(person_id_join)),
+        ),
+      )
+      .select((
+        post::all_columns,
+        Person::safe_columns_tuple(),
+        Community::safe_columns_tuple(),
+        community_person_ban::all_columns.nullable(),
+        post_aggregates::all_columns,
+        community_follower::all_columns.nullable(),
+        post_saved::all_columns.nullable(),
+        post_read::all_columns.nullable(),
+        post_like::score.nullable(),
+        person_note::all_columns.nullable(),
+      ))
+  }};
+}
+
+/// Hides the posts which are awaiting approval, except from their creator and the community mods
+macro_rules! visible_to {
+  ($person_id_join:expr) => {{
+    post::approved
+      .eq(true)
+      .or(post::local.eq(false))
+      .or(post::creator_id.eq($person_id_join))
+      .or(
+        post::community_id.eq_any(
+          community_moderator::table
+            .select(community_moderator::community_id)
+            .filter(community_moderator::person_id.eq($person_id_join)),
+        ),
+      )
+      .or(dsl::exists(
+        local_user::table
+          .filter(local_user::person_id.eq($person_id_join))
+          .filter(local_user::admin.eq(true)),
+      ))
+  }};
+}
+
+/// The filters which every post listing applies, shared by the boxed and the fixed queries
+macro_rules! post_list_filters {
+  ($query:expr, $show_nsfw:expr) => {{
+    let show_nsfw: bool = $show_nsfw;
+    $query
+      .filter(post::removed.eq(false))
+      .filter(post::deleted.eq(false))
+      .filter(community::removed.eq(false))
+      .filter(community::deleted.eq(false))
+      .filter(post::nsfw.eq(false).or(show_nsfw))
+      .filter(community::nsfw.eq(false).or(show_nsfw))
+  }};
+}
+
+/// Only the posts in communities which the person follows, not counting pending follows
+macro_rules! subscribed {
+  () => {{
+    community_follower::person_id
+      .is_not_null()
+      .and(community_follower::pending.eq(false))
+  }};
+}
+
 impl PostView {
-  pub fn read(conn: &PgConnection, post_id: i32, my_person_id: Option<i32>) -> Result<Self, Error> {
+  pub fn read(
+    conn: &PgConnection,
+    post_id: PostId,
+    my_person_id: Option<PersonId>,
+  ) -> Result<Self, Error> {
     // The left join below will return None in this case
-    let person_id_join = my_person_id.unwrap_or(-1);
+    let person_id_join = my_person_id.unwrap_or(PersonId(-1));
 
     let (
       post,
@@ -71,6 +204,7 @@ impl PostView {
       saved,
       read,
       post_like,
+      creator_note,
     ) = post::table
       .find(post_id)
       .inner_join(person::table)
@@ -111,6 +245,13 @@ impl PostView {
             .and(post_like::person_id.eq(person_id_join)),
         ),
       )
+      .left_join(
+        person_note::table.on(
+          post::creator_id
+            .eq(person_note::target_person_id)
+            .and(person_note::creator_person_id.eq(person_id_join)),
+        ),
+      )
       .select((
         post::all_columns,
         Person::safe_columns_tuple(),
@@ -121,6 +262,7 @@ impl PostView {
         post_saved::all_columns.nullable(),
         post_read::all_columns.nullable(),
         post_like::score.nullable(),
+        person_note::all_columns.nullable(),
       ))
       .first::<PostViewTuple>(conn)?;
 
@@ -134,7 +276,7 @@ impl PostView {
 
     Ok(PostView {
       post,
-      creator,
+      creator: creator.hide_if_deleted(),
       community,
       creator_banned_from_community: creator_banned_from_community.is_some(),
       counts,
@@ -142,25 +284,354 @@ impl PostView {
       saved: saved.is_some(),
       read: read.is_some(),
       my_vote,
+      creator_note,
     })
   }
+
+  /// Reads a random visible post of the community, or `None` if it has none.
+  ///
+  /// A random id between the smallest and the largest id of the visible posts is picked, and the
+  /// first visible post from there on is taken. Unlike counting the posts or `ORDER BY random()`,
+  /// this only looks up a few entries of the index on `post (community_id, id)`. Posts which
+  /// follow a gap in the ids, for example of posts in other communities, are picked more often.
+  pub fn read_random_in_community(
+    conn: &PgConnection,
+    community_id: CommunityId,
+    my_person_id: Option<PersonId>,
+    show_nsfw: bool,
+  ) -> Result<Option<Self>, Error> {
+    let candidates = || {
+      post::table
+        .filter(post::community_id.eq(community_id))
+        .filter(post::removed.eq(false))
+        .filter(post::deleted.eq(false))
+        .filter(post::nsfw.eq(false).or(show_nsfw))
+        .filter(post::approved.eq(true).or(post::local.eq(false)))
+        .filter(not(
+          post::creator_id.eq_any(
+            community_person_ban::table
+              .select(community_person_ban::person_id)
+              .filter(community_person_ban::community_id.eq(community_id)),
+          ),
+        ))
+        .filter(not(
+          post::creator_id.eq_any(
+            person::table
+              .select(person::id)
+              .filter(person::banned.eq(true)),
+          ),
+        ))
+    };
+    let (min_id, max_id) = match candidates()
+      .select((min(post::id), max(post::id)))
+      .first::<(Option<PostId>, Option<PostId>)>(conn)?
+    {
+      (Some(min_id), Some(max_id)) => (min_id, max_id),
+      _ => return Ok(None),
+    };
+    let random_id = PostId(rand::thread_rng().gen_range(min_id.0..=max_id.0));
+    let post_id = candidates()
+      .select(post::id)
+      .filter(post::id.ge(random_id))
+      .order_by(post::id)
+      .first::<PostId>(conn)
+      .optional()?;
+
+    match post_id {
+      // Deleted in the meantime
+      None => Ok(None),
+      Some(post_id) => Self::read(conn, post_id, my_person_id).map(Some),
+    }
+  }
+
+  /// Other posts which link to the same page as `post`, in any community which the person can
+  /// see, with the highest score first. Posts without a link have none.
+  pub fn list_cross_posts(
+    conn: &PgConnection,
+    post: &Post,
+    my_person_id: Option<PersonId>,
+    show_nsfw: bool,
+  ) -> Result<Vec<Self>, Error> {
+    let url_normalized = match &post.url_normalized {
+      Some(url_normalized) => url_normalized.to_owned(),
+      None => return Ok(Vec::new()),
+    };
+    // The left join below will return None in this case
+    let person_id_join = my_person_id.unwrap_or(PersonId(-1));
+
+    let res = post_list_filters!(post_list_query!(person_id_join), show_nsfw)
+      .filter(post::url_normalized.eq(url_normalized))
+      .filter(post::id.ne(post.id))
+      .filter(visible_to!(person_id_join))
+      .filter(person::banned.eq(false))
+      .filter(community_person_ban::id.is_null())
+      .filter(not(
+        post::community_id.eq_any(
+          community_person_ban::table
+            .select(community_person_ban::community_id)
+            .filter(community_person_ban::person_id.eq(person_id_join)),
+        ),
+      ))
+      .order_by((post_aggregates::score.desc(), post::id.desc()))
+      .limit(CROSS_POSTS_LIMIT)
+      .load::<PostViewTuple>(conn)?;
+    Ok(Self::from_tuple_to_vec(res))
+  }
+
+  /// The posts which `person_id` pinned to their profile, newest first. Posts which were deleted
+  /// or removed in the meantime are left out.
+  pub fn list_pinned_for_person(
+    conn: &PgConnection,
+    person_id: PersonId,
+    my_person_id: Option<PersonId>,
+  ) -> Result<Vec<Self>, Error> {
+    // The left join below will return None in this case
+    let person_id_join = my_person_id.unwrap_or(PersonId(-1));
+
+    // The person chose these posts for their profile, so nsfw ones aren't hidden
+    let res = post_list_filters!(post_list_query!(person_id_join), true)
+      .filter(
+        post::id.eq_any(
+          person_pinned_post::table
+            .select(person_pinned_post::post_id)
+            .filter(person_pinned_post::person_id.eq(person_id)),
+        ),
+      )
+      .filter(visible_to!(person_id_join))
+      .order_by((post::published.desc(), post::id.desc()))
+      .load::<PostViewTuple>(conn)?;
+    Ok(Self::from_tuple_to_vec(res))
+  }
+}
+
+/// How many cross posts are shown for a post
+const CROSS_POSTS_LIMIT: i64 = 10;
+
+/// Position in a post listing, taken from the last post of the previous page. It holds the values
+/// of all sort keys instead of a row offset, so the next page can be found with an index scan, and
+/// it stays valid if that post is deleted in the meantime.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct PaginationCursor {
+  post_id: PostId,
+  stickied: bool,
+  #[serde(default)]
+  featured_local: bool,
+  hot_rank: i32,
+  hot_rank_active: i32,
+  score: i64,
+  comments: i64,
+  #[serde(default)]
+  views: i64,
+  published: NaiveDateTime,
+  newest_comment_time: NaiveDateTime,
+  newest_comment_time_necro: NaiveDateTime,
+}
+
+impl PaginationCursor {
+  pub fn after_post(post_view: &PostView) -> Self {
+    PaginationCursor {
+      post_id: post_view.post.id,
+      stickied: post_view.counts.stickied,
+      featured_local: post_view.counts.featured_local,
+      hot_rank: post_view.counts.hot_rank,
+      hot_rank_active: post_view.counts.hot_rank_active,
+      score: post_view.counts.score,
+      comments: post_view.counts.comments,
+      views: post_view.counts.views,
+      published: post_view.counts.published,
+      newest_comment_time: post_view.counts.newest_comment_time,
+      newest_comment_time_necro: post_view.counts.newest_comment_time_necro,
+    }
+  }
+
+  /// Opaque string which is handed out to api clients
+  pub fn encode(&self) -> String {
+    let json = serde_json::to_vec(self).unwrap_or_default();
+    base64::encode_config(json, base64::URL_SAFE_NO_PAD)
+  }
+
+  /// Returns `None` if the string wasn't created by `encode()`
+  pub fn decode(cursor: &str) -> Option<Self> {
+    let json = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD).ok()?;
+    serde_json::from_slice(&json).ok()
+  }
+}
+
+/// Listings which are requested most often. They are loaded with queries of a fixed shape, so
+/// that diesel can cache their prepared statements, while the boxed query of `list()` is built and
+/// planned again for every request.
+#[derive(Clone, Copy)]
+enum FixedListing {
+  CommunityHot(CommunityId),
+  CommunityNew(CommunityId),
+  SubscribedHot,
+  SubscribedNew,
+}
+
+fn list_fixed(
+  conn: &PgConnection,
+  listing: FixedListing,
+  person_id_join: PersonId,
+  show_nsfw: bool,
+  limit: i64,
+  offset: i64,
+) -> Result<Vec<PostViewTuple>, Error> {
+  let query = post_list_filters!(post_list_query!(person_id_join), show_nsfw)
+    .filter(visible_to!(person_id_join));
+
+  match listing {
+    FixedListing::CommunityHot(community_id) => query
+      .filter(post::community_id.eq(community_id))
+      .order_by((
+        post_aggregates::stickied.desc(),
+        post_aggregates::hot_rank.desc(),
+        post_aggregates::published.desc(),
+        post::id.desc(),
+      ))
+      .limit(limit)
+      .offset(offset)
+      .load::<PostViewTuple>(conn),
+    FixedListing::CommunityNew(community_id) => query
+      .filter(post::community_id.eq(community_id))
+      .order_by((
+        post_aggregates::stickied.desc(),
+        post_aggregates::published.desc(),
+        post::id.desc(),
+      ))
+      .limit(limit)
+      .offset(offset)
+      .load::<PostViewTuple>(conn),
+    FixedListing::SubscribedHot => query
+      .filter(subscribed!())
+      .order_by((
+        post_aggregates::hot_rank.desc(),
+        post_aggregates::published.desc(),
+        post::id.desc(),
+      ))
+      .limit(limit)
+      .offset(offset)
+      .load::<PostViewTuple>(conn),
+    FixedListing::SubscribedNew => query
+      .filter(subscribed!())
+      .order_by((post_aggregates::published.desc(), post::id.desc()))
+      .limit(limit)
+      .offset(offset)
+      .load::<PostViewTuple>(conn),
+  }
+}
+
+/// The boxed query of a `PostQueryBuilder` with all of its filters, but without order and limit.
+/// Shared by `list()` and `count()`, so that the count matches the listing.
+macro_rules! filtered_post_list {
+  ($builder:expr, $person_id_join:expr) => {{
+    let builder = &$builder;
+    let person_id_join = $person_id_join;
+    let mut query = post_list_query!(person_id_join).into_boxed();
+
+    query = match builder.listing_type {
+      ListingType::Subscribed => query.filter(subscribed!()),
+      ListingType::Local => query.filter(post::local.eq(true)),
+      _ => query,
+    };
+
+    if let Some(community_id) = builder.community_id {
+      query = query.filter(post::community_id.eq(community_id));
+    }
+
+    if let Some(community_name) = builder.community_name.to_owned() {
+      query = query
+        .filter(community::name.eq(community_name))
+        .filter(community::local.eq(true));
+    }
+
+    if let Some(tag_id) = builder.tag_id {
+      query = query.filter(
+        post::id.eq_any(
+          post_tag::table
+            .select(post_tag::post_id)
+            .filter(post_tag::tag_id.eq(tag_id)),
+        ),
+      );
+    }
+
+    if let Some(url_search) = builder.url_search.to_owned() {
+      query = query.filter(post::url.eq(url_search));
+    }
+
+    if let Some(search_term) = &builder.search_term {
+      let searcher = fuzzy_search(search_term);
+      query = query.filter(
+        post::name
+          .ilike(searcher.to_owned())
+          .or(post::body.ilike(searcher)),
+      );
+    }
+
+    // If its for a specific person, show the removed / deleted
+    if let Some(creator_id) = builder.creator_id {
+      query = query.filter(post::creator_id.eq(creator_id));
+    }
+
+    query = post_list_filters!(query, builder.show_nsfw);
+
+    // TODO  These two might be wrong
+    if builder.saved_only {
+      query = query.filter(post_saved::id.is_not_null());
+    };
+
+    if builder.unread_only {
+      query = query.filter(post_read::id.is_not_null());
+    };
+
+    if builder.favorites_only {
+      query = query.filter(subscribed!()).filter(
+        post::community_id.eq_any(
+          community_favorite::table
+            .select(community_favorite::community_id)
+            .filter(community_favorite::person_id.eq(person_id_join)),
+        ),
+      );
+    }
+
+    if builder.pending_only {
+      query = query.filter(post::approved.is_null());
+    } else {
+      query = query.filter(visible_to!(person_id_join));
+    }
+
+    if let Some(window_start) = builder.sort.top_window_start() {
+      query = query.filter(post_aggregates::published.gt(window_start));
+    }
+
+    // Active sorts with a time window only include posts which were created or necro-bumped in it
+    if let Some(window_start) = builder.sort.active_window_start() {
+      query = query.filter(post_aggregates::newest_comment_time_necro.gt(window_start));
+    }
+
+    query
+  }};
 }
 
 pub struct PostQueryBuilder<'a> {
   conn: &'a PgConnection,
   listing_type: &'a ListingType,
   sort: &'a SortType,
-  creator_id: Option<i32>,
-  community_id: Option<i32>,
+  creator_id: Option<PersonId>,
+  community_id: Option<CommunityId>,
   community_name: Option<String>,
-  my_person_id: Option<i32>,
+  tag_id: Option<i32>,
+  my_person_id: Option<PersonId>,
   search_term: Option<String>,
   url_search: Option<String>,
   show_nsfw: bool,
   saved_only: bool,
+  favorites_only: bool,
   unread_only: bool,
+  pending_only: bool,
   page: Option<i64>,
+  page_after: Option<PaginationCursor>,
   limit: Option<i64>,
+  fixed_queries: bool,
 }
 
 impl<'a> PostQueryBuilder<'a> {
@@ -172,14 +643,19 @@ impl<'a> PostQueryBuilder<'a> {
       creator_id: None,
       community_id: None,
       community_name: None,
+      tag_id: None,
       my_person_id: None,
       search_term: None,
       url_search: None,
       show_nsfw: true,
       saved_only: false,
+      favorites_only: false,
       unread_only: false,
+      pending_only: false,
       page: None,
+      page_after: None,
       limit: None,
+      fixed_queries: true,
     }
   }
 
@@ -193,12 +669,12 @@ impl<'a> PostQueryBuilder<'a> {
     self
   }
 
-  pub fn community_id<T: MaybeOptional<i32>>(mut self, community_id: T) -> Self {
+  pub fn community_id<T: MaybeOptional<CommunityId>>(mut self, community_id: T) -> Self {
     self.community_id = community_id.get_optional();
     self
   }
 
-  pub fn my_person_id<T: MaybeOptional<i32>>(mut self, my_person_id: T) -> Self {
+  pub fn my_person_id<T: MaybeOptional<PersonId>>(mut self, my_person_id: T) -> Self {
     self.my_person_id = my_person_id.get_optional();
     self
   }
@@ -208,7 +684,13 @@ impl<'a> PostQueryBuilder<'a> {
     self
   }
 
-  pub fn creator_id<T: MaybeOptional<i32>>(mut self, creator_id: T) -> Self {
+  /// Only the posts which have this community tag
+  pub fn tag_id<T: MaybeOptional<i32>>(mut self, tag_id: T) -> Self {
+    self.tag_id = tag_id.get_optional();
+    self
+  }
+
+  pub fn creator_id<T: MaybeOptional<PersonId>>(mut self, creator_id: T) -> Self {
     self.creator_id = creator_id.get_optional();
     self
   }
@@ -233,166 +715,252 @@ impl<'a> PostQueryBuilder<'a> {
     self
   }
 
+  /// Only the posts of the communities which `my_person_id` favorited, out of the followed ones
+  pub fn favorites_only(mut self, favorites_only: bool) -> Self {
+    self.favorites_only = favorites_only;
+    self
+  }
+
+  /// Only the posts which wait for approval, or which were flagged for review. The caller has to
+  /// check that the person may see them.
+  pub fn pending_only(mut self, pending_only: bool) -> Self {
+    self.pending_only = pending_only;
+    self
+  }
+
   pub fn page<T: MaybeOptional<i64>>(mut self, page: T) -> Self {
     self.page = page.get_optional();
     self
   }
 
+  /// Only list posts which come after the cursor, `page` is ignored if this is set
+  pub fn page_after<T: MaybeOptional<PaginationCursor>>(mut self, page_after: T) -> Self {
+    self.page_after = page_after.get_optional();
+    self
+  }
+
   pub fn limit<T: MaybeOptional<i64>>(mut self, limit: T) -> Self {
     self.limit = limit.get_optional();
     self
   }
 
-  pub fn list(self) -> Result<Vec<PostView>, Error> {
-    use diesel::dsl::*;
+  /// Whether the common listings are loaded with a fixed query, which is the default. The results
+  /// are the same either way, this is only turned off to compare the two in the benchmarks.
+  pub fn fixed_queries(mut self, fixed_queries: bool) -> Self {
+    self.fixed_queries = fixed_queries;
+    self
+  }
 
-    // The left join below will return None in this case
-    let person_id_join = self.my_person_id.unwrap_or(-1);
+  /// Returns the listing if the options are one of the common combinations, which are loaded with
+  /// a fixed query instead of a boxed one.
+  fn fixed_listing(&self) -> Option<FixedListing> {
+    if !self.fixed_queries
+      || self.community_name.is_some()
+      || self.tag_id.is_some()
+      || self.creator_id.is_some()
+      || self.search_term.is_some()
+      || self.url_search.is_some()
+      || self.saved_only
+      || self.favorites_only
+      || self.unread_only
+      || self.pending_only
+      || self.page_after.is_some()
+    {
+      return None;
+    }
+    let community_listing = matches!(self.listing_type, ListingType::All | ListingType::Community);
+    match (self.community_id, self.sort) {
+      (Some(community_id), SortType::Hot) if community_listing => {
+        Some(FixedListing::CommunityHot(community_id))
+      }
+      (Some(community_id), SortType::New) if community_listing => {
+        Some(FixedListing::CommunityNew(community_id))
+      }
+      (None, SortType::Hot) if self.listing_type == &ListingType::Subscribed => {
+        Some(FixedListing::SubscribedHot)
+      }
+      (None, SortType::New) if self.listing_type == &ListingType::Subscribed => {
+        Some(FixedListing::SubscribedNew)
+      }
+      _ => None,
+    }
+  }
 
-    let mut query = post::table
-      .inner_join(person::table)
-      .inner_join(community::table)
-      .left_join(
-        community_person_ban::table.on(
-          post::community_id
-            .eq(community_person_ban::community_id)
-            .and(community_person_ban::person_id.eq(community::creator_id)),
-        ),
-      )
-      .inner_join(post_aggregates::table)
-      .left_join(
-        community_follower::table.on(
-          post::community_id
-            .eq(community_follower::community_id)
-            .and(community_follower::person_id.eq(person_id_join)),
-        ),
-      )
-      .left_join(
-        post_saved::table.on(
-          post::id
-            .eq(post_saved::post_id)
-            .and(post_saved::person_id.eq(person_id_join)),
-        ),
-      )
-      .left_join(
-        post_read::table.on(
-          post::id
-            .eq(post_read::post_id)
-            .and(post_read::person_id.eq(person_id_join)),
-        ),
-      )
-      .left_join(
-        post_like::table.on(
-          post::id
-            .eq(post_like::post_id)
-            .and(post_like::person_id.eq(person_id_join)),
-        ),
-      )
-      .select((
-        post::all_columns,
-        Person::safe_columns_tuple(),
-        Community::safe_columns_tuple(),
-        community_person_ban::all_columns.nullable(),
-        post_aggregates::all_columns,
-        community_follower::all_columns.nullable(),
-        post_saved::all_columns.nullable(),
-        post_read::all_columns.nullable(),
-        post_like::score.nullable(),
-      ))
-      .into_boxed();
+  /// The number of posts in the listing, on all pages together
+  pub fn count(self) -> Result<i64, Error> {
+    // The left join below will return None in this case
+    let person_id_join = self.my_person_id.unwrap_or(PersonId(-1));
+    filtered_post_list!(self, person_id_join)
+      .count()
+      .get_result(self.conn)
+  }
 
-    query = match self.listing_type {
-      ListingType::Subscribed => query.filter(community_follower::person_id.is_not_null()), // TODO could be this: and(community_follower::person_id.eq(person_id_join)),
-      ListingType::Local => query.filter(community::local.eq(true)),
-      _ => query,
-    };
+  pub fn list(self) -> Result<Vec<PostView>, Error> {
+    // The left join below will return None in this case
+    let person_id_join = self.my_person_id.unwrap_or(PersonId(-1));
 
-    if let Some(community_id) = self.community_id {
-      query = query
-        .filter(post::community_id.eq(community_id))
-        .then_order_by(post_aggregates::stickied.desc());
+    if let Some(listing) = self.fixed_listing() {
+      let (limit, offset) = limit_and_offset(self.page, self.limit);
+      let res = list_fixed(
+        self.conn,
+        listing,
+        person_id_join,
+        self.show_nsfw,
+        limit,
+        offset,
+      )?;
+      return Ok(PostView::from_tuple_to_vec(res));
     }
 
-    if let Some(community_name) = self.community_name {
-      query = query
-        .filter(community::name.eq(community_name))
-        .filter(community::local.eq(true))
-        .then_order_by(post_aggregates::stickied.desc());
-    }
+    let mut query = filtered_post_list!(self, person_id_join);
 
-    if let Some(url_search) = self.url_search {
-      query = query.filter(post::url.eq(url_search));
-    }
+    // Stickied posts come first when listing a single community, and the posts featured by the
+    // admins when listing all or local posts
+    let stickied_first = self.community_id.is_some() || self.community_name.is_some();
+    let featured_first = !stickied_first
+      && matches!(self.listing_type, ListingType::All | ListingType::Local)
+      && self.creator_id.is_none()
+      && self.search_term.is_none()
+      && self.url_search.is_none()
+      && !self.saved_only
+      && !self.favorites_only;
 
-    if let Some(search_term) = self.search_term {
-      let searcher = fuzzy_search(&search_term);
-      query = query.filter(
-        post::name
-          .ilike(searcher.to_owned())
-          .or(post::body.ilike(searcher)),
-      );
+    if stickied_first {
+      query = query.then_order_by(post_aggregates::stickied.desc());
+    } else if featured_first {
+      query = query.then_order_by(post_aggregates::featured_local.desc());
     }
 
-    // If its for a specific person, show the removed / deleted
-    if let Some(creator_id) = self.creator_id {
-      query = query.filter(post::creator_id.eq(creator_id));
-    }
-
-    if !self.show_nsfw {
-      query = query
-        .filter(post::nsfw.eq(false))
-        .filter(community::nsfw.eq(false));
-    };
-
-    // TODO  These two might be wrong
-    if self.saved_only {
-      query = query.filter(post_saved::id.is_not_null());
-    };
-
-    if self.unread_only {
-      query = query.filter(post_read::id.is_not_null());
-    };
-
     query = match self.sort {
-      SortType::Active => query
-        .then_order_by(
-          hot_rank(
-            post_aggregates::score,
-            post_aggregates::newest_comment_time_necro,
-          )
-          .desc(),
-        )
+      SortType::Active
+      | SortType::ActiveDaily
+      | SortType::ActiveWeekly
+      | SortType::ActiveMonthly
+      | SortType::ActiveSixMonths => query
+        .then_order_by(post_aggregates::hot_rank_active.desc())
         .then_order_by(post_aggregates::newest_comment_time_necro.desc()),
       SortType::Hot => query
-        .then_order_by(hot_rank(post_aggregates::score, post_aggregates::published).desc())
+        .then_order_by(post_aggregates::hot_rank.desc())
         .then_order_by(post_aggregates::published.desc()),
-      SortType::New => query.then_order_by(post_aggregates::published.desc()),
+      SortType::New | SortType::Old | SortType::QandA => {
+        query.then_order_by(post_aggregates::published.desc())
+      }
       SortType::MostComments => query.then_order_by(post_aggregates::comments.desc()),
+      SortType::MostViews => query.then_order_by(post_aggregates::views.desc()),
       SortType::NewComments => query.then_order_by(post_aggregates::newest_comment_time.desc()),
-      SortType::TopAll => query.then_order_by(post_aggregates::score.desc()),
-      SortType::TopYear => query
-        .filter(post::published.gt(now - 1.years()))
-        .then_order_by(post_aggregates::score.desc()),
-      SortType::TopMonth => query
-        .filter(post::published.gt(now - 1.months()))
-        .then_order_by(post_aggregates::score.desc()),
-      SortType::TopWeek => query
-        .filter(post::published.gt(now - 1.weeks()))
-        .then_order_by(post_aggregates::score.desc()),
-      SortType::TopDay => query
-        .filter(post::published.gt(now - 1.days()))
-        .then_order_by(post_aggregates::score.desc()),
-    };
-
-    let (limit, offset) = limit_and_offset(self.page, self.limit);
-
-    query = query
-      .limit(limit)
-      .offset(offset)
-      .filter(post::removed.eq(false))
-      .filter(post::deleted.eq(false))
-      .filter(community::removed.eq(false))
-      .filter(community::deleted.eq(false));
+      SortType::TopAll
+      | SortType::TopYear
+      | SortType::TopMonth
+      | SortType::TopWeek
+      | SortType::TopDay => query.then_order_by(post_aggregates::score.desc()),
+    };
+    // Makes the order unique, which keyset pagination relies on
+    query = query.then_order_by(post::id.desc());
+
+    if let Some(cursor) = &self.page_after {
+      // Keeps the posts which sort after the cursor. The sort keys are compared in the same order
+      // as in the order by clauses above, and the post id breaks ties.
+      macro_rules! after_cursor {
+        ($key:expr, $value:expr, $rest:expr) => {
+          $key.lt($value).or($key.eq($value).and($rest))
+        };
+      }
+      macro_rules! filter_after_pinned {
+        ($pinned:expr, $cursor_pinned:expr, $condition:expr) => {
+          if $cursor_pinned {
+            query.filter($pinned.eq(false).or($condition))
+          } else {
+            query.filter($pinned.eq(false)).filter($condition)
+          }
+        };
+      }
+      macro_rules! filter_after_cursor {
+        ($condition:expr) => {
+          if stickied_first {
+            filter_after_pinned!(post_aggregates::stickied, cursor.stickied, $condition)
+          } else if featured_first {
+            filter_after_pinned!(
+              post_aggregates::featured_local,
+              cursor.featured_local,
+              $condition
+            )
+          } else {
+            query.filter($condition)
+          }
+        };
+      }
+
+      let id_after = post::id.lt(cursor.post_id);
+      query = match self.sort {
+        SortType::Active
+        | SortType::ActiveDaily
+        | SortType::ActiveWeekly
+        | SortType::ActiveMonthly
+        | SortType::ActiveSixMonths => {
+          filter_after_cursor!(after_cursor!(
+            post_aggregates::hot_rank_active,
+            cursor.hot_rank_active,
+            after_cursor!(
+              post_aggregates::newest_comment_time_necro,
+              cursor.newest_comment_time_necro,
+              id_after
+            )
+          ))
+        }
+        SortType::Hot => {
+          filter_after_cursor!(after_cursor!(
+            post_aggregates::hot_rank,
+            cursor.hot_rank,
+            after_cursor!(post_aggregates::published, cursor.published, id_after)
+          ))
+        }
+        SortType::New | SortType::Old | SortType::QandA => {
+          filter_after_cursor!(after_cursor!(
+            post_aggregates::published,
+            cursor.published,
+            id_after
+          ))
+        }
+        SortType::MostComments => {
+          filter_after_cursor!(after_cursor!(
+            post_aggregates::comments,
+            cursor.comments,
+            id_after
+          ))
+        }
+        SortType::MostViews => {
+          filter_after_cursor!(after_cursor!(
+            post_aggregates::views,
+            cursor.views,
+            id_after
+          ))
+        }
+        SortType::NewComments => filter_after_cursor!(after_cursor!(
+          post_aggregates::newest_comment_time,
+          cursor.newest_comment_time,
+          id_after
+        )),
+        SortType::TopAll
+        | SortType::TopYear
+        | SortType::TopMonth
+        | SortType::TopWeek
+        | SortType::TopDay => {
+          filter_after_cursor!(after_cursor!(
+            post_aggregates::score,
+            cursor.score,
+            id_after
+          ))
+        }
+      };
+    }
+
+    // With a cursor the offset is already given by the filter above
+    let (limit, offset) = match self.page_after {
+      Some(_) => limit_and_offset(None, self.limit),
+      None => limit_and_offset(self.page, self.limit),
+    };
+
+    query = query.limit(limit).offset(offset);
 
     debug!("Post View Query: {:?}", debug_query::<Pg, _>(&query));
 
@@ -409,7 +977,7 @@ impl ViewToVec for PostView {
       .iter()
       .map(|a| Self {
         post: a.0.to_owned(),
-        creator: a.1.to_owned(),
+        creator: a.1.to_owned().hide_if_deleted(),
         community: a.2.to_owned(),
         creator_banned_from_community: a.3.is_some(),
         counts: a.4.to_owned(),
@@ -417,6 +985,7 @@ impl ViewToVec for PostView {
         saved: a.6.is_some(),
         read: a.7.is_some(),
         my_vote: a.8,
+        creator_note: a.9.to_owned(),
       })
       .collect::<Vec<Self>>()
   }
@@ -424,17 +993,41 @@ impl ViewToVec for PostView {
 
 #[cfg(test)]
 mod tests {
-  use crate::post_view::{PostQueryBuilder, PostView};
+  use crate::post_view::{PaginationCursor, PostQueryBuilder, PostView};
+  use diesel::{
+    delete,
+    insert_into,
+    sql_query,
+    sql_types::Text,
+    ExpressionMethods,
+    QueryDsl,
+    QueryableByName,
+    RunQueryDsl,
+  };
   use lemmy_db_queries::{
     aggregates::post_aggregates::PostAggregates,
     establish_unpooled_connection,
+    source::{
+      community::CommunityFavorite_,
+      community_tag::CommunityTag_,
+      post::{PersonPinnedPost_, Post_},
+    },
     Crud,
+    Followable,
+    Joinable,
     Likeable,
     ListingType,
+    Saveable,
     SortType,
   };
-  use lemmy_db_schema::source::{community::*, person::*, post::*};
+  use lemmy_db_schema::{
+    naive_now,
+    schema::post as post_table,
+    source::{community::*, community_tag::*, local_user::*, person::*, post::*},
+    PostId,
+  };
   use serial_test::serial;
+  use url::Url;
 
   #[test]
   #[serial]
@@ -486,6 +1079,13 @@ mod tests {
       followers_url: None,
       inbox_url: None,
       shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
     };
 
     let inserted_community = Community::create(&conn, &new_community).unwrap();
@@ -572,8 +1172,15 @@ mod tests {
         thumbnail_url: None,
         ap_id: inserted_post.ap_id.to_owned(),
         local: true,
+        deleted_by_community_delete: false,
+        featured_local: false,
+        removed_by_community_remove: false,
+        approved: Some(true),
+        url_normalized: None,
+        metadata_fetch_failed_at: None,
       },
       my_vote: None,
+      creator_note: None,
       creator: PersonSafe {
         id: inserted_person.id,
         name: person_name,
@@ -606,6 +1213,14 @@ mod tests {
         updated: None,
         banner: None,
         published: inserted_community.published,
+        exempt_from_slur_filter: false,
+        downvotes_disabled: false,
+        requires_post_approval: false,
+        theme_color: None,
+        short_description: None,
+        default_comment_sort: SortType::New as i16,
+        require_post_tag: false,
+        manual_follow_approval: false,
       },
       counts: PostAggregates {
         id: agg.id,
@@ -618,6 +1233,10 @@ mod tests {
         published: agg.published,
         newest_comment_time_necro: inserted_post.published,
         newest_comment_time: inserted_post.published,
+        hot_rank: agg.hot_rank,
+        hot_rank_active: agg.hot_rank_active,
+        featured_local: false,
+        views: 0,
       },
       subscribed: false,
       read: false,
@@ -658,4 +1277,1306 @@ mod tests {
     assert_eq!(1, like_removed);
     assert_eq!(1, num_deleted);
   }
+
+  #[test]
+  #[serial]
+  fn test_page_after_cursor() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "cursor_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "cursor_community".into(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let post_form = |name: &str| PostForm {
+      name: name.into(),
+      url: None,
+      body: None,
+      creator_id: inserted_person.id,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      updated: None,
+      nsfw: false,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      thumbnail_url: None,
+      ap_id: None,
+      local: true,
+      published: None,
+    };
+    let post_one = Post::create(&conn, &post_form("cursor post 1")).unwrap();
+    let post_two = Post::create(&conn, &post_form("cursor post 2")).unwrap();
+    let post_three = Post::create(&conn, &post_form("cursor post 3")).unwrap();
+
+    let first_page = PostQueryBuilder::create(&conn)
+      .sort(&SortType::Hot)
+      .community_id(inserted_community.id)
+      .limit(2)
+      .list()
+      .unwrap();
+    // The same listing through the boxed query, the posts are local so the filter is a no-op
+    let boxed_first_page = PostQueryBuilder::create(&conn)
+      .listing_type(&ListingType::Local)
+      .sort(&SortType::Hot)
+      .community_id(inserted_community.id)
+      .limit(2)
+      .list()
+      .unwrap();
+    let cursor = PaginationCursor::after_post(&first_page[1]);
+    let decoded_cursor = PaginationCursor::decode(&cursor.encode());
+
+    // The last post of the first page is deleted before the second page is loaded
+    Post::delete(&conn, post_two.id).unwrap();
+
+    let second_page = PostQueryBuilder::create(&conn)
+      .sort(&SortType::Hot)
+      .community_id(inserted_community.id)
+      .limit(2)
+      .page_after(cursor.to_owned())
+      .list()
+      .unwrap();
+
+    Post::delete(&conn, post_one.id).unwrap();
+    Post::delete(&conn, post_three.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(
+      vec![post_three.id, post_two.id],
+      first_page.iter().map(|p| p.post.id).collect::<Vec<_>>()
+    );
+    assert_eq!(
+      vec![post_one.id],
+      second_page.iter().map(|p| p.post.id).collect::<Vec<_>>()
+    );
+    assert_eq!(boxed_first_page, first_page);
+    assert_eq!(Some(cursor), decoded_cursor);
+    assert_eq!(None, PaginationCursor::decode("not a cursor"));
+  }
+
+  #[test]
+  #[serial]
+  fn test_featured_local() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "featured_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "featured_community".into(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let post_form = |name: &str| PostForm {
+      name: name.into(),
+      url: None,
+      body: None,
+      creator_id: inserted_person.id,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      updated: None,
+      nsfw: false,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      thumbnail_url: None,
+      ap_id: None,
+      local: true,
+      published: None,
+    };
+    let post_one = Post::create(&conn, &post_form("featured post 1")).unwrap();
+    let post_two = Post::create(&conn, &post_form("featured post 2")).unwrap();
+    Post::update_featured_local(&conn, post_one.id, true).unwrap();
+
+    let first_page = PostQueryBuilder::create(&conn)
+      .listing_type(&ListingType::Local)
+      .sort(&SortType::New)
+      .limit(1)
+      .list()
+      .unwrap();
+    let second_page = PostQueryBuilder::create(&conn)
+      .listing_type(&ListingType::Local)
+      .sort(&SortType::New)
+      .limit(1)
+      .page_after(PaginationCursor::after_post(&first_page[0]))
+      .list()
+      .unwrap();
+    // Featured posts are only pinned on the front page, not in the community
+    let community_page = PostQueryBuilder::create(&conn)
+      .sort(&SortType::New)
+      .community_id(inserted_community.id)
+      .limit(1)
+      .list()
+      .unwrap();
+
+    Post::delete(&conn, post_one.id).unwrap();
+    Post::delete(&conn, post_two.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(post_one.id, first_page[0].post.id);
+    assert!(first_page[0].counts.featured_local);
+    assert_eq!(post_two.id, second_page[0].post.id);
+    assert_eq!(post_two.id, community_page[0].post.id);
+  }
+
+  #[test]
+  #[serial]
+  fn test_awaiting_approval() {
+    let conn = establish_unpooled_connection();
+
+    let person_form = |name: &str| PersonForm {
+      name: name.into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let creator = Person::create(&conn, &person_form("approval_creator")).unwrap();
+    let moderator = Person::create(&conn, &person_form("approval_mod")).unwrap();
+    let other = Person::create(&conn, &person_form("approval_other")).unwrap();
+    let admin = Person::create(&conn, &person_form("approval_admin")).unwrap();
+    let admin_form = LocalUserForm {
+      person_id: admin.id,
+      password_encrypted: "pass".to_string(),
+      email: None,
+      matrix_user_id: None,
+      admin: Some(true),
+      show_nsfw: None,
+      theme: None,
+      default_sort_type: None,
+      default_listing_type: None,
+      lang: None,
+      show_avatars: None,
+      send_notifications_to_email: None,
+      email_digest_frequency: None,
+      hide_last_active: None,
+      hide_profile_from_federation: None,
+    };
+    LocalUser::create(&conn, &admin_form).unwrap();
+
+    let new_community = CommunityForm {
+      name: "approval_community".into(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: moderator.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: Some(true),
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+    let moderator_form = CommunityModeratorForm {
+      community_id: inserted_community.id,
+      person_id: moderator.id,
+    };
+    CommunityModerator::join(&conn, &moderator_form).unwrap();
+
+    let post_form = |name: &str| PostForm {
+      name: name.into(),
+      url: None,
+      body: None,
+      creator_id: creator.id,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      updated: None,
+      nsfw: false,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      thumbnail_url: None,
+      ap_id: None,
+      local: true,
+      published: None,
+    };
+    let approved_post = Post::create(&conn, &post_form("approved post")).unwrap();
+    let pending_post = Post::create(&conn, &post_form("pending post")).unwrap();
+    let pending_post = Post::update_approved(&conn, pending_post.id, None).unwrap();
+
+    let list_for = |person_id| {
+      PostQueryBuilder::create(&conn)
+        .sort(&SortType::New)
+        .community_id(inserted_community.id)
+        .my_person_id(person_id)
+        .list()
+        .unwrap()
+        .iter()
+        .map(|p| p.post.id)
+        .collect::<Vec<_>>()
+    };
+    let creator_listing = list_for(creator.id);
+    let mod_listing = list_for(moderator.id);
+    let other_listing = list_for(other.id);
+    let admin_listing = list_for(admin.id);
+    let pending_listing = PostQueryBuilder::create(&conn)
+      .community_id(inserted_community.id)
+      .pending_only(true)
+      .list()
+      .unwrap();
+
+    Post::delete(&conn, approved_post.id).unwrap();
+    Post::delete(&conn, pending_post.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, creator.id).unwrap();
+    Person::delete(&conn, moderator.id).unwrap();
+    Person::delete(&conn, other.id).unwrap();
+    Person::delete(&conn, admin.id).unwrap();
+
+    assert!(pending_post.is_awaiting_approval());
+    assert!(!approved_post.is_awaiting_approval());
+    assert_eq!(vec![pending_post.id, approved_post.id], creator_listing);
+    assert_eq!(vec![pending_post.id, approved_post.id], mod_listing);
+    assert_eq!(vec![approved_post.id], other_listing);
+    assert_eq!(vec![pending_post.id, approved_post.id], admin_listing);
+    assert_eq!(1, pending_listing.len());
+    assert_eq!(pending_post.id, pending_listing[0].post.id);
+  }
+
+  #[test]
+  #[serial]
+  fn test_listing_types() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "listing_type_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let community_form = |name: &str, local: bool| CommunityForm {
+      name: name.into(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let local_community =
+      Community::create(&conn, &community_form("listing_type_local", true)).unwrap();
+    let remote_community =
+      Community::create(&conn, &community_form("listing_type_remote", false)).unwrap();
+    let follower_form = CommunityFollowerForm {
+      community_id: remote_community.id,
+      person_id: inserted_person.id,
+      pending: false,
+    };
+    CommunityFollower::follow(&conn, &follower_form).unwrap();
+
+    let post_form = |name: &str, community: &Community, local: bool| PostForm {
+      name: name.into(),
+      url: None,
+      body: None,
+      creator_id: inserted_person.id,
+      community_id: community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      updated: None,
+      nsfw: false,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      thumbnail_url: None,
+      ap_id: None,
+      local,
+      published: None,
+    };
+    let local_post = Post::create(&conn, &post_form("local post", &local_community, true)).unwrap();
+    // Federated posts can be made in local communities too
+    let federated_post =
+      Post::create(&conn, &post_form("federated post", &local_community, false)).unwrap();
+    let remote_post =
+      Post::create(&conn, &post_form("remote post", &remote_community, false)).unwrap();
+
+    let list = |listing_type: &ListingType| {
+      let mut ids = PostQueryBuilder::create(&conn)
+        .listing_type(listing_type)
+        .sort(&SortType::New)
+        .my_person_id(inserted_person.id)
+        .list()
+        .unwrap()
+        .iter()
+        .map(|p| p.post.id)
+        .filter(|id| [local_post.id, federated_post.id, remote_post.id].contains(id))
+        .collect::<Vec<_>>();
+      ids.sort_by_key(|id| id.0);
+      ids
+    };
+    let all_listing = list(&ListingType::All);
+    let local_listing = list(&ListingType::Local);
+    let subscribed_listing = list(&ListingType::Subscribed);
+
+    // Subscribe to the local community as well, so both kinds are in the listing
+    let follower_form = CommunityFollowerForm {
+      community_id: local_community.id,
+      person_id: inserted_person.id,
+      pending: false,
+    };
+    CommunityFollower::follow(&conn, &follower_form).unwrap();
+    let subscribed_both_listing = list(&ListingType::Subscribed);
+
+    // Only the favorited one of the followed communities is on the favorites front page
+    let favorite_form = CommunityFavoriteForm {
+      community_id: remote_community.id,
+      person_id: inserted_person.id,
+    };
+    CommunityFavorite::favorite(&conn, &favorite_form).unwrap();
+    let favorites_listing = PostQueryBuilder::create(&conn)
+      .listing_type(&ListingType::Subscribed)
+      .favorites_only(true)
+      .my_person_id(inserted_person.id)
+      .list()
+      .unwrap()
+      .iter()
+      .map(|p| p.post.id)
+      .filter(|id| [local_post.id, federated_post.id, remote_post.id].contains(id))
+      .collect::<Vec<_>>();
+
+    // A pending follow doesn't count until it is accepted
+    CommunityFollower::unfollow(&conn, &follower_form).unwrap();
+    let pending_form = CommunityFollowerForm {
+      community_id: local_community.id,
+      person_id: inserted_person.id,
+      pending: true,
+    };
+    CommunityFollower::follow(&conn, &pending_form).unwrap();
+    let subscribed_pending_listing = list(&ListingType::Subscribed);
+
+    Post::delete(&conn, local_post.id).unwrap();
+    Post::delete(&conn, federated_post.id).unwrap();
+    Post::delete(&conn, remote_post.id).unwrap();
+    Community::delete(&conn, local_community.id).unwrap();
+    Community::delete(&conn, remote_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(
+      vec![local_post.id, federated_post.id, remote_post.id],
+      all_listing
+    );
+    assert_eq!(vec![local_post.id], local_listing);
+    assert_eq!(vec![remote_post.id], subscribed_listing);
+    assert_eq!(
+      vec![local_post.id, federated_post.id, remote_post.id],
+      subscribed_both_listing
+    );
+    assert_eq!(vec![remote_post.id], favorites_listing);
+    assert_eq!(vec![remote_post.id], subscribed_pending_listing);
+  }
+
+  #[test]
+  #[serial]
+  fn test_top_time_windows() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "top_window_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "top_window_community".into(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    // Published times are naive UTC, like those of federated posts
+    let post_form = |name: &str, hours_ago: i64| PostForm {
+      name: name.into(),
+      url: None,
+      body: None,
+      creator_id: inserted_person.id,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      updated: None,
+      nsfw: false,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      thumbnail_url: None,
+      ap_id: None,
+      local: true,
+      published: Some(naive_now() - chrono::Duration::hours(hours_ago)),
+    };
+    let hour_old_post = Post::create(&conn, &post_form("hour old post", 1)).unwrap();
+    let two_days_old_post = Post::create(&conn, &post_form("two days old post", 48)).unwrap();
+    let two_months_old_post =
+      Post::create(&conn, &post_form("two months old post", 24 * 62)).unwrap();
+    let two_years_old_post =
+      Post::create(&conn, &post_form("two years old post", 24 * 731)).unwrap();
+    let all_ids = [
+      hour_old_post.id,
+      two_days_old_post.id,
+      two_months_old_post.id,
+      two_years_old_post.id,
+    ];
+
+    let list = |sort: &SortType| {
+      let mut ids = PostQueryBuilder::create(&conn)
+        .community_id(inserted_community.id)
+        .sort(sort)
+        .list()
+        .unwrap()
+        .iter()
+        .map(|p| p.post.id)
+        .filter(|id| all_ids.contains(id))
+        .collect::<Vec<_>>();
+      ids.sort_by_key(|id| id.0);
+      ids
+    };
+    let top_week = list(&SortType::TopWeek);
+    let top_month = list(&SortType::TopMonth);
+    let top_year = list(&SortType::TopYear);
+    let top_all = list(&SortType::TopAll);
+    let active_daily = list(&SortType::ActiveDaily);
+    let active_six_months = list(&SortType::ActiveSixMonths);
+
+    // Many older posts, which the day window has to skip quickly
+    let old_forms = (0..1000)
+      .map(|i| post_form("old post", 48 + i))
+      .collect::<Vec<PostForm>>();
+    let old_ids = insert_into(post_table::table)
+      .values(&old_forms)
+      .returning(post_table::id)
+      .get_results::<PostId>(&conn)
+      .unwrap();
+    let start = std::time::Instant::now();
+    let top_day = list(&SortType::TopDay);
+    let top_day_millis = start.elapsed().as_millis();
+
+    let indexes = sql_query(
+      "select indexname from pg_indexes where indexname in \
+      ('idx_post_aggregates_published_score', 'idx_comment_aggregates_published_score') \
+      order by indexname",
+    )
+    .load::<IndexName>(&conn)
+    .unwrap()
+    .into_iter()
+    .map(|i| i.indexname)
+    .collect::<Vec<String>>();
+
+    for id in all_ids.iter() {
+      Post::delete(&conn, *id).unwrap();
+    }
+    delete(post_table::table.filter(post_table::id.eq_any(old_ids)))
+      .execute(&conn)
+      .unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    // The windows are served by the published and score indexes
+    assert_eq!(
+      vec![
+        "idx_comment_aggregates_published_score",
+        "idx_post_aggregates_published_score"
+      ],
+      indexes
+    );
+    assert!(top_day_millis < 50, "top day took {} ms", top_day_millis);
+    assert_eq!(vec![hour_old_post.id], top_day);
+    assert_eq!(vec![hour_old_post.id, two_days_old_post.id], top_week);
+    assert_eq!(vec![hour_old_post.id, two_days_old_post.id], top_month);
+    assert_eq!(
+      vec![
+        hour_old_post.id,
+        two_days_old_post.id,
+        two_months_old_post.id
+      ],
+      top_year
+    );
+    assert_eq!(all_ids.to_vec(), top_all);
+    assert_eq!(vec![hour_old_post.id], active_daily);
+    assert_eq!(
+      vec![
+        hour_old_post.id,
+        two_days_old_post.id,
+        two_months_old_post.id
+      ],
+      active_six_months
+    );
+  }
+
+  #[derive(QueryableByName)]
+  struct IndexName {
+    #[sql_type = "Text"]
+    indexname: String,
+  }
+
+  #[test]
+  #[serial]
+  fn test_search_includes_my_state() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "search_state_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "search_state_community".into(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let url = "https://example.com/search_state";
+    let new_post = PostForm {
+      name: "search state post".into(),
+      url: Some(url::Url::parse(url).unwrap().into()),
+      body: None,
+      creator_id: inserted_person.id,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      updated: None,
+      nsfw: false,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      thumbnail_url: None,
+      ap_id: None,
+      local: true,
+      published: None,
+    };
+    let inserted_post = Post::create(&conn, &new_post).unwrap();
+
+    let post_like_form = PostLikeForm {
+      post_id: inserted_post.id,
+      person_id: inserted_person.id,
+      score: 1,
+    };
+    PostLike::like(&conn, &post_like_form).unwrap();
+    let post_saved_form = PostSavedForm {
+      post_id: inserted_post.id,
+      person_id: inserted_person.id,
+    };
+    PostSaved::save(&conn, &post_saved_form).unwrap();
+
+    let term_results = PostQueryBuilder::create(&conn)
+      .sort(&SortType::New)
+      .show_nsfw(true)
+      .my_person_id(inserted_person.id)
+      .search_term("search state".to_string())
+      .list()
+      .unwrap();
+    let url_results = PostQueryBuilder::create(&conn)
+      .sort(&SortType::New)
+      .show_nsfw(true)
+      .my_person_id(inserted_person.id)
+      .url_search(url.to_string())
+      .list()
+      .unwrap();
+    let read_view = PostView::read(&conn, inserted_post.id, Some(inserted_person.id)).unwrap();
+
+    Post::delete(&conn, inserted_post.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    for results in &[term_results, url_results] {
+      let found = results
+        .iter()
+        .find(|p| p.post.id == inserted_post.id)
+        .unwrap();
+      assert_eq!(Some(1), found.my_vote);
+      assert!(found.saved);
+    }
+    // Resolving the post by its activitypub id in a search reads it like this
+    assert_eq!(Some(1), read_view.my_vote);
+    assert!(read_view.saved);
+  }
+
+  #[test]
+  #[serial]
+  fn test_read_random_in_community() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "random_post_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "random_post_community".into(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let empty =
+      PostView::read_random_in_community(&conn, inserted_community.id, None, false).unwrap();
+
+    let posts = (0..10)
+      .map(|i| {
+        let form = PostForm {
+          name: format!("random post {}", i),
+          url: None,
+          body: None,
+          creator_id: inserted_person.id,
+          community_id: inserted_community.id,
+          removed: None,
+          deleted: None,
+          locked: None,
+          stickied: None,
+          updated: None,
+          nsfw: false,
+          embed_title: None,
+          embed_description: None,
+          embed_html: None,
+          thumbnail_url: None,
+          ap_id: None,
+          local: true,
+          published: None,
+        };
+        Post::create(&conn, &form).unwrap()
+      })
+      .collect::<Vec<Post>>();
+    let removed_post = Post::update_removed(&conn, posts[0].id, true).unwrap();
+
+    let picked = (0..200)
+      .map(|_| {
+        PostView::read_random_in_community(&conn, inserted_community.id, None, false)
+          .unwrap()
+          .unwrap()
+          .post
+          .id
+      })
+      .collect::<Vec<_>>();
+    let mut distinct = picked.clone();
+    distinct.sort_by_key(|id| id.0);
+    distinct.dedup();
+
+    for post in &posts {
+      Post::delete(&conn, post.id).unwrap();
+    }
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert!(empty.is_none());
+    assert!(!picked.contains(&removed_post.id));
+    assert!(picked
+      .iter()
+      .all(|id| posts.iter().any(|post| post.id == *id)));
+    // The ids of the posts have no gaps, so the picks are uniform. The chance that one of the 9
+    // visible posts isn't among 200 of them is negligible.
+    assert_eq!(posts.len() - 1, distinct.len());
+  }
+
+  #[test]
+  #[serial]
+  fn test_tag_filter() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "tag_filter_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "tag_filter_community".into(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let posts = (0..2)
+      .map(|i| {
+        let form = PostForm {
+          name: format!("tagged post {}", i),
+          url: None,
+          body: None,
+          creator_id: inserted_person.id,
+          community_id: inserted_community.id,
+          removed: None,
+          deleted: None,
+          locked: None,
+          stickied: None,
+          updated: None,
+          nsfw: false,
+          embed_title: None,
+          embed_description: None,
+          embed_html: None,
+          thumbnail_url: None,
+          ap_id: None,
+          local: true,
+          published: None,
+        };
+        Post::create(&conn, &form).unwrap()
+      })
+      .collect::<Vec<Post>>();
+
+    let tag_form = CommunityTagForm {
+      community_id: inserted_community.id,
+      name: "meta".into(),
+      color: None,
+    };
+    let tag = CommunityTag::create(&conn, &tag_form).unwrap();
+    CommunityTag::set_for_post(&conn, posts[1].id, &[tag.id]).unwrap();
+
+    // A community listing with a tag doesn't use the fixed query
+    let tagged = PostQueryBuilder::create(&conn)
+      .listing_type(&ListingType::Community)
+      .sort(&SortType::New)
+      .community_id(inserted_community.id)
+      .tag_id(tag.id)
+      .list()
+      .unwrap();
+    let all = PostQueryBuilder::create(&conn)
+      .listing_type(&ListingType::Community)
+      .sort(&SortType::New)
+      .community_id(inserted_community.id)
+      .list()
+      .unwrap();
+
+    for post in &posts {
+      Post::delete(&conn, post.id).unwrap();
+    }
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(
+      vec![posts[1].id],
+      tagged.iter().map(|p| p.post.id).collect::<Vec<_>>()
+    );
+    assert_eq!(2, all.len());
+  }
+
+  #[test]
+  #[serial]
+  fn test_cross_posts() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "cross_post_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let communities = (0..2)
+      .map(|i| {
+        let form = CommunityForm {
+          name: format!("cross_post_community_{}", i),
+          title: "nada".to_owned(),
+          description: None,
+          creator_id: inserted_person.id,
+          removed: None,
+          deleted: None,
+          updated: None,
+          nsfw: false,
+          actor_id: None,
+          local: true,
+          private_key: None,
+          public_key: None,
+          last_refreshed_at: None,
+          published: None,
+          icon: None,
+          banner: None,
+          followers_url: None,
+          inbox_url: None,
+          shared_inbox_url: None,
+          downvotes_disabled: None,
+          requires_post_approval: None,
+          theme_color: None,
+          short_description: None,
+          default_comment_sort: None,
+          require_post_tag: None,
+          manual_follow_approval: None,
+        };
+        Community::create(&conn, &form).unwrap()
+      })
+      .collect::<Vec<Community>>();
+
+    let create_post = |community_id, url: Option<&str>, nsfw| {
+      let form = PostForm {
+        name: "cross post".into(),
+        url: url.map(|u| Url::parse(u).unwrap().into()),
+        body: None,
+        creator_id: inserted_person.id,
+        community_id,
+        removed: None,
+        deleted: None,
+        locked: None,
+        stickied: None,
+        updated: None,
+        nsfw,
+        embed_title: None,
+        embed_description: None,
+        embed_html: None,
+        thumbnail_url: None,
+        ap_id: None,
+        local: true,
+        published: None,
+      };
+      Post::create(&conn, &form).unwrap()
+    };
+    let original = create_post(
+      communities[0].id,
+      Some("https://example.com/article"),
+      false,
+    );
+    let same_link = create_post(
+      communities[1].id,
+      Some("http://www.example.com/article/?utm_source=feed"),
+      false,
+    );
+    let nsfw = create_post(communities[1].id, Some("https://example.com/article"), true);
+    let other_link = create_post(communities[1].id, Some("https://example.com/other"), false);
+    let text_only = create_post(communities[1].id, None, false);
+
+    let cross_posts = PostView::list_cross_posts(&conn, &original, None, false).unwrap();
+    let with_nsfw = PostView::list_cross_posts(&conn, &original, None, true).unwrap();
+    let of_text_only = PostView::list_cross_posts(&conn, &text_only, None, true).unwrap();
+
+    for post in &[&original, &same_link, &nsfw, &other_link, &text_only] {
+      Post::delete(&conn, post.id).unwrap();
+    }
+    for community in &communities {
+      Community::delete(&conn, community.id).unwrap();
+    }
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(
+      vec![same_link.id],
+      cross_posts.iter().map(|p| p.post.id).collect::<Vec<_>>()
+    );
+    assert_eq!(2, with_nsfw.len());
+    assert!(of_text_only.is_empty());
+  }
+
+  #[test]
+  #[serial]
+  fn test_pinned_posts() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "pinned_post_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "pinned_post_community".into(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let posts = (0..3)
+      .map(|_| {
+        let form = PostForm {
+          name: "pinned post".into(),
+          url: None,
+          body: None,
+          creator_id: inserted_person.id,
+          community_id: inserted_community.id,
+          removed: None,
+          deleted: None,
+          locked: None,
+          stickied: None,
+          updated: None,
+          nsfw: false,
+          embed_title: None,
+          embed_description: None,
+          embed_html: None,
+          thumbnail_url: None,
+          ap_id: None,
+          local: true,
+          published: None,
+        };
+        Post::create(&conn, &form).unwrap()
+      })
+      .collect::<Vec<Post>>();
+    // The last post stays unpinned
+    for post in &posts[..2] {
+      let form = PersonPinnedPostForm {
+        person_id: inserted_person.id,
+        post_id: post.id,
+      };
+      PersonPinnedPost::pin(&conn, &form).unwrap().unwrap();
+    }
+
+    let pinned = PostView::list_pinned_for_person(&conn, inserted_person.id, None).unwrap();
+    Post::update_removed(&conn, posts[0].id, true).unwrap();
+    let after_remove = PostView::list_pinned_for_person(&conn, inserted_person.id, None).unwrap();
+
+    for post in &posts {
+      Post::delete(&conn, post.id).unwrap();
+    }
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(2, pinned.len());
+    assert_eq!(
+      vec![posts[1].id],
+      after_remove.iter().map(|p| p.post.id).collect::<Vec<_>>()
+    );
+  }
 }
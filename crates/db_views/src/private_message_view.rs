@@ -6,6 +6,7 @@ use lemmy_db_schema::{
     person::{Person, PersonAlias1, PersonSafe, PersonSafeAlias1},
     private_message::PrivateMessage,
   },
+  PersonId,
 };
 use log::debug;
 use serde::Serialize;
@@ -43,14 +44,14 @@ impl PrivateMessageView {
 
 pub struct PrivateMessageQueryBuilder<'a> {
   conn: &'a PgConnection,
-  recipient_id: i32,
+  recipient_id: PersonId,
   unread_only: bool,
   page: Option<i64>,
   limit: Option<i64>,
 }
 
 impl<'a> PrivateMessageQueryBuilder<'a> {
-  pub fn create(conn: &'a PgConnection, recipient_id: i32) -> Self {
+  pub fn create(conn: &'a PgConnection, recipient_id: PersonId) -> Self {
     PrivateMessageQueryBuilder {
       conn,
       recipient_id,
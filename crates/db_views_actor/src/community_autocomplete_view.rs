@@ -0,0 +1,184 @@
+use diesel::{dsl::*, result::Error, *};
+use lemmy_db_queries::functions::lower;
+use lemmy_db_schema::{
+  schema::{community, community_aggregates, community_person_ban},
+  CommunityId,
+  DbUrl,
+  PersonId,
+};
+use serde::Serialize;
+
+/// The most results which an autocomplete query returns
+pub const COMMUNITY_AUTOCOMPLETE_MAX_LIMIT: i64 = 10;
+
+/// A slim community for name autocompletion, much cheaper to load than a `CommunityView`
+#[derive(Debug, Serialize, Clone, Queryable, PartialEq)]
+pub struct CommunityAutocompleteView {
+  pub id: CommunityId,
+  pub name: String,
+  pub title: String,
+  pub icon: Option<DbUrl>,
+  pub subscribers: i64,
+  pub local: bool,
+}
+
+impl CommunityAutocompleteView {
+  /// Lists the communities whose name starts with `prefix`, case insensitively, with the biggest
+  /// ones first. Removed and deleted communities, and the ones which `my_person_id` is banned
+  /// from, are left out. The prefix match is served by the index on `lower(name)`.
+  pub fn list(
+    conn: &PgConnection,
+    prefix: &str,
+    my_person_id: Option<PersonId>,
+    limit: Option<i64>,
+  ) -> Result<Vec<Self>, Error> {
+    let limit = limit
+      .unwrap_or(COMMUNITY_AUTOCOMPLETE_MAX_LIMIT)
+      .max(1)
+      .min(COMMUNITY_AUTOCOMPLETE_MAX_LIMIT);
+    let pattern = format!("{}%", escape_like(&prefix.to_lowercase()));
+
+    let mut query = community::table
+      .inner_join(community_aggregates::table)
+      .select((
+        community::id,
+        community::name,
+        community::title,
+        community::icon,
+        community_aggregates::subscribers,
+        community::local,
+      ))
+      .filter(lower(community::name).like(pattern))
+      .filter(community::removed.eq(false))
+      .filter(community::deleted.eq(false))
+      .into_boxed();
+
+    if let Some(my_person_id) = my_person_id {
+      query = query.filter(not(
+        community::id.eq_any(
+          community_person_ban::table
+            .select(community_person_ban::community_id)
+            .filter(community_person_ban::person_id.eq(my_person_id)),
+        ),
+      ));
+    }
+
+    query
+      .order_by(community_aggregates::subscribers.desc())
+      .then_order_by(community::name)
+      .limit(limit)
+      .load::<Self>(conn)
+  }
+}
+
+/// Escapes the characters which have a special meaning in a LIKE pattern
+fn escape_like(s: &str) -> String {
+  s.replace('\\', "\\\\")
+    .replace('%', "\\%")
+    .replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::community_autocomplete_view::CommunityAutocompleteView;
+  use lemmy_db_queries::{establish_unpooled_connection, Bannable, Crud};
+  use lemmy_db_schema::source::{community::*, person::*};
+  use serial_test::serial;
+
+  fn community_form(name: &str, creator_id: lemmy_db_schema::PersonId) -> CommunityForm {
+    CommunityForm {
+      name: name.into(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    }
+  }
+
+  #[test]
+  #[serial]
+  fn test_autocomplete_prefix_and_ban() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "autocomplete_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let allowed = Community::create(
+      &conn,
+      &community_form("Autocomplete_allowed", inserted_person.id),
+    )
+    .unwrap();
+    let banned = Community::create(
+      &conn,
+      &community_form("autocomplete_banned", inserted_person.id),
+    )
+    .unwrap();
+    let other = Community::create(
+      &conn,
+      &community_form("other_autocomplete", inserted_person.id),
+    )
+    .unwrap();
+
+    let ban_form = CommunityPersonBanForm {
+      community_id: banned.id,
+      person_id: inserted_person.id,
+    };
+    CommunityPersonBan::ban(&conn, &ban_form).unwrap();
+
+    let anonymous = CommunityAutocompleteView::list(&conn, "AUTOCOMPLETE_", None, None).unwrap();
+    let logged_in =
+      CommunityAutocompleteView::list(&conn, "autocomplete_", Some(inserted_person.id), None)
+        .unwrap();
+    // A percent sign in the query must not act as a wildcard
+    let wildcard = CommunityAutocompleteView::list(&conn, "autocomplete%", None, None).unwrap();
+
+    CommunityPersonBan::unban(&conn, &ban_form).unwrap();
+    Community::delete(&conn, allowed.id).unwrap();
+    Community::delete(&conn, banned.id).unwrap();
+    Community::delete(&conn, other.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(2, anonymous.len());
+    assert_eq!(1, logged_in.len());
+    assert_eq!(allowed.id, logged_in[0].id);
+    assert!(wildcard.is_empty());
+  }
+}
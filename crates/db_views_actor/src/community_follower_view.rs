@@ -1,11 +1,20 @@
-use diesel::{result::Error, *};
-use lemmy_db_queries::{ToSafe, ViewToVec};
+use crate::community_view::CommunityInstanceStatus;
+use diesel::{
+  pg::Pg,
+  result::Error,
+  sql_types::{Array, BigInt, Integer},
+  *,
+};
+use lemmy_db_queries::{limit_and_offset, ToSafe, ViewToVec};
 use lemmy_db_schema::{
+  naive_now,
   schema::{community, community_follower, person},
   source::{
     community::{Community, CommunitySafe},
     person::{Person, PersonSafe},
   },
+  CommunityId,
+  PersonId,
 };
 use serde::Serialize;
 
@@ -13,18 +22,81 @@ use serde::Serialize;
 pub struct CommunityFollowerView {
   pub community: CommunitySafe,
   pub follower: PersonSafe,
+  /// Number of unread posts since the follower last viewed the community, only filled by
+  /// `fill_unread_posts`
+  pub unread_posts: Option<i64>,
+  /// Whether federation with the instance of the community is refused, only filled by
+  /// `fill_instance_status`
+  pub instance_blocked: bool,
+  /// Since when the instance of the community couldn't be reached, only filled by
+  /// `fill_instance_status`
+  pub instance_unreachable_since: Option<chrono::NaiveDateTime>,
+  /// Whether the remote community didn't accept the follow yet
+  pub pending: bool,
+  /// Whether the follow was given up without an Accept, then it can be retried by following again
+  pub follow_failed: bool,
+  /// Until when the community is muted, its posts don't count as unread until then
+  pub mute_until: Option<chrono::NaiveDateTime>,
 }
 
-type CommunityFollowerViewTuple = (CommunitySafe, PersonSafe);
+type CommunityFollowerViewTuple = (
+  CommunitySafe,
+  PersonSafe,
+  Option<bool>,
+  bool,
+  Option<chrono::NaiveDateTime>,
+);
+
+/// Unread post counts are capped, so that counting them stays cheap for very active communities
+pub const MAX_UNREAD_POSTS: i64 = 99;
+
+#[derive(QueryableByName)]
+struct UnreadPostCount {
+  #[sql_type = "Integer"]
+  community_id: CommunityId,
+  #[sql_type = "BigInt"]
+  unread_posts: i64,
+}
+
+/// The joins of the followed communities, before any filters are applied
+type FollowedCommunitiesQuery<'a> = dsl::IntoBoxed<
+  'a,
+  dsl::InnerJoin<dsl::InnerJoin<community_follower::table, community::table>, person::table>,
+  Pg,
+>;
+
+/// The followed communities of a person, shared by the listing and its count so that they always
+/// match
+fn followed_communities_query<'a>(
+  person_id: PersonId,
+  include_removed: bool,
+) -> FollowedCommunitiesQuery<'a> {
+  let mut query = community_follower::table
+    .inner_join(community::table)
+    .inner_join(person::table)
+    .filter(community_follower::person_id.eq(person_id))
+    .into_boxed();
+
+  if !include_removed {
+    query = query
+      .filter(community::deleted.eq(false))
+      .filter(community::removed.eq(false));
+  }
+
+  query
+}
 
 impl CommunityFollowerView {
-  pub fn for_community(conn: &PgConnection, community_id: i32) -> Result<Vec<Self>, Error> {
+  pub fn for_community(conn: &PgConnection, community_id: CommunityId) -> Result<Vec<Self>, Error> {
     let res = community_follower::table
       .inner_join(community::table)
       .inner_join(person::table)
       .select((
         Community::safe_columns_tuple(),
         Person::safe_columns_tuple(),
+        community_follower::pending,
+        community_follower::follow_failed,
+        community_follower::mute_until,
       ))
       .filter(community_follower::community_id.eq(community_id))
       .order_by(community_follower::published)
@@ -33,20 +105,142 @@ impl CommunityFollowerView {
     Ok(Self::from_tuple_to_vec(res))
   }
 
-  pub fn for_person(conn: &PgConnection, person_id: i32) -> Result<Vec<Self>, Error> {
+  /// The follow requests which wait for a moderator of the community, oldest first
+  pub fn list_pending(conn: &PgConnection, community_id: CommunityId) -> Result<Vec<Self>, Error> {
     let res = community_follower::table
       .inner_join(community::table)
       .inner_join(person::table)
       .select((
         Community::safe_columns_tuple(),
         Person::safe_columns_tuple(),
+        community_follower::pending,
+        community_follower::follow_failed,
+        community_follower::mute_until,
       ))
-      .filter(community_follower::person_id.eq(person_id))
+      .filter(community_follower::community_id.eq(community_id))
+      .filter(community_follower::pending.eq(true))
       .order_by(community_follower::published)
       .load::<CommunityFollowerViewTuple>(conn)?;
 
     Ok(Self::from_tuple_to_vec(res))
   }
+
+  /// The communities which the person follows, ordered by name. All of them are returned if
+  /// neither `page` nor `limit` is given. Deleted and removed communities are only included with
+  /// `include_removed`, so that they can still be unfollowed.
+  pub fn for_person(
+    conn: &PgConnection,
+    person_id: PersonId,
+    include_removed: bool,
+    page: Option<i64>,
+    limit: Option<i64>,
+  ) -> Result<Vec<Self>, Error> {
+    Self::for_person_with_has_more(conn, person_id, include_removed, page, limit)
+      .map(|(communities, _)| communities)
+  }
+
+  /// Like `for_person()`, and also tells whether there are more communities after the page
+  pub fn for_person_with_has_more(
+    conn: &PgConnection,
+    person_id: PersonId,
+    include_removed: bool,
+    page: Option<i64>,
+    limit: Option<i64>,
+  ) -> Result<(Vec<Self>, bool), Error> {
+    let mut query = followed_communities_query(person_id, include_removed)
+      .select((
+        Community::safe_columns_tuple(),
+        Person::safe_columns_tuple(),
+        community_follower::pending,
+        community_follower::follow_failed,
+        community_follower::mute_until,
+      ))
+      .order_by(community::name);
+
+    if page.is_none() && limit.is_none() {
+      let res = query.load::<CommunityFollowerViewTuple>(conn)?;
+      return Ok((Self::from_tuple_to_vec(res), false));
+    }
+
+    // One more than the limit, to know whether there is a next page
+    let (limit, offset) = limit_and_offset(page, limit);
+    query = query.limit(limit + 1).offset(offset);
+    let mut res = query.load::<CommunityFollowerViewTuple>(conn)?;
+    let has_more = res.len() as i64 > limit;
+    res.truncate(limit as usize);
+
+    Ok((Self::from_tuple_to_vec(res), has_more))
+  }
+
+  /// The number of communities which `for_person()` returns on all pages
+  pub fn count_for_person(
+    conn: &PgConnection,
+    person_id: PersonId,
+    include_removed: bool,
+  ) -> Result<i64, Error> {
+    followed_communities_query(person_id, include_removed)
+      .count()
+      .get_result(conn)
+  }
+
+  /// Counts the posts of each followed community which were published since `person_id` last
+  /// viewed it, or since they followed it if they never did, and which they havent read. The
+  /// counts are capped at `MAX_UNREAD_POSTS`, and muted communities have none.
+  pub fn fill_unread_posts(
+    conn: &PgConnection,
+    person_id: PersonId,
+    views: &mut [Self],
+  ) -> Result<(), Error> {
+    let community_ids: Vec<i32> = views.iter().map(|v| v.community.id.0).collect();
+    let counts = sql_query(
+      "select cf.community_id, (
+        select count(*) from (
+          select 1 from post p
+          where p.community_id = cf.community_id
+          and p.published > coalesce(clv.last_viewed, cf.published)
+          and not p.deleted and not p.removed
+          and not exists (
+            select 1 from post_read pr where pr.post_id = p.id and pr.person_id = cf.person_id)
+          limit $3
+        ) as unread
+      ) as unread_posts
+      from community_follower cf
+      left join community_last_viewed clv
+        on clv.community_id = cf.community_id and clv.person_id = cf.person_id
+      where cf.person_id = $1 and cf.community_id = any($2)
+      and (cf.mute_until is null or cf.mute_until < now())",
+    )
+    .bind::<Integer, _>(person_id.0)
+    .bind::<Array<Integer>, _>(community_ids)
+    .bind::<BigInt, _>(MAX_UNREAD_POSTS)
+    .load::<UnreadPostCount>(conn)?;
+
+    for view in views.iter_mut() {
+      view.unread_posts = Some(
+        counts
+          .iter()
+          .find(|c| c.community_id == view.community.id)
+          .map(|c| c.unread_posts)
+          .unwrap_or(0),
+      );
+    }
+    Ok(())
+  }
+}
+
+impl CommunityInstanceStatus for CommunityFollowerView {
+  fn community(&self) -> &CommunitySafe {
+    &self.community
+  }
+
+  fn set_instance_status(
+    &mut self,
+    blocked: bool,
+    unreachable_since: Option<chrono::NaiveDateTime>,
+  ) {
+    self.instance_blocked = blocked;
+    self.instance_unreachable_since = unreachable_since;
+  }
 }
 
 impl ViewToVec for CommunityFollowerView {
@@ -57,7 +251,251 @@ impl ViewToVec for CommunityFollowerView {
       .map(|a| Self {
         community: a.0.to_owned(),
         follower: a.1.to_owned(),
+        unread_posts: None,
+        instance_blocked: false,
+        instance_unreachable_since: None,
+        pending: a.2.unwrap_or(false),
+        follow_failed: a.3,
+        // Expired mutes are only cleared from time to time
+        mute_until: a.4.filter(|until| *until > naive_now()),
       })
       .collect::<Vec<Self>>()
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::community_follower_view::CommunityFollowerView;
+  use lemmy_db_queries::{
+    establish_unpooled_connection,
+    source::community::{CommunityFollower_, CommunityLastViewed_, Community_},
+    Crud,
+    Followable,
+    Readable,
+  };
+  use lemmy_db_schema::{
+    naive_now,
+    source::{community::*, person::*, post::*},
+  };
+  use serial_test::serial;
+
+  #[test]
+  #[serial]
+  fn test_for_person_excludes_removed() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "follower_view_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "follower_view_community".into(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let follower_form = CommunityFollowerForm {
+      community_id: inserted_community.id,
+      person_id: inserted_person.id,
+      pending: false,
+    };
+    CommunityFollower::follow(&conn, &follower_form).unwrap();
+    let no_requests = CommunityFollowerView::list_pending(&conn, inserted_community.id).unwrap();
+    let request_form = CommunityFollowerForm {
+      pending: true,
+      ..follower_form.clone()
+    };
+    CommunityFollower::follow(&conn, &request_form).unwrap();
+    let requests = CommunityFollowerView::list_pending(&conn, inserted_community.id).unwrap();
+    CommunityFollower::follow(&conn, &follower_form).unwrap();
+
+    let before_remove =
+      CommunityFollowerView::for_person(&conn, inserted_person.id, false, None, None).unwrap();
+    Community::update_removed(&conn, inserted_community.id, true).unwrap();
+    let after_remove =
+      CommunityFollowerView::for_person(&conn, inserted_person.id, false, None, None).unwrap();
+    let including_removed =
+      CommunityFollowerView::for_person(&conn, inserted_person.id, true, None, None).unwrap();
+    let count_after_remove =
+      CommunityFollowerView::count_for_person(&conn, inserted_person.id, false).unwrap();
+    let count_including_removed =
+      CommunityFollowerView::count_for_person(&conn, inserted_person.id, true).unwrap();
+
+    Community::delete(&conn, inserted_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert!(no_requests.is_empty());
+    assert_eq!(1, requests.len());
+    assert!(requests[0].pending);
+    assert_eq!(1, before_remove.len());
+    assert!(after_remove.is_empty());
+    assert_eq!(1, including_removed.len());
+    assert_eq!(inserted_community.id, including_removed[0].community.id);
+    assert_eq!(0, count_after_remove);
+    assert_eq!(1, count_including_removed);
+  }
+
+  #[test]
+  #[serial]
+  fn test_fill_unread_posts() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "unread_posts_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "unread_posts_community".into(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+    let community_id = inserted_community.id;
+    let person_id = inserted_person.id;
+
+    let follower_form = CommunityFollowerForm {
+      community_id,
+      person_id,
+      pending: false,
+    };
+    CommunityFollower::follow(&conn, &follower_form).unwrap();
+
+    let create_post = |name: &str, deleted: bool| {
+      let form = PostForm {
+        name: name.into(),
+        url: None,
+        body: None,
+        creator_id: person_id,
+        community_id,
+        removed: None,
+        locked: None,
+        published: None,
+        updated: None,
+        deleted: Some(deleted),
+        nsfw: false,
+        stickied: None,
+        embed_title: None,
+        embed_description: None,
+        embed_html: None,
+        thumbnail_url: None,
+        ap_id: None,
+        local: true,
+      };
+      Post::create(&conn, &form).unwrap()
+    };
+    let unread = || {
+      let mut views =
+        CommunityFollowerView::for_person(&conn, person_id, false, None, None).unwrap();
+      CommunityFollowerView::fill_unread_posts(&conn, person_id, &mut views).unwrap();
+      views[0].unread_posts
+    };
+
+    let none_yet = unread();
+    let read_post = create_post("unread posts read", false);
+    create_post("unread posts unread", false);
+    create_post("unread posts deleted", true);
+    let read_form = PostReadForm {
+      post_id: read_post.id,
+      person_id,
+    };
+    PostRead::mark_as_read(&conn, &read_form).unwrap();
+    let one_unread = unread();
+
+    CommunityLastViewed::mark_viewed(&conn, community_id, person_id).unwrap();
+    let after_viewed = unread();
+    create_post("unread posts newer", false);
+    let newer_unread = unread();
+
+    let in_an_hour = naive_now() + chrono::Duration::hours(1);
+    CommunityFollower::mute(&conn, community_id, person_id, Some(in_an_hour)).unwrap();
+    let muted = unread();
+
+    Community::delete(&conn, community_id).unwrap();
+    Person::delete(&conn, person_id).unwrap();
+
+    assert_eq!(Some(0), none_yet);
+    assert_eq!(Some(1), one_unread);
+    assert_eq!(Some(0), after_viewed);
+    assert_eq!(Some(1), newer_unread);
+    assert_eq!(Some(0), muted);
+  }
+}
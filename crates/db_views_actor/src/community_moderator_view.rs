@@ -6,6 +6,8 @@ use lemmy_db_schema::{
     community::{Community, CommunitySafe},
     person::{Person, PersonSafe},
   },
+  CommunityId,
+  PersonId,
 };
 use serde::Serialize;
 
@@ -13,33 +15,38 @@ use serde::Serialize;
 pub struct CommunityModeratorView {
   pub community: CommunitySafe,
   pub moderator: PersonSafe,
+  /// 0 for the head moderator
+  pub rank: i32,
 }
 
-type CommunityModeratorViewTuple = (CommunitySafe, PersonSafe);
+type CommunityModeratorViewTuple = (CommunitySafe, PersonSafe, i32);
 
 impl CommunityModeratorView {
-  pub fn for_community(conn: &PgConnection, community_id: i32) -> Result<Vec<Self>, Error> {
+  pub fn for_community(conn: &PgConnection, community_id: CommunityId) -> Result<Vec<Self>, Error> {
     let res = community_moderator::table
       .inner_join(community::table)
       .inner_join(person::table)
       .select((
         Community::safe_columns_tuple(),
         Person::safe_columns_tuple(),
+        community_moderator::rank,
       ))
       .filter(community_moderator::community_id.eq(community_id))
-      .order_by(community_moderator::published)
+      .order_by(community_moderator::rank)
+      .then_order_by(community_moderator::published)
       .load::<CommunityModeratorViewTuple>(conn)?;
 
     Ok(Self::from_tuple_to_vec(res))
   }
 
-  pub fn for_person(conn: &PgConnection, person_id: i32) -> Result<Vec<Self>, Error> {
+  pub fn for_person(conn: &PgConnection, person_id: PersonId) -> Result<Vec<Self>, Error> {
     let res = community_moderator::table
       .inner_join(community::table)
       .inner_join(person::table)
       .select((
         Community::safe_columns_tuple(),
         Person::safe_columns_tuple(),
+        community_moderator::rank,
       ))
       .filter(community_moderator::person_id.eq(person_id))
       .order_by(community_moderator::published)
@@ -57,6 +64,7 @@ impl ViewToVec for CommunityModeratorView {
       .map(|a| Self {
         community: a.0.to_owned(),
         moderator: a.1.to_owned(),
+        rank: a.2,
       })
       .collect::<Vec<Self>>()
   }
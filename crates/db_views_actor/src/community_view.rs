@@ -1,10 +1,10 @@
 use crate::{community_moderator_view::CommunityModeratorView, person_view::PersonViewSafe};
-use diesel::{result::Error, *};
+use diesel::{pg::Pg, query_source::joins::OnClauseWrapper, result::Error, *};
 use lemmy_db_queries::{
   aggregates::community_aggregates::CommunityAggregates,
-  functions::hot_rank,
   fuzzy_search,
   limit_and_offset,
+  source::instance::Instance_,
   ListingType,
   MaybeOptional,
   SortType,
@@ -12,11 +12,21 @@ use lemmy_db_queries::{
   ViewToVec,
 };
 use lemmy_db_schema::{
-  schema::{community, community_aggregates, community_follower, person},
+  schema::{
+    community,
+    community_aggregates,
+    community_favorite,
+    community_follower,
+    community_moderator,
+    person,
+  },
   source::{
-    community::{Community, CommunityFollower, CommunitySafe},
+    community::{Community, CommunityFavorite, CommunityFollower, CommunitySafe},
+    instance::Instance,
     person::{Person, PersonSafe},
   },
+  CommunityId,
+  PersonId,
 };
 use serde::Serialize;
 
@@ -25,7 +35,15 @@ pub struct CommunityView {
   pub community: CommunitySafe,
   pub creator: PersonSafe,
   pub subscribed: bool,
+  /// Whether the community is on the front page of favorites of `my_person_id`
+  pub favorited: bool,
   pub counts: CommunityAggregates,
+  /// Whether federation with the instance of the community is refused, only filled by
+  /// `fill_instance_status`
+  pub instance_blocked: bool,
+  /// Since when the instance of the community couldn't be reached, only filled by
+  /// `fill_instance_status`
+  pub instance_unreachable_since: Option<chrono::NaiveDateTime>,
 }
 
 type CommunityViewTuple = (
@@ -33,18 +51,19 @@ type CommunityViewTuple = (
   PersonSafe,
   CommunityAggregates,
   Option<CommunityFollower>,
+  Option<CommunityFavorite>,
 );
 
 impl CommunityView {
   pub fn read(
     conn: &PgConnection,
-    community_id: i32,
-    my_person_id: Option<i32>,
+    community_id: CommunityId,
+    my_person_id: Option<PersonId>,
   ) -> Result<Self, Error> {
     // The left join below will return None in this case
-    let person_id_join = my_person_id.unwrap_or(-1);
+    let person_id_join = my_person_id.unwrap_or(PersonId(-1));
 
-    let (community, creator, counts, follower) = community::table
+    let (community, creator, counts, follower, favorite) = community::table
       .find(community_id)
       .inner_join(person::table)
       .inner_join(community_aggregates::table)
@@ -55,11 +74,19 @@ impl CommunityView {
             .and(community_follower::person_id.eq(person_id_join)),
         ),
       )
+      .left_join(
+        community_favorite::table.on(
+          community::id
+            .eq(community_favorite::community_id)
+            .and(community_favorite::person_id.eq(person_id_join)),
+        ),
+      )
       .select((
         Community::safe_columns_tuple(),
         Person::safe_columns_tuple(),
         community_aggregates::all_columns,
         community_follower::all_columns.nullable(),
+        community_favorite::all_columns.nullable(),
       ))
       .first::<CommunityViewTuple>(conn)?;
 
@@ -67,13 +94,19 @@ impl CommunityView {
       community,
       creator,
       subscribed: follower.is_some(),
+      favorited: favorite.is_some(),
       counts,
+      instance_blocked: false,
+      instance_unreachable_since: None,
     })
   }
 
   // TODO: this function is only used by is_mod_or_admin() below, can probably be merged
-  fn community_mods_and_admins(conn: &PgConnection, community_id: i32) -> Result<Vec<i32>, Error> {
-    let mut mods_and_admins: Vec<i32> = Vec::new();
+  fn community_mods_and_admins(
+    conn: &PgConnection,
+    community_id: CommunityId,
+  ) -> Result<Vec<PersonId>, Error> {
+    let mut mods_and_admins: Vec<PersonId> = Vec::new();
     mods_and_admins.append(
       &mut CommunityModeratorView::for_community(conn, community_id)
         .map(|v| v.into_iter().map(|m| m.moderator.id).collect())?,
@@ -84,19 +117,52 @@ impl CommunityView {
     Ok(mods_and_admins)
   }
 
-  pub fn is_mod_or_admin(conn: &PgConnection, person_id: i32, community_id: i32) -> bool {
+  pub fn is_mod_or_admin(
+    conn: &PgConnection,
+    person_id: PersonId,
+    community_id: CommunityId,
+  ) -> bool {
     Self::community_mods_and_admins(conn, community_id)
       .unwrap_or_default()
       .contains(&person_id)
   }
 }
 
+/// A join of the community listing with the follows or favorites of `my_person_id`
+type PersonJoin<T, CommunityColumn, PersonColumn> = OnClauseWrapper<
+  T,
+  dsl::And<dsl::Eq<community::id, CommunityColumn>, dsl::Eq<PersonColumn, PersonId>>,
+>;
+
+/// The joins of a community listing, before any filters are applied
+type CommunityListQuery<'a> = dsl::IntoBoxed<
+  'a,
+  dsl::LeftJoin<
+    dsl::LeftJoin<
+      dsl::InnerJoin<dsl::InnerJoin<community::table, person::table>, community_aggregates::table>,
+      PersonJoin<
+        community_follower::table,
+        community_follower::community_id,
+        community_follower::person_id,
+      >,
+    >,
+    PersonJoin<
+      community_favorite::table,
+      community_favorite::community_id,
+      community_favorite::person_id,
+    >,
+  >,
+  Pg,
+>;
+
 pub struct CommunityQueryBuilder<'a> {
   conn: &'a PgConnection,
   listing_type: &'a ListingType,
   sort: &'a SortType,
-  my_person_id: Option<i32>,
+  my_person_id: Option<PersonId>,
   show_nsfw: bool,
+  moderated_only: bool,
+  active_since: Option<chrono::NaiveDateTime>,
   search_term: Option<String>,
   page: Option<i64>,
   limit: Option<i64>,
@@ -110,6 +176,8 @@ impl<'a> CommunityQueryBuilder<'a> {
       listing_type: &ListingType::All,
       sort: &SortType::Hot,
       show_nsfw: true,
+      moderated_only: false,
+      active_since: None,
       search_term: None,
       page: None,
       limit: None,
@@ -131,12 +199,26 @@ impl<'a> CommunityQueryBuilder<'a> {
     self
   }
 
+  /// Only list the communities which `my_person_id` moderates. The listing type and `show_nsfw` are
+  /// ignored then.
+  pub fn moderated_only(mut self, moderated_only: bool) -> Self {
+    self.moderated_only = moderated_only;
+    self
+  }
+
+  /// Only list the communities with a post or comment after the given time. The communities which
+  /// `my_person_id` follows are always listed.
+  pub fn active_since<T: MaybeOptional<chrono::NaiveDateTime>>(mut self, active_since: T) -> Self {
+    self.active_since = active_since.get_optional();
+    self
+  }
+
   pub fn search_term<T: MaybeOptional<String>>(mut self, search_term: T) -> Self {
     self.search_term = search_term.get_optional();
     self
   }
 
-  pub fn my_person_id<T: MaybeOptional<i32>>(mut self, my_person_id: T) -> Self {
+  pub fn my_person_id<T: MaybeOptional<PersonId>>(mut self, my_person_id: T) -> Self {
     self.my_person_id = my_person_id.get_optional();
     self
   }
@@ -151,9 +233,11 @@ impl<'a> CommunityQueryBuilder<'a> {
     self
   }
 
-  pub fn list(self) -> Result<Vec<CommunityView>, Error> {
+  /// The joins and filters of the listing, without its order and page. Shared by `list()` and
+  /// `count()`, so that the count always matches the listing.
+  fn filtered_query(&self) -> CommunityListQuery<'a> {
     // The left join below will return None in this case
-    let person_id_join = self.my_person_id.unwrap_or(-1);
+    let person_id_join = self.my_person_id.unwrap_or(PersonId(-1));
 
     let mut query = community::table
       .inner_join(person::table)
@@ -165,58 +249,121 @@ impl<'a> CommunityQueryBuilder<'a> {
             .and(community_follower::person_id.eq(person_id_join)),
         ),
       )
-      .select((
-        Community::safe_columns_tuple(),
-        Person::safe_columns_tuple(),
-        community_aggregates::all_columns,
-        community_follower::all_columns.nullable(),
-      ))
+      .left_join(
+        community_favorite::table.on(
+          community::id
+            .eq(community_favorite::community_id)
+            .and(community_favorite::person_id.eq(person_id_join)),
+        ),
+      )
       .into_boxed();
 
-    if let Some(search_term) = self.search_term {
-      let searcher = fuzzy_search(&search_term);
+    if let Some(search_term) = &self.search_term {
+      let searcher = fuzzy_search(search_term);
       query = query
         .filter(community::name.ilike(searcher.to_owned()))
         .or_filter(community::title.ilike(searcher.to_owned()))
         .or_filter(community::description.ilike(searcher));
     };
 
+    // A moderator sees all of their communities, also the remote and nsfw ones
+    if self.moderated_only {
+      query = query.filter(
+        community::id.eq_any(
+          community_moderator::table
+            .select(community_moderator::community_id)
+            .filter(community_moderator::person_id.eq(person_id_join)),
+        ),
+      );
+    } else {
+      if !self.show_nsfw {
+        query = query.filter(community::nsfw.eq(false));
+      };
+
+      query = match self.listing_type {
+        ListingType::Subscribed => query.filter(community_follower::person_id.is_not_null()), // TODO could be this: and(community_follower::person_id.eq(person_id_join)),
+        ListingType::Local => query.filter(community::local.eq(true)),
+        _ => query,
+      };
+    }
+
+    if let Some(active_since) = self.active_since {
+      query = query.filter(
+        community_aggregates::last_activity
+          .gt(active_since)
+          .or(community_follower::person_id.is_not_null()),
+      );
+    }
+
+    query
+      .filter(community::removed.eq(false))
+      .filter(community::deleted.eq(false))
+  }
+
+  /// Like `list()`, and also tells whether there are more communities after the page
+  pub fn list_with_has_more(self) -> Result<(Vec<CommunityView>, bool), Error> {
+    let mut query = self.filtered_query().select((
+      Community::safe_columns_tuple(),
+      Person::safe_columns_tuple(),
+      community_aggregates::all_columns,
+      community_follower::all_columns.nullable(),
+      community_favorite::all_columns.nullable(),
+    ));
+
     match self.sort {
       SortType::New => query = query.order_by(community::published.desc()),
       SortType::TopAll => query = query.order_by(community_aggregates::subscribers.desc()),
+      // Top sorts with a time window rank communities by their active users in the window, not by
+      // when they were created. The longest window which is counted is half a year.
+      SortType::TopDay | SortType::ActiveDaily => {
+        query = query
+          .order_by(community_aggregates::users_active_day.desc())
+          .then_order_by(community_aggregates::subscribers.desc())
+      }
+      SortType::TopWeek | SortType::ActiveWeekly => {
+        query = query
+          .order_by(community_aggregates::users_active_week.desc())
+          .then_order_by(community_aggregates::subscribers.desc())
+      }
+      SortType::TopMonth | SortType::ActiveMonthly => {
+        query = query
+          .order_by(community_aggregates::users_active_month.desc())
+          .then_order_by(community_aggregates::subscribers.desc())
+      }
+      SortType::TopYear | SortType::ActiveSixMonths => {
+        query = query
+          .order_by(community_aggregates::users_active_half_year.desc())
+          .then_order_by(community_aggregates::subscribers.desc())
+      }
       // Covers all other sorts, including hot
       _ => {
         query = query
-          .order_by(
-            hot_rank(
-              community_aggregates::subscribers,
-              community_aggregates::published,
-            )
-            .desc(),
-          )
+          .order_by(community_aggregates::hot_rank.desc())
           .then_order_by(community_aggregates::published.desc())
       }
     };
 
-    if !self.show_nsfw {
-      query = query.filter(community::nsfw.eq(false));
-    };
-
-    query = match self.listing_type {
-      ListingType::Subscribed => query.filter(community_follower::person_id.is_not_null()), // TODO could be this: and(community_follower::person_id.eq(person_id_join)),
-      ListingType::Local => query.filter(community::local.eq(true)),
-      _ => query,
-    };
-
+    // One more than the limit, to know whether there is a next page
     let (limit, offset) = limit_and_offset(self.page, self.limit);
-    let res = query
-      .limit(limit)
+    let mut res = query
+      .limit(limit + 1)
       .offset(offset)
-      .filter(community::removed.eq(false))
-      .filter(community::deleted.eq(false))
       .load::<CommunityViewTuple>(self.conn)?;
+    let has_more = res.len() as i64 > limit;
+    res.truncate(limit as usize);
 
-    Ok(CommunityView::from_tuple_to_vec(res))
+    Ok((CommunityView::from_tuple_to_vec(res), has_more))
+  }
+
+  pub fn list(self) -> Result<Vec<CommunityView>, Error> {
+    self
+      .list_with_has_more()
+      .map(|(communities, _)| communities)
+  }
+
+  /// The number of communities on all pages of the listing
+  pub fn count(&self) -> Result<i64, Error> {
+    self.filtered_query().count().get_result(self.conn)
   }
 }
 
@@ -230,7 +377,330 @@ impl ViewToVec for CommunityView {
         creator: a.1.to_owned(),
         counts: a.2.to_owned(),
         subscribed: a.3.is_some(),
+        favorited: a.4.is_some(),
+        instance_blocked: false,
+        instance_unreachable_since: None,
       })
       .collect::<Vec<Self>>()
   }
 }
+
+/// A view of a community which shows the status of the instance of the community
+pub trait CommunityInstanceStatus {
+  fn community(&self) -> &CommunitySafe;
+  fn set_instance_status(
+    &mut self,
+    blocked: bool,
+    unreachable_since: Option<chrono::NaiveDateTime>,
+  );
+}
+
+impl CommunityInstanceStatus for CommunityView {
+  fn community(&self) -> &CommunitySafe {
+    &self.community
+  }
+
+  fn set_instance_status(
+    &mut self,
+    blocked: bool,
+    unreachable_since: Option<chrono::NaiveDateTime>,
+  ) {
+    self.instance_blocked = blocked;
+    self.instance_unreachable_since = unreachable_since;
+  }
+}
+
+/// Fills the instance status of remote communities. Whether an instance is blocked depends on the
+/// config, so it is decided by `is_blocked`. Local communities are never blocked or unreachable.
+pub fn fill_instance_status<T: CommunityInstanceStatus>(
+  conn: &PgConnection,
+  views: &mut [T],
+  is_blocked: impl Fn(&str) -> bool,
+) -> Result<(), Error> {
+  let domains: Vec<Option<String>> = views
+    .iter()
+    .map(|v| v.community())
+    .map(|c| {
+      if c.local {
+        None
+      } else {
+        c.actor_id
+          .to_owned()
+          .into_inner()
+          .domain()
+          .map(|d| d.to_string())
+      }
+    })
+    .collect();
+  let remote_domains: Vec<String> = domains.iter().flatten().cloned().collect();
+  let instances = if remote_domains.is_empty() {
+    vec![]
+  } else {
+    Instance::list_for_domains(conn, &remote_domains)?
+  };
+
+  for (view, domain) in views.iter_mut().zip(domains) {
+    if let Some(domain) = domain {
+      let unreachable_since = instances
+        .iter()
+        .find(|i| i.domain == domain)
+        .and_then(|i| i.unreachable_since);
+      view.set_instance_status(is_blocked(&domain), unreachable_since);
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::community_view::CommunityQueryBuilder;
+  use chrono::Duration;
+  use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+  use lemmy_db_queries::{
+    establish_unpooled_connection,
+    Crud,
+    Followable,
+    Joinable,
+    ListingType,
+    SortType,
+  };
+  use lemmy_db_schema::{
+    naive_now,
+    schema::community_aggregates,
+    source::{community::*, person::*},
+  };
+  use serial_test::serial;
+
+  #[test]
+  #[serial]
+  fn test_local_listing() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "community_listing_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let community_form = |name: &str, local: bool| CommunityForm {
+      name: name.into(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let local_community = Community::create(&conn, &community_form("listing_local", true)).unwrap();
+    let remote_community =
+      Community::create(&conn, &community_form("listing_remote", false)).unwrap();
+
+    let list = |listing_type: &ListingType| {
+      CommunityQueryBuilder::create(&conn)
+        .listing_type(listing_type)
+        .sort(&SortType::New)
+        .limit(50)
+        .list()
+        .unwrap()
+        .iter()
+        .map(|c| c.community.id)
+        .filter(|id| [local_community.id, remote_community.id].contains(id))
+        .collect::<Vec<_>>()
+    };
+    let all_listing = list(&ListingType::All);
+    let local_listing = list(&ListingType::Local);
+
+    // The moderated communities are listed regardless of the listing type
+    let moderator_form = CommunityModeratorForm {
+      community_id: remote_community.id,
+      person_id: inserted_person.id,
+    };
+    CommunityModerator::join(&conn, &moderator_form).unwrap();
+    let moderated_listing = CommunityQueryBuilder::create(&conn)
+      .listing_type(&ListingType::Local)
+      .sort(&SortType::New)
+      .show_nsfw(false)
+      .moderated_only(true)
+      .my_person_id(inserted_person.id)
+      .list()
+      .unwrap()
+      .iter()
+      .map(|c| c.community.id)
+      .collect::<Vec<_>>();
+
+    let search_builder = CommunityQueryBuilder::create(&conn)
+      .sort(&SortType::New)
+      .search_term("listing_".to_string())
+      .limit(1);
+    let search_count = search_builder.count().unwrap();
+    let (search_listing, search_has_more) = search_builder.list_with_has_more().unwrap();
+
+    Community::delete(&conn, local_community.id).unwrap();
+    Community::delete(&conn, remote_community.id).unwrap();
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(vec![remote_community.id, local_community.id], all_listing);
+    assert_eq!(vec![local_community.id], local_listing);
+    assert_eq!(vec![remote_community.id], moderated_listing);
+    assert_eq!(2, search_count);
+    assert_eq!(1, search_listing.len());
+    assert!(search_has_more);
+  }
+
+  #[test]
+  #[serial]
+  fn test_active_since() {
+    let conn = establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "community_active_person".into(),
+      preferred_username: None,
+      avatar: None,
+      banner: None,
+      banned: None,
+      deleted: None,
+      published: None,
+      updated: None,
+      actor_id: None,
+      bio: None,
+      local: None,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+    };
+    let inserted_person = Person::create(&conn, &new_person).unwrap();
+
+    let community_form = |name: &str| CommunityForm {
+      name: name.into(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_person.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      icon: None,
+      banner: None,
+      followers_url: None,
+      inbox_url: None,
+      shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
+    };
+    let active_community = Community::create(&conn, &community_form("active_community")).unwrap();
+    let inactive_community =
+      Community::create(&conn, &community_form("inactive_community")).unwrap();
+    let followed_inactive_community =
+      Community::create(&conn, &community_form("followed_inactive_community")).unwrap();
+    let all_ids = [
+      active_community.id,
+      inactive_community.id,
+      followed_inactive_community.id,
+    ];
+
+    // The last post or comment was 100 days ago
+    diesel::update(
+      community_aggregates::table.filter(
+        community_aggregates::community_id
+          .eq_any(vec![inactive_community.id, followed_inactive_community.id]),
+      ),
+    )
+    .set(community_aggregates::last_activity.eq(naive_now() - Duration::days(100)))
+    .execute(&conn)
+    .unwrap();
+    let follower_form = CommunityFollowerForm {
+      community_id: followed_inactive_community.id,
+      person_id: inserted_person.id,
+      pending: false,
+    };
+    CommunityFollower::follow(&conn, &follower_form).unwrap();
+
+    let active_since = naive_now() - Duration::days(90);
+    let list = |builder: CommunityQueryBuilder| {
+      let mut ids = builder
+        .sort(&SortType::New)
+        .limit(50)
+        .list()
+        .unwrap()
+        .iter()
+        .map(|c| c.community.id)
+        .filter(|id| all_ids.contains(id))
+        .collect::<Vec<_>>();
+      ids.sort_by_key(|id| id.0);
+      ids
+    };
+    let all = list(CommunityQueryBuilder::create(&conn));
+    let active = list(CommunityQueryBuilder::create(&conn).active_since(active_since));
+    let active_or_followed = list(
+      CommunityQueryBuilder::create(&conn)
+        .my_person_id(inserted_person.id)
+        .active_since(active_since),
+    );
+    let active_count = CommunityQueryBuilder::create(&conn)
+      .search_term("active_community".to_string())
+      .active_since(active_since)
+      .count()
+      .unwrap();
+
+    CommunityFollower::unfollow(&conn, &follower_form).unwrap();
+    for id in all_ids.iter() {
+      Community::delete(&conn, *id).unwrap();
+    }
+    Person::delete(&conn, inserted_person.id).unwrap();
+
+    assert_eq!(all_ids.to_vec(), all);
+    assert_eq!(vec![active_community.id], active);
+    // Followed communities are always listed
+    assert_eq!(
+      vec![active_community.id, followed_inactive_community.id],
+      active_or_followed
+    );
+    assert_eq!(1, active_count);
+  }
+}
@@ -1,6 +1,8 @@
+pub mod community_autocomplete_view;
 pub mod community_follower_view;
 pub mod community_moderator_view;
 pub mod community_person_ban_view;
 pub mod community_view;
 pub mod person_mention_view;
+pub mod person_post_mention_view;
 pub mod person_view;
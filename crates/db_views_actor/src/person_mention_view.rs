@@ -1,7 +1,6 @@
 use diesel::{result::Error, *};
 use lemmy_db_queries::{
   aggregates::comment_aggregates::CommentAggregates,
-  functions::hot_rank,
   limit_and_offset,
   MaybeOptional,
   SortType,
@@ -29,6 +28,8 @@ use lemmy_db_schema::{
     person_mention::PersonMention,
     post::Post,
   },
+  CommunityId,
+  PersonId,
 };
 use serde::Serialize;
 
@@ -65,10 +66,10 @@ impl PersonMentionView {
   pub fn read(
     conn: &PgConnection,
     person_mention_id: i32,
-    my_person_id: Option<i32>,
+    my_person_id: Option<PersonId>,
   ) -> Result<Self, Error> {
     // The left join below will return None in this case
-    let person_id_join = my_person_id.unwrap_or(-1);
+    let person_id_join = my_person_id.unwrap_or(PersonId(-1));
 
     let (
       person_mention,
@@ -136,10 +137,10 @@ impl PersonMentionView {
     Ok(PersonMentionView {
       person_mention,
       comment,
-      creator,
+      creator: creator.hide_if_deleted(),
       post,
       community,
-      recipient,
+      recipient: recipient.hide_if_deleted(),
       counts,
       creator_banned_from_community: creator_banned_from_community.is_some(),
       subscribed: subscribed.is_some(),
@@ -151,8 +152,9 @@ impl PersonMentionView {
 
 pub struct PersonMentionQueryBuilder<'a> {
   conn: &'a PgConnection,
-  my_person_id: Option<i32>,
-  recipient_id: Option<i32>,
+  my_person_id: Option<PersonId>,
+  recipient_id: Option<PersonId>,
+  community_id: Option<CommunityId>,
   sort: &'a SortType,
   unread_only: bool,
   page: Option<i64>,
@@ -165,6 +167,7 @@ impl<'a> PersonMentionQueryBuilder<'a> {
       conn,
       my_person_id: None,
       recipient_id: None,
+      community_id: None,
       sort: &SortType::New,
       unread_only: false,
       page: None,
@@ -182,12 +185,17 @@ impl<'a> PersonMentionQueryBuilder<'a> {
     self
   }
 
-  pub fn recipient_id<T: MaybeOptional<i32>>(mut self, recipient_id: T) -> Self {
+  pub fn recipient_id<T: MaybeOptional<PersonId>>(mut self, recipient_id: T) -> Self {
     self.recipient_id = recipient_id.get_optional();
     self
   }
 
-  pub fn my_person_id<T: MaybeOptional<i32>>(mut self, my_person_id: T) -> Self {
+  pub fn community_id<T: MaybeOptional<CommunityId>>(mut self, community_id: T) -> Self {
+    self.community_id = community_id.get_optional();
+    self
+  }
+
+  pub fn my_person_id<T: MaybeOptional<PersonId>>(mut self, my_person_id: T) -> Self {
     self.my_person_id = my_person_id.get_optional();
     self
   }
@@ -203,10 +211,8 @@ impl<'a> PersonMentionQueryBuilder<'a> {
   }
 
   pub fn list(self) -> Result<Vec<PersonMentionView>, Error> {
-    use diesel::dsl::*;
-
     // The left join below will return None in this case
-    let person_id_join = self.my_person_id.unwrap_or(-1);
+    let person_id_join = self.my_person_id.unwrap_or(PersonId(-1));
 
     let mut query = person_mention::table
       .inner_join(comment::table)
@@ -262,30 +268,42 @@ impl<'a> PersonMentionQueryBuilder<'a> {
       query = query.filter(person_mention::recipient_id.eq(recipient_id));
     }
 
+    if let Some(community_id) = self.community_id {
+      query = query.filter(post::community_id.eq(community_id));
+    }
+
     if self.unread_only {
       query = query.filter(person_mention::read.eq(false));
     }
 
+    if let Some(window_start) = self
+      .sort
+      .top_window_start()
+      .or_else(|| self.sort.active_window_start())
+    {
+      query = query.filter(comment::published.gt(window_start));
+    }
+
     query = match self.sort {
-      SortType::Hot | SortType::Active => query
-        .order_by(hot_rank(comment_aggregates::score, comment_aggregates::published).desc())
+      SortType::Hot
+      | SortType::Active
+      | SortType::ActiveDaily
+      | SortType::ActiveWeekly
+      | SortType::ActiveMonthly
+      | SortType::ActiveSixMonths => query
+        .order_by(comment_aggregates::hot_rank.desc())
         .then_order_by(comment_aggregates::published.desc()),
-      SortType::New | SortType::MostComments | SortType::NewComments => {
-        query.order_by(comment::published.desc())
-      }
-      SortType::TopAll => query.order_by(comment_aggregates::score.desc()),
-      SortType::TopYear => query
-        .filter(comment::published.gt(now - 1.years()))
-        .order_by(comment_aggregates::score.desc()),
-      SortType::TopMonth => query
-        .filter(comment::published.gt(now - 1.months()))
-        .order_by(comment_aggregates::score.desc()),
-      SortType::TopWeek => query
-        .filter(comment::published.gt(now - 1.weeks()))
-        .order_by(comment_aggregates::score.desc()),
-      SortType::TopDay => query
-        .filter(comment::published.gt(now - 1.days()))
-        .order_by(comment_aggregates::score.desc()),
+      SortType::New
+      | SortType::MostComments
+      | SortType::NewComments
+      | SortType::Old
+      | SortType::QandA
+      | SortType::MostViews => query.order_by(comment::published.desc()),
+      SortType::TopAll
+      | SortType::TopYear
+      | SortType::TopMonth
+      | SortType::TopWeek
+      | SortType::TopDay => query.order_by(comment_aggregates::score.desc()),
     };
 
     let (limit, offset) = limit_and_offset(self.page, self.limit);
@@ -307,10 +325,10 @@ impl ViewToVec for PersonMentionView {
       .map(|a| Self {
         person_mention: a.0.to_owned(),
         comment: a.1.to_owned(),
-        creator: a.2.to_owned(),
+        creator: a.2.to_owned().hide_if_deleted(),
         post: a.3.to_owned(),
         community: a.4.to_owned(),
-        recipient: a.5.to_owned(),
+        recipient: a.5.to_owned().hide_if_deleted(),
         counts: a.6.to_owned(),
         creator_banned_from_community: a.7.is_some(),
         subscribed: a.8.is_some(),
@@ -0,0 +1,329 @@
+use diesel::{result::Error, *};
+use lemmy_db_queries::{
+  aggregates::post_aggregates::PostAggregates,
+  limit_and_offset,
+  MaybeOptional,
+  SortType,
+  ToSafe,
+  ViewToVec,
+};
+use lemmy_db_schema::{
+  schema::{
+    community,
+    community_follower,
+    community_person_ban,
+    person,
+    person_alias_1,
+    person_post_mention,
+    post,
+    post_aggregates,
+    post_like,
+    post_saved,
+  },
+  source::{
+    community::{Community, CommunityFollower, CommunityPersonBan, CommunitySafe},
+    person::{Person, PersonAlias1, PersonSafe, PersonSafeAlias1},
+    person_post_mention::PersonPostMention,
+    post::{Post, PostSaved},
+  },
+  CommunityId,
+  PersonId,
+};
+use serde::Serialize;
+
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct PersonPostMentionView {
+  pub person_post_mention: PersonPostMention,
+  pub post: Post,
+  pub creator: PersonSafe,
+  pub community: CommunitySafe,
+  pub recipient: PersonSafeAlias1,
+  pub counts: PostAggregates,
+  pub creator_banned_from_community: bool, // Left Join to CommunityPersonBan
+  pub subscribed: bool,                    // Left join to CommunityFollower
+  pub saved: bool,                         // Left join to PostSaved
+  pub my_vote: Option<i16>,                // Left join to PostLike
+}
+
+type PersonPostMentionViewTuple = (
+  PersonPostMention,
+  Post,
+  PersonSafe,
+  CommunitySafe,
+  PersonSafeAlias1,
+  PostAggregates,
+  Option<CommunityPersonBan>,
+  Option<CommunityFollower>,
+  Option<PostSaved>,
+  Option<i16>,
+);
+
+impl PersonPostMentionView {
+  pub fn read(
+    conn: &PgConnection,
+    person_post_mention_id: i32,
+    my_person_id: Option<PersonId>,
+  ) -> Result<Self, Error> {
+    // The left join below will return None in this case
+    let person_id_join = my_person_id.unwrap_or(PersonId(-1));
+
+    let (
+      person_post_mention,
+      post,
+      creator,
+      community,
+      recipient,
+      counts,
+      creator_banned_from_community,
+      subscribed,
+      saved,
+      my_vote,
+    ) = person_post_mention::table
+      .find(person_post_mention_id)
+      .inner_join(post::table)
+      .inner_join(person::table.on(post::creator_id.eq(person::id)))
+      .inner_join(community::table.on(post::community_id.eq(community::id)))
+      .inner_join(person_alias_1::table)
+      .inner_join(post_aggregates::table.on(post::id.eq(post_aggregates::post_id)))
+      .left_join(
+        community_person_ban::table.on(
+          community::id
+            .eq(community_person_ban::community_id)
+            .and(community_person_ban::person_id.eq(post::creator_id)),
+        ),
+      )
+      .left_join(
+        community_follower::table.on(
+          post::community_id
+            .eq(community_follower::community_id)
+            .and(community_follower::person_id.eq(person_id_join)),
+        ),
+      )
+      .left_join(
+        post_saved::table.on(
+          post::id
+            .eq(post_saved::post_id)
+            .and(post_saved::person_id.eq(person_id_join)),
+        ),
+      )
+      .left_join(
+        post_like::table.on(
+          post::id
+            .eq(post_like::post_id)
+            .and(post_like::person_id.eq(person_id_join)),
+        ),
+      )
+      .select((
+        person_post_mention::all_columns,
+        post::all_columns,
+        Person::safe_columns_tuple(),
+        Community::safe_columns_tuple(),
+        PersonAlias1::safe_columns_tuple(),
+        post_aggregates::all_columns,
+        community_person_ban::all_columns.nullable(),
+        community_follower::all_columns.nullable(),
+        post_saved::all_columns.nullable(),
+        post_like::score.nullable(),
+      ))
+      .first::<PersonPostMentionViewTuple>(conn)?;
+
+    Ok(PersonPostMentionView {
+      person_post_mention,
+      post,
+      creator: creator.hide_if_deleted(),
+      community,
+      recipient: recipient.hide_if_deleted(),
+      counts,
+      creator_banned_from_community: creator_banned_from_community.is_some(),
+      subscribed: subscribed.is_some(),
+      saved: saved.is_some(),
+      my_vote,
+    })
+  }
+}
+
+pub struct PersonPostMentionQueryBuilder<'a> {
+  conn: &'a PgConnection,
+  my_person_id: Option<PersonId>,
+  recipient_id: Option<PersonId>,
+  community_id: Option<CommunityId>,
+  sort: &'a SortType,
+  unread_only: bool,
+  page: Option<i64>,
+  limit: Option<i64>,
+}
+
+impl<'a> PersonPostMentionQueryBuilder<'a> {
+  pub fn create(conn: &'a PgConnection) -> Self {
+    PersonPostMentionQueryBuilder {
+      conn,
+      my_person_id: None,
+      recipient_id: None,
+      community_id: None,
+      sort: &SortType::New,
+      unread_only: false,
+      page: None,
+      limit: None,
+    }
+  }
+
+  pub fn sort(mut self, sort: &'a SortType) -> Self {
+    self.sort = sort;
+    self
+  }
+
+  pub fn unread_only(mut self, unread_only: bool) -> Self {
+    self.unread_only = unread_only;
+    self
+  }
+
+  pub fn recipient_id<T: MaybeOptional<PersonId>>(mut self, recipient_id: T) -> Self {
+    self.recipient_id = recipient_id.get_optional();
+    self
+  }
+
+  pub fn community_id<T: MaybeOptional<CommunityId>>(mut self, community_id: T) -> Self {
+    self.community_id = community_id.get_optional();
+    self
+  }
+
+  pub fn my_person_id<T: MaybeOptional<PersonId>>(mut self, my_person_id: T) -> Self {
+    self.my_person_id = my_person_id.get_optional();
+    self
+  }
+
+  pub fn page<T: MaybeOptional<i64>>(mut self, page: T) -> Self {
+    self.page = page.get_optional();
+    self
+  }
+
+  pub fn limit<T: MaybeOptional<i64>>(mut self, limit: T) -> Self {
+    self.limit = limit.get_optional();
+    self
+  }
+
+  pub fn list(self) -> Result<Vec<PersonPostMentionView>, Error> {
+    // The left join below will return None in this case
+    let person_id_join = self.my_person_id.unwrap_or(PersonId(-1));
+
+    let mut query = person_post_mention::table
+      .inner_join(post::table)
+      .inner_join(person::table.on(post::creator_id.eq(person::id)))
+      .inner_join(community::table.on(post::community_id.eq(community::id)))
+      .inner_join(person_alias_1::table)
+      .inner_join(post_aggregates::table.on(post::id.eq(post_aggregates::post_id)))
+      .left_join(
+        community_person_ban::table.on(
+          community::id
+            .eq(community_person_ban::community_id)
+            .and(community_person_ban::person_id.eq(post::creator_id)),
+        ),
+      )
+      .left_join(
+        community_follower::table.on(
+          post::community_id
+            .eq(community_follower::community_id)
+            .and(community_follower::person_id.eq(person_id_join)),
+        ),
+      )
+      .left_join(
+        post_saved::table.on(
+          post::id
+            .eq(post_saved::post_id)
+            .and(post_saved::person_id.eq(person_id_join)),
+        ),
+      )
+      .left_join(
+        post_like::table.on(
+          post::id
+            .eq(post_like::post_id)
+            .and(post_like::person_id.eq(person_id_join)),
+        ),
+      )
+      .select((
+        person_post_mention::all_columns,
+        post::all_columns,
+        Person::safe_columns_tuple(),
+        Community::safe_columns_tuple(),
+        PersonAlias1::safe_columns_tuple(),
+        post_aggregates::all_columns,
+        community_person_ban::all_columns.nullable(),
+        community_follower::all_columns.nullable(),
+        post_saved::all_columns.nullable(),
+        post_like::score.nullable(),
+      ))
+      .into_boxed();
+
+    if let Some(recipient_id) = self.recipient_id {
+      query = query.filter(person_post_mention::recipient_id.eq(recipient_id));
+    }
+
+    if let Some(community_id) = self.community_id {
+      query = query.filter(post::community_id.eq(community_id));
+    }
+
+    if self.unread_only {
+      query = query.filter(person_post_mention::read.eq(false));
+    }
+
+    if let Some(window_start) = self.sort.top_window_start() {
+      query = query.filter(post::published.gt(window_start));
+    }
+
+    if let Some(window_start) = self.sort.active_window_start() {
+      query = query.filter(post_aggregates::newest_comment_time_necro.gt(window_start));
+    }
+
+    query = match self.sort {
+      SortType::Hot
+      | SortType::Active
+      | SortType::ActiveDaily
+      | SortType::ActiveWeekly
+      | SortType::ActiveMonthly
+      | SortType::ActiveSixMonths => query
+        .order_by(post_aggregates::hot_rank.desc())
+        .then_order_by(post_aggregates::published.desc()),
+      SortType::New
+      | SortType::MostComments
+      | SortType::NewComments
+      | SortType::Old
+      | SortType::QandA
+      | SortType::MostViews => query.order_by(post::published.desc()),
+      SortType::TopAll
+      | SortType::TopYear
+      | SortType::TopMonth
+      | SortType::TopWeek
+      | SortType::TopDay => query.order_by(post_aggregates::score.desc()),
+    };
+
+    let (limit, offset) = limit_and_offset(self.page, self.limit);
+
+    let res = query
+      .limit(limit)
+      .offset(offset)
+      .load::<PersonPostMentionViewTuple>(self.conn)?;
+
+    Ok(PersonPostMentionView::from_tuple_to_vec(res))
+  }
+}
+
+impl ViewToVec for PersonPostMentionView {
+  type DbTuple = PersonPostMentionViewTuple;
+  fn from_tuple_to_vec(items: Vec<Self::DbTuple>) -> Vec<Self> {
+    items
+      .into_iter()
+      .map(|a| Self {
+        person_post_mention: a.0,
+        post: a.1,
+        creator: a.2.hide_if_deleted(),
+        community: a.3,
+        recipient: a.4.hide_if_deleted(),
+        counts: a.5,
+        creator_banned_from_community: a.6.is_some(),
+        subscribed: a.7.is_some(),
+        saved: a.8.is_some(),
+        my_vote: a.9,
+      })
+      .collect::<Vec<Self>>()
+  }
+}
@@ -1,4 +1,4 @@
-use diesel::{dsl::*, result::Error, *};
+use diesel::{result::Error, *};
 use lemmy_db_queries::{
   aggregates::person_aggregates::PersonAggregates,
   fuzzy_search,
@@ -9,8 +9,10 @@ use lemmy_db_queries::{
   ViewToVec,
 };
 use lemmy_db_schema::{
+  naive_now,
   schema::{local_user, person, person_aggregates},
   source::person::{Person, PersonSafe},
+  PersonId,
 };
 use serde::Serialize;
 
@@ -18,25 +20,83 @@ use serde::Serialize;
 pub struct PersonViewSafe {
   pub person: PersonSafe,
   pub counts: PersonAggregates,
+  /// `None` if the person was never active since this was tracked, or hides it
+  pub last_active: Option<LastActive>,
 }
 
-type PersonViewSafeTuple = (PersonSafe, PersonAggregates);
+/// When a person was last active, coarse enough that it doesn't reveal their daily routine
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LastActive {
+  Today,
+  ThisWeek,
+  ThisMonth,
+  Older,
+}
+
+impl LastActive {
+  fn from_last_activity(last_activity: chrono::NaiveDateTime) -> Self {
+    let since = naive_now() - last_activity;
+    if since < chrono::Duration::days(1) {
+      LastActive::Today
+    } else if since < chrono::Duration::weeks(1) {
+      LastActive::ThisWeek
+    } else if since < chrono::Duration::days(30) {
+      LastActive::ThisMonth
+    } else {
+      LastActive::Older
+    }
+  }
+}
+
+type PersonViewSafeTuple = (
+  PersonSafe,
+  PersonAggregates,
+  Option<chrono::NaiveDateTime>,
+  Option<bool>,
+);
+
+/// Remote persons have no local user, so their last activity is never hidden
+fn last_active(
+  last_activity: Option<chrono::NaiveDateTime>,
+  hide_last_active: Option<bool>,
+) -> Option<LastActive> {
+  if hide_last_active.unwrap_or(false) {
+    return None;
+  }
+  last_activity.map(LastActive::from_last_activity)
+}
 
 impl PersonViewSafe {
-  pub fn read(conn: &PgConnection, id: i32) -> Result<Self, Error> {
-    let (person, counts) = person::table
-      .find(id)
+  pub fn read(conn: &PgConnection, person_id: PersonId) -> Result<Self, Error> {
+    let (person, counts, last_activity, hide_last_active) = person::table
+      .find(person_id)
       .inner_join(person_aggregates::table)
-      .select((Person::safe_columns_tuple(), person_aggregates::all_columns))
+      .left_join(local_user::table)
+      .select((
+        Person::safe_columns_tuple(),
+        person_aggregates::all_columns,
+        person::last_activity_at,
+        local_user::hide_last_active.nullable(),
+      ))
       .first::<PersonViewSafeTuple>(conn)?;
-    Ok(Self { person, counts })
+    Ok(Self {
+      person,
+      counts,
+      last_active: last_active(last_activity, hide_last_active),
+    })
   }
 
   pub fn admins(conn: &PgConnection) -> Result<Vec<Self>, Error> {
     let admins = person::table
       .inner_join(person_aggregates::table)
       .inner_join(local_user::table)
-      .select((Person::safe_columns_tuple(), person_aggregates::all_columns))
+      .select((
+        Person::safe_columns_tuple(),
+        person_aggregates::all_columns,
+        person::last_activity_at,
+        local_user::hide_last_active.nullable(),
+      ))
       .filter(local_user::admin.eq(true))
       .order_by(person::published)
       .load::<PersonViewSafeTuple>(conn)?;
@@ -47,7 +107,13 @@ impl PersonViewSafe {
   pub fn banned(conn: &PgConnection) -> Result<Vec<Self>, Error> {
     let banned = person::table
       .inner_join(person_aggregates::table)
-      .select((Person::safe_columns_tuple(), person_aggregates::all_columns))
+      .left_join(local_user::table)
+      .select((
+        Person::safe_columns_tuple(),
+        person_aggregates::all_columns,
+        person::last_activity_at,
+        local_user::hide_last_active.nullable(),
+      ))
       .filter(person::banned.eq(true))
       .load::<PersonViewSafeTuple>(conn)?;
 
@@ -97,36 +163,49 @@ impl<'a> PersonQueryBuilder<'a> {
   pub fn list(self) -> Result<Vec<PersonViewSafe>, Error> {
     let mut query = person::table
       .inner_join(person_aggregates::table)
-      .select((Person::safe_columns_tuple(), person_aggregates::all_columns))
+      .left_join(local_user::table)
+      .select((
+        Person::safe_columns_tuple(),
+        person_aggregates::all_columns,
+        person::last_activity_at,
+        local_user::hide_last_active.nullable(),
+      ))
       .into_boxed();
 
     if let Some(search_term) = self.search_term {
       query = query.filter(person::name.ilike(fuzzy_search(&search_term)));
     }
 
+    if let Some(window_start) = self.sort.top_window_start() {
+      query = query.filter(person::published.gt(window_start));
+    }
+
+    if let Some(window_start) = self.sort.active_window_start() {
+      query = query.filter(person::last_activity_at.gt(window_start));
+    }
+
     query = match self.sort {
       SortType::Hot => query
         .order_by(person_aggregates::comment_score.desc())
         .then_order_by(person::published.desc()),
-      SortType::Active => query
+      SortType::Active
+      | SortType::ActiveDaily
+      | SortType::ActiveWeekly
+      | SortType::ActiveMonthly
+      | SortType::ActiveSixMonths => query
         .order_by(person_aggregates::comment_score.desc())
         .then_order_by(person::published.desc()),
-      SortType::New | SortType::MostComments | SortType::NewComments => {
-        query.order_by(person::published.desc())
-      }
-      SortType::TopAll => query.order_by(person_aggregates::comment_score.desc()),
-      SortType::TopYear => query
-        .filter(person::published.gt(now - 1.years()))
-        .order_by(person_aggregates::comment_score.desc()),
-      SortType::TopMonth => query
-        .filter(person::published.gt(now - 1.months()))
-        .order_by(person_aggregates::comment_score.desc()),
-      SortType::TopWeek => query
-        .filter(person::published.gt(now - 1.weeks()))
-        .order_by(person_aggregates::comment_score.desc()),
-      SortType::TopDay => query
-        .filter(person::published.gt(now - 1.days()))
-        .order_by(person_aggregates::comment_score.desc()),
+      SortType::New
+      | SortType::MostComments
+      | SortType::NewComments
+      | SortType::Old
+      | SortType::QandA
+      | SortType::MostViews => query.order_by(person::published.desc()),
+      SortType::TopAll
+      | SortType::TopYear
+      | SortType::TopMonth
+      | SortType::TopWeek
+      | SortType::TopDay => query.order_by(person_aggregates::comment_score.desc()),
     };
 
     let (limit, offset) = limit_and_offset(self.page, self.limit);
@@ -146,6 +225,7 @@ impl ViewToVec for PersonViewSafe {
       .map(|a| Self {
         person: a.0.to_owned(),
         counts: a.1.to_owned(),
+        last_active: last_active(a.2, a.3),
       })
       .collect::<Vec<Self>>()
   }
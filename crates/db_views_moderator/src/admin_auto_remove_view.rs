@@ -0,0 +1,74 @@
+use diesel::{result::Error, *};
+use lemmy_db_queries::{limit_and_offset, ToSafe, ViewToVec};
+use lemmy_db_schema::{
+  schema::{admin_auto_remove, comment, community, post},
+  source::{
+    comment::Comment,
+    community::{Community, CommunitySafe},
+    moderator::AdminAutoRemove,
+    post::Post,
+  },
+  CommunityId,
+};
+use serde::Serialize;
+
+/// A post or comment which was removed by a spam rule. `comment` is only set for comments.
+#[derive(Debug, Serialize, Clone)]
+pub struct AdminAutoRemoveView {
+  pub admin_auto_remove: AdminAutoRemove,
+  pub post: Post,
+  pub comment: Option<Comment>,
+  pub community: CommunitySafe,
+}
+
+type AdminAutoRemoveViewTuple = (AdminAutoRemove, Post, Option<Comment>, CommunitySafe);
+
+impl AdminAutoRemoveView {
+  pub fn list(
+    conn: &PgConnection,
+    community_id: Option<CommunityId>,
+    page: Option<i64>,
+    limit: Option<i64>,
+  ) -> Result<Vec<Self>, Error> {
+    let mut query = admin_auto_remove::table
+      .inner_join(post::table)
+      .left_join(comment::table)
+      .inner_join(community::table.on(post::community_id.eq(community::id)))
+      .select((
+        admin_auto_remove::all_columns,
+        post::all_columns,
+        comment::all_columns.nullable(),
+        Community::safe_columns_tuple(),
+      ))
+      .into_boxed();
+
+    if let Some(community_id) = community_id {
+      query = query.filter(post::community_id.eq(community_id));
+    };
+
+    let (limit, offset) = limit_and_offset(page, limit);
+
+    let res = query
+      .limit(limit)
+      .offset(offset)
+      .order_by(admin_auto_remove::when_.desc())
+      .load::<AdminAutoRemoveViewTuple>(conn)?;
+
+    Ok(Self::from_tuple_to_vec(res))
+  }
+}
+
+impl ViewToVec for AdminAutoRemoveView {
+  type DbTuple = AdminAutoRemoveViewTuple;
+  fn from_tuple_to_vec(items: Vec<Self::DbTuple>) -> Vec<Self> {
+    items
+      .iter()
+      .map(|a| Self {
+        admin_auto_remove: a.0.to_owned(),
+        post: a.1.to_owned(),
+        comment: a.2.to_owned(),
+        community: a.3.to_owned(),
+      })
+      .collect::<Vec<Self>>()
+  }
+}
@@ -1,9 +1,15 @@
+pub mod admin_auto_remove_view;
 pub mod mod_add_community_view;
 pub mod mod_add_view;
 pub mod mod_ban_from_community_view;
 pub mod mod_ban_view;
+pub mod mod_edit_community_view;
+pub mod mod_exempt_community_view;
+pub mod mod_feature_post_view;
+pub mod mod_lock_comment_view;
 pub mod mod_lock_post_view;
 pub mod mod_remove_comment_view;
 pub mod mod_remove_community_view;
 pub mod mod_remove_post_view;
+pub mod mod_sticky_comment_view;
 pub mod mod_sticky_post_view;
@@ -7,6 +7,8 @@ use lemmy_db_schema::{
     moderator::ModBanFromCommunity,
     person::{Person, PersonAlias1, PersonSafe, PersonSafeAlias1},
   },
+  CommunityId,
+  PersonId,
 };
 use serde::Serialize;
 
@@ -16,6 +18,8 @@ pub struct ModBanFromCommunityView {
   pub moderator: PersonSafe,
   pub community: CommunitySafe,
   pub banned_person: PersonSafeAlias1,
+  /// True if the entry was fetched from the instance of a remote community
+  pub remote: bool,
 }
 
 type ModBanFromCommunityViewTuple = (
@@ -28,8 +32,8 @@ type ModBanFromCommunityViewTuple = (
 impl ModBanFromCommunityView {
   pub fn list(
     conn: &PgConnection,
-    community_id: Option<i32>,
-    mod_person_id: Option<i32>,
+    community_id: Option<CommunityId>,
+    mod_person_id: Option<PersonId>,
     page: Option<i64>,
     limit: Option<i64>,
   ) -> Result<Vec<Self>, Error> {
@@ -77,6 +81,7 @@ impl ViewToVec for ModBanFromCommunityView {
         moderator: a.1.to_owned(),
         community: a.2.to_owned(),
         banned_person: a.3.to_owned(),
+        remote: false,
       })
       .collect::<Vec<Self>>()
   }
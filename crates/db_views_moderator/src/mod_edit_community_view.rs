@@ -0,0 +1,74 @@
+use diesel::{result::Error, *};
+use lemmy_db_queries::{limit_and_offset, ToSafe, ViewToVec};
+use lemmy_db_schema::{
+  schema::{community, mod_edit_community, person},
+  source::{
+    community::{Community, CommunitySafe},
+    moderator::ModEditCommunity,
+    person::{Person, PersonSafe},
+  },
+  CommunityId,
+  PersonId,
+};
+use serde::Serialize;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ModEditCommunityView {
+  pub mod_edit_community: ModEditCommunity,
+  pub moderator: PersonSafe,
+  pub community: CommunitySafe,
+}
+
+type ModEditCommunityTuple = (ModEditCommunity, PersonSafe, CommunitySafe);
+
+impl ModEditCommunityView {
+  pub fn list(
+    conn: &PgConnection,
+    community_id: Option<CommunityId>,
+    mod_person_id: Option<PersonId>,
+    page: Option<i64>,
+    limit: Option<i64>,
+  ) -> Result<Vec<Self>, Error> {
+    let mut query = mod_edit_community::table
+      .inner_join(person::table)
+      .inner_join(community::table)
+      .select((
+        mod_edit_community::all_columns,
+        Person::safe_columns_tuple(),
+        Community::safe_columns_tuple(),
+      ))
+      .into_boxed();
+
+    if let Some(mod_person_id) = mod_person_id {
+      query = query.filter(mod_edit_community::mod_person_id.eq(mod_person_id));
+    };
+
+    if let Some(community_id) = community_id {
+      query = query.filter(mod_edit_community::community_id.eq(community_id));
+    };
+
+    let (limit, offset) = limit_and_offset(page, limit);
+
+    let res = query
+      .limit(limit)
+      .offset(offset)
+      .order_by(mod_edit_community::when_.desc())
+      .load::<ModEditCommunityTuple>(conn)?;
+
+    Ok(Self::from_tuple_to_vec(res))
+  }
+}
+
+impl ViewToVec for ModEditCommunityView {
+  type DbTuple = ModEditCommunityTuple;
+  fn from_tuple_to_vec(items: Vec<Self::DbTuple>) -> Vec<Self> {
+    items
+      .iter()
+      .map(|a| Self {
+        mod_edit_community: a.0.to_owned(),
+        moderator: a.1.to_owned(),
+        community: a.2.to_owned(),
+      })
+      .collect::<Vec<Self>>()
+  }
+}
@@ -8,6 +8,8 @@ use lemmy_db_schema::{
     person::{Person, PersonSafe},
     post::Post,
   },
+  CommunityId,
+  PersonId,
 };
 use serde::Serialize;
 
@@ -17,6 +19,8 @@ pub struct ModLockPostView {
   pub moderator: PersonSafe,
   pub post: Post,
   pub community: CommunitySafe,
+  /// True if the entry was fetched from the instance of a remote community
+  pub remote: bool,
 }
 
 type ModLockPostViewTuple = (ModLockPost, PersonSafe, Post, CommunitySafe);
@@ -24,8 +28,8 @@ type ModLockPostViewTuple = (ModLockPost, PersonSafe, Post, CommunitySafe);
 impl ModLockPostView {
   pub fn list(
     conn: &PgConnection,
-    community_id: Option<i32>,
-    mod_person_id: Option<i32>,
+    community_id: Option<CommunityId>,
+    mod_person_id: Option<PersonId>,
     page: Option<i64>,
     limit: Option<i64>,
   ) -> Result<Vec<Self>, Error> {
@@ -71,6 +75,7 @@ impl ViewToVec for ModLockPostView {
         moderator: a.1.to_owned(),
         post: a.2.to_owned(),
         community: a.3.to_owned(),
+        remote: false,
       })
       .collect::<Vec<Self>>()
   }
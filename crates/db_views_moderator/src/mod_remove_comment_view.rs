@@ -9,6 +9,8 @@ use lemmy_db_schema::{
     person::{Person, PersonAlias1, PersonSafe, PersonSafeAlias1},
     post::Post,
   },
+  CommunityId,
+  PersonId,
 };
 use serde::Serialize;
 
@@ -20,6 +22,8 @@ pub struct ModRemoveCommentView {
   pub commenter: PersonSafeAlias1,
   pub post: Post,
   pub community: CommunitySafe,
+  /// True if the entry was fetched from the instance of a remote community
+  pub remote: bool,
 }
 
 type ModRemoveCommentViewTuple = (
@@ -34,8 +38,8 @@ type ModRemoveCommentViewTuple = (
 impl ModRemoveCommentView {
   pub fn list(
     conn: &PgConnection,
-    community_id: Option<i32>,
-    mod_person_id: Option<i32>,
+    community_id: Option<CommunityId>,
+    mod_person_id: Option<PersonId>,
     page: Option<i64>,
     limit: Option<i64>,
   ) -> Result<Vec<Self>, Error> {
@@ -87,6 +91,7 @@ impl ViewToVec for ModRemoveCommentView {
         commenter: a.3.to_owned(),
         post: a.4.to_owned(),
         community: a.5.to_owned(),
+        remote: false,
       })
       .collect::<Vec<Self>>()
   }
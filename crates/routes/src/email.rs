@@ -0,0 +1,69 @@
+use actix_web::{error::ErrorBadRequest, *};
+use anyhow::anyhow;
+use lemmy_api_structs::blocking;
+use lemmy_db_queries::source::local_user::LocalUser_;
+use lemmy_db_schema::source::local_user::LocalUser;
+use lemmy_utils::{email::escape_html, LemmyError};
+use lemmy_websocket::LemmyContext;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+  cfg
+    .route(
+      "/email/unsubscribe/{token}",
+      web::get().to(unsubscribe_confirm),
+    )
+    .route("/email/unsubscribe/{token}", web::post().to(unsubscribe));
+}
+
+/// Linked from the digest emails. Mail clients and scanners open links on their own, so this only
+/// asks for confirmation, and the form unsubscribes.
+async fn unsubscribe_confirm(token: web::Path<String>) -> HttpResponse {
+  HttpResponse::Ok()
+    .content_type("text/html; charset=utf-8")
+    .body(format!(
+      "<form method=\"post\" action=\"/email/unsubscribe/{}\">\
+       <p>Do you want to stop receiving notification emails?</p>\
+       <button type=\"submit\">Unsubscribe</button></form>",
+      escape_html(&token)
+    ))
+}
+
+/// Stops the notification emails, without logging in
+async fn unsubscribe(
+  token: web::Path<String>,
+  context: web::Data<LemmyContext>,
+) -> Result<HttpResponse, Error> {
+  let token = token.into_inner();
+  blocking(context.pool(), move |conn| {
+    LocalUser::unsubscribe_from_emails(conn, &token)
+  })
+  .await?
+  .map_err(|_| ErrorBadRequest(LemmyError::from(anyhow!("invalid_unsubscribe_token"))))?;
+
+  Ok(
+    HttpResponse::Ok()
+      .content_type("text/html; charset=utf-8")
+      .body("<p>You won't receive any more notification emails.</p>"),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::config;
+  use actix_web::{http::StatusCode, test, App};
+
+  #[actix_rt::test]
+  async fn test_unsubscribe_link_only_asks_for_confirmation() {
+    // The confirmation page doesn't touch the database, so there is no context
+    let mut app = test::init_service(App::new().configure(config)).await;
+    let req = test::TestRequest::get()
+      .uri("/email/unsubscribe/abc")
+      .to_request();
+    let res = test::call_service(&mut app, req).await;
+    assert_eq!(StatusCode::OK, res.status());
+
+    let body = test::read_body(res).await;
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    assert!(body.contains("<form method=\"post\" action=\"/email/unsubscribe/abc\">"));
+  }
+}
@@ -4,11 +4,21 @@ use chrono::{DateTime, NaiveDateTime, Utc};
 use diesel::PgConnection;
 use lemmy_api_structs::blocking;
 use lemmy_db_queries::{
-  source::{community::Community_, person::Person_},
+  source::{
+    api_token::ApiToken_,
+    community::Community_,
+    login_session::LoginSession_,
+    person::Person_,
+  },
   ListingType,
   SortType,
 };
-use lemmy_db_schema::source::{community::Community, person::Person};
+use lemmy_db_schema::source::{
+  api_token::ApiToken,
+  community::Community,
+  login_session::LoginSession,
+  person::Person,
+};
 use lemmy_db_views::{
   comment_view::{CommentQueryBuilder, CommentView},
   post_view::{PostQueryBuilder, PostView},
@@ -217,13 +227,25 @@ fn get_feed_community(
   Ok(channel_builder)
 }
 
+/// Revoked sessions and API tokens can't be used for feeds either
+fn decode_jwt(conn: &PgConnection, jwt: &str) -> Result<i32, LemmyError> {
+  let claims = Claims::decode(jwt)?.claims;
+  if let Some(api_token_id) = claims.api_token_id {
+    ApiToken::read_valid(conn, api_token_id, claims.id)?;
+  }
+  if let Some(session_id) = claims.session_id {
+    LoginSession::read_and_mark_used(conn, session_id, claims.id)?;
+  }
+  Ok(claims.id)
+}
+
 fn get_feed_front(
   conn: &PgConnection,
   sort_type: &SortType,
   jwt: String,
 ) -> Result<ChannelBuilder, LemmyError> {
   let site_view = SiteView::read(&conn)?;
-  let person_id = Claims::decode(&jwt)?.claims.id;
+  let person_id = decode_jwt(conn, &jwt)?;
 
   let posts = PostQueryBuilder::create(&conn)
     .listing_type(&ListingType::Subscribed)
@@ -249,7 +271,7 @@ fn get_feed_front(
 
 fn get_feed_inbox(conn: &PgConnection, jwt: String) -> Result<ChannelBuilder, LemmyError> {
   let site_view = SiteView::read(&conn)?;
-  let person_id = Claims::decode(&jwt)?.claims.id;
+  let person_id = decode_jwt(conn, &jwt)?;
 
   let sort = SortType::New;
 
@@ -1,8 +1,23 @@
 use actix::clock::Duration;
-use actix_web::{body::BodyStream, http::StatusCode, *};
+use actix_web::{
+  body::BodyStream,
+  http::{
+    header::{CACHE_CONTROL, CONTENT_TYPE},
+    StatusCode,
+  },
+  *,
+};
 use awc::Client;
-use lemmy_utils::{claims::Claims, rate_limit::RateLimit, settings::structs::Settings};
+use lemmy_api_structs::decode_jwt;
+use lemmy_db_queries::ApiTokenScope;
+use lemmy_utils::{rate_limit::RateLimit, settings::structs::Settings};
+use lemmy_websocket::LemmyContext;
 use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, ToSocketAddrs};
+use url::Url;
+
+/// Maximum size of a remote image served through the image proxy
+const MAX_PROXY_IMAGE_SIZE: usize = 10 * 1024 * 1024;
 
 pub fn config(cfg: &mut web::ServiceConfig, rate_limit: &RateLimit) {
   let client = Client::builder()
@@ -19,7 +34,12 @@ pub fn config(cfg: &mut web::ServiceConfig, rate_limit: &RateLimit) {
     )
     // This has optional query params: /image/{filename}?format=jpg&thumbnail=256
     .service(web::resource("/pictrs/image/{filename}").route(web::get().to(full_res)))
-    .service(web::resource("/pictrs/image/delete/{token}/{filename}").route(web::get().to(delete)));
+    .service(web::resource("/pictrs/image/delete/{token}/{filename}").route(web::get().to(delete)))
+    .service(
+      web::resource("/api/v3/image_proxy")
+        .wrap(rate_limit.message())
+        .route(web::get().to(image_proxy)),
+    );
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -34,6 +54,11 @@ struct Images {
   files: Option<Vec<Image>>,
 }
 
+#[derive(Deserialize)]
+struct ImageProxyParams {
+  url: String,
+}
+
 #[derive(Deserialize)]
 struct PictrsParams {
   format: Option<String>,
@@ -44,15 +69,20 @@ async fn upload(
   req: HttpRequest,
   body: web::Payload,
   client: web::Data<Client>,
+  context: web::Data<LemmyContext>,
 ) -> Result<HttpResponse, Error> {
   // TODO: check rate limit here
   let jwt = req
     .cookie("jwt")
     .expect("No auth header for picture upload");
 
-  if Claims::decode(jwt.value()).is_err() {
-    return Ok(HttpResponse::Unauthorized().finish());
-  };
+  // Revoked sessions and API tokens are rejected like in the API, and tokens need to be allowed to
+  // write
+  match decode_jwt(jwt.value(), context.pool()).await {
+    Ok((_, None)) => {}
+    Ok((_, Some(scopes))) if scopes.contains(&ApiTokenScope::Write) => {}
+    _ => return Ok(HttpResponse::Unauthorized().finish()),
+  }
 
   let mut client_req = client.request_from(
     format!("{}/image", Settings::get().pictrs_url()),
@@ -151,3 +181,128 @@ async fn delete(
 
   Ok(HttpResponse::build(res.status()).body(BodyStream::new(res)))
 }
+
+/// Serves a remote image through our server, so that the remote host doesnt see the ip addresses of
+/// our users. Only images from public hosts are proxied, up to a maximum size.
+async fn image_proxy(
+  web::Query(params): web::Query<ImageProxyParams>,
+  client: web::Data<Client>,
+) -> Result<HttpResponse, Error> {
+  let url = match Url::parse(&params.url) {
+    Ok(url) => url,
+    Err(_) => return Ok(HttpResponse::BadRequest().finish()),
+  };
+  if !is_public_url(&url).await {
+    return Ok(HttpResponse::Forbidden().finish());
+  }
+
+  // Broken remote images are returned as not found, so that clients show their placeholder
+  let mut res = match client.get(url.as_str()).send().await {
+    Ok(res) if res.status().is_success() => res,
+    _ => return Ok(HttpResponse::NotFound().finish()),
+  };
+
+  let content_type = res
+    .headers()
+    .get(CONTENT_TYPE)
+    .and_then(|c| c.to_str().ok())
+    .unwrap_or_default()
+    .to_owned();
+  if !content_type.starts_with("image/") {
+    return Ok(HttpResponse::UnsupportedMediaType().finish());
+  }
+
+  let body = match res.body().limit(MAX_PROXY_IMAGE_SIZE).await {
+    Ok(body) => body,
+    Err(_) => return Ok(HttpResponse::PayloadTooLarge().finish()),
+  };
+
+  Ok(
+    HttpResponse::Ok()
+      .content_type(content_type)
+      .header(CACHE_CONTROL, "public, max-age=86400")
+      .body(body),
+  )
+}
+
+/// Checks that the url points to a host which is reachable over the public internet, so that the
+/// image proxy cant be used to access services in our private network.
+async fn is_public_url(url: &Url) -> bool {
+  if url.scheme() != "http" && url.scheme() != "https" {
+    return false;
+  }
+  let host = match url.host_str() {
+    Some(host) => host.to_owned(),
+    None => return false,
+  };
+  let port = url.port_or_known_default().unwrap_or(80);
+  let addrs = web::block(move || {
+    (host.as_str(), port)
+      .to_socket_addrs()
+      .map(|addrs| addrs.collect::<Vec<_>>())
+  })
+  .await;
+  match addrs {
+    Ok(addrs) => !addrs.is_empty() && addrs.iter().all(|a| is_public_ip(&a.ip())),
+    Err(_) => false,
+  }
+}
+
+fn is_public_ip(ip: &IpAddr) -> bool {
+  match ip {
+    IpAddr::V4(ip) => {
+      let octets = ip.octets();
+      let is_shared = octets[0] == 100 && (octets[1] & 0b1100_0000) == 64;
+      !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_unspecified()
+        || octets[0] == 0
+        || is_shared)
+    }
+    IpAddr::V6(ip) => {
+      if ip.is_loopback() || ip.is_unspecified() {
+        return false;
+      }
+      if let Some(ipv4) = ip.to_ipv4() {
+        return is_public_ip(&IpAddr::V4(ipv4));
+      }
+      let first_segment = ip.segments()[0];
+      let is_unique_local = (first_segment & 0xfe00) == 0xfc00;
+      let is_link_local = (first_segment & 0xffc0) == 0xfe80;
+      !(is_unique_local || is_link_local)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_is_public_ip() {
+    let public = ["1.1.1.1", "93.184.216.34", "2606:4700:4700::1111"];
+    for ip in &public {
+      assert!(is_public_ip(&ip.parse().unwrap()), "{}", ip);
+    }
+
+    let private = [
+      "127.0.0.1",
+      "10.1.2.3",
+      "172.16.0.1",
+      "192.168.1.1",
+      "169.254.169.254",
+      "100.64.0.1",
+      "0.0.0.0",
+      "::1",
+      "::ffff:192.168.1.1",
+      "fd00::1",
+      "fe80::1",
+    ];
+    for ip in &private {
+      assert!(!is_public_ip(&ip.parse().unwrap()), "{}", ip);
+    }
+  }
+}
@@ -1,6 +1,7 @@
 #[macro_use]
 extern crate lazy_static;
 
+pub mod email;
 pub mod feeds;
 pub mod images;
 pub mod nodeinfo;
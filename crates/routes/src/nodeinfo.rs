@@ -5,8 +5,19 @@ use lemmy_db_views::site_view::SiteView;
 use lemmy_utils::{settings::structs::Settings, version, LemmyError};
 use lemmy_websocket::LemmyContext;
 use serde::{Deserialize, Serialize};
+use std::{
+  sync::Mutex,
+  time::{Duration, Instant},
+};
 use url::Url;
 
+/// Crawlers request the nodeinfo a lot, so it is only recomputed after this interval.
+const NODE_INFO_CACHE_DURATION: Duration = Duration::from_secs(5 * 60);
+
+lazy_static! {
+  static ref NODE_INFO_CACHE: Mutex<Option<(Instant, NodeInfo)>> = Mutex::new(None);
+}
+
 pub fn config(cfg: &mut web::ServiceConfig) {
   cfg
     .route("/nodeinfo/2.0.json", web::get().to(node_info))
@@ -27,6 +38,12 @@ async fn node_info_well_known() -> Result<HttpResponse<Body>, LemmyError> {
 }
 
 async fn node_info(context: web::Data<LemmyContext>) -> Result<HttpResponse, Error> {
+  if let Some((created, node_info)) = NODE_INFO_CACHE.lock().expect("lock nodeinfo").as_ref() {
+    if created.elapsed() < NODE_INFO_CACHE_DURATION {
+      return Ok(HttpResponse::Ok().json(node_info));
+    }
+  }
+
   let site_view = blocking(context.pool(), SiteView::read)
     .await?
     .map_err(|_| ErrorBadRequest(LemmyError::from(anyhow!("not_found"))))?;
@@ -44,6 +61,7 @@ async fn node_info(context: web::Data<LemmyContext>) -> Result<HttpResponse, Err
       version: version::VERSION.to_string(),
     },
     protocols,
+    services: NodeInfoServices::default(),
     usage: NodeInfoUsage {
       users: NodeInfoUsers {
         total: site_view.counts.users,
@@ -54,9 +72,12 @@ async fn node_info(context: web::Data<LemmyContext>) -> Result<HttpResponse, Err
       local_comments: site_view.counts.comments,
     },
     open_registrations: site_view.site.open_registration,
+    metadata: NodeInfoMetadata::default(),
   };
 
-  Ok(HttpResponse::Ok().json(json))
+  let res = HttpResponse::Ok().json(&json);
+  *NODE_INFO_CACHE.lock().expect("lock nodeinfo") = Some((Instant::now(), json));
+  Ok(res)
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -76,8 +97,10 @@ struct NodeInfo {
   pub version: String,
   pub software: NodeInfoSoftware,
   pub protocols: Vec<String>,
+  pub services: NodeInfoServices,
   pub usage: NodeInfoUsage,
   pub open_registrations: bool,
+  pub metadata: NodeInfoMetadata,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -86,6 +109,15 @@ struct NodeInfoSoftware {
   pub version: String,
 }
 
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct NodeInfoServices {
+  pub inbound: Vec<String>,
+  pub outbound: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct NodeInfoMetadata {}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 struct NodeInfoUsage {
@@ -101,3 +133,35 @@ struct NodeInfoUsers {
   pub active_halfyear: i64,
   pub active_month: i64,
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_node_info_schema() {
+    let node_info = NodeInfo {
+      version: "2.0".to_string(),
+      software: NodeInfoSoftware {
+        name: "lemmy".to_string(),
+        version: "0.10.0".to_string(),
+      },
+      protocols: vec!["activitypub".to_string()],
+      services: NodeInfoServices::default(),
+      usage: NodeInfoUsage {
+        users: NodeInfoUsers {
+          total: 10,
+          active_halfyear: 5,
+          active_month: 3,
+        },
+        local_posts: 20,
+        local_comments: 30,
+      },
+      open_registrations: true,
+      metadata: NodeInfoMetadata::default(),
+    };
+
+    let expected = r#"{"version":"2.0","software":{"name":"lemmy","version":"0.10.0"},"protocols":["activitypub"],"services":{"inbound":[],"outbound":[]},"usage":{"users":{"total":10,"activeHalfyear":5,"activeMonth":3},"localPosts":20,"localComments":30},"openRegistrations":true,"metadata":{}}"#;
+    assert_eq!(expected, serde_json::to_string(&node_info).unwrap());
+  }
+}
@@ -0,0 +1,67 @@
+use crate::LemmyError;
+use std::{
+  future::Future,
+  sync::Arc,
+  time::{Duration, Instant},
+};
+use tokio::sync::RwLock;
+
+struct CacheState<T> {
+  value: Option<(Instant, T)>,
+  /// Incremented on every invalidation, so that a value which was built from old data isn't stored
+  generation: u64,
+}
+
+/// Keeps a single value in memory for up to `max_age`. Meant for expensive responses which are the
+/// same for every user.
+#[derive(Clone)]
+pub struct TimedCache<T> {
+  max_age: Duration,
+  state: Arc<RwLock<CacheState<T>>>,
+}
+
+impl<T: Clone> TimedCache<T> {
+  pub fn new(max_age: Duration) -> Self {
+    TimedCache {
+      max_age,
+      state: Arc::new(RwLock::new(CacheState {
+        value: None,
+        generation: 0,
+      })),
+    }
+  }
+
+  /// Returns the cached value if it is recent enough, otherwise builds and stores a new one with
+  /// `refresh`.
+  pub async fn get_or_refresh<F, Fut>(&self, refresh: F) -> Result<T, LemmyError>
+  where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<T, LemmyError>>,
+  {
+    let generation = {
+      let state = self.state.read().await;
+      if let Some((stored, value)) = &state.value {
+        if stored.elapsed() < self.max_age {
+          return Ok(value.to_owned());
+        }
+      }
+      state.generation
+    };
+
+    let value = refresh().await?;
+
+    let mut state = self.state.write().await;
+    // If the cache was invalidated in the meantime, the new value might already be outdated
+    if state.generation == generation {
+      state.value = Some((Instant::now(), value.to_owned()));
+    }
+    Ok(value)
+  }
+
+  /// Drops the cached value. Has to be called whenever the underlying data changes.
+  pub async fn invalidate(&self) {
+    let mut state = self.state.write().await;
+    state.value = None;
+    state.generation += 1;
+  }
+}
@@ -1,5 +1,14 @@
 use crate::settings::structs::Settings;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, TokenData, Validation};
+use jsonwebtoken::{
+  decode,
+  encode,
+  errors::ErrorKind,
+  DecodingKey,
+  EncodingKey,
+  Header,
+  TokenData,
+  Validation,
+};
 use serde::{Deserialize, Serialize};
 
 type Jwt = String;
@@ -8,29 +17,66 @@ type Jwt = String;
 pub struct Claims {
   pub id: i32,
   pub iss: String,
+  /// Set for API tokens, which are only valid as long as their row exists
+  #[serde(default)]
+  pub api_token_id: Option<i32>,
+  /// Set for logins, which are only valid as long as their session exists. Tokens which were
+  /// issued before sessions existed don't have it, and are rejected by `decode()`.
+  #[serde(default)]
+  pub session_id: Option<i32>,
 }
 
 impl Claims {
+  /// Decodes a jwt of a login or an API token. Logins from before sessions existed can't be
+  /// revoked, so they are rejected and have to log in again.
   pub fn decode(jwt: &str) -> Result<TokenData<Claims>, jsonwebtoken::errors::Error> {
     let v = Validation {
       validate_exp: false,
       ..Validation::default()
     };
-    decode::<Claims>(
+    let token = decode::<Claims>(
       &jwt,
       &DecodingKey::from_secret(Settings::get().jwt_secret().as_ref()),
       &v,
-    )
+    )?;
+    if token.claims.session_id.is_none() && token.claims.api_token_id.is_none() {
+      return Err(ErrorKind::InvalidToken.into());
+    }
+    Ok(token)
   }
 
-  pub fn jwt(local_user_id: i32, hostname: String) -> Result<Jwt, jsonwebtoken::errors::Error> {
+  pub fn jwt(
+    local_user_id: i32,
+    session_id: i32,
+    hostname: String,
+  ) -> Result<Jwt, jsonwebtoken::errors::Error> {
     let my_claims = Claims {
       id: local_user_id,
       iss: hostname,
+      api_token_id: None,
+      session_id: Some(session_id),
     };
+    Claims::encode(&my_claims)
+  }
+
+  pub fn api_token_jwt(
+    local_user_id: i32,
+    api_token_id: i32,
+    hostname: String,
+  ) -> Result<Jwt, jsonwebtoken::errors::Error> {
+    let my_claims = Claims {
+      id: local_user_id,
+      iss: hostname,
+      api_token_id: Some(api_token_id),
+      session_id: None,
+    };
+    Claims::encode(&my_claims)
+  }
+
+  fn encode(claims: &Claims) -> Result<Jwt, jsonwebtoken::errors::Error> {
     encode(
       &Header::default(),
-      &my_claims,
+      claims,
       &EncodingKey::from_secret(Settings::get().jwt_secret().as_ref()),
     )
   }
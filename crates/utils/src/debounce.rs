@@ -0,0 +1,213 @@
+use crate::settings::structs::VoteDebounceConfig;
+use std::{
+  collections::HashMap,
+  hash::Hash,
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+
+/// An activity which federates a vote
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VoteActivity {
+  Like,
+  Dislike,
+  UndoLike,
+  UndoDislike,
+}
+
+/// The activities which federate the change of a vote from `previous_score` to `score`, in the
+/// order they have to be sent
+pub fn vote_activities(previous_score: Option<i16>, score: i16) -> Vec<VoteActivity> {
+  let mut activities = Vec::new();
+  match previous_score {
+    Some(1) if score != 1 => activities.push(VoteActivity::UndoLike),
+    Some(-1) if score != -1 => activities.push(VoteActivity::UndoDislike),
+    _ => {}
+  }
+  match score {
+    1 => activities.push(VoteActivity::Like),
+    -1 => activities.push(VoteActivity::Dislike),
+    _ => {}
+  }
+  activities
+}
+
+/// A vote which is held back. `previous_score` is the last score which was federated, and `score`
+/// the latest one which the voter chose.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PendingVote {
+  pub previous_score: i16,
+  pub score: i16,
+}
+
+impl PendingVote {
+  /// Whether the votes in the meantime changed anything, otherwise nothing has to be sent
+  pub fn is_changed(&self) -> bool {
+    self.previous_score != self.score
+  }
+
+  /// The activities which have to be sent once the vote is flushed
+  pub fn activities(&self) -> Vec<VoteActivity> {
+    if self.is_changed() {
+      vote_activities(Some(self.previous_score), self.score)
+    } else {
+      Vec::new()
+    }
+  }
+}
+
+/// What the caller has to do with a vote after handing it to `VoteDebouncer::record()`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebouncedVote {
+  /// The first vote for this key, it has to be flushed with `take()` after `delay()`
+  Scheduled,
+  /// Merged into a vote which is already scheduled
+  Merged,
+  /// Debouncing is disabled or full, the vote has to be sent right away
+  SendNow,
+}
+
+/// Holds back outgoing votes for a few seconds per voter and object, so that a vote which is
+/// changed or undone right away is federated only once, or not at all. The number of held back
+/// votes is bounded, and the remaining ones have to be flushed with `take_all()` on shutdown.
+#[derive(Clone)]
+pub struct VoteDebouncer<K> {
+  config: VoteDebounceConfig,
+  pending: Arc<Mutex<HashMap<K, PendingVote>>>,
+}
+
+impl<K: Hash + Eq + Clone> VoteDebouncer<K> {
+  pub fn new(config: VoteDebounceConfig) -> Self {
+    VoteDebouncer {
+      config,
+      pending: Arc::new(Mutex::new(HashMap::new())),
+    }
+  }
+
+  pub fn delay(&self) -> Duration {
+    Duration::from_secs(self.config.seconds)
+  }
+
+  /// Records that the score of the vote `key` changed from `previous_score` to `score`
+  pub fn record(&self, key: K, previous_score: i16, score: i16) -> DebouncedVote {
+    if self.config.seconds == 0 {
+      return DebouncedVote::SendNow;
+    }
+    let mut pending = self.pending.lock().expect("lock vote debouncer");
+    if let Some(vote) = pending.get_mut(&key) {
+      vote.score = score;
+      return DebouncedVote::Merged;
+    }
+    if pending.len() >= self.config.max_pending {
+      return DebouncedVote::SendNow;
+    }
+    pending.insert(
+      key,
+      PendingVote {
+        previous_score,
+        score,
+      },
+    );
+    DebouncedVote::Scheduled
+  }
+
+  /// Removes the held back vote, so that it can be sent
+  pub fn take(&self, key: &K) -> Option<PendingVote> {
+    let mut pending = self.pending.lock().expect("lock vote debouncer");
+    pending.remove(key)
+  }
+
+  /// Removes all held back votes
+  pub fn take_all(&self) -> Vec<(K, PendingVote)> {
+    let mut pending = self.pending.lock().expect("lock vote debouncer");
+    pending.drain().collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    debounce::{vote_activities, DebouncedVote, PendingVote, VoteActivity, VoteDebouncer},
+    settings::structs::VoteDebounceConfig,
+  };
+
+  fn debouncer(seconds: u64, max_pending: usize) -> VoteDebouncer<(i32, i32)> {
+    VoteDebouncer::new(VoteDebounceConfig {
+      seconds,
+      max_pending,
+    })
+  }
+
+  #[test]
+  fn test_vote_then_unvote_sends_nothing() {
+    let debouncer = debouncer(10, 100);
+    assert_eq!(DebouncedVote::Scheduled, debouncer.record((1, 2), 0, 1));
+    assert_eq!(DebouncedVote::Merged, debouncer.record((1, 2), 1, 0));
+
+    let vote = debouncer.take(&(1, 2)).unwrap();
+    assert!(!vote.is_changed());
+    assert!(debouncer.take(&(1, 2)).is_none());
+  }
+
+  /// Votes like `send_vote_debounced()` does, and returns the activities which are sent right away
+  /// and the ones which are flushed afterwards
+  fn vote_and_flush(
+    debouncer: &VoteDebouncer<(i32, i32)>,
+    votes: &[(Option<i16>, i16)],
+  ) -> Vec<VoteActivity> {
+    let mut sent = Vec::new();
+    for (previous_score, score) in votes {
+      let debounced = debouncer.record((1, 2), previous_score.unwrap_or(0), *score);
+      if debounced == DebouncedVote::SendNow {
+        sent.extend(vote_activities(*previous_score, *score));
+      }
+    }
+    for (_, vote) in debouncer.take_all() {
+      sent.extend(vote.activities());
+    }
+    sent
+  }
+
+  #[test]
+  fn test_vote_then_unvote_enqueues_no_activities() {
+    let vote_then_unvote = [(None, 1), (Some(1), 0)];
+    assert!(vote_and_flush(&debouncer(10, 100), &vote_then_unvote).is_empty());
+
+    // Without debouncing, both the like and its undo are sent
+    assert_eq!(
+      vec![VoteActivity::Like, VoteActivity::UndoLike],
+      vote_and_flush(&debouncer(0, 100), &vote_then_unvote)
+    );
+    // A vote which is changed is sent as the net change
+    assert_eq!(
+      vec![VoteActivity::Dislike],
+      vote_and_flush(&debouncer(10, 100), &[(None, 1), (Some(1), -1)])
+    );
+  }
+
+  #[test]
+  fn test_flipped_vote_sends_net_change() {
+    let debouncer = debouncer(10, 100);
+    debouncer.record((1, 2), 1, 0);
+    debouncer.record((1, 2), 0, -1);
+
+    let expected = PendingVote {
+      previous_score: 1,
+      score: -1,
+    };
+    assert_eq!(Some(expected), debouncer.take(&(1, 2)));
+  }
+
+  #[test]
+  fn test_disabled_and_full() {
+    let disabled = debouncer(0, 100);
+    assert_eq!(DebouncedVote::SendNow, disabled.record((1, 2), 0, 1));
+
+    let full = debouncer(10, 1);
+    assert_eq!(DebouncedVote::Scheduled, full.record((1, 2), 0, 1));
+    assert_eq!(DebouncedVote::SendNow, full.record((1, 3), 0, 1));
+    // Votes which are already held back can still be merged
+    assert_eq!(DebouncedVote::Merged, full.record((1, 2), 1, 0));
+    assert_eq!(1, full.take_all().len());
+  }
+}
@@ -95,3 +95,63 @@ pub fn send_email(
     Err(e) => Err(e.to_string()),
   }
 }
+
+/// One notification in a digest email, as creator name, content and link
+pub type DigestItem = (String, String, String);
+
+/// The html of a digest email. Notifications are user content, so everything is escaped.
+pub fn digest_html(
+  sections: &[(&str, &[DigestItem])],
+  protocol_and_hostname: &str,
+  unsubscribe_token: &str,
+) -> String {
+  let count: usize = sections.iter().map(|(_, items)| items.len()).sum();
+  let sections = sections
+    .iter()
+    .map(|(title, items)| digest_section(title, items))
+    .collect::<String>();
+  format!(
+    "<h1>{} new notifications</h1>{}<br><a href=\"{}/inbox\">inbox</a><br>\
+     <a href=\"{}/email/unsubscribe/{}\">unsubscribe</a>",
+    count,
+    sections,
+    escape_html(protocol_and_hostname),
+    escape_html(protocol_and_hostname),
+    escape_html(unsubscribe_token),
+  )
+}
+
+/// Lists the notifications of one kind, nothing if there are none
+fn digest_section(title: &str, items: &[DigestItem]) -> String {
+  if items.is_empty() {
+    return String::new();
+  }
+  let items = items
+    .iter()
+    .map(|(creator, content, url)| {
+      format!(
+        "<li>{} - {} <a href=\"{}\">link</a></li>",
+        escape_html(creator),
+        escape_html(content),
+        escape_html(url)
+      )
+    })
+    .collect::<String>();
+  format!("<h2>{}</h2><ul>{}</ul>", escape_html(title), items)
+}
+
+/// Escapes text for use in html content and quoted attributes
+pub fn escape_html(text: &str) -> String {
+  let mut escaped = String::with_capacity(text.len());
+  for c in text.chars() {
+    match c {
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      '"' => escaped.push_str("&quot;"),
+      '\'' => escaped.push_str("&#39;"),
+      c => escaped.push(c),
+    }
+  }
+  escaped
+}
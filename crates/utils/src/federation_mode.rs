@@ -0,0 +1,58 @@
+use crate::settings::structs::Settings;
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+
+/// Who the instance federates with. It is set by the admins on the site, and is applied on top of
+/// the federation options of the config. The discriminants are stored on the site, so they must
+/// not change.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum FederationMode {
+  /// Federate with all instances which aren't blocked in the config
+  Open = 0,
+  /// Only federate with the `allowed_instances` of the config
+  Allowlist = 1,
+  /// Don't federate at all
+  Disabled = 2,
+}
+
+/// How often each process reads the mode from the site again
+pub const REFRESH_SECONDS: u64 = 30;
+
+lazy_static! {
+  static ref FEDERATION_MODE: RwLock<FederationMode> = RwLock::new(FederationMode::Open);
+}
+
+impl FederationMode {
+  /// The mode of the site. It is global instead of being part of the context, because the
+  /// federation checks happen in places without access to the context, like the activity queue.
+  /// Every server process has its own copy, which it reads from the site again every
+  /// `REFRESH_SECONDS`, so that changes made through another process take effect there too.
+  pub fn get() -> Self {
+    *FEDERATION_MODE.read().expect("lock federation mode")
+  }
+
+  /// Called at startup, after the site was edited and when refreshing, so that a new mode takes
+  /// effect immediately in this process
+  pub fn set(mode: Self) {
+    *FEDERATION_MODE.write().expect("lock federation mode") = mode;
+  }
+
+  /// The mode which is actually used, federation is always disabled if the config disables it
+  pub fn effective() -> Self {
+    if Settings::get().federation().enabled {
+      FederationMode::get()
+    } else {
+      FederationMode::Disabled
+    }
+  }
+
+  /// Converts the number which is stored on the site, unknown values are treated as `Open`
+  pub fn from_i16(value: i16) -> Self {
+    use FederationMode::*;
+    match value {
+      v if v == Allowlist as i16 => Allowlist,
+      v if v == Disabled as i16 => Disabled,
+      _ => Open,
+    }
+  }
+}
@@ -0,0 +1,102 @@
+use crate::{settings::structs::FetchLimitConfig, LemmyError};
+use serde::Serialize;
+use std::{
+  collections::HashMap,
+  sync::{Arc, Mutex},
+  time::{Duration, Instant},
+};
+use thiserror::Error;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+const BUDGET_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Returned when the hourly fetch budget for a remote instance is used up. Incoming activities
+/// which fail with this error should be retried later instead of being dropped.
+#[derive(Debug, Error)]
+#[error("Fetch budget exhausted for instance {domain}")]
+pub struct FetchBudgetExhausted {
+  pub domain: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct InstanceFetchStats {
+  pub domain: String,
+  pub fetches_this_hour: i32,
+  pub hourly_limit: i32,
+  pub in_flight: usize,
+}
+
+struct InstanceFetchState {
+  semaphore: Arc<Semaphore>,
+  window_start: Instant,
+  fetches: i32,
+}
+
+/// Limits the number of remote object fetches per remote instance, both in parallel and per hour.
+#[derive(Clone)]
+pub struct FetchLimiter {
+  config: FetchLimitConfig,
+  instances: Arc<Mutex<HashMap<String, InstanceFetchState>>>,
+}
+
+impl FetchLimiter {
+  pub fn new(config: FetchLimitConfig) -> Self {
+    FetchLimiter {
+      config,
+      instances: Arc::new(Mutex::new(HashMap::new())),
+    }
+  }
+
+  /// Maximum number of objects which may be fetched while handling a single activity.
+  pub fn per_activity(&self) -> i32 {
+    self.config.per_activity
+  }
+
+  /// Takes one fetch from the hourly budget of `domain`, and waits until one of the concurrent
+  /// fetch slots for this instance is free. The slot is released when the permit is dropped.
+  pub async fn acquire(&self, domain: &str) -> Result<OwnedSemaphorePermit, LemmyError> {
+    let semaphore = {
+      let mut instances = self.instances.lock().expect("lock fetch limiter");
+      let concurrent = self.config.concurrent_per_instance;
+      let state = instances
+        .entry(domain.to_owned())
+        .or_insert_with(|| InstanceFetchState {
+          semaphore: Arc::new(Semaphore::new(concurrent)),
+          window_start: Instant::now(),
+          fetches: 0,
+        });
+      if state.window_start.elapsed() >= BUDGET_WINDOW {
+        state.window_start = Instant::now();
+        state.fetches = 0;
+      }
+      if state.fetches >= self.config.per_instance_per_hour {
+        return Err(
+          FetchBudgetExhausted {
+            domain: domain.to_owned(),
+          }
+          .into(),
+        );
+      }
+      state.fetches += 1;
+      state.semaphore.clone()
+    };
+    Ok(semaphore.acquire_owned().await)
+  }
+
+  /// Current budget usage for every instance which was fetched from during the last hour.
+  pub fn stats(&self) -> Vec<InstanceFetchStats> {
+    let instances = self.instances.lock().expect("lock fetch limiter");
+    let mut stats: Vec<InstanceFetchStats> = instances
+      .iter()
+      .filter(|(_, state)| state.window_start.elapsed() < BUDGET_WINDOW)
+      .map(|(domain, state)| InstanceFetchStats {
+        domain: domain.to_owned(),
+        fetches_this_hour: state.fetches,
+        hourly_limit: self.config.per_instance_per_hour,
+        in_flight: self.config.concurrent_per_instance - state.semaphore.available_permits(),
+      })
+      .collect();
+    stats.sort_by(|a, b| b.fetches_this_hour.cmp(&a.fetches_this_hour));
+    stats
+  }
+}
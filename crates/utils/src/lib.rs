@@ -4,25 +4,30 @@ extern crate lazy_static;
 extern crate strum_macros;
 
 pub mod apub;
+pub mod cache;
 pub mod claims;
+pub mod debounce;
 pub mod email;
+pub mod federation_mode;
+pub mod fetch_limit;
 pub mod rate_limit;
 pub mod request;
 pub mod settings;
+pub mod slur_filter;
 
 #[cfg(test)]
 mod test;
 pub mod utils;
 pub mod version;
 
-use crate::settings::structs::Settings;
+use crate::{fetch_limit::FetchBudgetExhausted, settings::structs::Settings};
 use http::StatusCode;
 use regex::Regex;
-use thiserror::Error;
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+use serde_json::Value;
+use std::collections::BTreeMap;
 
 pub type ConnectionId = usize;
-pub type PostId = i32;
-pub type CommunityId = i32;
 pub type LocalUserId = i32;
 pub type IpAddr = String;
 
@@ -38,18 +43,215 @@ macro_rules! location_info {
   };
 }
 
-#[derive(Debug, Error)]
-#[error("{{\"error\":\"{message}\"}}")]
+/// Machine readable error codes returned by the api. The string representations are stable, and
+/// identical to the plain error strings which were returned before.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiErrorCode {
+  CommunityAlreadyExists,
+  CommunityFollowerAlreadyExists,
+  CommunityModeratorAlreadyExists,
+  CommunityUserAlreadyBanned,
+  CouldntFindCommunity,
+  CouldntUpdateCommunity,
+  CommentTooLong,
+  CouldntUpdateSite,
+  EmailAlreadyExists,
+  ImageNotLocal,
+  InsufficientScope,
+  InvalidContentLimits,
+  InvalidCommunityName,
+  InvalidEmail,
+  InvalidExpiry,
+  InvalidExpiryInPast,
+  InvalidExpiryTooFar,
+  InvalidPassword,
+  InvalidPostTag,
+  InvalidPostTagName,
+  InvalidRegex,
+  InvalidThemeColor,
+  InvalidUsername,
+  LanguageNotAllowed,
+  NoCommunityEditAllowed,
+  NotAModerator,
+  NotAnAdmin,
+  PasswordsDontMatch,
+  PasswordTooShort,
+  PersonNoteTooLong,
+  PostBodyTooLong,
+  PostTagAlreadyExists,
+  PostTagRequired,
+  RateLimited,
+  ShortDescriptionTooLong,
+  SiteAlreadyExists,
+  SystemErrLogin,
+  TooManyCommunities,
+  TooManyFeaturedPosts,
+  TooManyPostTags,
+  UrlSchemeNotAllowed,
+  UserAlreadyExists,
+  /// An error which doesnt have its own code yet, the string is passed through unchanged
+  Other(String),
+}
+
+impl ApiErrorCode {
+  pub fn as_str(&self) -> &str {
+    use ApiErrorCode::*;
+    match self {
+      CommunityAlreadyExists => "community_already_exists",
+      CommunityFollowerAlreadyExists => "community_follower_already_exists",
+      CommunityModeratorAlreadyExists => "community_moderator_already_exists",
+      CommunityUserAlreadyBanned => "community_user_already_banned",
+      CouldntFindCommunity => "couldnt_find_community",
+      CouldntUpdateCommunity => "couldnt_update_community",
+      CommentTooLong => "comment_too_long",
+      CouldntUpdateSite => "couldnt_update_site",
+      EmailAlreadyExists => "email_already_exists",
+      ImageNotLocal => "image_not_local",
+      InsufficientScope => "insufficient_scope",
+      InvalidContentLimits => "invalid_content_limits",
+      InvalidCommunityName => "invalid_community_name",
+      InvalidEmail => "invalid_email",
+      InvalidExpiry => "invalid_expiry",
+      InvalidExpiryInPast => "invalid_expiry_in_past",
+      InvalidExpiryTooFar => "invalid_expiry_too_far",
+      InvalidPassword => "invalid_password",
+      InvalidPostTag => "invalid_post_tag",
+      InvalidPostTagName => "invalid_post_tag_name",
+      InvalidRegex => "invalid_regex",
+      InvalidThemeColor => "invalid_theme_color",
+      InvalidUsername => "invalid_username",
+      LanguageNotAllowed => "language_not_allowed",
+      NoCommunityEditAllowed => "no_community_edit_allowed",
+      NotAModerator => "not_a_moderator",
+      NotAnAdmin => "not_an_admin",
+      PasswordsDontMatch => "passwords_dont_match",
+      PasswordTooShort => "password_too_short",
+      PersonNoteTooLong => "person_note_too_long",
+      PostBodyTooLong => "post_body_too_long",
+      PostTagAlreadyExists => "post_tag_already_exists",
+      PostTagRequired => "post_tag_required",
+      RateLimited => "rate_limited",
+      ShortDescriptionTooLong => "short_description_too_long",
+      SiteAlreadyExists => "site_already_exists",
+      SystemErrLogin => "system_err_login",
+      TooManyCommunities => "too_many_communities",
+      TooManyFeaturedPosts => "too_many_featured_posts",
+      TooManyPostTags => "too_many_post_tags",
+      UrlSchemeNotAllowed => "url_scheme_not_allowed",
+      UserAlreadyExists => "user_already_exists",
+      Other(s) => s,
+    }
+  }
+}
+
+impl Serialize for ApiErrorCode {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    serializer.serialize_str(self.as_str())
+  }
+}
+
+/// Error which is returned to api clients, both over http and websocket, serialized as json:
+///
+/// `{"error":"community_already_exists","code":"community_already_exists","message":"community_already_exists","field":"name","details":{"community_id":5}}`
+///
+/// `error` and `message` contain the plain error string for backwards compatibility, `field` and
+/// `details` are only included if they are set.
+#[derive(Debug, Clone, PartialEq)]
 pub struct ApiError {
+  pub code: ApiErrorCode,
   pub message: String,
+  pub field: Option<String>,
+  pub details: Option<BTreeMap<String, Value>>,
 }
 
 impl ApiError {
+  /// Error with a plain message, which is used as error code
   pub fn err(msg: &str) -> Self {
     ApiError {
+      code: ApiErrorCode::Other(msg.to_string()),
       message: msg.to_string(),
+      field: None,
+      details: None,
     }
   }
+
+  pub fn new(code: ApiErrorCode) -> Self {
+    ApiError {
+      message: code.as_str().to_string(),
+      code,
+      field: None,
+      details: None,
+    }
+  }
+
+  /// Name of the request field which caused the error
+  pub fn with_field(mut self, field: &str) -> Self {
+    self.field = Some(field.to_string());
+    self
+  }
+
+  pub fn with_detail<V: Into<Value>>(mut self, key: &str, value: V) -> Self {
+    self
+      .details
+      .get_or_insert_with(BTreeMap::new)
+      .insert(key.to_string(), value.into());
+    self
+  }
+
+  /// Combines several validation errors, so that clients can show all of them at once. The first
+  /// error stays the top level error, and all of them are listed in `details.errors`.
+  pub fn combine(mut errors: Vec<ApiError>) -> Option<Self> {
+    if errors.is_empty() {
+      return None;
+    }
+    let list = errors
+      .iter()
+      .map(|e| {
+        let mut item = serde_json::Map::new();
+        item.insert("code".to_string(), e.code.as_str().into());
+        if let Some(field) = &e.field {
+          item.insert("field".to_string(), field.as_str().into());
+        }
+        if let Some(details) = &e.details {
+          item.insert("details".to_string(), details.clone().into_iter().collect());
+        }
+        Value::Object(item)
+      })
+      .collect::<Vec<Value>>();
+    let first = errors.remove(0);
+    Some(first.with_detail("errors", list))
+  }
+}
+
+impl std::fmt::Display for ApiError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    let json = serde_json::to_string(self).map_err(|_| std::fmt::Error)?;
+    f.write_str(&json)
+  }
+}
+
+impl std::error::Error for ApiError {}
+
+impl Serialize for ApiError {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    let mut state = serializer.serialize_struct("ApiError", 5)?;
+    state.serialize_field("error", &self.message)?;
+    state.serialize_field("code", &self.code)?;
+    state.serialize_field("message", &self.message)?;
+    if let Some(field) = &self.field {
+      state.serialize_field("field", field)?;
+    }
+    if let Some(details) = &self.details {
+      state.serialize_field("details", details)?;
+    }
+    state.end()
+  }
 }
 
 #[derive(Debug)]
@@ -66,6 +268,23 @@ where
   }
 }
 
+impl LemmyError {
+  /// Whether this error was caused by an exhausted remote fetch budget, meaning that the
+  /// request can be retried later.
+  pub fn is_fetch_budget_exhausted(&self) -> bool {
+    self.inner.chain().any(|e| e.is::<FetchBudgetExhausted>())
+  }
+
+  /// Converts the error into the format which is returned to api clients. Errors which are not an
+  /// `ApiError` are passed through as plain message.
+  pub fn to_api_error(&self) -> ApiError {
+    match self.inner.downcast_ref::<ApiError>() {
+      Some(e) => e.to_owned(),
+      None => ApiError::err(&self.inner.to_string()),
+    }
+  }
+}
+
 impl std::fmt::Display for LemmyError {
   fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
     self.inner.fmt(f)
@@ -74,6 +293,9 @@ impl std::fmt::Display for LemmyError {
 
 impl actix_web::error::ResponseError for LemmyError {
   fn status_code(&self) -> StatusCode {
+    if self.is_fetch_budget_exhausted() {
+      return StatusCode::TOO_MANY_REQUESTS;
+    }
     match self.inner.downcast_ref::<diesel::result::Error>() {
       Some(diesel::result::Error::NotFound) => StatusCode::NOT_FOUND,
       _ => StatusCode::INTERNAL_SERVER_ERROR,
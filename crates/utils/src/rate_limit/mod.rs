@@ -151,6 +151,51 @@ impl RateLimited {
 
     res
   }
+
+  /// Like `wrap()`, for a request which creates up to `max_count` objects at once. As many of
+  /// them as the allowance covers are taken from it right away, at least one, and the future is
+  /// built with that number, so that it creates no more than that. Those which weren't created in
+  /// the end are given back.
+  pub async fn wrap_many<T, E, F>(
+    self,
+    ip_addr: String,
+    max_count: usize,
+    fut: impl FnOnce(usize) -> F,
+    created_count: impl FnOnce(&T) -> usize,
+  ) -> Result<T, E>
+  where
+    F: Future<Output = Result<T, E>>,
+    E: From<LemmyError>,
+  {
+    let (rate, per) = limits(self.type_, &Settings::get().rate_limit());
+    let allowed = self
+      .rate_limiter
+      .lock()
+      .await
+      .take_up_to(self.type_, &ip_addr, rate, per, max_count)?;
+
+    let res = fut(allowed).await;
+
+    let created = res.as_ref().map(created_count).unwrap_or(0);
+    if created < allowed {
+      self
+        .rate_limiter
+        .lock()
+        .await
+        .give_back(self.type_, &ip_addr, rate, per, allowed - created);
+    }
+    res
+  }
+}
+
+/// The number of actions of the type which are allowed per interval, and the interval in seconds
+fn limits(type_: RateLimitType, config: &RateLimitConfig) -> (i32, i32) {
+  match type_ {
+    RateLimitType::Message => (config.message, config.message_per_second),
+    RateLimitType::Post => (config.post, config.post_per_second),
+    RateLimitType::Register => (config.register, config.register_per_second),
+    RateLimitType::Image => (config.image, config.image_per_second),
+  }
 }
 
 impl<S> Transform<S> for RateLimited
@@ -52,7 +52,6 @@ impl RateLimiter {
     }
   }
 
-  #[allow(clippy::float_cmp)]
   pub(super) fn check_rate_limit_full(
     &mut self,
     type_: RateLimitType,
@@ -60,6 +59,20 @@ impl RateLimiter {
     rate: i32,
     per: i32,
     check_only: bool,
+  ) -> Result<(), LemmyError> {
+    self.check_rate_limit_many(type_, ip, rate, per, 1, check_only)
+  }
+
+  /// Like `check_rate_limit_full()`, for `count` actions at once
+  #[allow(clippy::float_cmp)]
+  pub(super) fn check_rate_limit_many(
+    &mut self,
+    type_: RateLimitType,
+    ip: &str,
+    rate: i32,
+    per: i32,
+    count: usize,
+    check_only: bool,
   ) -> Result<(), LemmyError> {
     self.insert_ip(ip);
     if let Some(bucket) = self.buckets.get_mut(&type_) {
@@ -78,7 +91,7 @@ impl RateLimiter {
           rate_limit.allowance = rate as f64;
         }
 
-        if rate_limit.allowance < 1.0 {
+        if rate_limit.allowance < count as f64 {
           debug!(
             "Rate limited type: {}, IP: {}, time_passed: {}, allowance: {}",
             type_.as_ref(),
@@ -87,20 +100,18 @@ impl RateLimiter {
             rate_limit.allowance
           );
           Err(
-            ApiError {
-              message: format!(
-                "Too many requests. type: {}, IP: {}, {} per {} seconds",
-                type_.as_ref(),
-                ip,
-                rate,
-                per
-              ),
-            }
+            ApiError::err(&format!(
+              "Too many requests. type: {}, IP: {}, {} per {} seconds",
+              type_.as_ref(),
+              ip,
+              rate,
+              per
+            ))
             .into(),
           )
         } else {
           if !check_only {
-            rate_limit.allowance -= 1.0;
+            rate_limit.allowance -= count as f64;
           }
           Ok(())
         }
@@ -111,4 +122,131 @@ impl RateLimiter {
       Ok(())
     }
   }
+
+  /// Takes as many of `max_count` actions from the allowance as it covers, and returns how many
+  /// that are. Fails if not even one action is allowed. Checking and taking happen at once, so
+  /// that concurrent requests can't use the same allowance.
+  pub(super) fn take_up_to(
+    &mut self,
+    type_: RateLimitType,
+    ip: &str,
+    rate: i32,
+    per: i32,
+    max_count: usize,
+  ) -> Result<usize, LemmyError> {
+    let rate_limit = self.refilled_bucket(type_, ip, rate, per);
+    let allowed = (rate_limit.allowance.max(0f64) as usize).min(max_count);
+    if allowed == 0 && max_count > 0 {
+      debug!(
+        "Rate limited type: {}, IP: {}, allowance: {}",
+        type_.as_ref(),
+        ip,
+        rate_limit.allowance
+      );
+      return Err(
+        ApiError::err(&format!(
+          "Too many requests. type: {}, IP: {}, {} per {} seconds",
+          type_.as_ref(),
+          ip,
+          rate,
+          per
+        ))
+        .into(),
+      );
+    }
+    rate_limit.allowance -= allowed as f64;
+    Ok(allowed)
+  }
+
+  /// Returns `count` actions which were taken with `take_up_to()`, but not done in the end
+  pub(super) fn give_back(
+    &mut self,
+    type_: RateLimitType,
+    ip: &str,
+    rate: i32,
+    per: i32,
+    count: usize,
+  ) {
+    let rate_limit = self.refilled_bucket(type_, ip, rate, per);
+    rate_limit.allowance = (rate_limit.allowance + count as f64).min(rate as f64);
+  }
+
+  /// The bucket of the ip, with the allowance which was gained since the last check added
+  #[allow(clippy::float_cmp)]
+  fn refilled_bucket(
+    &mut self,
+    type_: RateLimitType,
+    ip: &str,
+    rate: i32,
+    per: i32,
+  ) -> &mut RateLimitBucket {
+    let rate_limit = self
+      .buckets
+      .entry(type_)
+      .or_insert_with(HashMap::new)
+      .entry(ip.to_string())
+      .or_insert(RateLimitBucket {
+        last_checked: SystemTime::now(),
+        allowance: -2f64,
+      });
+    let current = SystemTime::now();
+    let time_passed = current
+      .duration_since(rate_limit.last_checked)
+      .map(|d| d.as_secs() as f64)
+      .unwrap_or(0f64);
+
+    // The initial value
+    if rate_limit.allowance == -2f64 {
+      rate_limit.allowance = rate as f64;
+    };
+
+    rate_limit.last_checked = current;
+    rate_limit.allowance += time_passed * (rate as f64 / per as f64);
+    if rate_limit.allowance > rate as f64 {
+      rate_limit.allowance = rate as f64;
+    }
+    rate_limit
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{RateLimitType, RateLimiter};
+
+  #[test]
+  fn test_check_rate_limit_many() {
+    let mut limiter = RateLimiter::default();
+    let ip = "1.2.3.4";
+    let check = |limiter: &mut RateLimiter, count, check_only| {
+      limiter.check_rate_limit_many(RateLimitType::Post, ip, 6, 600, count, check_only)
+    };
+
+    // More than the whole allowance can never go through
+    assert!(check(&mut limiter, 7, true).is_err());
+    assert!(check(&mut limiter, 6, true).is_ok());
+
+    // Each counted action uses up the allowance, checks alone don't
+    assert!(check(&mut limiter, 4, false).is_ok());
+    assert!(check(&mut limiter, 2, true).is_ok());
+    assert!(check(&mut limiter, 3, true).is_err());
+    assert!(check(&mut limiter, 2, false).is_ok());
+    assert!(limiter
+      .check_rate_limit_full(RateLimitType::Post, ip, 6, 600, true)
+      .is_err());
+
+    // A new ip only gets as many actions as its allowance covers, and none once it is used up
+    let ip = "5.6.7.8";
+    let take = |limiter: &mut RateLimiter, max_count| {
+      limiter.take_up_to(RateLimitType::Post, ip, 6, 600, max_count)
+    };
+    assert_eq!(4, take(&mut limiter, 4).unwrap());
+    assert_eq!(2, take(&mut limiter, 10).unwrap());
+    assert!(take(&mut limiter, 1).is_err());
+
+    // Actions which weren't done are given back, but never above the whole allowance
+    limiter.give_back(RateLimitType::Post, ip, 6, 600, 3);
+    assert_eq!(3, take(&mut limiter, 10).unwrap());
+    limiter.give_back(RateLimitType::Post, ip, 6, 600, 100);
+    assert_eq!(6, take(&mut limiter, 10).unwrap());
+  }
 }
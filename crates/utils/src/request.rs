@@ -1,13 +1,22 @@
-use crate::{settings::structs::Settings, LemmyError};
+use crate::{
+  settings::structs::{RemoteImageMode, Settings},
+  LemmyError,
+};
 use anyhow::anyhow;
 use log::error;
 use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use regex::{Captures, Regex};
 use reqwest::Client;
 use serde::Deserialize;
-use std::future::Future;
+use std::{future::Future, time::Duration};
 use thiserror::Error;
 use url::Url;
 
+lazy_static! {
+  static ref MARKDOWN_IMAGE_REGEX: Regex =
+    Regex::new(r"!\[(?P<alt>[^\]]*)\]\((?P<url>https?://[^\s)]+)\)").expect("compile regex");
+}
+
 #[derive(Clone, Debug, Error)]
 #[error("Error sending request, {0}")]
 struct SendError(pub String);
@@ -108,71 +117,266 @@ pub(crate) async fn fetch_pictrs(
   }
 }
 
+/// Fetching the metadata of a post url is given up after this time, so that slow sites or a slow
+/// iframely or pictrs don't hold up the post
+const POST_METADATA_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The embed data and the pictrs thumbnail of a post url
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PostMetadata {
+  pub title: Option<String>,
+  pub description: Option<String>,
+  pub html: Option<String>,
+  pub thumbnail_url: Option<Url>,
+}
+
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum PostMetadataError {
+  #[error("Fetching post metadata timed out")]
+  Timeout,
+  #[error("Fetching post metadata failed, {0}")]
+  Failed(String),
+}
+
+/// Fetches the embed data of the url from iframely, and lets pictrs cache a thumbnail. Either of
+/// them may fail, the fetch only fails if neither returns anything or it takes too long.
+pub async fn fetch_post_metadata(
+  client: &Client,
+  url: &Url,
+) -> Result<PostMetadata, PostMetadataError> {
+  actix_rt::time::timeout(
+    POST_METADATA_TIMEOUT,
+    fetch_post_metadata_untimed(client, url),
+  )
+  .await
+  .map_err(|_| PostMetadataError::Timeout)?
+}
+
+async fn fetch_post_metadata_untimed(
+  client: &Client,
+  url: &Url,
+) -> Result<PostMetadata, PostMetadataError> {
+  let iframely = fetch_iframely(client, url).await;
+  let (title, description, iframely_thumbnail_url, html) = match &iframely {
+    Ok(res) => (
+      res.title.to_owned(),
+      res.description.to_owned(),
+      res.thumbnail_url.to_owned(),
+      res.html.to_owned(),
+    ),
+    Err(_) => (None, None, None, None),
+  };
+
+  // Try to generate a small thumbnail from the url itself if iframely has none
+  let pictrs = fetch_pictrs(client, iframely_thumbnail_url.as_ref().unwrap_or(url)).await;
+
+  let pictrs_hash = match (&iframely, pictrs) {
+    (Err(iframely_err), Err(pictrs_err)) => {
+      return Err(PostMetadataError::Failed(format!(
+        "iframely: {}, pictrs: {}",
+        iframely_err, pictrs_err
+      )))
+    }
+    (_, Ok(res)) => res.files.first().map(|f| f.file.to_owned()),
+    (Ok(_), Err(e)) => {
+      error!("pictrs err: {}", e);
+      None
+    }
+  };
+
+  // The full urls are necessary for federation
+  let thumbnail_url = if let Some(pictrs_hash) = pictrs_hash {
+    let url = Url::parse(&format!(
+      "{}/pictrs/image/{}",
+      Settings::get().get_protocol_and_hostname(),
+      pictrs_hash
+    ));
+    match url {
+      Ok(parsed_url) => Some(parsed_url),
+      Err(e) => {
+        // This really shouldn't happen unless the settings or hash are malformed
+        error!("Unexpected error constructing pictrs thumbnail URL: {}", e);
+        None
+      }
+    }
+  } else {
+    None
+  };
+
+  Ok(PostMetadata {
+    title,
+    description,
+    html,
+    thumbnail_url,
+  })
+}
+
+/// Like `fetch_post_metadata()`, but a failed fetch is only logged
 pub async fn fetch_iframely_and_pictrs_data(
   client: &Client,
   url: Option<&Url>,
 ) -> (Option<String>, Option<String>, Option<String>, Option<Url>) {
-  match &url {
-    Some(url) => {
-      // Fetch iframely data
-      let (iframely_title, iframely_description, iframely_thumbnail_url, iframely_html) =
-        match fetch_iframely(client, url).await {
-          Ok(res) => (res.title, res.description, res.thumbnail_url, res.html),
-          Err(e) => {
-            error!("iframely err: {}", e);
-            (None, None, None, None)
-          }
-        };
-
-      // Fetch pictrs thumbnail
-      let pictrs_hash = match iframely_thumbnail_url {
-        Some(iframely_thumbnail_url) => match fetch_pictrs(client, &iframely_thumbnail_url).await {
-          Ok(res) => Some(res.files[0].file.to_owned()),
-          Err(e) => {
-            error!("pictrs err: {}", e);
-            None
-          }
-        },
-        // Try to generate a small thumbnail if iframely is not supported
-        None => match fetch_pictrs(client, &url).await {
-          Ok(res) => Some(res.files[0].file.to_owned()),
-          Err(e) => {
-            error!("pictrs err: {}", e);
-            None
-          }
-        },
-      };
+  let metadata = match url {
+    Some(url) => match fetch_post_metadata(client, url).await {
+      Ok(metadata) => metadata,
+      Err(e) => {
+        error!("{}", e);
+        PostMetadata::default()
+      }
+    },
+    None => PostMetadata::default(),
+  };
+  (
+    metadata.title,
+    metadata.description,
+    metadata.html,
+    metadata.thumbnail_url,
+  )
+}
+
+/// Remote instances which don't answer quickly are skipped, their nodeinfo is fetched again later.
+const NODEINFO_TIMEOUT: Duration = Duration::from_secs(10);
+
+const NODEINFO_SCHEMA_PREFIX: &str = "http://nodeinfo.diaspora.software/ns/schema/2.";
+
+#[derive(Deserialize, Debug)]
+struct NodeInfoWellKnown {
+  links: NodeInfoWellKnownLinks,
+}
+
+/// Some implementations return a single link, others a list with one link per schema version
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum NodeInfoWellKnownLinks {
+  One(NodeInfoWellKnownLink),
+  Many(Vec<NodeInfoWellKnownLink>),
+}
 
-      // The full urls are necessary for federation
-      let pictrs_thumbnail = if let Some(pictrs_hash) = pictrs_hash {
-        let url = Url::parse(&format!(
+#[derive(Deserialize, Debug)]
+struct NodeInfoWellKnownLink {
+  rel: String,
+  href: Url,
+}
+
+#[derive(Deserialize, Debug)]
+struct NodeInfo {
+  software: NodeInfoSoftware,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct NodeInfoSoftware {
+  pub name: String,
+  pub version: Option<String>,
+}
+
+/// Reads the software name and version of a remote instance from its nodeinfo 2.x document.
+pub async fn fetch_nodeinfo_software(
+  client: &Client,
+  domain: &str,
+) -> Result<NodeInfoSoftware, LemmyError> {
+  let well_known_url = format!(
+    "{}://{}/.well-known/nodeinfo",
+    Settings::get().get_protocol_string(),
+    domain
+  );
+  let well_known: NodeInfoWellKnown = client
+    .get(&well_known_url)
+    .timeout(NODEINFO_TIMEOUT)
+    .send()
+    .await
+    .map_err(|e| SendError(e.to_string()))?
+    .json()
+    .await
+    .map_err(|e| RecvError(e.to_string()))?;
+
+  let links = match well_known.links {
+    NodeInfoWellKnownLinks::One(link) => vec![link],
+    NodeInfoWellKnownLinks::Many(links) => links,
+  };
+  let href = links
+    .into_iter()
+    .find(|l| l.rel.starts_with(NODEINFO_SCHEMA_PREFIX))
+    .map(|l| l.href)
+    .ok_or_else(|| anyhow!("No nodeinfo 2.x link for {}", domain))?;
+  // Dont let a remote instance make us send requests to arbitrary hosts
+  if href.domain() != Some(domain) {
+    return Err(anyhow!("Nodeinfo link of {} points to another host", domain).into());
+  }
+
+  let node_info: NodeInfo = client
+    .get(href)
+    .timeout(NODEINFO_TIMEOUT)
+    .send()
+    .await
+    .map_err(|e| SendError(e.to_string()))?
+    .json()
+    .await
+    .map_err(|e| RecvError(e.to_string()))?;
+  Ok(node_info.software)
+}
+
+/// Makes sure that showing a remote image doesnt leak the ip addresses of our users to the remote
+/// host. Depending on the `remote_images` setting, the image is either kept as is, uploaded to
+/// pictrs, or rewritten to go through the local image proxy.
+///
+/// Returns `None` if the image couldnt be rehosted, so that no broken thumbnail is shown.
+pub async fn localize_remote_image(client: &Client, image_url: &Url) -> Option<Url> {
+  if image_url.host_str() == Some(&Settings::get().hostname()) {
+    return Some(image_url.to_owned());
+  }
+  match Settings::get().remote_images() {
+    RemoteImageMode::Keep => Some(image_url.to_owned()),
+    RemoteImageMode::Rehost => match fetch_pictrs(client, image_url).await {
+      Ok(res) => {
+        let url = format!(
           "{}/pictrs/image/{}",
           Settings::get().get_protocol_and_hostname(),
-          pictrs_hash
-        ));
-        match url {
-          Ok(parsed_url) => Some(parsed_url),
-          Err(e) => {
-            // This really shouldn't happen unless the settings or hash are malformed
-            error!("Unexpected error constructing pictrs thumbnail URL: {}", e);
-            None
-          }
-        }
-      } else {
+          res.files[0].file
+        );
+        Url::parse(&url).ok()
+      }
+      Err(e) => {
+        error!("pictrs err: {}", e);
         None
-      };
-
-      (
-        iframely_title,
-        iframely_description,
-        iframely_html,
-        pictrs_thumbnail,
-      )
-    }
-    None => (None, None, None, None),
+      }
+    },
+    RemoteImageMode::Proxy => proxied_image_url(image_url).ok(),
   }
 }
 
+/// Returns the url of the local image proxy for the given remote image.
+pub fn proxied_image_url(image_url: &Url) -> Result<Url, LemmyError> {
+  let proxy_url = format!(
+    "{}/api/v3/image_proxy",
+    Settings::get().get_protocol_and_hostname()
+  );
+  Ok(Url::parse_with_params(
+    &proxy_url,
+    &[("url", image_url.as_str())],
+  )?)
+}
+
+/// If the `remote_images` setting is `proxy`, rewrites all remote inline images in the markdown
+/// text to go through the local image proxy.
+pub fn proxy_markdown_images(text: &str) -> String {
+  if Settings::get().remote_images() != RemoteImageMode::Proxy {
+    return text.to_owned();
+  }
+  let hostname = Settings::get().hostname();
+  MARKDOWN_IMAGE_REGEX
+    .replace_all(text, |caps: &Captures| {
+      let original = caps[0].to_owned();
+      match Url::parse(&caps["url"]) {
+        Ok(url) if url.host_str() != Some(&hostname) => match proxied_image_url(&url) {
+          Ok(proxied) => format!("![{}]({})", &caps["alt"], proxied),
+          Err(_) => original,
+        },
+        _ => original,
+      }
+    })
+    .to_string()
+}
+
 async fn is_image_content_type(client: &Client, test: &Url) -> Result<(), LemmyError> {
   let response = retry(|| client.get(test.to_owned()).send()).await?;
   if response
@@ -1,4 +1,13 @@
-use crate::settings::{CaptchaConfig, DatabaseConfig, FederationConfig, RateLimitConfig, Settings};
+use crate::settings::{
+  CaptchaConfig,
+  DatabaseConfig,
+  FederationConfig,
+  FetchLimitConfig,
+  RateLimitConfig,
+  RemoteImageMode,
+  Settings,
+  VoteDebounceConfig,
+};
 use std::net::{IpAddr, Ipv4Addr};
 
 impl Default for Settings {
@@ -7,6 +16,8 @@ impl Default for Settings {
       database: Some(DatabaseConfig::default()),
       rate_limit: Some(RateLimitConfig::default()),
       federation: Some(FederationConfig::default()),
+      fetch_limit: Some(FetchLimitConfig::default()),
+      vote_debounce: Some(VoteDebounceConfig::default()),
       captcha: Some(CaptchaConfig::default()),
       email: None,
       setup: None,
@@ -17,6 +28,11 @@ impl Default for Settings {
       jwt_secret: Some("changeme".into()),
       pictrs_url: Some("http://pictrs:8080".into()),
       iframely_url: Some("http://iframely".into()),
+      synchronous_post_metadata: Some(false),
+      remote_images: Some(RemoteImageMode::default()),
+      received_activity_retention_days: Some(90),
+      deleted_content_retention_days: Some(30),
+      removed_content_retention_days: Some(180),
     }
   }
 }
@@ -30,6 +46,7 @@ impl Default for DatabaseConfig {
       port: 5432,
       database: "lemmy".into(),
       pool_size: 5,
+      read_replica_url: None,
     }
   }
 }
@@ -53,6 +70,31 @@ impl Default for FederationConfig {
   }
 }
 
+impl Default for FetchLimitConfig {
+  fn default() -> Self {
+    Self {
+      per_activity: 25,
+      concurrent_per_instance: 5,
+      per_instance_per_hour: 1000,
+    }
+  }
+}
+
+impl Default for VoteDebounceConfig {
+  fn default() -> Self {
+    Self {
+      seconds: 10,
+      max_pending: 10000,
+    }
+  }
+}
+
+impl Default for RemoteImageMode {
+  fn default() -> Self {
+    RemoteImageMode::Keep
+  }
+}
+
 impl Default for RateLimitConfig {
   fn default() -> Self {
     Self {
@@ -5,9 +5,12 @@ use crate::{
     DatabaseConfig,
     EmailConfig,
     FederationConfig,
+    FetchLimitConfig,
     RateLimitConfig,
+    RemoteImageMode,
     Settings,
     SetupConfig,
+    VoteDebounceConfig,
   },
   LemmyError,
 };
@@ -154,12 +157,33 @@ impl Settings {
   pub fn iframely_url(&self) -> String {
     self.iframely_url.to_owned().unwrap_or_default()
   }
+  pub fn synchronous_post_metadata(&self) -> bool {
+    self.synchronous_post_metadata.unwrap_or_default()
+  }
+  pub fn remote_images(&self) -> RemoteImageMode {
+    self.remote_images.unwrap_or_default()
+  }
+  pub fn received_activity_retention_days(&self) -> i64 {
+    self.received_activity_retention_days.unwrap_or_default()
+  }
+  pub fn deleted_content_retention_days(&self) -> i64 {
+    self.deleted_content_retention_days.unwrap_or_default()
+  }
+  pub fn removed_content_retention_days(&self) -> i64 {
+    self.removed_content_retention_days.unwrap_or_default()
+  }
   pub fn rate_limit(&self) -> RateLimitConfig {
     self.rate_limit.to_owned().unwrap_or_default()
   }
   pub fn federation(&self) -> FederationConfig {
     self.federation.to_owned().unwrap_or_default()
   }
+  pub fn fetch_limit(&self) -> FetchLimitConfig {
+    self.fetch_limit.to_owned().unwrap_or_default()
+  }
+  pub fn vote_debounce(&self) -> VoteDebounceConfig {
+    self.vote_debounce.to_owned().unwrap_or_default()
+  }
   pub fn captcha(&self) -> CaptchaConfig {
     self.captcha.to_owned().unwrap_or_default()
   }
@@ -7,6 +7,8 @@ pub struct Settings {
   pub(crate) database: Option<DatabaseConfig>,
   pub(crate) rate_limit: Option<RateLimitConfig>,
   pub(crate) federation: Option<FederationConfig>,
+  pub(crate) fetch_limit: Option<FetchLimitConfig>,
+  pub(crate) vote_debounce: Option<VoteDebounceConfig>,
   pub(crate) hostname: Option<String>,
   pub(crate) bind: Option<IpAddr>,
   pub(crate) port: Option<u16>,
@@ -14,6 +16,11 @@ pub struct Settings {
   pub(crate) jwt_secret: Option<String>,
   pub(crate) pictrs_url: Option<String>,
   pub(crate) iframely_url: Option<String>,
+  pub(crate) synchronous_post_metadata: Option<bool>,
+  pub(crate) remote_images: Option<RemoteImageMode>,
+  pub(crate) received_activity_retention_days: Option<i64>,
+  pub(crate) deleted_content_retention_days: Option<i64>,
+  pub(crate) removed_content_retention_days: Option<i64>,
   pub(crate) captcha: Option<CaptchaConfig>,
   pub(crate) email: Option<EmailConfig>,
   pub(crate) setup: Option<SetupConfig>,
@@ -33,6 +40,8 @@ pub struct DatabaseConfig {
   pub port: i32,
   pub database: String,
   pub pool_size: u32,
+  /// Connection url of a read replica, which is used for expensive read-only queries
+  pub read_replica_url: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -51,6 +60,21 @@ pub struct FederationConfig {
   pub blocked_instances: Option<Vec<String>>,
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct FetchLimitConfig {
+  pub per_activity: i32,
+  pub concurrent_per_instance: usize,
+  pub per_instance_per_hour: i32,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct VoteDebounceConfig {
+  /// How long outgoing votes are held back, 0 sends them right away
+  pub seconds: u64,
+  /// Votes beyond this many held back ones are sent right away
+  pub max_pending: usize,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct RateLimitConfig {
   pub message: i32,
@@ -63,6 +87,18 @@ pub struct RateLimitConfig {
   pub image_per_second: i32,
 }
 
+/// How images linked from federated posts and comments are handled.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RemoteImageMode {
+  /// Link remote images directly
+  Keep,
+  /// Upload remote thumbnails to pictrs, and link the local copy
+  Rehost,
+  /// Serve remote images through the local image proxy
+  Proxy,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct SetupConfig {
   pub admin_username: String,
@@ -0,0 +1,37 @@
+use regex::Regex;
+use std::sync::{Arc, RwLock};
+
+/// The compiled slur filter regex of the site, shared between all requests. It is `None` if the
+/// filter is disabled.
+#[derive(Clone)]
+pub struct SlurFilter {
+  regex: Arc<RwLock<Option<Regex>>>,
+}
+
+impl SlurFilter {
+  pub fn new(regex: Option<Regex>) -> Self {
+    SlurFilter {
+      regex: Arc::new(RwLock::new(regex)),
+    }
+  }
+
+  /// The current regex, to be passed to `check_slurs` and `remove_slurs`
+  pub fn regex(&self) -> Option<Regex> {
+    self.regex.read().expect("lock slur filter").to_owned()
+  }
+
+  /// The regex for posts and comments in a community. Communities can be exempt from the filter,
+  /// but their own name and title are always checked with `regex()`.
+  pub fn regex_for_community(&self, exempt_from_slur_filter: bool) -> Option<Regex> {
+    if exempt_from_slur_filter {
+      None
+    } else {
+      self.regex()
+    }
+  }
+
+  /// Replaces the regex after the site was edited
+  pub fn set(&self, regex: Option<Regex>) {
+    *self.regex.write().expect("lock slur filter") = regex;
+  }
+}
@@ -1,13 +1,40 @@
-use crate::utils::{
-  is_valid_community_name,
-  is_valid_post_title,
-  is_valid_preferred_username,
-  is_valid_username,
-  remove_slurs,
-  scrape_text_for_mentions,
-  slur_check,
-  slurs_vec_to_str,
+use crate::{
+  cache::TimedCache,
+  claims::Claims,
+  email::{digest_html, escape_html},
+  federation_mode::FederationMode,
+  fetch_limit::FetchLimiter,
+  settings::structs::{FetchLimitConfig, Settings},
+  utils::{
+    build_slur_regex,
+    check_max_length,
+    ip_from_remote_addr,
+    is_local_image_url,
+    is_url_scheme_allowed,
+    is_valid_community_name,
+    is_valid_post_tag_name,
+    is_valid_post_title,
+    is_valid_preferred_username,
+    is_valid_theme_color,
+    is_valid_username,
+    normalize_url,
+    parse_expires,
+    remove_slurs,
+    scrape_text_for_mentions,
+    scrape_text_for_new_mentions,
+    slur_check,
+    slurs_vec_to_str,
+    truncate_ip,
+    truncate_to_max_length,
+    DEFAULT_SLUR_REGEX,
+    MAX_MENTIONS,
+  },
+  ApiError,
+  ApiErrorCode,
+  LemmyError,
 };
+use std::time::Duration;
+use url::Url;
 
 #[test]
 fn test_mentions_regex() {
@@ -19,6 +46,34 @@ fn test_mentions_regex() {
   assert_eq!(mentions[1].domain, "lemmy-alpha:8540".to_string());
 }
 
+#[test]
+fn test_mentions_limit() {
+  let text = (0..20)
+    .map(|i| format!("@user{}@example.com", i))
+    .collect::<Vec<String>>()
+    .join(" ");
+  let mentions = scrape_text_for_mentions(&text);
+  assert_eq!(mentions.len(), MAX_MENTIONS);
+  assert_eq!(mentions[0].name, "user0".to_string());
+}
+
+#[test]
+fn test_mentions_deduplicated() {
+  let text = "@tedu@honk.teduangst.com ".repeat(5);
+  let mentions = scrape_text_for_mentions(&text);
+  assert_eq!(mentions.len(), 1);
+}
+
+#[test]
+fn test_new_mentions() {
+  let old_text = "Hello @tedu@honk.teduangst.com";
+  let new_text = "Hello @tedu@honk.teduangst.com and @lemmy@lemmy-alpha:8540";
+  let mentions = scrape_text_for_new_mentions(old_text, new_text);
+  assert_eq!(mentions.len(), 1);
+  assert_eq!(mentions[0].name, "lemmy".to_string());
+  assert!(scrape_text_for_new_mentions(new_text, old_text).is_empty());
+}
+
 #[test]
 fn test_valid_register_username() {
   assert!(is_valid_username("Hello_98"));
@@ -50,13 +105,59 @@ fn test_valid_post_title() {
   assert!(!is_valid_post_title("\n \n \n \n    		")); // tabs/spaces/newlines
 }
 
+#[test]
+fn test_valid_theme_color() {
+  assert!(is_valid_theme_color("#1e90ff"));
+  assert!(is_valid_theme_color("#FFF"));
+  assert!(!is_valid_theme_color("1e90ff"));
+  assert!(!is_valid_theme_color("#1e90f"));
+  assert!(!is_valid_theme_color("#gggggg"));
+  assert!(!is_valid_theme_color(""));
+}
+
+#[test]
+fn test_normalize_url() {
+  let normalize = |url: &str| normalize_url(&Url::parse(url).unwrap());
+  assert_eq!(
+    "example.com/page",
+    normalize("https://www.Example.com/page/")
+  );
+  assert_eq!(
+    "example.com/page",
+    normalize("http://example.com/page#comments")
+  );
+  assert_eq!(
+    "example.com/watch?a=1&v=xyz",
+    normalize("https://example.com/watch?v=xyz&utm_source=feed&a=1&fbclid=abc")
+  );
+  assert_eq!("example.com:8080", normalize("https://example.com:8080/"));
+  assert_ne!(
+    normalize("https://example.com/a"),
+    normalize("https://example.com/b")
+  );
+  assert_eq!(
+    "magnet:?xt=urn:btih:abc",
+    normalize("magnet:?xt=urn:btih:abc")
+  );
+}
+
+#[test]
+fn test_valid_post_tag_name() {
+  assert!(is_valid_post_tag_name("meta"));
+  assert!(is_valid_post_tag_name("help-wanted_2"));
+  assert!(!is_valid_post_tag_name("two words"));
+  assert!(!is_valid_post_tag_name("#meta"));
+  assert!(!is_valid_post_tag_name(""));
+}
+
 #[test]
 fn test_slur_filter() {
   let test =
       "faggot test kike tranny cocksucker retardeds. Capitalized Niggerz. This is a bunch of other safe text.";
   let slur_free = "No slurs here";
+  let slur_regex = build_slur_regex(Some(DEFAULT_SLUR_REGEX)).unwrap();
   assert_eq!(
-      remove_slurs(&test),
+      remove_slurs(&test, &slur_regex),
       "*removed* test *removed* *removed* *removed* *removed*. Capitalized *removed*. This is a bunch of other safe text."
         .to_string()
     );
@@ -71,16 +172,311 @@ fn test_slur_filter() {
   ];
   let has_slurs_err_str = "No slurs - Niggerz, cocksucker, faggot, kike, retardeds, tranny";
 
-  assert_eq!(slur_check(test), Err(has_slurs_vec));
-  assert_eq!(slur_check(slur_free), Ok(()));
-  if let Err(slur_vec) = slur_check(test) {
+  assert_eq!(slur_check(test, &slur_regex), Err(has_slurs_vec));
+  assert_eq!(slur_check(slur_free, &slur_regex), Ok(()));
+  if let Err(slur_vec) = slur_check(test, &slur_regex) {
     assert_eq!(&slurs_vec_to_str(slur_vec), has_slurs_err_str);
   }
 }
 
+#[test]
+fn test_custom_slur_filter() {
+  let slur_regex = build_slur_regex(Some("bad(ger)?s?")).unwrap();
+  assert_eq!(
+    remove_slurs("Badgers are bad, kike", &slur_regex),
+    "*removed* are *removed*, kike"
+  );
+  assert_eq!(slur_check("Nothing to see", &slur_regex), Ok(()));
+
+  // An empty or missing regex disables the filter
+  let disabled = build_slur_regex(Some("")).unwrap();
+  assert!(disabled.is_none());
+  assert!(build_slur_regex(None).unwrap().is_none());
+  assert_eq!(remove_slurs("kike", &disabled), "kike");
+  assert_eq!(slur_check("kike", &disabled), Ok(()));
+
+  let invalid = build_slur_regex(Some("bad(")).unwrap_err();
+  assert_eq!(invalid.code, ApiErrorCode::InvalidRegex);
+}
+
 // These helped with testing
 // #[test]
 // fn test_send_email() {
 //  let result =  send_email("not a subject", "test_email@gmail.com", "ur user", "<h1>HI there</h1>");
 //   assert!(result.is_ok());
 // }
+
+#[actix_rt::test]
+async fn test_fetch_budget() {
+  let limiter = FetchLimiter::new(FetchLimitConfig {
+    per_activity: 10,
+    concurrent_per_instance: 2,
+    per_instance_per_hour: 2,
+  });
+
+  let first = limiter.acquire("example.com").await.unwrap();
+  let _second = limiter.acquire("example.com").await.unwrap();
+  let exhausted = limiter.acquire("example.com").await.unwrap_err();
+  assert!(exhausted.is_fetch_budget_exhausted());
+  assert!(limiter.acquire("other.example").await.is_ok());
+
+  let stats = limiter.stats();
+  let example = stats.iter().find(|s| s.domain == "example.com").unwrap();
+  assert_eq!(2, example.fetches_this_hour);
+  assert_eq!(2, example.in_flight);
+  drop(first);
+  let stats = limiter.stats();
+  let example = stats.iter().find(|s| s.domain == "example.com").unwrap();
+  assert_eq!(1, example.in_flight);
+}
+
+#[test]
+fn test_local_image_url() {
+  let is_local =
+    |url: &str, hostname: &str| is_local_image_url(&Url::parse(url).unwrap(), hostname);
+
+  assert!(is_local("https://lemmy.ml/pictrs/image/a.png", "lemmy.ml"));
+  assert!(is_local("https://LEMMY.ml/pictrs/image/a.png", "lemmy.ml"));
+  // Default ports are not part of the url
+  assert!(is_local(
+    "https://lemmy.ml:443/pictrs/image/a.png",
+    "lemmy.ml"
+  ));
+  assert!(is_local(
+    "http://lemmy.ml:80/pictrs/image/a.png",
+    "lemmy.ml"
+  ));
+
+  // Subdomains and lookalike domains
+  assert!(!is_local("https://images.lemmy.ml/a.png", "lemmy.ml"));
+  assert!(!is_local("https://lemmy.ml.example.com/a.png", "lemmy.ml"));
+  assert!(!is_local("https://evillemmy.ml/a.png", "lemmy.ml"));
+  assert!(!is_local("https://lemmy.ml@example.com/a.png", "lemmy.ml"));
+
+  // Ports, as used in the federation test setup
+  assert!(is_local(
+    "http://lemmy-alpha:8541/pictrs/image/a.png",
+    "lemmy-alpha:8541"
+  ));
+  assert!(!is_local(
+    "http://lemmy-alpha:8542/a.png",
+    "lemmy-alpha:8541"
+  ));
+  assert!(!is_local("http://lemmy-alpha/a.png", "lemmy-alpha:8541"));
+  assert!(!is_local("https://lemmy.ml:8443/a.png", "lemmy.ml"));
+}
+
+#[test]
+fn test_truncate_ip() {
+  assert_eq!(Some("192.168.1.0".to_string()), truncate_ip("192.168.1.77"));
+  assert_eq!(
+    Some("2001:db8:85a3::".to_string()),
+    truncate_ip("2001:db8:85a3:8d3:1319:8a2e:370:7348")
+  );
+  assert_eq!(None, truncate_ip("blank_ip"));
+}
+
+#[test]
+fn test_ip_from_remote_addr() {
+  assert_eq!("192.168.1.77", ip_from_remote_addr("192.168.1.77:8536"));
+  assert_eq!("192.168.1.77", ip_from_remote_addr("192.168.1.77"));
+  assert_eq!("2001:db8::1", ip_from_remote_addr("[2001:db8::1]:8536"));
+  assert_eq!("2001:db8::1", ip_from_remote_addr("2001:db8::1"));
+}
+
+#[test]
+fn test_legacy_jwt_is_rejected() {
+  let session = Claims::jwt(3, 7, "example.com".into()).unwrap();
+  assert_eq!(Some(7), Claims::decode(&session).unwrap().claims.session_id);
+
+  let legacy = Claims {
+    id: 3,
+    iss: "example.com".into(),
+    api_token_id: None,
+    session_id: None,
+  };
+  let legacy = jsonwebtoken::encode(
+    &jsonwebtoken::Header::default(),
+    &legacy,
+    &jsonwebtoken::EncodingKey::from_secret(Settings::get().jwt_secret().as_ref()),
+  )
+  .unwrap();
+  assert!(Claims::decode(&legacy).is_err());
+}
+
+#[test]
+fn test_content_limits() {
+  assert!(check_max_length("äöü", 3, ApiErrorCode::CommentTooLong).is_ok());
+  assert_eq!(
+    ApiErrorCode::CommentTooLong,
+    check_max_length("äöüß", 3, ApiErrorCode::CommentTooLong)
+      .unwrap_err()
+      .code
+  );
+  // Multibyte characters are not cut in half
+  assert_eq!("äö", truncate_to_max_length("äöüß", 2));
+  assert_eq!("short", truncate_to_max_length("short", 100));
+
+  let allowed = vec!["http".to_string(), "https".to_string()];
+  let is_allowed = |url: &str| is_url_scheme_allowed(&Url::parse(url).unwrap(), &allowed);
+  assert!(is_allowed("https://example.com"));
+  assert!(is_allowed("HTTP://example.com"));
+  assert!(!is_allowed("javascript:alert(1)"));
+  assert!(!is_allowed(
+    "magnet:?xt=urn:btih:c12fe1c06bba254a9dc9f519b335aa7c1367a88a"
+  ));
+}
+
+#[test]
+fn test_api_error_json() {
+  let legacy = ApiError::err("not_logged_in");
+  assert_eq!(
+    r#"{"error":"not_logged_in","code":"not_logged_in","message":"not_logged_in"}"#,
+    legacy.to_string()
+  );
+
+  let structured = ApiError::new(ApiErrorCode::CommunityAlreadyExists)
+    .with_field("name")
+    .with_detail("community_id", 5);
+  assert_eq!(
+    r#"{"error":"community_already_exists","code":"community_already_exists","message":"community_already_exists","field":"name","details":{"community_id":5}}"#,
+    serde_json::to_string(&structured).unwrap()
+  );
+
+  // The same json is used when the error is returned through LemmyError
+  let lemmy_error: LemmyError = structured.clone().into();
+  assert_eq!(structured.to_string(), lemmy_error.to_string());
+}
+
+#[test]
+fn test_combine_api_errors() {
+  assert_eq!(None, ApiError::combine(vec![]));
+
+  let combined = ApiError::combine(vec![
+    ApiError::new(ApiErrorCode::UserAlreadyExists).with_field("username"),
+    ApiError::new(ApiErrorCode::PasswordTooShort)
+      .with_field("password")
+      .with_detail("min_length", 10),
+  ])
+  .unwrap();
+  assert_eq!(
+    r#"{"error":"user_already_exists","code":"user_already_exists","message":"user_already_exists","field":"username","details":{"errors":[{"code":"user_already_exists","field":"username"},{"code":"password_too_short","field":"password","details":{"min_length":10}}]}}"#,
+    combined.to_string()
+  );
+}
+
+#[actix_rt::test]
+async fn test_timed_cache() {
+  let cache = TimedCache::new(Duration::from_secs(60));
+
+  assert_eq!(1, cache.get_or_refresh(|| async { Ok(1) }).await.unwrap());
+  // Served from the cache
+  assert_eq!(1, cache.get_or_refresh(|| async { Ok(2) }).await.unwrap());
+
+  // An invalidation takes effect immediately
+  cache.invalidate().await;
+  assert_eq!(3, cache.get_or_refresh(|| async { Ok(3) }).await.unwrap());
+  assert_eq!(3, cache.get_or_refresh(|| async { Ok(4) }).await.unwrap());
+
+  // A value which was built while the cache got invalidated is returned, but not stored
+  cache.invalidate().await;
+  let stale = cache
+    .get_or_refresh(|| async {
+      cache.invalidate().await;
+      Ok(5)
+    })
+    .await
+    .unwrap();
+  assert_eq!(5, stale);
+  assert_eq!(6, cache.get_or_refresh(|| async { Ok(6) }).await.unwrap());
+
+  // Errors are passed through and not cached
+  cache.invalidate().await;
+  assert!(cache
+    .get_or_refresh(|| async { Err(ApiError::err("couldnt_find_site").into()) })
+    .await
+    .is_err());
+  assert_eq!(7, cache.get_or_refresh(|| async { Ok(7) }).await.unwrap());
+
+  // Expired values are rebuilt
+  let expired = TimedCache::new(Duration::from_secs(0));
+  assert_eq!(1, expired.get_or_refresh(|| async { Ok(1) }).await.unwrap());
+  assert_eq!(2, expired.get_or_refresh(|| async { Ok(2) }).await.unwrap());
+}
+
+#[test]
+fn test_parse_expires() {
+  let in_a_day = chrono::Utc::now() + chrono::Duration::days(1);
+  let expires = parse_expires(Some(in_a_day.timestamp()), None).unwrap();
+  assert_eq!(Some(in_a_day.timestamp()), expires.map(|e| e.timestamp()));
+  let expires = parse_expires(None, Some(&in_a_day.to_rfc3339())).unwrap();
+  assert_eq!(Some(in_a_day.timestamp()), expires.map(|e| e.timestamp()));
+  assert_eq!(None, parse_expires(None, None).unwrap());
+
+  let code = |res: Result<_, ApiError>| res.unwrap_err().code;
+  assert_eq!(
+    ApiErrorCode::InvalidExpiryInPast,
+    code(parse_expires(Some(1_000_000), None))
+  );
+  assert_eq!(
+    ApiErrorCode::InvalidExpiryTooFar,
+    code(parse_expires(None, Some("3021-01-01T00:00:00Z")))
+  );
+  assert_eq!(
+    ApiErrorCode::InvalidExpiry,
+    code(parse_expires(None, Some("tomorrow")))
+  );
+  assert_eq!(
+    ApiErrorCode::InvalidExpiry,
+    code(parse_expires(Some(i64::MAX), None))
+  );
+  assert_eq!(
+    ApiErrorCode::InvalidExpiry,
+    code(parse_expires(
+      Some(in_a_day.timestamp()),
+      Some(&in_a_day.to_rfc3339())
+    ))
+  );
+}
+
+#[test]
+fn test_federation_mode_is_stored_by_discriminant() {
+  use FederationMode::*;
+  for mode in &[Open, Allowlist, Disabled] {
+    assert_eq!(*mode, FederationMode::from_i16(*mode as i16));
+  }
+  assert_eq!(1, Allowlist as i16);
+  assert_eq!(Open, FederationMode::from_i16(17));
+}
+
+#[test]
+fn test_escape_html() {
+  assert_eq!(
+    "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;",
+    escape_html("<a href=\"x\">Tom & Jerry's</a>")
+  );
+  assert_eq!("plain text", escape_html("plain text"));
+}
+
+#[test]
+fn test_digest_html() {
+  let replies = vec![(
+    "alice".to_string(),
+    "<script>alert(1)</script>".to_string(),
+    "https://example.com/post/1/comment/2".to_string(),
+  )];
+  let html = digest_html(
+    &[("Replies", &replies[..]), ("Mentions", &[])],
+    "https://example.com",
+    "token",
+  );
+
+  assert!(html.starts_with("<h1>1 new notifications</h1>"));
+  assert!(html.contains("<h2>Replies</h2>"));
+  // Empty sections are left out
+  assert!(!html.contains("Mentions"));
+  assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+  assert!(!html.contains("<script>"));
+  assert!(html.contains("<a href=\"https://example.com/post/1/comment/2\">link</a>"));
+  assert!(html.contains("<a href=\"https://example.com/email/unsubscribe/token\">"));
+}
@@ -1,13 +1,14 @@
-use crate::{settings::structs::Settings, ApiError};
+use crate::{settings::structs::Settings, ApiError, ApiErrorCode};
 use actix_web::dev::ConnectionInfo;
-use chrono::{DateTime, FixedOffset, NaiveDateTime};
+use chrono::{DateTime, Duration, FixedOffset, NaiveDateTime, Utc};
 use itertools::Itertools;
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
 use regex::{Regex, RegexBuilder};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use url::Url;
 
 lazy_static! {
   static ref EMAIL_REGEX: Regex = Regex::new(r"^[a-zA-Z0-9.!#$%&’*+/=?^_`{|}~-]+@[a-zA-Z0-9-]+(?:\.[a-zA-Z0-9-]+)*$").expect("compile regex");
-  static ref SLUR_REGEX: Regex = RegexBuilder::new(r"(fag(g|got|tard)?\b|cock\s?sucker(s|ing)?|\bn(i|1)g(\b|g?(a|er)?(s|z)?)\b|mudslime?s?|kikes?|\bspi(c|k)s?\b|\bchinks?|gooks?|bitch(es|ing|y)?|whor(es?|ing)|\btr(a|@)nn?(y|ies?)|\b(b|re|r)tard(ed)?s?)").case_insensitive(true).build().expect("compile regex");
   static ref USERNAME_MATCHES_REGEX: Regex = Regex::new(r"/u/[a-zA-Z][0-9a-zA-Z_]*").expect("compile regex");
   // TODO keep this old one, it didn't work with port well tho
   // static ref MENTIONS_REGEX: Regex = Regex::new(r"@(?P<name>[\w.]+)@(?P<domain>[a-zA-Z0-9._-]+\.[a-zA-Z0-9_-]+)").expect("compile regex");
@@ -15,22 +16,168 @@ lazy_static! {
   static ref VALID_USERNAME_REGEX: Regex = Regex::new(r"^[a-zA-Z0-9_]{3,20}$").expect("compile regex");
   static ref VALID_COMMUNITY_NAME_REGEX: Regex = Regex::new(r"^[a-z0-9_]{3,20}$").expect("compile regex");
   static ref VALID_POST_TITLE_REGEX: Regex = Regex::new(r".*\S.*").expect("compile regex");
+  static ref VALID_POST_TAG_NAME_REGEX: Regex = Regex::new(r"^[\w-]{1,30}$").expect("compile regex");
+  static ref VALID_THEME_COLOR_REGEX: Regex = Regex::new(r"^#([0-9a-fA-F]{3}|[0-9a-fA-F]{6})$").expect("compile regex");
 }
 
+/// The slur filter regex which new sites start with
+pub const DEFAULT_SLUR_REGEX: &str = r"(fag(g|got|tard)?\b|cock\s?sucker(s|ing)?|\bn(i|1)g(\b|g?(a|er)?(s|z)?)\b|mudslime?s?|kikes?|\bspi(c|k)s?\b|\bchinks?|gooks?|bitch(es|ing|y)?|whor(es?|ing)|\btr(a|@)nn?(y|ies?)|\b(b|re|r)tard(ed)?s?)";
+
 pub fn naive_from_unix(time: i64) -> NaiveDateTime {
   NaiveDateTime::from_timestamp(time, 0)
 }
 
+/// Bans and removals can't expire later than this many years from now
+pub const MAX_EXPIRY_YEARS: i64 = 100;
+
+/// Reads the expiry of a ban or removal, which is given either as unix timestamp in `expires`, or
+/// as RFC 3339 string in `expires_rfc3339`. It has to be in the future, and at most
+/// `MAX_EXPIRY_YEARS` away. Without either of them, it never expires.
+pub fn parse_expires(
+  expires: Option<i64>,
+  expires_rfc3339: Option<&str>,
+) -> Result<Option<NaiveDateTime>, ApiError> {
+  let (expires, field) = match (expires, expires_rfc3339) {
+    (None, None) => return Ok(None),
+    (Some(_), Some(_)) => {
+      return Err(
+        ApiError::new(ApiErrorCode::InvalidExpiry)
+          .with_field("expires_rfc3339")
+          .with_detail("reason", "expires_given_twice"),
+      )
+    }
+    (Some(time), None) => (NaiveDateTime::from_timestamp_opt(time, 0), "expires"),
+    (None, Some(time)) => (
+      DateTime::parse_from_rfc3339(time)
+        .ok()
+        .map(|t| t.naive_utc()),
+      "expires_rfc3339",
+    ),
+  };
+  let expires =
+    expires.ok_or_else(|| ApiError::new(ApiErrorCode::InvalidExpiry).with_field(field))?;
+
+  let now = Utc::now().naive_utc();
+  if expires <= now {
+    return Err(ApiError::new(ApiErrorCode::InvalidExpiryInPast).with_field(field));
+  }
+  if expires > now + Duration::days(MAX_EXPIRY_YEARS * 365) {
+    return Err(ApiError::new(ApiErrorCode::InvalidExpiryTooFar).with_field(field));
+  }
+  Ok(Some(expires))
+}
+
 pub fn convert_datetime(datetime: NaiveDateTime) -> DateTime<FixedOffset> {
   DateTime::<FixedOffset>::from_utc(datetime, FixedOffset::east(0))
 }
 
-pub fn remove_slurs(test: &str) -> String {
-  SLUR_REGEX.replace_all(test, "*removed*").to_string()
+/// Compiles the slur filter regex of the site. `None` or an empty string disables the filter.
+pub fn build_slur_regex(regex_str: Option<&str>) -> Result<Option<Regex>, ApiError> {
+  match regex_str {
+    None | Some("") => Ok(None),
+    Some(regex_str) => RegexBuilder::new(regex_str)
+      .case_insensitive(true)
+      .build()
+      .map(Some)
+      .map_err(|_| ApiError::new(ApiErrorCode::InvalidRegex).with_field("slur_filter_regex")),
+  }
+}
+
+/// Returns true if the image is hosted by the instance with the given `hostname`, which may include
+/// a port. Subdomains and other ports are treated as different hosts.
+pub fn is_local_image_url(url: &Url, hostname: &str) -> bool {
+  let authority = match (url.host_str(), url.port()) {
+    (Some(host), Some(port)) => format!("{}:{}", host, port),
+    (Some(host), None) => host.to_owned(),
+    (None, _) => return false,
+  };
+  authority.eq_ignore_ascii_case(hostname)
+}
+
+/// Rejects an avatar, banner or icon which isnt hosted by this instance. `field` is the name of the
+/// api field which is returned with the error.
+pub fn check_image_is_local(url: &Url, field: &str) -> Result<(), ApiError> {
+  if is_local_image_url(url, &Settings::get().hostname()) {
+    Ok(())
+  } else {
+    Err(ApiError::new(ApiErrorCode::ImageNotLocal).with_field(field))
+  }
+}
+
+/// The url schemes which admins can allow for post links
+pub const SUPPORTED_URL_SCHEMES: [&str; 3] = ["http", "https", "magnet"];
+
+/// Rejects a post body or comment which is longer than `max_length` characters, with the given
+/// error code.
+pub fn check_max_length(text: &str, max_length: i32, code: ApiErrorCode) -> Result<(), ApiError> {
+  if text.chars().count() > max_length.max(0) as usize {
+    Err(ApiError::new(code).with_detail("max_length", max_length))
+  } else {
+    Ok(())
+  }
+}
+
+/// Cuts off text after `max_length` characters. Used instead of `check_max_length()` for federated
+/// content, which shouldnt be rejected.
+pub fn truncate_to_max_length(text: &str, max_length: i32) -> String {
+  text.chars().take(max_length.max(0) as usize).collect()
 }
 
-pub(crate) fn slur_check(test: &str) -> Result<(), Vec<&str>> {
-  let mut matches: Vec<&str> = SLUR_REGEX.find_iter(test).map(|mat| mat.as_str()).collect();
+pub fn is_url_scheme_allowed(url: &Url, allowed_schemes: &[String]) -> bool {
+  allowed_schemes
+    .iter()
+    .any(|scheme| scheme.eq_ignore_ascii_case(url.scheme()))
+}
+
+/// Query parameters which only track where a link was shared from
+const TRACKING_QUERY_PARAMS: [&str; 4] = ["fbclid", "gclid", "igshid", "ref_src"];
+
+/// Reduces a link to the parts which identify the linked page, so that posts of the same link are
+/// found even if it was shared in slightly different forms. The http(s) scheme, a `www.` prefix,
+/// the fragment, tracking parameters and trailing slashes are dropped, and the remaining query
+/// parameters are sorted. Links with other schemes, like magnet links, are kept as they are.
+pub fn normalize_url(url: &Url) -> String {
+  let host = match url.host_str() {
+    Some(host) if matches!(url.scheme(), "http" | "https") => host,
+    _ => return url.to_string(),
+  };
+  let mut normalized = host.trim_start_matches("www.").to_owned();
+  if let Some(port) = url.port() {
+    normalized.push_str(&format!(":{}", port));
+  }
+  normalized.push_str(url.path().trim_end_matches('/'));
+
+  let mut params = url
+    .query_pairs()
+    .filter(|(key, _)| !key.starts_with("utm_") && !TRACKING_QUERY_PARAMS.contains(&key.as_ref()))
+    .collect::<Vec<_>>();
+  params.sort();
+  if !params.is_empty() {
+    let query = url::form_urlencoded::Serializer::new(String::new())
+      .extend_pairs(params)
+      .finish();
+    normalized.push('?');
+    normalized.push_str(&query);
+  }
+  normalized
+}
+
+pub fn remove_slurs(test: &str, slur_regex: &Option<Regex>) -> String {
+  match slur_regex {
+    Some(slur_regex) => slur_regex.replace_all(test, "*removed*").to_string(),
+    None => test.to_string(),
+  }
+}
+
+pub(crate) fn slur_check<'a>(
+  test: &'a str,
+  slur_regex: &Option<Regex>,
+) -> Result<(), Vec<&'a str>> {
+  let slur_regex = match slur_regex {
+    Some(slur_regex) => slur_regex,
+    None => return Ok(()),
+  };
+  let mut matches: Vec<&str> = slur_regex.find_iter(test).map(|mat| mat.as_str()).collect();
 
   // Unique
   matches.sort_unstable();
@@ -43,17 +190,17 @@ pub(crate) fn slur_check(test: &str) -> Result<(), Vec<&str>> {
   }
 }
 
-pub fn check_slurs(text: &str) -> Result<(), ApiError> {
-  if let Err(slurs) = slur_check(text) {
+pub fn check_slurs(text: &str, slur_regex: &Option<Regex>) -> Result<(), ApiError> {
+  if let Err(slurs) = slur_check(text, slur_regex) {
     Err(ApiError::err(&slurs_vec_to_str(slurs)))
   } else {
     Ok(())
   }
 }
 
-pub fn check_slurs_opt(text: &Option<String>) -> Result<(), ApiError> {
+pub fn check_slurs_opt(text: &Option<String>, slur_regex: &Option<Regex>) -> Result<(), ApiError> {
   match text {
-    Some(t) => check_slurs(t),
+    Some(t) => check_slurs(t, slur_regex),
     None => Ok(()),
   }
 }
@@ -76,6 +223,9 @@ pub fn markdown_to_html(text: &str) -> String {
   comrak::markdown_to_html(text, &comrak::ComrakOptions::default())
 }
 
+/// Maximum number of mentions which are processed for a single post or comment
+pub const MAX_MENTIONS: usize = 10;
+
 // TODO nothing is done with community / group webfingers yet, so just ignore those for now
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct MentionData {
@@ -100,7 +250,17 @@ pub fn scrape_text_for_mentions(text: &str) -> Vec<MentionData> {
       domain: caps["domain"].to_string(),
     });
   }
-  out.into_iter().unique().collect()
+  out.into_iter().unique().take(MAX_MENTIONS).collect()
+}
+
+/// The mentions in `new_text` which weren't in `old_text` yet, so that editing a post or comment
+/// doesn't notify the same persons again.
+pub fn scrape_text_for_new_mentions(old_text: &str, new_text: &str) -> Vec<MentionData> {
+  let old_mentions = scrape_text_for_mentions(old_text);
+  scrape_text_for_mentions(new_text)
+    .into_iter()
+    .filter(|m| !old_mentions.contains(m))
+    .collect()
 }
 
 pub fn is_valid_username(name: &str) -> bool {
@@ -122,12 +282,53 @@ pub fn is_valid_post_title(title: &str) -> bool {
   VALID_POST_TITLE_REGEX.is_match(title)
 }
 
+/// Names of community post tags are single words, so that they can be federated as hashtags
+pub fn is_valid_post_tag_name(name: &str) -> bool {
+  VALID_POST_TAG_NAME_REGEX.is_match(name)
+}
+
+/// Limits for the length of account passwords, in characters
+pub const MIN_PASSWORD_LENGTH: usize = 10;
+pub const MAX_PASSWORD_LENGTH: usize = 60;
+/// Bcrypt ignores everything after the first 72 bytes of a password
+pub const MAX_PASSWORD_BYTES: usize = 72;
+
+/// Maximum length of the short community description, in characters
+pub const MAX_SHORT_DESCRIPTION_LENGTH: usize = 150;
+
+/// A css hex color like `#1e90ff` or `#fff`
+pub fn is_valid_theme_color(color: &str) -> bool {
+  VALID_THEME_COLOR_REGEX.is_match(color)
+}
+
 pub fn get_ip(conn_info: &ConnectionInfo) -> String {
-  conn_info
-    .realip_remote_addr()
-    .unwrap_or("127.0.0.1:12345")
-    .split(':')
-    .next()
-    .unwrap_or("127.0.0.1")
-    .to_string()
+  ip_from_remote_addr(conn_info.realip_remote_addr().unwrap_or("127.0.0.1:12345"))
+}
+
+/// The remote address is either a socket address like `[::1]:8536`, or a plain ip taken from a
+/// forwarding header
+pub(crate) fn ip_from_remote_addr(remote_addr: &str) -> String {
+  if let Ok(socket_addr) = remote_addr.parse::<SocketAddr>() {
+    socket_addr.ip().to_string()
+  } else if let Ok(ip) = remote_addr.parse::<IpAddr>() {
+    ip.to_string()
+  } else {
+    remote_addr.to_string()
+  }
+}
+
+/// Keeps only the network part of an ip address, so that it can be shown to the user without
+/// storing exactly where they are. The last byte of IPv4 addresses and all but the first 48 bits
+/// of IPv6 addresses are zeroed.
+pub fn truncate_ip(ip: &str) -> Option<String> {
+  match ip.parse::<IpAddr>().ok()? {
+    IpAddr::V4(v4) => {
+      let [a, b, c, _] = v4.octets();
+      Some(Ipv4Addr::new(a, b, c, 0).to_string())
+    }
+    IpAddr::V6(v6) => {
+      let s = v6.segments();
+      Some(Ipv6Addr::new(s[0], s[1], s[2], 0, 0, 0, 0, 0).to_string())
+    }
+  }
 }
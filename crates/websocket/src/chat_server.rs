@@ -6,24 +6,35 @@ use diesel::{
   r2d2::{ConnectionManager, Pool},
   PgConnection,
 };
-use lemmy_api_structs::{comment::*, post::*};
+use lemmy_api_structs::{
+  blocking,
+  comment::*,
+  post::*,
+  site::SiteCache,
+  websocket::PostUsersOnline,
+  VoteDebouncer,
+};
+use lemmy_db_queries::aggregates::post_aggregates::PostAggregates;
+use lemmy_db_schema::{source::post_view_log::PostViewLogForm, CommunityId, PersonId, PostId};
 use lemmy_utils::{
+  fetch_limit::FetchLimiter,
   location_info,
   rate_limit::RateLimit,
+  slur_filter::SlurFilter,
   ApiError,
-  CommunityId,
   ConnectionId,
   IpAddr,
   LemmyError,
   LocalUserId,
-  PostId,
 };
+use log::error;
 use rand::rngs::ThreadRng;
 use reqwest::Client;
 use serde::Serialize;
 use serde_json::Value;
 use std::{
-  collections::{HashMap, HashSet},
+  collections::{hash_map::DefaultHasher, HashMap, HashSet},
+  hash::{Hash, Hasher},
   str::FromStr,
 };
 use tokio::macros::support::Pin;
@@ -44,9 +55,27 @@ pub struct ChatServer {
   /// A map from post_id to set of connectionIDs
   pub post_rooms: HashMap<PostId, HashSet<ConnectionId>>,
 
+  /// The post room which each connection has joined, so that it can be left without going through
+  /// all rooms
+  post_room_of: HashMap<ConnectionId, PostId>,
+
+  /// Post rooms whose number of connections changed since the counts were last sent out
+  changed_post_rooms: HashSet<PostId>,
+
+  /// Post views of persons since the last flush. They are deduplicated per day in the database.
+  person_post_views: HashSet<(PostId, PersonId)>,
+
+  /// Post views of anonymous users since the last flush, with the hash of their ip. They are only
+  /// deduplicated within one flush interval.
+  anonymous_post_views: HashSet<(PostId, u64)>,
+
   /// A map from community to set of connectionIDs
   pub community_rooms: HashMap<CommunityId, HashSet<ConnectionId>>,
 
+  /// The person of each connection in a community room which joined with a login, so that it can
+  /// be sent messages with its own `subscribed` state
+  community_room_persons: HashMap<ConnectionId, PersonId>,
+
   pub mod_rooms: HashMap<CommunityId, HashSet<ConnectionId>>,
 
   /// A map from user id to its connection ID for joined users. Remember a user can have multiple
@@ -58,6 +87,9 @@ pub struct ChatServer {
   /// The DB Pool
   pub(super) pool: Pool<ConnectionManager<PgConnection>>,
 
+  /// The DB Pool of the read replica, if one is configured
+  read_pool: Option<Pool<ConnectionManager<PgConnection>>>,
+
   /// Rate limiting based on rate type and IP addr
   pub(super) rate_limiter: RateLimit,
 
@@ -70,6 +102,18 @@ pub struct ChatServer {
   client: Client,
 
   activity_queue: QueueHandle,
+
+  /// Limits for fetching remote objects, shared with the HTTP handlers
+  fetch_limiter: FetchLimiter,
+
+  /// Cached site info, shared with the HTTP handlers
+  site_cache: SiteCache,
+
+  /// The slur filter of the site, shared with the HTTP handlers
+  slur_filter: SlurFilter,
+
+  /// Outgoing votes which are held back, shared with the HTTP handlers
+  vote_debouncer: VoteDebouncer,
 }
 
 pub struct SessionInfo {
@@ -83,24 +127,39 @@ pub struct SessionInfo {
 impl ChatServer {
   pub fn startup(
     pool: Pool<ConnectionManager<PgConnection>>,
+    read_pool: Option<Pool<ConnectionManager<PgConnection>>>,
     rate_limiter: RateLimit,
     message_handler: MessageHandlerType,
     client: Client,
     activity_queue: QueueHandle,
+    fetch_limiter: FetchLimiter,
+    site_cache: SiteCache,
+    slur_filter: SlurFilter,
+    vote_debouncer: VoteDebouncer,
   ) -> ChatServer {
     ChatServer {
       sessions: HashMap::new(),
       post_rooms: HashMap::new(),
+      post_room_of: HashMap::new(),
+      changed_post_rooms: HashSet::new(),
+      person_post_views: HashSet::new(),
+      anonymous_post_views: HashSet::new(),
       community_rooms: HashMap::new(),
+      community_room_persons: HashMap::new(),
       mod_rooms: HashMap::new(),
       user_rooms: HashMap::new(),
       rng: rand::thread_rng(),
       pool,
+      read_pool,
       rate_limiter,
       captchas: Vec::new(),
       message_handler,
       client,
       activity_queue,
+      fetch_limiter,
+      site_cache,
+      slur_filter,
+      vote_debouncer,
     }
   }
 
@@ -108,17 +167,17 @@ impl ChatServer {
     &mut self,
     community_id: CommunityId,
     id: ConnectionId,
+    person_id: Option<PersonId>,
   ) -> Result<(), LemmyError> {
     // remove session from all rooms
-    for sessions in self.community_rooms.values_mut() {
-      sessions.remove(&id);
+    self.leave_community_rooms(id);
+    if let Some(person_id) = person_id {
+      self.community_room_persons.insert(id, person_id);
     }
 
     // Also leave all post rooms
     // This avoids double messages
-    for sessions in self.post_rooms.values_mut() {
-      sessions.remove(&id);
-    }
+    self.leave_post_room(id);
 
     // If the room doesn't exist yet
     if self.community_rooms.get_mut(&community_id).is_none() {
@@ -158,18 +217,14 @@ impl ChatServer {
 
   pub fn join_post_room(&mut self, post_id: PostId, id: ConnectionId) -> Result<(), LemmyError> {
     // remove session from all rooms
-    for sessions in self.post_rooms.values_mut() {
-      sessions.remove(&id);
-    }
+    self.leave_post_room(id);
 
     // Also leave all communities
     // This avoids double messages
     // TODO found a bug, whereby community messages like
     // delete and remove aren't sent, because
     // you left the community room
-    for sessions in self.community_rooms.values_mut() {
-      sessions.remove(&id);
-    }
+    self.leave_community_rooms(id);
 
     // If the room doesn't exist yet
     if self.post_rooms.get_mut(&post_id).is_none() {
@@ -181,10 +236,107 @@ impl ChatServer {
       .get_mut(&post_id)
       .context(location_info!())?
       .insert(id);
+    self.post_room_of.insert(id, post_id);
+    self.changed_post_rooms.insert(post_id);
+
+    Ok(())
+  }
+
+  /// Removes the session from all community rooms
+  pub fn leave_community_rooms(&mut self, id: ConnectionId) {
+    for sessions in self.community_rooms.values_mut() {
+      sessions.remove(&id);
+    }
+    self.community_room_persons.remove(&id);
+  }
+
+  /// The persons of the connections in the community rooms which joined with a login
+  pub fn community_room_persons(&self, community_ids: &[CommunityId]) -> Vec<PersonId> {
+    community_ids
+      .iter()
+      .filter_map(|community_id| self.community_rooms.get(community_id))
+      .flatten()
+      .filter_map(|id| self.community_room_persons.get(id))
+      .copied()
+      .collect::<HashSet<_>>()
+      .into_iter()
+      .collect()
+  }
+
+  /// Removes the session from the post room it has joined, if any. Empty rooms are dropped.
+  pub fn leave_post_room(&mut self, id: ConnectionId) {
+    if let Some(post_id) = self.post_room_of.remove(&id) {
+      if let Some(sessions) = self.post_rooms.get_mut(&post_id) {
+        sessions.remove(&id);
+        if sessions.is_empty() {
+          self.post_rooms.remove(&post_id);
+        }
+      }
+      self.changed_post_rooms.insert(post_id);
+    }
+  }
 
+  /// Sends the new number of connections to each post room which was joined or left since the last
+  /// call. Called periodically, so that busy posts don't get a message for every join.
+  pub fn send_post_users_online(&mut self) -> Result<(), LemmyError> {
+    for post_id in std::mem::take(&mut self.changed_post_rooms) {
+      if let Some(sessions) = self.post_rooms.get(&post_id) {
+        let res = PostUsersOnline {
+          post_id,
+          online: sessions.len(),
+        };
+        self.send_post_room_message(&UserOperation::PostUsersOnline, &res, post_id, None)?;
+      }
+    }
     Ok(())
   }
 
+  pub fn record_post_view(&mut self, msg: RecordPostView) {
+    match msg.person_id {
+      Some(person_id) => {
+        self.person_post_views.insert((msg.post_id, person_id));
+      }
+      None => {
+        let ip = msg.ip.or_else(|| {
+          msg
+            .websocket_id
+            .and_then(|id| self.sessions.get(&id))
+            .map(|session| session.ip.to_owned())
+        });
+        let mut hasher = DefaultHasher::new();
+        ip.hash(&mut hasher);
+        self
+          .anonymous_post_views
+          .insert((msg.post_id, hasher.finish()));
+      }
+    }
+  }
+
+  /// Adds the post views since the last call to the view counts. Called periodically, so that a
+  /// view doesn't need its own write.
+  pub fn flush_post_views(&mut self) {
+    if self.person_post_views.is_empty() && self.anonymous_post_views.is_empty() {
+      return;
+    }
+    let mut anonymous_views = HashMap::new();
+    for (post_id, _) in std::mem::take(&mut self.anonymous_post_views) {
+      *anonymous_views.entry(post_id).or_insert(0) += 1;
+    }
+    let person_views: Vec<PostViewLogForm> = std::mem::take(&mut self.person_post_views)
+      .into_iter()
+      .map(|(post_id, person_id)| PostViewLogForm { post_id, person_id })
+      .collect();
+
+    let pool = self.pool.clone();
+    actix::spawn(async move {
+      let record_views =
+        move |conn: &'_ _| PostAggregates::record_views(conn, &anonymous_views, &person_views);
+      if let Err(e) = blocking(&pool, record_views).await.and_then(|res| Ok(res?)) {
+        error!("Failed to record post views: {}", e);
+      }
+    });
+  }
+
   pub fn join_user_room(
     &mut self,
     user_id: LocalUserId,
@@ -233,10 +385,14 @@ impl ChatServer {
     Ok(())
   }
 
+  /// Sends the response to all connections in the community room. Connections of the persons in
+  /// `person_responses` get their own response instead, the others get the one without personal
+  /// data.
   pub fn send_community_room_message<Response>(
     &self,
     op: &UserOperation,
     response: &Response,
+    person_responses: &HashMap<PersonId, Response>,
     community_id: CommunityId,
     websocket_id: Option<ConnectionId>,
   ) -> Result<(), LemmyError>
@@ -244,6 +400,13 @@ impl ChatServer {
     Response: Serialize,
   {
     let res_str = &serialize_websocket_message(op, response)?;
+    let mut person_res_strs = HashMap::new();
+    for (person_id, person_response) in person_responses {
+      person_res_strs.insert(
+        *person_id,
+        serialize_websocket_message(op, person_response)?,
+      );
+    }
     if let Some(sessions) = self.community_rooms.get(&community_id) {
       for id in sessions {
         if let Some(my_id) = websocket_id {
@@ -251,7 +414,11 @@ impl ChatServer {
             continue;
           }
         }
-        self.sendit(res_str, *id);
+        let person_res_str = self
+          .community_room_persons
+          .get(id)
+          .and_then(|person_id| person_res_strs.get(person_id));
+        self.sendit(person_res_str.unwrap_or(res_str), *id);
       }
     }
     Ok(())
@@ -326,16 +493,20 @@ impl ChatServer {
     Ok(())
   }
 
+  /// Sends the comment to its post and community rooms, and to its recipients. The persons in the
+  /// community rooms get their own vote from `my_votes`.
   pub fn send_comment(
     &self,
     user_operation: &UserOperation,
     comment: &CommentResponse,
+    my_votes: &HashMap<PersonId, i16>,
     websocket_id: Option<ConnectionId>,
   ) -> Result<(), LemmyError> {
     let mut comment_reply_sent = comment.clone();
 
     // Strip out my specific user info
     comment_reply_sent.comment_view.my_vote = None;
+    comment_reply_sent.comment_view.creator_note = None;
 
     // Send it to the post room
     let mut comment_post_sent = comment_reply_sent.clone();
@@ -349,10 +520,25 @@ impl ChatServer {
     )?;
 
     // Send it to the community too
-    self.send_community_room_message(user_operation, &comment_post_sent, 0, websocket_id)?;
+    let person_responses = my_votes
+      .iter()
+      .map(|(person_id, vote)| {
+        let mut person_res = comment_post_sent.clone();
+        person_res.comment_view.my_vote = Some(*vote);
+        (*person_id, person_res)
+      })
+      .collect();
+    self.send_community_room_message(
+      user_operation,
+      &comment_post_sent,
+      &person_responses,
+      CommunityId(0),
+      websocket_id,
+    )?;
     self.send_community_room_message(
       user_operation,
       &comment_post_sent,
+      &person_responses,
       comment.comment_view.community.id,
       websocket_id,
     )?;
@@ -370,10 +556,13 @@ impl ChatServer {
     Ok(())
   }
 
+  /// Sends the post to its post and community rooms. The persons in the community rooms get their
+  /// own vote from `my_votes`.
   pub fn send_post(
     &self,
     user_operation: &UserOperation,
     post_res: &PostResponse,
+    my_votes: &HashMap<PersonId, i16>,
     websocket_id: Option<ConnectionId>,
   ) -> Result<(), LemmyError> {
     let community_id = post_res.post_view.community.id;
@@ -381,10 +570,31 @@ impl ChatServer {
     // Don't send my data with it
     let mut post_sent = post_res.clone();
     post_sent.post_view.my_vote = None;
+    post_sent.post_view.creator_note = None;
 
     // Send it to /c/all and that community
-    self.send_community_room_message(user_operation, &post_sent, 0, websocket_id)?;
-    self.send_community_room_message(user_operation, &post_sent, community_id, websocket_id)?;
+    let person_responses = my_votes
+      .iter()
+      .map(|(person_id, vote)| {
+        let mut person_res = post_sent.clone();
+        person_res.post_view.my_vote = Some(*vote);
+        (*person_id, person_res)
+      })
+      .collect();
+    self.send_community_room_message(
+      user_operation,
+      &post_sent,
+      &person_responses,
+      CommunityId(0),
+      websocket_id,
+    )?;
+    self.send_community_room_message(
+      user_operation,
+      &post_sent,
+      &person_responses,
+      community_id,
+      websocket_id,
+    )?;
 
     // Send it to the post room
     self.send_post_room_message(
@@ -417,19 +627,48 @@ impl ChatServer {
 
     let context = LemmyContext {
       pool: self.pool.clone(),
+      read_pool: self.read_pool.clone(),
       chat_server: ctx.address(),
       client: self.client.to_owned(),
       activity_queue: self.activity_queue.to_owned(),
+      fetch_limiter: self.fetch_limiter.to_owned(),
+      site_cache: self.site_cache.to_owned(),
+      slur_filter: self.slur_filter.to_owned(),
+      vote_debouncer: self.vote_debouncer.to_owned(),
     };
     let message_handler = self.message_handler;
     async move {
       let json: Value = serde_json::from_str(&msg.msg)?;
       let data = &json["data"].to_string();
-      let op = &json["op"].as_str().ok_or(ApiError {
-        message: "Unknown op type".to_string(),
-      })?;
+      let op = &json["op"]
+        .as_str()
+        .ok_or(ApiError::err("Unknown op type"))?;
 
       let user_operation = UserOperation::from_str(&op)?;
+      // Only as many posts as the allowance covers are created, the handler is told how many
+      if let UserOperation::CreateMultiPost = user_operation {
+        let max_count = json["data"]["community_ids"]
+          .as_array()
+          .map(|ids| ids.len())
+          .unwrap_or(0);
+        let mut data = json["data"].to_owned();
+        return rate_limiter
+          .post()
+          .wrap_many(
+            ip,
+            max_count,
+            move |allowed| {
+              if let Some(data) = data.as_object_mut() {
+                data.insert("rate_limit_allowance".to_string(), allowed.into());
+              }
+              let data = data.to_string();
+              async move { (message_handler)(context, msg.id, user_operation, &data).await }
+            },
+            |res| count_created_posts(res),
+          )
+          .await;
+      }
+
       let fut = (message_handler)(context, msg.id, user_operation.clone(), data);
       match user_operation {
         UserOperation::Register => rate_limiter.register().wrap(ip, fut).await,
@@ -440,3 +679,15 @@ impl ChatServer {
     }
   }
 }
+
+/// The number of posts which a serialized `CreateMultiPostResponse` contains
+fn count_created_posts(message: &str) -> usize {
+  serde_json::from_str::<Value>(message)
+    .ok()
+    .and_then(|json| {
+      json["data"]["results"]
+        .as_array()
+        .map(|results| results.iter().filter(|r| !r["post_view"].is_null()).count())
+    })
+    .unwrap_or(0)
+}
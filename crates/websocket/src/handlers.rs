@@ -2,17 +2,43 @@ use crate::{
   chat_server::{ChatServer, SessionInfo},
   messages::*,
 };
-use actix::{Actor, Context, Handler, ResponseFuture};
-use lemmy_db_schema::naive_now;
+use actix::{Actor, ActorFuture, AsyncContext, Context, Handler, ResponseFuture, WrapFuture};
+use lemmy_api_structs::blocking;
+use lemmy_db_queries::Likeable;
+use lemmy_db_schema::{
+  naive_now,
+  source::{comment::CommentLike, post::PostLike},
+  CommunityId,
+  PersonId,
+};
+use lemmy_utils::LemmyError;
 use log::{error, info};
 use rand::Rng;
 use serde::Serialize;
+use std::{collections::HashMap, time::Duration};
+
+/// How often the number of connections in post rooms is sent out, at most
+const POST_USERS_ONLINE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often the collected post views are written to the database
+const POST_VIEWS_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
 
 /// Make actor from `ChatServer`
 impl Actor for ChatServer {
   /// We are going to use simple Context, we just need ability to communicate
   /// with other actors.
   type Context = Context<Self>;
+
+  fn started(&mut self, ctx: &mut Self::Context) {
+    ctx.run_interval(POST_USERS_ONLINE_INTERVAL, |chat_server, _| {
+      if let Err(e) = chat_server.send_post_users_online() {
+        error!("Failed to send post users online: {}", e);
+      }
+    });
+    ctx.run_interval(POST_VIEWS_FLUSH_INTERVAL, |chat_server, _| {
+      chat_server.flush_post_views();
+    });
+  }
 }
 
 /// Handler for Connect message.
@@ -49,13 +75,8 @@ impl Handler<Disconnect> for ChatServer {
         sessions.remove(&msg.id);
       }
 
-      for sessions in self.post_rooms.values_mut() {
-        sessions.remove(&msg.id);
-      }
-
-      for sessions in self.community_rooms.values_mut() {
-        sessions.remove(&msg.id);
-      }
+      self.leave_post_room(msg.id);
+      self.leave_community_rooms(msg.id);
     }
   }
 }
@@ -74,7 +95,7 @@ impl Handler<StandardMessage> for ChatServer {
         }
         Err(e) => {
           error!("Error during message handling {}", e);
-          Ok(e.to_string())
+          Ok(e.to_api_error().to_string())
         }
       }
     })
@@ -120,7 +141,13 @@ where
 
   fn handle(&mut self, msg: SendCommunityRoomMessage<Response>, _: &mut Context<Self>) {
     self
-      .send_community_room_message(&msg.op, &msg.response, msg.community_id, msg.websocket_id)
+      .send_community_room_message(
+        &msg.op,
+        &msg.response,
+        &msg.person_responses,
+        msg.community_id,
+        msg.websocket_id,
+      )
       .ok();
   }
 }
@@ -141,18 +168,84 @@ where
 impl Handler<SendPost> for ChatServer {
   type Result = ();
 
-  fn handle(&mut self, msg: SendPost, _: &mut Context<Self>) {
-    self.send_post(&msg.op, &msg.post, msg.websocket_id).ok();
+  fn handle(&mut self, msg: SendPost, ctx: &mut Context<Self>) {
+    // Everyone in the community rooms gets their own vote on the post
+    let person_ids =
+      self.community_room_persons(&[CommunityId(0), msg.post.post_view.community.id]);
+    if person_ids.is_empty() {
+      self
+        .send_post(&msg.op, &msg.post, &HashMap::new(), msg.websocket_id)
+        .ok();
+      return;
+    }
+
+    let pool = self.pool.clone();
+    let post_id = msg.post.post_view.post.id;
+    async move {
+      blocking(&pool, move |conn| {
+        PostLike::scores_among(conn, post_id, &person_ids)
+      })
+      .await
+    }
+    .into_actor(self)
+    .then(move |res, chat_server, _| {
+      let my_votes = collect_votes(res);
+      chat_server
+        .send_post(&msg.op, &msg.post, &my_votes, msg.websocket_id)
+        .ok();
+      actix::fut::ready(())
+    })
+    .spawn(ctx);
   }
 }
 
 impl Handler<SendComment> for ChatServer {
   type Result = ();
 
-  fn handle(&mut self, msg: SendComment, _: &mut Context<Self>) {
-    self
-      .send_comment(&msg.op, &msg.comment, msg.websocket_id)
-      .ok();
+  fn handle(&mut self, msg: SendComment, ctx: &mut Context<Self>) {
+    // Everyone in the community rooms gets their own vote on the comment
+    let person_ids =
+      self.community_room_persons(&[CommunityId(0), msg.comment.comment_view.community.id]);
+    if person_ids.is_empty() {
+      self
+        .send_comment(&msg.op, &msg.comment, &HashMap::new(), msg.websocket_id)
+        .ok();
+      return;
+    }
+
+    let pool = self.pool.clone();
+    let comment_id = msg.comment.comment_view.comment.id;
+    async move {
+      blocking(&pool, move |conn| {
+        CommentLike::scores_among(conn, comment_id, &person_ids)
+      })
+      .await
+    }
+    .into_actor(self)
+    .then(move |res, chat_server, _| {
+      let my_votes = collect_votes(res);
+      chat_server
+        .send_comment(&msg.op, &msg.comment, &my_votes, msg.websocket_id)
+        .ok();
+      actix::fut::ready(())
+    })
+    .spawn(ctx);
+  }
+}
+
+/// The votes by person, or none if they couldn't be read, so that the message still goes out
+fn collect_votes(
+  res: Result<Result<Vec<(PersonId, i16)>, diesel::result::Error>, LemmyError>,
+) -> HashMap<PersonId, i16> {
+  match res.and_then(|res| Ok(res?)) {
+    Ok(votes) => votes.into_iter().collect(),
+    Err(e) => {
+      error!(
+        "Failed to read the votes of the community room persons: {}",
+        e
+      );
+      HashMap::new()
+    }
   }
 }
 
@@ -168,7 +261,9 @@ impl Handler<JoinCommunityRoom> for ChatServer {
   type Result = ();
 
   fn handle(&mut self, msg: JoinCommunityRoom, _: &mut Context<Self>) {
-    self.join_community_room(msg.community_id, msg.id).ok();
+    self
+      .join_community_room(msg.community_id, msg.id, msg.person_id)
+      .ok();
   }
 }
 
@@ -220,6 +315,22 @@ impl Handler<GetCommunityUsersOnline> for ChatServer {
   }
 }
 
+impl Handler<GetCommunityRoomPersons> for ChatServer {
+  type Result = Vec<PersonId>;
+
+  fn handle(&mut self, msg: GetCommunityRoomPersons, _: &mut Context<Self>) -> Self::Result {
+    self.community_room_persons(&[msg.community_id])
+  }
+}
+
+impl Handler<RecordPostView> for ChatServer {
+  type Result = ();
+
+  fn handle(&mut self, msg: RecordPostView, _: &mut Context<Self>) {
+    self.record_post_view(msg);
+  }
+}
+
 impl Handler<CaptchaItem> for ChatServer {
   type Result = ();
 
@@ -246,3 +357,14 @@ impl Handler<CheckCaptcha> for ChatServer {
     check
   }
 }
+
+impl Handler<TakeCaptcha> for ChatServer {
+  type Result = Option<String>;
+
+  fn handle(&mut self, msg: TakeCaptcha, _: &mut Context<Self>) -> Self::Result {
+    self.captchas.retain(|x| x.expires.gt(&naive_now()));
+
+    let position = self.captchas.iter().position(|r| r.uuid == msg.uuid)?;
+    Some(self.captchas.remove(position).answer)
+  }
+}
@@ -4,8 +4,9 @@ extern crate strum_macros;
 use crate::chat_server::ChatServer;
 use actix::Addr;
 use background_jobs::QueueHandle;
+use lemmy_api_structs::{site::SiteCache, VoteDebouncer};
 use lemmy_db_queries::DbPool;
-use lemmy_utils::LemmyError;
+use lemmy_utils::{fetch_limit::FetchLimiter, slur_filter::SlurFilter, LemmyError};
 use reqwest::Client;
 use serde::Serialize;
 
@@ -13,31 +14,51 @@ pub mod chat_server;
 pub mod handlers;
 pub mod messages;
 pub mod routes;
+pub mod send;
 
 pub struct LemmyContext {
   pub pool: DbPool,
+  pub read_pool: Option<DbPool>,
   pub chat_server: Addr<ChatServer>,
   pub client: Client,
   pub activity_queue: QueueHandle,
+  pub fetch_limiter: FetchLimiter,
+  pub site_cache: SiteCache,
+  pub slur_filter: SlurFilter,
+  pub vote_debouncer: VoteDebouncer,
 }
 
 impl LemmyContext {
   pub fn create(
     pool: DbPool,
+    read_pool: Option<DbPool>,
     chat_server: Addr<ChatServer>,
     client: Client,
     activity_queue: QueueHandle,
+    fetch_limiter: FetchLimiter,
+    site_cache: SiteCache,
+    slur_filter: SlurFilter,
+    vote_debouncer: VoteDebouncer,
   ) -> LemmyContext {
     LemmyContext {
       pool,
+      read_pool,
       chat_server,
       client,
       activity_queue,
+      fetch_limiter,
+      site_cache,
+      slur_filter,
+      vote_debouncer,
     }
   }
   pub fn pool(&self) -> &DbPool {
     &self.pool
   }
+  /// The pool of the read replica, or the primary pool if no replica is configured
+  pub fn read_pool(&self) -> &DbPool {
+    self.read_pool.as_ref().unwrap_or(&self.pool)
+  }
   pub fn chat_server(&self) -> &Addr<ChatServer> {
     &self.chat_server
   }
@@ -47,15 +68,32 @@ impl LemmyContext {
   pub fn activity_queue(&self) -> &QueueHandle {
     &self.activity_queue
   }
+  pub fn fetch_limiter(&self) -> &FetchLimiter {
+    &self.fetch_limiter
+  }
+  pub fn site_cache(&self) -> &SiteCache {
+    &self.site_cache
+  }
+  pub fn slur_filter(&self) -> &SlurFilter {
+    &self.slur_filter
+  }
+  pub fn vote_debouncer(&self) -> &VoteDebouncer {
+    &self.vote_debouncer
+  }
 }
 
 impl Clone for LemmyContext {
   fn clone(&self) -> Self {
     LemmyContext {
       pool: self.pool.clone(),
+      read_pool: self.read_pool.clone(),
       chat_server: self.chat_server.clone(),
       client: self.client.clone(),
       activity_queue: self.activity_queue.clone(),
+      fetch_limiter: self.fetch_limiter.clone(),
+      site_cache: self.site_cache.clone(),
+      slur_filter: self.slur_filter.clone(),
+      vote_debouncer: self.vote_debouncer.clone(),
     }
   }
 }
@@ -84,16 +122,23 @@ where
 pub enum UserOperation {
   Login,
   Register,
+  CheckUsernameAvailable,
   GetCaptcha,
+  GetRegistrationChallenge,
   CreateCommunity,
   CreatePost,
+  CreateMultiPost,
   ListCommunities,
+  CommunityAutocomplete,
   GetPost,
+  MarkPostViewed,
   GetCommunity,
   CreateComment,
   EditComment,
   DeleteComment,
   RemoveComment,
+  LockComment,
+  StickyComment,
   MarkCommentAsRead,
   SaveComment,
   CreateCommentLike,
@@ -101,12 +146,16 @@ pub enum UserOperation {
   ResolveCommentReport,
   ListCommentReports,
   GetPosts,
+  GetRandomPost,
   CreatePostLike,
   EditPost,
   DeletePost,
   RemovePost,
+  ListPendingPosts,
+  ApprovePost,
   LockPost,
   StickyPost,
+  RefetchPostMetadata,
   SavePost,
   CreatePostReport,
   ResolvePostReport,
@@ -115,12 +164,27 @@ pub enum UserOperation {
   EditCommunity,
   DeleteCommunity,
   RemoveCommunity,
+  ExemptCommunityFromSlurFilter,
+  CreateCommunityTag,
+  DeleteCommunityTag,
+  GetCommunityFederationStatus,
+  RefreshRemoteCommunity,
+  ExportCommunity,
+  ImportCommunity,
+  DeleteCommunityProgress,
   FollowCommunity,
+  FavoriteCommunity,
+  MuteCommunity,
+  ListFollowRequests,
+  ApproveFollowRequest,
   GetFollowedCommunities,
   GetPersonDetails,
+  PinPersonPost,
+  SetPersonNote,
   GetReplies,
   GetPersonMentions,
   MarkPersonMentionAsRead,
+  MarkPersonPostMentionAsRead,
   GetModlog,
   BanFromCommunity,
   AddModToCommunity,
@@ -129,6 +193,7 @@ pub enum UserOperation {
   GetSite,
   AddAdmin,
   BanPerson,
+  MergePersons,
   Search,
   MarkAllAsRead,
   SaveUserSettings,
@@ -137,16 +202,29 @@ pub enum UserOperation {
   DeleteAccount,
   PasswordReset,
   PasswordChange,
+  CreateApiToken,
+  ListApiTokens,
+  RevokeApiToken,
+  ListSessions,
+  RevokeSession,
   CreatePrivateMessage,
   EditPrivateMessage,
   DeletePrivateMessage,
   MarkPrivateMessageAsRead,
   GetPrivateMessages,
   UserJoin,
+  GetComment,
   GetComments,
   GetSiteConfig,
   SaveSiteConfig,
+  GetSpamRules,
+  EditSpamRules,
+  GetAdminStats,
+  RecalculateAggregates,
+  ListScheduledTasks,
+  RunScheduledTask,
   PostJoin,
+  PostUsersOnline,
   CommunityJoin,
   ModJoin,
 }
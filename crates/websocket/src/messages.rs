@@ -1,8 +1,10 @@
 use crate::UserOperation;
 use actix::{prelude::*, Recipient};
 use lemmy_api_structs::{comment::CommentResponse, post::PostResponse};
-use lemmy_utils::{CommunityId, ConnectionId, IpAddr, LocalUserId, PostId};
+use lemmy_db_schema::{CommunityId, PersonId, PostId};
+use lemmy_utils::{ConnectionId, IpAddr, LocalUserId};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// Chat server sends this messages to session
 #[derive(Message)]
@@ -58,7 +60,11 @@ pub struct SendUserRoomMessage<Response> {
 #[rtype(result = "()")]
 pub struct SendCommunityRoomMessage<Response> {
   pub op: UserOperation,
+  /// Sent to anonymous connections and to the persons without their own response, so it must not
+  /// contain personal data like `subscribed`
   pub response: Response,
+  /// Responses for single persons in the room, eg with their own `subscribed` state
+  pub person_responses: HashMap<PersonId, Response>,
   pub community_id: CommunityId,
   pub websocket_id: Option<ConnectionId>,
 }
@@ -100,6 +106,8 @@ pub struct JoinUserRoom {
 pub struct JoinCommunityRoom {
   pub community_id: CommunityId,
   pub id: ConnectionId,
+  /// The logged in person of the connection, if any
+  pub person_id: Option<PersonId>,
 }
 
 #[derive(Message)]
@@ -126,12 +134,30 @@ pub struct GetPostUsersOnline {
   pub post_id: PostId,
 }
 
+/// Counts a view of a post. Views are collected in memory and written to the database in batches.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RecordPostView {
+  pub post_id: PostId,
+  pub person_id: Option<PersonId>,
+  /// The ip of an anonymous http request, websocket requests use the ip of their session
+  pub ip: Option<IpAddr>,
+  pub websocket_id: Option<ConnectionId>,
+}
+
 #[derive(Message)]
 #[rtype(usize)]
 pub struct GetCommunityUsersOnline {
   pub community_id: CommunityId,
 }
 
+/// The persons who joined the community room with a login
+#[derive(Message)]
+#[rtype(result = "Vec<PersonId>")]
+pub struct GetCommunityRoomPersons {
+  pub community_id: CommunityId,
+}
+
 #[derive(Message, Debug)]
 #[rtype(result = "()")]
 pub struct CaptchaItem {
@@ -146,3 +172,10 @@ pub struct CheckCaptcha {
   pub uuid: String,
   pub answer: String,
 }
+
+/// Removes a stored registration challenge and returns its answer, if it hasn't expired
+#[derive(Message)]
+#[rtype(result = "Option<String>")]
+pub struct TakeCaptcha {
+  pub uuid: String,
+}
@@ -0,0 +1,57 @@
+use crate::{
+  messages::{GetCommunityRoomPersons, SendCommunityRoomMessage},
+  LemmyContext,
+  UserOperation,
+};
+use lemmy_api_structs::{blocking, community::CommunityResponse};
+use lemmy_db_queries::source::community::{CommunityFavorite_, CommunityFollower_};
+use lemmy_db_schema::source::community::{CommunityFavorite, CommunityFollower};
+use lemmy_utils::{ConnectionId, LemmyError};
+use std::collections::HashMap;
+
+/// Sends a changed community to its room. The persons in the room who follow the community or
+/// have it as favorite get a response with their own state, everyone else gets it stripped.
+pub async fn send_community_websocket(
+  res: &CommunityResponse,
+  context: &LemmyContext,
+  websocket_id: Option<ConnectionId>,
+  op: UserOperation,
+) -> Result<(), LemmyError> {
+  // Strip out the person specific data when sending to others
+  let mut res_sent = res.clone();
+  res_sent.community_view.subscribed = false;
+  res_sent.community_view.favorited = false;
+
+  let community_id = res.community_view.community.id;
+  let person_ids = context
+    .chat_server()
+    .send(GetCommunityRoomPersons { community_id })
+    .await
+    .unwrap_or_default();
+
+  let mut person_responses = HashMap::new();
+  if !person_ids.is_empty() {
+    let (followers, favorites) = blocking(context.pool(), move |conn| {
+      let followers = CommunityFollower::followers_among(conn, community_id, &person_ids)?;
+      let favorites = CommunityFavorite::favorited_among(conn, community_id, &person_ids)?;
+      Ok((followers, favorites)) as Result<_, LemmyError>
+    })
+    .await??;
+
+    for person_id in followers.iter().chain(favorites.iter()) {
+      let mut person_res = res_sent.clone();
+      person_res.community_view.subscribed = followers.contains(person_id);
+      person_res.community_view.favorited = favorites.contains(person_id);
+      person_responses.insert(*person_id, person_res);
+    }
+  }
+
+  context.chat_server().do_send(SendCommunityRoomMessage {
+    op,
+    response: res_sent,
+    person_responses,
+    community_id,
+    websocket_id,
+  });
+  Ok(())
+}
@@ -0,0 +1,20 @@
+-- Slim table holding the ids of received activities, which is used to detect duplicate
+-- deliveries without scanning the much larger activity table.
+create table received_activity (
+  id bigserial primary key,
+  ap_id text not null unique,
+  published timestamp not null default now()
+);
+
+-- Backfill the recently received activities. This only reads from the activity table, so inbox
+-- deliveries can continue to write to it while the migration runs.
+insert into received_activity (ap_id, published)
+  select ap_id, published
+  from activity
+  where local = false
+    and ap_id is not null
+    and published > now() - interval '90 days'
+  order by published
+on conflict (ap_id) do nothing;
+
+create index idx_received_activity_published on received_activity (published);
@@ -0,0 +1,29 @@
+drop trigger post_aggregates_hot_rank on post_aggregates;
+drop trigger comment_aggregates_hot_rank on comment_aggregates;
+drop trigger community_aggregates_hot_rank on community_aggregates;
+drop function
+  post_aggregates_hot_rank,
+  comment_aggregates_hot_rank,
+  community_aggregates_hot_rank;
+
+drop index
+  idx_post_aggregates_hot,
+  idx_post_aggregates_stickied_hot,
+  idx_post_aggregates_active,
+  idx_post_aggregates_stickied_active,
+  idx_comment_aggregates_hot,
+  idx_community_aggregates_hot,
+  idx_post_aggregates_published_id,
+  idx_comment_aggregates_published_id;
+
+alter table post_aggregates drop column hot_rank;
+alter table post_aggregates drop column hot_rank_active;
+alter table comment_aggregates drop column hot_rank;
+alter table community_aggregates drop column hot_rank;
+
+create index idx_post_aggregates_hot on post_aggregates (hot_rank(score, published) desc, published desc);
+create index idx_post_aggregates_stickied_hot on post_aggregates (stickied desc, hot_rank(score, published) desc, published desc);
+create index idx_post_aggregates_active on post_aggregates (hot_rank(score, newest_comment_time_necro) desc, newest_comment_time_necro desc);
+create index idx_post_aggregates_stickied_active on post_aggregates (stickied desc, hot_rank(score, newest_comment_time_necro) desc, newest_comment_time_necro desc);
+create index idx_comment_aggregates_hot on comment_aggregates (hot_rank(score, published) desc, published desc);
+create index idx_community_aggregates_hot on community_aggregates (hot_rank(subscribers, published) desc, published desc);
@@ -0,0 +1,80 @@
+-- hot_rank() depends on now(), so the indexes over it kept the rank from the time a row was last
+-- written, and index scans returned posts in that stale order. Store the rank in a column instead,
+-- which the scheduled tasks recalculate for recent rows.
+--
+-- The new indexes can be checked with:
+--   explain analyze select * from post_aggregates order by hot_rank desc, published desc limit 20;
+-- which should use an index scan on idx_post_aggregates_hot instead of a sort.
+
+-- 1728 is the rank of a new item with a score of 1
+alter table post_aggregates add column hot_rank integer not null default 1728;
+alter table post_aggregates add column hot_rank_active integer not null default 1728;
+alter table comment_aggregates add column hot_rank integer not null default 1728;
+alter table community_aggregates add column hot_rank integer not null default 1728;
+
+update post_aggregates set
+  hot_rank = hot_rank(score, published),
+  hot_rank_active = hot_rank(score, newest_comment_time_necro);
+update comment_aggregates set hot_rank = hot_rank(score, published);
+update community_aggregates set hot_rank = hot_rank(subscribers, published);
+
+-- New rows get the rank of their publish time instead of the default. Federated items can be old
+-- already, and the scheduled tasks only recalculate the ranks of recent rows.
+create function post_aggregates_hot_rank()
+returns trigger language plpgsql
+as $$
+begin
+  NEW.hot_rank = hot_rank(NEW.score, NEW.published);
+  NEW.hot_rank_active = hot_rank(NEW.score, NEW.newest_comment_time_necro);
+  return NEW;
+end $$;
+
+create trigger post_aggregates_hot_rank
+before insert on post_aggregates
+for each row
+execute procedure post_aggregates_hot_rank();
+
+create function comment_aggregates_hot_rank()
+returns trigger language plpgsql
+as $$
+begin
+  NEW.hot_rank = hot_rank(NEW.score, NEW.published);
+  return NEW;
+end $$;
+
+create trigger comment_aggregates_hot_rank
+before insert on comment_aggregates
+for each row
+execute procedure comment_aggregates_hot_rank();
+
+create function community_aggregates_hot_rank()
+returns trigger language plpgsql
+as $$
+begin
+  NEW.hot_rank = hot_rank(NEW.subscribers, NEW.published);
+  return NEW;
+end $$;
+
+create trigger community_aggregates_hot_rank
+before insert on community_aggregates
+for each row
+execute procedure community_aggregates_hot_rank();
+
+drop index
+  idx_post_aggregates_hot,
+  idx_post_aggregates_stickied_hot,
+  idx_post_aggregates_active,
+  idx_post_aggregates_stickied_active,
+  idx_comment_aggregates_hot,
+  idx_community_aggregates_hot;
+
+create index idx_post_aggregates_hot on post_aggregates (hot_rank desc, published desc);
+create index idx_post_aggregates_stickied_hot on post_aggregates (stickied desc, hot_rank desc, published desc);
+create index idx_post_aggregates_active on post_aggregates (hot_rank_active desc, newest_comment_time_necro desc);
+create index idx_post_aggregates_stickied_active on post_aggregates (stickied desc, hot_rank_active desc, newest_comment_time_necro desc);
+create index idx_comment_aggregates_hot on comment_aggregates (hot_rank desc, published desc);
+create index idx_community_aggregates_hot on community_aggregates (hot_rank desc, published desc);
+
+-- Used by the scheduled rank updates, which only touch recent rows
+create index idx_post_aggregates_published_id on post_aggregates (published, id);
+create index idx_comment_aggregates_published_id on comment_aggregates (published, id);
@@ -0,0 +1,8 @@
+drop index
+  idx_post_published,
+  idx_post_like_published,
+  idx_comment_like_published,
+  idx_community_aggregates_users_active_day,
+  idx_community_aggregates_users_active_week,
+  idx_community_aggregates_users_active_month,
+  idx_community_aggregates_users_active_half_year;
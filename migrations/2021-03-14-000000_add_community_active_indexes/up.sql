@@ -0,0 +1,30 @@
+-- Indexes for the scheduled refresh of the community active user counts. The refresh reads every
+-- post, comment, post like and comment like of the last 6 months through these indexes and
+-- groups them by community in a single pass, so its run time depends on the recent activity and
+-- not on the number of communities. The expected plan is:
+--
+--   Update on community_aggregates
+--     -> Hash Right Join (community_aggregates.community_id = activity.community_id)
+--       -> GroupAggregate (group by community_id)
+--         -> Sort (community_id, person_id)
+--           -> Append
+--             -> Index Scan using idx_post_published on post
+--             -> Nested Loop
+--               -> Index Scan using idx_comment_published on comment
+--               -> Index Scan using post_pkey on post
+--             -> Nested Loop
+--               -> Index Scan using idx_post_like_published on post_like
+--               -> Index Scan using post_pkey on post
+--             -> Nested Loop
+--               -> Index Scan using idx_comment_like_published on comment_like
+--               -> Index Scan using post_pkey on post
+--       -> Seq Scan on community_aggregates
+create index idx_post_published on post (published desc);
+create index idx_post_like_published on post_like (published desc);
+create index idx_comment_like_published on comment_like (published desc);
+
+-- Used by the active community sorts
+create index idx_community_aggregates_users_active_day on community_aggregates (users_active_day desc, subscribers desc);
+create index idx_community_aggregates_users_active_week on community_aggregates (users_active_week desc, subscribers desc);
+create index idx_community_aggregates_users_active_month on community_aggregates (users_active_month desc, subscribers desc);
+create index idx_community_aggregates_users_active_half_year on community_aggregates (users_active_half_year desc, subscribers desc);
@@ -0,0 +1,12 @@
+-- Bookkeeping for the scheduled tasks, one row per task. A server only runs a task after it took
+-- the lock on its row, so that a task runs once even if multiple lemmy_server replicas share the
+-- database. A lock which isn't released, because the server died, expires at locked_until.
+create table task_lock (
+  id serial primary key,
+  name text not null unique,
+  locked_by text,
+  locked_until timestamp,
+  last_run timestamp,
+  last_error text,
+  next_run timestamp not null default now()
+);
@@ -0,0 +1,3 @@
+-- Null disables the slur filter, existing sites keep the built-in slur list
+alter table site add column slur_filter_regex text;
+update site set slur_filter_regex = '(fag(g|got|tard)?\b|cock\s?sucker(s|ing)?|\bn(i|1)g(\b|g?(a|er)?(s|z)?)\b|mudslime?s?|kikes?|\bspi(c|k)s?\b|\bchinks?|gooks?|bitch(es|ing|y)?|whor(es?|ing)|\btr(a|@)nn?(y|ies?)|\b(b|re|r)tard(ed)?s?)';
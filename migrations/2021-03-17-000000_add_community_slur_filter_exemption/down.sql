@@ -0,0 +1,2 @@
+drop table mod_exempt_community;
+alter table community drop column exempt_from_slur_filter;
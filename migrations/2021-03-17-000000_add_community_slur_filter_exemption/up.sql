@@ -0,0 +1,9 @@
+alter table community add column exempt_from_slur_filter boolean not null default false;
+
+create table mod_exempt_community (
+  id serial primary key,
+  mod_person_id int references person on update cascade on delete cascade not null,
+  community_id int references community on update cascade on delete cascade not null,
+  exempt boolean default true,
+  when_ timestamp not null default now()
+);
@@ -0,0 +1 @@
+alter table community add column downvotes_disabled boolean not null default false;
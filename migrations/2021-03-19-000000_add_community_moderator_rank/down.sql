@@ -0,0 +1,6 @@
+drop trigger community_moderator_rank_insert on community_moderator;
+drop trigger community_moderator_rank_delete on community_moderator;
+drop function
+  community_moderator_rank_insert,
+  community_moderator_rank_delete;
+alter table community_moderator drop column rank;
@@ -0,0 +1,44 @@
+-- The rank of a moderator within the community, 0 is the head moderator
+alter table community_moderator add column rank int not null default 0;
+
+-- Backfill the ranks from the order in which the moderators were added
+update community_moderator cm
+set rank = ranked.rank
+from (
+  select id, row_number() over (partition by community_id order by published, id) - 1 as rank
+  from community_moderator
+) ranked
+where cm.id = ranked.id;
+
+-- New moderators are ranked below the existing ones
+create function community_moderator_rank_insert()
+returns trigger language plpgsql
+as $$
+begin
+  select coalesce(max(rank) + 1, 0) into NEW.rank
+  from community_moderator
+  where community_id = NEW.community_id;
+  return NEW;
+end $$;
+
+create trigger community_moderator_rank_insert
+before insert on community_moderator
+for each row
+execute procedure community_moderator_rank_insert();
+
+-- Close the gap left by a moderator who leaves
+create function community_moderator_rank_delete()
+returns trigger language plpgsql
+as $$
+begin
+  update community_moderator
+  set rank = rank - 1
+  where community_id = OLD.community_id
+  and rank > OLD.rank;
+  return null;
+end $$;
+
+create trigger community_moderator_rank_delete
+after delete on community_moderator
+for each row
+execute procedure community_moderator_rank_delete();
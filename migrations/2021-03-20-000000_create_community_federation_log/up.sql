@@ -0,0 +1,15 @@
+-- Recent inbound and outbound activities of each local community, for debugging federation
+-- problems. Rows are removed by a scheduled task after a few days.
+create table community_federation_log (
+  id bigserial primary key,
+  community_id int references community on update cascade on delete cascade not null,
+  inbound boolean not null,
+  kind text,
+  domain text not null,
+  -- Null if the activity was accepted or delivered
+  error text,
+  published timestamp not null default now()
+);
+
+create index idx_community_federation_log_community on community_federation_log (community_id, inbound, published desc);
+create index idx_community_federation_log_published on community_federation_log (published);
@@ -0,0 +1,2 @@
+-- Only allow avatars, banners and icons which are hosted by this instance
+alter table site add column require_local_images boolean default false not null;
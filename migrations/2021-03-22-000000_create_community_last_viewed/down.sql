@@ -0,0 +1 @@
+drop table community_last_viewed;
@@ -0,0 +1,8 @@
+-- When a person last opened a community, used to count the new posts of followed communities
+create table community_last_viewed (
+  id serial primary key,
+  community_id int references community on update cascade on delete cascade not null,
+  person_id int references person on update cascade on delete cascade not null,
+  last_viewed timestamp not null default now(),
+  unique (community_id, person_id)
+);
@@ -0,0 +1,4 @@
+drop view comment_alias_1;
+alter table post drop column deleted_by_community_delete;
+alter table comment drop column deleted_by_community_delete;
+create view comment_alias_1 as select * from comment;
@@ -0,0 +1,8 @@
+-- Marks the posts and comments which were deleted together with their community, so that only
+-- those are restored when the community is restored
+alter table post add column deleted_by_community_delete boolean default false not null;
+alter table comment add column deleted_by_community_delete boolean default false not null;
+
+-- The alias view only has the columns which existed when it was created
+drop view comment_alias_1;
+create view comment_alias_1 as select * from comment;
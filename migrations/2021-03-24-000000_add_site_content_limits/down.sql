@@ -0,0 +1,3 @@
+alter table site drop column max_post_body_length;
+alter table site drop column max_comment_length;
+alter table site drop column allowed_url_schemes;
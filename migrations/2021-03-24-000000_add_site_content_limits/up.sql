@@ -0,0 +1,4 @@
+-- Limits for local and federated posts and comments, and the url schemes allowed for post links
+alter table site add column max_post_body_length int not null default 50000;
+alter table site add column max_comment_length int not null default 10000;
+alter table site add column allowed_url_schemes text[] not null default '{http,https}';
@@ -0,0 +1,2 @@
+drop trigger post_aggregates_newest_comment_time on comment;
+drop function post_aggregates_newest_comment_time();
@@ -0,0 +1,42 @@
+-- The newest comment times of a post were only ever moved forward, so deleting or removing the
+-- newest comment kept the post on top of the active sort. Recalculate them from the remaining
+-- visible comments whenever a comment is deleted, removed, or restored.
+create function post_aggregates_newest_comment_time()
+returns trigger language plpgsql
+as $$
+declare
+  changed_post_id integer;
+begin
+  IF (TG_OP = 'UPDATE') THEN
+    IF (OLD.deleted = NEW.deleted and OLD.removed = NEW.removed) THEN
+      return null;
+    END IF;
+    changed_post_id = NEW.post_id;
+  ELSE
+    changed_post_id = OLD.post_id;
+  END IF;
+
+  update post_aggregates pa
+  set newest_comment_time = greatest(ct.newest_comment_time, p.published),
+  newest_comment_time_necro = greatest(ct.newest_comment_time_necro, p.published),
+  hot_rank_active = hot_rank(pa.score, greatest(ct.newest_comment_time_necro, p.published))
+  from post p, lateral (
+    select max(c.published) as newest_comment_time,
+      -- A 2 day necro-bump limit
+      max(c.published) filter (where c.published < p.published + interval '2 days')
+        as newest_comment_time_necro
+    from comment c
+    where c.post_id = p.id
+    and c.deleted = false
+    and c.removed = false
+  ) ct
+  where pa.post_id = p.id
+  and p.id = changed_post_id;
+
+  return null;
+end $$;
+
+create trigger post_aggregates_newest_comment_time
+after update of deleted, removed or delete on comment
+for each row
+execute procedure post_aggregates_newest_comment_time();
@@ -0,0 +1,5 @@
+drop table mod_feature_post;
+drop trigger post_aggregates_featured_local on post;
+drop function post_aggregates_featured_local;
+alter table post_aggregates drop column featured_local;
+alter table post drop column featured_local;
@@ -0,0 +1,33 @@
+-- Posts which admins pinned to the top of the Local and All listings of this instance. Unlike
+-- stickied, this is never federated.
+alter table post add column featured_local boolean not null default false;
+alter table post_aggregates add column featured_local boolean not null default false;
+
+create function post_aggregates_featured_local()
+returns trigger language plpgsql
+as $$
+begin
+  update post_aggregates pa
+  set featured_local = NEW.featured_local
+  where pa.post_id = NEW.id;
+
+  return null;
+end $$;
+
+create trigger post_aggregates_featured_local
+after update on post
+for each row
+when (OLD.featured_local is distinct from NEW.featured_local)
+execute procedure post_aggregates_featured_local();
+
+create index idx_post_aggregates_featured_local_hot on post_aggregates (featured_local desc, hot_rank desc, published desc);
+create index idx_post_aggregates_featured_local_active on post_aggregates (featured_local desc, hot_rank_active desc, newest_comment_time_necro desc);
+create index idx_post_aggregates_featured_local_published on post_aggregates (featured_local desc, published desc);
+
+create table mod_feature_post (
+  id serial primary key,
+  mod_person_id int references person on update cascade on delete cascade not null,
+  post_id int references post on update cascade on delete cascade not null,
+  featured boolean default true,
+  when_ timestamp not null default now()
+);
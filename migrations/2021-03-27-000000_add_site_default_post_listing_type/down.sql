@@ -0,0 +1 @@
+alter table site drop column default_post_listing_type;
@@ -0,0 +1,3 @@
+-- The listing type for users who aren't logged in, stored like local_user.default_listing_type
+-- (0 = All, 1 = Local)
+alter table site add column default_post_listing_type smallint not null default 1;
@@ -0,0 +1 @@
+alter table site drop column show_deleted_usernames_to_admins;
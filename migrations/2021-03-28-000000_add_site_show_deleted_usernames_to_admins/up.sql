@@ -0,0 +1,3 @@
+-- Post and comment views hide the profiles of deleted accounts. This lets admins still see them
+-- in the report listings.
+alter table site add column show_deleted_usernames_to_admins boolean not null default true;
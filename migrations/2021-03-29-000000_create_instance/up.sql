@@ -0,0 +1,12 @@
+-- Software of the instances which we federate with, as reported by their nodeinfo. The rows are
+-- refreshed by a scheduled task. last_seen is the time of the last successful fetch, last_fetched
+-- the time of the last attempt.
+create table instance (
+  id serial primary key,
+  domain varchar(255) not null unique,
+  software varchar(255),
+  version varchar(255),
+  last_seen timestamp,
+  last_fetched timestamp,
+  published timestamp not null default now()
+);
@@ -0,0 +1,11 @@
+-- Mentions of local persons in the body of a post, like person_mention for comments
+create table person_post_mention (
+  id serial primary key,
+  recipient_id int references person on update cascade on delete cascade not null,
+  post_id int references post on update cascade on delete cascade not null,
+  read bool default false not null,
+  published timestamp not null default now(),
+  unique(recipient_id, post_id)
+);
+
+create index idx_person_post_mention_recipient on person_post_mention (recipient_id, published desc);
@@ -0,0 +1,5 @@
+drop view comment_alias_1;
+alter table post drop column removed_by_community_remove;
+alter table comment drop column removed_by_community_remove;
+alter table mod_remove_community drop column remove_content;
+create view comment_alias_1 as select * from comment;
@@ -0,0 +1,11 @@
+-- Marks the posts and comments which were removed together with their community, so that only
+-- those are restored when the community is restored
+alter table post add column removed_by_community_remove boolean default false not null;
+alter table comment add column removed_by_community_remove boolean default false not null;
+
+-- The alias view only has the columns which existed when it was created
+drop view comment_alias_1;
+create view comment_alias_1 as select * from comment;
+
+-- Whether the posts and comments of the community were removed too
+alter table mod_remove_community add column remove_content boolean default false not null;
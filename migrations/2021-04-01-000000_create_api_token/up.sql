@@ -0,0 +1,12 @@
+-- Tokens which third party apps can use instead of a login. The token itself is a jwt which
+-- references this row, so deleting the row revokes it.
+create table api_token (
+  id serial primary key,
+  local_user_id int references local_user on update cascade on delete cascade not null,
+  name text not null,
+  scopes text[] not null,
+  expires timestamp,
+  published timestamp not null default now()
+);
+
+create index idx_api_token_local_user on api_token (local_user_id);
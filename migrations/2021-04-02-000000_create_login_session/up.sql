@@ -0,0 +1,12 @@
+-- Logins of local users. The jwt of a login references its session, so deleting the row revokes
+-- it. The ip is truncated, so that it only shows roughly where the login came from.
+create table login_session (
+  id serial primary key,
+  local_user_id int references local_user on update cascade on delete cascade not null,
+  user_agent text,
+  ip text,
+  issued_at timestamp not null default now(),
+  last_used timestamp not null default now()
+);
+
+create index idx_login_session_local_user on login_session (local_user_id);
@@ -0,0 +1 @@
+alter table site drop column federation_mode;
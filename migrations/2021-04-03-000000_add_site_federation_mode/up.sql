@@ -0,0 +1,3 @@
+-- 0 is open federation, 1 only federates with the allowed instances of the config, 2 disables
+-- federation
+alter table site add column federation_mode smallint not null default 0;
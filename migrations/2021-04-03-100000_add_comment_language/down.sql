@@ -0,0 +1,7 @@
+drop view comment_alias_1;
+alter table comment drop column language_id;
+create view comment_alias_1 as select * from comment;
+
+drop table community_language;
+drop table local_user_language;
+drop table language;
@@ -0,0 +1,67 @@
+-- Languages which content can be written in. Undetermined has the id 0, and is always allowed.
+create table language (
+  id serial primary key,
+  code varchar(3) not null unique,
+  name text not null
+);
+
+insert into language (id, code, name) values
+  (0, 'und', 'Undetermined'),
+  (1, 'ar', 'العربية'),
+  (2, 'ca', 'Català'),
+  (3, 'cs', 'Čeština'),
+  (4, 'da', 'Dansk'),
+  (5, 'de', 'Deutsch'),
+  (6, 'el', 'Ελληνικά'),
+  (7, 'en', 'English'),
+  (8, 'eo', 'Esperanto'),
+  (9, 'es', 'Español'),
+  (10, 'eu', 'Euskara'),
+  (11, 'fa', 'فارسی'),
+  (12, 'fi', 'Suomi'),
+  (13, 'fr', 'Français'),
+  (14, 'ga', 'Gaeilge'),
+  (15, 'gl', 'Galego'),
+  (16, 'he', 'עברית'),
+  (17, 'hi', 'हिन्दी'),
+  (18, 'hu', 'Magyar'),
+  (19, 'id', 'Bahasa Indonesia'),
+  (20, 'it', 'Italiano'),
+  (21, 'ja', '日本語'),
+  (22, 'ko', '한국어'),
+  (23, 'nl', 'Nederlands'),
+  (24, 'no', 'Norsk'),
+  (25, 'pl', 'Polski'),
+  (26, 'pt', 'Português'),
+  (27, 'ro', 'Română'),
+  (28, 'ru', 'Русский'),
+  (29, 'sk', 'Slovenčina'),
+  (30, 'sv', 'Svenska'),
+  (31, 'tr', 'Türkçe'),
+  (32, 'uk', 'Українська'),
+  (33, 'vi', 'Tiếng Việt'),
+  (34, 'zh', '中文');
+
+select setval('language_id_seq', (select max(id) from language));
+
+-- The languages which a local user wants to read. Without any, all languages are shown.
+create table local_user_language (
+  id serial primary key,
+  local_user_id int references local_user on update cascade on delete cascade not null,
+  language_id int references language on update cascade on delete cascade not null,
+  unique (local_user_id, language_id)
+);
+
+-- The languages which are allowed in a community. Without any, all languages are allowed.
+create table community_language (
+  id serial primary key,
+  community_id int references community on update cascade on delete cascade not null,
+  language_id int references language on update cascade on delete cascade not null,
+  unique (community_id, language_id)
+);
+
+alter table comment add column language_id int references language on update cascade not null default 0;
+
+-- The alias view only has the columns which existed when it was created
+drop view comment_alias_1;
+create view comment_alias_1 as select * from comment;
@@ -0,0 +1,2 @@
+alter table community drop column requires_post_approval;
+alter table post drop column approved;
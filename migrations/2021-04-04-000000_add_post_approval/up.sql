@@ -0,0 +1,4 @@
+alter table community add column requires_post_approval boolean not null default false;
+
+-- Null while the post waits for the approval of a mod, false if it was rejected
+alter table post add column approved boolean default true;
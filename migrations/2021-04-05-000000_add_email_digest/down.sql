@@ -0,0 +1,3 @@
+alter table local_user drop column email_digest_frequency;
+alter table local_user drop column last_email_digest;
+alter table local_user drop column email_unsubscribe_token;
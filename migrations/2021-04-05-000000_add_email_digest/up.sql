@@ -0,0 +1,8 @@
+-- Off, Immediate, Hourly or Daily, see EmailDigestFrequency
+alter table local_user add column email_digest_frequency smallint not null default 1;
+-- Notifications which were published before this time were already handled by a digest
+alter table local_user add column last_email_digest timestamp not null default now();
+-- Secret for the unsubscribe links in digest emails, created with the first digest
+alter table local_user add column email_unsubscribe_token text unique;
+
+update local_user set email_digest_frequency = 0 where not send_notifications_to_email;
@@ -0,0 +1,2 @@
+alter table community drop column theme_color;
+alter table community drop column short_description;
@@ -0,0 +1,2 @@
+alter table community add column theme_color varchar(7);
+alter table community add column short_description varchar(150);
@@ -0,0 +1 @@
+alter table instance drop column unreachable_since;
@@ -0,0 +1,2 @@
+-- Set by the first failed nodeinfo fetch after the instance was reachable, cleared on success
+alter table instance add column unreachable_since timestamp;
@@ -0,0 +1,4 @@
+drop table mod_lock_comment;
+drop view comment_alias_1;
+alter table comment drop column locked;
+create view comment_alias_1 as select * from comment;
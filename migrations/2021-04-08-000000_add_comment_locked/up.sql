@@ -0,0 +1,15 @@
+-- Locked comments can't receive new replies anywhere below them.
+alter table comment add column locked boolean not null default false;
+
+-- The alias view only has the columns which existed when it was created
+drop view comment_alias_1;
+create view comment_alias_1 as select * from comment;
+
+create table mod_lock_comment (
+  id serial primary key,
+  mod_person_id int references person on update cascade on delete cascade not null,
+  comment_id int references comment on update cascade on delete cascade not null,
+  locked boolean default true,
+  reason text,
+  when_ timestamp not null default now()
+);
@@ -0,0 +1,2 @@
+drop index idx_post_aggregates_published_score;
+drop index idx_comment_aggregates_published_score;
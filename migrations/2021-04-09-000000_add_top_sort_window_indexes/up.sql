@@ -0,0 +1,10 @@
+-- The top sorts with a time window filter on published and then order by score. With these
+-- indexes postgres only reads the rows of the window instead of scanning the whole score index,
+-- for example:
+--   explain analyze select * from post_aggregates where published > now() - interval '1 day' order by score desc limit 20;
+--
+-- They can't be partial indexes on the window itself, because index predicates have to be
+-- immutable and the window moves with the current time.
+create index idx_post_aggregates_published_score on post_aggregates (published desc, score desc);
+create index idx_comment_aggregates_published_score on comment_aggregates (published desc, score desc);
+-- Communities are ranked by their active users in the window, which are indexed already.
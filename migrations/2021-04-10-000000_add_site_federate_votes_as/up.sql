@@ -0,0 +1,56 @@
+-- 0 federates votes from the actors of the voters, 1 sends the summed score of the local votes
+-- from a service actor of the instance
+alter table site add column federate_votes_as smallint not null default 0;
+
+-- A vote received as an instance aggregate is stored as a single row whose score is the summed
+-- score of all votes of that instance, so it has to be split into upvotes and downvotes by its
+-- size instead of only counting scores of 1 and -1.
+create or replace function post_aggregates_score()
+returns trigger language plpgsql
+as $$
+begin
+  IF (TG_OP = 'INSERT') THEN
+    update post_aggregates pa
+    set score = score + NEW.score,
+    upvotes = upvotes + greatest(NEW.score, 0),
+    downvotes = downvotes + greatest(-NEW.score, 0)
+    where pa.post_id = NEW.post_id;
+
+  ELSIF (TG_OP = 'DELETE') THEN
+    -- Join to post because that post may not exist anymore
+    update post_aggregates pa
+    set score = score - OLD.score,
+    upvotes = upvotes - greatest(OLD.score, 0),
+    downvotes = downvotes - greatest(-OLD.score, 0)
+    from post p
+    where pa.post_id = p.id
+    and pa.post_id = OLD.post_id;
+
+  END IF;
+  return null;
+end $$;
+
+create or replace function comment_aggregates_score()
+returns trigger language plpgsql
+as $$
+begin
+  IF (TG_OP = 'INSERT') THEN
+    update comment_aggregates ca
+    set score = score + NEW.score,
+    upvotes = upvotes + greatest(NEW.score, 0),
+    downvotes = downvotes + greatest(-NEW.score, 0)
+    where ca.comment_id = NEW.comment_id;
+
+  ELSIF (TG_OP = 'DELETE') THEN
+    -- Join to comment because that comment may not exist anymore
+    update comment_aggregates ca
+    set score = score - OLD.score,
+    upvotes = upvotes - greatest(OLD.score, 0),
+    downvotes = downvotes - greatest(-OLD.score, 0)
+    from comment c
+    where ca.comment_id = c.id
+    and ca.comment_id = OLD.comment_id;
+
+  END IF;
+  return null;
+end $$;
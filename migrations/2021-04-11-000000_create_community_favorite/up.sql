@@ -0,0 +1,8 @@
+-- Communities which a person starred for their front page, always a subset of the followed ones
+create table community_favorite (
+  id serial primary key,
+  community_id int references community on update cascade on delete cascade not null,
+  person_id int references person on update cascade on delete cascade not null,
+  published timestamp not null default now(),
+  unique (community_id, person_id)
+);
@@ -0,0 +1 @@
+alter table community drop column default_comment_sort;
@@ -0,0 +1,3 @@
+-- The comment sort of a post page when the client doesn't request one, stored like the sort in the
+-- user settings. Defaults to New, which posts have used so far.
+alter table community add column default_comment_sort smallint not null default 2;
@@ -0,0 +1,5 @@
+drop trigger community_aggregates_post_activity on post;
+drop trigger community_aggregates_comment_activity on comment;
+drop function community_aggregates_post_activity, community_aggregates_comment_activity;
+drop index idx_community_aggregates_last_activity;
+alter table community_aggregates drop column last_activity;
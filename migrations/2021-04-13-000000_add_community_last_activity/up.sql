@@ -0,0 +1,49 @@
+-- The time of the newest post or comment in a community. Federated content counts as well, as it
+-- is inserted when the community announces it. New communities start with their creation time.
+alter table community_aggregates add column last_activity timestamp not null default now();
+
+update community_aggregates ca
+set last_activity = greatest(
+  ca.published,
+  (select max(p.published) from post p where p.community_id = ca.community_id),
+  (select max(c.published) from comment c
+    inner join post p on p.id = c.post_id
+    where p.community_id = ca.community_id)
+);
+
+create index idx_community_aggregates_last_activity on community_aggregates (last_activity desc);
+
+-- Federated objects keep their original time, so older ones don't move it back
+create function community_aggregates_post_activity()
+returns trigger language plpgsql
+as $$
+begin
+  update community_aggregates
+  set last_activity = NEW.published
+  where community_id = NEW.community_id
+  and last_activity < NEW.published;
+  return null;
+end $$;
+
+create trigger community_aggregates_post_activity
+after insert on post
+for each row
+execute procedure community_aggregates_post_activity();
+
+create function community_aggregates_comment_activity()
+returns trigger language plpgsql
+as $$
+begin
+  update community_aggregates ca
+  set last_activity = NEW.published
+  from post p
+  where p.id = NEW.post_id
+  and ca.community_id = p.community_id
+  and ca.last_activity < NEW.published;
+  return null;
+end $$;
+
+create trigger community_aggregates_comment_activity
+after insert on comment
+for each row
+execute procedure community_aggregates_comment_activity();
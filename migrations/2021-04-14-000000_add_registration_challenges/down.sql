@@ -0,0 +1,3 @@
+drop table registration_question;
+alter table site drop column registration_challenge;
+alter table site drop column proof_of_work_difficulty;
@@ -0,0 +1,13 @@
+-- The challenge which new users have to solve: 0 the image captcha of the config, 1 a proof of
+-- work, 2 a question from registration_question.
+alter table site add column registration_challenge smallint not null default 0;
+-- Number of leading zero bits which the hash of a proof of work needs
+alter table site add column proof_of_work_difficulty int not null default 20;
+
+create table registration_question (
+  id serial primary key,
+  question text not null,
+  -- Compared case insensitively, after trimming whitespace
+  answers text[] not null,
+  published timestamp not null default now()
+);
@@ -0,0 +1,5 @@
+drop table post_view_log;
+
+drop index idx_post_aggregates_views;
+
+alter table post_aggregates drop column views;
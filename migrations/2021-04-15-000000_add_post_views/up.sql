@@ -0,0 +1,13 @@
+-- Views are counted by the server in batches, there is no trigger for them
+alter table post_aggregates add column views bigint not null default 0;
+
+create index idx_post_aggregates_views on post_aggregates (views desc);
+
+-- Which posts a person viewed on a day, so that repeated views only count once per day. Rows of
+-- past days are cleared by a scheduled task.
+create table post_view_log (
+  post_id int references post on update cascade on delete cascade not null,
+  person_id int references person on update cascade on delete cascade not null,
+  viewed_on date not null default current_date,
+  primary key (post_id, person_id, viewed_on)
+);
@@ -0,0 +1 @@
+drop table mod_edit_community;
@@ -0,0 +1,8 @@
+-- Edits of a community by an admin who isn't one of its mods, so that the mods can see them
+create table mod_edit_community (
+  id serial primary key,
+  mod_person_id int references person on update cascade on delete cascade not null,
+  community_id int references community on update cascade on delete cascade not null,
+  changed_fields text[] not null default '{}',
+  when_ timestamp not null default now()
+);
@@ -0,0 +1 @@
+drop index idx_community_lower_name_prefix;
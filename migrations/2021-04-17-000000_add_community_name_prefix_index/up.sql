@@ -0,0 +1,2 @@
+-- Serves the case insensitive prefix matches of the community autocomplete
+create index idx_community_lower_name_prefix on community (lower(name) text_pattern_ops);
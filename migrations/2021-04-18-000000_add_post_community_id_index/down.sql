@@ -0,0 +1,2 @@
+drop index idx_post_community_id;
+create index idx_post_community on post (community_id);
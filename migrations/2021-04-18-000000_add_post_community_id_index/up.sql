@@ -0,0 +1,3 @@
+-- Lets a random post of a community be found by an index range scan from a random id
+drop index idx_post_community;
+create index idx_post_community_id on post (community_id, id);
@@ -0,0 +1,2 @@
+alter table person drop column last_activity_at;
+alter table local_user drop column hide_last_active;
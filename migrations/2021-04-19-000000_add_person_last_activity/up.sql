@@ -0,0 +1,3 @@
+-- Updated at most every 15 minutes, from api requests of local users and activities of remote ones
+alter table person add column last_activity_at timestamp;
+alter table local_user add column hide_last_active boolean not null default false;
@@ -0,0 +1,3 @@
+alter table community drop column require_post_tag;
+drop table post_tag;
+drop table community_tag;
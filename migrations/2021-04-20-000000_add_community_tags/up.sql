@@ -0,0 +1,21 @@
+-- Post tags which the mods of a community can choose, like "meta" or "question"
+create table community_tag (
+  id serial primary key,
+  community_id int references community on update cascade on delete cascade not null,
+  name varchar(30) not null,
+  color varchar(7),
+  published timestamp not null default now(),
+  unique (community_id, name)
+);
+
+create table post_tag (
+  id serial primary key,
+  post_id int references post on update cascade on delete cascade not null,
+  tag_id int references community_tag on update cascade on delete cascade not null,
+  unique (post_id, tag_id)
+);
+
+-- Listings filtered by tag
+create index idx_post_tag_tag_id on post_tag (tag_id, post_id);
+
+alter table community add column require_post_tag boolean default false not null;
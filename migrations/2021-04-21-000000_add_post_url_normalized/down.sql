@@ -0,0 +1,2 @@
+drop index idx_post_url_normalized;
+alter table post drop column url_normalized;
@@ -0,0 +1,5 @@
+-- The url of a post reduced to the parts which identify the linked page, to find cross posts. It
+-- is computed by the server, existing posts are filled in by a code migration.
+alter table post add column url_normalized text;
+
+create index idx_post_url_normalized on post (url_normalized);
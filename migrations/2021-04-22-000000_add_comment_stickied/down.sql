@@ -0,0 +1,5 @@
+drop table mod_sticky_comment;
+drop index idx_comment_stickied_post;
+drop view comment_alias_1;
+alter table comment drop column stickied;
+create view comment_alias_1 as select * from comment;
@@ -0,0 +1,17 @@
+-- A stickied comment is shown first among the top-level comments of its post.
+alter table comment add column stickied boolean not null default false;
+
+-- The alias view only has the columns which existed when it was created
+drop view comment_alias_1;
+create view comment_alias_1 as select * from comment;
+
+-- At most one stickied comment per post
+create unique index idx_comment_stickied_post on comment (post_id) where stickied;
+
+create table mod_sticky_comment (
+  id serial primary key,
+  mod_person_id int references person on update cascade on delete cascade not null,
+  comment_id int references comment on update cascade on delete cascade not null,
+  stickied boolean default true,
+  when_ timestamp not null default now()
+);
@@ -0,0 +1,4 @@
+drop index idx_community_follower_pending;
+alter table community_follower drop column follow_sent;
+alter table community_follower drop column follow_resends;
+alter table community_follower drop column follow_failed;
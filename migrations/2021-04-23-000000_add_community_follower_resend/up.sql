@@ -0,0 +1,11 @@
+-- Accepting a follow used to set it to pending, so the flag of existing follows means nothing.
+-- They are all treated as accepted, instead of resending the Follow for every one of them.
+update community_follower set pending = false;
+
+-- When the Follow activity was last sent to the remote community, how often it was sent again
+-- without an Accept, and whether we gave up waiting for one.
+alter table community_follower add column follow_sent timestamp not null default now();
+alter table community_follower add column follow_resends int not null default 0;
+alter table community_follower add column follow_failed boolean not null default false;
+
+create index idx_community_follower_pending on community_follower (follow_sent) where pending;
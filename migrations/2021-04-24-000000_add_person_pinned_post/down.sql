@@ -0,0 +1 @@
+drop table person_pinned_post;
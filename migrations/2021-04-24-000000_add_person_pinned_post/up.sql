@@ -0,0 +1,8 @@
+-- Posts which a person pinned to the top of their profile
+create table person_pinned_post (
+  id serial primary key,
+  person_id int references person on update cascade on delete cascade not null,
+  post_id int references post on update cascade on delete cascade not null,
+  published timestamp not null default now(),
+  unique(person_id, post_id)
+);
@@ -0,0 +1,2 @@
+drop table admin_auto_remove;
+drop table spam_rule;
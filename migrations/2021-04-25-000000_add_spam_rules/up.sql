@@ -0,0 +1,19 @@
+-- Rules which new posts and comments are checked against. `pattern` is used by the url denylist,
+-- `threshold` by the other kinds of rules.
+create table spam_rule (
+  id serial primary key,
+  kind smallint not null,
+  pattern text,
+  threshold int,
+  action smallint not null,
+  published timestamp not null default now()
+);
+
+-- Content which was removed by a spam rule, for review by the mods
+create table admin_auto_remove (
+  id serial primary key,
+  post_id int references post on update cascade on delete cascade not null,
+  comment_id int references comment on update cascade on delete cascade,
+  rule_kind smallint not null,
+  when_ timestamp not null default now()
+);
@@ -0,0 +1 @@
+alter table community_follower drop column mute_until;
@@ -0,0 +1,3 @@
+-- Until when the follower doesn't want to be notified about the community. Expired mutes are
+-- ignored when reading, and cleared by a scheduled task.
+alter table community_follower add column mute_until timestamp;
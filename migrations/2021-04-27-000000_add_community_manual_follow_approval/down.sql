@@ -0,0 +1,2 @@
+alter table community drop column manual_follow_approval;
+alter table community_follower drop column follow_activity_id;
@@ -0,0 +1,6 @@
+-- Follows of the community need to be approved by a moderator
+alter table community add column manual_follow_approval boolean not null default false;
+
+-- The id of the Follow activity of a remote person, so that the Accept or Reject of a follow
+-- request can refer to it
+alter table community_follower add column follow_activity_id varchar(255);
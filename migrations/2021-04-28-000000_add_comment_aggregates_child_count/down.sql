@@ -0,0 +1,5 @@
+drop trigger comment_aggregates_child_count_delete on comment;
+drop trigger comment_aggregates_child_count on comment;
+drop function comment_aggregates_child_count;
+drop function comment_aggregates_child_count_change;
+alter table comment_aggregates drop column child_count;
@@ -0,0 +1,67 @@
+-- The number of replies to a comment, including the replies to its replies. Deleted and removed
+-- replies aren't counted.
+alter table comment_aggregates add column child_count integer not null default 0;
+
+update comment_aggregates ca set child_count = (
+  with recursive descendants (id, deleted, removed) as (
+    select id, deleted, removed from comment where parent_id = ca.comment_id
+    union all
+    select c.id, c.deleted, c.removed from comment c join descendants d on c.parent_id = d.id
+  )
+  select count(*) from descendants where not deleted and not removed
+);
+
+-- Adds the change to the child counts of all ancestors of a comment
+create function comment_aggregates_child_count_change(comment_parent_id integer, change integer)
+returns void language sql
+as $$
+  update comment_aggregates ca set child_count = child_count + change
+  from (
+    with recursive ancestors (id, parent_id) as (
+      select id, parent_id from comment where id = comment_parent_id
+      union all
+      select c.id, c.parent_id from comment c join ancestors a on c.id = a.parent_id
+    )
+    select id from ancestors
+  ) a
+  where ca.comment_id = a.id;
+$$;
+
+-- A reply counts for its ancestors while it is neither deleted nor removed. Deleting a comment
+-- deletes its replies with it, so it takes its own child count from its ancestors. This is done
+-- before the delete, while the ancestors can still be found. The replies which are deleted
+-- afterwards don't find their ancestors anymore, so they aren't taken again.
+create function comment_aggregates_child_count()
+returns trigger language plpgsql
+as $$
+begin
+  IF (TG_OP = 'INSERT') THEN
+    IF (NOT NEW.deleted AND NOT NEW.removed) THEN
+      perform comment_aggregates_child_count_change(NEW.parent_id, 1);
+    END IF;
+  ELSIF (TG_OP = 'UPDATE') THEN
+    IF ((NOT OLD.deleted AND NOT OLD.removed) AND (NEW.deleted OR NEW.removed)) THEN
+      perform comment_aggregates_child_count_change(NEW.parent_id, -1);
+    ELSIF ((OLD.deleted OR OLD.removed) AND (NOT NEW.deleted AND NOT NEW.removed)) THEN
+      perform comment_aggregates_child_count_change(NEW.parent_id, 1);
+    END IF;
+  ELSIF (TG_OP = 'DELETE') THEN
+    perform comment_aggregates_child_count_change(
+      OLD.parent_id,
+      - (case when OLD.deleted or OLD.removed then 0 else 1 end)
+      - coalesce((select child_count from comment_aggregates where comment_id = OLD.id), 0)
+    );
+    return OLD;
+  END IF;
+  return null;
+end $$;
+
+create trigger comment_aggregates_child_count
+after insert or update of deleted, removed on comment
+for each row
+execute procedure comment_aggregates_child_count();
+
+create trigger comment_aggregates_child_count_delete
+before delete on comment
+for each row
+execute procedure comment_aggregates_child_count();
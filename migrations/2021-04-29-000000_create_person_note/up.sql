@@ -0,0 +1,11 @@
+-- Private labels which a person gives to other persons, only visible to their creator
+create table person_note (
+  id serial primary key,
+  creator_person_id int references person on update cascade on delete cascade not null,
+  target_person_id int references person on update cascade on delete cascade not null,
+  note varchar(50) not null,
+  color varchar(7),
+  published timestamp not null default now(),
+  updated timestamp,
+  unique (creator_person_id, target_person_id)
+);
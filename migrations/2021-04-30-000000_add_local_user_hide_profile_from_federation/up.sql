@@ -0,0 +1,2 @@
+-- Serves an empty outbox for the person to other instances
+alter table local_user add column hide_profile_from_federation boolean not null default false;
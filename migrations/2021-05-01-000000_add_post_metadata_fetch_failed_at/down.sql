@@ -0,0 +1,2 @@
+drop index idx_post_metadata_fetch_failed_at;
+alter table post drop column metadata_fetch_failed_at;
@@ -0,0 +1,5 @@
+-- Set when fetching the embed data and thumbnail of the post url failed, so that it is retried
+alter table post add column metadata_fetch_failed_at timestamp;
+
+create index idx_post_metadata_fetch_failed_at on post (metadata_fetch_failed_at)
+  where metadata_fetch_failed_at is not null;
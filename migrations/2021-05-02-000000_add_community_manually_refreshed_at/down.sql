@@ -0,0 +1 @@
+alter table community drop column manually_refreshed_at;
@@ -0,0 +1,3 @@
+-- When an admin or mod last refreshed a remote community. It is separate from last_refreshed_at,
+-- which the regular refetch sets as well.
+alter table community add column manually_refreshed_at timestamp;
@@ -0,0 +1 @@
+alter table instance drop column vote_actor_id;
@@ -0,0 +1,3 @@
+-- The service actor which sends the aggregated votes of the instance. Aggregates of any other
+-- actor are counted as a single vote.
+alter table instance add column vote_actor_id int references person on update cascade on delete set null;
@@ -23,9 +23,16 @@ use lemmy_db_schema::{
     post::Post,
     private_message::PrivateMessage,
   },
+  PostId,
+};
+use lemmy_utils::{
+  apub::generate_actor_keypair,
+  settings::structs::Settings,
+  utils::normalize_url,
+  LemmyError,
 };
-use lemmy_utils::{apub::generate_actor_keypair, settings::structs::Settings, LemmyError};
 use log::info;
+use url::Url;
 
 pub fn run_advanced_migrations(conn: &PgConnection) -> Result<(), LemmyError> {
   user_updates_2020_04_02(&conn)?;
@@ -35,6 +42,7 @@ pub fn run_advanced_migrations(conn: &PgConnection) -> Result<(), LemmyError> {
   private_message_updates_2020_05_05(&conn)?;
   post_thumbnail_url_updates_2020_07_27(&conn)?;
   apub_columns_2021_02_02(&conn)?;
+  post_url_normalized_2021_04_21(&conn)?;
 
   Ok(())
 }
@@ -115,6 +123,13 @@ fn community_updates_2020_04_02(conn: &PgConnection) -> Result<(), LemmyError> {
       followers_url: None,
       inbox_url: None,
       shared_inbox_url: None,
+      downvotes_disabled: None,
+      requires_post_approval: None,
+      theme_color: None,
+      short_description: None,
+      default_comment_sort: None,
+      require_post_tag: None,
+      manual_follow_approval: None,
     };
 
     Community::update(&conn, ccommunity.id, &form)?;
@@ -260,3 +275,33 @@ fn apub_columns_2021_02_02(conn: &PgConnection) -> Result<(), LemmyError> {
 
   Ok(())
 }
+
+/// Fills in the normalized urls of the posts which existed before cross posts were detected.
+/// Urls which can't be parsed are left out.
+fn post_url_normalized_2021_04_21(conn: &PgConnection) -> Result<(), LemmyError> {
+  use lemmy_db_schema::schema::post::dsl::*;
+
+  info!("Running post_url_normalized_2021_04_21");
+
+  let posts = post
+    .filter(url.is_not_null())
+    .filter(url_normalized.is_null())
+    .select((id, url))
+    .load::<(PostId, Option<String>)>(conn)?;
+
+  let mut updated = 0;
+  for (post_id, post_url) in posts {
+    let parsed = match post_url.as_deref().map(Url::parse) {
+      Some(Ok(parsed)) => parsed,
+      _ => continue,
+    };
+    diesel::update(post.find(post_id))
+      .set(url_normalized.eq(normalize_url(&parsed)))
+      .execute(conn)?;
+    updated += 1;
+  }
+
+  info!("{} Post url_normalized rows updated.", updated);
+
+  Ok(())
+}
@@ -1,3 +1,4 @@
 #![recursion_limit = "512"]
 pub mod code_migrations;
+pub mod scheduled_task;
 pub mod scheduled_tasks;
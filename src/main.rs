@@ -7,20 +7,26 @@ use diesel::{
   r2d2::{ConnectionManager, Pool},
   PgConnection,
 };
-use lemmy_api::match_websocket_operation;
-use lemmy_api_structs::blocking;
-use lemmy_apub::activity_queue::create_activity_queue;
-use lemmy_db_queries::get_database_url_from_env;
-use lemmy_routes::{feeds, images, nodeinfo, webfinger};
+use lemmy_api::{flush_pending_votes, match_websocket_operation};
+use lemmy_api_structs::{blocking, site::SiteCache, VoteDebouncer};
+use lemmy_apub::{activity_queue::create_activity_queue, resend_pending_follows};
+use lemmy_db_queries::{get_database_url_from_env, source::site::Site_};
+use lemmy_db_schema::source::site::Site;
+use lemmy_routes::{email, feeds, images, nodeinfo, webfinger};
 use lemmy_server::{code_migrations::run_advanced_migrations, scheduled_tasks};
 use lemmy_utils::{
+  federation_mode::{self, FederationMode},
+  fetch_limit::FetchLimiter,
   rate_limit::{rate_limiter::RateLimiter, RateLimit},
   settings::structs::Settings,
+  slur_filter::SlurFilter,
+  utils::{build_slur_regex, DEFAULT_SLUR_REGEX},
   LemmyError,
 };
 use lemmy_websocket::{chat_server::ChatServer, LemmyContext};
+use log::error;
 use reqwest::Client;
-use std::{sync::Arc, thread};
+use std::{sync::Arc, thread, time::Duration};
 use tokio::sync::Mutex;
 
 embed_migrations!();
@@ -41,6 +47,15 @@ async fn main() -> Result<(), LemmyError> {
     .build(manager)
     .unwrap_or_else(|_| panic!("Error connecting to {}", db_url));
 
+  // The optional read replica gets its own pool, so that expensive reads can't starve writes
+  let read_pool = settings.database().read_replica_url.map(|read_url| {
+    let manager = ConnectionManager::<PgConnection>::new(&read_url);
+    Pool::builder()
+      .max_size(settings.database().pool_size)
+      .build(manager)
+      .unwrap_or_else(|_| panic!("Error connecting to read replica {}", read_url))
+  });
+
   // Run the migrations from code
   blocking(&pool, move |conn| {
     embedded_migrations::run(conn)?;
@@ -49,6 +64,20 @@ async fn main() -> Result<(), LemmyError> {
   })
   .await??;
 
+  // The slur filter regex and the federation mode are stored on the site, which doesn't exist yet
+  // on a new instance
+  let site = blocking(&pool, move |conn| Site::read_simple(conn))
+    .await?
+    .ok();
+  let slur_filter_regex = match &site {
+    Some(site) => site.slur_filter_regex.to_owned(),
+    None => Some(DEFAULT_SLUR_REGEX.to_owned()),
+  };
+  let slur_filter = SlurFilter::new(build_slur_regex(slur_filter_regex.as_deref())?);
+  if let Some(site) = &site {
+    FederationMode::set(FederationMode::from_i16(site.federation_mode));
+  }
+
   let pool2 = pool.clone();
   thread::spawn(move || {
     scheduled_tasks::setup(pool2);
@@ -65,23 +94,79 @@ async fn main() -> Result<(), LemmyError> {
     settings.port()
   );
 
-  let activity_queue = create_activity_queue();
+  let activity_queue = create_activity_queue(pool.clone());
+  let fetch_limiter = FetchLimiter::new(settings.fetch_limit());
+  let site_cache = SiteCache::new(Duration::from_secs(60));
+  let vote_debouncer = VoteDebouncer::new(settings.vote_debounce());
   let chat_server = ChatServer::startup(
     pool.clone(),
+    read_pool.clone(),
     rate_limiter.clone(),
     |c, i, o, d| Box::pin(match_websocket_operation(c, i, o, d)),
     Client::default(),
     activity_queue.clone(),
+    fetch_limiter.clone(),
+    site_cache.clone(),
+    slur_filter.clone(),
+    vote_debouncer.clone(),
   )
   .start();
 
+  // Kept for sending the held back votes on shutdown
+  let shutdown_context = LemmyContext::create(
+    pool.clone(),
+    read_pool.clone(),
+    chat_server.to_owned(),
+    Client::default(),
+    activity_queue.to_owned(),
+    fetch_limiter.to_owned(),
+    site_cache.to_owned(),
+    slur_filter.to_owned(),
+    vote_debouncer.to_owned(),
+  );
+
+  // Unanswered follows of remote communities are sent through the activity queue, which needs
+  // the actix system, so this can't run with the other scheduled tasks
+  let follow_context = shutdown_context.clone();
+  actix_rt::spawn(async move {
+    let mut interval = actix_rt::time::interval(Duration::from_secs(10 * 60));
+    loop {
+      interval.tick().await;
+      if let Err(e) = resend_pending_follows(&follow_context).await {
+        error!("Failed to resend pending follows: {}", e);
+      }
+    }
+  });
+
+  // The federation mode can be changed through any of the server processes, so each one reads it
+  // from the site again from time to time
+  let federation_mode_pool = pool.clone();
+  actix_rt::spawn(async move {
+    let mut interval =
+      actix_rt::time::interval(Duration::from_secs(federation_mode::REFRESH_SECONDS));
+    loop {
+      interval.tick().await;
+      match blocking(&federation_mode_pool, move |conn| Site::read_simple(conn)).await {
+        Ok(Ok(site)) => FederationMode::set(FederationMode::from_i16(site.federation_mode)),
+        // Not set up yet
+        Ok(Err(_)) => {}
+        Err(e) => error!("Failed to refresh the federation mode: {}", e),
+      }
+    }
+  });
+
   // Create Http server with websocket support
   HttpServer::new(move || {
     let context = LemmyContext::create(
       pool.clone(),
+      read_pool.clone(),
       chat_server.to_owned(),
       Client::default(),
       activity_queue.to_owned(),
+      fetch_limiter.to_owned(),
+      site_cache.to_owned(),
+      slur_filter.to_owned(),
+      vote_debouncer.to_owned(),
     );
     let rate_limiter = rate_limiter.clone();
     App::new()
@@ -90,6 +175,7 @@ async fn main() -> Result<(), LemmyError> {
       // The routes
       .configure(|cfg| lemmy_api::routes::config(cfg, &rate_limiter))
       .configure(lemmy_apub::routes::config)
+      .configure(email::config)
       .configure(feeds::config)
       .configure(|cfg| images::config(cfg, &rate_limiter))
       .configure(nodeinfo::config)
@@ -99,5 +185,7 @@ async fn main() -> Result<(), LemmyError> {
   .run()
   .await?;
 
+  flush_pending_votes(&shutdown_context).await;
+
   Ok(())
 }
@@ -0,0 +1,278 @@
+use diesel::PgConnection;
+use lemmy_db_queries::{source::task_lock::TaskLock_, DbPool};
+use lemmy_db_schema::source::task_lock::TaskLock;
+use lemmy_utils::{utils::generate_random_string, LemmyError};
+use log::{error, info};
+use std::{
+  panic::{catch_unwind, AssertUnwindSafe},
+  sync::mpsc::{channel, RecvTimeoutError},
+  thread,
+  time::Duration,
+};
+
+/// How long the lock of a task is held without a heartbeat. If a server dies while running a
+/// task, another one takes over after this time.
+const LOCK_SECONDS: i64 = 2 * 60;
+
+/// How often the lock of a running task is extended
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often the registry checks if one of the tasks is due
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+type Job = Box<dyn Fn(&PgConnection) -> Result<(), LemmyError> + Send>;
+
+struct ScheduledTask {
+  name: &'static str,
+  interval: Duration,
+  job: Job,
+}
+
+/// A set of named periodic jobs. The schedule of each job is stored in the `task_lock` table, so
+/// that when multiple lemmy servers share a database, each run of a job happens on only one of
+/// them.
+pub struct TaskRegistry {
+  tasks: Vec<ScheduledTask>,
+  owner: String,
+}
+
+impl TaskRegistry {
+  pub fn new() -> Self {
+    TaskRegistry {
+      tasks: vec![],
+      owner: generate_random_string(),
+    }
+  }
+
+  pub fn register<F>(&mut self, name: &'static str, interval: Duration, job: F)
+  where
+    F: Fn(&PgConnection) -> Result<(), LemmyError> + Send + 'static,
+  {
+    self.tasks.push(ScheduledTask {
+      name,
+      interval,
+      job: Box::new(job),
+    });
+  }
+
+  /// Runs the due tasks forever, this blocks the current thread.
+  pub fn run(self, pool: DbPool) {
+    loop {
+      match pool.get() {
+        Ok(conn) => {
+          for task in &self.tasks {
+            if let Err(e) = TaskLock::register(&conn, task.name) {
+              error!("Failed to register scheduled task {}: {}", task.name, e);
+            }
+          }
+          break;
+        }
+        Err(e) => error!("Failed to get connection for scheduled tasks: {}", e),
+      }
+      thread::sleep(CHECK_INTERVAL);
+    }
+
+    loop {
+      self.run_due_tasks(&pool);
+      thread::sleep(CHECK_INTERVAL);
+    }
+  }
+
+  fn run_due_tasks(&self, pool: &DbPool) {
+    match pool.get() {
+      Ok(conn) => {
+        for task in &self.tasks {
+          if let Err(e) = self.run_if_due(&conn, pool, task) {
+            error!("Failed to run scheduled task {}: {}", task.name, e);
+          }
+        }
+      }
+      Err(e) => error!("Failed to get connection for scheduled tasks: {}", e),
+    }
+  }
+
+  fn run_if_due(
+    &self,
+    conn: &PgConnection,
+    pool: &DbPool,
+    task: &ScheduledTask,
+  ) -> Result<(), LemmyError> {
+    if !TaskLock::try_lock(conn, task.name, &self.owner, LOCK_SECONDS)? {
+      return Ok(());
+    }
+
+    // Extends the lock on a separate connection while the task runs, until the sender is dropped
+    let (finished, heartbeat_finished) = channel::<()>();
+    let heartbeat = {
+      let pool = pool.clone();
+      let name = task.name;
+      let owner = self.owner.to_owned();
+      thread::spawn(move || {
+        while let Err(RecvTimeoutError::Timeout) =
+          heartbeat_finished.recv_timeout(HEARTBEAT_INTERVAL)
+        {
+          let extended = pool.get().map_err(LemmyError::from).and_then(|conn| {
+            TaskLock::extend_lock(&conn, name, &owner, LOCK_SECONDS).map_err(LemmyError::from)
+          });
+          match extended {
+            Ok(true) => {}
+            Ok(false) => error!("Lost the lock of scheduled task {}", name),
+            Err(e) => error!(
+              "Failed to extend the lock of scheduled task {}: {}",
+              name, e
+            ),
+          }
+        }
+      })
+    };
+
+    info!("Running scheduled task {}", task.name);
+    let error = match catch_unwind(AssertUnwindSafe(|| (task.job)(conn))) {
+      Ok(Ok(())) => None,
+      Ok(Err(e)) => Some(e.to_string()),
+      Err(_) => Some("panicked".to_string()),
+    };
+    drop(finished);
+    if heartbeat.join().is_err() {
+      error!("Heartbeat of scheduled task {} panicked", task.name);
+    }
+    if let Some(e) = &error {
+      error!("Scheduled task {} failed: {}", task.name, e);
+    }
+
+    let interval_seconds = task.interval.as_secs() as i64;
+    TaskLock::finish(conn, task.name, &self.owner, error, interval_seconds)?;
+    Ok(())
+  }
+}
+
+impl Default for TaskRegistry {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use diesel::{
+    r2d2::{ConnectionManager, Pool},
+    *,
+  };
+  use lemmy_db_queries::get_database_url_from_env;
+  use lemmy_db_schema::schema::task_lock;
+  use serial_test::serial;
+  use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+  };
+
+  fn test_pool() -> DbPool {
+    let manager = ConnectionManager::<PgConnection>::new(get_database_url_from_env().unwrap());
+    Pool::builder().max_size(3).build(manager).unwrap()
+  }
+
+  fn counting_registry(name: &'static str, runs: &Arc<AtomicUsize>) -> TaskRegistry {
+    let mut registry = TaskRegistry::new();
+    let runs = runs.clone();
+    registry.register(name, Duration::from_secs(3600), move |_conn| {
+      runs.fetch_add(1, Ordering::SeqCst);
+      Ok(())
+    });
+    registry
+  }
+
+  #[test]
+  #[serial]
+  fn test_task_registry() {
+    let pool = test_pool();
+    let conn = pool.get().unwrap();
+    let name = "test_task_registry";
+
+    let runs_a = Arc::new(AtomicUsize::new(0));
+    let runs_b = Arc::new(AtomicUsize::new(0));
+    let server_a = counting_registry(name, &runs_a);
+    let server_b = counting_registry(name, &runs_b);
+    TaskLock::register(&conn, name).unwrap();
+
+    // A new task is due immediately, and only runs on one server
+    server_a.run_due_tasks(&pool);
+    server_b.run_due_tasks(&pool);
+    assert_eq!(1, runs_a.load(Ordering::SeqCst));
+    assert_eq!(0, runs_b.load(Ordering::SeqCst));
+
+    // After the run it isn't due again before the interval
+    server_a.run_due_tasks(&pool);
+    assert_eq!(1, runs_a.load(Ordering::SeqCst));
+
+    // A server which holds the lock keeps the others out
+    TaskLock::trigger(&conn, name).unwrap();
+    assert!(TaskLock::try_lock(&conn, name, &server_a.owner, LOCK_SECONDS).unwrap());
+    server_b.run_due_tasks(&pool);
+    assert_eq!(0, runs_b.load(Ordering::SeqCst));
+
+    // Failed and panicking tasks release the lock, and record the error
+    let mut failing = TaskRegistry::new();
+    failing.register(name, Duration::from_secs(3600), |_conn| {
+      Err(anyhow::anyhow!("failed").into())
+    });
+    TaskLock::finish(&conn, name, &server_a.owner, None, 0).unwrap();
+    failing.run_due_tasks(&pool);
+    let mut panicking = TaskRegistry::new();
+    panicking.register(name, Duration::from_secs(3600), |_conn| panic!("panicked"));
+    TaskLock::trigger(&conn, name).unwrap();
+    panicking.run_due_tasks(&pool);
+    let task = TaskLock::list(&conn)
+      .unwrap()
+      .into_iter()
+      .find(|t| t.name == name)
+      .unwrap();
+    assert_eq!(None, task.locked_by);
+    assert_eq!(Some("panicked".to_string()), task.last_error);
+
+    diesel::delete(task_lock::table.filter(task_lock::name.eq(name)))
+      .execute(&conn)
+      .unwrap();
+  }
+
+  #[test]
+  #[serial]
+  fn test_task_registry_heartbeat() {
+    let pool = test_pool();
+    let conn = pool.get().unwrap();
+    let name = "test_task_registry_heartbeat";
+    TaskLock::register(&conn, name).unwrap();
+
+    // The lock outlives a task which runs longer than the heartbeat interval
+    let mut registry = TaskRegistry::new();
+    registry.register(name, Duration::from_secs(3600), |conn| {
+      let locked_until = || {
+        task_lock::table
+          .filter(task_lock::name.eq("test_task_registry_heartbeat"))
+          .select(task_lock::locked_until)
+          .first::<Option<chrono::NaiveDateTime>>(conn)
+      };
+      let before = locked_until()?;
+      thread::sleep(HEARTBEAT_INTERVAL + Duration::from_secs(2));
+      let after = locked_until()?;
+      if after > before {
+        Ok(())
+      } else {
+        Err(anyhow::anyhow!("lock wasn't extended").into())
+      }
+    });
+    registry.run_due_tasks(&pool);
+
+    let task = TaskLock::list(&conn)
+      .unwrap()
+      .into_iter()
+      .find(|t| t.name == name)
+      .unwrap();
+    assert_eq!(None, task.last_error);
+    assert!(task.last_run.is_some());
+
+    diesel::delete(task_lock::table.filter(task_lock::name.eq(name)))
+      .execute(&conn)
+      .unwrap();
+  }
+}
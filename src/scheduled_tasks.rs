@@ -1,66 +1,307 @@
-// Scheduler, and trait for .seconds(), .minutes(), etc.
-use clokwerk::{Scheduler, TimeUnits};
-// Import week days and WeekDay
+use crate::scheduled_task::TaskRegistry;
+use chrono::Duration as ChronoDuration;
 use diesel::{sql_query, PgConnection, RunQueryDsl};
-use lemmy_db_queries::{source::activity::Activity_, DbPool};
-use lemmy_db_schema::source::activity::Activity;
-use log::info;
-use std::{thread, time::Duration};
+use lemmy_api::linked_instances;
+use lemmy_api_structs::{save_post_metadata, send_email_digest};
+use lemmy_db_queries::{
+  aggregates::{
+    self,
+    comment_aggregates::CommentAggregates,
+    community_aggregates::CommunityAggregates,
+    post_aggregates::PostAggregates,
+  },
+  source::{
+    activity::Activity_,
+    comment::Comment_,
+    community::CommunityFollower_,
+    community_federation_log::CommunityFederationLog_,
+    instance::Instance_,
+    local_user::LocalUser_,
+    post::Post_,
+    post_view_log::PostViewLog_,
+    received_activity::ReceivedActivity_,
+  },
+  DbPool,
+};
+use lemmy_db_schema::{
+  naive_now,
+  source::{
+    activity::Activity,
+    comment::Comment,
+    community::CommunityFollower,
+    community_federation_log::CommunityFederationLog,
+    instance::Instance,
+    local_user::LocalUser,
+    post::Post,
+    post_view_log::PostViewLog,
+    received_activity::ReceivedActivity,
+  },
+};
+use lemmy_db_views::local_user_view::LocalUserView;
+use lemmy_utils::{
+  federation_mode::FederationMode,
+  request::{fetch_nodeinfo_software, fetch_post_metadata},
+  settings::structs::Settings,
+  LemmyError,
+};
+use log::{info, warn};
+use std::time::Duration;
 
-/// Schedules various cleanup tasks for lemmy in a background thread
+/// Registers the periodic cleanup tasks of lemmy, and runs them in a background thread
 pub fn setup(pool: DbPool) {
-  let mut scheduler = Scheduler::new();
-
-  let conn = pool.get().unwrap();
-  active_counts(&conn);
-  reindex_aggregates_tables(&conn);
-  scheduler.every(1.hour()).run(move || {
-    active_counts(&conn);
-    reindex_aggregates_tables(&conn);
-  });
-
-  let conn = pool.get().unwrap();
-  clear_old_activities(&conn);
-  scheduler.every(1.weeks()).run(move || {
-    clear_old_activities(&conn);
-  });
-
-  // Manually run the scheduler in an event loop
-  loop {
-    scheduler.run_pending();
-    thread::sleep(Duration::from_millis(1000));
-  }
+  let mut registry = TaskRegistry::new();
+  registry.register("active_counts", Duration::from_secs(60 * 60), active_counts);
+  registry.register(
+    "update_hot_ranks",
+    Duration::from_secs(5 * 60),
+    update_hot_ranks,
+  );
+  registry.register(
+    "recalculate_aggregates",
+    Duration::from_secs(24 * 60 * 60),
+    recalculate_aggregates,
+  );
+  registry.register(
+    "clear_old_activities",
+    Duration::from_secs(7 * 24 * 60 * 60),
+    clear_old_activities,
+  );
+  registry.register(
+    "purge_expired_content",
+    Duration::from_secs(24 * 60 * 60),
+    purge_expired_content,
+  );
+  registry.register(
+    "clear_old_received_activities",
+    Duration::from_secs(24 * 60 * 60),
+    clear_old_received_activities,
+  );
+  registry.register(
+    "clear_community_federation_log",
+    Duration::from_secs(24 * 60 * 60),
+    clear_community_federation_log,
+  );
+  registry.register(
+    "clear_expired_community_mutes",
+    Duration::from_secs(24 * 60 * 60),
+    clear_expired_community_mutes,
+  );
+  registry.register(
+    "clear_post_view_log",
+    Duration::from_secs(60 * 60),
+    clear_post_view_log,
+  );
+  registry.register(
+    "fetch_instance_software",
+    Duration::from_secs(60 * 60),
+    fetch_instance_software,
+  );
+  registry.register(
+    "refetch_post_metadata",
+    Duration::from_secs(60 * 60),
+    refetch_post_metadata,
+  );
+  registry.register(
+    "send_email_digests",
+    Duration::from_secs(5 * 60),
+    send_email_digests,
+  );
+  registry.run(pool);
 }
 
-/// Reindex the aggregates tables every one hour
-/// This is necessary because hot_rank is actually a mutable function:
-/// https://dba.stackexchange.com/questions/284052/how-to-create-an-index-based-on-a-time-based-function-in-postgres?noredirect=1#comment555727_284052
-fn reindex_aggregates_tables(conn: &PgConnection) {
-  for table_name in &[
-    "post_aggregates",
-    "comment_aggregates",
-    "community_aggregates",
-  ] {
-    reindex_table(&conn, &table_name);
+/// Number of rows which get their hot rank updated per statement
+const HOT_RANK_BATCH_SIZE: i64 = 1000;
+
+/// Recalculate the stored hot ranks, which would otherwise stay at their value from the last write.
+/// Posts and comments older than a week are skipped, their rank barely changes anymore.
+fn update_hot_ranks(conn: &PgConnection) -> Result<(), LemmyError> {
+  info!("Updating hot ranks ...");
+
+  let mut after_id = 0;
+  while let Some(last_id) = PostAggregates::update_hot_ranks(conn, after_id, HOT_RANK_BATCH_SIZE)? {
+    after_id = last_id;
+  }
+
+  let mut after_id = 0;
+  while let Some(last_id) =
+    CommentAggregates::update_hot_ranks(conn, after_id, HOT_RANK_BATCH_SIZE)?
+  {
+    after_id = last_id;
+  }
+
+  let mut after_id = 0;
+  while let Some(last_id) =
+    CommunityAggregates::update_hot_ranks(conn, after_id, HOT_RANK_BATCH_SIZE)?
+  {
+    after_id = last_id;
   }
-}
 
-fn reindex_table(conn: &PgConnection, table_name: &str) {
-  info!("Reindexing table {} ...", table_name);
-  let query = format!("reindex table concurrently {}", table_name);
-  sql_query(query).execute(conn).expect("reindex table");
   info!("Done.");
+  Ok(())
+}
+
+/// Number of ids which are covered by one aggregates recalculation statement
+const RECALCULATE_BATCH_SIZE: i32 = 1000;
+
+/// Fix aggregates which drifted from the base tables, eg after a failed migration
+fn recalculate_aggregates(conn: &PgConnection) -> Result<(), LemmyError> {
+  info!("Recalculating aggregates ...");
+  let corrected = aggregates::recalculate_all(conn, RECALCULATE_BATCH_SIZE)?;
+  info!("Done, {} rows corrected.", corrected);
+  Ok(())
 }
 
 /// Clear old activities (this table gets very large)
-fn clear_old_activities(conn: &PgConnection) {
+fn clear_old_activities(conn: &PgConnection) -> Result<(), LemmyError> {
   info!("Clearing old activities...");
-  Activity::delete_olds(&conn).expect("clear old activities");
+  Activity::delete_olds(&conn)?;
   info!("Done.");
+  Ok(())
+}
+
+/// Clear ids of received activities which are older than the configured retention period
+fn clear_old_received_activities(conn: &PgConnection) -> Result<(), LemmyError> {
+  info!("Clearing old received activities...");
+  let days = Settings::get().received_activity_retention_days();
+  ReceivedActivity::delete_older_than_days(&conn, days)?;
+  info!("Done.");
+  Ok(())
+}
+
+/// Number of days for which the federation activity of communities is kept
+const COMMUNITY_FEDERATION_LOG_DAYS: i64 = 7;
+
+/// Clear the federation log of communities, it is only meant for debugging recent problems
+fn clear_community_federation_log(conn: &PgConnection) -> Result<(), LemmyError> {
+  info!("Clearing community federation log...");
+  CommunityFederationLog::delete_older_than_days(&conn, COMMUNITY_FEDERATION_LOG_DAYS)?;
+  info!("Done.");
+  Ok(())
+}
+
+/// Clear the community mutes which are over, they are already ignored when reading
+fn clear_expired_community_mutes(conn: &PgConnection) -> Result<(), LemmyError> {
+  info!("Clearing expired community mutes...");
+  let cleared = CommunityFollower::clear_expired_mutes(&conn)?;
+  info!("Done, {} mutes cleared.", cleared);
+  Ok(())
+}
+
+/// Clear the post views of past days, they are only needed to count each person once per day
+fn clear_post_view_log(conn: &PgConnection) -> Result<(), LemmyError> {
+  info!("Clearing post view log...");
+  let deleted = PostViewLog::delete_before_today(&conn)?;
+  info!("Done, {} rows deleted.", deleted);
+  Ok(())
 }
 
-/// Re-calculate the site and community active counts every 12 hours
-fn active_counts(conn: &PgConnection) {
+/// Number of instances whose nodeinfo is fetched per run, so that a run doesn't take forever when
+/// we federate with many instances. The least recently fetched ones go first.
+const NODEINFO_BATCH_SIZE: i64 = 50;
+
+/// Update the software and version of the linked instances from their nodeinfo. Instances without
+/// nodeinfo, or which are down, keep their previously known software.
+fn fetch_instance_software(conn: &PgConnection) -> Result<(), LemmyError> {
+  if FederationMode::effective() == FederationMode::Disabled {
+    return Ok(());
+  }
+  info!("Fetching software of linked instances...");
+  let domains = linked_instances(conn)?;
+  Instance::insert_missing(conn, &domains)?;
+  let instances = Instance::list_for_refresh(conn, &domains, NODEINFO_BATCH_SIZE)?;
+
+  let client = reqwest::Client::default();
+  let mut runner = actix_rt::System::new("fetch_instance_software");
+  let mut updated = 0;
+  for instance in &instances {
+    match runner.block_on(fetch_nodeinfo_software(&client, &instance.domain)) {
+      Ok(software) => {
+        Instance::update_software(conn, instance.id, &software.name, software.version)?;
+        updated += 1;
+      }
+      Err(e) => {
+        warn!("Failed to fetch nodeinfo of {}: {}", instance.domain, e);
+        Instance::update_fetch_failed(conn, instance.id)?;
+      }
+    }
+  }
+  info!(
+    "Done, updated {} of {} instances.",
+    updated,
+    instances.len()
+  );
+  Ok(())
+}
+
+/// Number of posts whose metadata is fetched again per run
+const POST_METADATA_BATCH_SIZE: i64 = 50;
+
+/// Posts older than this keep their missing metadata, the site is probably gone for good
+const POST_METADATA_RETRY_HOURS: i64 = 24;
+
+/// Fetch the embed data and thumbnails of the recent posts for which this failed before
+fn refetch_post_metadata(conn: &PgConnection) -> Result<(), LemmyError> {
+  let published_after = naive_now() - ChronoDuration::hours(POST_METADATA_RETRY_HOURS);
+  let posts = Post::list_for_metadata_refetch(conn, published_after, POST_METADATA_BATCH_SIZE)?;
+  if posts.is_empty() {
+    return Ok(());
+  }
+  info!("Refetching metadata of {} posts...", posts.len());
+
+  let client = reqwest::Client::default();
+  let mut runner = actix_rt::System::new("refetch_post_metadata");
+  let mut fetched = 0;
+  for post in posts {
+    if let Some(url) = post.url {
+      let url = url.into_inner();
+      let result = runner.block_on(fetch_post_metadata(&client, &url));
+      if result.is_ok() {
+        fetched += 1;
+      }
+      save_post_metadata(conn, post.id, &url, result)?;
+    }
+  }
+  info!("Done, fetched {} of them.", fetched);
+  Ok(())
+}
+
+/// Send the hourly and daily notification digests of the users which are due
+fn send_email_digests(conn: &PgConnection) -> Result<(), LemmyError> {
+  if Settings::get().email().is_none() {
+    return Ok(());
+  }
+  let local_users = LocalUser::list_due_for_email_digest(conn)?;
+  for local_user in &local_users {
+    let result = LocalUserView::read(conn, local_user.id)
+      .map_err(LemmyError::from)
+      .and_then(|local_user_view| send_email_digest(conn, &local_user_view));
+    if let Err(e) = result {
+      warn!(
+        "Failed to send email digest to user {}: {}",
+        local_user.id, e
+      );
+    }
+  }
+  Ok(())
+}
+
+/// Blank the content of posts and comments which were deleted or removed long enough ago. This
+/// is automatic, so nothing is written to the modlog.
+fn purge_expired_content(conn: &PgConnection) -> Result<(), LemmyError> {
+  info!("Purging expired deleted and removed content...");
+  let settings = Settings::get();
+  let deleted_before =
+    naive_now() - ChronoDuration::days(settings.deleted_content_retention_days());
+  let removed_before =
+    naive_now() - ChronoDuration::days(settings.removed_content_retention_days());
+  let posts = Post::purge_expired(conn, deleted_before, removed_before)?;
+  let comments = Comment::purge_expired(conn, deleted_before, removed_before)?;
+  info!("Done, purged {} posts and {} comments.", posts, comments);
+  Ok(())
+}
+
+/// Re-calculate the site and community active counts
+fn active_counts(conn: &PgConnection) -> Result<(), LemmyError> {
   info!("Updating active site and community aggregates ...");
 
   let intervals = vec![
@@ -75,15 +316,11 @@ fn active_counts(conn: &PgConnection) {
       "update site_aggregates set users_active_{} = (select * from site_aggregates_activity('{}'))",
       i.1, i.0
     );
-    sql_query(update_site_stmt)
-      .execute(conn)
-      .expect("update site stats");
-
-    let update_community_stmt = format!("update community_aggregates ca set users_active_{} = mv.count_ from community_aggregates_activity('{}') mv where ca.community_id = mv.community_id_", i.1, i.0);
-    sql_query(update_community_stmt)
-      .execute(conn)
-      .expect("update community stats");
+    sql_query(update_site_stmt).execute(conn)?;
   }
 
+  CommunityAggregates::update_active_counts(conn)?;
+
   info!("Done.");
+  Ok(())
 }